@@ -120,8 +120,13 @@ impl C99Display for llir::UnOp {
         match self {
             UnOp::Move { .. } => Ok(()),
             UnOp::Cast { dst_t, .. } => write!(fmt, "({})", dst_t.c99()),
-            UnOp::Exp { t: ir::Type::F(32) } => write!(fmt, "expf"),
+            UnOp::Exp {
+                t: ir::Type::F(32), ..
+            } => write!(fmt, "expf"),
             UnOp::Exp { .. } => panic!("{}: non-atomic C99 instruction", self),
+            UnOp::Sqrt { t: ir::Type::F(32) } => write!(fmt, "sqrtf"),
+            UnOp::Sqrt { t: ir::Type::F(64) } => write!(fmt, "sqrt"),
+            UnOp::Sqrt { .. } => panic!("{}: non-atomic C99 instruction", self),
         }
     }
 }
@@ -193,6 +198,7 @@ impl C99Display for llir::TernOp {
                 write!(fmt, "__mad{}{}", arg_t.bitwidth().unwrap(), spec.c99())
             }
             FFma { .. } => write!(fmt, "__fma"),
+            Mma { .. } => panic!("tensor cores are not available in C"),
         }
     }
 }
@@ -5,7 +5,6 @@
 use lazy_static::lazy_static;
 use rand::Rng;
 use std::sync::Arc;
-use telamon::explorer::choice::ActionEx;
 use telamon::search_space::*;
 use telamon::{explorer, helper, ir};
 use telamon_cuda as cuda;
@@ -103,17 +102,10 @@ pub fn descend_without_copies(mut space: SearchSpace) {
         choice
     } {
         let id = rand::thread_rng().gen_range(0, choice.len());
-        let res = match choice.swap_remove(id) {
-            ActionEx::Action(action) => space.apply_decisions(vec![action]),
-            ActionEx::LowerLayout {
-                mem,
-                ref st_dims,
-                ref ld_dims,
-            } => space.lower_layout(mem, st_dims, ld_dims),
+        space = match choice.swap_remove(id).apply_to(space) {
+            Ok(space) => space,
+            Err(_) => return,
         };
-        if res.is_err() {
-            return;
-        }
     }
 }
 
@@ -126,17 +118,10 @@ pub fn descend_with_copies(mut space: SearchSpace) -> Vec<SearchSpace> {
         choice
     } {
         let id = rand::thread_rng().gen_range(0, choice.len());
-        let res = match choice.swap_remove(id) {
-            ActionEx::Action(action) => space.apply_decisions(vec![action]),
-            ActionEx::LowerLayout {
-                mem,
-                ref st_dims,
-                ref ld_dims,
-            } => space.lower_layout(mem, st_dims, ld_dims),
+        space = match choice.swap_remove(id).apply_to(space) {
+            Ok(space) => space,
+            Err(_) => return spaces,
         };
-        if res.is_err() {
-            return spaces;
-        }
         spaces.push(space.clone());
     }
     spaces
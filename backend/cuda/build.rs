@@ -45,6 +45,8 @@ fn compile_link_cuda() {
     add_lib("cuda");
     add_lib("curand");
     add_lib("cupti");
+    add_lib("nvidia-ml");
+    add_lib("nvrtc");
 }
 
 fn main() {
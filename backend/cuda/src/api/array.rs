@@ -3,6 +3,7 @@ use crate::api::wrapper::*;
 use crate::api::Argument;
 use libc;
 use num::integer::div_rem;
+use std::sync::Arc;
 use telamon::device;
 
 /// An array allocated on a CUDA device.
@@ -45,6 +46,13 @@ impl<'a, T> Array<'a, T> {
         }
     }
 
+    /// Sets all the elements of the array to zero, without going through the host.
+    pub fn zero(&self) {
+        unsafe {
+            zero_array(self.context, self.array, self.byte_len() as u64);
+        }
+    }
+
     /// Returns the number of bytes in the array.
     fn byte_len(&self) -> usize {
         self.len * std::mem::size_of::<T>()
@@ -132,4 +140,35 @@ where
         let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
         Array::copy_from_host(self, bytes);
     }
+
+    fn zero(&self) {
+        Array::zero(self);
+    }
+
+    fn raw_device_ptr(&self) -> Option<u64> {
+        // `CudaArray` is a pointer to the `CUdeviceptr` of the array.
+        Some(unsafe { *(self.array as *const u64) })
+    }
+}
+
+/// An array allocated outside of the context, passed to kernels through its device address.
+pub struct ExternalArray<'a> {
+    ptr: u64,
+    // Keeps the array alive as long as it is bound.
+    _array: Arc<dyn device::ArrayArgument + 'a>,
+}
+
+impl<'a> ExternalArray<'a> {
+    /// Wraps an array, if it exposes its device address.
+    pub fn new(array: Arc<dyn device::ArrayArgument + 'a>) -> Option<Self> {
+        array
+            .raw_device_ptr()
+            .map(|ptr| ExternalArray { ptr, _array: array })
+    }
+}
+
+impl<'a> Argument for ExternalArray<'a> {
+    fn raw_ptr(&self) -> *const libc::c_void {
+        &self.ptr as *const u64 as *const libc::c_void
+    }
 }
@@ -6,3 +6,11 @@ pub enum InitError {
     #[fail(display = "must be compiled with --feature=cuda to use cuda")]
     NeedsCudaFeature,
 }
+
+/// Error returned when NVRTC fails to compile CUDA C code.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+#[fail(display = "NVRTC failed to compile the kernel: {}", log)]
+pub struct NvrtcError {
+    /// The compilation log of NVRTC.
+    pub log: String,
+}
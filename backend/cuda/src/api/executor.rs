@@ -3,17 +3,35 @@ use crate::api::wrapper::*;
 use crate::api::*;
 use lazy_static::lazy_static;
 use libc;
-use std::ffi::CStr;
-use std::sync::Mutex;
+use std::collections::hash_map::RandomState;
+use std::convert::Infallible;
+use std::ffi::{CStr, CString};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use telamon::device;
 use utils::*;
 
 lazy_static! {
     static ref JIT_SPAWNER: Mutex<DaemonSpawner> = Mutex::new(DaemonSpawner::new());
 }
 
+/// Maximal number of cubin images kept in the cache of an `Executor`.
+const MAX_CACHED_CUBINS: usize = 1024;
+
+/// Digest of the code, compute capability and optimization level a cubin image is
+/// compiled from.
+type CubinKey = (u128, Option<(u8, u8)>, usize);
+
 /// Interface with a CUDA device.
 pub struct Executor {
     context: *mut CudaContext,
+    /// Compiled cubin images, indexed by a digest of their PTX or CUDA C code, by the
+    /// compute capability CUDA C code is compiled for and by their optimization level.
+    /// Candidates that only differ by the order of their decisions often generate the
+    /// same code, which is then only compiled once.
+    cubins: Cache<CubinKey, Vec<u8>>,
+    /// Independent keys of the two halves of the code digest.
+    digest_keys: (RandomState, RandomState),
 }
 
 impl Executor {
@@ -28,6 +46,8 @@ impl Executor {
         let _ = unwrap!(JIT_SPAWNER.lock());
         Ok(Executor {
             context: unsafe { init_cuda(0) },
+            cubins: Cache::new(MAX_CACHED_CUBINS),
+            digest_keys: Default::default(),
         })
     }
 
@@ -38,12 +58,82 @@ impl Executor {
 
     /// Compiles a PTX module.
     pub fn compile_ptx<'a>(&'a self, code: &str, opt_level: usize) -> Module<'a> {
-        Module::new(unsafe { &*self.context as &'a _ }, code, opt_level)
+        let context = unsafe { &*self.context as &'a _ };
+        let cubin = self.cubin(code, None, opt_level, || {
+            Module::compile_cubin(context, code, opt_level)
+        });
+        Module::from_cubin(context, &cubin)
     }
 
     /// Compiles a PTX module using a separate process.
     pub fn compile_remote<'a>(&'a self, jit: &mut JITDaemon, code: &str) -> Module<'a> {
-        jit.compile(unsafe { &*self.context as &'a _ }, code)
+        let cubin = self.cubin(code, None, jit.opt_level(), || jit.compile_cubin(code));
+        Module::from_cubin(unsafe { &*self.context as &'a _ }, &cubin)
+    }
+
+    /// Compiles a CUDA C module with NVRTC for the given compute capability, in the
+    /// current process.  Returns the compilation log if NVRTC fails.
+    pub fn compile_cuda<'a>(
+        &'a self,
+        code: &str,
+        (sm_major, sm_minor): (u8, u8),
+        opt_level: usize,
+    ) -> Result<Module<'a>, NvrtcError> {
+        let context = unsafe { &*self.context as &'a _ };
+        let compute_capability = Some((sm_major, sm_minor));
+        let cubin = self.try_cubin(code, compute_capability, opt_level, || {
+            let ptx = nvrtc_compile(code, sm_major, sm_minor)?;
+            Ok(Module::compile_cubin(context, &ptx, opt_level))
+        })?;
+        Ok(Module::from_cubin(context, &cubin))
+    }
+
+    /// Returns the cubin image of `code`, calling `compile` to build it if it is not
+    /// already in the cache.  `compute_capability` is the target of CUDA C code, and is
+    /// `None` for PTX code.
+    pub(super) fn cubin<F>(
+        &self,
+        code: &str,
+        compute_capability: Option<(u8, u8)>,
+        opt_level: usize,
+        compile: F,
+    ) -> Arc<Vec<u8>>
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        let compile = || Ok::<_, Infallible>(compile());
+        match self.try_cubin(code, compute_capability, opt_level, compile) {
+            Ok(cubin) => cubin,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Same as `cubin`, but `compile` may fail, in which case the error is returned and
+    /// nothing is cached.
+    fn try_cubin<F, E>(
+        &self,
+        code: &str,
+        compute_capability: Option<(u8, u8)>,
+        opt_level: usize,
+        compile: F,
+    ) -> Result<Arc<Vec<u8>>, E>
+    where
+        F: FnOnce() -> Result<Vec<u8>, E>,
+    {
+        let key = (self.digest(code), compute_capability, opt_level);
+        self.cubins.try_get(&key, compile)
+    }
+
+    /// Returns a 128-bit digest of `code`, made of two independently keyed hashes. Unlike
+    /// a single 64-bit hash, collisions between the codes of a search are negligible.
+    fn digest(&self, code: &str) -> u128 {
+        let hash = |state: &RandomState| {
+            let mut hasher = state.build_hasher();
+            code.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (high, low) = (hash(&self.digest_keys.0), hash(&self.digest_keys.1));
+        (u128::from(high) << 64) | u128::from(low)
     }
 
     /// Allocates an array on the CUDA device.
@@ -74,6 +164,29 @@ impl Executor {
     pub fn device_attribute(&self, attribute: DeviceAttribute) -> i32 {
         unsafe { device_attribute(self.context, attribute as u32) }
     }
+
+    /// Reads the temperature and the current clocks of the device.  Returns `None` if NVML
+    /// cannot access the device.
+    pub fn thermal_state(&self) -> Option<device::ThermalState> {
+        let (mut temperature, mut sm_clock, mut mem_clock) = (0, 0, 0);
+        let ret = unsafe {
+            thermal_state(
+                self.context,
+                &mut temperature,
+                &mut sm_clock,
+                &mut mem_clock,
+            )
+        };
+        if ret == 0 {
+            Some(device::ThermalState {
+                temperature,
+                sm_clock,
+                mem_clock,
+            })
+        } else {
+            None
+        }
+    }
 }
 
 impl Drop for Executor {
@@ -114,4 +227,38 @@ pub enum DeviceAttribute {
     GlobalL1CacheSupported = 79,
     /// Maximum shared memory available per multiprocessor in bytes.
     MaxSharedMemoryPerSmx = 81,
+    /// Device supports launching kernels with thread block clusters.  Only known to drivers
+    /// supporting compute capability 9.0 and later.
+    ClusterLaunch = 120,
+}
+
+/// Compiles CUDA C code to PTX with NVRTC, for the given compute capability.
+fn nvrtc_compile(code: &str, sm_major: u8, sm_minor: u8) -> Result<String, NvrtcError> {
+    let c_code = unwrap!(CString::new(code));
+    let mut log = std::ptr::null_mut();
+    unsafe {
+        let ptx = compile_cuda_to_ptx(
+            c_code.as_ptr(),
+            u32::from(sm_major),
+            u32::from(sm_minor),
+            &mut log,
+        );
+        if ptx.is_null() {
+            let log = if log.is_null() {
+                "unknown error".to_string()
+            } else {
+                take_c_string(log)
+            };
+            Err(NvrtcError { log })
+        } else {
+            Ok(take_c_string(ptx))
+        }
+    }
+}
+
+/// Copies a string allocated by the C wrapper and frees it.
+unsafe fn take_c_string(c_ptr: *mut libc::c_char) -> String {
+    let string = CStr::from_ptr(c_ptr).to_string_lossy().into_owned();
+    libc::free(c_ptr as *mut libc::c_void);
+    string
 }
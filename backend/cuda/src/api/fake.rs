@@ -4,6 +4,7 @@
 
 use crate::api;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use telamon::device;
 
 /// An argument that can be passed to the executor.
@@ -42,6 +43,22 @@ where
 
 impl<'a, T> Argument for Array<'a, T> where T: device::ScalarArgument {}
 
+/// An array allocated outside of the context, passed to kernels through its device address.
+pub struct ExternalArray<'a> {
+    _array: Arc<dyn device::ArrayArgument + 'a>,
+}
+
+impl<'a> ExternalArray<'a> {
+    /// Wraps an array, if it exposes its device address.
+    pub fn new(array: Arc<dyn device::ArrayArgument + 'a>) -> Option<Self> {
+        array
+            .raw_device_ptr()
+            .map(|_| ExternalArray { _array: array })
+    }
+}
+
+impl<'a> Argument for ExternalArray<'a> {}
+
 /// Interface with a CUDA device.
 pub enum Executor {}
 
@@ -75,6 +92,21 @@ impl Executor {
     pub fn compile_remote<'a>(&'a self, _: &mut JITDaemon, _: &str) -> Module<'a> {
         match *self {}
     }
+
+    /// Compiles a CUDA C module with NVRTC.
+    pub fn compile_cuda<'a>(
+        &'a self,
+        _: &str,
+        _: (u8, u8),
+        _: usize,
+    ) -> Result<Module<'a>, api::NvrtcError> {
+        match *self {}
+    }
+
+    /// Reads the temperature and the current clocks of the device.
+    pub fn thermal_state(&self) -> Option<device::ThermalState> {
+        match *self {}
+    }
 }
 
 /// A process that compiles PTX in a separate process.
@@ -122,6 +154,20 @@ impl<'a> Kernel<'a> {
         match *self.executor {}
     }
 
+    /// Runs the kernel repeatedly for at least `min_duration` nanoseconds and returns the
+    /// average execution time, in nanoseconds, and the average energy consumed by an
+    /// execution, in nanojoules.  Returns `None` if the device does not support energy
+    /// measurement.
+    pub fn measure_energy(
+        &self,
+        _: &[u32; 3],
+        _: &[u32; 3],
+        _: &[&dyn Argument],
+        _: f64,
+    ) -> Option<(f64, f64)> {
+        match *self.executor {}
+    }
+
     /// Indicates the number of active block of threads per multiprocessors.
     pub fn blocks_per_smx(&self, _: &[u32; 3]) -> u32 {
         match *self.executor {}
@@ -15,14 +15,24 @@ pub struct JITDaemon {
     daemon: libc::pid_t,
     ptx_sender: ipc::IpcBytesSender,
     cubin_receiver: ipc::IpcBytesReceiver,
+    opt_level: usize,
 }
 
 impl JITDaemon {
     pub fn compile<'a>(&mut self, context: &'a CudaContext, code: &str) -> Module<'a> {
+        Module::from_cubin(context, &self.compile_cubin(code))
+    }
+
+    /// Compiles PTX code into a cubin image.
+    pub fn compile_cubin(&mut self, code: &str) -> Vec<u8> {
         debug!("compiling {}", code);
         unwrap!(self.ptx_sender.send(code.as_bytes()));
-        let cubin = unwrap!(self.cubin_receiver.recv());
-        Module::from_cubin(context, &cubin)
+        unwrap!(self.cubin_receiver.recv())
+    }
+
+    /// Returns the optimization level the daemon compiles with.
+    pub fn opt_level(&self) -> usize {
+        self.opt_level
     }
 }
 
@@ -133,6 +143,7 @@ impl DaemonSpawner {
             daemon,
             ptx_sender,
             cubin_receiver,
+            opt_level,
         }
     }
 }
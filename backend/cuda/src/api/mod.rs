@@ -8,7 +8,7 @@ mod jit_daemon;
 mod module;
 mod wrapper;
 
-pub use self::array::Array;
+pub use self::array::{Array, ExternalArray};
 pub use self::counter::{PerfCounter, PerfCounterSet};
 pub use self::error::*;
 pub use self::executor::*;
@@ -22,6 +22,7 @@ use self::jit_daemon::DaemonSpawner;
 mod tests {
     use super::array;
     use super::*;
+    use telamon::device::ArrayArgument;
     use utils::*;
 
     /// Tries to initialize a CUDA execution context.
@@ -45,6 +46,30 @@ mod tests {
             executor.compile_ptx(".version 3.0\n.target sm_30\n.address_size 64\n", 1);
     }
 
+    /// Ensures a compiled PTX module is reused for the same code and optimization level
+    /// only.
+    #[test]
+    fn test_cached_module() {
+        let executor = Executor::init();
+        let code = ".version 3.0\n.target sm_30\n.address_size 64\n";
+        let _ = executor.compile_ptx(code, 1);
+        let cubin = executor.cubin(code, None, 1, || panic!("the cubin is not cached"));
+        assert!(!cubin.is_empty());
+        let mut compiled = false;
+        executor.cubin(code, None, 2, || {
+            compiled = true;
+            vec![]
+        });
+        assert!(compiled);
+        let other_code = ".version 3.0\n.target sm_30\n.address_size 64\n\n";
+        let mut compiled = false;
+        executor.cubin(other_code, None, 1, || {
+            compiled = true;
+            vec![]
+        });
+        assert!(compiled);
+    }
+
     /// Tries to compile an empty PTX kernel and execute it.
     #[test]
     fn test_empty_kernel() {
@@ -106,4 +131,40 @@ mod tests {
         unwrap!(kernel.execute(&[block_dim, 1, 1], &[1, 1, 1], &[&src, &dst]));
         assert!(array::compare_f32(&src, &dst) < 1e-5);
     }
+
+    /// Ensures arrays expose their device address, and that kernels can access arrays
+    /// through it.
+    #[test]
+    fn test_external_array() {
+        let executor = Executor::init();
+        let mut src = executor.allocate_array::<f32>(1);
+        let dst = std::sync::Arc::new(executor.allocate_array::<f32>(1));
+        array::randomize_f32(&mut src);
+        let src_ptr = unwrap!(src.raw_device_ptr());
+        let dst_ptr = unwrap!(dst.raw_device_ptr());
+        assert_ne!(src_ptr, 0);
+        assert_ne!(src_ptr, dst_ptr);
+        assert_eq!(unsafe { *(src.raw_ptr() as *const u64) }, src_ptr);
+
+        let external = unwrap!(ExternalArray::new(dst.clone()));
+        let module = executor.compile_ptx(
+            ".version 3.0\n.target sm_30\n.address_size 64\n
+            .entry copy(
+                .param.u64.ptr.global .align 16 src,
+                .param.u64.ptr.global .align 16 dst
+            ) {
+                .reg.u64 %rd<2>;
+                .reg.f32 %f;
+                ld.param.u64 %rd0, [src];
+                ld.param.u64 %rd1, [dst];
+                ld.global.f32 %f, [%rd0];
+                st.global.f32 [%rd1], %f;
+                ret;
+            }",
+            1,
+        );
+        let kernel = module.kernel("copy");
+        unwrap!(kernel.execute(&[1, 1, 1], &[1, 1, 1], &[&src, &external]));
+        assert!(array::compare_f32(&src, &dst) < 1e-5);
+    }
 }
@@ -18,16 +18,21 @@ pub struct Module<'a> {
 impl<'a> Module<'a> {
     /// Creates a new `Module`.
     pub fn new(context: &'a CudaContext, code: &str, opt_level: usize) -> Self {
+        Self::from_cubin(context, &Self::compile_cubin(context, code, opt_level))
+    }
+
+    /// Compiles PTX code into a cubin image, that can be loaded with `from_cubin`.
+    pub fn compile_cubin(context: &CudaContext, code: &str, opt_level: usize) -> Vec<u8> {
         debug!("compiling... {}", code);
         let c_str = unwrap!(CString::new(code));
-        let module = unsafe {
+        unsafe {
             let cubin_obj =
                 compile_ptx_to_cubin(context, c_str.as_ptr(), code.len(), opt_level);
-            let module = load_cubin(context, cubin_obj.data as *const libc::c_void);
+            let cubin =
+                std::slice::from_raw_parts(cubin_obj.data, cubin_obj.data_size).to_vec();
             free_cubin_object(cubin_obj);
-            module
-        };
-        Module { module, context }
+            cubin
+        }
     }
 
     /// Creates a `Module` from a cubin image.
@@ -128,6 +133,38 @@ impl<'a> Kernel<'a> {
         }
     }
 
+    /// Runs the kernel repeatedly for at least `min_duration` nanoseconds and returns the
+    /// average execution time, in nanoseconds, and the average energy consumed by an
+    /// execution, in nanojoules.  Returns `None` if the device does not support energy
+    /// measurement.
+    pub fn measure_energy(
+        &self,
+        blocks: &[u32; 3],
+        threads: &[u32; 3],
+        args: &[&dyn Argument],
+        min_duration: f64,
+    ) -> Option<(f64, f64)> {
+        unsafe {
+            let arg_raw_ptrs = args.iter().map(|x| x.raw_ptr()).collect_vec();
+            let (mut time, mut energy) = (0., 0.);
+            let ret = measure_energy(
+                self.context,
+                self.function,
+                blocks.as_ptr(),
+                threads.as_ptr(),
+                arg_raw_ptrs.as_ptr(),
+                min_duration,
+                &mut time,
+                &mut energy,
+            );
+            if ret == 0 {
+                Some((time, energy))
+            } else {
+                None
+            }
+        }
+    }
+
     /// Indicates the number of active block of threads per multiprocessors.
     pub fn blocks_per_smx(&self, threads: &[u32; 3]) -> u32 {
         let block_size = threads.iter().product::<u32>();
@@ -3,8 +3,11 @@
 #include <cuda.h>
 #include <cupti.h>
 #include <curand.h>
+#include <nvml.h>
+#include <nvrtc.h>
 #include <stdint.h>
 #include <stdio.h>
+#include <string.h>
 
 #define ERROR_BUFF_SIZE 500
 
@@ -54,6 +57,10 @@ typedef struct CudaContext {
   CUcontext ctx;
   curandGenerator_t rng;
   CUpti_EventGroup num_cycle_event;
+  // NVML handle on the device, only valid if `has_nvml` is set.
+  nvmlDevice_t nvml_device;
+  int has_nvml;
+  int has_energy_counter;
 } CudaContext;
 
 // Holds informations on an event group.
@@ -71,6 +78,24 @@ typedef struct EventSets {
   uint32_t max_num_value_per_group;
 } EventSets;
 
+// Retrieves the NVML handle on the device of the context and checks if the device supports
+// energy measurement. NVML is optional, so errors are not fatal.
+void init_energy_counter(CudaContext* context) {
+  context->has_nvml = 0;
+  context->has_energy_counter = 0;
+  if (nvmlInit_v2() != NVML_SUCCESS) { return; }
+  char pci_bus_id[32];
+  unsigned long long energy;
+  if (cuDeviceGetPCIBusId(pci_bus_id, 32, context->device) == CUDA_SUCCESS
+      && nvmlDeviceGetHandleByPciBusId_v2(pci_bus_id, &context->nvml_device) == NVML_SUCCESS) {
+    context->has_nvml = 1;
+    context->has_energy_counter =
+      nvmlDeviceGetTotalEnergyConsumption(context->nvml_device, &energy) == NVML_SUCCESS;
+  } else {
+    nvmlShutdown();
+  }
+}
+
 // Initalize a CUDA execution context. This context may only be used from the thread that
 // created it (https://devtalk.nvidia.com/default/topic/519087/cuda-context-and-threading/).
 CudaContext* init_cuda(uint64_t seed) {
@@ -84,6 +109,7 @@ CudaContext* init_cuda(uint64_t seed) {
   CUpti_EventID event;
   CHECK_CUPTI(cuptiEventGetIdFromName(context->device, "elapsed_cycles_sm", &event));
   CHECK_CUPTI(cuptiEventGroupAddEvent(context->num_cycle_event, event));
+  init_energy_counter(context);
   return context;
 }
 
@@ -93,6 +119,7 @@ void free_cuda(CudaContext* context) {
   CHECK_CUPTI(cuptiEventGroupDestroy(context->num_cycle_event));
   CHECK_CURAND(curandDestroyGenerator(context->rng));
   HARD_CHECK_CUDA(cuCtxDestroy(context->ctx));
+  if (context->has_nvml) { nvmlShutdown(); }
   free(context);
 }
 
@@ -154,6 +181,41 @@ CubinObject compile_ptx_to_cubin(CudaContext* ctx,
   return object;
 }
 
+// Compiles CUDA C code into PTX with NVRTC, for the given compute capability.  Returns
+// the PTX code, null-terminated.  If the compilation fails, returns NULL and sets `log`
+// to the compilation log instead.  The returned string must be freed by the caller.
+char* compile_cuda_to_ptx(const char* code, uint32_t sm_major, uint32_t sm_minor,
+                          char** log) {
+  nvrtcProgram program;
+  nvrtcResult create_err = nvrtcCreateProgram(&program, code, "kernel.cu", 0, NULL, NULL);
+  if (create_err != NVRTC_SUCCESS) {
+    *log = strdup(nvrtcGetErrorString(create_err));
+    return NULL;
+  }
+  char arch[64];
+  snprintf(arch, sizeof(arch), "--gpu-architecture=compute_%u%u", sm_major, sm_minor);
+  const char* options[] = { arch };
+  char* ptx = NULL;
+  nvrtcResult err = nvrtcCompileProgram(program, 1, options);
+  if (err == NVRTC_SUCCESS) {
+    size_t ptx_size;
+    nvrtcGetPTXSize(program, &ptx_size);
+    ptx = malloc(ptx_size);
+    nvrtcGetPTX(program, ptx);
+  } else {
+    const char* err_string = nvrtcGetErrorString(err);
+    size_t log_size;
+    nvrtcGetProgramLogSize(program, &log_size);
+    // The log size includes the terminating null character.
+    *log = malloc(strlen(err_string) + 2 + log_size);
+    strcpy(*log, err_string);
+    strcat(*log, ": ");
+    nvrtcGetProgramLog(program, *log + strlen(*log));
+  }
+  nvrtcDestroyProgram(&program);
+  return ptx;
+}
+
 void free_cubin_object(CubinObject object) {
   HARD_CHECK_CUDA(cuLinkDestroy(*object.state));
   free(object.state);
@@ -230,6 +292,67 @@ double time_with_events(CudaContext* context, CUfunction* function, uint32_t* bl
   return ((double)ms) * 1e6;
 }
 
+// Runs a kernel repeatedly for at least `min_duration` nanoseconds while reading the energy
+// counter of the device, whose resolution is too coarse to measure a single execution. Stores
+// the average execution time in nanoseconds in `time` and the average energy consumed by an
+// execution in nanojoules in `energy`. Returns -1 if the device does not support energy
+// measurement.
+int32_t measure_energy(CudaContext* context, CUfunction* function, uint32_t* blocks,
+    uint32_t* threads, void** params, double min_duration, double* time,
+    double* energy) {
+  if (!context->has_energy_counter) { return -1; }
+  HARD_CHECK_CUDA(cuCtxSetCurrent(context->ctx));
+
+  CUevent start, stop;
+  HARD_CHECK_CUDA(cuEventCreate(&start, CU_EVENT_DEFAULT));
+  HARD_CHECK_CUDA(cuEventCreate(&stop, CU_EVENT_DEFAULT));
+
+  unsigned long long energy_start, energy_stop;
+  nvmlReturn_t start_status =
+    nvmlDeviceGetTotalEnergyConsumption(context->nvml_device, &energy_start);
+  HARD_CHECK_CUDA(cuEventRecord(start, 0));
+  uint64_t num_runs = 0;
+  float ms = 0;
+  do {
+    HARD_CHECK_CUDA(cuLaunchKernel(*function, blocks[0], blocks[1], blocks[2],
+          threads[0], threads[1], threads[2], 0, NULL, params, NULL));
+    HARD_CHECK_CUDA(cuEventRecord(stop, 0));
+    HARD_CHECK_CUDA(cuEventSynchronize(stop));
+    HARD_CHECK_CUDA(cuEventElapsedTime(&ms, start, stop));
+    ++num_runs;
+  } while (((double)ms) * 1e6 < min_duration);
+  nvmlReturn_t stop_status =
+    nvmlDeviceGetTotalEnergyConsumption(context->nvml_device, &energy_stop);
+
+  HARD_CHECK_CUDA(cuEventDestroy(start));
+  HARD_CHECK_CUDA(cuEventDestroy(stop));
+  if (start_status != NVML_SUCCESS || stop_status != NVML_SUCCESS) { return -1; }
+
+  *time = ((double)ms) * 1e6 / num_runs;
+  // The energy counter is in millijoules.
+  *energy = ((double)(energy_stop - energy_start)) * 1e6 / num_runs;
+  return 0;
+}
+
+// Reads the temperature of the device in degrees Celsius and its current SM and memory
+// clocks in MHz. Returns -1 if the sensors cannot be read through NVML.
+int32_t thermal_state(CudaContext* context, uint32_t* temperature, uint32_t* sm_clock,
+    uint32_t* mem_clock) {
+  if (!context->has_nvml) { return -1; }
+  unsigned int temp, sm, mem;
+  if (nvmlDeviceGetTemperature(context->nvml_device, NVML_TEMPERATURE_GPU, &temp)
+        != NVML_SUCCESS
+      || nvmlDeviceGetClockInfo(context->nvml_device, NVML_CLOCK_SM, &sm) != NVML_SUCCESS
+      || nvmlDeviceGetClockInfo(context->nvml_device, NVML_CLOCK_MEM, &mem)
+        != NVML_SUCCESS) {
+    return -1;
+  }
+  *temperature = temp;
+  *sm_clock = sm;
+  *mem_clock = mem;
+  return 0;
+}
+
 // Runs a kernel multiple times to gather a set of performance counter values.
 void instrument_kernel(CudaContext* ctx, CUfunction* function, uint32_t* blocks,
     uint32_t* threads, void** params, EventSets* events, CUpti_EventID* event_ids,
@@ -301,6 +424,12 @@ void copy_DtoD(CudaContext* ctx, CUdeviceptr* src, CUdeviceptr* dst, uint64_t si
   HARD_CHECK_CUDA(cuMemcpyDtoD(*dst, *src, size));
 }
 
+// Sets all the bytes of an array to zero.
+void zero_array(CudaContext* ctx, CUdeviceptr* dst, uint64_t size) {
+  HARD_CHECK_CUDA(cuCtxSetCurrent(ctx->ctx));
+  HARD_CHECK_CUDA(cuMemsetD8(*dst, 0, size));
+}
+
 // Fills a float array with random data.
 void randomize_float_array(CudaContext* ctx, CUdeviceptr* dst, uint64_t size,
     float mean, float stddev) {
@@ -44,6 +44,22 @@ extern "C" {
         threads: *const u32,
         params: *const *const libc::c_void,
     ) -> f64;
+    pub fn measure_energy(
+        context: *const CudaContext,
+        function: *mut CudaFunction,
+        blocks: *const u32,
+        threads: *const u32,
+        params: *const *const libc::c_void,
+        min_duration: f64,
+        time: *mut f64,
+        energy: *mut f64,
+    ) -> i32;
+    pub fn thermal_state(
+        context: *const CudaContext,
+        temperature: *mut u32,
+        sm_clock: *mut u32,
+        mem_clock: *mut u32,
+    ) -> i32;
     pub fn instrument_kernel(
         context: *const CudaContext,
         function: *const CudaFunction,
@@ -74,6 +90,7 @@ extern "C" {
         dst: *mut CudaArray,
         size: u64,
     );
+    pub fn zero_array(context: *const CudaContext, dst: *mut CudaArray, size: u64);
     pub fn randomize_float_array(
         ctx: *const CudaContext,
         dst: *mut CudaArray,
@@ -104,4 +121,10 @@ extern "C" {
         opt_level: libc::size_t,
     ) -> CubinObject;
     pub fn free_cubin_object(object: CubinObject);
+    pub fn compile_cuda_to_ptx(
+        code: *const libc::c_char,
+        sm_major: u32,
+        sm_minor: u32,
+        log: *mut *mut libc::c_char,
+    ) -> *mut libc::c_char;
 }
@@ -0,0 +1,311 @@
+//! Prints CUDA C kernels, to be compiled with NVRTC.
+//!
+//! The code is printed with the C99 printer shared with the other C-based backends.  Memory
+//! accesses go through generic pointers, so they do not need to specify their state space.
+//! Vector instructions, half-precision values and tensor cores are not supported:
+//! printing a kernel that uses them fails with `PrintError::Unsupported`.
+use crate::{NameGenerator, PrintError};
+use itertools::Itertools;
+use std::fmt::Write as WriteFmt;
+use telamon::codegen::*;
+use telamon::ir::{self, Type};
+use telamon::search_space::DimKind;
+use telamon_c::C99Display as _;
+use utils::unwrap;
+
+#[derive(Default)]
+pub(crate) struct CPrinter {
+    buffer: CodeBuffer,
+}
+
+/// Returns the type of a kernel parameter.
+fn param_t(param: &ParamVal) -> String {
+    match param {
+        ParamVal::External(param, _) if param.elem_t.is_some() => "void*".to_string(),
+        ParamVal::External(_, par_type) => par_type.c99().to_string(),
+        ParamVal::Size(_) => "uint32_t".to_string(),
+        ParamVal::GlobalMem(..) => "void*".to_string(),
+    }
+}
+
+/// Returns a description of the first construct of `function` that cannot be expressed in
+/// CUDA C, if any.
+fn unsupported_construct(function: &Function) -> Option<&'static str> {
+    if function
+        .dimensions()
+        .any(|dim| dim.kind().intersects(DimKind::VECTOR))
+    {
+        return Some("vector instructions");
+    }
+    let is_half = |t: Type| t == Type::F(16);
+    for inst in function.cfg().instructions() {
+        let operator = inst.operator();
+        if let ir::Operator::Mma(..) = operator {
+            return Some("tensor cores");
+        }
+        let operand_types = operator.operands().into_iter().map(|op| op.t());
+        if operator.t().into_iter().chain(operand_types).any(is_half) {
+            return Some("half-precision values");
+        }
+    }
+    None
+}
+
+impl CPrinter {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        CPrinter {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+        }
+    }
+
+    /// Declares all parameters of the function with the appropriate type
+    fn param_decl(&self, param: &ParamVal) -> String {
+        format!("{} {}", param_t(param), param.key().ident())
+    }
+
+    /// Declared all variables that have been required from the namegen
+    fn var_decls(&self, namegen: &NameGenerator) -> String {
+        let print_decl = |(&t, &n)| {
+            let prefix = NameGenerator::gen_prefix(t);
+            let names = (0..n).map(|i| format!("{}{}", prefix, i)).join(", ");
+            format!("{} {};\n  ", t.c99(), names)
+        };
+        namegen.num_var.iter().map(print_decl).join("\n  ")
+    }
+
+    /// Declares block and thread indexes.
+    fn decl_par_indexes(&self, function: &Function, name_map: &NameMap<'_>) -> String {
+        let mut decls = vec![];
+        // Load block indexes.
+        for (dim, dir) in function.block_dims().iter().zip(&["x", "y", "z"]) {
+            let index = name_map.name_index(dim.id());
+            decls.push(format!("{} = blockIdx.{};", index.c99(), dir));
+        }
+        // Compute thread indexes.
+        for (dim, dir) in function.thread_dims().iter().rev().zip(&["x", "y", "z"]) {
+            let index = name_map.name_index(dim.id());
+            decls.push(format!("{} = threadIdx.{};", index.c99(), dir));
+        }
+        decls.join("\n  ")
+    }
+
+    /// Declares a block of shared memory.
+    fn shared_mem_decl(&mut self, block: &MemoryRegion, name_map: &NameMap<'_>) {
+        unwrap!(writeln!(
+            self.buffer,
+            "__shared__ __align__(16) char shmem{id}[{size}];
+{name} = (intptr_t)shmem{id};",
+            id = block.id().0,
+            name = name_map.name_addr(block.id()).c99(),
+            size = unwrap!(block.alloc_size().as_int()),
+        ));
+    }
+
+    /// Prints the CUDA C code of a kernel.  Fails if the kernel uses constructs that
+    /// cannot be expressed in CUDA C or if the code exceeds the size limit of the
+    /// printer.
+    pub fn kernel<'a: 'b, 'b>(
+        &mut self,
+        function: &'b Function<'a>,
+    ) -> Result<String, PrintError> {
+        if let Some(construct) = unsupported_construct(function) {
+            return Err(PrintError::Unsupported(construct));
+        }
+        let mut namegen = NameGenerator::default();
+        let interner = Interner::default();
+        let name_map = &mut NameMap::new(&interner, function, &mut namegen);
+
+        let param_decls = function
+            .device_code_args()
+            .map(|v| self.param_decl(v))
+            .join(",\n  ");
+        let mut return_string = format!(
+            include_str!("template/kernel.cu"),
+            name = function.name(),
+            params = param_decls,
+            num_thread = function.num_threads(),
+        );
+        // INDEX LOADS
+        let idx_loads = self.decl_par_indexes(function, name_map);
+        unwrap!(writeln!(self.buffer, "{}", idx_loads));
+        // LOAD PARAM
+        for val in function.device_code_args() {
+            let var_name = name_map.name_param_val(val.key());
+            unwrap!(writeln!(
+                self.buffer,
+                "{var_name} = {cast}{name}; // {param}",
+                cast = if val.elem_t().is_some() {
+                    "(intptr_t)"
+                } else {
+                    ""
+                },
+                var_name = var_name.c99(),
+                name = val.key().ident(),
+                param = val.key(),
+            ));
+        }
+        // MEM DECL
+        for block in function.mem_blocks() {
+            match block.alloc_scheme() {
+                AllocationScheme::Shared => self.shared_mem_decl(block, name_map),
+                AllocationScheme::PrivatisedGlobal => {
+                    Printer::new(self, name_map).privatise_global_block(block, function)
+                }
+                AllocationScheme::Global => (),
+            }
+        }
+        // Compute size casts
+        for dim in function.dimensions() {
+            if !dim.kind().intersects(DimKind::UNROLL | DimKind::LOOP) {
+                continue;
+            }
+            for level in dim.induction_levels() {
+                if let Some((_, ref incr)) = level.increment {
+                    let reg = name_map.declare_size_cast(incr, level.t());
+                    if let Some(reg) = reg {
+                        let old_name = name_map.name_size(incr, Type::I(32));
+                        self.print_inst(
+                            llir::Instruction::cast(level.t(), reg, old_name)
+                                .unwrap()
+                                .into(),
+                        );
+                    }
+                }
+            }
+        }
+        // INIT
+        let ind_levels = function.init_induction_levels().iter().chain(
+            function
+                .block_dims()
+                .iter()
+                .flat_map(|d| d.induction_levels()),
+        );
+        for level in ind_levels {
+            Printer::new(self, name_map).parallel_induction_level(level);
+        }
+        // BODY
+        Printer::new(self, name_map).cfg(function, function.cfg());
+        let var_decls = self.var_decls(&namegen);
+        let body = self.buffer.take()?;
+        return_string.push_str("  ");
+        return_string.push_str(&var_decls);
+        return_string.push_str(&body);
+        return_string.push_str("}\n");
+        Ok(return_string)
+    }
+}
+
+impl InstPrinter for CPrinter {
+    fn print_label(&mut self, label: llir::Label<'_>) {
+        // C++ requires a statement after a label, which may end the kernel.
+        writeln!(self.buffer, "{};", label.c99()).unwrap()
+    }
+
+    fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
+        writeln!(self.buffer, "{}", inst.c99()).unwrap();
+    }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use telamon::helper;
+    use telamon::search_space::{Action, SearchSpace};
+
+    use super::*;
+    use crate::printer::tests::{gpu, implementation};
+
+    /// Prints the first implementation of `space`.
+    fn print(space: SearchSpace) -> Result<String, PrintError> {
+        let space = implementation(space);
+        CPrinter::default().kernel(&Function::build(&space))
+    }
+
+    /// Returns a builder for a kernel without parameters.
+    fn builder() -> helper::Builder {
+        crate::printer::tests::builder(&gpu(7, 0))
+    }
+
+    /// Ensures the kernel is declared with C linkage, so NVRTC does not mangle its name,
+    /// and that its launch bounds match its number of threads.
+    #[test]
+    fn kernel_decl() {
+        let _ = env_logger::try_init();
+        let mut builder = builder();
+        let size_64 = builder.cst_size(64);
+        let d0 = builder.open_dim_ex(size_64, DimKind::THREAD);
+        builder.mov(&d0);
+        let code = unwrap!(print(builder.get()));
+        let decl = "extern \"C\" __global__ void __launch_bounds__(64) test(";
+        assert!(code.contains(decl));
+        assert!(code.contains("threadIdx.x;"));
+        assert!(code.ends_with("}\n"));
+    }
+
+    /// Ensures block indexes are loaded from `blockIdx`.
+    #[test]
+    fn block_dims() {
+        let _ = env_logger::try_init();
+        let mut builder = builder();
+        let size_16 = builder.cst_size(16);
+        let d0 = builder.open_dim_ex(size_16.clone(), DimKind::BLOCK);
+        let d1 = builder.open_dim_ex(size_16, DimKind::BLOCK);
+        builder.mov(&d0);
+        builder.mov(&d1);
+        let code = unwrap!(print(builder.get()));
+        assert!(code.contains("__launch_bounds__(1)"));
+        assert!(code.contains("blockIdx.x;"));
+        assert!(code.contains("blockIdx.y;"));
+        assert!(!code.contains("threadIdx"));
+    }
+
+    /// Ensures printing fails once the code exceeds the size limit.
+    #[test]
+    fn size_limit() {
+        let _ = env_logger::try_init();
+        let mut builder = builder();
+        let size_64 = builder.cst_size(64);
+        let d0 = builder.open_dim_ex(size_64, DimKind::UNROLL);
+        builder.mov(&d0);
+        let space = implementation(builder.get());
+        let function = Function::build(&space);
+        assert_eq!(
+            CPrinter::with_size_limit(16).kernel(&function),
+            Err(PrintError::CodeSize(CodeSizeError { size_limit: 16 }))
+        );
+        assert!(CPrinter::default().kernel(&function).is_ok());
+    }
+
+    /// Ensures vector instructions are rejected instead of printed.
+    #[test]
+    fn reject_vectors() {
+        let _ = env_logger::try_init();
+        let mut builder = builder();
+        let size_4 = builder.cst_size(4);
+        let dim = builder.open_dim_ex(size_4, DimKind::UNROLL);
+        let x = builder.mov(&1f32);
+        let vec_dim = builder.open_mapped_dim(&dim);
+        builder.action(Action::DimKind(vec_dim[0], DimKind::INNER_VECTOR));
+        builder.add(&x, &x);
+        assert_eq!(
+            print(builder.get()),
+            Err(PrintError::Unsupported("vector instructions"))
+        );
+    }
+
+    /// Ensures half-precision values are rejected instead of printed.
+    #[test]
+    fn reject_half() {
+        let _ = env_logger::try_init();
+        let mut builder = builder();
+        builder.cast(&1f32, Type::F(16));
+        assert_eq!(
+            print(builder.get()),
+            Err(PrintError::Unsupported("half-precision values"))
+        );
+    }
+}
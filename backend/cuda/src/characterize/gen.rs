@@ -504,7 +504,12 @@ pub fn run(
         panic!("The benchmark is not completely scheduled: {:?}", choice);
     }
     let dev_fun = codegen::Function::build(space);
-    let kernel = Kernel::compile(&dev_fun, context.gpu(), context.executor(), 1);
+    let kernel = unwrap!(Kernel::compile(
+        &dev_fun,
+        context.gpu(),
+        context.executor(),
+        1
+    ));
     for &(arg, range) in args_range {
         bind_scalar(arg, range[0], context);
     }
@@ -24,8 +24,16 @@
 //!  - For Turing (Compute Capability 7.5):
 //!    https://www.nvidia.com/content/dam/en-zz/Solutions/design-visualization/technologies/turing-architecture/NVIDIA-Turing-Architecture-Whitepaper.pdf
 //!    https://docs.nvidia.com/cuda/turing-tuning-guide/index.html
+//!
+//!  - For Ampere (Compute Capability 8.x):
+//!    https://images.nvidia.com/aem-dam/en-zz/Solutions/data-center/nvidia-ampere-architecture-whitepaper.pdf
+//!    https://docs.nvidia.com/cuda/ampere-tuning-guide/index.html
+//!
+//!  - For Hopper (Compute Capability 9.0):
+//!    https://docs.nvidia.com/cuda/hopper-tuning-guide/index.html
 
 use crate::characterize::instruction;
+use crate::gpu::{default_max_code_size, default_max_unrolled_insts};
 use crate::DeviceAttribute::*;
 use crate::{Executor, Gpu, InstDesc};
 use log::*;
@@ -47,6 +55,7 @@ pub fn functional_desc(executor: &Executor) -> Gpu {
     let sm_major = executor.device_attribute(ComputeCapabilityMajor);
     let sm_minor = executor.device_attribute(ComputeCapabilityMinor);
     let (l1_cache_sectors_per_line, l1_cache_sector) = l1_cache_line(sm_major, sm_minor);
+    let max_cluster_size = max_cluster_size(executor, sm_major);
     Gpu {
         name: executor.device_name(),
         sm_major: sm_major as u8,
@@ -67,6 +76,12 @@ pub fn functional_desc(executor: &Executor) -> Gpu {
         num_smx: executor.device_attribute(SmxCount) as u32,
         max_block_per_smx: block_per_smx(sm_major, sm_minor),
         smx_clock: f64::from(executor.device_attribute(ClockRate)) / 1.0E+6,
+        max_cluster_size,
+        distributed_shared_mem: max_cluster_size > 1,
+        l2_partitions: l2_partitions(sm_major, sm_minor),
+        grid_constant_params: sm_major >= 7,
+        max_unrolled_insts: default_max_unrolled_insts(),
+        max_code_size: default_max_code_size(),
 
         thread_rates: EMPTY_INST_DESC,
         smx_rates: EMPTY_INST_DESC,
@@ -87,6 +102,8 @@ pub fn functional_desc(executor: &Executor) -> Gpu {
         mad_i64_inst: EMPTY_INST_DESC,
         mad_wide_inst: EMPTY_INST_DESC,
         div_f32_inst: EMPTY_INST_DESC,
+        div_approx_f32_inst: EMPTY_INST_DESC,
+        mma_f16_f32_inst: EMPTY_INST_DESC,
         div_f64_inst: EMPTY_INST_DESC,
         div_i32_inst: EMPTY_INST_DESC,
         div_i64_inst: EMPTY_INST_DESC,
@@ -95,6 +112,7 @@ pub fn functional_desc(executor: &Executor) -> Gpu {
         max_i32_inst: EMPTY_INST_DESC,
         max_i64_inst: EMPTY_INST_DESC,
         exp_f32_inst: EMPTY_INST_DESC,
+        exp_approx_f32_inst: EMPTY_INST_DESC,
         syncthread_inst: EMPTY_INST_DESC,
         loop_init_overhead: EMPTY_INST_DESC,
         loop_iter_overhead: EMPTY_INST_DESC,
@@ -134,6 +152,29 @@ fn allow_l1_for_global_mem(sm_major: i32, sm_minor: i32) -> bool {
     }
 }
 
+/// Returns the maximal number of blocks in a thread block cluster.
+/// Clusters were introduced with compute capability 9.0, where 8 is the maximal portable cluster
+/// size (see "Thread Block Clusters" in the CPG).  Larger, non-portable, sizes must be opted
+/// into for each kernel and are ignored here.
+fn max_cluster_size(executor: &Executor, sm_major: i32) -> u32 {
+    // Older drivers do not know about the `ClusterLaunch` attribute.
+    if sm_major >= 9 && executor.device_attribute(ClusterLaunch) != 0 {
+        8
+    } else {
+        1
+    }
+}
+
+/// Returns the number of partitions of the L2 cache.
+/// The A100 and H100 whitepapers describe an L2 cache split in two partitions, each directly
+/// connected to half of the SMXs.
+fn l2_partitions(sm_major: i32, sm_minor: i32) -> u32 {
+    match (sm_major, sm_minor) {
+        (8, 0) | (9, 0) => 2,
+        _ => 1,
+    }
+}
+
 /// Returns the maximum number of resident blocks on an SMX.
 /// From line "Maximum number of resident blocks per multiprocessor" on Table 14.
 fn block_per_smx(sm_major: i32, sm_minor: i32) -> u32 {
@@ -356,6 +397,7 @@ pub fn performance_desc(executor: &Executor, gpu: &mut Gpu) {
     gpu.mad_i64_inst = instruction::mad_i64(gpu, executor);
     gpu.mad_wide_inst = instruction::mad_wide(gpu, executor);
     gpu.div_f32_inst = instruction::div_f32(gpu, executor);
+    gpu.div_approx_f32_inst = instruction::div_approx_f32(gpu, executor);
     gpu.div_f64_inst = instruction::div_f64(gpu, executor);
     gpu.div_i32_inst = instruction::div_i32(gpu, executor);
     gpu.div_i64_inst = instruction::div_i64(gpu, executor);
@@ -364,6 +406,7 @@ pub fn performance_desc(executor: &Executor, gpu: &mut Gpu) {
     gpu.max_i32_inst = instruction::max_i32(gpu, executor);
     gpu.max_i64_inst = instruction::max_i64(gpu, executor);
     gpu.exp_f32_inst = instruction::exp_f32(gpu, executor);
+    gpu.exp_approx_f32_inst = instruction::exp_approx_f32(gpu, executor);
     gpu.mul_wide_inst = gpu.mul_i32_inst; // TODO(model): benchmark mul wide.
                                           // Compute memory accesses overhead.
     gpu.load_l2_latency = instruction::load_l2(gpu, executor);
@@ -10,6 +10,7 @@ use telamon::codegen;
 use telamon::device::{Device, ScalarArgument};
 use telamon::helper::tensor::DimSize;
 use telamon::ir;
+use telamon::search_space::{Action, Precision};
 use utils::*;
 
 /// Instruments a single thread with a loop containing chained instructions.
@@ -149,6 +150,15 @@ pub fn div_f32(gpu: &Gpu, executor: &Executor) -> InstDesc {
     inst::<f32>(gpu, executor, &|init, arg, b| b.div(init, arg))
 }
 
+pub fn div_approx_f32(gpu: &Gpu, executor: &Executor) -> InstDesc {
+    info!("Instruction: Div approx f32");
+    inst::<f32>(gpu, executor, &|init, arg, b| {
+        let inst = b.div(init, arg);
+        b.action(Action::Precision(inst, Precision::APPROX));
+        inst
+    })
+}
+
 pub fn div_f64(gpu: &Gpu, executor: &Executor) -> InstDesc {
     info!("Instruction: Div f64");
     inst::<f64>(gpu, executor, &|init, arg, b| b.div(init, arg))
@@ -189,6 +199,15 @@ pub fn exp_f32(gpu: &Gpu, executor: &Executor) -> InstDesc {
     inst::<f32>(gpu, executor, &|init, _arg, b| b.exp(init))
 }
 
+pub fn exp_approx_f32(gpu: &Gpu, executor: &Executor) -> InstDesc {
+    info!("Instruction: Exp approx f32");
+    inst::<f32>(gpu, executor, &|init, _arg, b| {
+        let inst = b.exp(init);
+        b.action(Action::Precision(inst, Precision::APPROX));
+        inst
+    })
+}
+
 /// Micro-bench a load instruction.
 ///
 /// * `stride` is the stride between accesses in number of `i64`.
@@ -208,7 +227,7 @@ fn load(gpu: &Gpu, executor: &Executor, stride: u32, num_load: u32) -> f64 {
         stride as i32,
     );
     let init_dev_fun = codegen::Function::build(&init_fun);
-    let init_dev_kernel = Kernel::compile(&init_dev_fun, gpu, executor, 1);
+    let init_dev_kernel = unwrap!(Kernel::compile(&init_dev_fun, gpu, executor, 1));
 
     let mut context = Context::from_gpu(gpu.clone(), executor);
     gen::bind_array::<i64>("array", array_size as usize, &mut context);
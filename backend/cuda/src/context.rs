@@ -1,29 +1,51 @@
-use crate::api::Argument;
+use crate::api::{self, Argument};
 use crate::kernel::Thunk;
-use crate::{Executor, Gpu, JITDaemon, Kernel};
+use crate::{Executor, Gpu, JITDaemon, Kernel, PrintError};
 ///! Defines the CUDA evaluation context.
 use crossbeam;
 use fxhash::FxHashMap;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use std::f64;
 use std::fmt;
 use std::sync::{atomic, mpsc, Arc};
 use telamon::device::{
-    self, AsyncCallback, Device, EvalMode, KernelEvaluator, ScalarArgument,
+    self, AsyncCallback, Device, EvalMode, KernelEvaluator, ParamUpdateError,
+    ScalarArgument,
 };
 use telamon::{codegen, explorer, ir};
 use utils::*;
 
 /// Max number of candidates waiting to be evaluated.
 const EVAL_BUFFER_SIZE: usize = 100;
+/// Max number of candidates waiting to be compiled by the compilation threads.
+const COMPILE_BUFFER_SIZE: usize = 100;
 // TODO(perf): enable optimizations when possible
 const JIT_OPT_LEVEL: usize = 2;
 
+/// The compiler used to generate the kernels evaluated by a `Context`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compiler {
+    /// Prints PTX code and compiles it with the JIT of the driver.  Candidates evaluated
+    /// asynchronously are compiled by daemons running in separate processes.
+    Ptx,
+    /// Prints CUDA C code and compiles it with NVRTC in the current process.  This does not
+    /// spawn any process, but does not support vector accesses, half-precision values and
+    /// tensor cores: candidates that use them fail to compile.
+    Nvrtc,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::Ptx
+    }
+}
+
 /// A CUDA evaluation context.
 pub struct Context<'a> {
     gpu_model: Arc<Gpu>,
     executor: &'a Executor,
     parameters: FxHashMap<String, Arc<dyn Argument + 'a>>,
+    compiler: Compiler,
 }
 
 impl<'a> Context<'a> {
@@ -38,9 +60,16 @@ impl<'a> Context<'a> {
             gpu_model: Arc::new(gpu),
             executor,
             parameters: FxHashMap::default(),
+            compiler: Compiler::default(),
         }
     }
 
+    /// Selects the compiler used to generate the kernels.
+    pub fn with_compiler(mut self, compiler: Compiler) -> Self {
+        self.compiler = compiler;
+        self
+    }
+
     /// Returns the GPU description.
     pub fn gpu(&self) -> &Arc<Gpu> {
         &self.gpu_model
@@ -56,6 +85,11 @@ impl<'a> Context<'a> {
         self.parameters[name].as_ref()
     }
 
+    /// Returns a parameter given its name, if it is bound.
+    pub(crate) fn find_param(&self, name: &str) -> Option<&dyn Argument> {
+        self.parameters.get(name).map(|arg| arg.as_ref())
+    }
+
     /// Binds a parameter to the gien name.
     pub fn bind_param(&mut self, name: String, arg: Arc<dyn Argument + 'a>) {
         self.parameters.insert(name, arg);
@@ -68,6 +102,31 @@ impl<'a> Context<'a> {
             EvalMode::FindBest | EvalMode::TestEval => JIT_OPT_LEVEL,
         }
     }
+
+    /// Compiles a device function in the current thread, with the compiler of the context.
+    /// Fails if the code of the function cannot be generated.
+    fn compile<'b>(
+        &self,
+        function: &'b codegen::Function<'b>,
+        opt_level: usize,
+    ) -> Result<Kernel<'a, 'b>, PrintError> {
+        let gpu = &self.gpu_model;
+        match self.compiler {
+            Compiler::Ptx => Kernel::compile(function, gpu, self.executor, opt_level),
+            Compiler::Nvrtc => {
+                Kernel::compile_nvrtc(function, gpu, self.executor, opt_level)
+            }
+        }
+    }
+
+    /// Returns the compiler of a thread compiling candidates asynchronously.
+    fn thread_compiler(&self, mode: EvalMode) -> ThreadCompiler {
+        let opt_level = Self::opt_level(mode);
+        match self.compiler {
+            Compiler::Ptx => ThreadCompiler::Daemon(self.executor.spawn_jit(opt_level)),
+            Compiler::Nvrtc => ThreadCompiler::Nvrtc(opt_level),
+        }
+    }
 }
 
 impl<'a> device::ArgMap<'a> for Context<'a> {
@@ -91,6 +150,19 @@ impl<'a> device::ArgMap<'a> for Context<'a> {
         self.bind_param(param.name.clone(), array.clone());
         array
     }
+
+    fn bind_existing_array(
+        &mut self,
+        param: &ir::Parameter,
+        array: Arc<dyn device::ArrayArgument + 'a>,
+    ) {
+        let array = unwrap!(
+            api::ExternalArray::new(array),
+            "parameter `{}` must be bound to an array on the device",
+            param.name
+        );
+        self.bind_param(param.name.clone(), Arc::new(array));
+    }
 }
 
 impl<'a> device::Context for Context<'a> {
@@ -106,59 +178,126 @@ impl<'a> device::Context for Context<'a> {
         device::Stabilizer::default().num_evals(20).num_outliers(4)
     }
 
+    fn thermal_state(&self) -> Option<device::ThermalState> {
+        self.executor.thermal_state()
+    }
+
     fn evaluate(&self, function: &codegen::Function, mode: EvalMode) -> Result<f64, ()> {
-        let gpu = &self.gpu_model;
-        let kernel = Kernel::compile(function, gpu, self.executor, Self::opt_level(mode));
+        let kernel = self
+            .compile(function, Self::opt_level(mode))
+            .map_err(|err| {
+                error!("cannot compile kernel {}: {}", function.name(), err)
+            })?;
         kernel
             .evaluate(self)
             .map(|t| t as f64 / self.gpu_model.smx_clock)
     }
 
     fn benchmark(&self, function: &codegen::Function, num_samples: usize) -> Vec<f64> {
-        let gpu = &self.gpu_model;
-        let kernel = Kernel::compile(function, gpu, self.executor, 4);
+        let kernel = unwrap!(self.compile(function, 4));
         kernel.evaluate_real(self, num_samples)
     }
 
+    fn compile_kernel<'b>(
+        &'b self,
+        function: &'b codegen::Function<'b>,
+    ) -> Box<dyn KernelEvaluator + 'b> {
+        let kernel = unwrap!(self.compile(function, 4));
+        Box::new(RealtimeThunk {
+            thunk: kernel.gen_thunk(self),
+            smx_clock: self.gpu_model.smx_clock,
+        })
+    }
+
+    fn export_artifact(&self, function: &codegen::Function) -> Option<codegen::Artifact> {
+        let ptx = self
+            .gpu_model
+            .print_ptx(function)
+            .map_err(|err| error!("cannot export kernel {}: {}", function.name(), err))
+            .ok()?;
+        let entry_point = function.name().to_string();
+        Some(codegen::Artifact::new(function, entry_point, ptx, self))
+    }
+
+    fn load_artifact<'b>(
+        &'b self,
+        artifact: &codegen::Artifact,
+    ) -> Result<Box<dyn KernelEvaluator + 'b>, codegen::ArtifactError> {
+        Ok(Box::new(RealtimeThunk {
+            thunk: Thunk::from_artifact(artifact, self, 4)?,
+            smx_clock: self.gpu_model.smx_clock,
+        }))
+    }
+
     fn async_eval<'c>(
         &self,
         num_workers: usize,
+        num_compilers: usize,
         mode: EvalMode,
+        cancel: &device::CancellationToken,
         inner: &(dyn Fn(&mut dyn device::AsyncEvaluator<'c>) + Sync),
     ) {
         // Setup the evaluator.
         let blocked_time = &atomic::AtomicUsize::new(0);
         let (send, recv) = mpsc::sync_channel(EVAL_BUFFER_SIZE);
+        let (compile_send, compile_recv) =
+            crossbeam::channel::bounded::<CompilePayload>(COMPILE_BUFFER_SIZE);
         // Correct because the thread handle is not escaped.
         crossbeam::scope(move |scope| {
+            // Start the compilation threads, if any.  They compile the candidates ahead of
+            // their evaluation so that the GPU does not wait for the JIT.
+            for _ in 0..num_compilers {
+                let send = send.clone();
+                let compile_recv = compile_recv.clone();
+                let mut compiler = self.thread_compiler(mode);
+                unwrap!(scope
+                    .builder()
+                    .name("Telamon - Compilation Thread".to_string())
+                    .spawn(move |_| {
+                        let queue = compile_recv
+                            .into_iter()
+                            .map(|(candidate, callback)| (candidate, (), callback));
+                        cancel.drain(queue, |candidate, (), callback| {
+                            let thunk =
+                                compile_candidate(self, &candidate, &mut compiler);
+                            unwrap!(send.send((candidate, thunk, callback)));
+                        });
+                    }));
+            }
+            std::mem::drop(compile_recv);
             // Start the explorer threads.
             for _ in 0..num_workers {
+                let compilation = if num_compilers == 0 {
+                    Compilation::Inline(self.thread_compiler(mode))
+                } else {
+                    Compilation::Pool(compile_send.clone())
+                };
                 let mut evaluator = AsyncEvaluator {
                     context: self,
                     sender: send.clone(),
-                    ptx_daemon: self.executor.spawn_jit(Self::opt_level(mode)),
+                    compilation,
                     blocked_time,
+                    cancel,
                 };
                 unwrap!(scope
                     .builder()
                     .name("Telamon - Explorer Thread".to_string())
                     .spawn(move |_| inner(&mut evaluator)));
             }
+            std::mem::drop(compile_send);
             // Start the evaluation thread.
             let eval_thread_name = "Telamon - GPU Evaluation Thread".to_string();
             let res = scope.builder().name(eval_thread_name).spawn(move |_| {
-                while let Ok((candidate, thunk, callback)) = recv.recv() {
-                    match thunk {
-                        Some(thunk) => callback.call(
-                            candidate,
-                            &mut RealtimeThunk {
-                                thunk,
-                                smx_clock: self.gpu_model.smx_clock,
-                            },
-                        ),
-                        None => callback.call(candidate, &mut ErrorThunk { _priv: () }),
-                    }
-                }
+                cancel.drain(recv, |candidate, thunk, callback| match thunk {
+                    Some(thunk) => callback.call(
+                        candidate,
+                        &mut RealtimeThunk {
+                            thunk,
+                            smx_clock: self.gpu_model.smx_clock,
+                        },
+                    ),
+                    None => callback.call(candidate, &mut ErrorThunk { _priv: () }),
+                });
             });
             unwrap!(res);
             std::mem::drop(send);
@@ -174,11 +313,31 @@ impl<'a> device::Context for Context<'a> {
 
 type AsyncPayload<'b> = (explorer::Candidate, Option<Thunk<'b>>, AsyncCallback<'b>);
 
+/// A candidate waiting to be compiled by a compilation thread.
+type CompilePayload<'b> = (explorer::Candidate, AsyncCallback<'b>);
+
+/// Compiles the candidates evaluated asynchronously in a thread.
+enum ThreadCompiler {
+    /// Compiles PTX code with a daemon running in a separate process.
+    Daemon(JITDaemon),
+    /// Compiles CUDA C code with NVRTC, with the given optimization level.
+    Nvrtc(usize),
+}
+
+/// Indicates where the candidates of an explorer thread are compiled.
+enum Compilation<'b> {
+    /// Candidates are compiled by the explorer thread itself.
+    Inline(ThreadCompiler),
+    /// Candidates are sent to the compilation threads.
+    Pool(crossbeam::channel::Sender<CompilePayload<'b>>),
+}
+
 pub struct AsyncEvaluator<'b> {
     context: &'b Context<'b>,
     sender: mpsc::SyncSender<AsyncPayload<'b>>,
-    ptx_daemon: JITDaemon,
+    compilation: Compilation<'b>,
     blocked_time: &'b atomic::AtomicUsize,
+    cancel: &'b device::CancellationToken,
 }
 
 impl<'b, 'c> device::AsyncEvaluator<'c> for AsyncEvaluator<'b>
@@ -190,66 +349,20 @@ where
         candidate: explorer::Candidate,
         callback: device::AsyncCallback<'c>,
     ) {
-        let thunk = {
-            let dev_fun = codegen::Function::build(&candidate.space);
-            debug!(
-                "compiling kernel with bound {} and actions {:?}",
-                candidate.bound, candidate.actions
-            );
-
-            // TODO(cc_perf): cuModuleLoadData is waiting the end of any running kernel
-            let kernel = Kernel::compile_remote(
-                &dev_fun,
-                &self.context.gpu(),
-                self.context.executor(),
-                &mut self.ptx_daemon,
-            );
+        if self.cancel.is_cancelled() {
+            return device::CancelledKernel::report(candidate, callback);
+        }
 
-            // In case kernel compilation fails, we try to catch the failure and keep going.
-            //
-            // Ideally, the compilation code would be structured so as to not use incoherent
-            // assertions and have a failure path using `Result` directly.  Unfortunately that will
-            // not be the case for the foreseeable future -- and being able to ignore the corner
-            // cases where some underlying assertion fails (the initial motivation for this is
-            // related to invalid sizes, which should get fixed independently) during a run is
-            // useful.  We still display an error message to let the user investigate.
-            //
-            // TODO: We might want some sort of counters to stop retrying if *all* compilations
-            // fail.
-
-            // We need `AssertUnwindSafe` because the `kernel` and `context` have references to
-            // structures with raw pointers or interior mutability and we could end up in an
-            // inconsistent state of those structures in case of a panic.
-            //
-            // Those are references to the CUDA module (which gets destroyed with the kernel) and
-            // the CUDA context.  The CUDA context is used through FFI APIs and has no knowledge of
-            // Rust panics, and so won't get into an inconsistent state due to panics.
-            let kernel = std::panic::AssertUnwindSafe(kernel);
-            let context = std::panic::AssertUnwindSafe(self.context);
-            match std::panic::catch_unwind(move || kernel.0.gen_thunk(&*context)) {
-                Ok(thunk) => Some(thunk),
-                Err(err) => {
-                    use std::borrow::Cow;
-
-                    let message = err
-                        .downcast::<String>()
-                        .map(|s| Cow::Owned(*s))
-                        .or_else(|err| {
-                            err.downcast::<&'static str>().map(|s| Cow::Borrowed(*s))
-                        })
-                        .unwrap_or_else(|_| Cow::Borrowed("<unknown error>"));
-
-                    error!(
-                        "Async evaluator panicked: {} (while compiling kernel {})",
-                        message, candidate
-                    );
-
-                    None
-                }
-            }
-        };
         let t0 = std::time::Instant::now();
-        unwrap!(self.sender.send((candidate, thunk, callback)));
+        match &mut self.compilation {
+            Compilation::Inline(compiler) => {
+                let thunk = compile_candidate(self.context, &candidate, compiler);
+                unwrap!(self.sender.send((candidate, thunk, callback)));
+            }
+            Compilation::Pool(compile_sender) => {
+                unwrap!(compile_sender.send((candidate, callback)));
+            }
+        }
         let t = std::time::Instant::now() - t0;
         let t_usize = t.as_secs() as usize * 1_000_000_000 + t.subsec_nanos() as usize;
         self.blocked_time
@@ -257,6 +370,89 @@ where
     }
 }
 
+/// Compiles a candidate into a thunk ready to be evaluated.  Returns `None` if the
+/// compilation fails.
+fn compile_candidate<'b>(
+    context: &'b Context<'b>,
+    candidate: &explorer::Candidate,
+    compiler: &mut ThreadCompiler,
+) -> Option<Thunk<'b>> {
+    let dev_fun = codegen::Function::build(&candidate.space);
+    debug!(
+        "compiling kernel with bound {} and actions {:?}",
+        candidate.bound, candidate.actions
+    );
+
+    // In case kernel compilation fails, we try to catch the failure and keep going.
+    //
+    // Ideally, the compilation code would be structured so as to not use incoherent
+    // assertions and have a failure path using `Result` directly.  Unfortunately that will
+    // not be the case for the foreseeable future -- and being able to ignore the corner
+    // cases where some underlying assertion fails (the initial motivation for this is
+    // related to invalid sizes, which should get fixed independently) during a run is
+    // useful.  We still display an error message to let the user investigate.
+    //
+    // TODO: We might want some sort of counters to stop retrying if *all* compilations
+    // fail.
+
+    // The compilation itself is done inside `catch_unwind` since NVRTC reports invalid
+    // kernels by panicking.
+    //
+    // We need `AssertUnwindSafe` because the `kernel` and `context` have references to
+    // structures with raw pointers or interior mutability and we could end up in an
+    // inconsistent state of those structures in case of a panic.
+    //
+    // Those are references to the CUDA module (which gets destroyed with the kernel) and
+    // the CUDA context.  The CUDA context is used through FFI APIs and has no knowledge of
+    // Rust panics, and so won't get into an inconsistent state due to panics.
+    let compile = std::panic::AssertUnwindSafe(|| {
+        let (gpu, executor) = (context.gpu(), context.executor());
+        // TODO(cc_perf): cuModuleLoadData is waiting the end of any running kernel
+        let kernel = match compiler {
+            ThreadCompiler::Daemon(ptx_daemon) => {
+                Kernel::compile_remote(&dev_fun, gpu, executor, ptx_daemon)
+            }
+            ThreadCompiler::Nvrtc(opt_level) => {
+                Kernel::compile_nvrtc(&dev_fun, gpu, executor, *opt_level)
+            }
+        };
+        kernel.map(|kernel| kernel.gen_thunk(context))
+    });
+    match std::panic::catch_unwind(compile) {
+        // Kernels that use too many resources for a single block to fit on a
+        // multiprocessor can't be launched, so there is no point in timing them.
+        Ok(Ok(ref thunk)) if thunk.occupancy() <= 0. => {
+            warn!(
+                "skipping kernel {}: no block fits on a multiprocessor",
+                candidate
+            );
+            None
+        }
+        Ok(Ok(thunk)) => Some(thunk),
+        // The candidate is evaluated as a failure, which excludes it from the search.
+        Ok(Err(err)) => {
+            warn!("skipping kernel {}: {}", candidate, err);
+            None
+        }
+        Err(err) => {
+            use std::borrow::Cow;
+
+            let message = err
+                .downcast::<String>()
+                .map(|s| Cow::Owned(*s))
+                .or_else(|err| err.downcast::<&'static str>().map(|s| Cow::Borrowed(*s)))
+                .unwrap_or_else(|_| Cow::Borrowed("<unknown error>"));
+
+            error!(
+                "Async evaluator panicked: {} (while compiling kernel {})",
+                message, candidate
+            );
+
+            None
+        }
+    }
+}
+
 // Helper to convert `Thunk` measurements (in cycles) into nanoseconds based on the GPU frequency
 struct RealtimeThunk<'a> {
     thunk: Thunk<'a>,
@@ -273,6 +469,18 @@ impl<'a> KernelEvaluator for RealtimeThunk<'a> {
     fn evaluate(&mut self) -> Option<f64> {
         Some(self.thunk.execute().ok()? as f64 / self.smx_clock)
     }
+
+    fn evaluate_energy(&mut self) -> Option<(f64, f64)> {
+        self.thunk.measure_energy()
+    }
+
+    fn set_scalar(
+        &mut self,
+        name: &str,
+        value: Box<dyn ScalarArgument>,
+    ) -> Result<(), ParamUpdateError> {
+        self.thunk.set_scalar(name, value)
+    }
 }
 
 // Helper struct to represent a kernel whose compilation failed.  Evaluation of such a kernel
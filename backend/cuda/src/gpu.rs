@@ -1,20 +1,50 @@
 //! Describes CUDA-enabled GPUs.
+use std::fmt;
 use std::io::Write;
 
 use fxhash::FxHashMap;
 use log::warn;
 use serde::{Deserialize, Serialize};
 
-use telamon::codegen::Function;
+use telamon::codegen::{CodeSizeError, Function};
+use telamon::device::vectorization::{OpKind, VectorRule};
 use telamon::device::{self, Device};
 use telamon::ir::{self, Operator, Type};
 use telamon::model::{self, HwPressure};
-use telamon::search_space::{DimKind, Domain, InstFlag, MemSpace, SearchSpace};
+use telamon::search_space::{
+    DimKind, Domain, InstFlag, MemSpace, Precision, SearchSpace,
+};
 
+use crate::api::NvrtcError;
 #[cfg(feature = "real_gpu")]
 use crate::characterize;
 use crate::mem_model::{self, MemInfo};
-use crate::{printer::CudaPrinter, Executor};
+use crate::{c_printer::CPrinter, printer::CudaPrinter, Executor};
+
+/// Minimal delay between two kernels launched back to back on the same stream, in
+/// nanoseconds.  This is a conservative value, actual overheads are usually higher.
+const KERNEL_LAUNCH_OVERHEAD: f64 = 2e3;
+
+/// Maximal vectorization factors of the instructions.  Half-precision arithmetic
+/// instructions process pairs of values with the `f16x2` instructions, while other
+/// instructions are vectorized by up to 4 values.
+const VECTORIZATION_RULES: &[VectorRule] = &[
+    half2_rule(OpKind::BinOp(ir::BinOp::Add)),
+    half2_rule(OpKind::BinOp(ir::BinOp::Sub)),
+    half2_rule(OpKind::Mul),
+    half2_rule(OpKind::Mad),
+    VectorRule::any([1, 4]),
+];
+
+/// Returns the vectorization rule of a half-precision arithmetic instruction.  See
+/// `is_half2_op`.
+const fn half2_rule(op: OpKind) -> VectorRule {
+    VectorRule {
+        op: Some(op),
+        t: Some(Type::F(16)),
+        max_factors: [1, 2],
+    }
+}
 
 // FIXME: fix performance model
 // - l1_lines constraint for stores ?
@@ -127,6 +157,30 @@ pub struct Gpu {
     pub max_block_per_smx: u32,
     /// The clock of an SMX, in GHz.
     pub smx_clock: f64,
+    /// Maximal number of blocks in a thread block cluster.  Blocks of a cluster are scheduled
+    /// concurrently on neighbouring SMXs.  This is 1 on GPUs without cluster support, i.e.
+    /// before compute capability 9.0.
+    #[serde(default = "one")]
+    pub max_cluster_size: u32,
+    /// `true` when blocks of a cluster can access the shared memory of each other.
+    #[serde(default)]
+    pub distributed_shared_mem: bool,
+    /// Number of partitions of the L2 cache.  Accesses from an SMX to data cached in a partition
+    /// it is not attached to are slower.
+    #[serde(default = "one")]
+    pub l2_partitions: u32,
+    /// `true` when kernel parameters can be marked `__grid_constant__` and accessed by address
+    /// without being copied to local memory.
+    #[serde(default)]
+    pub grid_constant_params: bool,
+    /// Maximal number of instructions in the generated code, once unrolled loops are
+    /// expanded.  Bigger kernels take forever to compile.
+    #[serde(default = "default_max_unrolled_insts")]
+    pub max_unrolled_insts: u32,
+    /// Maximal size in bytes of the generated code.  Printing candidates with a bigger
+    /// code is aborted, since they use a lot of memory and take forever to compile.
+    #[serde(default = "default_max_code_size")]
+    pub max_code_size: usize,
 
     /// Amount of processing power available on a single thread.
     pub thread_rates: InstDesc,
@@ -151,6 +205,14 @@ pub struct Gpu {
     pub mad_i64_inst: InstDesc,
     pub mad_wide_inst: InstDesc,
     pub div_f32_inst: InstDesc,
+    /// Approximate division, `div.approx.f32`.  Defaults to no pressure if the GPU was
+    /// characterized before approximate divisions were supported.
+    #[serde(default)]
+    pub div_approx_f32_inst: InstDesc,
+    /// Warp-level matrix multiply-accumulate on tensor cores, from `f16` to `f32`.  It is
+    /// not characterized yet and defaults to zero, in which case `mma_desc` estimates it.
+    #[serde(default)]
+    pub mma_f16_f32_inst: InstDesc,
     pub div_f64_inst: InstDesc,
     pub div_i32_inst: InstDesc,
     pub div_i64_inst: InstDesc,
@@ -159,6 +221,11 @@ pub struct Gpu {
     pub max_i32_inst: InstDesc,
     pub max_i64_inst: InstDesc,
     pub exp_f32_inst: InstDesc,
+    /// Approximate exponential, `ex2.approx.f32` applied to the rounded `x * log2(e)`.
+    /// Defaults to no pressure if the GPU was characterized before approximate
+    /// exponentials were supported.
+    #[serde(default)]
+    pub exp_approx_f32_inst: InstDesc,
     pub syncthread_inst: InstDesc,
 
     /// Overhead for entring the loop.
@@ -169,6 +236,66 @@ pub struct Gpu {
     pub loop_end_latency: f64,
 }
 
+/// Default value for fields that were added to the GPU description after it was first
+/// serialized, and for which 1 means the feature is unavailable.
+fn one() -> u32 {
+    1
+}
+
+/// Default value of `Gpu::max_unrolled_insts`.
+pub(crate) fn default_max_unrolled_insts() -> u32 {
+    1 << 16
+}
+
+/// Default value of `Gpu::max_code_size`.
+pub(crate) fn default_max_code_size() -> usize {
+    64 << 20
+}
+
+/// Error returned when the code of a kernel cannot be generated or compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrintError {
+    /// The kernel has more than `limit` instructions once unrolled loops are expanded.
+    TooManyInstructions { num_insts: u64, limit: u32 },
+    /// The code of the kernel exceeds `Gpu::max_code_size`.
+    CodeSize(CodeSizeError),
+    /// The kernel uses a construct the CUDA C printer does not support.
+    Unsupported(&'static str),
+    /// NVRTC failed to compile the CUDA C code of the kernel.
+    Nvrtc(NvrtcError),
+}
+
+impl From<CodeSizeError> for PrintError {
+    fn from(err: CodeSizeError) -> Self {
+        PrintError::CodeSize(err)
+    }
+}
+
+impl From<NvrtcError> for PrintError {
+    fn from(err: NvrtcError) -> Self {
+        PrintError::Nvrtc(err)
+    }
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrintError::TooManyInstructions { num_insts, limit } => write!(
+                fmt,
+                "the kernel has {} instructions once unrolled (limit: {})",
+                num_insts, limit
+            ),
+            PrintError::CodeSize(err) => write!(fmt, "{}", err),
+            PrintError::Unsupported(construct) => {
+                write!(fmt, "{} are not supported in CUDA C", construct)
+            }
+            PrintError::Nvrtc(err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for PrintError {}
+
 impl Gpu {
     /// Returns the GPU model corresponding to `name.
     #[cfg(feature = "real_gpu")]
@@ -203,6 +330,12 @@ impl Gpu {
             shared_bank_stride: 8,
             num_smx: 4,
             max_block_per_smx: 16,
+            max_cluster_size: 1,
+            distributed_shared_mem: false,
+            l2_partitions: 1,
+            grid_constant_params: false,
+            max_unrolled_insts: default_max_unrolled_insts(),
+            max_code_size: default_max_code_size(),
 
             smx_clock: -1.,
             load_l2_latency: -1.,
@@ -228,6 +361,8 @@ impl Gpu {
             mad_i64_inst: InstDesc::default(),
             mad_wide_inst: InstDesc::default(),
             div_f32_inst: InstDesc::default(),
+            div_approx_f32_inst: InstDesc::default(),
+            mma_f16_f32_inst: InstDesc::default(),
             div_f64_inst: InstDesc::default(),
             div_i32_inst: InstDesc::default(),
             div_i64_inst: InstDesc::default(),
@@ -236,6 +371,7 @@ impl Gpu {
             max_i32_inst: InstDesc::default(),
             max_i64_inst: InstDesc::default(),
             exp_f32_inst: InstDesc::default(),
+            exp_approx_f32_inst: InstDesc::default(),
             syncthread_inst: InstDesc::default(),
             loop_init_overhead: InstDesc::default(),
             loop_iter_overhead: InstDesc::default(),
@@ -243,9 +379,53 @@ impl Gpu {
     }
 
     /// Returns the PTX code for a Function.
-    pub fn print_ptx(&self, fun: &Function) -> String {
-        let mut printer = CudaPrinter::default();
-        printer.function(fun, self)
+    ///
+    /// Fails if the function has more instructions than `max_unrolled_insts` once unrolled
+    /// loops are expanded, which is checked before printing anything, or if the code
+    /// exceeds `max_code_size` bytes.
+    pub fn print_ptx(&self, fun: &Function) -> Result<String, PrintError> {
+        self.check_num_unrolled_insts(fun)?;
+        let mut printer = CudaPrinter::with_size_limit(self.max_code_size);
+        Ok(printer.function(fun, self)?)
+    }
+
+    /// Returns the CUDA C code for a Function, to be compiled with NVRTC.
+    ///
+    /// Fails under the same conditions as `print_ptx`, or if the function uses constructs
+    /// that cannot be expressed in CUDA C.
+    pub fn print_cuda(&self, fun: &Function) -> Result<String, PrintError> {
+        self.check_num_unrolled_insts(fun)?;
+        let mut printer = CPrinter::with_size_limit(self.max_code_size);
+        printer.kernel(fun)
+    }
+
+    /// Ensures `fun` has at most `max_unrolled_insts` instructions once unrolled loops
+    /// are expanded.
+    fn check_num_unrolled_insts(&self, fun: &Function) -> Result<(), PrintError> {
+        let num_insts = fun.cfg().num_unrolled_insts();
+        if num_insts > u64::from(self.max_unrolled_insts) {
+            Err(PrintError::TooManyInstructions {
+                num_insts,
+                limit: self.max_unrolled_insts,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the description of a warp-level matrix multiply-accumulate.  Until the
+    /// instruction is characterized, tensor cores are assumed to be as fast as `f16x2`
+    /// FMAs.  This underestimates their throughput, but it keeps the model from
+    /// considering them free.
+    fn mma_desc(&self, shape: ir::MmaShape) -> InstDesc {
+        if self.mma_f16_f32_inst.issue > 0.0 {
+            return self.mma_f16_f32_inst;
+        }
+        let fma_per_thread = f64::from(shape.m * shape.n * shape.k / self.wrap_size);
+        InstDesc {
+            alu: self.mad_f32_inst.alu * fma_per_thread / 2.0,
+            ..self.mad_f32_inst
+        }
     }
 
     /// Returns the description of a load instruction.
@@ -320,6 +500,8 @@ impl Gpu {
     ) -> HwPressure {
         use telamon::ir::Operator::*;
         let t = inst.t().map(|t| self.lower_type(t, space).unwrap_or(t));
+        // Half-precision instructions are modeled as their single-precision counterparts.
+        let t = t.map(|t| if t == Type::F(16) { Type::F(32) } else { t });
         match (inst.operator(), t) {
             (&BinOp(ir::BinOp::Add, ..), Some(Type::F(32)))
             | (&BinOp(ir::BinOp::Sub, ..), Some(Type::F(32))) => self.add_f32_inst.into(),
@@ -355,7 +537,16 @@ impl Gpu {
                     self.mad_wide_inst.into()
                 }
             }
-            (&BinOp(ir::BinOp::Div, ..), Some(Type::F(32))) => self.div_f32_inst.into(),
+            (&BinOp(ir::BinOp::Div, ..), Some(Type::F(32))) => {
+                // The approximate version is faster: use it as long as it is possible.
+                let precision = space.domain().get_precision(inst.id());
+                if precision.contains(Precision::APPROX) {
+                    self.div_approx_f32_inst.into()
+                } else {
+                    self.div_f32_inst.into()
+                }
+            }
+            (&Mma(.., shape), _) => self.mma_desc(shape).into(),
             (&BinOp(ir::BinOp::Div, ..), Some(Type::F(64))) => self.div_f64_inst.into(),
             (&BinOp(ir::BinOp::Div, ..), Some(Type::I(32))) => self.div_i32_inst.into(),
             (&BinOp(ir::BinOp::Div, ..), Some(Type::I(64))) => self.div_i64_inst.into(),
@@ -374,7 +565,13 @@ impl Gpu {
                 self.store_desc(&mem_info, flag).into()
             }
             (&UnaryOp(ir::UnaryOp::Exp(..), ..), Some(Type::F(32))) => {
-                self.exp_f32_inst.into()
+                // As for divisions, use the approximate version while it is possible.
+                let precision = space.domain().get_precision(inst.id());
+                if precision.contains(Precision::APPROX) {
+                    self.exp_approx_f32_inst.into()
+                } else {
+                    self.exp_f32_inst.into()
+                }
             }
             // TODO(model): Instruction description for mov and cast.
             (&UnaryOp(..), _) => HwPressure::zero(self),
@@ -412,14 +609,6 @@ impl Gpu {
         .into()
     }
 
-    /// Computes the ratio `num_wraps*wrap_size/num_threads`. This ratio may be `>1`
-    /// because the hardware creates additionnal threads to fill the wraps.
-    fn waste_ratio(&self, lcm_threads_per_block: u64) -> f64 {
-        let wrap_size = u64::from(self.wrap_size);
-        let n_wraps = (lcm_threads_per_block + wrap_size - 1) / wrap_size;
-        (n_wraps * wrap_size) as f64 / lcm_threads_per_block as f64
-    }
-
     pub fn l1_cache_line(&self) -> u32 {
         self.l1_cache_sectors_per_line * self.l1_cache_sector
     }
@@ -434,6 +623,8 @@ impl device::Device for Gpu {
     fn check_type(&self, t: Type) -> Result<(), ir::TypeError> {
         match t {
             Type::I(i) | Type::F(i) if i == 32 || i == 64 => Ok(()),
+            // Half-precision arithmetic requires compute capability 5.3.
+            Type::F(16) if (self.sm_major, self.sm_minor) >= (5, 3) => Ok(()),
             Type::PtrTo(_) => Ok(()),
             t => Err(ir::TypeError::InvalidType { t }),
         }
@@ -455,6 +646,10 @@ impl device::Device for Gpu {
         512
     }
 
+    fn max_unrolled_insts(&self) -> u32 {
+        self.max_unrolled_insts
+    }
+
     fn can_vectorize(&self, dim: &ir::Dimension, op: &ir::Operator) -> bool {
         match *op {
             Operator::TmpLd(..) | Operator::TmpSt(..) => true,
@@ -474,14 +669,24 @@ impl device::Device for Gpu {
                     .map(|sizes| !sizes.contains(&3))
                     .unwrap_or(false)
             }
+            // The accumulator fragments of tensor core instructions span at most 4
+            // registers.
+            Operator::Mma(..) => dim
+                .possible_sizes()
+                .map(|sizes| sizes.iter().all(|&size| size <= 4))
+                .unwrap_or(false),
+            ref op if is_half2_op(op) => dim
+                .possible_sizes()
+                .map(|sizes| sizes.iter().all(|&size| size <= 2))
+                .unwrap_or(false),
             _ => false,
         }
     }
 
-    fn max_vectorization(&self, _: &ir::Operator) -> [u32; 2] {
-        // No need to discriminate on the operator since this is already handled by
-        // `can_vectorize`.
-        [1, 4]
+    fn vectorization_rules(&self) -> &[VectorRule] {
+        // No need to discriminate further on the operator since this is already handled
+        // by `can_vectorize`.
+        VECTORIZATION_RULES
     }
 
     fn has_vector_registers(&self) -> bool {
@@ -521,6 +726,23 @@ impl device::Device for Gpu {
         flags
     }
 
+    fn supports_approx(&self, op: &ir::Operator) -> bool {
+        match op {
+            Operator::BinOp(ir::BinOp::Div, lhs, ..) => lhs.t() == Type::F(32),
+            Operator::UnaryOp(ir::UnaryOp::Exp(..), arg) => arg.t() == Type::F(32),
+            _ => false,
+        }
+    }
+
+    fn supports_mma(&self, shape: ir::MmaShape, t: Type, acc_t: Type) -> bool {
+        // Only the shape introduced with compute capability 7.5 is supported.
+        let m16n8k8 = ir::MmaShape { m: 16, n: 8, k: 8 };
+        (self.sm_major, self.sm_minor) >= (7, 5)
+            && shape == m16n8k8
+            && t == Type::F(16)
+            && acc_t == Type::F(32)
+    }
+
     fn name(&self) -> &str {
         &self.name
     }
@@ -620,10 +842,10 @@ impl device::Device for Gpu {
         predication_factor: model::size::Range,
         pressure: &mut HwPressure,
     ) {
-        let active_ratio = self.waste_ratio(max_active_threads.lcm);
+        let active_ratio = model::simt_waste_ratio(self, max_active_threads.lcm);
         pressure.multiply(&InstDesc::wasted_ratio(active_ratio).into());
         // Account for inactive wraps.
-        let total_ratio = self.waste_ratio(max_threads.lcm);
+        let total_ratio = model::simt_waste_ratio(self, max_threads.lcm);
         // TODO(model): might be able to do better since `predication_factor` value is
         // linked to `max_threads` value.
         let num_skipped = total_ratio * predication_factor.min as f64 - active_ratio;
@@ -631,6 +853,30 @@ impl device::Device for Gpu {
             pressure.repeat_and_add_bottlenecks(num_skipped, &self.skipped_pressure());
         }
     }
+
+    fn simt_width(&self) -> u32 {
+        self.wrap_size
+    }
+
+    fn kernel_launch_overhead(&self) -> f64 {
+        KERNEL_LAUNCH_OVERHEAD
+    }
+
+    fn global_mem_bandwidth(&self) -> f64 {
+        self.gpu_rates.ram_bw
+    }
+}
+
+/// Indicates if `op` is a half-precision arithmetic instruction, that processes two values
+/// at once when vectorized.
+fn is_half2_op(op: &ir::Operator) -> bool {
+    match op {
+        Operator::BinOp(ir::BinOp::Add, lhs, ..)
+        | Operator::BinOp(ir::BinOp::Sub, lhs, ..)
+        | Operator::Mul(lhs, ..)
+        | Operator::Mad(lhs, ..) => lhs.t() == Type::F(16),
+        _ => false,
+    }
 }
 
 /// Asigns min(lhs, rhs) to lhs.
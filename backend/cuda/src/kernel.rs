@@ -1,11 +1,17 @@
 //! IR instances compiled into CUDA kernels.
 #[cfg(feature = "real_gpu")]
 use crate::PerfCounterSet;
-use crate::{api, Context, Gpu, JITDaemon};
+use crate::{api, Context, Gpu, JITDaemon, PrintError};
 use itertools::Itertools;
 use log::warn;
-use telamon::codegen::{self, ParamVal};
-use telamon::device::{self, Context as ContextTrait};
+use telamon::codegen::{self, ArtifactError, ArtifactParam, ParamVal};
+use telamon::device::{self, Context as ContextTrait, ParamUpdateError};
+use telamon::ir;
+use utils::unwrap;
+
+/// Minimal duration of an energy measurement, in nanoseconds.  The energy counter of the
+/// device is only updated every few milliseconds.
+const MIN_ENERGY_MEASUREMENT_DURATION: f64 = 1e8;
 
 /// An IR instance compiled into a CUDA kernel.
 pub struct Kernel<'a, 'b> {
@@ -13,25 +19,29 @@ pub struct Kernel<'a, 'b> {
     module: api::Module<'a>,
     function: &'b codegen::Function<'b>,
     expected_blocks_per_smx: u32,
-    ptx: String,
+    thread_per_smx: u32,
+    /// Code the kernel was compiled from: PTX, or CUDA C when compiled with NVRTC.
+    source: String,
 }
 
 impl<'a, 'b> Kernel<'a, 'b> {
-    /// Compiles a device function.
+    /// Compiles a device function.  Fails if the code of the function cannot be
+    /// generated.
     pub fn compile(
         fun: &'b codegen::Function<'b>,
         gpu: &Gpu,
         executor: &'a api::Executor,
         opt_level: usize,
-    ) -> Self {
-        let ptx = gpu.print_ptx(fun);
-        Kernel {
+    ) -> Result<Self, PrintError> {
+        let ptx = gpu.print_ptx(fun)?;
+        Ok(Kernel {
             module: executor.compile_ptx(&ptx, opt_level),
             executor,
-            ptx,
+            source: ptx,
             function: fun,
             expected_blocks_per_smx: gpu.blocks_per_smx(fun.space()),
-        }
+            thread_per_smx: gpu.thread_per_smx,
+        })
     }
 
     /// Compiles a device function, using a separate process.
@@ -40,16 +50,37 @@ impl<'a, 'b> Kernel<'a, 'b> {
         gpu: &Gpu,
         executor: &'a api::Executor,
         jit_daemon: &mut JITDaemon,
-    ) -> Self {
-        let ptx = gpu.print_ptx(function);
+    ) -> Result<Self, PrintError> {
+        let ptx = gpu.print_ptx(function)?;
         let module = executor.compile_remote(jit_daemon, &ptx);
-        Kernel {
+        Ok(Kernel {
             executor,
-            ptx,
+            source: ptx,
             module,
             function,
             expected_blocks_per_smx: gpu.blocks_per_smx(function.space()),
-        }
+            thread_per_smx: gpu.thread_per_smx,
+        })
+    }
+
+    /// Compiles a device function from CUDA C, using NVRTC in the current process.
+    pub fn compile_nvrtc(
+        function: &'b codegen::Function<'b>,
+        gpu: &Gpu,
+        executor: &'a api::Executor,
+        opt_level: usize,
+    ) -> Result<Self, PrintError> {
+        let code = gpu.print_cuda(function)?;
+        let module =
+            executor.compile_cuda(&code, (gpu.sm_major, gpu.sm_minor), opt_level)?;
+        Ok(Kernel {
+            executor,
+            source: code,
+            module,
+            function,
+            expected_blocks_per_smx: gpu.blocks_per_smx(function.space()),
+            thread_per_smx: gpu.thread_per_smx,
+        })
     }
 
     /// Runs a kernel and returns the number of cycles it takes to execute in cycles.
@@ -81,7 +112,7 @@ impl<'a, 'b> Kernel<'a, 'b> {
             name: self.function.name().to_string(),
             module: self.module,
             executor: self.executor,
-            ptx: self.ptx,
+            source: self.source,
             args,
         }
     }
@@ -90,11 +121,19 @@ impl<'a, 'b> Kernel<'a, 'b> {
         let block_sizes = get_sizes(self.function.block_dims(), args);
         let thread_sizes = get_sizes(self.function.thread_dims().iter().rev(), args);
         let mut tmp_arrays = vec![];
+        let updatable = self.function.updatable_params().collect_vec();
+        let mut updatable_params = vec![];
         let params = self
             .function
             .device_code_args()
-            .map(|x| match *x {
-                ParamVal::External(ref p, _) => ThunkArg::ArgRef(args.get_param(&p.name)),
+            .enumerate()
+            .map(|(index, x)| match *x {
+                ParamVal::External(ref p, _) => {
+                    if updatable.contains(&&**p) {
+                        updatable_params.push((ir::Parameter::clone(p), index));
+                    }
+                    ThunkArg::ArgRef(args.get_param(&p.name))
+                }
                 ParamVal::Size(ref s) => {
                     ThunkArg::Size(Box::new(args.eval_size(s) as i32))
                 }
@@ -109,7 +148,9 @@ impl<'a, 'b> Kernel<'a, 'b> {
             threads: thread_sizes,
             tmp_arrays,
             args: params,
-            expected_blocks_per_smx: self.expected_blocks_per_smx,
+            updatable_params,
+            expected_blocks_per_smx: Some(self.expected_blocks_per_smx),
+            thread_per_smx: self.thread_per_smx,
         }
     }
 }
@@ -131,28 +172,105 @@ where
     sizes
 }
 
+/// Pads the sizes of up to three dimensions with ones.
+fn pad_sizes<'a>(dims: impl IntoIterator<Item = &'a u32>) -> [u32; 3] {
+    let mut sizes = [1, 1, 1];
+    for (i, &size) in dims.into_iter().enumerate() {
+        assert!(i < 3);
+        sizes[i] = size;
+    }
+    sizes
+}
+
 /// A kernel ready to execute.
 pub struct Thunk<'a> {
     name: String,
-    ptx: String,
+    source: String,
     module: api::Module<'a>,
     executor: &'a api::Executor,
     args: ThunkArgs<'a>,
 }
 
 impl<'a> Thunk<'a> {
+    /// Loads a kernel from an artifact, binding its parameters to the arguments of
+    /// `context`.
+    pub fn from_artifact(
+        artifact: &codegen::Artifact,
+        context: &'a Context,
+        opt_level: usize,
+    ) -> Result<Self, ArtifactError> {
+        artifact.check_device(context)?;
+        let launch_config = &artifact.launch_config;
+        let mut tmp_arrays = vec![];
+        let args = artifact
+            .params
+            .iter()
+            .map(|param| match *param {
+                ArtifactParam::External { ref name, .. } => context
+                    .find_param(name)
+                    .map(ThunkArg::ArgRef)
+                    .ok_or_else(|| ArtifactError::UnknownParam(name.clone())),
+                ArtifactParam::Size(size) => Ok(ThunkArg::Size(Box::new(size as i32))),
+                ArtifactParam::TmpArray(size) => {
+                    tmp_arrays.push(size as usize);
+                    Ok(ThunkArg::TmpArray(tmp_arrays.len() - 1))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let executor = context.executor();
+        Ok(Thunk {
+            name: artifact.entry_point.clone(),
+            source: artifact.source.clone(),
+            module: executor.compile_ptx(&artifact.source, opt_level),
+            executor,
+            args: ThunkArgs {
+                blocks: pad_sizes(&launch_config.block_dims),
+                // Thread dimensions are given to CUDA from the innermost to the outermost.
+                threads: pad_sizes(launch_config.thread_dims.iter().rev()),
+                tmp_arrays,
+                args,
+                updatable_params: vec![],
+                expected_blocks_per_smx: None,
+                thread_per_smx: context.gpu().thread_per_smx,
+            },
+        })
+    }
+
     /// Executes the kernel and returns the number of cycles it took to execute.
     pub fn execute(&self) -> Result<u64, ()> {
         let cuda_kernel = self.module.kernel(&self.name);
         self.args.execute(&cuda_kernel, self.executor)
     }
+
+    /// Returns the execution time of the kernel in nanoseconds, and the energy it consumes
+    /// in nanojoules.  Returns `None` if the device does not support energy measurement.
+    pub fn measure_energy(&self) -> Option<(f64, f64)> {
+        let cuda_kernel = self.module.kernel(&self.name);
+        self.args.measure_energy(&cuda_kernel, self.executor)
+    }
+
+    /// Returns the theoretical occupancy of the kernel, as reported by the CUDA driver:
+    /// the fraction of the threads of a multiprocessor that can be active at the same time.
+    pub fn occupancy(&self) -> f64 {
+        let cuda_kernel = self.module.kernel(&self.name);
+        self.args.occupancy(&cuda_kernel)
+    }
+
+    /// Changes the value of a scalar parameter for the next executions.
+    pub fn set_scalar(
+        &mut self,
+        name: &str,
+        value: Box<dyn device::ScalarArgument>,
+    ) -> Result<(), ParamUpdateError> {
+        self.args.set_scalar(name, value)
+    }
 }
 
 impl<'a> std::fmt::Debug for Thunk<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         writeln!(f, "kernel: {}", self.name)?;
         self.args.fmt(f)?;
-        write!(f, "{}", self.ptx)
+        write!(f, "{}", self.source)
     }
 }
 
@@ -162,7 +280,11 @@ struct ThunkArgs<'a> {
     threads: [u32; 3],
     tmp_arrays: Vec<usize>,
     args: Vec<ThunkArg<'a>>,
-    expected_blocks_per_smx: u32,
+    /// The scalar parameters that can be changed, with their position in `args`.
+    updatable_params: Vec<(ir::Parameter, usize)>,
+    /// The number of blocks per SMX predicted by the performance model, if known.
+    expected_blocks_per_smx: Option<u32>,
+    thread_per_smx: u32,
 }
 
 impl<'a> ThunkArgs<'a> {
@@ -183,7 +305,7 @@ impl<'a> ThunkArgs<'a> {
             .iter()
             .map(|x| match *x {
                 ThunkArg::ArgRef(arg) => arg,
-                ThunkArg::Size(ref arg) => arg,
+                ThunkArg::Size(ref arg) | ThunkArg::Scalar(ref arg) => arg,
                 ThunkArg::TmpArray(id) => &tmp_arrays[id],
             })
             .collect_vec();
@@ -209,7 +331,7 @@ impl<'a> ThunkArgs<'a> {
             .iter()
             .map(|x| match *x {
                 ThunkArg::ArgRef(arg) => arg,
-                ThunkArg::Size(ref arg) => arg,
+                ThunkArg::Size(ref arg) | ThunkArg::Scalar(ref arg) => arg,
                 ThunkArg::TmpArray(id) => &tmp_arrays[id],
             })
             .collect_vec();
@@ -234,7 +356,7 @@ impl<'a> ThunkArgs<'a> {
             .iter()
             .map(|x| match *x {
                 ThunkArg::ArgRef(arg) => arg,
-                ThunkArg::Size(ref arg) => arg,
+                ThunkArg::Size(ref arg) | ThunkArg::Scalar(ref arg) => arg,
                 ThunkArg::TmpArray(id) => &tmp_arrays[id],
             })
             .collect_vec();
@@ -248,13 +370,65 @@ impl<'a> ThunkArgs<'a> {
             .collect()
     }
 
+    /// Measures the execution time and the energy consumption of the kernel.
+    fn measure_energy(
+        &self,
+        cuda_kernel: &api::Kernel,
+        executor: &api::Executor,
+    ) -> Option<(f64, f64)> {
+        let tmp_arrays = self
+            .tmp_arrays
+            .iter()
+            .map(|&size| executor.allocate_array::<i8>(size))
+            .collect_vec();
+        let params = self
+            .args
+            .iter()
+            .map(|x| match *x {
+                ThunkArg::ArgRef(arg) => arg,
+                ThunkArg::Size(ref arg) | ThunkArg::Scalar(ref arg) => arg,
+                ThunkArg::TmpArray(id) => &tmp_arrays[id],
+            })
+            .collect_vec();
+        cuda_kernel.measure_energy(
+            &self.blocks,
+            &self.threads,
+            &params,
+            MIN_ENERGY_MEASUREMENT_DURATION,
+        )
+    }
+
+    /// Replaces the value of an updatable scalar parameter.
+    fn set_scalar(
+        &mut self,
+        name: &str,
+        value: Box<dyn device::ScalarArgument>,
+    ) -> Result<(), ParamUpdateError> {
+        let params = self.updatable_params.iter().map(|(param, _)| param);
+        device::check_param_update(params, name, &*value)?;
+        let (_, index) = unwrap!(self
+            .updatable_params
+            .iter()
+            .find(|(param, _)| param.name == name));
+        self.args[*index] = ThunkArg::Scalar(value);
+        Ok(())
+    }
+
+    /// Computes the theoretical occupancy of the kernel.
+    fn occupancy(&self, cuda_kernel: &api::Kernel) -> f64 {
+        let block_size = self.threads.iter().product::<u32>();
+        let active_threads = cuda_kernel.blocks_per_smx(&self.threads) * block_size;
+        f64::from(active_threads) / f64::from(self.thread_per_smx)
+    }
+
     fn check_blocks_per_smx(&self, cuda_kernel: &api::Kernel) {
         let blocks_per_smx = cuda_kernel.blocks_per_smx(&self.threads);
-        if blocks_per_smx != self.expected_blocks_per_smx {
-            warn!(
+        match self.expected_blocks_per_smx {
+            Some(expected) if expected != blocks_per_smx => warn!(
                 "mismatch in the number of blocks per SMX: expected {}, got {}",
-                self.expected_blocks_per_smx, blocks_per_smx
-            );
+                expected, blocks_per_smx
+            ),
+            _ => (),
         }
     }
 }
@@ -277,6 +451,8 @@ impl<'a> std::fmt::Debug for ThunkArgs<'a> {
 enum ThunkArg<'a> {
     ArgRef(&'a dyn api::Argument),
     Size(Box<dyn device::ScalarArgument>),
+    /// A scalar parameter whose value was changed after the kernel was compiled.
+    Scalar(Box<dyn device::ScalarArgument>),
     TmpArray(usize),
 }
 
@@ -285,6 +461,7 @@ impl<'a> std::fmt::Debug for ThunkArg<'a> {
         match self {
             ThunkArg::ArgRef(_) => write!(f, "context argument"),
             ThunkArg::Size(size) => write!(f, "size = {}", size),
+            ThunkArg::Scalar(value) => write!(f, "scalar = {}", value),
             ThunkArg::TmpArray(size) => write!(f, "temporary array of size {}", size),
         }
     }
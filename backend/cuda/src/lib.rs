@@ -11,6 +11,7 @@ mod api {
     pub use self::error::*;
     pub use self::fake::*;
 }
+mod c_printer;
 mod context;
 mod gpu;
 mod kernel;
@@ -25,8 +26,8 @@ pub mod characterize;
 pub use self::api::{Array, Executor, JITDaemon};
 #[cfg(feature = "real_gpu")]
 pub use self::api::{DeviceAttribute, PerfCounter, PerfCounterSet};
-pub use self::context::Context;
-pub use self::gpu::{Gpu, InstDesc};
+pub use self::context::{Compiler, Context};
+pub use self::gpu::{Gpu, InstDesc, PrintError};
 pub use self::kernel::Kernel;
 
 use fxhash::FxHashMap;
@@ -81,8 +81,8 @@ fn unknown_info(
         info.access_shared = true;
     }
     if is_shared_access.maybe_false() {
-        info.l1_coalescing = 1.0 / f64::from(gpu.wrap_size);
-        info.l2_coalescing = 1.0 / f64::from(gpu.wrap_size);
+        info.l1_coalescing = 1.0 / f64::from(gpu.simt_width());
+        info.l2_coalescing = 1.0 / f64::from(gpu.simt_width());
         info.memory_transactions = 1.0;
         info.access_global = true;
     }
@@ -220,6 +220,9 @@ fn tensor_thread_dims(
     let mut out = Vec::new();
     for (id, is_active_thread) in dims {
         let size = sizes[&id];
+        // Strides are stored as absolute values.  Reversing a dimension changes the order in
+        // which a warp accesses its addresses, but not the set of addresses: the number of
+        // transactions and of bank conflicts is the same as with the opposite stride.
         let stride_size = tensor_dims.get(&id);
         let stride = stride_size
             .map(|s| size::bounds(s, space, ctx))
@@ -324,7 +327,7 @@ fn sort_thread_dims(
         }
         out.push(d);
         heap.extend(dim_groups.remove(&out.len()));
-        if total_size >= u64::from(gpu.wrap_size) {
+        if total_size >= u64::from(gpu.simt_width()) {
             break;
         }
     }
@@ -340,7 +343,7 @@ fn cmp_thread_dims(
     gpu: &Gpu,
 ) -> std::cmp::Ordering {
     let (lhs_val, rhs_val) = if use_gcd {
-        let replay_distance = u64::from(gpu.wrap_size * gpu.shared_bank_stride);
+        let replay_distance = u64::from(gpu.simt_width() * gpu.shared_bank_stride);
         let lhs_val = lhs.stride_factors.gcd.gcd(&replay_distance);
         let rhs_val = rhs.stride_factors.gcd.gcd(&replay_distance);
         (lhs_val, rhs_val)
@@ -359,10 +362,10 @@ fn wrap_access_offsets(
     use_gcd: bool,
     gpu: &Gpu,
 ) -> Vec<u64> {
-    let mut offsets = Vec::with_capacity(gpu.wrap_size as usize);
+    let mut offsets = Vec::with_capacity(gpu.simt_width() as usize);
     offsets.push(0);
     let mut indexes = vec![0; thread_dims.len()];
-    while offsets.len() < gpu.wrap_size as usize {
+    while offsets.len() < gpu.simt_width() as usize {
         let mut incr = true;
         for (i, dim) in thread_dims.iter().enumerate() {
             if incr {
@@ -460,7 +463,7 @@ fn offsets_shared_memory_transactions(offsets: &[u64], gpu: &Gpu) -> u32 {
     let mut hits: FxHashSet<_> = std::iter::once(0).collect();
     for &offset in offsets {
         let num_bank_stride = offset / u64::from(gpu.shared_bank_stride);
-        let (hit_id, rem) = num_bank_stride.div_rem(&(u64::from(gpu.wrap_size)));
+        let (hit_id, rem) = num_bank_stride.div_rem(&(u64::from(gpu.simt_width())));
         if rem == 0 {
             hits.insert(hit_id);
         }
@@ -80,12 +80,13 @@ impl PTXDisplay for llir::Operand<'_> {
                 use num::ToPrimitive;
                 assert!(bits <= 64);
 
-                write!(
-                    fmt,
-                    "0D{:016X}",
-                    (val.numer().to_f64().unwrap() / val.denom().to_f64().unwrap())
-                        .to_bits()
-                )
+                let val = val.numer().to_f64().unwrap() / val.denom().to_f64().unwrap();
+                if bits == 16 {
+                    // PTX has no half-precision literals: they are given as their bits.
+                    write!(fmt, "0x{:04X}", half::f16::from_f64(val).to_bits())
+                } else {
+                    write!(fmt, "0D{:016X}", val.to_bits())
+                }
             }
         }
     }
@@ -106,22 +107,32 @@ impl<T: PTXDisplay> PTXDisplay for llir::ScalarOrVector<T> {
 
 #[derive(Default)]
 pub(crate) struct CudaPrinter {
-    buffer: String,
+    buffer: CodeBuffer,
 }
 
 impl CudaPrinter {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        CudaPrinter {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+        }
+    }
+
     /// Prints the variables declared by the `NameGenerator`.
     fn var_decls(&mut self, namegen: &NameGenerator) -> String {
         let print_decl = |(&t, n)| {
             let prefix = NameGenerator::gen_prefix(t);
             format!(".reg.{} %{}<{}>;", t.ptx(), prefix, n)
         };
-        namegen
-            .num_var
-            .iter()
-            .map(print_decl)
-            .collect_vec()
-            .join("\n  ")
+        let mut decls = namegen.num_var.iter().map(print_decl).collect_vec();
+        if namegen.num_var.contains_key(&Type::F(16)) {
+            decls.push(format!(".reg.b32 %{}<4>;", HALF2_PREFIX));
+            decls.push(format!(".reg.f32 %{}<3>;", HALF_AS_F32_PREFIX));
+        }
+        if namegen.num_var.contains_key(&Type::F(32)) {
+            decls.push(format!(".reg.f32 %{}<2>;", EXP_PREFIX));
+        }
+        decls.join("\n  ")
     }
 
     /// Declares block and thread indexes.
@@ -160,6 +171,7 @@ impl CudaPrinter {
     fn host_type(t: Type) -> &'static str {
         match t {
             Type::PtrTo(..) => "CUdeviceptr",
+            Type::F(16) => "uint16_t",
             Type::F(32) => "float",
             Type::F(64) => "double",
             Type::I(8) => "int8_t",
@@ -198,13 +210,17 @@ impl CudaPrinter {
     fn param_decl(&mut self, param: &ParamVal) -> String {
         format!(
             ".param .{t} {name}",
-            t = param.t().ptx(),
+            t = DataType(param.t()).ptx(),
             name = param.key().ident(),
         )
     }
 
-    /// Prints a `Function`.
-    pub fn function(&mut self, function: &Function, gpu: &Gpu) -> String {
+    /// Prints a `Function`.  Fails if the code exceeds the size limit of the printer.
+    pub fn function(
+        &mut self,
+        function: &Function,
+        gpu: &Gpu,
+    ) -> Result<String, CodeSizeError> {
         let mut namegen = NameGenerator::default();
         let interner = Interner::default();
         let name_map = &mut NameMap::new(&interner, function, &mut namegen);
@@ -217,7 +233,7 @@ impl CudaPrinter {
             unwrap!(writeln!(
                 self.buffer,
                 "  ld.param.{t} {var_name}, [{name}];",
-                t = val.t().ptx(),
+                t = DataType(val.t()).ptx(),
                 var_name = name_map.name_param_val(val.key()).ptx(),
                 name = val.key().ident(),
             ));
@@ -270,9 +286,10 @@ impl CudaPrinter {
         let mut body = String::new();
         body.push_str(&var_decls);
         body.push_str(&"\n");
-        body.push_str(&self.buffer);
-        format!(
+        body.push_str(&self.buffer.take()?);
+        Ok(format!(
             include_str!("template/device.ptx"),
+            ptx_version = ptx_version(gpu),
             sm_major = gpu.sm_major,
             sm_minor = gpu.sm_minor,
             addr_size = gpu.addr_size,
@@ -280,7 +297,7 @@ impl CudaPrinter {
             params = param_decls,
             num_thread = function.num_threads(),
             body = body
-        )
+        ))
     }
 
     pub fn host_function(&mut self, fun: &Function, gpu: &Gpu, out: &mut dyn Write) {
@@ -333,7 +350,7 @@ impl CudaPrinter {
             .map(|p| format!("{} {}", Self::host_type(p.t), p.name))
             .collect_vec()
             .join(", ");
-        let ptx_code = self.function(fun, gpu);
+        let ptx_code = unwrap!(self.function(fun, gpu));
         let res = write!(
             out,
             include_str!("template/host.c"),
@@ -364,6 +381,10 @@ impl InstPrinter for CudaPrinter {
     fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
         writeln!(self.buffer, "{};", inst.ptx()).unwrap();
     }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
 }
 
 impl PTXDisplay for llir::UnOp {
@@ -371,7 +392,7 @@ impl PTXDisplay for llir::UnOp {
         use llir::UnOp;
 
         match self {
-            UnOp::Move { t } => write!(fmt, "mov.{}", t.ptx()),
+            UnOp::Move { t } => write!(fmt, "mov.{}", DataType(*t).ptx()),
             UnOp::Cast { src_t, dst_t } => {
                 // Integer rounding is required for float-to-integer conversions, and for
                 // same-size float-to-float conversions where the value is rounded to an
@@ -402,6 +423,7 @@ impl PTXDisplay for llir::UnOp {
                 write!(fmt, "cvt{}.{}.{}", rnd, dst_t.ptx(), src_t.ptx())
             }
             UnOp::Exp { .. } => panic!("{}: non-atomic PTX instruction", self),
+            UnOp::Sqrt { t } => write!(fmt, "sqrt.rn.{}", t.ptx()),
         }
     }
 }
@@ -441,6 +463,13 @@ impl PTXDisplay for llir::TernOp {
         match self {
             IMad { arg_t, spec } => write!(fmt, "mad.{}.{}", spec.ptx(), arg_t.ptx()),
             FFma { t, rounding } => write!(fmt, "fma.{}.{}", rounding.ptx(), t.ptx()),
+            Mma { shape, t, acc_t } => write!(
+                fmt,
+                "mma.sync.aligned.{}.row.col.{acc}.{t}.{t}.{acc}",
+                shape,
+                acc = acc_t.ptx(),
+                t = t.ptx()
+            ),
         }
     }
 }
@@ -465,6 +494,7 @@ impl PTXDisplay for llir::FpRounding {
             llir::FpRounding::Zero => "rz",
             llir::FpRounding::NegativeInfinite => "rm",
             llir::FpRounding::PositiveInfinite => "rp",
+            llir::FpRounding::Approx => "approx",
         })
     }
 }
@@ -542,7 +572,7 @@ impl PTXDisplay for llir::LoadSpec {
         if self.vector_factor().get() > 1 {
             write!(fmt, ".v{}", self.vector_factor())?;
         }
-        write!(fmt, ".{}", self.t().ptx())
+        write!(fmt, ".{}", DataType(self.t()).ptx())
     }
 }
 
@@ -552,23 +582,271 @@ impl PTXDisplay for llir::StoreSpec {
         if self.vector_factor().get() > 1 {
             write!(fmt, ".v{}", self.vector_factor())?;
         }
-        write!(fmt, ".{}", self.t().ptx())
+        write!(fmt, ".{}", DataType(self.t()).ptx())
     }
 }
 
 impl PTXDisplay for llir::PredicatedInstruction<'_> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
+        let predicate = self
+            .predicate
+            .into_iter()
+            .format_with("", |predicate, f| f(&format_args!("@{} ", predicate.ptx())))
+            .to_string();
+        match &self.instruction {
+            llir::Instruction::Binary(op, llir::ScalarOrVector::Vector(d), [a, b])
+                if is_half2(d) =>
+            {
+                fmt_half2(fmt, &predicate, op.ptx(), d, &[a, b])
+            }
+            llir::Instruction::Ternary(
+                op,
+                llir::ScalarOrVector::Vector(d),
+                [a, b, c],
+            ) if is_half2(d) => fmt_half2(fmt, &predicate, op.ptx(), d, &[a, b, c]),
+            llir::Instruction::Ternary(
+                op @ llir::TernOp::Mma { shape, .. },
+                d,
+                [a, b, c],
+            ) => fmt_mma(fmt, &predicate, op, *shape, d, [a, b, c]),
+            instruction if needs_f32(instruction) => {
+                fmt_through_f32(fmt, &predicate, instruction)
+            }
+            instruction @ llir::Instruction::Unary(
+                llir::UnOp::Exp {
+                    t: Type::F(32),
+                    approx: false,
+                },
+                ..,
+            ) => fmt_accurate_exp(fmt, &predicate, instruction),
+            instruction => write!(fmt, "{}{}", predicate, instruction.ptx()),
+        }
+    }
+}
+
+/// Returns the version of PTX to target. Tensor core instructions on compute capability
+/// 7.5 require PTX 6.5, other instructions only PTX 6.1.
+fn ptx_version(gpu: &Gpu) -> &'static str {
+    if (gpu.sm_major, gpu.sm_minor) >= (7, 5) {
+        "6.5"
+    } else {
+        "6.1"
+    }
+}
+
+/// Prefix of the 32-bit registers holding pairs of half-precision values.
+const HALF2_PREFIX: &str = "hh";
+
+/// Indicates if `d` holds the result of an arithmetic instruction on pairs of
+/// half-precision values, that maps to a single `f16x2` instruction.
+fn is_half2(d: &[llir::Register<'_>]) -> bool {
+    d.len() == 2 && d[0].t() == Type::F(16)
+}
+
+/// Prints an arithmetic instruction `op` on pairs of half-precision values as a single
+/// `f16x2` instruction. The operands are first packed into the 32-bit registers declared
+/// by `CudaPrinter::var_decls`, and the result is then unpacked into `d`. Operands that
+/// are not vectorized are broadcast to both halves.
+fn fmt_half2(
+    fmt: &mut fmt::Formatter<'_>,
+    predicate: &str,
+    op: impl fmt::Display,
+    d: &[llir::Register<'_>],
+    args: &[&llir::OpVec<'_>],
+) -> fmt::Result {
+    for (idx, arg) in args.iter().enumerate() {
+        let (lo, hi) = match arg {
+            llir::ScalarOrVector::Scalar(arg) => (arg, arg),
+            llir::ScalarOrVector::Vector(args) => (&args[0], &args[1]),
+        };
+        writeln!(
             fmt,
-            "{}{}",
-            self.predicate
-                .into_iter()
-                .format_with("", |predicate, f| f(&format_args!(
-                    "@{} ",
-                    predicate.ptx()
-                ))),
-            self.instruction.ptx()
-        )
+            "{}mov.b32 %{}{}, {{{}, {}}};",
+            predicate,
+            HALF2_PREFIX,
+            idx + 1,
+            lo.ptx(),
+            hi.ptx()
+        )?;
+    }
+    writeln!(
+        fmt,
+        "{}{}x2 %{}0, {};",
+        predicate,
+        op,
+        HALF2_PREFIX,
+        (1..=args.len())
+            .format_with(", ", |idx, f| f(&format_args!("%{}{}", HALF2_PREFIX, idx)))
+    )?;
+    write!(
+        fmt,
+        "{}mov.b32 {{{}, {}}}, %{}0",
+        predicate,
+        d[0].ptx(),
+        d[1].ptx(),
+        HALF2_PREFIX
+    )
+}
+
+/// Prefix of the single-precision registers holding the operands and the result of
+/// half-precision instructions that PTX only supports in single precision.
+const HALF_AS_F32_PREFIX: &str = "hf";
+
+/// Indicates if PTX has no half-precision version of the instruction, which must then be
+/// computed in single precision.
+fn needs_f32(instruction: &llir::Instruction<'_>) -> bool {
+    use llir::Instruction::{Binary, Unary};
+    match instruction {
+        Unary(llir::UnOp::Exp { t, .. }, ..)
+        | Unary(llir::UnOp::Sqrt { t }, ..)
+        | Binary(llir::BinOp::FDiv { t, .. }, ..) => *t == Type::F(16),
+        _ => false,
+    }
+}
+
+/// Prints a half-precision instruction that PTX only supports in single precision. The
+/// operands are converted into the registers declared by `CudaPrinter::var_decls`, and
+/// the result is rounded back to half precision.
+fn fmt_through_f32(
+    fmt: &mut fmt::Formatter<'_>,
+    predicate: &str,
+    instruction: &llir::Instruction<'_>,
+) -> fmt::Result {
+    use llir::Instruction::{Binary, Unary};
+    let (d, args) = match instruction {
+        Unary(_, d, [a]) => (d, vec![a]),
+        Binary(_, d, [a, b]) => (d, vec![a, b]),
+        _ => panic!("{}: no single-precision version", instruction),
+    };
+    for (idx, arg) in args.iter().enumerate() {
+        writeln!(
+            fmt,
+            "{}cvt.f32.f16 %{}{}, {};",
+            predicate,
+            HALF_AS_F32_PREFIX,
+            idx + 1,
+            arg.ptx()
+        )?;
+    }
+    let reg = |idx| format!("%{}{}", HALF_AS_F32_PREFIX, idx);
+    match instruction {
+        Unary(llir::UnOp::Exp { .. }, ..) => {
+            writeln!(
+                fmt,
+                "{}mul.f32 {}, 0f3fb8aa3b, {}; // 0f3fb8aa3b = log2(e)",
+                predicate,
+                reg(0),
+                reg(1)
+            )?;
+            writeln!(fmt, "{}ex2.approx.f32 {}, {};", predicate, reg(0), reg(0))?;
+        }
+        Unary(llir::UnOp::Sqrt { .. }, ..) => {
+            writeln!(fmt, "{}sqrt.rn.f32 {}, {};", predicate, reg(0), reg(1))?;
+        }
+        Binary(llir::BinOp::FDiv { rounding, .. }, ..) => writeln!(
+            fmt,
+            "{}div.{}.f32 {}, {}, {};",
+            predicate,
+            rounding.ptx(),
+            reg(0),
+            reg(1),
+            reg(2)
+        )?,
+        _ => panic!("{}: no single-precision version", instruction),
+    }
+    write!(fmt, "{}cvt.rn.f16.f32 {}, {}", predicate, d.ptx(), reg(0))
+}
+
+/// Prefix of the single-precision registers holding the intermediate values of accurate
+/// exponentials.
+const EXP_PREFIX: &str = "ex";
+
+/// Prints a single-precision exponential computed as `2^t`, with `t = a * log2(e)`.
+/// Rounding `t` loses more precision than `ex2.approx` itself as `|a|` grows: the
+/// rounding error `e` of `t`, along with the error on the constant `log2(e)`, is
+/// recovered with fused multiply-adds and applied to the result as
+/// `2^(t + e) ~= 2^t * (1 + e * ln(2))`.
+fn fmt_accurate_exp(
+    fmt: &mut fmt::Formatter<'_>,
+    predicate: &str,
+    instruction: &llir::Instruction<'_>,
+) -> fmt::Result {
+    let (d, a) = match instruction {
+        llir::Instruction::Unary(_, d, [a]) => (d.ptx(), a.ptx()),
+        _ => panic!("{}: not an exponential", instruction),
+    };
+    let (t, e) = (format!("%{}0", EXP_PREFIX), format!("%{}1", EXP_PREFIX));
+    // log2(e), the error on its single-precision value, and ln(2).
+    let (log2_e, log2_e_lo, ln_2) = ("0f3fb8aa3b", "0f32a57060", "0f3f317218");
+    let p = predicate;
+    writeln!(fmt, "{}mul.rn.f32 {}, {}, {};", p, t, a, log2_e)?;
+    writeln!(fmt, "{}neg.f32 {}, {};", p, e, t)?;
+    writeln!(fmt, "{}fma.rn.f32 {}, {}, {}, {};", p, e, a, log2_e, e)?;
+    writeln!(fmt, "{}fma.rn.f32 {}, {}, {}, {};", p, e, a, log2_e_lo, e)?;
+    writeln!(fmt, "{}fma.rn.f32 {}, {}, {}, 0f3f800000;", p, e, e, ln_2)?;
+    writeln!(fmt, "{}ex2.approx.f32 {}, {};", p, t, t)?;
+    write!(fmt, "{}mul.rn.f32 {}, {}, {}", p, d, t, e)
+}
+
+/// Prints a warp-level matrix multiply-accumulate. PTX expects the half-precision
+/// fragments of the input tiles packed by pairs in 32-bit registers: they are first packed
+/// into the registers declared by `CudaPrinter::var_decls`.
+///
+/// The search space ensures the instruction is vectorized by the size of the accumulator
+/// fragment. The fragments of the operands are the distinct values they take along the
+/// vector dimensions, so the input tiles may be broadcast along some of them.
+fn fmt_mma(
+    fmt: &mut fmt::Formatter<'_>,
+    predicate: &str,
+    op: &llir::TernOp,
+    shape: ir::MmaShape,
+    d: &llir::RegVec<'_>,
+    [a, b, c]: [&llir::OpVec<'_>; 3],
+) -> fmt::Result {
+    let [a_size, b_size, c_size] = shape.fragment_sizes(32);
+    let fragment = |value: &llir::OpVec<'_>| {
+        elements(value)
+            .iter()
+            .map(|x| x.ptx().to_string())
+            .unique()
+            .collect_vec()
+    };
+    let (a, b, c, d) = (fragment(a), fragment(b), fragment(c), elements(d));
+    assert_eq!(d.len(), c_size as usize);
+    assert!(
+        a.len() == a_size as usize && b.len() == b_size as usize && c.len() == d.len(),
+        "the operands of the {} mma do not vary along the vector dimensions as its \
+         fragments",
+        shape
+    );
+    let mut packed = Vec::new();
+    for pair in a.chunks(2).chain(b.chunks(2)) {
+        let reg = format!("%{}{}", HALF2_PREFIX, packed.len() + 1);
+        writeln!(
+            fmt,
+            "{}mov.b32 {}, {{{}, {}}};",
+            predicate, reg, pair[0], pair[1]
+        )?;
+        packed.push(reg);
+    }
+    let (a, b) = packed.split_at(a.len() / 2);
+    write!(
+        fmt,
+        "{}{} {{{}}}, {{{}}}, {{{}}}, {{{}}}",
+        predicate,
+        op.ptx(),
+        d.iter().format_with(", ", |x, f| f(&x.ptx())),
+        a.iter().format(", "),
+        b.iter().format(", "),
+        c.iter().format(", "),
+    )
+}
+
+/// Returns the elements of a scalar or vector value.
+fn elements<T>(value: &llir::ScalarOrVector<T>) -> &[T] {
+    match value {
+        llir::ScalarOrVector::Scalar(x) => std::slice::from_ref(x),
+        llir::ScalarOrVector::Vector(xs) => xs,
     }
 }
 
@@ -581,8 +859,9 @@ impl PTXDisplay for llir::Instruction<'_> {
             // 2^(log2(e)*x).
             //
             // Only implemented for f32, since other types require
-            // significant software emulation
-            Unary(llir::UnOp::Exp { t }, d, [a]) if t == &ir::Type::F(32) => {
+            // significant software emulation. The accurate version is printed by
+            // `fmt_accurate_exp`.
+            Unary(llir::UnOp::Exp { t, .. }, d, [a]) if t == &ir::Type::F(32) => {
                 writeln!(
                     fmt,
                     "mul.{t} {d}, 0f3fb8aa3b, {a}; // 0f3fb8aa3b = log2(e)",
@@ -615,6 +894,20 @@ impl PTXDisplay for llir::Instruction<'_> {
     }
 }
 
+/// Prints a type as the type of data movement instructions and of parameters. PTX has
+/// no half-precision variant of those: half-precision values are moved around as
+/// untyped 16-bit values instead.
+struct DataType(ir::Type);
+
+impl PTXDisplay for DataType {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Type::F(16) => write!(fmt, "b16"),
+            ref t => PTXDisplay::fmt(t, fmt),
+        }
+    }
+}
+
 impl PTXDisplay for ir::Type {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -625,3 +918,198 @@ impl PTXDisplay for ir::Type {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use telamon::explorer::choice;
+    use telamon::helper;
+    use telamon::search_space::{Action, DimKind, Order, Precision, SearchSpace};
+
+    use super::*;
+    use crate::PrintError;
+
+    /// Returns a GPU with the given compute capability.
+    pub(crate) fn gpu(sm_major: u8, sm_minor: u8) -> Gpu {
+        Gpu {
+            sm_major,
+            sm_minor,
+            ..Gpu::dummy()
+        }
+    }
+
+    /// Returns the first implementation of `space`, obtained by applying the first valid
+    /// action of each choice.
+    pub(crate) fn implementation(mut space: SearchSpace) -> SearchSpace {
+        while let Some(choice) = choice::default_list(&space).next() {
+            space = choice
+                .iter()
+                .find_map(|action| action.apply_to(space.clone()).ok())
+                .expect("no valid action");
+        }
+        choice::fix_order(space)
+    }
+
+    /// Prints the first implementation of `space`.
+    fn print(space: SearchSpace, gpu: &Gpu) -> String {
+        let space = implementation(space);
+        unwrap!(CudaPrinter::default().function(&Function::build(&space), gpu))
+    }
+
+    /// Returns a builder for a kernel without parameters.
+    pub(crate) fn builder(gpu: &Gpu) -> helper::Builder {
+        let signature = ir::Signature::new("test");
+        helper::Builder::new(signature.into(), Arc::new(gpu.clone()))
+    }
+
+    /// Ensures pairs of half-precision additions are printed as `f16x2` instructions.
+    #[test]
+    fn half2() {
+        let _ = env_logger::try_init();
+        let gpu = gpu(5, 3);
+        let mut builder = builder(&gpu);
+        let size_2 = builder.cst_size(2);
+        let dim = builder.open_dim_ex(size_2, DimKind::UNROLL);
+        let x = builder.cast(&1f32, Type::F(16));
+        let vec_dim = builder.open_mapped_dim(&dim);
+        builder.action(Action::DimKind(vec_dim[0], DimKind::INNER_VECTOR));
+        builder.add(&x, &x);
+        let ptx = print(builder.get(), &gpu);
+        assert!(ptx.contains("add.rn.f16x2 %hh0, %hh1, %hh2;"));
+    }
+
+    /// Ensures half-precision divisions, square roots and exponentials, which PTX does
+    /// not support, are computed in single precision.
+    #[test]
+    fn half_through_f32() {
+        let _ = env_logger::try_init();
+        let gpu = gpu(5, 3);
+        let mut builder = builder(&gpu);
+        let x = builder.cast(&2f32, Type::F(16));
+        let y = builder.div(&x, &x);
+        let z = builder.sqrt(&y);
+        builder.exp(&z);
+        let ptx = print(builder.get(), &gpu);
+        assert!(ptx.contains(".f32 %hf0, %hf1, %hf2;"));
+        assert!(ptx.contains("sqrt.rn.f32 %hf0, %hf1;"));
+        assert!(ptx.contains("ex2.approx.f32 %hf0, %hf0;"));
+        assert_eq!(ptx.matches("cvt.rn.f16.f32").count(), 4);
+        assert!(!ptx.contains("div.rn.f16") && !ptx.contains("sqrt.rn.f16"));
+    }
+
+    /// Ensures single-precision divisions and exponentials are only approximated when
+    /// requested, and that exponentials are otherwise corrected for the rounding of their
+    /// argument.
+    #[test]
+    fn precision() {
+        let _ = env_logger::try_init();
+        let gpu = gpu(6, 0);
+        let build = |precision| {
+            let mut builder = builder(&gpu);
+            let x = builder.div(&2f32, &3f32);
+            let y = builder.exp(&x);
+            if let Some(precision) = precision {
+                builder.action(Action::Precision(x, precision));
+                builder.action(Action::Precision(y, precision));
+            }
+            print(builder.get(), &gpu)
+        };
+
+        let accurate = build(None);
+        assert!(accurate.contains("div.rn.f32"));
+        assert!(accurate.contains("fma.rn.f32 %ex1, %ex1, 0f3f317218, 0f3f800000;"));
+        assert!(accurate.contains("ex2.approx.f32 %ex0, %ex0;"));
+
+        let approx = build(Some(Precision::APPROX));
+        assert!(approx.contains("div.approx.f32"));
+        assert!(approx.contains("ex2.approx.f32"));
+        assert!(!approx.contains("%ex1,"));
+    }
+
+    /// Builds a kernel with a `m16n8k8` matrix multiply-accumulate. Each thread holds two
+    /// rows of the `lhs` and accumulator fragments, and pairs of values along `k` and `n`.
+    fn mma_kernel(gpu: &Gpu, pair_kind: DimKind) -> helper::Builder {
+        let mut builder = builder(gpu);
+        let size_2 = builder.cst_size(2);
+        let pair = builder.open_dim_ex(size_2.clone(), DimKind::UNROLL);
+        let rhs = builder.cast(&1f32, Type::F(16));
+        let row = builder.open_dim_ex(size_2, DimKind::UNROLL);
+        let lhs = builder.cast(&1f32, Type::F(16));
+        let acc = builder.mov(&0f32);
+        let row_vec = builder.open_mapped_dim(&row);
+        let pair_vec = builder.open_mapped_dim(&pair);
+        builder.action(Action::DimKind(row_vec[0], DimKind::INNER_VECTOR));
+        builder.action(Action::DimKind(pair_vec[0], pair_kind));
+        builder.order(&row_vec, &pair_vec, Order::OUTER);
+        builder.mma(&lhs, &rhs, &acc, ir::MmaShape { m: 16, n: 8, k: 8 });
+        builder
+    }
+
+    /// Ensures matrix multiply-accumulates are printed with their fragments packed.
+    #[test]
+    fn mma() {
+        let _ = env_logger::try_init();
+        let gpu = gpu(7, 5);
+        let ptx = print(mma_kernel(&gpu, DimKind::INNER_VECTOR).get(), &gpu);
+        assert!(ptx.starts_with(".version 6.5"));
+        assert!(ptx.contains("mma.sync.aligned.m16n8k8.row.col.f32.f16.f16.f32"));
+        // Two registers for the `lhs` fragment and one for the `rhs` fragment.
+        assert!(ptx.contains("mov.b32 %hh3"));
+        assert!(!ptx.contains("mov.b32 %hh4"));
+    }
+
+    /// Ensures matrix multiply-accumulates are only vectorized by the size of their
+    /// accumulator fragment.
+    #[test]
+    fn mma_vectorization() {
+        let _ = env_logger::try_init();
+        let gpu = gpu(7, 5);
+        assert!(mma_kernel(&gpu, DimKind::UNROLL).try_get().is_err());
+    }
+
+    /// Ensures kernels with too many instructions once unrolled are rejected before
+    /// being printed.
+    #[test]
+    fn too_many_instructions() {
+        let _ = env_logger::try_init();
+        let gpu = gpu(6, 0);
+        let mut builder = builder(&gpu);
+        let size_16 = builder.cst_size(16);
+        let dim = builder.open_dim_ex(size_16, DimKind::UNROLL);
+        builder.mov(&0f32);
+        builder.close_dim(&dim);
+        let space = implementation(builder.get());
+        let function = Function::build(&space);
+        assert!(gpu.print_ptx(&function).is_ok());
+
+        let small_gpu = Gpu {
+            max_unrolled_insts: 8,
+            ..gpu
+        };
+        assert_eq!(
+            small_gpu.print_ptx(&function),
+            Err(PrintError::TooManyInstructions {
+                num_insts: 16,
+                limit: 8
+            })
+        );
+    }
+
+    /// Ensures printing a kernel bigger than the code size limit fails.
+    #[test]
+    fn code_size_limit() {
+        let _ = env_logger::try_init();
+        let gpu = Gpu {
+            max_code_size: 8,
+            ..gpu(6, 0)
+        };
+        let mut builder = builder(&gpu);
+        builder.mov(&0f32);
+        let space = implementation(builder.get());
+        assert_eq!(
+            gpu.print_ptx(&Function::build(&space)),
+            Err(PrintError::CodeSize(CodeSizeError { size_limit: 8 }))
+        );
+    }
+}
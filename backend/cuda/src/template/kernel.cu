@@ -0,0 +1,24 @@
+typedef signed char int8_t;
+typedef short int16_t;
+typedef int int32_t;
+typedef long long int64_t;
+typedef unsigned int uint32_t;
+typedef long long intptr_t;
+
+#define __max(a, b) ((a) > (b) ? (a) : (b))
+#define __min(a, b) ((a) < (b) ? (a) : (b))
+#define __mul32Hi(a, b) __mulhi((int32_t)(a), (int32_t)(b))
+#define __mul64Hi(a, b) __mul64hi((int64_t)(a), (int64_t)(b))
+#define __mul32Wide(a, b) ((int64_t)(int32_t)(a) * (int64_t)(int32_t)(b))
+#define __mad(a, b, c) ((a) * (b) + (c))
+#define __mad32(a, b, c) ((a) * (b) + (c))
+#define __mad64(a, b, c) ((a) * (b) + (c))
+#define __mad32Hi(a, b, c) (__mul32Hi(a, b) + (c))
+#define __mad32Wide(a, b, c) (__mul32Wide(a, b) + (int64_t)(c))
+#define __fma(a, b, c) fma(a, b, c)
+#define __sync() __syncthreads()
+
+extern "C" __global__ void __launch_bounds__({num_thread}) {name}(
+  {params}
+)
+{{
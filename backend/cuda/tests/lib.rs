@@ -1,8 +1,12 @@
 #![cfg(feature = "cuda")]
+use std::sync::atomic::{self, AtomicUsize};
+
 use itertools::Itertools;
 use log::debug;
 
-use telamon::device::{ArrayArgumentExt, Context, EvalMode};
+use telamon::device::{
+    ArrayArgument, ArrayArgumentExt, CancellationToken, Context, EvalMode,
+};
 use telamon::search_space::*;
 use telamon::{codegen, explorer};
 use telamon::{helper, ir};
@@ -16,9 +20,15 @@ pub fn gen_best(context: &Context, space: SearchSpace) {
     context.device().gen_code(&best, &mut std::io::sink());
 }
 
-/// Checks the result of all valid candidates.
-pub fn check_candidates<F>(space: SearchSpace, ctx: &Context, mut check: F)
-where
+/// Checks the result of all valid candidates.  `outputs` are reset to zero on the device
+/// before each evaluation, so that a candidate cannot pass by reusing the results of the
+/// previous one.
+pub fn check_candidates<F>(
+    space: SearchSpace,
+    ctx: &Context,
+    outputs: &[&dyn ArrayArgument],
+    mut check: F,
+) where
     F: FnMut(),
 {
     explorer::gen_space(
@@ -28,6 +38,9 @@ where
         |candidate| {
             debug!("testing candidate with actions {:?}", candidate.actions);
             let fun = codegen::Function::build(&candidate.space);
+            for output in outputs {
+                output.zero();
+            }
             ctx.evaluate(&fun, EvalMode::FindBest).unwrap();
             check();
         },
@@ -186,7 +199,7 @@ fn induction_var_nested() {
     let pattern = ir::AccessPattern::Unknown(None);
     let _ = builder.st(&"out", &ind_var, pattern);
 
-    check_candidates(builder.get(), &context, || {
+    check_candidates(builder.get(), &context, &[out.as_ref()], || {
         let res = out.as_ref().read::<i32>();
         // 1*(k/4 - 1) + (k/4)*(4 - 1) + k*(5 - 1) = 5*k - 1 = 59
         assert_eq!(res[0], 59);
@@ -213,7 +226,7 @@ fn induction_var_simple() {
     let pattern = ir::AccessPattern::Unknown(None);
     let _ = builder.st(&"out", &ind_var, pattern);
 
-    check_candidates(builder.get(), &context, || {
+    check_candidates(builder.get(), &context, &[out.as_ref()], || {
         let res = out.as_ref().read::<i32>();
         assert_eq!(res[0], 8);
     });
@@ -251,7 +264,7 @@ fn global_vector_load() {
     let output_pattern = ir::AccessPattern::Unknown(None);
     builder.st_ex(&"output", &ld, true, output_pattern, InstFlag::NO_CACHE);
 
-    check_candidates(builder.get(), &context, || {
+    check_candidates(builder.get(), &context, &[output.as_ref()], || {
         let res = output.as_ref().read::<i32>()[0];
         assert_eq!(res, 13);
     });
@@ -277,7 +290,7 @@ fn size_cast() {
     let pattern = ir::AccessPattern::Unknown(None);
     let _ = builder.st(&"out", &ind_var, pattern);
 
-    check_candidates(builder.get(), &context, || {
+    check_candidates(builder.get(), &context, &[out.as_ref()], || {
         let res = out.as_ref().read::<i64>();
         assert_eq!(res[0], 8);
     });
@@ -311,7 +324,7 @@ fn perf_model_0() {
     let _d4 = builder.open_dim_ex(size_16, DimKind::UNROLL);
     let _ = builder.mov(&0f32);
 
-    check_candidates(builder.get(), &context, || ());
+    check_candidates(builder.get(), &context, &[], || ());
 }
 
 /// Three merged loop nests.
@@ -340,7 +353,7 @@ fn merge_0() {
     builder.order(&d0, &d1, Order::MERGED);
     builder.order(&d1, &d2, Order::MERGED);
 
-    check_candidates(builder.get(), &context, || ());
+    check_candidates(builder.get(), &context, &[], || ());
 }
 
 /// Two merge loop nest, with a third dimension that is either merged or outer.
@@ -367,7 +380,7 @@ fn merge_1() {
     builder.order(&d1, &d2, Order::OUTER | Order::MERGED);
     builder.order(&d0, &d1, Order::MERGED);
 
-    check_candidates(builder.get(), &context, || ());
+    check_candidates(builder.get(), &context, &[], || ());
 }
 
 #[test]
@@ -393,7 +406,7 @@ fn dim_map_reduce_0() {
 
     builder.order(&inst2, &d1, Order::AFTER);
 
-    check_candidates(builder.get(), &context, || ());
+    check_candidates(builder.get(), &context, &[], || ());
 }
 
 #[test]
@@ -451,3 +464,124 @@ fn test0() {
         .apply_decisions(vec![Action::DimKind(d4[0], DimKind::UNROLL)])
         .unwrap();
 }
+
+/// Returns the first implementations of a kernel with a single dimension.
+fn small_candidates(context: &cuda::Context, name: &str) -> Vec<explorer::Candidate> {
+    let signature = ir::Signature::new(name);
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let size_64 = builder.cst_size(64);
+    let d0 = builder.open_dim(size_64);
+    builder.mov(&d0);
+    let mut candidates = Vec::new();
+    explorer::gen_space(
+        context,
+        builder.get(),
+        |_| (),
+        |candidate| candidates.push(candidate.clone()),
+    );
+    candidates.truncate(8);
+    candidates
+}
+
+/// Evaluates `candidates` with `num_compilers` compilation threads and returns the number
+/// of candidates that were evaluated.
+fn async_eval_all(
+    context: &cuda::Context,
+    candidates: &[explorer::Candidate],
+    num_compilers: usize,
+    cancel: &CancellationToken,
+) -> usize {
+    let num_evaluated = AtomicUsize::new(0);
+    context.async_eval(1, num_compilers, EvalMode::FindBest, cancel, &|evaluator| {
+        for candidate in candidates {
+            let num_evaluated = &num_evaluated;
+            evaluator.add_kernel(candidate.clone(), move |_, kernel| {
+                if kernel.evaluate().map_or(false, f64::is_finite) {
+                    num_evaluated.fetch_add(1, atomic::Ordering::SeqCst);
+                }
+            });
+        }
+    });
+    num_evaluated.into_inner()
+}
+
+/// Ensures every candidate is evaluated, whether it is compiled by the exploration thread
+/// or by compilation threads.
+#[test]
+fn compile_ahead() {
+    let _ = env_logger::try_init();
+    let executor = cuda::Executor::init();
+    let context = cuda::Context::new(&executor);
+    let candidates = small_candidates(&context, "compile_ahead");
+    assert!(!candidates.is_empty());
+    for &num_compilers in &[0, 1, 4] {
+        let cancel = CancellationToken::new();
+        let num_evaluated = async_eval_all(&context, &candidates, num_compilers, &cancel);
+        assert_eq!(
+            num_evaluated,
+            candidates.len(),
+            "{} compilers",
+            num_compilers
+        );
+    }
+}
+
+/// Ensures a cancelled evaluation with compilation threads terminates without evaluating
+/// the candidates.
+#[test]
+fn compile_ahead_cancelled() {
+    let _ = env_logger::try_init();
+    let executor = cuda::Executor::init();
+    let context = cuda::Context::new(&executor);
+    let candidates = small_candidates(&context, "compile_ahead_cancelled");
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    assert_eq!(async_eval_all(&context, &candidates, 2, &cancel), 0);
+}
+
+/// Ensures candidates compiled with NVRTC compute the same results as with the PTX JIT.
+#[test]
+fn nvrtc_induction_var() {
+    let _ = env_logger::try_init();
+    let executor = cuda::Executor::init();
+    let mut context = cuda::Context::new(&executor).with_compiler(cuda::Compiler::Nvrtc);
+    let out;
+    let signature = {
+        let mut builder = helper::SignatureBuilder::new("nvrtc_ind_var", &mut context);
+        out = builder.array::<i32>("out", 1);
+        builder.get()
+    };
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let size_3 = builder.cst_size(3);
+    let size_4 = builder.cst_size(4);
+    let d0 = builder.open_dim_ex(size_3, DimKind::LOOP);
+    let ind_var = builder.induction_var(&0i32, vec![(&d0, size_4)]);
+    let pattern = ir::AccessPattern::Unknown(None);
+    let _ = builder.st(&"out", &ind_var, pattern);
+
+    check_candidates(builder.get(), &context, &[out.as_ref()], || {
+        let res = out.as_ref().read::<i32>();
+        assert_eq!(res[0], 8);
+    });
+}
+
+/// Ensures candidates are compiled with NVRTC by the compilation threads as well as by
+/// the exploration thread.
+#[test]
+fn nvrtc_compile_ahead() {
+    let _ = env_logger::try_init();
+    let executor = cuda::Executor::init();
+    let context = cuda::Context::new(&executor).with_compiler(cuda::Compiler::Nvrtc);
+    let candidates = small_candidates(&context, "nvrtc_compile_ahead");
+    assert!(!candidates.is_empty());
+    for &num_compilers in &[0, 2] {
+        let cancel = CancellationToken::new();
+        let num_evaluated = async_eval_all(&context, &candidates, num_compilers, &cancel);
+        assert_eq!(
+            num_evaluated,
+            candidates.len(),
+            "{} compilers",
+            num_compilers
+        );
+    }
+}
@@ -75,8 +75,9 @@ fn main() {
 
                 debug!("Invoking backend for code generation");
                 let function = codegen::Function::build(&implementation.space);
-                let kernel_code =
-                    mppa::printer::MppaPrinter::default().wrapper_function(&function, 1);
+                let kernel_code = mppa::printer::MppaPrinter::default()
+                    .wrapper_function(&function, 1)
+                    .unwrap();
 
                 action_list.push((implementation.actions.reverse(), kernel_code));
                 impls_generated += 1;
@@ -135,7 +135,7 @@ impl Context {
     /// Compiles and sets the arguments of a kernel.
     fn setup_kernel(&self, fun: &Function) -> (telajax::Kernel, Vec<KernelArg>) {
         let id = ATOMIC_KERNEL_ID.fetch_add(1, Ordering::SeqCst);
-        let kernel_code = MppaPrinter::default().wrapper_function(fun, id);
+        let kernel_code = unwrap!(MppaPrinter::default().wrapper_function(fun, id));
         let wrapper = self.get_wrapper(fun, id);
 
         // Compiler and linker flags
@@ -243,7 +243,9 @@ impl device::Context for Context {
     fn async_eval<'d>(
         &self,
         num_workers: usize,
+        _num_compilers: usize,
         _mode: EvalMode,
+        cancel: &device::CancellationToken,
         inner: &(dyn Fn(&mut dyn device::AsyncEvaluator<'d>) + Sync),
     ) {
         // FIXME: execute in parallel
@@ -254,6 +256,7 @@ impl device::Context for Context {
                 let mut evaluator = AsyncEvaluator {
                     context: self,
                     sender: send.clone(),
+                    cancel,
                 };
                 unwrap!(scope
                     .builder()
@@ -263,15 +266,15 @@ impl device::Context for Context {
             // Start the evaluation thread.
             let eval_thread_name = "Telamon - CPU Evaluation Thread".to_string();
             unwrap!(scope.builder().name(eval_thread_name).spawn(move |_| {
-                while let Ok((candidate, kernel, callback)) = recv.recv() {
+                cancel.drain(recv, |candidate, kernel, callback| {
                     callback.call(
                         candidate,
                         &mut Code {
                             kernel,
                             executor: self.executor,
                         },
-                    );
-                }
+                    )
+                });
             }));
         })
         .unwrap();
@@ -314,6 +317,7 @@ type AsyncPayload<'b> = (explorer::Candidate, telajax::Kernel, AsyncCallback<'b>
 struct AsyncEvaluator<'b> {
     context: &'b Context,
     sender: mpsc::SyncSender<AsyncPayload<'b>>,
+    cancel: &'b device::CancellationToken,
 }
 
 impl<'b, 'c> device::AsyncEvaluator<'c> for AsyncEvaluator<'b>
@@ -325,6 +329,10 @@ where
         candidate: explorer::Candidate,
         callback: device::AsyncCallback<'c>,
     ) {
+        if self.cancel.is_cancelled() {
+            return device::CancelledKernel::report(candidate, callback);
+        }
+
         let (kernel, _) = {
             let dev_fun = Function::build(&candidate.space);
             self.context.setup_kernel(&dev_fun)
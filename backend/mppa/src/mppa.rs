@@ -2,7 +2,7 @@ use fxhash::FxHashMap;
 use std;
 use std::io::Write;
 use telamon::codegen::Function;
-use telamon::device;
+use telamon::device::{self, vectorization::VectorRule};
 use telamon::ir::{self, Type};
 use telamon::model::{self, HwPressure};
 use telamon::search_space::{DimKind, InstFlag, MemSpace, SearchSpace};
@@ -51,8 +51,9 @@ impl device::Device for Mppa {
         false
     }
 
-    fn max_vectorization(&self, _op: &ir::Operator) -> [u32; 2] {
-        [1, 1]
+    fn vectorization_rules(&self) -> &[VectorRule] {
+        // Operators without a rule are not vectorized.
+        &[]
     }
 
     fn shared_mem(&self) -> u32 {
@@ -6,11 +6,10 @@ use telamon::ir::{self, Type};
 use telamon::search_space::{DimKind, Domain};
 use telamon_c::C99Display as _;
 use utils::unwrap;
-// TODO(cc_perf): avoid concatenating strings.
 
 #[derive(Default)]
 pub struct MppaPrinter {
-    buffer: String,
+    buffer: CodeBuffer,
 }
 
 fn param_t(param: &ParamVal) -> String {
@@ -28,6 +27,13 @@ fn param_t(param: &ParamVal) -> String {
 }
 
 impl MppaPrinter {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        MppaPrinter {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+        }
+    }
+
     /// Declares all parameters of the function with the appropriate type
     fn param_decl(&self, param: &ParamVal) -> String {
         format!("{} {}", param_t(param), param.key().ident())
@@ -81,8 +87,13 @@ impl MppaPrinter {
         decls.join("\n  ")
     }
 
-    /// Prints a `Function`.
-    fn function<'a: 'b, 'b>(&mut self, function: &'b Function<'a>) -> String {
+    /// Prints a `Function` to `out`.  Fails if the code exceeds the size limit of the
+    /// printer.
+    fn function<'a: 'b, 'b>(
+        &mut self,
+        function: &'b Function<'a>,
+        out: &mut dyn WriteFmt,
+    ) -> Result<(), CodeSizeError> {
         let mut namegen = NameGenerator::default();
         let interner = Interner::default();
         let name_map = &mut NameMap::new(&interner, function, &mut namegen);
@@ -92,11 +103,12 @@ impl MppaPrinter {
             .map(|v| self.param_decl(v))
             .join(",\n  ");
         // SIGNATURE AND OPEN BRACKET
-        let mut return_string = format!(
+        unwrap!(write!(
+            out,
             include_str!("template/signature.c.template"),
             name = function.name(),
             params = param_decls
-        );
+        ));
         // INDEX LOADS
         let idx_loads = self.decl_par_indexes(function, name_map);
         unwrap!(writeln!(self.buffer, "{}", idx_loads));
@@ -157,12 +169,13 @@ impl MppaPrinter {
         }
         // BODY
         Printer::new(self, name_map).cfg(function, function.cfg());
-        let var_decls = self.var_decls(&namegen);
-        return_string.push_str(&var_decls);
-        return_string.push_str(&self.buffer);
+        // The variables are declared once the body is printed, as they are named while
+        // printing it.
+        unwrap!(out.write_str(&self.var_decls(&namegen)));
+        self.buffer.write_to(out)?;
         // Close function bracket
-        return_string.push('}');
-        return_string
+        unwrap!(out.write_char('}'));
+        Ok(())
     }
 
     /// Function takes parameters as an array of void* pointers
@@ -315,8 +328,27 @@ impl MppaPrinter {
         &mut self,
         func: &'b Function<'a>,
         id: usize,
-    ) -> String {
-        let fun_str = self.function(func);
+    ) -> Result<String, CodeSizeError> {
+        let mut code = String::new();
+        self.print_wrapper(func, id, &mut code)?;
+        Ok(code)
+    }
+
+    /// Prints the kernel and its wrapper to `out`, as returned by `wrapper_function`.
+    /// The code is written as it is generated, except for the body of the kernel which
+    /// is buffered until its variables are declared.
+    pub fn print_wrapper<'a: 'b, 'b>(
+        &mut self,
+        func: &'b Function<'a>,
+        id: usize,
+        out: &mut dyn WriteFmt,
+    ) -> Result<(), CodeSizeError> {
+        unwrap!(write!(
+            out,
+            include_str!("template/host_prelude.c.template"),
+            dim_decl = self.build_thread_id_struct(func),
+        ));
+        self.function(func, out)?;
         let fun_params = self.params_call(func);
         let (lower_bound, upper_n_arg) = func.device_code_args().size_hint();
         let n_args = if let Some(upper_bound) = upper_n_arg {
@@ -329,20 +361,20 @@ impl MppaPrinter {
             .device_code_args()
             .map(|v| self.param_decl(v))
             .join(",  ");
-        format!(
-            include_str!("template/host.c.template"),
+        unwrap!(write!(
+            out,
+            include_str!("template/host_entry.c.template"),
             id = id,
             cl_arg_def = cl_arg_def,
             n_arg = n_args,
             build_ptr_struct = self.build_ptr_struct(func),
             fun_name = func.name(),
-            fun_str = fun_str,
             fun_params_cast = self.fun_params_cast(func),
             fun_params = fun_params,
             gen_threads = self.thread_gen(func),
-            dim_decl = self.build_thread_id_struct(func),
             thread_join = self.thread_join(func),
-        )
+        ));
+        Ok(())
     }
 
     /// Returns the name of a type.
@@ -400,4 +432,8 @@ impl InstPrinter for MppaPrinter {
     fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
         writeln!(self.buffer, "{}", inst.c99()).unwrap();
     }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
 }
@@ -31,8 +31,9 @@ fn print_candidates() {
 
         let function = codegen::Function::build(&implementation.space);
 
-        let generated_code =
-            mppa::printer::MppaPrinter::default().wrapper_function(&function, 1);
+        let generated_code = mppa::printer::MppaPrinter::default()
+            .wrapper_function(&function, 1)
+            .unwrap();
 
         assert!(
             *expected_code == generated_code,
@@ -0,0 +1,100 @@
+//! A fake OpenCL runtime, with the same interface as the real one. It allows compiling the
+//! crate on machines without OpenCL, but panics as soon as the device is used.
+use libc::c_void;
+use std::fmt;
+
+/// An error returned by the OpenCL runtime.
+#[derive(Debug)]
+pub enum Error {
+    /// No OpenCL device is available.
+    NoDevice,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "OpenCL support is disabled, enable the `real_opencl` feature"
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone)]
+pub struct Executor;
+
+impl Executor {
+    pub fn init() -> Result<Self, Error> {
+        Err(Error::NoDevice)
+    }
+
+    pub fn device_name(&self) -> String {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn max_work_group_size(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn local_mem_size(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn max_compute_units(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn max_clock_frequency(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn allocate(&self, _: usize) -> Result<Buffer, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn build_kernel(&self, _: &str, _: &str) -> Result<Kernel, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+}
+
+pub struct Buffer;
+
+impl Buffer {
+    pub fn len(&self) -> usize {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn read(&self) -> Result<Vec<i8>, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn write(&self, _: &[i8]) -> Result<(), Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn zero(&self) -> Result<(), Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+}
+
+pub struct Kernel;
+
+impl Kernel {
+    pub fn set_buffer_arg(&mut self, _: u32, _: &Buffer) -> Result<(), Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn set_scalar_arg(
+        &mut self,
+        _: u32,
+        _: usize,
+        _: *const c_void,
+    ) -> Result<(), Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn execute(&mut self, _: [usize; 3], _: [usize; 3]) -> Result<u64, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+}
@@ -0,0 +1,580 @@
+//! Safe bindings to the subset of the OpenCL 1.2 API needed to run kernels.
+#![allow(non_camel_case_types)]
+use libc::{c_char, c_void, size_t};
+use std::ffi::CString;
+use std::sync::Arc;
+use std::{fmt, ptr};
+
+type cl_int = i32;
+type cl_uint = u32;
+type cl_ulong = u64;
+type cl_bitfield = cl_ulong;
+type cl_platform_id = *mut c_void;
+type cl_device_id = *mut c_void;
+type cl_context = *mut c_void;
+type cl_command_queue = *mut c_void;
+type cl_mem = *mut c_void;
+type cl_program = *mut c_void;
+type cl_kernel = *mut c_void;
+type cl_event = *mut c_void;
+
+const CL_SUCCESS: cl_int = 0;
+const CL_TRUE: cl_uint = 1;
+const CL_DEVICE_TYPE_ALL: cl_bitfield = 0xFFFF_FFFF;
+const CL_DEVICE_MAX_COMPUTE_UNITS: cl_uint = 0x1002;
+const CL_DEVICE_MAX_WORK_GROUP_SIZE: cl_uint = 0x1004;
+const CL_DEVICE_MAX_CLOCK_FREQUENCY: cl_uint = 0x100C;
+const CL_DEVICE_LOCAL_MEM_SIZE: cl_uint = 0x1023;
+const CL_DEVICE_NAME: cl_uint = 0x102B;
+const CL_QUEUE_PROFILING_ENABLE: cl_bitfield = 1 << 1;
+const CL_MEM_READ_WRITE: cl_bitfield = 1;
+const CL_PROGRAM_BUILD_LOG: cl_uint = 0x1183;
+const CL_PROFILING_COMMAND_START: cl_uint = 0x1282;
+const CL_PROFILING_COMMAND_END: cl_uint = 0x1283;
+
+#[link(name = "OpenCL")]
+extern "C" {
+    fn clGetPlatformIDs(
+        num_entries: cl_uint,
+        platforms: *mut cl_platform_id,
+        num_platforms: *mut cl_uint,
+    ) -> cl_int;
+    fn clGetDeviceIDs(
+        platform: cl_platform_id,
+        device_type: cl_bitfield,
+        num_entries: cl_uint,
+        devices: *mut cl_device_id,
+        num_devices: *mut cl_uint,
+    ) -> cl_int;
+    fn clGetDeviceInfo(
+        device: cl_device_id,
+        param_name: cl_uint,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+    fn clCreateContext(
+        properties: *const isize,
+        num_devices: cl_uint,
+        devices: *const cl_device_id,
+        pfn_notify: *const c_void,
+        user_data: *mut c_void,
+        errcode_ret: *mut cl_int,
+    ) -> cl_context;
+    fn clCreateCommandQueue(
+        context: cl_context,
+        device: cl_device_id,
+        properties: cl_bitfield,
+        errcode_ret: *mut cl_int,
+    ) -> cl_command_queue;
+    fn clCreateBuffer(
+        context: cl_context,
+        flags: cl_bitfield,
+        size: size_t,
+        host_ptr: *mut c_void,
+        errcode_ret: *mut cl_int,
+    ) -> cl_mem;
+    fn clEnqueueReadBuffer(
+        queue: cl_command_queue,
+        buffer: cl_mem,
+        blocking_read: cl_uint,
+        offset: size_t,
+        size: size_t,
+        ptr: *mut c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+    fn clEnqueueWriteBuffer(
+        queue: cl_command_queue,
+        buffer: cl_mem,
+        blocking_write: cl_uint,
+        offset: size_t,
+        size: size_t,
+        ptr: *const c_void,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+    fn clEnqueueFillBuffer(
+        queue: cl_command_queue,
+        buffer: cl_mem,
+        pattern: *const c_void,
+        pattern_size: size_t,
+        offset: size_t,
+        size: size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+    fn clCreateProgramWithSource(
+        context: cl_context,
+        count: cl_uint,
+        strings: *const *const c_char,
+        lengths: *const size_t,
+        errcode_ret: *mut cl_int,
+    ) -> cl_program;
+    fn clBuildProgram(
+        program: cl_program,
+        num_devices: cl_uint,
+        device_list: *const cl_device_id,
+        options: *const c_char,
+        pfn_notify: *const c_void,
+        user_data: *mut c_void,
+    ) -> cl_int;
+    fn clGetProgramBuildInfo(
+        program: cl_program,
+        device: cl_device_id,
+        param_name: cl_uint,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+    fn clCreateKernel(
+        program: cl_program,
+        kernel_name: *const c_char,
+        errcode_ret: *mut cl_int,
+    ) -> cl_kernel;
+    fn clSetKernelArg(
+        kernel: cl_kernel,
+        arg_index: cl_uint,
+        arg_size: size_t,
+        arg_value: *const c_void,
+    ) -> cl_int;
+    fn clEnqueueNDRangeKernel(
+        queue: cl_command_queue,
+        kernel: cl_kernel,
+        work_dim: cl_uint,
+        global_work_offset: *const size_t,
+        global_work_size: *const size_t,
+        local_work_size: *const size_t,
+        num_events_in_wait_list: cl_uint,
+        event_wait_list: *const cl_event,
+        event: *mut cl_event,
+    ) -> cl_int;
+    fn clWaitForEvents(num_events: cl_uint, event_list: *const cl_event) -> cl_int;
+    fn clGetEventProfilingInfo(
+        event: cl_event,
+        param_name: cl_uint,
+        param_value_size: size_t,
+        param_value: *mut c_void,
+        param_value_size_ret: *mut size_t,
+    ) -> cl_int;
+    fn clFinish(queue: cl_command_queue) -> cl_int;
+    fn clReleaseEvent(event: cl_event) -> cl_int;
+    fn clReleaseKernel(kernel: cl_kernel) -> cl_int;
+    fn clReleaseProgram(program: cl_program) -> cl_int;
+    fn clReleaseMemObject(mem: cl_mem) -> cl_int;
+    fn clReleaseCommandQueue(queue: cl_command_queue) -> cl_int;
+    fn clReleaseContext(context: cl_context) -> cl_int;
+}
+
+/// An error returned by the OpenCL runtime.
+#[derive(Debug)]
+pub enum Error {
+    /// An OpenCL call returned an error code.
+    Code(i32),
+    /// The compilation of a kernel failed.
+    Build(String),
+    /// No OpenCL device is available.
+    NoDevice,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Code(code) => write!(fmt, "OpenCL error {}", code),
+            Error::Build(log) => write!(fmt, "OpenCL build failed:\n{}", log),
+            Error::NoDevice => write!(fmt, "no OpenCL device found"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Converts an OpenCL error code into a `Result`.
+fn check(code: cl_int) -> Result<(), Error> {
+    if code == CL_SUCCESS {
+        Ok(())
+    } else {
+        Err(Error::Code(code))
+    }
+}
+
+/// The OpenCL objects shared by all the handles on a device.
+struct ExecutorInner {
+    device: cl_device_id,
+    context: cl_context,
+    queue: cl_command_queue,
+}
+
+// OpenCL objects are thread-safe, except kernels.
+unsafe impl Send for ExecutorInner {}
+unsafe impl Sync for ExecutorInner {}
+
+impl Drop for ExecutorInner {
+    fn drop(&mut self) {
+        unsafe {
+            clFinish(self.queue);
+            clReleaseCommandQueue(self.queue);
+            clReleaseContext(self.context);
+        }
+    }
+}
+
+/// Executes code on an OpenCL device.
+#[derive(Clone)]
+pub struct Executor {
+    inner: Arc<ExecutorInner>,
+}
+
+impl Executor {
+    /// Creates an executor on the first OpenCL device of the first platform.
+    pub fn init() -> Result<Self, Error> {
+        unsafe {
+            let mut platform = ptr::null_mut();
+            let mut num_platforms = 0;
+            check(clGetPlatformIDs(1, &mut platform, &mut num_platforms))?;
+            if num_platforms == 0 {
+                return Err(Error::NoDevice);
+            }
+            let mut device = ptr::null_mut();
+            let mut num_devices = 0;
+            check(clGetDeviceIDs(
+                platform,
+                CL_DEVICE_TYPE_ALL,
+                1,
+                &mut device,
+                &mut num_devices,
+            ))?;
+            if num_devices == 0 {
+                return Err(Error::NoDevice);
+            }
+            let mut err = CL_SUCCESS;
+            let context = clCreateContext(
+                ptr::null(),
+                1,
+                &device,
+                ptr::null(),
+                ptr::null_mut(),
+                &mut err,
+            );
+            check(err)?;
+            let queue = clCreateCommandQueue(
+                context,
+                device,
+                CL_QUEUE_PROFILING_ENABLE,
+                &mut err,
+            );
+            if let Err(err) = check(err) {
+                clReleaseContext(context);
+                return Err(err);
+            }
+            let inner = ExecutorInner {
+                device,
+                context,
+                queue,
+            };
+            Ok(Executor {
+                inner: Arc::new(inner),
+            })
+        }
+    }
+
+    /// Queries a device parameter of type `T`.
+    fn device_info<T: Default>(&self, param: cl_uint) -> T {
+        let mut value = T::default();
+        unsafe {
+            let size = std::mem::size_of::<T>();
+            let value_ptr = &mut value as *mut T as *mut c_void;
+            let ret_size = ptr::null_mut();
+            let err =
+                clGetDeviceInfo(self.inner.device, param, size, value_ptr, ret_size);
+            check(err).unwrap();
+        }
+        value
+    }
+
+    /// Returns the name of the device.
+    pub fn device_name(&self) -> String {
+        let mut name = vec![0u8; 256];
+        let mut len = 0;
+        unsafe {
+            let name_ptr = name.as_mut_ptr() as *mut c_void;
+            let err = clGetDeviceInfo(
+                self.inner.device,
+                CL_DEVICE_NAME,
+                name.len(),
+                name_ptr,
+                &mut len,
+            );
+            check(err).unwrap();
+        }
+        name.truncate(len.saturating_sub(1));
+        String::from_utf8_lossy(&name).into_owned()
+    }
+
+    /// Returns the maximal number of work-items in a work-group.
+    pub fn max_work_group_size(&self) -> u32 {
+        self.device_info::<size_t>(CL_DEVICE_MAX_WORK_GROUP_SIZE) as u32
+    }
+
+    /// Returns the amount of local memory available to a work-group, in bytes.
+    pub fn local_mem_size(&self) -> u32 {
+        self.device_info::<cl_ulong>(CL_DEVICE_LOCAL_MEM_SIZE) as u32
+    }
+
+    /// Returns the number of compute units on the device.
+    pub fn max_compute_units(&self) -> u32 {
+        self.device_info::<cl_uint>(CL_DEVICE_MAX_COMPUTE_UNITS)
+    }
+
+    /// Returns the maximal clock frequency of the device, in MHz.
+    pub fn max_clock_frequency(&self) -> u32 {
+        self.device_info::<cl_uint>(CL_DEVICE_MAX_CLOCK_FREQUENCY)
+    }
+
+    /// Allocates a buffer of `size` bytes on the device.
+    pub fn allocate(&self, size: usize) -> Result<Buffer, Error> {
+        let mut err = CL_SUCCESS;
+        // OpenCL does not allow empty buffers.
+        let alloc_size = std::cmp::max(size, 1);
+        let mem = unsafe {
+            let context = self.inner.context;
+            let host_ptr = ptr::null_mut();
+            clCreateBuffer(context, CL_MEM_READ_WRITE, alloc_size, host_ptr, &mut err)
+        };
+        check(err)?;
+        Ok(Buffer {
+            executor: self.clone(),
+            mem,
+            size,
+        })
+    }
+
+    /// Compiles the kernel `name` from its OpenCL C source.
+    pub fn build_kernel(&self, source: &str, name: &str) -> Result<Kernel, Error> {
+        let source = CString::new(source).unwrap();
+        let name = CString::new(name).unwrap();
+        let mut err = CL_SUCCESS;
+        unsafe {
+            let context = self.inner.context;
+            let source_ptr = source.as_ptr();
+            let program =
+                clCreateProgramWithSource(context, 1, &source_ptr, ptr::null(), &mut err);
+            check(err)?;
+            let options = ptr::null();
+            let device = self.inner.device;
+            let build_err = clBuildProgram(
+                program,
+                1,
+                &device,
+                options,
+                ptr::null(),
+                ptr::null_mut(),
+            );
+            if build_err != CL_SUCCESS {
+                let log = self.build_log(program);
+                clReleaseProgram(program);
+                return Err(Error::Build(log));
+            }
+            let kernel = clCreateKernel(program, name.as_ptr(), &mut err);
+            if let Err(err) = check(err) {
+                clReleaseProgram(program);
+                return Err(err);
+            }
+            Ok(Kernel {
+                executor: self.clone(),
+                program,
+                kernel,
+            })
+        }
+    }
+
+    /// Returns the compilation log of a program.
+    unsafe fn build_log(&self, program: cl_program) -> String {
+        let device = self.inner.device;
+        let mut len = 0;
+        let param = CL_PROGRAM_BUILD_LOG;
+        clGetProgramBuildInfo(program, device, param, 0, ptr::null_mut(), &mut len);
+        let mut log = vec![0u8; len];
+        let log_ptr = log.as_mut_ptr() as *mut c_void;
+        clGetProgramBuildInfo(program, device, param, len, log_ptr, ptr::null_mut());
+        log.truncate(len.saturating_sub(1));
+        String::from_utf8_lossy(&log).into_owned()
+    }
+}
+
+/// A buffer allocated on the device.
+pub struct Buffer {
+    executor: Executor,
+    mem: cl_mem,
+    size: usize,
+}
+
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    /// Returns the size of the buffer, in bytes.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Copies the content of the buffer to the host.
+    pub fn read(&self) -> Result<Vec<i8>, Error> {
+        let mut data = vec![0i8; self.size];
+        if self.size > 0 {
+            unsafe {
+                let data_ptr = data.as_mut_ptr() as *mut c_void;
+                check(clEnqueueReadBuffer(
+                    self.executor.inner.queue,
+                    self.mem,
+                    CL_TRUE,
+                    0,
+                    self.size,
+                    data_ptr,
+                    0,
+                    ptr::null(),
+                    ptr::null_mut(),
+                ))?;
+            }
+        }
+        Ok(data)
+    }
+
+    /// Copies `data` to the buffer.
+    pub fn write(&self, data: &[i8]) -> Result<(), Error> {
+        assert_eq!(data.len(), self.size);
+        if self.size > 0 {
+            unsafe {
+                check(clEnqueueWriteBuffer(
+                    self.executor.inner.queue,
+                    self.mem,
+                    CL_TRUE,
+                    0,
+                    self.size,
+                    data.as_ptr() as *const c_void,
+                    0,
+                    ptr::null(),
+                    ptr::null_mut(),
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets all the bytes of the buffer to zero.
+    pub fn zero(&self) -> Result<(), Error> {
+        if self.size > 0 {
+            let pattern = 0u8;
+            unsafe {
+                let queue = self.executor.inner.queue;
+                check(clEnqueueFillBuffer(
+                    queue,
+                    self.mem,
+                    &pattern as *const u8 as *const c_void,
+                    1,
+                    0,
+                    self.size,
+                    0,
+                    ptr::null(),
+                    ptr::null_mut(),
+                ))?;
+                check(clFinish(queue))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            clReleaseMemObject(self.mem);
+        }
+    }
+}
+
+/// A compiled kernel.
+pub struct Kernel {
+    executor: Executor,
+    program: cl_program,
+    kernel: cl_kernel,
+}
+
+// Kernels are not thread-safe, but can be moved between threads.
+unsafe impl Send for Kernel {}
+
+impl Kernel {
+    /// Passes a buffer as the argument at `index`.
+    pub fn set_buffer_arg(&mut self, index: u32, buffer: &Buffer) -> Result<(), Error> {
+        let size = std::mem::size_of::<cl_mem>();
+        let value = &buffer.mem as *const cl_mem as *const c_void;
+        unsafe { check(clSetKernelArg(self.kernel, index, size, value)) }
+    }
+
+    /// Passes a scalar of `size` bytes, stored at `value`, as the argument at `index`.
+    pub fn set_scalar_arg(
+        &mut self,
+        index: u32,
+        size: usize,
+        value: *const c_void,
+    ) -> Result<(), Error> {
+        unsafe { check(clSetKernelArg(self.kernel, index, size, value)) }
+    }
+
+    /// Runs the kernel on `global` work-items, grouped by `local` work-items. Returns the
+    /// execution time, in nanoseconds.
+    pub fn execute(
+        &mut self,
+        global: [usize; 3],
+        local: [usize; 3],
+    ) -> Result<u64, Error> {
+        let mut event = ptr::null_mut();
+        unsafe {
+            check(clEnqueueNDRangeKernel(
+                self.executor.inner.queue,
+                self.kernel,
+                3,
+                ptr::null(),
+                global.as_ptr(),
+                local.as_ptr(),
+                0,
+                ptr::null(),
+                &mut event,
+            ))?;
+            let res = check(clWaitForEvents(1, &event)).and_then(|()| {
+                let start = profiling_info(event, CL_PROFILING_COMMAND_START)?;
+                let end = profiling_info(event, CL_PROFILING_COMMAND_END)?;
+                Ok(end - start)
+            });
+            clReleaseEvent(event);
+            res
+        }
+    }
+}
+
+/// Reads a timestamp of an event, in nanoseconds.
+unsafe fn profiling_info(event: cl_event, param: cl_uint) -> Result<u64, Error> {
+    let mut value: cl_ulong = 0;
+    let size = std::mem::size_of::<cl_ulong>();
+    let value_ptr = &mut value as *mut cl_ulong as *mut c_void;
+    check(clGetEventProfilingInfo(
+        event,
+        param,
+        size,
+        value_ptr,
+        ptr::null_mut(),
+    ))?;
+    Ok(value)
+}
+
+impl Drop for Kernel {
+    fn drop(&mut self) {
+        unsafe {
+            clReleaseKernel(self.kernel);
+            clReleaseProgram(self.program);
+        }
+    }
+}
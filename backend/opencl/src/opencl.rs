@@ -0,0 +1,285 @@
+//! Describes OpenCL devices.
+use crate::api;
+use crate::printer::OpenClPrinter;
+use fxhash::FxHashMap;
+use std::io::Write;
+use telamon::codegen::Function;
+use telamon::device::{self, vectorization::VectorRule};
+use telamon::ir::{self, Type};
+use telamon::model::{self, HwPressure};
+use telamon::search_space::{DimKind, InstFlag, MemSpace, SearchSpace};
+use utils::unwrap;
+
+/// Maximal number of threads in a block we consider, even if the device supports more.
+const MAX_THREADS: u32 = 1024;
+
+// The model below only knows what OpenCL reports about the device. Latencies, in cycles,
+// are optimistic for the devices we target so that the model remains a lower bound.
+
+/// Latency of simple arithmetic instructions on 32-bit values.
+const ALU_LATENCY: f64 = 4.0;
+/// Latency of divisions and transcendental functions on 32-bit values.
+const SLOW_ALU_LATENCY: f64 = 16.0;
+/// Latency of accesses to local memory.
+const LOCAL_MEM_LATENCY: f64 = 16.0;
+/// Latency of accesses to global memory.
+const GLOBAL_MEM_LATENCY: f64 = 100.0;
+/// Latency of work-group barriers.
+const BARRIER_LATENCY: f64 = 16.0;
+
+/// Describes an OpenCL device.
+#[derive(Clone, Debug)]
+pub struct OpenClDevice {
+    /// The name of the device.
+    pub name: String,
+    /// The maximal number of threads in a work-group.
+    pub max_threads: u32,
+    /// The amount of local memory available to a work-group, in bytes.
+    pub local_mem: u32,
+    /// The number of compute units of the device.
+    pub num_compute_units: u32,
+    /// The number of work-items that execute in lockstep. OpenCL does not expose it, so it
+    /// defaults to `1` and should be set for GPUs, for example to `64` for AMD wavefronts.
+    pub simt_width: u32,
+    /// The clock of the compute units, in GHz.
+    pub clock_rate: f64,
+}
+
+impl OpenClDevice {
+    /// Queries the description of the device used by `executor`.
+    pub(crate) fn from_executor(executor: &api::Executor) -> Self {
+        OpenClDevice {
+            name: executor.device_name(),
+            max_threads: std::cmp::min(executor.max_work_group_size(), MAX_THREADS),
+            local_mem: executor.local_mem_size(),
+            num_compute_units: executor.max_compute_units(),
+            simt_width: 1,
+            clock_rate: f64::from(executor.max_clock_frequency()) / 1000.,
+        }
+    }
+
+    /// Returns the pressure of an instruction on the issue, ALU and memory units, for
+    /// the given latency.
+    fn pressure(latency: f64, alu: f64, mem: f64) -> HwPressure {
+        HwPressure::new(latency, vec![1.0, alu, mem])
+    }
+
+    /// Returns the pressure of an instruction computing values of type `t`. Operations
+    /// on 64-bit values are modeled as taking twice as long.
+    fn alu_pressure(latency: f64, t: Option<Type>) -> HwPressure {
+        let factor = match t.and_then(Type::bitwidth) {
+            Some(64) => 2.0,
+            _ => 1.0,
+        };
+        Self::pressure(latency * factor, factor, 0.0)
+    }
+
+    /// Returns the overhead for a single instance of the instruction.
+    fn inst_pressure(&self, inst: &ir::Instruction) -> HwPressure {
+        use telamon::ir::Operator::*;
+        let t = inst.t();
+        match inst.operator() {
+            BinOp(ir::BinOp::Div, ..) => Self::alu_pressure(SLOW_ALU_LATENCY, t),
+            UnaryOp(ir::UnaryOp::Exp(..), ..) | UnaryOp(ir::UnaryOp::Sqrt(..), ..) => {
+                Self::alu_pressure(SLOW_ALU_LATENCY, t)
+            }
+            // Moves and casts are mostly folded by the compiler.
+            UnaryOp(..) => HwPressure::zero(self),
+            BinOp(..) | Mul(..) | Mad(..) | Mma(..) => Self::alu_pressure(ALU_LATENCY, t),
+            Ld(..) | St(..) => Self::pressure(GLOBAL_MEM_LATENCY, 0.0, 1.0),
+            TmpLd(..) | TmpSt(..) => Self::pressure(LOCAL_MEM_LATENCY, 0.0, 1.0),
+        }
+    }
+
+    /// Returns the overhead induced by all the iterations of a loop.
+    fn dim_pressure(&self, kind: DimKind, size: model::size::Range) -> HwPressure {
+        if kind == DimKind::LOOP {
+            let mut pressure = self.loop_iter_pressure(kind).0;
+            pressure.repeat_sequential(size.min as f64);
+            pressure
+        } else if DimKind::THREAD.contains(kind) {
+            // The repetition along the thread is taken into account by
+            // `num_unmapped_thread` as the current thread is accounted as not mapped.
+            self.loop_iter_pressure(kind).0
+        } else {
+            HwPressure::zero(self)
+        }
+    }
+
+    /// Returns the processing power of a compute unit, in units per nanosecond.
+    fn compute_unit_rates(&self) -> Vec<f64> {
+        let simt_width = f64::from(self.simt_width);
+        vec![
+            self.clock_rate * simt_width,
+            self.clock_rate * simt_width,
+            self.clock_rate,
+        ]
+    }
+}
+
+impl Default for OpenClDevice {
+    /// Describes a generic device, with the minimal capabilities required by OpenCL 1.2.
+    fn default() -> Self {
+        OpenClDevice {
+            name: "OpenCL".to_string(),
+            max_threads: 256,
+            local_mem: 32 * 1024,
+            num_compute_units: 1,
+            simt_width: 1,
+            clock_rate: 1.0,
+        }
+    }
+}
+
+impl device::Device for OpenClDevice {
+    fn print(&self, fun: &Function, out: &mut dyn Write) {
+        let source = unwrap!(OpenClPrinter::default().kernel(fun));
+        unwrap!(write!(out, "{}", source));
+    }
+
+    fn check_type(&self, t: Type) -> Result<(), ir::TypeError> {
+        match t {
+            Type::I(i) | Type::F(i) if i == 32 || i == 64 => Ok(()),
+            Type::I(i) if i == 1 || i == 8 || i == 16 => Ok(()),
+            Type::PtrTo(_) => Ok(()),
+            t => Err(ir::TypeError::InvalidType { t }),
+        }
+    }
+
+    fn max_block_dims(&self) -> u32 {
+        3
+    }
+
+    fn max_inner_block_size(&self) -> u32 {
+        65535
+    }
+
+    fn max_threads(&self) -> u32 {
+        self.max_threads
+    }
+
+    fn max_unrolling(&self) -> u32 {
+        512
+    }
+
+    fn has_vector_registers(&self) -> bool {
+        false
+    }
+
+    fn can_vectorize(&self, _dim: &ir::Dimension, _op: &ir::Operator) -> bool {
+        // TODO(opencl): use OpenCL vector types.
+        false
+    }
+
+    fn vectorization_rules(&self) -> &[VectorRule] {
+        // Operators without a rule are not vectorized.
+        &[]
+    }
+
+    fn shared_mem(&self) -> u32 {
+        self.local_mem
+    }
+
+    fn pointer_type(&self, _: MemSpace) -> ir::Type {
+        // Pointers are printed as `intptr_t` and cast to the right address space on each
+        // access. Use 0 as a dummy memory ID.
+        ir::Type::PtrTo(ir::MemId(0))
+    }
+
+    fn supported_mem_flags(&self, op: &ir::Operator) -> InstFlag {
+        match op {
+            ir::Operator::Ld(..)
+            | ir::Operator::St(..)
+            | ir::Operator::TmpLd(..)
+            | ir::Operator::TmpSt(..) => InstFlag::BLOCK_COHERENT,
+            _ => panic!("not a memory operation"),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hw_pressure(
+        &self,
+        space: &SearchSpace,
+        dim_sizes: &FxHashMap<ir::DimId, model::size::Range>,
+        _: &FxHashMap<ir::StmtId, model::Nesting>,
+        stmt: &dyn ir::Statement,
+        _: &dyn device::Context,
+    ) -> model::HwPressure {
+        if let Some(inst) = stmt.as_inst() {
+            self.inst_pressure(inst)
+        } else if let Some(dim) = stmt.as_dim() {
+            let kind = space.domain().get_dim_kind(dim.id());
+            self.dim_pressure(kind, dim_sizes[&dim.id()])
+        } else {
+            panic!()
+        }
+    }
+
+    fn loop_iter_pressure(&self, kind: DimKind) -> (HwPressure, HwPressure) {
+        if kind == DimKind::LOOP {
+            // Each iteration increments and compares the index, then branches.
+            let iter = Self::pressure(2.0 * ALU_LATENCY, 2.0, 0.0);
+            let end = HwPressure::new(ALU_LATENCY, vec![0.0; 3]);
+            (iter, end)
+        } else if DimKind::THREAD.contains(kind) {
+            let barrier = Self::pressure(BARRIER_LATENCY, 0.0, 0.0);
+            (barrier, HwPressure::zero(self))
+        } else {
+            (HwPressure::zero(self), HwPressure::zero(self))
+        }
+    }
+
+    fn thread_rates(&self) -> HwPressure {
+        HwPressure::new(self.clock_rate, vec![self.clock_rate; 3])
+    }
+
+    fn block_rates(&self) -> HwPressure {
+        HwPressure::new(self.clock_rate, self.compute_unit_rates())
+    }
+
+    fn total_rates(&self) -> HwPressure {
+        let num_compute_units = f64::from(self.num_compute_units);
+        let rates = self.compute_unit_rates();
+        let rates = rates.into_iter().map(|r| r * num_compute_units).collect();
+        HwPressure::new(self.clock_rate, rates)
+    }
+
+    fn bottlenecks(&self) -> &[&'static str] {
+        &["issue", "alu", "mem"]
+    }
+
+    fn block_parallelism(&self, _space: &SearchSpace) -> u32 {
+        self.num_compute_units
+    }
+
+    fn additive_indvar_pressure(&self, t: &ir::Type) -> HwPressure {
+        Self::alu_pressure(ALU_LATENCY, Some(*t))
+    }
+
+    fn multiplicative_indvar_pressure(&self, t: &ir::Type) -> HwPressure {
+        Self::alu_pressure(ALU_LATENCY, Some(*t))
+    }
+
+    fn add_block_overhead(
+        &self,
+        max_active_threads: model::size::FactorRange,
+        _: model::size::FactorRange,
+        _: model::size::Range,
+        pressure: &mut HwPressure,
+    ) {
+        // Work-items of a partially filled SIMT group still occupy the whole group.
+        let ratio = model::simt_waste_ratio(self, max_active_threads.lcm);
+        pressure.multiply(&HwPressure::new(1.0, vec![ratio; 3]));
+    }
+
+    fn lower_type(&self, t: ir::Type, _space: &SearchSpace) -> Option<ir::Type> {
+        Some(t)
+    }
+
+    fn simt_width(&self) -> u32 {
+        self.simt_width
+    }
+}
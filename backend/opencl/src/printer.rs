@@ -0,0 +1,217 @@
+//! Prints OpenCL C kernels.
+use crate::NameGenerator;
+use itertools::Itertools;
+use std::fmt::Write as WriteFmt;
+use telamon::codegen::*;
+use telamon::ir::Type;
+use telamon::search_space::DimKind;
+use telamon_c::C99Display as _;
+use utils::unwrap;
+
+#[derive(Default)]
+pub struct OpenClPrinter {
+    buffer: CodeBuffer,
+}
+
+/// Returns the type of a kernel parameter.
+fn param_t(param: &ParamVal) -> String {
+    match param {
+        ParamVal::External(param, _) if param.elem_t.is_some() => {
+            "__global void*".to_string()
+        }
+        ParamVal::External(_, par_type) => par_type.c99().to_string(),
+        ParamVal::Size(_) => "uint32_t".to_string(),
+        ParamVal::GlobalMem(..) => "__global void*".to_string(),
+    }
+}
+
+/// Returns the address space qualifier of a state space.
+fn address_space(ss: llir::StateSpace) -> &'static str {
+    match ss {
+        llir::StateSpace::Global => "__global",
+        llir::StateSpace::Shared => "__local",
+    }
+}
+
+impl OpenClPrinter {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        OpenClPrinter {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+        }
+    }
+
+    /// Declares all parameters of the function with the appropriate type
+    fn param_decl(&self, param: &ParamVal) -> String {
+        format!("{} {}", param_t(param), param.key().ident())
+    }
+
+    /// Declared all variables that have been required from the namegen
+    fn var_decls(&self, namegen: &NameGenerator) -> String {
+        let print_decl = |(&t, &n)| {
+            let prefix = NameGenerator::gen_prefix(t);
+            let names = (0..n).map(|i| format!("{}{}", prefix, i)).join(", ");
+            format!("{} {};\n  ", t.c99(), names)
+        };
+        let other_var_decl = namegen.num_var.iter().map(print_decl).join("\n  ");
+        if namegen.num_glob_ptr == 0 {
+            other_var_decl
+        } else {
+            let names = (0..namegen.num_glob_ptr)
+                .map(|i| format!("ptr{}", i))
+                .join(", ");
+            format!("intptr_t {};\n  {}", names, other_var_decl)
+        }
+    }
+
+    /// Declares block and thread indexes.
+    fn decl_par_indexes(&self, function: &Function, name_map: &NameMap<'_>) -> String {
+        let mut decls = vec![];
+        // Load block indexes.
+        for (ind, dim) in function.block_dims().iter().enumerate() {
+            let index = name_map.name_index(dim.id());
+            decls.push(format!("{} = get_group_id({});", index.c99(), ind));
+        }
+        // Compute thread indexes.
+        for (ind, dim) in function.thread_dims().iter().rev().enumerate() {
+            let index = name_map.name_index(dim.id());
+            decls.push(format!("{} = get_local_id({});", index.c99(), ind));
+        }
+        decls.join("\n  ")
+    }
+
+    /// Declares a block of local memory.
+    fn shared_mem_decl(&mut self, block: &MemoryRegion, name_map: &NameMap<'_>) {
+        unwrap!(writeln!(
+            self.buffer,
+            "__local char shmem{id}[{size}] __attribute__((aligned(16)));
+{name} = (intptr_t)shmem{id};",
+            id = block.id().0,
+            name = name_map.name_addr(block.id()).c99(),
+            size = unwrap!(block.alloc_size().as_int()),
+        ));
+    }
+
+    /// Prints the OpenCL C code of a kernel.  Fails if the code exceeds the size limit of
+    /// the printer.
+    pub fn kernel<'a: 'b, 'b>(
+        &mut self,
+        function: &'b Function<'a>,
+    ) -> Result<String, CodeSizeError> {
+        let mut namegen = NameGenerator::default();
+        let interner = Interner::default();
+        let name_map = &mut NameMap::new(&interner, function, &mut namegen);
+
+        let param_decls = function
+            .device_code_args()
+            .map(|v| self.param_decl(v))
+            .join(",\n  ");
+        let mut return_string = format!(
+            include_str!("template/kernel.cl.template"),
+            name = function.name(),
+            params = param_decls
+        );
+        // INDEX LOADS
+        let idx_loads = self.decl_par_indexes(function, name_map);
+        unwrap!(writeln!(self.buffer, "{}", idx_loads));
+        // LOAD PARAM
+        for val in function.device_code_args() {
+            let var_name = name_map.name_param_val(val.key());
+            unwrap!(writeln!(
+                self.buffer,
+                "{var_name} = {cast}{name}; // {param}",
+                cast = if val.elem_t().is_some() {
+                    "(intptr_t)"
+                } else {
+                    ""
+                },
+                var_name = var_name.c99(),
+                name = val.key().ident(),
+                param = val.key(),
+            ));
+        }
+        // MEM DECL
+        for block in function.mem_blocks() {
+            match block.alloc_scheme() {
+                AllocationScheme::Shared => self.shared_mem_decl(block, name_map),
+                AllocationScheme::PrivatisedGlobal => {
+                    Printer::new(self, name_map).privatise_global_block(block, function)
+                }
+                AllocationScheme::Global => (),
+            }
+        }
+        // Compute size casts
+        for dim in function.dimensions() {
+            if !dim.kind().intersects(DimKind::UNROLL | DimKind::LOOP) {
+                continue;
+            }
+            for level in dim.induction_levels() {
+                if let Some((_, ref incr)) = level.increment {
+                    let reg = name_map.declare_size_cast(incr, level.t());
+                    if let Some(reg) = reg {
+                        let old_name = name_map.name_size(incr, Type::I(32));
+                        self.print_inst(
+                            llir::Instruction::cast(level.t(), reg, old_name)
+                                .unwrap()
+                                .into(),
+                        );
+                    }
+                }
+            }
+        }
+        // INIT
+        let ind_levels = function.init_induction_levels().iter().chain(
+            function
+                .block_dims()
+                .iter()
+                .flat_map(|d| d.induction_levels()),
+        );
+        for level in ind_levels {
+            Printer::new(self, name_map).parallel_induction_level(level);
+        }
+        // BODY
+        Printer::new(self, name_map).cfg(function, function.cfg());
+        let var_decls = self.var_decls(&namegen);
+        let body = self.buffer.take()?;
+        return_string.push_str("  ");
+        return_string.push_str(&var_decls);
+        return_string.push_str(&body);
+        return_string.push_str("}\n");
+        Ok(return_string)
+    }
+}
+
+impl InstPrinter for OpenClPrinter {
+    fn print_label(&mut self, label: llir::Label<'_>) {
+        writeln!(self.buffer, "{}", label.c99()).unwrap()
+    }
+
+    fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
+        // Memory accesses need an explicit address space, that the C99 printer ignores.
+        let access = match inst.instruction {
+            llir::Instruction::Load(spec, d, a) => format!(
+                "{d} = *({ss} {t}*)({a})",
+                d = d.c99(),
+                ss = address_space(spec.state_space()),
+                t = spec.t().c99(),
+                a = a.c99(),
+            ),
+            llir::Instruction::Store(spec, a, [b]) => format!(
+                "*({ss} {t}*)({a}) = {b}",
+                ss = address_space(spec.state_space()),
+                t = spec.t().c99(),
+                a = a.c99(),
+                b = b.c99(),
+            ),
+            _ => return writeln!(self.buffer, "{}", inst.c99()).unwrap(),
+        };
+        if let Some(predicate) = inst.predicate {
+            write!(self.buffer, "if ({}) ", predicate.c99()).unwrap();
+        }
+        writeln!(self.buffer, "{};", access).unwrap();
+    }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
+}
@@ -0,0 +1,100 @@
+//! A fake Vulkan runtime, with the same interface as the real one. It allows compiling the
+//! crate on machines without Vulkan, but panics as soon as the device is used.
+use std::fmt;
+
+/// An error returned by the Vulkan runtime.
+#[derive(Debug)]
+pub enum Error {
+    /// No Vulkan device is available.
+    NoDevice,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "Vulkan support is disabled, enable the `real_vulkan` feature"
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone)]
+pub struct Executor;
+
+impl Executor {
+    pub fn init() -> Result<Self, Error> {
+        Err(Error::NoDevice)
+    }
+
+    pub fn device_name(&self) -> String {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn max_work_group_invocations(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn max_shared_memory_size(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn max_push_constants_size(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn subgroup_size(&self) -> u32 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn supports_float64(&self) -> bool {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn allocate(&self, _: usize) -> Result<Buffer, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn build_kernel(
+        &self,
+        _: &str,
+        _: &str,
+        _: [u32; 3],
+        _: u32,
+    ) -> Result<Kernel, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+}
+
+pub struct Buffer;
+
+impl Buffer {
+    pub fn len(&self) -> usize {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn address(&self) -> u64 {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn read(&self) -> Result<Vec<i8>, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn write(&self, _: &[i8]) -> Result<(), Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+
+    pub fn zero(&self) -> Result<(), Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+}
+
+pub struct Kernel;
+
+impl Kernel {
+    pub fn execute(&mut self, _: &[u8], _: [u32; 3]) -> Result<u64, Error> {
+        unreachable!("the fake executor cannot be created")
+    }
+}
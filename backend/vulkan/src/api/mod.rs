@@ -0,0 +1,521 @@
+//! Safe wrappers around the subset of the Vulkan 1.2 API needed to run compute shaders.
+use ash::version::{DeviceV1_0, DeviceV1_2, EntryV1_0, InstanceV1_0, InstanceV1_1};
+use ash::vk;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// An error returned by the Vulkan runtime.
+#[derive(Debug)]
+pub enum Error {
+    /// A Vulkan call returned an error code.
+    Vulkan(vk::Result),
+    /// The Vulkan library could not be loaded or initialized.
+    Loading(String),
+    /// The compilation of a shader failed.
+    Build(String),
+    /// No device supports the features needed by Telamon.
+    NoDevice,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Vulkan(code) => write!(fmt, "Vulkan error {}", code),
+            Error::Loading(err) => write!(fmt, "unable to initialize Vulkan: {}", err),
+            Error::Build(log) => write!(fmt, "shader compilation failed:\n{}", log),
+            Error::NoDevice => write!(
+                fmt,
+                "no Vulkan device with compute queues, timestamps, 64-bit integers and \
+                 buffer device addresses found"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<vk::Result> for Error {
+    fn from(code: vk::Result) -> Self {
+        Error::Vulkan(code)
+    }
+}
+
+/// The Vulkan objects shared by all the handles on a device.
+struct ExecutorInner {
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    device: ash::Device,
+    /// Queues must be externally synchronized.
+    queue: Mutex<vk::Queue>,
+    queue_family: u32,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    properties: vk::PhysicalDeviceProperties,
+    subgroup_size: u32,
+    float64: bool,
+}
+
+impl Drop for ExecutorInner {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// Executes code on a Vulkan device.
+#[derive(Clone)]
+pub struct Executor {
+    inner: Arc<ExecutorInner>,
+}
+
+/// Returns the index of a queue family of `physical_device` that supports compute and
+/// timestamps, if the device supports the features needed by Telamon.
+unsafe fn compute_queue_family(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Option<u32> {
+    let mut features12 = vk::PhysicalDeviceVulkan12Features::default();
+    let mut features = vk::PhysicalDeviceFeatures2::builder()
+        .push_next(&mut features12)
+        .build();
+    instance.get_physical_device_features2(physical_device, &mut features);
+    if features.features.shader_int64 != vk::TRUE
+        || features12.buffer_device_address != vk::TRUE
+    {
+        return None;
+    }
+    instance
+        .get_physical_device_queue_family_properties(physical_device)
+        .iter()
+        .position(|family| {
+            family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                && family.timestamp_valid_bits > 0
+        })
+        .map(|family| family as u32)
+}
+
+impl Executor {
+    /// Creates an executor on the first Vulkan device that supports the features needed by
+    /// Telamon.
+    pub fn init() -> Result<Self, Error> {
+        unsafe {
+            let entry =
+                ash::Entry::new().map_err(|err| Error::Loading(format!("{:?}", err)))?;
+            let app_info =
+                vk::ApplicationInfo::builder().api_version(vk::make_version(1, 2, 0));
+            let instance_info =
+                vk::InstanceCreateInfo::builder().application_info(&app_info);
+            let instance = entry
+                .create_instance(&instance_info, None)
+                .map_err(|err| Error::Loading(format!("{:?}", err)))?;
+            let (physical_device, queue_family) = instance
+                .enumerate_physical_devices()?
+                .into_iter()
+                .find_map(|physical_device| {
+                    compute_queue_family(&instance, physical_device)
+                        .map(|family| (physical_device, family))
+                })
+                .ok_or(Error::NoDevice)?;
+
+            let mut subgroup = vk::PhysicalDeviceSubgroupProperties::default();
+            let mut properties = vk::PhysicalDeviceProperties2::builder()
+                .push_next(&mut subgroup)
+                .build();
+            instance.get_physical_device_properties2(physical_device, &mut properties);
+            let float64 = instance
+                .get_physical_device_features(physical_device)
+                .shader_float64
+                == vk::TRUE;
+
+            let priorities = [1.0];
+            let queue_infos = [vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_family)
+                .queue_priorities(&priorities)
+                .build()];
+            let features = vk::PhysicalDeviceFeatures::builder()
+                .shader_int64(true)
+                .shader_float64(float64);
+            let mut features12 =
+                vk::PhysicalDeviceVulkan12Features::builder().buffer_device_address(true);
+            let device_info = vk::DeviceCreateInfo::builder()
+                .queue_create_infos(&queue_infos)
+                .enabled_features(&features)
+                .push_next(&mut features12);
+            let device = instance.create_device(physical_device, &device_info, None)?;
+            let queue = device.get_device_queue(queue_family, 0);
+            let memory_properties =
+                instance.get_physical_device_memory_properties(physical_device);
+            Ok(Executor {
+                inner: Arc::new(ExecutorInner {
+                    _entry: entry,
+                    instance,
+                    device,
+                    queue: Mutex::new(queue),
+                    queue_family,
+                    memory_properties,
+                    properties: properties.properties,
+                    subgroup_size: subgroup.subgroup_size,
+                    float64,
+                }),
+            })
+        }
+    }
+
+    /// Returns the name of the device.
+    pub fn device_name(&self) -> String {
+        let name = &self.inner.properties.device_name;
+        unsafe { CStr::from_ptr(name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Returns the maximal number of invocations in a work-group.
+    pub fn max_work_group_invocations(&self) -> u32 {
+        self.inner
+            .properties
+            .limits
+            .max_compute_work_group_invocations
+    }
+
+    /// Returns the amount of shared memory available to a work-group, in bytes.
+    pub fn max_shared_memory_size(&self) -> u32 {
+        self.inner.properties.limits.max_compute_shared_memory_size
+    }
+
+    /// Returns the maximal size of the push constants of a kernel, in bytes.
+    pub fn max_push_constants_size(&self) -> u32 {
+        self.inner.properties.limits.max_push_constants_size
+    }
+
+    /// Returns the number of invocations in a subgroup.
+    pub fn subgroup_size(&self) -> u32 {
+        self.inner.subgroup_size
+    }
+
+    /// Indicates if shaders can use 64-bit floats.
+    pub fn supports_float64(&self) -> bool {
+        self.inner.float64
+    }
+
+    /// Finds a memory type compatible with `type_bits` and mapped on the host.  Memory that
+    /// is also local to the device is preferred.
+    fn memory_type(&self, type_bits: u32) -> Option<u32> {
+        let properties = &self.inner.memory_properties;
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE
+            | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let candidates = [host | vk::MemoryPropertyFlags::DEVICE_LOCAL, host];
+        candidates.iter().find_map(|&flags| {
+            (0..properties.memory_type_count).find(|&index| {
+                let memory_type = properties.memory_types[index as usize];
+                type_bits & (1 << index) != 0
+                    && memory_type.property_flags.contains(flags)
+            })
+        })
+    }
+
+    /// Allocates a buffer of `size` bytes on the device.
+    ///
+    /// The buffer is mapped on the host to simplify transfers.
+    // TODO(vulkan): use a staging buffer when the device has no host-visible local memory.
+    pub fn allocate(&self, size: usize) -> Result<Buffer, Error> {
+        let device = &self.inner.device;
+        unsafe {
+            let buffer_info = vk::BufferCreateInfo::builder()
+                // Vulkan does not support empty buffers.
+                .size(std::cmp::max(size, 1) as u64)
+                .usage(
+                    vk::BufferUsageFlags::STORAGE_BUFFER
+                        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                )
+                .sharing_mode(vk::SharingMode::EXCLUSIVE);
+            let buffer = device.create_buffer(&buffer_info, None)?;
+            let requirements = device.get_buffer_memory_requirements(buffer);
+            let memory_type = self
+                .memory_type(requirements.memory_type_bits)
+                .ok_or(Error::Vulkan(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY))?;
+            let mut flags_info = vk::MemoryAllocateFlagsInfo::builder()
+                .flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+            let alloc_info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(memory_type)
+                .push_next(&mut flags_info);
+            let memory = device.allocate_memory(&alloc_info, None)?;
+            device.bind_buffer_memory(buffer, memory, 0)?;
+            let data = device.map_memory(
+                memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+            )? as *mut i8;
+            let address_info = vk::BufferDeviceAddressInfo::builder().buffer(buffer);
+            let address = device.get_buffer_device_address(&address_info);
+            Ok(Buffer {
+                executor: self.clone(),
+                buffer,
+                memory,
+                data,
+                address,
+                size,
+            })
+        }
+    }
+
+    /// Compiles a GLSL compute shader for work-groups of size `local_size`, with
+    /// `push_constants_size` bytes of push constants.
+    pub fn build_kernel(
+        &self,
+        source: &str,
+        name: &str,
+        local_size: [u32; 3],
+        push_constants_size: u32,
+    ) -> Result<Kernel, Error> {
+        let spirv = compile(source, name)?;
+        let device = &self.inner.device;
+        unsafe {
+            let module_info = vk::ShaderModuleCreateInfo::builder().code(&spirv);
+            let module = device.create_shader_module(&module_info, None)?;
+
+            let push_constant_ranges = [vk::PushConstantRange::builder()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(push_constants_size)
+                .build()];
+            let num_ranges = if push_constants_size > 0 { 1 } else { 0 };
+            let layout_info = vk::PipelineLayoutCreateInfo::builder()
+                .push_constant_ranges(&push_constant_ranges[..num_ranges]);
+            let layout = device.create_pipeline_layout(&layout_info, None)?;
+
+            // The work-group size is given by specialization constants 0, 1 and 2.
+            let map_entries = (0..3)
+                .map(|id| vk::SpecializationMapEntry {
+                    constant_id: id,
+                    offset: 4 * id,
+                    size: 4,
+                })
+                .collect::<Vec<_>>();
+            let data = local_size
+                .iter()
+                .flat_map(|size| size.to_ne_bytes().to_vec())
+                .collect::<Vec<_>>();
+            let specialization = vk::SpecializationInfo::builder()
+                .map_entries(&map_entries)
+                .data(&data);
+            let entry_point = CString::new("main").unwrap();
+            let stage = vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::COMPUTE)
+                .module(module)
+                .name(&entry_point)
+                .specialization_info(&specialization);
+            let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+                .stage(*stage)
+                .layout(layout)
+                .build();
+            let pipelines = device.create_compute_pipelines(
+                vk::PipelineCache::null(),
+                &[pipeline_info],
+                None,
+            );
+            device.destroy_shader_module(module, None);
+            let pipeline = pipelines.map_err(|(_, code)| code)?[0];
+
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .queue_family_index(self.inner.queue_family)
+                .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+            let command_pool = device.create_command_pool(&pool_info, None)?;
+            let command_buffer_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1);
+            let command_buffer =
+                device.allocate_command_buffers(&command_buffer_info)?[0];
+            let query_pool_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::TIMESTAMP)
+                .query_count(2);
+            let query_pool = device.create_query_pool(&query_pool_info, None)?;
+            let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+            Ok(Kernel {
+                executor: self.clone(),
+                layout,
+                pipeline,
+                command_pool,
+                command_buffer,
+                query_pool,
+                fence,
+            })
+        }
+    }
+}
+
+/// Compiles a GLSL compute shader to SPIR-V.
+fn compile(source: &str, name: &str) -> Result<Vec<u32>, Error> {
+    let init_error = || Error::Build("unable to initialize shaderc".to_string());
+    let mut compiler = shaderc::Compiler::new().ok_or_else(init_error)?;
+    let mut options = shaderc::CompileOptions::new().ok_or_else(init_error)?;
+    options.set_target_env(
+        shaderc::TargetEnv::Vulkan,
+        shaderc::EnvVersion::Vulkan1_2 as u32,
+    );
+    options.set_optimization_level(shaderc::OptimizationLevel::Performance);
+    let artifact = compiler
+        .compile_into_spirv(
+            source,
+            shaderc::ShaderKind::Compute,
+            name,
+            "main",
+            Some(&options),
+        )
+        .map_err(|err| Error::Build(err.to_string()))?;
+    Ok(artifact.as_binary().to_vec())
+}
+
+/// A buffer allocated on the device and mapped on the host.
+pub struct Buffer {
+    executor: Executor,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    data: *mut i8,
+    address: vk::DeviceAddress,
+    size: usize,
+}
+
+// The mapped memory is only accessed by the host when no kernel is running.
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    /// Returns the size of the buffer, in bytes.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the address of the buffer on the device.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Copies the content of the buffer to the host.
+    pub fn read(&self) -> Result<Vec<i8>, Error> {
+        let data = unsafe { std::slice::from_raw_parts(self.data, self.size) };
+        Ok(data.to_vec())
+    }
+
+    /// Copies `data` to the buffer.
+    pub fn write(&self, data: &[i8]) -> Result<(), Error> {
+        assert_eq!(data.len(), self.size);
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), self.data, self.size) };
+        Ok(())
+    }
+
+    /// Sets all the bytes of the buffer to zero.
+    pub fn zero(&self) -> Result<(), Error> {
+        unsafe { std::ptr::write_bytes(self.data, 0, self.size) };
+        Ok(())
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let device = &self.executor.inner.device;
+        unsafe {
+            device.unmap_memory(self.memory);
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// A compiled kernel, with the objects needed to launch it.
+pub struct Kernel {
+    executor: Executor,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    query_pool: vk::QueryPool,
+    fence: vk::Fence,
+}
+
+impl Kernel {
+    /// Runs `num_groups` work-groups of the kernel and returns the execution time in
+    /// nanoseconds, as measured by the device.
+    pub fn execute(
+        &mut self,
+        push_constants: &[u8],
+        num_groups: [u32; 3],
+    ) -> Result<u64, Error> {
+        let inner = &self.executor.inner;
+        let device = &inner.device;
+        let cmd = self.command_buffer;
+        unsafe {
+            device.reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())?;
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            device.begin_command_buffer(cmd, &begin_info)?;
+            device.cmd_reset_query_pool(cmd, self.query_pool, 0, 2);
+            device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            if !push_constants.is_empty() {
+                device.cmd_push_constants(
+                    cmd,
+                    self.layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    push_constants,
+                );
+            }
+            let [x, y, z] = num_groups;
+            device.cmd_write_timestamp(
+                cmd,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool,
+                0,
+            );
+            device.cmd_dispatch(cmd, x, y, z);
+            device.cmd_write_timestamp(
+                cmd,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool,
+                1,
+            );
+            device.end_command_buffer(cmd)?;
+
+            let command_buffers = [cmd];
+            let submit_info = vk::SubmitInfo::builder()
+                .command_buffers(&command_buffers)
+                .build();
+            {
+                let queue = inner.queue.lock().unwrap();
+                device.queue_submit(*queue, &[submit_info], self.fence)?;
+            }
+            device.wait_for_fences(&[self.fence], true, std::u64::MAX)?;
+            device.reset_fences(&[self.fence])?;
+
+            let mut timestamps = [0u64; 2];
+            device.get_query_pool_results(
+                self.query_pool,
+                0,
+                2,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+            )?;
+            let ticks = timestamps[1].wrapping_sub(timestamps[0]);
+            let period = f64::from(inner.properties.limits.timestamp_period);
+            Ok((ticks as f64 * period) as u64)
+        }
+    }
+}
+
+impl Drop for Kernel {
+    fn drop(&mut self) {
+        let device = &self.executor.inner.device;
+        unsafe {
+            device.destroy_fence(self.fence, None);
+            device.destroy_query_pool(self.query_pool, None);
+            device.destroy_command_pool(self.command_pool, None);
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
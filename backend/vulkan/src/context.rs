@@ -0,0 +1,408 @@
+//! Vulkan evaluation context.
+use crate::api;
+use crate::printer::{self, VulkanPrinter};
+use crate::vulkan::VulkanDevice;
+use crossbeam;
+use fxhash::FxHashMap;
+use log::warn;
+use std::fmt;
+use std::sync::{mpsc, Arc};
+use telamon::codegen::{CodeSizeError, Function, ParamVal};
+use telamon::device::{
+    self, ArrayArgument, AsyncCallback, Context as ContextTrait, EvalMode,
+    KernelEvaluator, ParamUpdateError, ScalarArgument,
+};
+use telamon::explorer;
+use telamon::ir;
+use utils::unwrap;
+
+/// Max number of candidates waiting to be evaluated.
+const EVAL_BUFFER_SIZE: usize = 100;
+
+/// Max size in bytes of the code generated for candidates during the search.  Candidates
+/// with a bigger code are not evaluated.
+const MAX_CODE_SIZE: usize = 64 << 20;
+
+/// A value that can be passed to kernels.
+trait Argument: Sync + Send {
+    /// Writes the argument in the push constants, at the beginning of `out`.
+    fn push(&self, out: &mut [u8]);
+    /// Returns the argument value if it can represent a size.
+    fn as_size(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl Argument for Box<dyn ScalarArgument> {
+    fn push(&self, out: &mut [u8]) {
+        let size = unwrap!(self.get_type().len_byte()) as usize;
+        let value = ScalarArgument::raw_ptr(&**self as &dyn ScalarArgument);
+        let bytes = unsafe { std::slice::from_raw_parts(value as *const u8, size) };
+        out[..size].copy_from_slice(bytes);
+    }
+
+    fn as_size(&self) -> Option<u32> {
+        ScalarArgument::as_size(&**self as &dyn ScalarArgument)
+    }
+}
+
+/// An array allocated on the device.
+struct Array(api::Buffer);
+
+impl ArrayArgument for Array {
+    fn read_i8(&self) -> Vec<i8> {
+        unwrap!(self.0.read())
+    }
+
+    fn write_i8(&self, slice: &[i8]) {
+        unwrap!(self.0.write(slice));
+    }
+
+    fn zero(&self) {
+        unwrap!(self.0.zero());
+    }
+}
+
+impl Argument for Array {
+    fn push(&self, out: &mut [u8]) {
+        out[..8].copy_from_slice(&self.0.address().to_ne_bytes());
+    }
+
+    fn as_size(&self) -> Option<u32> {
+        Some(self.0.len() as u32)
+    }
+}
+
+/// Vulkan evaluation context.
+pub struct Context {
+    device: Arc<VulkanDevice>,
+    executor: api::Executor,
+    parameters: FxHashMap<String, Arc<dyn Argument>>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Context {
+    /// Creates a new `Context` on the first Vulkan device that supports the features
+    /// required by Telamon.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such device is found.
+    pub fn new() -> Self {
+        let executor = api::Executor::init().unwrap_or_else(|err| panic!("{}", err));
+        Context {
+            device: Arc::new(VulkanDevice::from_executor(&executor)),
+            executor,
+            parameters: FxHashMap::default(),
+        }
+    }
+
+    fn bind_param(&mut self, name: String, value: Arc<dyn Argument>) {
+        self.parameters.insert(name, value);
+    }
+
+    /// Compiles a kernel and computes its arguments.
+    fn compile(
+        &self,
+        fun: &Function,
+        mut printer: VulkanPrinter,
+    ) -> Result<Code, CompileError> {
+        let (offsets, push_constants_size) = printer::push_constants_layout(fun);
+        if push_constants_size > self.device.max_push_constants {
+            return Err(CompileError::PushConstants {
+                size: push_constants_size,
+                limit: self.device.max_push_constants,
+            });
+        }
+        let source = printer.kernel(fun)?;
+        // Thread dimensions are mapped to invocations in reverse order, as in the printer.
+        let mut local_size = [1; 3];
+        for (i, dim) in fun.thread_dims().iter().rev().enumerate() {
+            local_size[i] = self.eval_size(dim.size());
+        }
+        let mut num_groups = [1; 3];
+        for (i, dim) in fun.block_dims().iter().enumerate() {
+            num_groups[i] = self.eval_size(dim.size());
+        }
+        let kernel = self.executor.build_kernel(
+            &source,
+            fun.name(),
+            local_size,
+            push_constants_size,
+        )?;
+        let mut push_constants = vec![0; push_constants_size as usize];
+        let mut temporaries = vec![];
+        let updatable = fun.updatable_params().collect::<Vec<_>>();
+        let mut updatable_params = vec![];
+        for (arg, offset) in fun.device_code_args().zip(offsets) {
+            let out = &mut push_constants[offset as usize..];
+            match arg {
+                ParamVal::External(param, _) => {
+                    if updatable.contains(&&**param) {
+                        updatable_params.push((ir::Parameter::clone(param), offset));
+                    }
+                    self.parameters[&param.name].push(out)
+                }
+                ParamVal::GlobalMem(_, size, _) => {
+                    let size = self.eval_size(size) as usize;
+                    let buffer = unwrap!(self.executor.allocate(size));
+                    out[..8].copy_from_slice(&buffer.address().to_ne_bytes());
+                    temporaries.push(buffer);
+                }
+                ParamVal::Size(size) => {
+                    let size = self.eval_size(size);
+                    out[..4].copy_from_slice(&size.to_ne_bytes());
+                }
+            }
+        }
+        Ok(Code {
+            source,
+            kernel,
+            push_constants,
+            updatable_params,
+            num_groups,
+            _temporaries: temporaries,
+        })
+    }
+}
+
+impl device::Context for Context {
+    fn device(&self) -> Arc<dyn device::Device> {
+        Arc::<VulkanDevice>::clone(&self.device)
+    }
+
+    fn benchmark(&self, fun: &Function, num_samples: usize) -> Vec<f64> {
+        match self.compile(fun, VulkanPrinter::default()) {
+            Ok(mut code) => (0..num_samples).filter_map(|_| code.evaluate()).collect(),
+            Err(err) => {
+                warn!("cannot benchmark kernel {}: {}", fun.name(), err);
+                vec![]
+            }
+        }
+    }
+
+    fn evaluate(&self, fun: &Function, _mode: EvalMode) -> Result<f64, ()> {
+        match self.compile(fun, VulkanPrinter::default()) {
+            Ok(mut code) => code.evaluate().ok_or(()),
+            Err(err) => {
+                warn!("cannot evaluate kernel {}: {}", fun.name(), err);
+                Err(())
+            }
+        }
+    }
+
+    fn compile_kernel<'b>(
+        &'b self,
+        fun: &'b Function<'b>,
+    ) -> Box<dyn KernelEvaluator + 'b> {
+        match self.compile(fun, VulkanPrinter::default()) {
+            Ok(code) => Box::new(code),
+            Err(error) => {
+                warn!("cannot compile kernel {}: {}", fun.name(), error);
+                Box::new(ErrorCode { error })
+            }
+        }
+    }
+
+    fn async_eval<'d>(
+        &self,
+        num_workers: usize,
+        _num_compilers: usize,
+        _mode: EvalMode,
+        cancel: &device::CancellationToken,
+        inner: &(dyn Fn(&mut dyn device::AsyncEvaluator<'d>) + Sync),
+    ) {
+        let (send, recv) = mpsc::sync_channel(EVAL_BUFFER_SIZE);
+        crossbeam::scope(move |scope| {
+            // Start the explorer threads.
+            for _ in 0..num_workers {
+                let mut evaluator = AsyncEvaluator {
+                    context: self,
+                    sender: send.clone(),
+                    cancel,
+                };
+                unwrap!(scope
+                    .builder()
+                    .name("Telamon - Explorer Thread".to_string())
+                    .spawn(move |_| inner(&mut evaluator)));
+            }
+            // Start the evaluation thread.
+            let eval_thread_name = "Telamon - Vulkan Evaluation Thread".to_string();
+            unwrap!(scope.builder().name(eval_thread_name).spawn(move |_| {
+                cancel.drain(recv, |candidate, code, callback| match code {
+                    Ok(mut code) => callback.call(candidate, &mut code),
+                    Err(error) => callback.call(candidate, &mut ErrorCode { error }),
+                });
+            }));
+        })
+        .unwrap();
+    }
+
+    fn param_as_size(&self, name: &str) -> Option<u32> {
+        self.parameters[name].as_size()
+    }
+}
+
+impl<'a> device::ArgMap<'a> for Context {
+    fn bind_erased_scalar(
+        &mut self,
+        param: &ir::Parameter,
+        value: Box<dyn ScalarArgument>,
+    ) {
+        assert_eq!(param.t, value.get_type());
+        self.bind_param(param.name.clone(), Arc::new(value));
+    }
+
+    fn bind_erased_array(
+        &mut self,
+        param: &ir::Parameter,
+        t: ir::Type,
+        len: usize,
+    ) -> Arc<dyn ArrayArgument + 'a> {
+        let size = len * unwrap!(t.len_byte()) as usize;
+        let array = Arc::new(Array(unwrap!(self.executor.allocate(size))));
+        self.bind_param(param.name.clone(), Arc::clone(&array) as Arc<dyn Argument>);
+        array
+    }
+}
+
+type AsyncPayload<'b> = (
+    explorer::Candidate,
+    Result<Code, CompileError>,
+    AsyncCallback<'b>,
+);
+
+/// Asynchronous evaluator.
+struct AsyncEvaluator<'b> {
+    context: &'b Context,
+    sender: mpsc::SyncSender<AsyncPayload<'b>>,
+    cancel: &'b device::CancellationToken,
+}
+
+impl<'b, 'c> device::AsyncEvaluator<'c> for AsyncEvaluator<'b>
+where
+    'c: 'b,
+{
+    fn add_dyn_kernel(
+        &mut self,
+        candidate: explorer::Candidate,
+        callback: device::AsyncCallback<'c>,
+    ) {
+        if self.cancel.is_cancelled() {
+            return device::CancelledKernel::report(candidate, callback);
+        }
+        let code = {
+            let dev_fun = Function::build(&candidate.space);
+            let printer = VulkanPrinter::with_size_limit(MAX_CODE_SIZE);
+            self.context.compile(&dev_fun, printer)
+        };
+        if let Err(ref err) = code {
+            warn!("skipping kernel {}: {}", candidate, err);
+        }
+        unwrap!(self.sender.send((candidate, code, callback)));
+    }
+}
+
+/// A kernel compiled for the device, with its arguments.
+struct Code {
+    source: String,
+    kernel: api::Kernel,
+    push_constants: Vec<u8>,
+    /// The scalar parameters that can be changed, with their offset in `push_constants`.
+    updatable_params: Vec<(ir::Parameter, u32)>,
+    num_groups: [u32; 3],
+    /// Temporary arrays passed to the kernel.
+    _temporaries: Vec<api::Buffer>,
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.source)
+    }
+}
+
+/// Error returned when a kernel cannot be compiled.
+#[derive(Debug)]
+enum CompileError {
+    /// The generated code exceeds the size limit.
+    CodeSize(CodeSizeError),
+    /// The parameters of the kernel do not fit in the push constants of the device.
+    PushConstants { size: u32, limit: u32 },
+    /// The shader compiler or the driver rejected the generated code.
+    Build(api::Error),
+}
+
+impl From<CodeSizeError> for CompileError {
+    fn from(err: CodeSizeError) -> Self {
+        CompileError::CodeSize(err)
+    }
+}
+
+impl From<api::Error> for CompileError {
+    fn from(err: api::Error) -> Self {
+        CompileError::Build(err)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::CodeSize(err) => write!(fmt, "{}", err),
+            CompileError::PushConstants { size, limit } => write!(
+                fmt,
+                "the parameters take {} bytes of push constants (limit: {})",
+                size, limit
+            ),
+            CompileError::Build(err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+/// Stands for a candidate whose code could not be compiled.  Its evaluation always fails.
+struct ErrorCode {
+    error: CompileError,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "<{}>", self.error)
+    }
+}
+
+impl KernelEvaluator for ErrorCode {
+    fn evaluate(&mut self) -> Option<f64> {
+        None
+    }
+}
+
+impl KernelEvaluator for Code {
+    fn evaluate(&mut self) -> Option<f64> {
+        match self.kernel.execute(&self.push_constants, self.num_groups) {
+            Ok(time) => Some(time as f64),
+            Err(err) => {
+                warn!("kernel execution failed: {}", err);
+                None
+            }
+        }
+    }
+
+    fn set_scalar(
+        &mut self,
+        name: &str,
+        value: Box<dyn ScalarArgument>,
+    ) -> Result<(), ParamUpdateError> {
+        let params = self.updatable_params.iter().map(|(param, _)| param);
+        device::check_param_update(params, name, &*value)?;
+        let (_, offset) = unwrap!(self
+            .updatable_params
+            .iter()
+            .find(|(param, _)| param.name == name));
+        value.push(&mut self.push_constants[*offset as usize..]);
+        Ok(())
+    }
+}
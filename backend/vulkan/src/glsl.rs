@@ -0,0 +1,225 @@
+//! Prints `llir` constructs in GLSL syntax.
+//!
+//! GLSL is stricter than C: operands are not implicitly converted, so operands whose type
+//! differs from the one expected by an operator are explicitly cast.  Memory accesses and
+//! control flow depend on the state of the printer and are handled in `printer`.
+use std::fmt;
+
+use telamon::codegen::llir;
+use telamon::ir;
+
+/// Formatting trait for GLSL values.
+pub trait GlslDisplay {
+    /// Formats the value using the given formatter.
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result;
+
+    /// Wraps `self` into a `Display` implementation which calls back into
+    /// `GlslDisplay::fmt`.
+    fn glsl(&self) -> DisplayGlsl<'_, Self> {
+        DisplayGlsl { inner: self }
+    }
+}
+
+/// Helper struct for printing values in GLSL syntax.
+pub struct DisplayGlsl<'a, T: ?Sized> {
+    inner: &'a T,
+}
+
+impl<T: GlslDisplay + ?Sized> fmt::Display for DisplayGlsl<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        GlslDisplay::fmt(self.inner, fmt)
+    }
+}
+
+impl GlslDisplay for ir::Type {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(match self {
+            // Pointers are buffer device addresses, cast to buffer references on access.
+            ir::Type::PtrTo(..) => "uint64_t",
+            ir::Type::F(32) => "float",
+            ir::Type::F(64) => "double",
+            ir::Type::I(1) => "bool",
+            ir::Type::I(8) => "int8_t",
+            ir::Type::I(16) => "int16_t",
+            ir::Type::I(32) => "int32_t",
+            ir::Type::I(64) => "int64_t",
+            _ => panic!("invalid GLSL type: {}", self),
+        })
+    }
+}
+
+impl GlslDisplay for llir::Register<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.name())
+    }
+}
+
+impl GlslDisplay for llir::Operand<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use llir::Operand::*;
+
+        match self {
+            Register(register) => GlslDisplay::fmt(register, fmt),
+            IntLiteral(val, 1) => {
+                use num::Zero;
+
+                fmt.write_str(if val.is_zero() { "false" } else { "true" })
+            }
+            IntLiteral(val, 32) => write!(fmt, "{}", val),
+            IntLiteral(val, 64) => write!(fmt, "{}l", val),
+            IntLiteral(val, bits) => write!(fmt, "int{}_t({})", bits, val),
+            FloatLiteral(val, bits) => {
+                use num::ToPrimitive;
+
+                let f = val.numer().to_f64().unwrap() / val.denom().to_f64().unwrap();
+                // GLSL has no hexadecimal float literals: print the shortest decimal
+                // representation that round-trips, and the bits of infinities and NaNs.
+                match bits {
+                    32 if (f as f32).is_finite() => write!(fmt, "{:?}", f as f32),
+                    32 => write!(fmt, "uintBitsToFloat(0x{:x}u)", (f as f32).to_bits()),
+                    64 if f.is_finite() => write!(fmt, "{:?}lf", f),
+                    64 => write!(fmt, "uint64BitsToDouble(0x{:x}ul)", f.to_bits()),
+                    _ => panic!("Cannot print floating point value with {} bits", bits),
+                }
+            }
+        }
+    }
+}
+
+impl<T: GlslDisplay> GlslDisplay for llir::ScalarOrVector<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            llir::ScalarOrVector::Scalar(scalar) => GlslDisplay::fmt(scalar, fmt),
+            llir::ScalarOrVector::Vector(..) => {
+                panic!("Vulkan backend does not support vectors.")
+            }
+        }
+    }
+}
+
+impl GlslDisplay for llir::CmpOp {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(match self {
+            llir::CmpOp::Eq => "==",
+            llir::CmpOp::Ne => "!=",
+            llir::CmpOp::Lt => "<",
+            llir::CmpOp::Le => "<=",
+            llir::CmpOp::Gt => ">",
+            llir::CmpOp::Ge => ">=",
+        })
+    }
+}
+
+impl GlslDisplay for llir::MulSpec {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(match self {
+            llir::MulSpec::Low => "",
+            llir::MulSpec::High => "Hi",
+            llir::MulSpec::Wide => "Wide",
+        })
+    }
+}
+
+/// Prints `operand`, converted to `t` if needed.
+pub fn operand_as(operand: &llir::OpVec<'_>, t: ir::Type) -> String {
+    if operand.t() == t {
+        operand.glsl().to_string()
+    } else {
+        format!("{}({})", t.glsl(), operand.glsl())
+    }
+}
+
+/// Prints an assignment of an expression of type `expr_t` to `dst`.
+fn assign(dst: &llir::RegVec<'_>, expr: String, expr_t: ir::Type) -> String {
+    if dst.t() == expr_t {
+        format!("{} = {}", dst.glsl(), expr)
+    } else {
+        format!("{} = {}({})", dst.glsl(), dst.t().glsl(), expr)
+    }
+}
+
+/// Returns the GLSL operator implementing a logical operator on `t`.
+fn logic_op(t: ir::Type, bitwise: &'static str, boolean: &'static str) -> &'static str {
+    if t == ir::Type::I(1) {
+        boolean
+    } else {
+        bitwise
+    }
+}
+
+/// Prints an arithmetic instruction, without the trailing semicolon.
+///
+/// # Panics
+///
+/// Panics if `inst` is a memory access or a control flow instruction.
+pub fn arith_inst(inst: &llir::Instruction<'_>) -> String {
+    use llir::BinOp::*;
+    use llir::Instruction::*;
+
+    match inst {
+        Unary(op, d, [a]) => match *op {
+            llir::UnOp::Move { t } => assign(d, operand_as(a, t), t),
+            llir::UnOp::Cast { dst_t, .. } => {
+                let expr = format!("{}({})", dst_t.glsl(), a.glsl());
+                assign(d, expr, dst_t)
+            }
+            llir::UnOp::Exp { t, .. } => {
+                assign(d, format!("exp({})", operand_as(a, t)), t)
+            }
+            llir::UnOp::Sqrt { t } => assign(d, format!("sqrt({})", operand_as(a, t)), t),
+        },
+        Binary(op, d, [a, b]) => {
+            let [a_t, b_t] = op.arg_t();
+            let (a, b) = (operand_as(a, a_t), operand_as(b, b_t));
+            let infix = |symbol: &dyn fmt::Display| format!("{} {} {}", a, symbol, b);
+            let expr = match *op {
+                IAdd { .. } | FAdd { .. } => infix(&"+"),
+                ISub { .. } | FSub { .. } => infix(&"-"),
+                IDiv { .. } | FDiv { .. } => infix(&"/"),
+                FMul { .. } => infix(&"*"),
+                IMul {
+                    spec: llir::MulSpec::Low,
+                    ..
+                } => infix(&"*"),
+                IMul { spec, arg_t } => format!(
+                    "__mul{}{}({}, {})",
+                    arg_t.bitwidth().unwrap(),
+                    spec.glsl(),
+                    a,
+                    b
+                ),
+                IMax { .. } | FMax { .. } => format!("max({}, {})", a, b),
+                FMin { .. } => format!("min({}, {})", a, b),
+                Set { op, .. } => infix(&op.glsl()),
+                And { t } => infix(&logic_op(t, "&", "&&")),
+                Or { t } => infix(&logic_op(t, "|", "||")),
+                Xor { t } => infix(&logic_op(t, "^", "^^")),
+            };
+            assign(d, expr, op.ret_t())
+        }
+        Ternary(op, d, [a, b, c]) => {
+            let [a_t, b_t, c_t] = op.arg_t();
+            let (a, b, c) = (operand_as(a, a_t), operand_as(b, b_t), operand_as(c, c_t));
+            let expr = match *op {
+                llir::TernOp::IMad {
+                    spec: llir::MulSpec::Low,
+                    ..
+                } => format!("{} * {} + {}", a, b, c),
+                llir::TernOp::IMad { spec, arg_t } => format!(
+                    "__mad{}{}({}, {}, {})",
+                    arg_t.bitwidth().unwrap(),
+                    spec.glsl(),
+                    a,
+                    b,
+                    c
+                ),
+                llir::TernOp::FFma { .. } => format!("fma({}, {}, {})", a, b, c),
+                llir::TernOp::Mma { .. } => panic!("tensor cores are not available"),
+            };
+            assign(d, expr, op.ret_t())
+        }
+        Load(..) | Store(..) | Jump(..) | Sync => {
+            panic!("{} is not an arithmetic instruction", inst)
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Prints GLSL compute shaders.
+//!
+//! Kernel parameters are passed as push constants and global memory is accessed through
+//! buffer references.  Since GLSL has no pointers to shared variables, all shared memory
+//! blocks are packed in a single array of 32-bit words and addressed by their byte offset in
+//! the array.  Loops are printed as `do { .. } while (..)` blocks: Telamon only jumps back to
+//! the beginning of the innermost loop.
+use crate::glsl::{self, GlslDisplay};
+use crate::NameGenerator;
+use itertools::Itertools;
+use std::fmt::Write as WriteFmt;
+use telamon::codegen::*;
+use telamon::ir::{self, Type};
+use telamon::search_space::DimKind;
+use utils::unwrap;
+
+/// Alignment of shared memory blocks, in bytes.
+const SHARED_ALIGNMENT: u32 = 8;
+
+#[derive(Default)]
+pub struct VulkanPrinter {
+    buffer: CodeBuffer,
+    /// Types accessed through buffer references.
+    ref_types: Vec<Type>,
+    /// Labels of the loops being printed, from the outermost to the innermost.
+    open_loops: Vec<String>,
+}
+
+/// Returns the number of bytes a parameter occupies in the push constants.
+fn param_size(param: &ParamVal) -> u32 {
+    match param.t() {
+        Type::PtrTo(..) => 8,
+        t => unwrap!(t.len_byte()),
+    }
+}
+
+/// Computes the offset of each parameter in the push constants of `function`, along with
+/// the total size of the push constants.  Each parameter is aligned to its size.
+pub fn push_constants_layout(function: &Function) -> (Vec<u32>, u32) {
+    let mut size = 0;
+    let offsets = function
+        .device_code_args()
+        .map(|param| {
+            let param_size = param_size(param);
+            let offset = utils::div_ceil(size, param_size) * param_size;
+            size = offset + param_size;
+            offset
+        })
+        .collect();
+    (offsets, size)
+}
+
+/// Returns the name of the buffer reference type used to access values of type `t`.
+fn ref_type(t: Type) -> &'static str {
+    match t {
+        Type::PtrTo(..) => "PtrRef",
+        Type::F(32) => "F32Ref",
+        Type::F(64) => "F64Ref",
+        Type::I(8) => "I8Ref",
+        Type::I(16) => "I16Ref",
+        Type::I(32) => "I32Ref",
+        Type::I(64) => "I64Ref",
+        t => panic!("cannot access values of type {} in memory", t),
+    }
+}
+
+/// Prints an address as an expression of type `uint64_t`.
+fn address(addr: llir::Address<'_>) -> String {
+    let llir::Address::Register(reg, offset) = addr;
+    let base = match reg.t() {
+        Type::PtrTo(..) => reg.glsl().to_string(),
+        _ => format!("uint64_t({})", reg.glsl()),
+    };
+    match offset {
+        0 => base,
+        offset if offset > 0 => format!("({} + {}ul)", base, offset),
+        offset => format!("({} - {}ul)", base, -i64::from(offset)),
+    }
+}
+
+impl VulkanPrinter {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        VulkanPrinter {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+            ..VulkanPrinter::default()
+        }
+    }
+
+    /// Declares the push constant block holding the parameters of the function.
+    fn params_decl(&self, function: &Function) -> String {
+        let (offsets, _) = push_constants_layout(function);
+        if offsets.is_empty() {
+            return String::new();
+        }
+        let members = function
+            .device_code_args()
+            .zip_eq(offsets)
+            .map(|(param, offset)| {
+                format!(
+                    "  layout(offset = {}) {} {}; // {}",
+                    offset,
+                    param.t().glsl(),
+                    param.key().ident(),
+                    param.key(),
+                )
+            })
+            .join("\n");
+        format!(
+            "layout(push_constant) uniform Parameters {{\n{}\n}} params;\n",
+            members
+        )
+    }
+
+    /// Declares the buffer reference types used by the kernel.
+    fn ref_types_decl(&self) -> String {
+        self.ref_types
+            .iter()
+            .map(|&t| {
+                let align = match t {
+                    Type::PtrTo(..) => 8,
+                    t => unwrap!(t.len_byte()),
+                };
+                format!(
+                    "layout(buffer_reference, std430, buffer_reference_align = {}) \
+                     buffer {} {{ {} v; }};\n",
+                    align,
+                    ref_type(t),
+                    t.glsl(),
+                )
+            })
+            .collect()
+    }
+
+    /// Declared all variables that have been required from the namegen
+    fn var_decls(&self, namegen: &NameGenerator) -> String {
+        let print_decl = |(&t, &n)| {
+            let prefix = NameGenerator::gen_prefix(t);
+            let names = (0..n).map(|i| format!("{}{}", prefix, i)).join(", ");
+            format!("{} {};\n  ", t.glsl(), names)
+        };
+        let other_var_decl = namegen.num_var.iter().map(print_decl).join("\n  ");
+        if namegen.num_glob_ptr == 0 {
+            other_var_decl
+        } else {
+            let names = (0..namegen.num_glob_ptr)
+                .map(|i| format!("ptr{}", i))
+                .join(", ");
+            format!("uint64_t {};\n  {}", names, other_var_decl)
+        }
+    }
+
+    /// Declares block and thread indexes.
+    fn decl_par_indexes(&self, function: &Function, name_map: &NameMap<'_>) -> String {
+        const AXES: [&str; 3] = ["x", "y", "z"];
+        let mut decls = vec![];
+        // Load block indexes.
+        for (ind, dim) in function.block_dims().iter().enumerate() {
+            let index = name_map.name_index(dim.id());
+            decls.push(format!(
+                "{} = int32_t(gl_WorkGroupID.{});",
+                index.glsl(),
+                AXES[ind]
+            ));
+        }
+        // Compute thread indexes.
+        for (ind, dim) in function.thread_dims().iter().rev().enumerate() {
+            let index = name_map.name_index(dim.id());
+            decls.push(format!(
+                "{} = int32_t(gl_LocalInvocationID.{});",
+                index.glsl(),
+                AXES[ind]
+            ));
+        }
+        decls.join("\n  ")
+    }
+
+    /// Sets the address of shared memory blocks and returns the size of the shared memory
+    /// array, in 32-bit words.
+    fn shared_mem_decls(&mut self, function: &Function, name_map: &NameMap<'_>) -> u32 {
+        let mut size = 0;
+        for block in function.mem_blocks() {
+            if block.alloc_scheme() != AllocationScheme::Shared {
+                continue;
+            }
+            let offset = utils::div_ceil(size, SHARED_ALIGNMENT) * SHARED_ALIGNMENT;
+            unwrap!(writeln!(
+                self.buffer,
+                "{} = {}ul; // shared block {}",
+                name_map.name_addr(block.id()).glsl(),
+                offset,
+                block.id().0,
+            ));
+            size = offset + unwrap!(block.alloc_size().as_int());
+        }
+        utils::div_ceil(size, 4)
+    }
+
+    /// Prints the GLSL code of a kernel.  Fails if the code exceeds the size limit of the
+    /// printer.
+    pub fn kernel<'a: 'b, 'b>(
+        &mut self,
+        function: &'b Function<'a>,
+    ) -> Result<String, CodeSizeError> {
+        let mut namegen = NameGenerator::default();
+        let interner = Interner::default();
+        let name_map = &mut NameMap::new(&interner, function, &mut namegen);
+
+        // INDEX LOADS
+        let idx_loads = self.decl_par_indexes(function, name_map);
+        unwrap!(writeln!(self.buffer, "{}", idx_loads));
+        // LOAD PARAM
+        for val in function.device_code_args() {
+            let var_name = name_map.name_param_val(val.key());
+            unwrap!(writeln!(
+                self.buffer,
+                "{var_name} = params.{name};",
+                var_name = var_name.glsl(),
+                name = val.key().ident(),
+            ));
+        }
+        // MEM DECL
+        let shared_words = self.shared_mem_decls(function, name_map);
+        for block in function.mem_blocks() {
+            if block.alloc_scheme() == AllocationScheme::PrivatisedGlobal {
+                Printer::new(self, name_map).privatise_global_block(block, function);
+            }
+        }
+        // Compute size casts
+        for dim in function.dimensions() {
+            if !dim.kind().intersects(DimKind::UNROLL | DimKind::LOOP) {
+                continue;
+            }
+            for level in dim.induction_levels() {
+                if let Some((_, ref incr)) = level.increment {
+                    let reg = name_map.declare_size_cast(incr, level.t());
+                    if let Some(reg) = reg {
+                        let old_name = name_map.name_size(incr, Type::I(32));
+                        self.print_inst(
+                            llir::Instruction::cast(level.t(), reg, old_name)
+                                .unwrap()
+                                .into(),
+                        );
+                    }
+                }
+            }
+        }
+        // INIT
+        let ind_levels = function.init_induction_levels().iter().chain(
+            function
+                .block_dims()
+                .iter()
+                .flat_map(|d| d.induction_levels()),
+        );
+        for level in ind_levels {
+            Printer::new(self, name_map).parallel_induction_level(level);
+        }
+        // BODY
+        Printer::new(self, name_map).cfg(function, function.cfg());
+        assert!(self.open_loops.is_empty(), "unterminated loops");
+        let var_decls = self.var_decls(&namegen);
+        let body = self.buffer.take()?;
+
+        let shared = if shared_words > 0 {
+            format!("shared uint shmem[{}];\n", shared_words)
+        } else {
+            String::new()
+        };
+        let mut return_string = format!(
+            include_str!("template/kernel.comp.template"),
+            name = function.name(),
+            ref_types = self.ref_types_decl(),
+            params = self.params_decl(function),
+            shared = shared,
+        );
+        return_string.push_str("  ");
+        return_string.push_str(&var_decls);
+        return_string.push_str(&body);
+        return_string.push_str("}\n");
+        Ok(return_string)
+    }
+
+    /// Prints a load from global memory.
+    fn global_load(&mut self, t: Type, dst: &llir::RegVec<'_>, addr: String) -> String {
+        if !self.ref_types.contains(&t) {
+            self.ref_types.push(t);
+        }
+        format!("{} = {}({}).v;", dst.glsl(), ref_type(t), addr)
+    }
+
+    /// Prints a store to global memory.
+    fn global_store(&mut self, t: Type, addr: String, value: &llir::OpVec<'_>) -> String {
+        if !self.ref_types.contains(&t) {
+            self.ref_types.push(t);
+        }
+        let value = glsl::operand_as(value, t);
+        format!("{}({}).v = {};", ref_type(t), addr, value)
+    }
+}
+
+/// Prints a load from shared memory.
+fn shared_load(t: Type, dst: &llir::RegVec<'_>, addr: String) -> String {
+    let word = |i| format!("shmem[uint({} >> 2) + {}]", addr, i);
+    let value = match t {
+        Type::F(32) => format!("uintBitsToFloat({})", word(0)),
+        Type::I(32) => format!("int32_t({})", word(0)),
+        Type::F(64) => format!("packDouble2x32(uvec2({}, {}))", word(0), word(1)),
+        Type::I(64) => format!("int64_t(pack64(u32vec2({}, {})))", word(0), word(1)),
+        Type::PtrTo(..) => format!("pack64(u32vec2({}, {}))", word(0), word(1)),
+        t => panic!("cannot load values of type {} from shared memory", t),
+    };
+    format!("{} = {};", dst.glsl(), value)
+}
+
+/// Prints a store to shared memory.
+fn shared_store(t: Type, addr: String, value: &llir::OpVec<'_>) -> String {
+    let value = glsl::operand_as(value, t);
+    let index = format!("uint({} >> 2)", addr);
+    match t {
+        Type::F(32) => format!("shmem[{}] = floatBitsToUint({});", index, value),
+        Type::I(32) => format!("shmem[{}] = uint({});", index, value),
+        Type::F(64) | Type::I(64) | Type::PtrTo(..) => {
+            let words = if let Type::F(64) = t {
+                format!("uvec2 words = unpackDouble2x32({});", value)
+            } else {
+                format!("uvec2 words = unpack32(uint64_t({}));", value)
+            };
+            format!(
+                "{{ uint index = {}; {} shmem[index] = words.x; shmem[index + 1] = words.y; }}",
+                index, words
+            )
+        }
+        t => panic!("cannot store values of type {} to shared memory", t),
+    }
+}
+
+impl InstPrinter for VulkanPrinter {
+    fn print_label(&mut self, label: llir::Label<'_>) {
+        self.open_loops.push(label.name().to_string());
+        writeln!(self.buffer, "do {{ // {}", label.name()).unwrap()
+    }
+
+    fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
+        let stmt = match inst.instruction {
+            llir::Instruction::Jump(label) => {
+                // Loops are the only source of jumps: they jump back to their beginning.
+                let open_loop = self.open_loops.pop();
+                assert_eq!(open_loop.as_ref().map(|s| &s[..]), Some(label.name()));
+                let cond = inst.predicate.map(|p| p.glsl().to_string());
+                let cond = cond.unwrap_or_else(|| "true".to_string());
+                return writeln!(self.buffer, "}} while ({});", cond).unwrap();
+            }
+            llir::Instruction::Load(spec, ref d, a) => match spec.state_space() {
+                llir::StateSpace::Global => self.global_load(spec.t(), d, address(a)),
+                llir::StateSpace::Shared => shared_load(spec.t(), d, address(a)),
+            },
+            llir::Instruction::Store(spec, a, [ref b]) => match spec.state_space() {
+                llir::StateSpace::Global => self.global_store(spec.t(), address(a), b),
+                llir::StateSpace::Shared => shared_store(spec.t(), address(a), b),
+            },
+            llir::Instruction::Sync => {
+                "{ memoryBarrierShared(); memoryBarrierBuffer(); barrier(); }".to_string()
+            }
+            ref inst => format!("{};", glsl::arith_inst(inst)),
+        };
+        if let Some(predicate) = inst.predicate {
+            write!(self.buffer, "if ({}) ", predicate.glsl()).unwrap();
+        }
+        writeln!(self.buffer, "{}", stmt).unwrap();
+    }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
+}
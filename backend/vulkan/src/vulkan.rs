@@ -0,0 +1,298 @@
+//! Describes Vulkan devices.
+use crate::api;
+use crate::printer::VulkanPrinter;
+use fxhash::FxHashMap;
+use std::io::Write;
+use telamon::codegen::Function;
+use telamon::device::{self, vectorization::VectorRule};
+use telamon::ir::{self, Type};
+use telamon::model::{self, HwPressure};
+use telamon::search_space::{DimKind, InstFlag, MemSpace, SearchSpace};
+use utils::unwrap;
+
+/// Maximal number of threads in a block we consider, even if the device supports more.
+const MAX_THREADS: u32 = 1024;
+
+// The model below only knows what Vulkan reports about the device. Latencies, in cycles,
+// are optimistic for the devices we target so that the model remains a lower bound.
+
+/// Latency of simple arithmetic instructions on 32-bit values.
+const ALU_LATENCY: f64 = 4.0;
+/// Latency of divisions and transcendental functions on 32-bit values.
+const SLOW_ALU_LATENCY: f64 = 16.0;
+/// Latency of accesses to shared memory.
+const SHARED_MEM_LATENCY: f64 = 16.0;
+/// Latency of accesses to global memory.
+const GLOBAL_MEM_LATENCY: f64 = 100.0;
+/// Latency of work-group barriers.
+const BARRIER_LATENCY: f64 = 16.0;
+
+/// Describes a Vulkan device.
+#[derive(Clone, Debug)]
+pub struct VulkanDevice {
+    /// The name of the device.
+    pub name: String,
+    /// The maximal number of invocations in a work-group.
+    pub max_threads: u32,
+    /// The amount of shared memory available to a work-group, in bytes.
+    pub shared_mem: u32,
+    /// The maximal size of the push constants holding the parameters of a kernel, in bytes.
+    pub max_push_constants: u32,
+    /// The number of work-groups that can run in parallel. Vulkan does not expose it, so it
+    /// defaults to `1`.
+    pub num_compute_units: u32,
+    /// The number of invocations in a subgroup.
+    pub simt_width: u32,
+    /// Indicates if the device supports 64-bit floats.
+    pub float64: bool,
+    /// The clock of the compute units, in GHz. Vulkan does not expose it, so it defaults
+    /// to `1.0` and the model then counts cycles instead of nanoseconds.
+    pub clock_rate: f64,
+}
+
+impl VulkanDevice {
+    /// Queries the description of the device used by `executor`.
+    pub(crate) fn from_executor(executor: &api::Executor) -> Self {
+        VulkanDevice {
+            name: executor.device_name(),
+            max_threads: std::cmp::min(
+                executor.max_work_group_invocations(),
+                MAX_THREADS,
+            ),
+            shared_mem: executor.max_shared_memory_size(),
+            max_push_constants: executor.max_push_constants_size(),
+            num_compute_units: 1,
+            // Some drivers do not report the size of subgroups.
+            simt_width: std::cmp::max(executor.subgroup_size(), 1),
+            float64: executor.supports_float64(),
+            clock_rate: 1.0,
+        }
+    }
+
+    /// Returns the pressure of an instruction on the issue, ALU and memory units, for
+    /// the given latency.
+    fn pressure(latency: f64, alu: f64, mem: f64) -> HwPressure {
+        HwPressure::new(latency, vec![1.0, alu, mem])
+    }
+
+    /// Returns the pressure of an instruction computing values of type `t`. Operations
+    /// on 64-bit values are modeled as taking twice as long.
+    fn alu_pressure(latency: f64, t: Option<Type>) -> HwPressure {
+        let factor = match t.and_then(Type::bitwidth) {
+            Some(64) => 2.0,
+            _ => 1.0,
+        };
+        Self::pressure(latency * factor, factor, 0.0)
+    }
+
+    /// Returns the overhead for a single instance of the instruction.
+    fn inst_pressure(&self, inst: &ir::Instruction) -> HwPressure {
+        use telamon::ir::Operator::*;
+        let t = inst.t();
+        match inst.operator() {
+            BinOp(ir::BinOp::Div, ..) => Self::alu_pressure(SLOW_ALU_LATENCY, t),
+            UnaryOp(ir::UnaryOp::Exp(..), ..) | UnaryOp(ir::UnaryOp::Sqrt(..), ..) => {
+                Self::alu_pressure(SLOW_ALU_LATENCY, t)
+            }
+            // Moves and casts are mostly folded by the compiler.
+            UnaryOp(..) => HwPressure::zero(self),
+            BinOp(..) | Mul(..) | Mad(..) | Mma(..) => Self::alu_pressure(ALU_LATENCY, t),
+            Ld(..) | St(..) => Self::pressure(GLOBAL_MEM_LATENCY, 0.0, 1.0),
+            TmpLd(..) | TmpSt(..) => Self::pressure(SHARED_MEM_LATENCY, 0.0, 1.0),
+        }
+    }
+
+    /// Returns the overhead induced by all the iterations of a loop.
+    fn dim_pressure(&self, kind: DimKind, size: model::size::Range) -> HwPressure {
+        if kind == DimKind::LOOP {
+            let mut pressure = self.loop_iter_pressure(kind).0;
+            pressure.repeat_sequential(size.min as f64);
+            pressure
+        } else if DimKind::THREAD.contains(kind) {
+            // The repetition along the thread is taken into account by
+            // `num_unmapped_thread` as the current thread is accounted as not mapped.
+            self.loop_iter_pressure(kind).0
+        } else {
+            HwPressure::zero(self)
+        }
+    }
+
+    /// Returns the processing power of a compute unit, in units per nanosecond.
+    fn compute_unit_rates(&self) -> Vec<f64> {
+        let simt_width = f64::from(self.simt_width);
+        vec![
+            self.clock_rate * simt_width,
+            self.clock_rate * simt_width,
+            self.clock_rate,
+        ]
+    }
+}
+
+impl Default for VulkanDevice {
+    /// Describes a generic device, with the minimal capabilities required by Vulkan 1.2.
+    fn default() -> Self {
+        VulkanDevice {
+            name: "Vulkan".to_string(),
+            max_threads: 128,
+            shared_mem: 16 * 1024,
+            max_push_constants: 128,
+            num_compute_units: 1,
+            simt_width: 1,
+            float64: false,
+            clock_rate: 1.0,
+        }
+    }
+}
+
+impl device::Device for VulkanDevice {
+    fn print(&self, fun: &Function, out: &mut dyn Write) {
+        let source = unwrap!(VulkanPrinter::default().kernel(fun));
+        unwrap!(write!(out, "{}", source));
+    }
+
+    fn check_type(&self, t: Type) -> Result<(), ir::TypeError> {
+        match t {
+            Type::I(1) | Type::I(32) | Type::I(64) | Type::F(32) => Ok(()),
+            Type::F(64) if self.float64 => Ok(()),
+            Type::PtrTo(_) => Ok(()),
+            t => Err(ir::TypeError::InvalidType { t }),
+        }
+    }
+
+    fn max_block_dims(&self) -> u32 {
+        3
+    }
+
+    fn max_inner_block_size(&self) -> u32 {
+        65535
+    }
+
+    fn max_threads(&self) -> u32 {
+        self.max_threads
+    }
+
+    fn max_unrolling(&self) -> u32 {
+        512
+    }
+
+    fn has_vector_registers(&self) -> bool {
+        false
+    }
+
+    fn can_vectorize(&self, _dim: &ir::Dimension, _op: &ir::Operator) -> bool {
+        // TODO(vulkan): use GLSL vector types.
+        false
+    }
+
+    fn vectorization_rules(&self) -> &[VectorRule] {
+        // Operators without a rule are not vectorized.
+        &[]
+    }
+
+    fn shared_mem(&self) -> u32 {
+        self.shared_mem
+    }
+
+    fn pointer_type(&self, _: MemSpace) -> ir::Type {
+        // Global pointers are device addresses and shared pointers are offsets in the shared
+        // memory array, both printed as `uint64_t`. Use 0 as a dummy memory ID.
+        ir::Type::PtrTo(ir::MemId(0))
+    }
+
+    fn supported_mem_flags(&self, op: &ir::Operator) -> InstFlag {
+        match op {
+            ir::Operator::Ld(..)
+            | ir::Operator::St(..)
+            | ir::Operator::TmpLd(..)
+            | ir::Operator::TmpSt(..) => InstFlag::BLOCK_COHERENT,
+            _ => panic!("not a memory operation"),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hw_pressure(
+        &self,
+        space: &SearchSpace,
+        dim_sizes: &FxHashMap<ir::DimId, model::size::Range>,
+        _: &FxHashMap<ir::StmtId, model::Nesting>,
+        stmt: &dyn ir::Statement,
+        _: &dyn device::Context,
+    ) -> model::HwPressure {
+        if let Some(inst) = stmt.as_inst() {
+            self.inst_pressure(inst)
+        } else if let Some(dim) = stmt.as_dim() {
+            let kind = space.domain().get_dim_kind(dim.id());
+            self.dim_pressure(kind, dim_sizes[&dim.id()])
+        } else {
+            panic!()
+        }
+    }
+
+    fn loop_iter_pressure(&self, kind: DimKind) -> (HwPressure, HwPressure) {
+        if kind == DimKind::LOOP {
+            // Each iteration increments and compares the index, then branches.
+            let iter = Self::pressure(2.0 * ALU_LATENCY, 2.0, 0.0);
+            let end = HwPressure::new(ALU_LATENCY, vec![0.0; 3]);
+            (iter, end)
+        } else if DimKind::THREAD.contains(kind) {
+            let barrier = Self::pressure(BARRIER_LATENCY, 0.0, 0.0);
+            (barrier, HwPressure::zero(self))
+        } else {
+            (HwPressure::zero(self), HwPressure::zero(self))
+        }
+    }
+
+    fn thread_rates(&self) -> HwPressure {
+        HwPressure::new(self.clock_rate, vec![self.clock_rate; 3])
+    }
+
+    fn block_rates(&self) -> HwPressure {
+        HwPressure::new(self.clock_rate, self.compute_unit_rates())
+    }
+
+    fn total_rates(&self) -> HwPressure {
+        let num_compute_units = f64::from(self.num_compute_units);
+        let rates = self.compute_unit_rates();
+        let rates = rates.into_iter().map(|r| r * num_compute_units).collect();
+        HwPressure::new(self.clock_rate, rates)
+    }
+
+    fn bottlenecks(&self) -> &[&'static str] {
+        &["issue", "alu", "mem"]
+    }
+
+    fn block_parallelism(&self, _space: &SearchSpace) -> u32 {
+        self.num_compute_units
+    }
+
+    fn additive_indvar_pressure(&self, t: &ir::Type) -> HwPressure {
+        Self::alu_pressure(ALU_LATENCY, Some(*t))
+    }
+
+    fn multiplicative_indvar_pressure(&self, t: &ir::Type) -> HwPressure {
+        Self::alu_pressure(ALU_LATENCY, Some(*t))
+    }
+
+    fn add_block_overhead(
+        &self,
+        max_active_threads: model::size::FactorRange,
+        _: model::size::FactorRange,
+        _: model::size::Range,
+        pressure: &mut HwPressure,
+    ) {
+        // Invocations of a partially filled subgroup still occupy the whole subgroup.
+        let ratio = model::simt_waste_ratio(self, max_active_threads.lcm);
+        pressure.multiply(&HwPressure::new(1.0, vec![ratio; 3]));
+    }
+
+    fn lower_type(&self, t: ir::Type, _space: &SearchSpace) -> Option<ir::Type> {
+        Some(t)
+    }
+
+    fn simt_width(&self) -> u32 {
+        self.simt_width
+    }
+}
@@ -21,12 +21,18 @@ pub fn compile(mut source_file: File, lib_path: &str) -> ExitStatus {
         .expect("Could not execute gcc")
 }
 
-pub fn link_and_exec(
-    lib_path: &str,
+/// Loads a library compiled by `compile`.
+pub fn link(lib_path: &str) -> libloading::Library {
+    libloading::Library::new(lib_path).expect("Library not found")
+}
+
+/// Runs a function of a library loaded by `link` and returns its execution time in
+/// nanoseconds.
+pub fn exec(
+    lib: &libloading::Library,
     fun_name: &str,
     mut args: Vec<*mut libc::c_void>,
 ) -> f64 {
-    let lib = libloading::Library::new(lib_path).expect("Library not found");
     unsafe {
         let func: libloading::Symbol<unsafe extern "C" fn(*mut *mut libc::c_void)> = lib
             .get(fun_name.as_bytes())
@@ -34,6 +40,6 @@ pub fn link_and_exec(
         let t0 = Instant::now();
         func(args.as_mut_ptr());
         let t = Instant::now() - t0;
-        f64::from(t.subsec_nanos())
+        t.as_secs() as f64 * 1e9 + f64::from(t.subsec_nanos())
     }
 }
@@ -1,11 +1,13 @@
 use crate::compile;
 use crate::cpu::Cpu;
 use crate::cpu_argument::{ArgLock, Argument, CpuArray};
+#[cfg(feature = "llvm")]
+use crate::llvm::{self, LlvmPrinter};
 use crate::printer::X86printer;
 ///! Defines the CPU evaluation context.
 use telamon::codegen::ParamVal;
 
-use telamon::codegen;
+use telamon::codegen::{self, ArtifactError, ArtifactParam, CodeSizeError};
 use telamon::device::{
     self, AsyncCallback, Device, EvalMode, KernelEvaluator, ScalarArgument,
 };
@@ -16,7 +18,7 @@ use crossbeam;
 use fxhash::FxHashMap;
 use itertools::Itertools;
 use libc;
-use log::debug;
+use log::{debug, warn};
 use std::f64;
 use std::io::Write;
 use std::sync::{mpsc, Arc, MutexGuard};
@@ -27,13 +29,39 @@ use utils::*;
 /// Max number of candidates waiting to be evaluated.
 const EVAL_BUFFER_SIZE: usize = 100;
 
+/// Max size in bytes of the code generated for candidates during the search.  Candidates
+/// with a bigger code are not evaluated.
+const MAX_CODE_SIZE: usize = 64 << 20;
+
+/// Name of the function to call in the generated C code.
+const ENTRY_POINT: &str = "entry_point";
+
+/// The compiler used to run the generated code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compiler {
+    /// Prints C code and compiles it into a shared library with `gcc`.
+    Gcc,
+    /// Prints LLVM IR and compiles it in-process with the LLVM JIT.
+    #[cfg(feature = "llvm")]
+    Llvm,
+}
+
 /// A CPU evaluation context.
 pub struct Context {
     cpu_model: Arc<Cpu>,
     parameters: FxHashMap<String, Arc<dyn Argument>>,
+    compiler: Compiler,
 }
 
 impl Context {
+    /// Creates a new evaluation context that compiles the generated code with `compiler`.
+    pub fn with_compiler(compiler: Compiler) -> Self {
+        Context {
+            compiler,
+            ..Context::default()
+        }
+    }
+
     /// Returns a parameter given its name.
     pub fn get_param(&self, name: &str) -> &dyn Argument {
         self.parameters[name].as_ref()
@@ -64,6 +92,28 @@ impl Context {
             })
             .collect_vec()
     }
+
+    /// Prints a function for the compiler of the context.  Fails if the code exceeds
+    /// `size_limit`.
+    fn print(
+        &self,
+        func: &codegen::Function,
+        size_limit: Option<usize>,
+    ) -> Result<Source, CodeSizeError> {
+        Ok(match self.compiler {
+            Compiler::Gcc => {
+                let mut printer = size_limit
+                    .map_or_else(X86printer::default, X86printer::with_size_limit);
+                Source::C(printer.wrapper_function(func)?)
+            }
+            #[cfg(feature = "llvm")]
+            Compiler::Llvm => {
+                let mut printer = size_limit
+                    .map_or_else(LlvmPrinter::default, LlvmPrinter::with_size_limit);
+                Source::Llvm(printer.module(func)?)
+            }
+        })
+    }
 }
 
 impl Default for Context {
@@ -73,6 +123,7 @@ impl Default for Context {
         Context {
             cpu_model: Arc::new(default_cpu),
             parameters: FxHashMap::default(),
+            compiler: Compiler::Gcc,
         }
     }
 }
@@ -112,27 +163,62 @@ impl device::Context for Context {
 
     /// Evaluation in sequential mode
     fn evaluate(&self, func: &codegen::Function, _mode: EvalMode) -> Result<f64, ()> {
-        let mut printer = X86printer::default();
-        let fun_str = printer.wrapper_function(func);
-        function_evaluate(&fun_str, &self.gen_args(func))
+        let source = unwrap!(self.print(func, None));
+        let args = self.gen_args(func);
+        Ok(with_compiled(&source, |run| with_raw_args(&args, run)))
     }
 
-    /// returns a vec containing num_sample runs of function_evaluate
+    /// Compiles the function once and returns the execution time of `num_samples` runs.
     fn benchmark(&self, func: &codegen::Function, num_samples: usize) -> Vec<f64> {
-        let mut printer = X86printer::default();
-        let fun_str = printer.wrapper_function(func);
+        let source = unwrap!(self.print(func, None));
         let args = self.gen_args(func);
-        let mut res = vec![];
-        for _ in 0..num_samples {
-            res.push(function_evaluate(&fun_str, &args).unwrap_or(std::f64::INFINITY));
-        }
-        res
+        with_compiled(&source, |run| {
+            (0..num_samples)
+                .map(|_| with_raw_args(&args, run))
+                .collect()
+        })
+    }
+
+    fn export_artifact(&self, function: &codegen::Function) -> Option<codegen::Artifact> {
+        let source = unwrap!(X86printer::default().wrapper_function(function));
+        let entry_point = ENTRY_POINT.to_string();
+        Some(codegen::Artifact::new(function, entry_point, source, self))
+    }
+
+    fn load_artifact<'b>(
+        &'b self,
+        artifact: &codegen::Artifact,
+    ) -> Result<Box<dyn KernelEvaluator + 'b>, ArtifactError> {
+        artifact.check_device(self)?;
+        let arguments = artifact
+            .params
+            .iter()
+            .map(|param| match *param {
+                ArtifactParam::External { ref name, .. } => self
+                    .parameters
+                    .get(name)
+                    .map(|arg| ThunkArg::ArgRef(Arc::clone(arg)))
+                    .ok_or_else(|| ArtifactError::UnknownParam(name.clone())),
+                ArtifactParam::Size(size) => Ok(ThunkArg::Size(size as i32)),
+                ArtifactParam::TmpArray(size) => Ok(ThunkArg::TmpArray(size)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (lib_dir, lib_path) = compile_library(&artifact.source);
+        Ok(Box::new(LoadedArtifact {
+            source: artifact.source.clone(),
+            entry_point: artifact.entry_point.clone(),
+            arguments,
+            library: compile::link(&lib_path),
+            _lib_dir: lib_dir,
+        }))
     }
 
     fn async_eval<'c>(
         &self,
         num_workers: usize,
+        _num_compilers: usize,
         _mode: EvalMode,
+        cancel: &device::CancellationToken,
         inner: &(dyn Fn(&mut dyn device::AsyncEvaluator<'c>) + Sync),
     ) {
         let (send, recv) = mpsc::sync_channel(EVAL_BUFFER_SIZE);
@@ -142,6 +228,7 @@ impl device::Context for Context {
                 let mut evaluator = AsyncEvaluator {
                     context: self,
                     sender: send.clone(),
+                    cancel,
                 };
                 scope
                     .builder()
@@ -155,16 +242,23 @@ impl device::Context for Context {
                 .builder()
                 .name(eval_thread_name)
                 .spawn(move |_| {
-                    while let Ok((candidate, fun_str, code_args, callback)) = recv.recv()
-                    {
-                        callback.call(
-                            candidate,
-                            &mut Code {
-                                source: &fun_str,
-                                arguments: &code_args,
-                            },
-                        );
-                    }
+                    cancel.drain(recv, |candidate, (source, code_args), callback| {
+                        match source {
+                            Ok(source) => with_compiled(&source, |run| {
+                                callback.call(
+                                    candidate,
+                                    &mut Code {
+                                        source: &source,
+                                        run,
+                                        arguments: &code_args,
+                                    },
+                                )
+                            }),
+                            Err(error) => {
+                                callback.call(candidate, &mut ErrorCode { error })
+                            }
+                        }
+                    })
                 })
                 .unwrap();
         })
@@ -185,8 +279,32 @@ enum ThunkArg {
     TmpArray(u32),
 }
 
+/// Code generated for a function, ready to be compiled.
+enum Source {
+    C(String),
+    #[cfg(feature = "llvm")]
+    Llvm(llvm::Module),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::C(source) => write!(fmt, "{}", source),
+            #[cfg(feature = "llvm")]
+            Source::Llvm(module) => write!(fmt, "{}", module),
+        }
+    }
+}
+
+/// Runs compiled code on the given arguments and returns the execution time in
+/// nanoseconds.
+type Runner<'a> = dyn Fn(Vec<*mut libc::c_void>) -> f64 + 'a;
+
+/// Code compiled for a candidate.  The code is compiled once and then run for each
+/// evaluation.
 struct Code<'a> {
-    source: &'a str,
+    source: &'a Source,
+    run: &'a Runner<'a>,
     arguments: &'a [ThunkArg],
 }
 
@@ -198,7 +316,49 @@ impl<'a> fmt::Display for Code<'a> {
 
 impl<'a> KernelEvaluator for Code<'a> {
     fn evaluate(&mut self) -> Option<f64> {
-        function_evaluate(self.source, self.arguments).ok()
+        Some(with_raw_args(self.arguments, self.run))
+    }
+}
+
+/// Stands for a candidate whose code could not be generated.  Its evaluation always fails.
+struct ErrorCode {
+    error: CodeSizeError,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "<{}>", self.error)
+    }
+}
+
+impl KernelEvaluator for ErrorCode {
+    fn evaluate(&mut self) -> Option<f64> {
+        None
+    }
+}
+
+/// An artifact compiled into a shared library.
+struct LoadedArtifact {
+    source: String,
+    entry_point: String,
+    arguments: Vec<ThunkArg>,
+    library: libloading::Library,
+    /// The temporary directory holding the library, removed when the artifact is dropped.
+    /// Declared after `library` so that the library is unloaded first.
+    _lib_dir: tempfile::TempDir,
+}
+
+impl fmt::Display for LoadedArtifact {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.source)
+    }
+}
+
+impl KernelEvaluator for LoadedArtifact {
+    fn evaluate(&mut self) -> Option<f64> {
+        Some(with_raw_args(&self.arguments, |ptrs| {
+            compile::exec(&self.library, &self.entry_point, ptrs)
+        }))
     }
 }
 
@@ -208,11 +368,26 @@ enum RawArg {
     Array(*mut libc::c_void),
 }
 
-/// Given a function string and its arguments as ThunkArg, compile to a binary, executes it and
-/// returns the time elapsed. Converts ThunkArgs to HoldTHunk as we want to allocate memory for
-/// temporary arrays at the last possible moment
-fn function_evaluate(fun_str: &str, args: &[ThunkArg]) -> Result<f64, ()> {
-    debug!("running code {}", fun_str);
+/// Compiles a function source and calls `f` with a function that runs the compiled code,
+/// so that the code is compiled only once for all the runs.
+fn with_compiled<T>(source: &Source, f: impl FnOnce(&Runner) -> T) -> T {
+    debug!("compiling code {}", source);
+    match source {
+        Source::C(fun_str) => {
+            let (_temp_dir, templib_name) = compile_library(fun_str);
+            let library = compile::link(&templib_name);
+            f(&|ptrs| compile::exec(&library, ENTRY_POINT, ptrs))
+        }
+        #[cfg(feature = "llvm")]
+        Source::Llvm(module) => {
+            llvm::jit(module, |kernel| f(&|ptrs| kernel.execute(ptrs)))
+        }
+    }
+}
+
+/// Compiles C code into a shared library.  Returns the temporary directory holding the
+/// library, which is removed when dropped, and the path of the library.
+fn compile_library(source: &str) -> (tempfile::TempDir, String) {
     let temp_dir = unwrap!(tempfile::tempdir());
     let templib_name = temp_dir
         .path()
@@ -220,11 +395,17 @@ fn function_evaluate(fun_str: &str, args: &[ThunkArg]) -> Result<f64, ()> {
         .to_string_lossy()
         .into_owned();
     let mut source_file = unwrap!(tempfile::tempfile());
-    unwrap!(source_file.write_all(fun_str.as_bytes()));
+    unwrap!(source_file.write_all(source.as_bytes()));
     let compile_status = compile::compile(source_file, &templib_name);
     if !compile_status.success() {
-        panic!("Could not compile file:\n{}", fun_str);
+        panic!("Could not compile file:\n{}", source);
     }
+    (temp_dir, templib_name)
+}
+
+/// Calls `f` with pointers to the values of the arguments. Converts ThunkArgs to HoldTHunk as
+/// we want to allocate memory for temporary arrays at the last possible moment
+fn with_raw_args<T>(args: &[ThunkArg], f: impl FnOnce(Vec<*mut libc::c_void>) -> T) -> T {
     // Lock the arguments and allocate temporary arrays
     //
     // `thunks` owns the array values
@@ -269,20 +450,19 @@ fn function_evaluate(fun_str: &str, args: &[ThunkArg]) -> Result<f64, ()> {
             RawArg::Size(size) => size as *mut i32 as *mut libc::c_void,
         })
         .collect::<Vec<_>>();
-    let time = compile::link_and_exec(&templib_name, &String::from("entry_point"), ptrs);
-    Ok(time)
+    f(ptrs)
 }
 
 type AsyncPayload<'b> = (
     explorer::Candidate,
-    String,
-    Vec<ThunkArg>,
+    (Result<Source, CodeSizeError>, Vec<ThunkArg>),
     AsyncCallback<'b>,
 );
 
 pub struct AsyncEvaluator<'b> {
     context: &'b Context,
     sender: mpsc::SyncSender<AsyncPayload<'b>>,
+    cancel: &'b device::CancellationToken,
 }
 
 impl<'b, 'c> device::AsyncEvaluator<'c> for AsyncEvaluator<'b>
@@ -294,13 +474,19 @@ where
         candidate: explorer::Candidate,
         callback: device::AsyncCallback<'c>,
     ) {
-        let (fun_str, code_args);
+        if self.cancel.is_cancelled() {
+            return device::CancelledKernel::report(candidate, callback);
+        }
+
+        let (source, code_args);
         {
             let dev_fun = codegen::Function::build(&candidate.space);
             code_args = self.context.gen_args(&dev_fun);
-            let mut printer = X86printer::default();
-            fun_str = printer.wrapper_function(&dev_fun);
+            source = self.context.print(&dev_fun, Some(MAX_CODE_SIZE));
+        }
+        if let Err(ref err) = source {
+            warn!("skipping kernel {}: {}", candidate, err);
         }
-        unwrap!(self.sender.send((candidate, fun_str, code_args, callback)));
+        unwrap!(self.sender.send((candidate, (source, code_args), callback)));
     }
 }
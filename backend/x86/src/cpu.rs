@@ -1,6 +1,6 @@
 //! Describes CUDA-enabled GPUs.
 use telamon::codegen::Function;
-use telamon::device;
+use telamon::device::{self, vectorization::VectorRule};
 use telamon::ir::{self, Type};
 use telamon::model::{self, HwPressure};
 use telamon::search_space::*;
@@ -27,7 +27,8 @@ impl Cpu {
 
 impl device::Device for Cpu {
     fn print(&self, fun: &Function, out: &mut dyn Write) {
-        write!(out, "{}", X86printer::default().wrapper_function(fun)).unwrap();
+        let source = X86printer::default().wrapper_function(fun).unwrap();
+        write!(out, "{}", source).unwrap();
     }
 
     fn check_type(&self, t: Type) -> Result<(), ir::TypeError> {
@@ -60,8 +61,9 @@ impl device::Device for Cpu {
         false
     }
 
-    fn max_vectorization(&self, _: &ir::Operator) -> [u32; 2] {
-        [1, 1]
+    fn vectorization_rules(&self) -> &[VectorRule] {
+        // Operators without a rule are not vectorized.
+        &[]
     }
 
     fn has_vector_registers(&self) -> bool {
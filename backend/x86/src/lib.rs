@@ -6,9 +6,11 @@ mod compile;
 mod context;
 mod cpu;
 mod cpu_argument;
+#[cfg(feature = "llvm")]
+mod llvm;
 mod printer;
 
-pub use crate::context::Context;
+pub use crate::context::{Compiler, Context};
 pub use crate::cpu::Cpu;
 
 use fxhash::FxHashMap;
@@ -0,0 +1,583 @@
+//! Prints functions as LLVM IR and compiles them in-process with the LLVM JIT.
+//!
+//! Registers are allocated on the stack and loops are printed as branches between basic
+//! blocks; LLVM promotes the registers back to SSA values when optimizing the module.
+use crate::NameGenerator;
+use inkwell::module::Module as LlvmModule;
+use inkwell::passes::{PassManager, PassManagerBuilder};
+use inkwell::targets::{InitializationConfig, Target};
+use inkwell::{memory_buffer::MemoryBuffer, OptimizationLevel};
+use itertools::Itertools;
+use num::{ToPrimitive, Zero};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as WriteFmt};
+use std::sync::{Barrier, Once};
+use std::time::Instant;
+use telamon::codegen::*;
+use telamon::ir::Type;
+use telamon::search_space::{DimKind, Domain};
+use utils::unwrap;
+
+/// Name of the function executed by each thread.
+const KERNEL_NAME: &str = "telamon_kernel";
+
+/// Signature of the function executed by each thread.  It takes the index of the thread in
+/// each thread dimension, the arguments of the kernel, and a function to call with the
+/// last argument to synchronize threads.
+type KernelFn = unsafe extern "C" fn(
+    *const u32,
+    *mut *mut libc::c_void,
+    extern "C" fn(*mut libc::c_void),
+    *mut libc::c_void,
+);
+
+/// A function printed as an LLVM module.
+pub(crate) struct Module {
+    /// The LLVM IR of the module.
+    ir: String,
+    /// The size of thread dimensions.
+    thread_dims: Vec<u32>,
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.ir)
+    }
+}
+
+/// Returns the LLVM type used to represent values of type `t`.
+fn llvm_type(t: Type) -> &'static str {
+    match t {
+        Type::I(1) => "i1",
+        Type::I(8) => "i8",
+        Type::I(16) => "i16",
+        Type::I(32) => "i32",
+        Type::I(64) | Type::PtrTo(..) => "i64",
+        Type::F(32) => "float",
+        Type::F(64) => "double",
+        _ => panic!("invalid LLVM type: {}", t),
+    }
+}
+
+/// Returns the number of bits of values of type `t`.
+fn bits(t: Type) -> u16 {
+    match t {
+        Type::I(bits) | Type::F(bits) => bits,
+        Type::PtrTo(..) => 64,
+    }
+}
+
+/// Returns the scalar value of an instruction operand or result.
+fn scalar<T>(value: &llir::ScalarOrVector<T>) -> &T {
+    match value {
+        llir::ScalarOrVector::Scalar(scalar) => scalar,
+        llir::ScalarOrVector::Vector(..) => {
+            panic!("x86 backend does not support vectors.")
+        }
+    }
+}
+
+/// Prints a floating-point literal.  LLVM expects the hexadecimal representation of the
+/// value as a double, even for single-precision values.
+fn float_literal(value: &num::rational::Ratio<num::BigInt>, bits: u16) -> String {
+    let value = unwrap!(value.numer().to_f64()) / unwrap!(value.denom().to_f64());
+    let value = match bits {
+        32 => f64::from(value as f32),
+        64 => value,
+        _ => panic!("Cannot print floating point value with {} bits", bits),
+    };
+    format!("0x{:016X}", value.to_bits())
+}
+
+/// Prints functions as LLVM modules.
+#[derive(Default)]
+pub(crate) struct LlvmPrinter {
+    buffer: CodeBuffer,
+    /// The type of the registers used by the function.  Each register is allocated on the
+    /// stack at the beginning of the function.
+    registers: BTreeMap<String, Type>,
+    num_values: usize,
+    num_blocks: usize,
+}
+
+impl LlvmPrinter {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        LlvmPrinter {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+            ..LlvmPrinter::default()
+        }
+    }
+
+    /// Prints a `Function` as an LLVM module.  Fails if the code exceeds the size limit of
+    /// the printer.
+    pub fn module(&mut self, function: &Function) -> Result<Module, CodeSizeError> {
+        let mut namegen = NameGenerator::default();
+        let interner = Interner::default();
+        let name_map = &mut NameMap::new(&interner, function, &mut namegen);
+        // Load thread indexes.
+        assert!(function.block_dims().is_empty());
+        for (i, dim) in function.thread_dims().iter().enumerate() {
+            let ptr = self.emit(format_args!("getelementptr i32, i32* %tid, i32 {}", i));
+            let index = self.emit(format_args!("load i32, i32* {}", ptr));
+            self.assign(name_map.name_index(dim.id()), &index);
+        }
+        // Load parameters.
+        for (i, val) in function.device_code_args().enumerate() {
+            let reg = name_map.name_param_val(val.key());
+            let ptr = self.emit(format_args!("getelementptr i8*, i8** %args, i32 {}", i));
+            let arg = self.emit(format_args!("load i8*, i8** {}", ptr));
+            let value = if val.elem_t().is_some() {
+                let ptr = self.emit(format_args!("bitcast i8* {} to i8**", arg));
+                let array = self.emit(format_args!("load i8*, i8** {}", ptr));
+                let t = llvm_type(reg.t());
+                self.emit(format_args!("ptrtoint i8* {} to {}", array, t))
+            } else {
+                let t = llvm_type(val.t());
+                let ptr = self.emit(format_args!("bitcast i8* {} to {}*", arg, t));
+                self.emit(format_args!("load {}, {}* {}", t, t, ptr))
+            };
+            self.assign(reg, &value);
+        }
+        // Declare memory blocks.
+        for block in function.mem_blocks() {
+            match block.alloc_scheme() {
+                AllocationScheme::Shared => panic!("No shared mem in cpu!!"),
+                AllocationScheme::PrivatisedGlobal => {
+                    Printer::new(self, name_map).privatise_global_block(block, function)
+                }
+                AllocationScheme::Global => (),
+            }
+        }
+        // Compute size casts.
+        for dim in function.dimensions() {
+            if !dim.kind().intersects(DimKind::UNROLL | DimKind::LOOP) {
+                continue;
+            }
+            for level in dim.induction_levels() {
+                if let Some((_, ref incr)) = level.increment {
+                    let reg = name_map.declare_size_cast(incr, level.t());
+                    if let Some(reg) = reg {
+                        let old_name = name_map.name_size(incr, Type::I(32));
+                        self.print_inst(
+                            llir::Instruction::cast(level.t(), reg, old_name)
+                                .unwrap()
+                                .into(),
+                        );
+                    }
+                }
+            }
+        }
+        // Initialize induction levels.
+        for level in function.init_induction_levels() {
+            Printer::new(self, name_map).parallel_induction_level(level);
+        }
+        // Print the body.
+        Printer::new(self, name_map).cfg(function, function.cfg());
+        let body = self.buffer.take()?;
+        let allocas = std::mem::replace(&mut self.registers, BTreeMap::new())
+            .into_iter()
+            .format_with("", |(name, t), f| {
+                f(&format_args!("  %{} = alloca {}\n", name, llvm_type(t)))
+            })
+            .to_string();
+        let ir = format!(
+            include_str!("template/kernel.ll.template"),
+            name = KERNEL_NAME,
+            allocas = allocas,
+            body = body,
+        );
+        let thread_dims = function
+            .thread_dims()
+            .iter()
+            .map(|dim| unwrap!(dim.size().as_int()))
+            .collect();
+        Ok(Module { ir, thread_dims })
+    }
+
+    /// Prints an instruction that produces a value and returns the name of the value.
+    fn emit(&mut self, inst: fmt::Arguments) -> String {
+        let value = format!("%.v{}", self.num_values);
+        self.num_values += 1;
+        unwrap!(writeln!(self.buffer, "  {} = {}", value, inst));
+        value
+    }
+
+    /// Returns a fresh basic block name.
+    fn gen_block(&mut self) -> String {
+        let block = format!(".b{}", self.num_blocks);
+        self.num_blocks += 1;
+        block
+    }
+
+    /// Ends the current basic block and starts a new one with the given name.
+    fn start_block(&mut self, block: &str) {
+        unwrap!(writeln!(self.buffer, "  br label %{}\n{}:", block, block));
+    }
+
+    /// Records that a register is used by the function.
+    fn declare(&mut self, reg: llir::Register<'_>) {
+        let t = *self
+            .registers
+            .entry(reg.name().to_string())
+            .or_insert(reg.t());
+        assert_eq!(t, reg.t(), "register {} used with different types", reg);
+    }
+
+    /// Loads the value of a register.
+    fn load(&mut self, reg: llir::Register<'_>) -> String {
+        self.declare(reg);
+        let t = llvm_type(reg.t());
+        self.emit(format_args!("load {}, {}* %{}", t, t, reg.name()))
+    }
+
+    /// Stores a value in a register.
+    fn assign(&mut self, reg: llir::Register<'_>, value: &str) {
+        self.declare(reg);
+        let t = llvm_type(reg.t());
+        unwrap!(writeln!(
+            self.buffer,
+            "  store {} {}, {}* %{}",
+            t,
+            value,
+            t,
+            reg.name()
+        ));
+    }
+
+    /// Returns the value of an operand.
+    fn operand(&mut self, operand: &llir::Operand<'_>) -> String {
+        match operand {
+            llir::Operand::Register(reg) => self.load(*reg),
+            llir::Operand::IntLiteral(value, 1) => {
+                let value = if value.is_zero() { "false" } else { "true" };
+                value.to_string()
+            }
+            llir::Operand::IntLiteral(value, _) => value.to_string(),
+            llir::Operand::FloatLiteral(value, bits) => float_literal(value, *bits),
+        }
+    }
+
+    /// Prints a binary instruction whose operands and result have type `t`.
+    fn arith(&mut self, inst: &str, t: Type, lhs: &str, rhs: &str) -> String {
+        self.emit(format_args!("{} {} {}, {}", inst, llvm_type(t), lhs, rhs))
+    }
+
+    /// Selects `lhs` if `lhs cmp rhs` and `rhs` otherwise.
+    fn select(&mut self, cmp: &str, t: Type, lhs: &str, rhs: &str) -> String {
+        let cond = self.arith(cmp, t, lhs, rhs);
+        let t = llvm_type(t);
+        self.emit(format_args!(
+            "select i1 {}, {} {}, {} {}",
+            cond, t, lhs, t, rhs
+        ))
+    }
+
+    /// Converts a value from `src_t` to `dst_t`.
+    fn cast(&mut self, value: &str, src_t: Type, dst_t: Type) -> String {
+        let (src_bits, dst_bits) = (bits(src_t), bits(dst_t));
+        let op = match (src_t.is_float(), dst_t.is_float()) {
+            (false, false) if src_bits == dst_bits => return value.to_string(),
+            (false, false) if src_bits > dst_bits => "trunc",
+            (false, false) if src_bits == 1 => "zext",
+            (false, false) => "sext",
+            (false, true) if src_bits == 1 => "uitofp",
+            (false, true) => "sitofp",
+            (true, false) => "fptosi",
+            (true, true) if src_bits == dst_bits => return value.to_string(),
+            (true, true) if src_bits > dst_bits => "fptrunc",
+            (true, true) => "fpext",
+        };
+        let (src_t, dst_t) = (llvm_type(src_t), llvm_type(dst_t));
+        self.emit(format_args!("{} {} {} to {}", op, src_t, value, dst_t))
+    }
+
+    /// Multiplies two integers of type `arg_t`.
+    fn mul(&mut self, arg_t: Type, spec: llir::MulSpec, lhs: &str, rhs: &str) -> String {
+        if let llir::MulSpec::Low = spec {
+            return self.arith("mul", arg_t, lhs, rhs);
+        }
+        let wide_t = Type::I(2 * bits(arg_t));
+        let lhs = self.cast(lhs, arg_t, wide_t);
+        let rhs = self.cast(rhs, arg_t, wide_t);
+        let product = self.arith("mul", wide_t, &lhs, &rhs);
+        match spec {
+            llir::MulSpec::Low => unreachable!(),
+            llir::MulSpec::Wide => product,
+            llir::MulSpec::High => {
+                let shift = bits(arg_t).to_string();
+                let high = self.arith("ashr", wide_t, &product, &shift);
+                self.cast(&high, wide_t, arg_t)
+            }
+        }
+    }
+
+    fn unary(&mut self, op: llir::UnOp, arg: &str) -> String {
+        match op {
+            llir::UnOp::Move { .. } => arg.to_string(),
+            llir::UnOp::Cast { src_t, dst_t } => self.cast(arg, src_t, dst_t),
+            llir::UnOp::Exp { t, .. } => self.emit(format_args!(
+                "call {t} @llvm.exp.f{bits}({t} {arg})",
+                t = llvm_type(t),
+                bits = bits(t),
+                arg = arg
+            )),
+            llir::UnOp::Sqrt { t } => self.emit(format_args!(
+                "call {t} @llvm.sqrt.f{bits}({t} {arg})",
+                t = llvm_type(t),
+                bits = bits(t),
+                arg = arg
+            )),
+        }
+    }
+
+    fn binary(&mut self, op: llir::BinOp, lhs: &str, rhs: &str) -> String {
+        use llir::BinOp::*;
+
+        match op {
+            IAdd { arg_t } => self.arith("add", arg_t, lhs, rhs),
+            ISub { arg_t } => self.arith("sub", arg_t, lhs, rhs),
+            IDiv { arg_t } => self.arith("sdiv", arg_t, lhs, rhs),
+            IMul { arg_t, spec } => self.mul(arg_t, spec, lhs, rhs),
+            IMax { arg_t } => self.select("icmp sgt", arg_t, lhs, rhs),
+            FAdd { t, .. } => self.arith("fadd", t, lhs, rhs),
+            FSub { t, .. } => self.arith("fsub", t, lhs, rhs),
+            FMul { t, .. } => self.arith("fmul", t, lhs, rhs),
+            FDiv { t, .. } => self.arith("fdiv", t, lhs, rhs),
+            FMax { t } => self.select("fcmp ogt", t, lhs, rhs),
+            FMin { t } => self.select("fcmp olt", t, lhs, rhs),
+            Set { op, arg_t } => {
+                let cmp = match (arg_t.is_float(), op) {
+                    (false, llir::CmpOp::Eq) => "icmp eq",
+                    (false, llir::CmpOp::Ne) => "icmp ne",
+                    (false, llir::CmpOp::Lt) => "icmp slt",
+                    (false, llir::CmpOp::Le) => "icmp sle",
+                    (false, llir::CmpOp::Gt) => "icmp sgt",
+                    (false, llir::CmpOp::Ge) => "icmp sge",
+                    (true, llir::CmpOp::Eq) => "fcmp oeq",
+                    (true, llir::CmpOp::Ne) => "fcmp une",
+                    (true, llir::CmpOp::Lt) => "fcmp olt",
+                    (true, llir::CmpOp::Le) => "fcmp ole",
+                    (true, llir::CmpOp::Gt) => "fcmp ogt",
+                    (true, llir::CmpOp::Ge) => "fcmp oge",
+                };
+                self.arith(cmp, arg_t, lhs, rhs)
+            }
+            And { t } => self.arith("and", t, lhs, rhs),
+            Or { t } => self.arith("or", t, lhs, rhs),
+            Xor { t } => self.arith("xor", t, lhs, rhs),
+        }
+    }
+
+    fn ternary(&mut self, op: llir::TernOp, a: &str, b: &str, c: &str) -> String {
+        match op {
+            llir::TernOp::IMad { arg_t, spec } => {
+                let product = self.mul(arg_t, spec, a, b);
+                self.arith("add", op.ret_t(), &product, c)
+            }
+            // Like the C printer, do not fuse the operations.
+            llir::TernOp::FFma { t, .. } => {
+                let product = self.arith("fmul", t, a, b);
+                self.arith("fadd", t, &product, c)
+            }
+            llir::TernOp::Mma { .. } => panic!("tensor cores are not available on x86"),
+        }
+    }
+
+    /// Returns a pointer to values of type `t` at the given address.
+    fn address(&mut self, address: llir::Address<'_>, t: Type) -> String {
+        let llir::Address::Register(reg, offset) = address;
+        let base = self.load(reg);
+        let base = self.cast(&base, reg.t(), Type::I(64));
+        let address = if offset == 0 {
+            base
+        } else {
+            self.emit(format_args!("add i64 {}, {}", base, offset))
+        };
+        self.emit(format_args!(
+            "inttoptr i64 {} to {}*",
+            address,
+            llvm_type(t)
+        ))
+    }
+
+    /// Prints an instruction other than a jump.
+    fn instruction(&mut self, inst: &llir::Instruction<'_>) {
+        use llir::Instruction::*;
+
+        match inst {
+            Unary(op, d, [a]) => {
+                let a = self.operand(scalar(a));
+                let value = self.unary(*op, &a);
+                self.assign(*scalar(d), &value);
+            }
+            Binary(op, d, [a, b]) => {
+                let a = self.operand(scalar(a));
+                let b = self.operand(scalar(b));
+                let value = self.binary(*op, &a, &b);
+                self.assign(*scalar(d), &value);
+            }
+            Ternary(op, d, [a, b, c]) => {
+                let a = self.operand(scalar(a));
+                let b = self.operand(scalar(b));
+                let c = self.operand(scalar(c));
+                let value = self.ternary(*op, &a, &b, &c);
+                self.assign(*scalar(d), &value);
+            }
+            Load(spec, d, address) => {
+                let t = llvm_type(spec.t());
+                let ptr = self.address(*address, spec.t());
+                let value = self.emit(format_args!("load {}, {}* {}", t, t, ptr));
+                self.assign(*scalar(d), &value);
+            }
+            Store(spec, address, [value]) => {
+                let value = self.operand(scalar(value));
+                let ptr = self.address(*address, spec.t());
+                let t = llvm_type(spec.t());
+                unwrap!(writeln!(
+                    self.buffer,
+                    "  store {} {}, {}* {}",
+                    t, value, t, ptr
+                ));
+            }
+            Jump(..) => unreachable!("jumps are printed by `print_inst`"),
+            Sync => unwrap!(writeln!(self.buffer, "  call void %sync(i8* %barrier)")),
+        }
+    }
+}
+
+impl InstPrinter for LlvmPrinter {
+    fn print_label(&mut self, label: llir::Label<'_>) {
+        self.start_block(label.name());
+    }
+
+    fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
+        match (inst.predicate, &inst.instruction) {
+            (Some(predicate), llir::Instruction::Jump(label)) => {
+                let predicate = self.load(predicate);
+                let next = self.gen_block();
+                unwrap!(writeln!(
+                    self.buffer,
+                    "  br i1 {}, label %{}, label %{}\n{}:",
+                    predicate,
+                    label.name(),
+                    next,
+                    next
+                ));
+            }
+            (None, llir::Instruction::Jump(label)) => {
+                // Start an unreachable block to hold the code following the jump.
+                let next = self.gen_block();
+                unwrap!(writeln!(
+                    self.buffer,
+                    "  br label %{}\n{}:",
+                    label.name(),
+                    next
+                ));
+            }
+            (Some(predicate), instruction) => {
+                let predicate = self.load(predicate);
+                let (then, next) = (self.gen_block(), self.gen_block());
+                unwrap!(writeln!(
+                    self.buffer,
+                    "  br i1 {}, label %{}, label %{}\n{}:",
+                    predicate, then, next, then
+                ));
+                self.instruction(instruction);
+                self.start_block(&next);
+            }
+            (None, instruction) => self.instruction(instruction),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
+}
+
+/// A kernel compiled by the LLVM JIT.
+pub(crate) struct Kernel<'a> {
+    function: KernelFn,
+    thread_dims: &'a [u32],
+}
+
+/// The arguments of a kernel, shared by all its threads.
+struct SharedArgs(*mut *mut libc::c_void);
+
+// The kernel is responsible for synchronizing accesses to the arguments.
+unsafe impl Sync for SharedArgs {}
+
+/// Synchronizes the threads of a kernel.  `barrier` points to a `Barrier`.
+extern "C" fn sync(barrier: *mut libc::c_void) {
+    unsafe { (*(barrier as *const Barrier)).wait() };
+}
+
+impl<'a> Kernel<'a> {
+    /// Runs the kernel with the given arguments and returns the execution time in
+    /// nanoseconds.  The time needed to spawn and join the threads is not accounted for.
+    pub fn execute(&self, mut args: Vec<*mut libc::c_void>) -> f64 {
+        let function = self.function;
+        let num_threads = self.thread_dims.iter().product::<u32>();
+        let barrier = Barrier::new(num_threads as usize);
+        // Synchronizes the spawned threads with the current one, which measures the time
+        // between the start and the end of the kernel.
+        let start = Barrier::new(num_threads as usize + 1);
+        let end = Barrier::new(num_threads as usize + 1);
+        let args = SharedArgs(args.as_mut_ptr());
+        let t = unwrap!(crossbeam::scope(|scope| {
+            for thread_id in 0..num_threads {
+                // Thread indexes are linearized with the first dimension varying fastest.
+                let mut rest = thread_id;
+                let tid = self
+                    .thread_dims
+                    .iter()
+                    .map(|&size| {
+                        let index = rest % size;
+                        rest /= size;
+                        index
+                    })
+                    .collect_vec();
+                let (barrier, args, start, end) = (&barrier, &args, &start, &end);
+                scope.spawn(move |_| {
+                    let barrier = barrier as *const Barrier as *mut libc::c_void;
+                    start.wait();
+                    unsafe { function(tid.as_ptr(), args.0, sync, barrier) };
+                    end.wait();
+                });
+            }
+            start.wait();
+            let t0 = Instant::now();
+            end.wait();
+            Instant::now() - t0
+        }));
+        t.as_secs() as f64 * 1e9 + f64::from(t.subsec_nanos())
+    }
+}
+
+static INIT_TARGET: Once = Once::new();
+
+/// Compiles a module with the LLVM JIT and calls `f` with the compiled kernel.
+pub(crate) fn jit<T>(module: &Module, f: impl FnOnce(&Kernel) -> T) -> T {
+    INIT_TARGET.call_once(|| {
+        unwrap!(Target::initialize_native(&InitializationConfig::default()));
+    });
+    let context = inkwell::context::Context::create();
+    let buffer =
+        MemoryBuffer::create_from_memory_range_copy(module.ir.as_bytes(), KERNEL_NAME);
+    let llvm_module: LlvmModule = context
+        .create_module_from_ir(buffer)
+        .unwrap_or_else(|err| panic!("{}\n{}", err, module.ir));
+    let pass_manager_builder = PassManagerBuilder::create();
+    pass_manager_builder.set_optimization_level(OptimizationLevel::Aggressive);
+    let pass_manager = PassManager::create(());
+    pass_manager_builder.populate_module_pass_manager(&pass_manager);
+    pass_manager.run_on(&llvm_module);
+    let engine =
+        unwrap!(llvm_module.create_jit_execution_engine(OptimizationLevel::Aggressive));
+    let address = unwrap!(engine.get_function_address(KERNEL_NAME));
+    let kernel = Kernel {
+        function: unsafe { std::mem::transmute::<usize, KernelFn>(address) },
+        thread_dims: &module.thread_dims,
+    };
+    f(&kernel)
+}
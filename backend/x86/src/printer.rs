@@ -10,7 +10,7 @@ use utils::unwrap;
 
 #[derive(Default)]
 pub(crate) struct X86printer {
-    buffer: String,
+    buffer: CodeBuffer,
 }
 
 fn param_t(param: &ParamVal) -> String {
@@ -28,6 +28,13 @@ fn param_t(param: &ParamVal) -> String {
 }
 
 impl X86printer {
+    /// Creates a printer which aborts when the generated code exceeds `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        X86printer {
+            buffer: CodeBuffer::with_size_limit(size_limit),
+        }
+    }
+
     /// Declares all parameters of the function with the appropriate type
     fn param_decl(&self, param: &ParamVal) -> String {
         format!("{} {}", param_t(param), param.key().ident())
@@ -80,8 +87,8 @@ impl X86printer {
         decls.join("\n  ")
     }
 
-    /// Prints a `Function`.
-    pub fn function(&mut self, function: &Function) -> String {
+    /// Prints a `Function`.  Fails if the code exceeds the size limit of the printer.
+    pub fn function(&mut self, function: &Function) -> Result<String, CodeSizeError> {
         let mut namegen = NameGenerator::default();
         let interner = Interner::default();
         let name_map = &mut NameMap::new(&interner, function, &mut namegen);
@@ -157,11 +164,12 @@ impl X86printer {
         // BODY
         Printer::new(self, name_map).cfg(function, function.cfg());
         let var_decls = self.var_decls(&namegen);
+        let body = self.buffer.take()?;
         return_string.push_str(&var_decls);
-        return_string.push_str(&self.buffer);
+        return_string.push_str(&body);
         // Close function bracket
         return_string.push('}');
-        return_string
+        Ok(return_string)
     }
 
     /// Function takes parameters as an array of void* pointers
@@ -270,9 +278,9 @@ pthread_barrier_destroy(&barrier);
     }
 
     /// wrap the kernel call into a function with a fixed interface
-    pub fn wrapper_function(&mut self, func: &Function) -> String {
-        let fun_str = self.function(func);
-        format!(
+    pub fn wrapper_function(&mut self, func: &Function) -> Result<String, CodeSizeError> {
+        let fun_str = self.function(func)?;
+        Ok(format!(
             include_str!("template/host.c.template"),
             fun_name = func.name(),
             fun_str = fun_str,
@@ -283,7 +291,7 @@ pthread_barrier_destroy(&barrier);
                 .format_with("", |p, f| f(&format_args!(", {}", p.key().ident()))),
             entry_point = self.thread_gen(func),
             dim_decl = self.build_thread_id_struct(func),
-        )
+        ))
     }
 }
 
@@ -295,4 +303,8 @@ impl InstPrinter for X86printer {
     fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>) {
         writeln!(self.buffer, "{}", inst.c99()).unwrap();
     }
+
+    fn is_full(&self) -> bool {
+        self.buffer.is_overflowed()
+    }
 }
@@ -0,0 +1,42 @@
+//! Compares the sequential and parallel filtering of the initial domain.
+use criterion::{criterion_group, criterion_main, Criterion};
+use telamon::device::{fake, Context};
+use telamon::helper;
+use telamon::ir::{self, Size, Type};
+use telamon::search_space::MemSpace;
+
+/// Builds a kernel with a few loop nests, so that there are enough filters to run.
+fn kernel(context: &dyn Context) -> helper::Builder {
+    let signature = ir::Signature::new("init_domain");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let base_addr = builder.cast(&0i64, context.device().pointer_type(MemSpace::GLOBAL));
+    for _ in 0..4 {
+        let d0 = builder.open_dim(Size::new_const(64));
+        let d1 = builder.open_dim(Size::new_const(16));
+        let (addr, pattern) =
+            builder.tensor_access(&base_addr, None, Type::F(32), &[&d0, &d1]);
+        let ld = builder.ld(Type::F(32), &addr, pattern);
+        let d2 = builder.open_dim(Size::new_const(4));
+        builder.mad(&ld, &ld, &2f32);
+        builder.close_dim(&d2);
+        builder.close_dim(&d1);
+        builder.close_dim(&d0);
+    }
+    builder
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let builder = kernel(&context);
+    c.bench_function("init_domain sequential", move |b| {
+        b.iter(|| builder.get_sequential())
+    });
+    let builder = kernel(&context);
+    c.bench_function("init_domain parallel", move |b| {
+        b.iter(|| builder.get_clone())
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -10,7 +10,23 @@ fn add_dependency(dep: &Path) {
     println!("cargo:rerun-if-changed={}", dep.display());
 }
 
+/// Exports the version of the compiler, which backend plugins must share with the host.
+fn export_rustc_version() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .unwrap_or_else(|err| {
+            eprintln!("could not run rustc: {}", err);
+            std::process::exit(-1);
+        });
+    let version = String::from_utf8_lossy(&output.stdout);
+    println!("cargo:rustc-env=TELAMON_RUSTC_VERSION={}", version.trim());
+}
+
 fn main() {
+    export_rustc_version();
+
     let exh_file = "src/search_space/choices.exh";
     let out_dir = std::env::var_os("OUT_DIR").unwrap();
     for file in glob::glob("src/search_space/*.exh").unwrap() {
@@ -118,7 +118,8 @@ fn run_evaluations(
 ) -> Vec<Vec<f64>> {
     let runtimes = candidates.iter().map(|_| Mutex::new(vec![])).collect_vec();
     let stabilizer = &context.stabilizer();
-    context.async_eval(1, device::EvalMode::TestEval, &|evaluator| {
+    let cancel = device::CancellationToken::new();
+    context.async_eval(1, 0, device::EvalMode::TestEval, &cancel, &|evaluator| {
         for (candidate, results) in candidates.iter().zip_eq(&runtimes) {
             for _ in 0..num_samples {
                 if let Some(duration) = sleep {
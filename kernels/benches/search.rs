@@ -0,0 +1,65 @@
+//! Benchmarks the hot paths of the search on representative kernels.
+use std::rc::Rc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use telamon::explorer::config::BanditConfig;
+use telamon_kernels::search_bench;
+
+/// Configure the bencher.
+fn config_criterion() -> Criterion {
+    Criterion::default().sample_size(20).configure_from_args()
+}
+
+/// Benchmarks the operations performed on candidates.
+fn candidates(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+    for kernel in search_bench::kernels() {
+        let kernel = Rc::new(kernel);
+
+        let k = Rc::clone(&kernel);
+        c.bench_function(&format!("{} clone", kernel.name), move |b| {
+            b.iter(|| k.clone_root())
+        });
+
+        let k = Rc::clone(&kernel);
+        c.bench_function(&format!("{} apply_choice", kernel.name), move |b| {
+            b.iter(|| k.apply_choice())
+        });
+
+        let k = Rc::clone(&kernel);
+        c.bench_function(&format!("{} bound", kernel.name), move |b| {
+            b.iter(|| k.bound())
+        });
+
+        let k = Rc::clone(&kernel);
+        c.bench_function(&format!("{} fix_order", kernel.name), move |b| {
+            let implementation = k.implementation();
+            b.iter(|| k.fix_order(&implementation))
+        });
+    }
+}
+
+/// Benchmarks a MCTS selection followed by the backpropagation of its evaluation.
+fn mcts(c: &mut Criterion) {
+    let _ = env_logger::try_init();
+    for kernel in search_bench::kernels() {
+        let name = format!("{} mcts step", kernel.name);
+        c.bench_function(&name, move |b| {
+            let config = BanditConfig::default();
+            let mut mcts = kernel.mcts(&config);
+            b.iter(|| {
+                if !mcts.step() {
+                    mcts = kernel.mcts(&config);
+                }
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = config_criterion();
+    targets = candidates, mcts
+}
+
+criterion_main!(benches);
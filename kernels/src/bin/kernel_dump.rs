@@ -42,4 +42,40 @@ fn main() {
         100,
         linalg::FusedMMP::new(16, 16, 16).activation_fun(ActivationFunction::Sigmoid)
     );
+    kernel_dump!(
+        linalg::Conv2d<f32>,
+        conv2d,
+        100,
+        linalg::Conv2dP::new(1, 4, 4, 8, 8)
+    );
+    kernel_dump!(
+        linalg::DepthwiseConv<f32>,
+        depthwise_conv,
+        100,
+        linalg::DepthwiseConvP::new(1, 4, 8, 8)
+    );
+    kernel_dump!(
+        linalg::PointwiseConv<f32>,
+        pointwise_conv,
+        100,
+        linalg::PointwiseConvP::new(1, 4, 8, 8, 8)
+    );
+    kernel_dump!(
+        linalg::Reduce<f32>,
+        reduce_sum,
+        100,
+        linalg::ReduceP::new(linalg::ReduceOp::Sum, &[16, 64], 1)
+    );
+    kernel_dump!(
+        linalg::Softmax<f32>,
+        softmax,
+        100,
+        linalg::SoftmaxP::new(16, 64)
+    );
+    kernel_dump!(
+        linalg::LayerNorm<f32>,
+        layer_norm,
+        100,
+        linalg::LayerNormP::new(16, 64)
+    );
 }
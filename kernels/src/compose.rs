@@ -4,9 +4,56 @@ use serde::{Deserialize, Serialize};
 use telamon::helper::tensor::*;
 use telamon::helper::{AutoOperand, Builder, Reduce};
 use telamon::ir;
+use telamon::search_space::{Action, Order};
+
+/// Indicates if the loop nest of a consumer is fused with the loop nest of its producer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Fusion {
+    /// Lets the search space decide whether to fuse the loop nests.
+    Free,
+    /// Fuses the loop nests: values are passed from the producer to the consumer in
+    /// registers.
+    Always,
+    /// Runs the loop nest of the producer before the one of the consumer: values are
+    /// materialized in memory.
+    Never,
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Fusion::Free
+    }
+}
+
+/// Constrains the fusion of the loop nest of `consumer` with the loop nest of `producer`.
+/// `consumer` must have been built by mapping the dimensions of `producer`, in the same
+/// order, as done by the other functions of this module.
+///
+/// Each tiling level of a consumer dimension is fused with the same level of the
+/// corresponding producer dimension, so that fusing logical dimensions never merges
+/// dimensions of different sizes.
+pub fn fuse(
+    builder: &mut Builder,
+    producer: &VirtualTensor,
+    consumer: &VirtualTensor,
+    fusion: Fusion,
+) {
+    assert!(producer.same_shape(consumer, builder.function()));
+
+    let order = match fusion {
+        Fusion::Free => return,
+        Fusion::Always => Order::MERGED,
+        Fusion::Never => Order::BEFORE,
+    };
+    for (producer_dim, consumer_dim) in producer.iter().zip_eq(consumer) {
+        for (lhs, rhs) in producer_dim.iter().zip_eq(consumer_dim.iter()) {
+            builder.action(Action::Order(lhs.into(), rhs.into(), order));
+        }
+    }
+}
 
 /// Multiplies a matrix `lhs` with a vector `rhs`
-pub fn matrix_vector_multiply(
+pub fn matrix_vector_multiply<S: Scalar>(
     builder: &mut Builder,
     lhs: &VirtualTensor,
     rhs: &VirtualTensor,
@@ -20,7 +67,7 @@ pub fn matrix_vector_multiply(
 
     // Initialize accumulator
     let accu_init_m = builder.open_mapped_dim(&m);
-    let accu_init_instr = builder.mov(&0f32);
+    let accu_init_instr = builder.mov(&S::zero());
     builder.close_dim(&accu_init_m);
 
     // Map operands and assign accumulator
@@ -43,8 +90,9 @@ pub fn matrix_vector_multiply(
     VirtualTensor::new(acc_instr, vec![acc_dim_m])
 }
 
-/// Multiplies two matrices `lhs` and `rhs`
-pub fn matrix_matrix_multiply(
+/// Multiplies two matrices `lhs` and `rhs`. The products are accumulated in
+/// `S::Accumulator` and the result is rounded back to `S`.
+pub fn matrix_matrix_multiply<S: Scalar>(
     builder: &mut Builder,
     lhs: &VirtualTensor,
     rhs: &VirtualTensor,
@@ -61,7 +109,7 @@ pub fn matrix_matrix_multiply(
     let accu_init_m = builder.open_mapped_dim(&m);
     let accu_init_n = builder.open_mapped_dim(&n);
 
-    let accu_init_instr = builder.mov(&0f32);
+    let accu_init_instr = builder.mov(&S::Accumulator::zero());
 
     builder.close_dim(&accu_init_m);
     builder.close_dim(&accu_init_n);
@@ -83,13 +131,142 @@ pub fn matrix_matrix_multiply(
         builder,
     );
 
-    let acc_instr = builder.mad(&a_operand, &b_operand, &Reduce(accu_init_instr));
+    let acc_instr = if S::t() == S::Accumulator::t() {
+        builder.mad(&a_operand, &b_operand, &Reduce(accu_init_instr))
+    } else {
+        let a_operand = builder.cast(&a_operand, S::Accumulator::t());
+        let b_operand = builder.cast(&b_operand, S::Accumulator::t());
+        builder.mad(&a_operand, &b_operand, &Reduce(accu_init_instr))
+    };
 
     builder.close_dim(&acc_dim_m);
     builder.close_dim(&acc_dim_n);
     builder.close_dim(&acc_dim_k);
 
-    VirtualTensor::new(acc_instr, vec![acc_dim_m, acc_dim_n])
+    let acc = VirtualTensor::new(acc_instr, vec![acc_dim_m, acc_dim_n]);
+    if S::t() == S::Accumulator::t() {
+        acc
+    } else {
+        tensor_map(builder, &acc, |acc, builder| builder.cast(acc, S::t()))
+    }
+}
+
+/// Convolves each channel of an image with its own filter. `windows` holds the windows
+/// of the input the filter is applied to, with dimensions `(n x c x y x x x r x s)` for
+/// the batch, the channels, the output positions and the filter positions, and `filter`
+/// has dimensions `(c x r x s)`.
+pub fn depthwise_conv<S: Scalar>(
+    builder: &mut Builder,
+    windows: &VirtualTensor,
+    filter: &VirtualTensor,
+) -> VirtualTensor {
+    assert!(windows.num_dims() == 6 && filter.num_dims() == 3);
+    assert!(windows[1].size_eq(&filter[0], builder.function()));
+
+    // Initialize accumulator
+    let accu_init_dims = windows
+        .iter()
+        .take(4)
+        .map(|dim| builder.open_mapped_dim(dim))
+        .collect_vec();
+    let accu_init_instr = builder.mov(&S::zero());
+    for dim in &accu_init_dims {
+        builder.close_dim(dim);
+    }
+
+    // Map operands and assign accumulator
+    let acc_dims = accu_init_dims
+        .iter()
+        .map(|dim| builder.open_mapped_dim(dim))
+        .collect_vec();
+    let acc_dim_r = builder.open_mapped_dim(&windows[4]);
+    let acc_dim_s = builder.open_mapped_dim(&windows[5]);
+
+    let window_operand = windows.dim_map(
+        &[
+            &acc_dims[0],
+            &acc_dims[1],
+            &acc_dims[2],
+            &acc_dims[3],
+            &acc_dim_r,
+            &acc_dim_s,
+        ],
+        ir::DimMapScope::Global(()),
+        builder,
+    );
+
+    let filter_operand = filter.dim_map(
+        &[&acc_dims[1], &acc_dim_r, &acc_dim_s],
+        ir::DimMapScope::Global(()),
+        builder,
+    );
+
+    let acc_instr =
+        builder.mad(&window_operand, &filter_operand, &Reduce(accu_init_instr));
+
+    for dim in &acc_dims {
+        builder.close_dim(dim);
+    }
+    builder.close_dim(&acc_dim_r);
+    builder.close_dim(&acc_dim_s);
+
+    VirtualTensor::new(acc_instr, acc_dims)
+}
+
+/// Mixes the channels of an image `input` of dimensions `(n x c x h x w)` with a
+/// `(k x c)` matrix `filter`. Returns an image of dimensions `(n x k x h x w)`.
+pub fn pointwise_conv<S: Scalar>(
+    builder: &mut Builder,
+    input: &VirtualTensor,
+    filter: &VirtualTensor,
+) -> VirtualTensor {
+    assert!(input.num_dims() == 4 && filter.num_dims() == 2);
+    assert!(input[1].size_eq(&filter[1], builder.function()));
+
+    let n = &input[0];
+    let c = &input[1];
+    let h = &input[2];
+    let w = &input[3];
+    let k = &filter[0];
+
+    // Initialize accumulator
+    let accu_init_dims = [n, k, h, w]
+        .iter()
+        .map(|dim| builder.open_mapped_dim(dim))
+        .collect_vec();
+    let accu_init_instr = builder.mov(&S::zero());
+    for dim in &accu_init_dims {
+        builder.close_dim(dim);
+    }
+
+    // Map operands and assign accumulator
+    let acc_dims = accu_init_dims
+        .iter()
+        .map(|dim| builder.open_mapped_dim(dim))
+        .collect_vec();
+    let acc_dim_c = builder.open_mapped_dim(c);
+
+    let input_operand = input.dim_map(
+        &[&acc_dims[0], &acc_dim_c, &acc_dims[2], &acc_dims[3]],
+        ir::DimMapScope::Global(()),
+        builder,
+    );
+
+    let filter_operand = filter.dim_map(
+        &[&acc_dims[1], &acc_dim_c],
+        ir::DimMapScope::Global(()),
+        builder,
+    );
+
+    let acc_instr =
+        builder.mad(&input_operand, &filter_operand, &Reduce(accu_init_instr));
+
+    for dim in &acc_dims {
+        builder.close_dim(dim);
+    }
+    builder.close_dim(&acc_dim_c);
+
+    VirtualTensor::new(acc_instr, acc_dims)
 }
 
 /// Adds two tensors `lhs` and `rhs` of the same shape
@@ -193,6 +370,92 @@ pub fn tensor_map(
     VirtualTensor::new(res_instr, dims)
 }
 
+/// Reduces a virtual tensor `a` along its dimension `axis` with `op`. Returns a tensor
+/// with the other dimensions of `a`, in the same order.
+pub fn tensor_reduce<S: Scalar>(
+    builder: &mut Builder,
+    a: &VirtualTensor,
+    axis: usize,
+    op: ReduceOp,
+) -> VirtualTensor {
+    assert!(axis < a.num_dims());
+
+    // Initialize accumulator
+    let init_dims = a
+        .iter()
+        .enumerate()
+        .filter(|&(pos, _)| pos != axis)
+        .map(|(_, dim)| builder.open_mapped_dim(dim))
+        .collect_vec();
+    let init_instr = builder.mov(&op.identity::<S>());
+    for dim in &init_dims {
+        builder.close_dim(dim);
+    }
+
+    // Map the operand and accumulate
+    let acc_dims = init_dims
+        .iter()
+        .map(|dim| builder.open_mapped_dim(dim))
+        .collect_vec();
+    let acc_dim_reduced = builder.open_mapped_dim(&a[axis]);
+
+    let mut operand_dims = acc_dims.iter().collect_vec();
+    operand_dims.insert(axis, &acc_dim_reduced);
+    let operand = a.dim_map(&operand_dims, ir::DimMapScope::Global(()), builder);
+
+    let acc_instr = match op {
+        ReduceOp::Sum => builder.add(&operand, &Reduce(init_instr)),
+        ReduceOp::Max => builder.max(&operand, &Reduce(init_instr)),
+    };
+
+    for dim in &acc_dims {
+        builder.close_dim(dim);
+    }
+    builder.close_dim(&acc_dim_reduced);
+
+    VirtualTensor::new(acc_instr, acc_dims)
+}
+
+/// Combines each element of a virtual tensor `lhs` with an element of `rhs` using `f`.
+/// `rhs` has the dimensions of `lhs` except the ones listed in `axes`, and its values
+/// are broadcast along them.
+pub fn tensor_broadcast(
+    builder: &mut Builder,
+    lhs: &VirtualTensor,
+    rhs: &VirtualTensor,
+    axes: &[usize],
+    f: impl FnOnce(&ir::Operand<()>, &ir::Operand<()>, &mut Builder) -> ir::InstId,
+) -> VirtualTensor {
+    assert_eq!(lhs.num_dims(), rhs.num_dims() + axes.len());
+
+    let dims = lhs
+        .iter()
+        .map(|dim| builder.open_mapped_dim(dim))
+        .collect_vec();
+
+    let lhs_operand = lhs.dim_map(
+        &dims.iter().collect_vec(),
+        ir::DimMapScope::Global(()),
+        builder,
+    );
+
+    let rhs_dims = dims
+        .iter()
+        .enumerate()
+        .filter(|(pos, _)| !axes.contains(pos))
+        .map(|(_, dim)| dim)
+        .collect_vec();
+    let rhs_operand = rhs.dim_map(&rhs_dims, ir::DimMapScope::Global(()), builder);
+
+    let res_instr = f(&lhs_operand, &rhs_operand, builder);
+
+    for dim in &dims {
+        builder.close_dim(dim);
+    }
+
+    VirtualTensor::new(res_instr, dims)
+}
+
 /// Multiplies each element of a virtual tensor `rhs` with a scalar
 /// operand `lhs`
 pub fn tensor_elementwise_mul(
@@ -217,6 +480,35 @@ pub fn tensor_elementwise_max(
     })
 }
 
+/// Operation used to combine values by `tensor_reduce`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ReduceOp {
+    /// Sums the values.
+    Sum,
+    /// Keeps the largest value.
+    Max,
+}
+
+impl ReduceOp {
+    /// Returns the neutral element of the operation, used to initialize accumulators.
+    /// The neutral element of `Max` is the lowest finite value, since infinite values
+    /// cannot be represented in the IR.
+    pub fn identity<S: Scalar>(self) -> S {
+        match self {
+            ReduceOp::Sum => S::zero(),
+            ReduceOp::Max => S::min_value(),
+        }
+    }
+
+    /// Combines two values on the host.
+    pub fn combine<S: Scalar>(self, lhs: S, rhs: S) -> S {
+        match self {
+            ReduceOp::Sum => lhs + rhs,
+            ReduceOp::Max => lhs.max(rhs),
+        }
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub enum ActivationFunction {
     /// Linear rectifier (i.e., max(0, v))
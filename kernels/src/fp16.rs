@@ -0,0 +1,293 @@
+//! Half-precision floating point numbers.
+use std::borrow::Cow;
+use std::fmt;
+use std::num::FpCategory;
+use std::ops::*;
+
+use num::bigint::BigInt;
+use num::rational::Ratio;
+use num::traits::{Float, Num, NumCast, One, ToPrimitive, Zero};
+use rand::Rng;
+use telamon::device::ScalarArgument;
+use telamon::ir;
+use utils::unwrap;
+
+/// A half-precision floating point number. Values are stored on 16 bits, as on the
+/// device, but the host computes in single precision and rounds the results.
+#[derive(Clone, Copy, Default, PartialEq, PartialOrd)]
+#[repr(transparent)]
+pub struct F16(pub half::f16);
+
+impl F16 {
+    /// Rounds a single precision number to the nearest half-precision number.
+    pub fn from_f32(value: f32) -> Self {
+        F16(half::f16::from_f32(value))
+    }
+
+    /// Converts the number to single precision. The conversion is exact.
+    pub fn to_f32(self) -> f32 {
+        self.0.to_f32()
+    }
+
+    fn from_bits(bits: u16) -> Self {
+        F16(half::f16::from_bits(bits))
+    }
+}
+
+macro_rules! impl_binary_op {
+    ($($op:ident::$fun:ident, $op_assign:ident::$fun_assign:ident;)*) => {
+        $(impl $op for F16 {
+            type Output = F16;
+
+            fn $fun(self, rhs: F16) -> F16 {
+                F16::from_f32($op::$fun(self.to_f32(), rhs.to_f32()))
+            }
+        }
+
+        impl $op_assign for F16 {
+            fn $fun_assign(&mut self, rhs: F16) {
+                *self = $op::$fun(*self, rhs);
+            }
+        })*
+    };
+}
+
+impl_binary_op! {
+    Add::add, AddAssign::add_assign;
+    Sub::sub, SubAssign::sub_assign;
+    Mul::mul, MulAssign::mul_assign;
+    Div::div, DivAssign::div_assign;
+    Rem::rem, RemAssign::rem_assign;
+}
+
+impl Neg for F16 {
+    type Output = F16;
+
+    fn neg(self) -> F16 {
+        F16::from_bits(self.0.to_bits() ^ 0x8000)
+    }
+}
+
+macro_rules! impl_fmt {
+    ($($fmt:ident),*) => {
+        $(impl fmt::$fmt for F16 {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt::$fmt::fmt(&self.to_f32(), fmt)
+            }
+        })*
+    };
+}
+
+impl_fmt!(Display, Debug, LowerExp, UpperExp);
+
+impl Zero for F16 {
+    fn zero() -> Self {
+        F16::from_bits(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.to_bits() & 0x7FFF == 0
+    }
+}
+
+impl One for F16 {
+    fn one() -> Self {
+        F16::from_f32(1.)
+    }
+}
+
+impl Num for F16 {
+    type FromStrRadixErr = <f32 as Num>::FromStrRadixErr;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        f32::from_str_radix(s, radix).map(F16::from_f32)
+    }
+}
+
+impl ToPrimitive for F16 {
+    fn to_i64(&self) -> Option<i64> {
+        self.to_f32().to_i64()
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        self.to_f32().to_u64()
+    }
+
+    fn to_f32(&self) -> Option<f32> {
+        Some(F16::to_f32(*self))
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        Some(F16::to_f32(*self).into())
+    }
+}
+
+impl NumCast for F16 {
+    fn from<T: ToPrimitive>(n: T) -> Option<Self> {
+        n.to_f32().map(F16::from_f32)
+    }
+}
+
+/// Implements methods of `Float` by computing them in single precision.
+macro_rules! single_precision {
+    ($($fun:ident($($arg:ident),*);)*) => {
+        $(fn $fun(self $(, $arg: Self)*) -> Self {
+            F16::from_f32(self.to_f32().$fun($($arg.to_f32()),*))
+        })*
+    };
+}
+
+impl Float for F16 {
+    fn nan() -> Self {
+        F16::from_bits(0x7E00)
+    }
+
+    fn infinity() -> Self {
+        F16::from_bits(0x7C00)
+    }
+
+    fn neg_infinity() -> Self {
+        F16::from_bits(0xFC00)
+    }
+
+    fn neg_zero() -> Self {
+        F16::from_bits(0x8000)
+    }
+
+    fn min_value() -> Self {
+        F16::from_bits(0xFBFF)
+    }
+
+    fn min_positive_value() -> Self {
+        F16::from_bits(0x0400)
+    }
+
+    fn max_value() -> Self {
+        F16::from_bits(0x7BFF)
+    }
+
+    fn is_nan(self) -> bool {
+        self.classify() == FpCategory::Nan
+    }
+
+    fn is_infinite(self) -> bool {
+        self.classify() == FpCategory::Infinite
+    }
+
+    fn is_finite(self) -> bool {
+        !self.is_nan() && !self.is_infinite()
+    }
+
+    fn is_normal(self) -> bool {
+        self.classify() == FpCategory::Normal
+    }
+
+    fn classify(self) -> FpCategory {
+        let bits = self.0.to_bits();
+        match (bits & 0x7C00, bits & 0x03FF) {
+            (0, 0) => FpCategory::Zero,
+            (0, _) => FpCategory::Subnormal,
+            (0x7C00, 0) => FpCategory::Infinite,
+            (0x7C00, _) => FpCategory::Nan,
+            _ => FpCategory::Normal,
+        }
+    }
+
+    fn is_sign_positive(self) -> bool {
+        self.0.to_bits() & 0x8000 == 0
+    }
+
+    fn is_sign_negative(self) -> bool {
+        !self.is_sign_positive()
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        let (sin, cos) = self.to_f32().sin_cos();
+        (F16::from_f32(sin), F16::from_f32(cos))
+    }
+
+    fn integer_decode(self) -> (u64, i16, i8) {
+        self.to_f32().integer_decode()
+    }
+
+    fn powi(self, n: i32) -> Self {
+        F16::from_f32(self.to_f32().powi(n))
+    }
+
+    fn abs_sub(self, other: Self) -> Self {
+        if self <= other {
+            F16::zero()
+        } else {
+            self - other
+        }
+    }
+
+    single_precision! {
+        floor();
+        ceil();
+        round();
+        trunc();
+        fract();
+        abs();
+        signum();
+        mul_add(a, b);
+        recip();
+        powf(n);
+        sqrt();
+        exp();
+        exp2();
+        ln();
+        log(base);
+        log2();
+        log10();
+        max(other);
+        min(other);
+        cbrt();
+        hypot(other);
+        sin();
+        cos();
+        tan();
+        asin();
+        acos();
+        atan();
+        atan2(other);
+        exp_m1();
+        ln_1p();
+        sinh();
+        cosh();
+        tanh();
+        asinh();
+        acosh();
+        atanh();
+    }
+}
+
+impl ::ndarray::ScalarOperand for F16 {}
+
+unsafe impl ScalarArgument for F16 {
+    fn t() -> ir::Type {
+        ir::Type::F(16)
+    }
+
+    fn get_type(&self) -> ir::Type {
+        Self::t()
+    }
+
+    fn raw_ptr(&self) -> *const libc::c_void {
+        self as *const F16 as *const libc::c_void
+    }
+
+    fn as_operand<L>(&self) -> ir::Operand<L> {
+        ir::Operand::new_float(*self)
+    }
+
+    fn gen_random<R: Rng>(rng: &mut R) -> Self {
+        F16::from_f32(rng.gen_range(0., 1.))
+    }
+}
+
+impl<'a> ir::FloatLiteral<'a> for F16 {
+    fn decompose(self) -> (Cow<'a, Ratio<BigInt>>, u16) {
+        (Cow::Owned(unwrap!(Ratio::from_float(self.to_f32()))), 16)
+    }
+}
@@ -4,9 +4,12 @@
 //! implementation. generate_dump is only used in binary src/bin/kernel_dump.rs and not directly in
 //! tests
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 
 use crate::statistics;
+use crate::ParamsError;
 use itertools::Itertools;
 use log::*;
 use num_cpus;
@@ -34,11 +37,14 @@ const MAX_DEADEND_RATIO: f32 = 0.95;
 
 /// Kernel factory, which can be used in order to generate a new kernel.
 ///
-/// The two configurations available are:
+/// The configurations available are:
 ///
 ///  - [`name`]: specifies an associated name for the kernel's signature.  If not specified, this is
 ///    taken from `Kernel::name()`.
 ///  - [`mem_init`]: specifies the memory initialization strategy for the parameters.
+///  - [`seed`]: specifies the seed of the random data the parameters are initialized with.
+///  - [`existing_array`]: binds an array parameter to an existing array instead of allocating
+///    a new one.
 ///
 /// # Examples
 ///
@@ -57,12 +63,32 @@ const MAX_DEADEND_RATIO: f32 = 0.95;
 ///
 /// [`name`]: #method.name
 /// [`mem_init`]: #method.mem_init
-#[derive(Debug, Clone, Default)]
+/// [`seed`]: #method.seed
+/// [`existing_array`]: #method.existing_array
+#[derive(Clone, Default)]
 pub struct KernelBuilder<'a> {
     /// The name of the kernel.  If `None`, taken from the `Kernel::name`.
     name: Option<Cow<'a, str>>,
     /// Memory initialisation strategy.
     mem_init: MemInit,
+    /// Seed of the random arguments.
+    seed: u64,
+    /// Arrays to bind to parameters instead of allocating new ones, indexed by parameter name.
+    existing_arrays: HashMap<String, Arc<dyn device::ArrayArgument>>,
+}
+
+impl<'a> fmt::Debug for KernelBuilder<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KernelBuilder")
+            .field("name", &self.name)
+            .field("mem_init", &self.mem_init)
+            .field("seed", &self.seed)
+            .field(
+                "existing_arrays",
+                &self.existing_arrays.keys().collect_vec(),
+            )
+            .finish()
+    }
 }
 
 impl<'a> KernelBuilder<'a> {
@@ -84,8 +110,36 @@ impl<'a> KernelBuilder<'a> {
         self
     }
 
+    /// Sets the seed used to generate random arrays and scalar arguments.  Kernels built
+    /// with the same seed and parameters get the same data, so that failures of the
+    /// correctness checks can be reproduced.  Defaults to zero.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Binds the array parameter with the given name to an existing array, such as a buffer
+    /// already holding live data, instead of allocating a new one.  The array is neither
+    /// copied nor initialized.  The caller is responsible for providing an array of the size
+    /// the kernel expects, allocated on the device of the context.
+    pub fn existing_array<T: Into<String>>(
+        mut self,
+        name: T,
+        array: Arc<dyn device::ArrayArgument>,
+    ) -> Self {
+        self.existing_arrays.insert(name.into(), array);
+        self
+    }
+
     /// Create a kernel in the given context.  This returns a frozen reference to the context, the
     /// kernel, and its signature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are rejected by `Kernel::validate_params`.  Use [`try_build`]
+    /// to handle invalid parameters gracefully.
+    ///
+    /// [`try_build`]: #method.try_build
     pub fn build<'b, K, AM>(
         &self,
         params: K::Parameters,
@@ -95,6 +149,23 @@ impl<'a> KernelBuilder<'a> {
         AM: device::ArgMap<'a> + device::Context,
         K: Kernel<'a> + 'b,
     {
+        self.try_build(params, context).unwrap_or_else(|err| {
+            panic!("invalid parameters for kernel {}: {}", K::name(), err)
+        })
+    }
+
+    /// Create a kernel in the given context, after checking its parameters with
+    /// `Kernel::validate_params`.
+    pub fn try_build<'b, K, AM>(
+        &self,
+        params: K::Parameters,
+        context: &'b mut AM,
+    ) -> Result<(ir::Signature, K, &'b AM), ParamsError>
+    where
+        AM: device::ArgMap<'a> + device::Context,
+        K: Kernel<'a> + 'b,
+    {
+        K::validate_params(&params)?;
         let name = self
             .name
             .as_ref()
@@ -104,11 +175,15 @@ impl<'a> KernelBuilder<'a> {
         {
             let mut builder = SignatureBuilder::new(&name, context);
             builder.set_mem_init(self.mem_init);
+            builder.set_seed(self.seed);
+            for (name, array) in &self.existing_arrays {
+                builder.set_existing_array(name, Arc::clone(array));
+            }
             kernel = K::build_signature(params, &mut builder);
             signature = builder.get();
         }
 
-        (signature, kernel, context)
+        Ok((signature, kernel, context))
     }
 }
 
@@ -122,6 +197,12 @@ pub trait Kernel<'a>: Sized + Sync {
     /// The name of the function computed by the kernel.
     fn name() -> &'static str;
 
+    /// Checks that the parameters describe a valid kernel, for instance that tiling factors
+    /// divide the sizes they apply to.  This is called before building the signature.
+    fn validate_params(_params: &Self::Parameters) -> Result<(), ParamsError> {
+        Ok(())
+    }
+
     /// Builds the signature of the kernel in the builder and returns an object that
     /// stores enough information to later build the kernel body and check its result.
     fn build_signature<AM>(
@@ -146,6 +227,18 @@ pub trait Kernel<'a>: Sized + Sync {
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+    ) -> Result<(), String> {
+        self.check_result_within(expected, context, 1.)
+    }
+
+    /// Ensures the generated code performs the correct operation, with the tolerances on
+    /// output errors multiplied by `accuracy_budget`.  A budget above one accepts the
+    /// larger errors of implementations using approximate operations.
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String>;
 
     /// Generate a dump of a specific implementation of Self in a file, so we can rerun tests on
@@ -290,7 +383,9 @@ pub trait Kernel<'a>: Sized + Sync {
         let stabilizer = &context.stabilizer();
         context.async_eval(
             num_cpus::get(),
+            0,
             device::EvalMode::TestBound,
+            &device::CancellationToken::new(),
             &|evaluator| loop {
                 if num_tested.fetch_add(1, atomic::Ordering::SeqCst) >= num_tests {
                     if num_tested.fetch_sub(1, atomic::Ordering::SeqCst) > num_tests {
@@ -337,6 +432,9 @@ pub trait Kernel<'a>: Sized + Sync {
     }
 
     /// Runs the search and benchmarks the resulting candidate.
+    ///
+    /// If `config.tuning_cache` is set, the implementation found by a previous search with
+    /// the same parameters on the same device is reused when it exists.
     fn benchmark<AM>(
         config: &explorer::Config,
         params: Self::Parameters,
@@ -358,7 +456,8 @@ pub trait Kernel<'a>: Sized + Sync {
                 config,
                 context,
                 search_space,
-                Some(&|_, context| kernel.check_result(&expected, context))
+                Some(&|_, context| kernel.check_result(&expected, context)),
+                &[],
             ),
             "no candidates found for kernel {}",
             signature.name,
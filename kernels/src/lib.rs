@@ -1,20 +1,24 @@
 //! Defines common kernels used to test and benchmark Telamon.
 #![deny(bare_trait_objects)]
 
+mod fp16;
 mod kernel;
 
 pub mod compose;
 pub mod linalg;
+pub mod replays;
+pub mod search_bench;
 pub mod statistics;
 
 use std::fmt;
 
+pub use crate::fp16::F16;
 pub use crate::kernel::{analyze_bounds, Kernel, KernelBuilder};
 
 use telamon::device::{self, ArgMap, Context};
 use telamon::helper::tensor::DimSize;
 use telamon::helper::{self, SignatureBuilder};
-use telamon::{explorer, model, search_space};
+use telamon::{codegen, explorer, ir, model, search_space};
 
 use ::ndarray::{ArrayBase, Data, Dimension, FoldWhile, Zip};
 
@@ -23,10 +27,37 @@ fn build_candidate(
     space: search_space::SearchSpace,
     ctx: &dyn device::Context,
 ) -> explorer::Candidate {
+    check_tilings(&space, ctx);
     let bound = model::bound(&space, ctx);
     explorer::Candidate::new(space, bound)
 }
 
+/// Ensures the size of each logical dimension is a multiple of its tiling factors.  The
+/// generated code has no remainder handling, so it would otherwise skip the end of the
+/// dimension.
+///
+/// # Panics
+///
+/// Panics if a tiling factor does not divide the size.  Kernels must reject such sizes in
+/// `Kernel::validate_params`, before building the search space.
+fn check_tilings(space: &search_space::SearchSpace, ctx: &dyn device::Context) {
+    for ldim in space.ir_instance().logical_dims() {
+        let total_size = ir::PartialSize::from(ldim.total_size().clone());
+        let (factor, params, _) = total_size.factors();
+        let size = ctx.eval_size(&codegen::Size::new(factor, params.to_vec(), 1));
+        for &tiling in ldim.possible_tilings() {
+            assert_eq!(
+                size % tiling,
+                0,
+                "the size {} of {} is not a multiple of its tiling factor {}",
+                size,
+                ldim.id(),
+                tiling
+            );
+        }
+    }
+}
+
 /// Creates a `DimSize`. If the instantiate flag is true, it uses a constant size,
 /// otherwise it creates a parameter with the given name.
 fn create_size<'a, AM>(
@@ -56,10 +87,103 @@ fn infer_tiling(
         .unwrap_or_else(|| helper::TilingPattern::infer_pattern(size as u32, max_sizes))
 }
 
+/// Returns the tiling patterns the explorer chooses from for a dimension of size `size`:
+/// the given pattern if there is one, and otherwise no tiling or up to `max_levels` tiling
+/// dimensions whose sizes are divisors of `size` within the limits of `device`.
+fn tiling_choices(
+    size: i32,
+    given_pattern: &Option<helper::TilingPattern>,
+    max_levels: usize,
+    device: &dyn device::Device,
+) -> Vec<helper::TilingPattern> {
+    if let Some(pattern) = given_pattern {
+        return vec![pattern.clone()];
+    }
+    let size = size as u32;
+    let max_tile_size = std::cmp::max(device.max_threads(), device.max_unrolling());
+    let has_divisors = (2..=std::cmp::min(size, max_tile_size)).any(|d| size % d == 0);
+    let max_levels = if has_divisors { max_levels } else { 0 };
+    (0..=max_levels)
+        .map(|num_levels| {
+            helper::TilingPattern::divisors(size, num_levels, max_tile_size)
+        })
+        .collect()
+}
+
+/// Invalid parameters for a kernel, detected before building its signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamsError {
+    /// A size is zero or negative.
+    InvalidSize { name: &'static str, value: i32 },
+    /// A tiling factor does not divide the size of the tiled dimension.
+    IndivisibleTiling {
+        name: &'static str,
+        size: i32,
+        factor: u32,
+    },
+    /// The stride of an array is zero or too large to address all its elements.
+    InvalidStride { name: &'static str, stride: u32 },
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamsError::InvalidSize { name, value } => {
+                write!(fmt, "size `{}` must be positive, got {}", name, value)
+            }
+            ParamsError::IndivisibleTiling { name, size, factor } => write!(
+                fmt,
+                "tiling factor {} does not divide size `{}` = {}",
+                factor, name, size
+            ),
+            ParamsError::InvalidStride { name, stride } => write!(
+                fmt,
+                "stride {} of `{}` is inconsistent with the shape of the array",
+                stride, name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+/// Ensures a size is strictly positive.
+fn check_size(name: &'static str, value: i32) -> Result<(), ParamsError> {
+    if value > 0 {
+        Ok(())
+    } else {
+        Err(ParamsError::InvalidSize { name, value })
+    }
+}
+
+/// Ensures a size is strictly positive and that the given tiling pattern, if any, divides it.
+fn check_tiled_size(
+    name: &'static str,
+    size: i32,
+    pattern: &Option<helper::TilingPattern>,
+) -> Result<(), ParamsError> {
+    check_size(name, size)?;
+    let factor = pattern
+        .as_ref()
+        .and_then(|pattern| pattern.non_dividing_factor(size as u32));
+    if let Some(factor) = factor {
+        Err(ParamsError::IndivisibleTiling { name, size, factor })
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the absolute and relative tolerances of `Scalar`, multiplied by the accuracy
+/// budget.
+fn tolerances<A: Scalar>(accuracy_budget: f64) -> (A, A) {
+    let budget = A::from(accuracy_budget).unwrap();
+    (A::atol() * budget, A::rtol() * budget)
+}
+
 /// Returns `true` if two arrays are element-wise equal within a tolerance.
 ///
 /// The tolerance values are defined by the absolute and relative offsets from the `Scalar` trait
-/// for the corresponding type.
+/// for the corresponding type, multiplied by `accuracy_budget`.
 ///
 /// The relative difference (`rtol` * abs(`b`)) and the absolute difference `atol` are added
 /// together and compared against the absolute difference between `a` and `b`.
@@ -67,7 +191,11 @@ fn infer_tiling(
 /// # Panics
 ///
 /// If broadcasting the arrays to the same shape is not possible.
-fn allclose<A, S, D, S2, E>(a: &ArrayBase<S, D>, b: &ArrayBase<S2, E>) -> bool
+fn allclose<A, S, D, S2, E>(
+    a: &ArrayBase<S, D>,
+    b: &ArrayBase<S2, E>,
+    accuracy_budget: f64,
+) -> bool
 where
     A: Scalar,
     S: Data<Elem = A>,
@@ -75,10 +203,11 @@ where
     D: Dimension,
     E: Dimension,
 {
+    let (atol, rtol) = tolerances::<A>(accuracy_budget);
     !Zip::from(a)
         .and_broadcast(b)
         .fold_while((), |_, x, y| {
-            if (*x - *y).abs() < A::atol() + A::rtol() * y.abs() {
+            if (*x - *y).abs() < atol + rtol * y.abs() {
                 FoldWhile::Continue(())
             } else {
                 FoldWhile::Done(())
@@ -160,6 +289,7 @@ where
 fn check_output<A, S, D, S2, E>(
     actual: &ArrayBase<S, D>,
     expected: &ArrayBase<S2, E>,
+    accuracy_budget: f64,
 ) -> Result<(), IncorrectOutputError<A>>
 where
     A: Scalar,
@@ -168,7 +298,8 @@ where
     D: Dimension,
     E: Dimension,
 {
-    if allclose(actual, expected) {
+    let (atol, rtol) = tolerances::<A>(accuracy_budget);
+    if allclose(actual, expected, accuracy_budget) {
         Ok(())
     } else {
         Err(Zip::from(actual)
@@ -198,7 +329,7 @@ where
                         sum_relative_error: output_diff.sum_relative_error
                             + relative_error,
                         num_above_threshold: output_diff.num_above_threshold
-                            + if absolute_error < A::atol() + A::rtol() * expected.abs() {
+                            + if absolute_error < atol + rtol * expected.abs() {
                                 0
                             } else {
                                 1
@@ -213,6 +344,9 @@ where
 
 /// A scalar that can be used as the data type for tests.
 pub trait Scalar: device::ScalarArgument + ndarray::NdFloat {
+    /// Type in which kernels accumulate long sums of values of this type.
+    type Accumulator: Scalar;
+
     /// Absolute tolerance for errors.
     fn atol() -> Self;
 
@@ -221,6 +355,8 @@ pub trait Scalar: device::ScalarArgument + ndarray::NdFloat {
 }
 
 impl Scalar for f32 {
+    type Accumulator = f32;
+
     fn atol() -> Self {
         1e-8
     }
@@ -231,6 +367,8 @@ impl Scalar for f32 {
 }
 
 impl Scalar for f64 {
+    type Accumulator = f64;
+
     fn atol() -> Self {
         1e-8
     }
@@ -240,6 +378,18 @@ impl Scalar for f64 {
     }
 }
 
+impl Scalar for F16 {
+    type Accumulator = f32;
+
+    fn atol() -> Self {
+        F16::from_f32(1e-3)
+    }
+
+    fn rtol() -> Self {
+        F16::from_f32(1e-2)
+    }
+}
+
 // FIXME: implement kernels
 // tensor reduction
 // floyd warshall: for a fixed K
@@ -253,3 +403,21 @@ impl Scalar for f64 {
 // dicgi, mvt, dot > need global reduction
 // 2mm, two-level NN > need global bcast or global reduction
 // lstm: too complex for now
+
+#[cfg(test)]
+mod tests {
+    use ndarray::arr1;
+
+    use super::*;
+
+    /// Ensures the accuracy budget scales the tolerances of the output checks.
+    #[test]
+    fn accuracy_budget() {
+        let expected = arr1(&[1f32, 100., 1e4]);
+        let actual = &expected * 1.000_05;
+        assert!(check_output(&expected, &expected, 1.).is_ok());
+        let error = check_output(&actual, &expected, 1.).unwrap_err();
+        assert_eq!(error.num_above_threshold, 3);
+        assert!(check_output(&actual, &expected, 10.).is_ok());
+    }
+}
@@ -2,15 +2,20 @@
 #![allow(clippy::many_single_char_names)]
 use std::sync::Arc;
 
-pub use crate::compose::ActivationFunction;
 use crate::compose::{
-    matrix_matrix_multiply, matrix_vector_multiply, tensor_elementwise_mul, tensor_mad,
+    depthwise_conv, fuse, matrix_matrix_multiply, matrix_vector_multiply, pointwise_conv,
+    tensor_broadcast, tensor_elementwise_mul, tensor_mad, tensor_map, tensor_reduce,
 };
+pub use crate::compose::{ActivationFunction, Fusion, ReduceOp};
 use crate::kernel::Kernel;
-use crate::{build_candidate, check_output, create_size, infer_tiling, Scalar};
-use ::ndarray::{Array1, Array2, Array3, ArrayD};
-use rand;
+use crate::{
+    build_candidate, check_output, check_size, check_tiled_size, create_size,
+    infer_tiling, tiling_choices, ParamsError, Scalar,
+};
+use ::ndarray::{Array1, Array2, Array3, Array4, ArrayD, Axis};
+use itertools::{iproduct, Itertools};
 use serde::{Deserialize, Serialize};
+use telamon::device::ArrayArgumentExt;
 use telamon::explorer::Candidate;
 use telamon::helper::tensor::*;
 use telamon::helper::{self, Builder, SignatureBuilder};
@@ -41,6 +46,10 @@ where
         "axpy"
     }
 
+    fn validate_params(&(n, _): &(i32, bool)) -> Result<(), ParamsError> {
+        check_size("n", n)
+    }
+
     fn build_signature<AM>(
         (n, generic): (i32, bool),
         builder: &mut SignatureBuilder<AM>,
@@ -77,13 +86,14 @@ where
         self.x.read_to_host(context) + self.y.read_to_host(context)
     }
 
-    fn check_result(
+    fn check_result_within(
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String> {
         let z = self.z.read_to_host(context);
-        if let Err(invalid) = check_output(&z, expected) {
+        if let Err(invalid) = check_output(&z, expected, accuracy_budget) {
             Err(format!("Invalid axpy output: {}", invalid))
         } else {
             Ok(())
@@ -114,6 +124,11 @@ where
         "mv"
     }
 
+    fn validate_params(&(m, n, _): &(i32, i32, bool)) -> Result<(), ParamsError> {
+        check_size("m", m)?;
+        check_size("n", n)
+    }
+
     fn build_signature<AM>(
         (m, n, generic): (i32, i32, bool),
         builder: &mut SignatureBuilder<AM>,
@@ -140,7 +155,7 @@ where
         let x = self.x.load(vec![n_tiling.clone()], &mut builder);
         let a = self.a.load(vec![m_tiling, n_tiling], &mut builder);
 
-        let ax = matrix_vector_multiply(&mut builder, &a, &x);
+        let ax = matrix_vector_multiply::<S>(&mut builder, &a, &x);
         ax.store(&self.y, &mut builder);
 
         vec![build_candidate(builder.get(), ctx)]
@@ -161,17 +176,18 @@ where
             )
     }
 
-    fn check_result(
+    fn check_result_within(
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String> {
         let y = self
             .y
             .read_to_host(context)
             .into_shape(self.m as usize)
             .unwrap();
-        if let Err(invalid) = check_output(&y, expected) {
+        if let Err(invalid) = check_output(&y, expected, accuracy_budget) {
             Err(format!("Invalid mv output: {}", invalid))
         } else {
             Ok(())
@@ -199,6 +215,11 @@ impl<'a, S: Scalar> Kernel<'a> for Gesummv<'a, S> {
         "gesummv"
     }
 
+    fn validate_params(&(m, n, _): &(i32, i32, bool)) -> Result<(), ParamsError> {
+        check_size("m", m)?;
+        check_size("n", n)
+    }
+
     fn build_signature<AM>(
         (m, n, generic): (i32, i32, bool),
         builder: &mut SignatureBuilder<AM>,
@@ -208,9 +229,8 @@ impl<'a, S: Scalar> Kernel<'a> for Gesummv<'a, S> {
     {
         let m_size = create_size(m, "m", generic, builder);
         let n_size = create_size(n, "n", generic, builder);
-        let mut rng = rand::thread_rng();
-        let alpha = S::gen_random(&mut rng);
-        let beta = S::gen_random(&mut rng);
+        let alpha = S::gen_random(builder.rng());
+        let beta = S::gen_random(builder.rng());
         builder.scalar("alpha", alpha);
         builder.scalar("beta", beta);
         Gesummv {
@@ -240,10 +260,10 @@ impl<'a, S: Scalar> Kernel<'a> for Gesummv<'a, S> {
         let a = self.a.load(ab_tiling.clone(), &mut builder);
         let b = self.b.load(ab_tiling, &mut builder);
 
-        let ax = matrix_vector_multiply(&mut builder, &a, &x);
+        let ax = matrix_vector_multiply::<S>(&mut builder, &a, &x);
         let aax = tensor_elementwise_mul(&mut builder, &"alpha", &ax);
 
-        let bx = matrix_vector_multiply(&mut builder, &b, &x);
+        let bx = matrix_vector_multiply::<S>(&mut builder, &b, &x);
 
         let aaxpbbx = tensor_mad(&mut builder, &bx, &"beta", &aax);
 
@@ -260,13 +280,14 @@ impl<'a, S: Scalar> Kernel<'a> for Gesummv<'a, S> {
         a.dot(&x) * self.alpha + b.dot(&x) * self.beta
     }
 
-    fn check_result(
+    fn check_result_within(
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String> {
         let y = unwrap!(self.y.read_to_host(context).into_shape(self.m as usize));
-        if let Err(invalid) = check_output(&y, expected) {
+        if let Err(invalid) = check_output(&y, expected, accuracy_budget) {
             Err(format!("Invalid gesummv output: {}", invalid))
         } else {
             Ok(())
@@ -287,6 +308,13 @@ pub struct FusedMMP {
     pub n_tiling: Option<helper::TilingPattern>,
     pub k_tiling: Option<helper::TilingPattern>,
     pub activation_fun: Option<ActivationFunction>,
+    /// Lets the explorer choose the number and the sizes of the tiling dimensions of the
+    /// sizes that have no explicit tiling pattern.
+    #[serde(default)]
+    pub search_tiling: bool,
+    /// Adds a bias of size `n` to each row of `A.B`, before the activation function.
+    #[serde(default)]
+    pub bias: bool,
 }
 
 impl FusedMMP {
@@ -303,6 +331,8 @@ impl FusedMMP {
             n_tiling: None,
             k_tiling: None,
             activation_fun: None,
+            search_tiling: false,
+            bias: false,
         }
     }
 
@@ -316,6 +346,12 @@ impl FusedMMP {
         self
     }
 
+    /// Adds a bias to each row of the product, before the activation function.
+    pub fn bias(mut self) -> Self {
+        self.bias = true;
+        self
+    }
+
     pub fn stride_a(mut self, stride: u32) -> Self {
         self.a_stride = stride;
         self
@@ -334,17 +370,75 @@ impl FusedMMP {
         self.generic = false;
         self
     }
+
+    /// Exposes the tiling of the sizes without an explicit tiling pattern as choices of
+    /// the search space: the body has one candidate for each number of tiling dimensions,
+    /// whose sizes can be any divisor of the size within the limits of the device.
+    pub fn search_tiling(mut self) -> Self {
+        self.search_tiling = true;
+        self
+    }
+
+    /// Ensures the sizes are positive, that the tiling patterns divide them and that the
+    /// stride of `A` is non-zero and small enough for the `m x k` strided elements of `A`
+    /// to be addressable.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_tiled_size("m", self.m, &self.m_tiling)?;
+        check_tiled_size("n", self.n, &self.n_tiling)?;
+        check_tiled_size("k", self.k, &self.k_tiling)?;
+        let a_len = i64::from(self.m) * i64::from(self.k) * i64::from(self.a_stride);
+        if self.a_stride == 0 || a_len > i64::from(i32::max_value()) {
+            let stride = self.a_stride;
+            return Err(ParamsError::InvalidStride { name: "a", stride });
+        }
+        Ok(())
+    }
 }
 
-/// Computes `C = A.B` and applies an activation function to each
-/// element of C.
+/// Computes `C = A.B`, optionally adds a bias to each row of `C` and applies an
+/// activation function to each element of C.
 pub struct FusedMM<'a, S: Scalar> {
     pub params: FusedMMP,
     a: Tensor<'a, S>,
     b: Tensor<'a, S>,
+    bias: Option<Tensor<'a, S>>,
     c: Tensor<'a, S>,
 }
 
+impl<'a, S: Scalar> FusedMM<'a, S> {
+    /// Builds the candidate where `m`, `n` and `k` are tiled with the given patterns.
+    fn build_tiled(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &dyn device::Context,
+        m_tiling: helper::TilingPattern,
+        n_tiling: helper::TilingPattern,
+        k_tiling: helper::TilingPattern,
+    ) -> Candidate {
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let a = self.a.load(vec![m_tiling, k_tiling.clone()], &mut builder);
+        let b = self.b.load(vec![k_tiling, n_tiling.clone()], &mut builder);
+
+        let mut ab = matrix_matrix_multiply::<S>(&mut builder, &a, &b);
+        if let Some(bias) = &self.bias {
+            let bias = bias.load(vec![n_tiling], &mut builder);
+            ab = tensor_broadcast(&mut builder, &ab, &bias, &[0], |x, bias, b| {
+                b.add(x, bias)
+            });
+        }
+
+        if let Some(activation_fun) = &self.params.activation_fun {
+            let res = activation_fun.apply::<S>(&mut builder, &ab);
+            res.store(&self.c, &mut builder);
+        } else {
+            ab.store(&self.c, &mut builder);
+        }
+
+        build_candidate(builder.get(), ctx)
+    }
+}
+
 impl<'a, S: Scalar> Kernel<'a> for FusedMM<'a, S> {
     type Parameters = FusedMMP;
     type ExpectedOutput = Array2<S>;
@@ -353,6 +447,10 @@ impl<'a, S: Scalar> Kernel<'a> for FusedMM<'a, S> {
         "fused_mm"
     }
 
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
     fn build_signature<AM>(params: FusedMMP, builder: &mut SignatureBuilder<AM>) -> Self
     where
         AM: device::ArgMap<'a> + device::Context,
@@ -368,8 +466,19 @@ impl<'a, S: Scalar> Kernel<'a> for FusedMM<'a, S> {
         let b = TensorBuilder::new("b", vec![k_size, n_size.clone()])
             .doif(params.transpose_b, |b| b.transpose(0, 1))
             .finish(builder);
+        let bias = if params.bias {
+            Some(builder.tensor::<S>("bias", vec![n_size.clone()], true))
+        } else {
+            None
+        };
         let c = builder.tensor::<S>("c", vec![m_size, n_size], false);
-        FusedMM { params, a, b, c }
+        FusedMM {
+            params,
+            a,
+            b,
+            bias,
+            c,
+        }
     }
 
     fn build_body<'b>(
@@ -377,25 +486,22 @@ impl<'a, S: Scalar> Kernel<'a> for FusedMM<'a, S> {
         signature: Arc<ir::Signature>,
         ctx: &'b dyn device::Context,
     ) -> Vec<Candidate> {
-        let m_tiling = infer_tiling(self.params.m, &self.params.m_tiling, &[32, 4]);
-        let n_tiling = infer_tiling(self.params.n, &self.params.n_tiling, &[32, 4]);
-        let k_tiling = infer_tiling(self.params.k, &self.params.k_tiling, &[32]);
-
-        let mut builder = helper::Builder::new(signature, ctx.device());
-
-        let a = self.a.load(vec![m_tiling, k_tiling.clone()], &mut builder);
-        let b = self.b.load(vec![k_tiling, n_tiling], &mut builder);
-
-        let ab = matrix_matrix_multiply(&mut builder, &a, &b);
-
-        if let Some(activation_fun) = &self.params.activation_fun {
-            let res = activation_fun.apply::<S>(&mut builder, &ab);
-            res.store(&self.c, &mut builder);
-        } else {
-            ab.store(&self.c, &mut builder);
+        if !self.params.search_tiling {
+            let m_tiling = infer_tiling(self.params.m, &self.params.m_tiling, &[32, 4]);
+            let n_tiling = infer_tiling(self.params.n, &self.params.n_tiling, &[32, 4]);
+            let k_tiling = infer_tiling(self.params.k, &self.params.k_tiling, &[32]);
+            return vec![self.build_tiled(signature, ctx, m_tiling, n_tiling, k_tiling)];
         }
-
-        vec![build_candidate(builder.get(), ctx)]
+        let device = ctx.device();
+        let m_tilings = tiling_choices(self.params.m, &self.params.m_tiling, 2, &*device);
+        let n_tilings = tiling_choices(self.params.n, &self.params.n_tiling, 2, &*device);
+        let k_tilings = tiling_choices(self.params.k, &self.params.k_tiling, 1, &*device);
+        iproduct!(m_tilings, n_tilings, k_tilings)
+            .map(|(m_tiling, n_tiling, k_tiling)| {
+                let signature = Arc::clone(&signature);
+                self.build_tiled(signature, ctx, m_tiling, n_tiling, k_tiling)
+            })
+            .collect()
     }
 
     fn get_expected_output(&self, context: &dyn device::Context) -> Array2<S> {
@@ -403,7 +509,13 @@ impl<'a, S: Scalar> Kernel<'a> for FusedMM<'a, S> {
         let b_shape = (self.params.k as usize, self.params.n as usize);
         let a = unwrap!(self.a.read_to_host(context).into_shape(a_shape));
         let b = unwrap!(self.b.read_to_host(context).into_shape(b_shape));
-        let mut res = a.dot(&b);
+        let a = a.mapv(|x| unwrap!(S::Accumulator::from(x)));
+        let b = b.mapv(|x| unwrap!(S::Accumulator::from(x)));
+        let mut res = a.dot(&b).mapv(|x| unwrap!(S::from(x)));
+        if let Some(bias) = &self.bias {
+            let n = self.params.n as usize;
+            res += &unwrap!(bias.read_to_host(context).into_shape(n));
+        }
 
         match self.params.activation_fun {
             Some(ActivationFunction::ReLU) => {
@@ -421,14 +533,15 @@ impl<'a, S: Scalar> Kernel<'a> for FusedMM<'a, S> {
         res
     }
 
-    fn check_result(
+    fn check_result_within(
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String> {
         let c_shape = (self.params.m as usize, self.params.n as usize);
         let c = unwrap!(self.c.read_to_host(context).into_shape(c_shape));
-        if let Err(invalid) = check_output(&c, expected) {
+        if let Err(invalid) = check_output(&c, expected, accuracy_budget) {
             Err(format!("Invalid fused_mm output: {}", invalid))
         } else {
             Ok(())
@@ -457,6 +570,13 @@ pub struct BatchMMP {
     pub transpose_b: bool,
     pub batch_b: bool,
     pub generic: bool,
+    /// Number of tiles of the batch dimension, if the batch size is only known at runtime.
+    #[serde(default)]
+    pub grid_stride: Option<u32>,
+    /// Lets the explorer choose the number and the sizes of the tiling dimensions of `m`,
+    /// `n` and `k`.
+    #[serde(default)]
+    pub search_tiling: bool,
 }
 
 impl BatchMMP {
@@ -470,6 +590,8 @@ impl BatchMMP {
             transpose_b: false,
             batch_b: true,
             generic: true,
+            grid_stride: None,
+            search_tiling: false,
         }
     }
 
@@ -495,46 +617,53 @@ impl BatchMMP {
         self.batch_b = false;
         self
     }
-}
 
-impl<'a, S: Scalar> Kernel<'a> for BatchMM<'a, S> {
-    type Parameters = BatchMMP;
-    type ExpectedOutput = Array3<S>;
+    /// Tiles the batch dimension by `tiles` instead of inferring a tiling from the batch size,
+    /// so that the search does not depend on the value of the batch size. The tile can be
+    /// mapped to blocks, with each block iterating over its share of the batch (grid-stride
+    /// loop). The batch size must be a multiple of `tiles`.
+    pub fn grid_stride(mut self, tiles: u32) -> Self {
+        self.grid_stride = Some(tiles);
+        self
+    }
 
-    fn name() -> &'static str {
-        "batch_mm"
+    /// Exposes the tiling of `m`, `n` and `k` as choices of the search space: the body
+    /// has one candidate for each number of tiling dimensions, whose sizes can be any
+    /// divisor of the size within the limits of the device.
+    pub fn search_tiling(mut self) -> Self {
+        self.search_tiling = true;
+        self
     }
 
-    fn build_signature<AM>(params: BatchMMP, builder: &mut SignatureBuilder<AM>) -> Self
-    where
-        AM: device::ArgMap<'a> + device::Context,
-    {
-        let m_size = create_size(params.m, "m", params.generic, builder);
-        let n_size = create_size(params.n, "n", params.generic, builder);
-        let k_size = create_size(params.k, "k", params.generic, builder);
-        let batch = create_size(params.batch, "batch", true, builder);
-        let a_dims = vec![batch.clone(), m_size.clone(), k_size.clone()];
-        let a = TensorBuilder::new("a", a_dims)
-            .doif(params.transpose_a, |b| b.transpose(1, 2))
-            .finish(builder);
-        let b = TensorBuilder::new("b", vec![batch.clone(), k_size, n_size.clone()])
-            .doif(params.transpose_b, |b| b.transpose(1, 2))
-            .doif(!params.batch_b, |b| b.stride_dim(0))
-            .finish(builder);
-        let c = builder.tensor::<S>("c", vec![batch, m_size, n_size], false);
-        BatchMM { params, a, b, c }
+    /// Returns the tiling of the batch dimension.
+    fn batch_tiling(&self) -> helper::TilingPattern {
+        match self.grid_stride {
+            Some(tiles) => helper::TilingPattern::new_fixed(&[tiles]),
+            None => helper::TilingPattern::infer_pattern(self.batch as u32, &[128]),
+        }
     }
 
-    fn build_body<'b>(
+    /// Ensures the sizes are positive and that the batch size is compatible with its tiling.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        let grid_stride = self.grid_stride.map(|_| self.batch_tiling());
+        check_tiled_size("batch", self.batch, &grid_stride)?;
+        check_size("m", self.m)?;
+        check_size("n", self.n)?;
+        check_size("k", self.k)
+    }
+}
+
+impl<'a, S: Scalar> BatchMM<'a, S> {
+    /// Builds the candidate where `m`, `n` and `k` are tiled with the given patterns.
+    fn build_tiled(
         &self,
         signature: Arc<ir::Signature>,
-        ctx: &'b dyn device::Context,
-    ) -> Vec<Candidate> {
-        let m_tiling = helper::TilingPattern::infer_pattern(self.params.m as u32, &[64]);
-        let n_tiling = helper::TilingPattern::infer_pattern(self.params.n as u32, &[64]);
-        let k_tiling = helper::TilingPattern::infer_pattern(self.params.k as u32, &[64]);
-        let batch_tiling =
-            helper::TilingPattern::infer_pattern(self.params.batch as u32, &[128]);
+        ctx: &dyn device::Context,
+        m_tiling: helper::TilingPattern,
+        n_tiling: helper::TilingPattern,
+        k_tiling: helper::TilingPattern,
+    ) -> Candidate {
+        let batch_tiling = self.params.batch_tiling();
         let mut builder = helper::Builder::new(signature, ctx.device());
         let a_tiling = vec![batch_tiling.clone(), m_tiling, k_tiling.clone()];
         let ld_a = self.a.load(a_tiling, &mut builder);
@@ -549,7 +678,7 @@ impl<'a, S: Scalar> Kernel<'a> for BatchMM<'a, S> {
         let init_dim_m = builder.open_mapped_dim(&ld_a[1]);
         let dim_n = &ld_b[if self.params.batch_b { 2 } else { 1 }];
         let init_dim_n = builder.open_mapped_dim(dim_n);
-        let acc_init = builder.mov(&0f32);
+        let acc_init = builder.mov(&S::zero());
         let acc_batch = builder.open_mapped_dim(&init_batch);
         let acc_dim_m = builder.open_mapped_dim(&init_dim_m);
         let acc_dim_n = builder.open_mapped_dim(&init_dim_n);
@@ -576,7 +705,66 @@ impl<'a, S: Scalar> Kernel<'a> for BatchMM<'a, S> {
 
         // Order for correctness.
         builder.order(&st_c.inst(), &acc_dim_k, Order::AFTER);
-        vec![build_candidate(builder.get(), ctx)]
+        build_candidate(builder.get(), ctx)
+    }
+}
+
+impl<'a, S: Scalar> Kernel<'a> for BatchMM<'a, S> {
+    type Parameters = BatchMMP;
+    type ExpectedOutput = Array3<S>;
+
+    fn name() -> &'static str {
+        "batch_mm"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(params: BatchMMP, builder: &mut SignatureBuilder<AM>) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let m_size = create_size(params.m, "m", params.generic, builder);
+        let n_size = create_size(params.n, "n", params.generic, builder);
+        let k_size = create_size(params.k, "k", params.generic, builder);
+        let batch = create_size(params.batch, "batch", true, builder);
+        let a_dims = vec![batch.clone(), m_size.clone(), k_size.clone()];
+        let a = TensorBuilder::new("a", a_dims)
+            .doif(params.transpose_a, |b| b.transpose(1, 2))
+            .finish(builder);
+        let b = TensorBuilder::new("b", vec![batch.clone(), k_size, n_size.clone()])
+            .doif(params.transpose_b, |b| b.transpose(1, 2))
+            .doif(!params.batch_b, |b| b.stride_dim(0))
+            .finish(builder);
+        let c = builder.tensor::<S>("c", vec![batch, m_size, n_size], false);
+        BatchMM { params, a, b, c }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        if !self.params.search_tiling {
+            let m_tiling =
+                helper::TilingPattern::infer_pattern(self.params.m as u32, &[64]);
+            let n_tiling =
+                helper::TilingPattern::infer_pattern(self.params.n as u32, &[64]);
+            let k_tiling =
+                helper::TilingPattern::infer_pattern(self.params.k as u32, &[64]);
+            return vec![self.build_tiled(signature, ctx, m_tiling, n_tiling, k_tiling)];
+        }
+        let device = ctx.device();
+        let m_tilings = tiling_choices(self.params.m, &None, 1, &*device);
+        let n_tilings = tiling_choices(self.params.n, &None, 1, &*device);
+        let k_tilings = tiling_choices(self.params.k, &None, 1, &*device);
+        iproduct!(m_tilings, n_tilings, k_tilings)
+            .map(|(m_tiling, n_tiling, k_tiling)| {
+                let signature = Arc::clone(&signature);
+                self.build_tiled(signature, ctx, m_tiling, n_tiling, k_tiling)
+            })
+            .collect()
     }
 
     fn get_expected_output(&self, context: &dyn device::Context) -> Array3<S> {
@@ -602,15 +790,16 @@ impl<'a, S: Scalar> Kernel<'a> for BatchMM<'a, S> {
         c
     }
 
-    fn check_result(
+    fn check_result_within(
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String> {
         let batch = self.params.batch as usize;
         let c_shape = (batch, self.params.m as usize, self.params.n as usize);
         let c = self.c.read_to_host(context).into_shape(c_shape).unwrap();
-        if let Err(invalid) = check_output(&c, expected) {
+        if let Err(invalid) = check_output(&c, expected, accuracy_budget) {
             Err(format!("Invalid batched_gemm output: {}", invalid))
         } else {
             Ok(())
@@ -636,6 +825,9 @@ pub struct Fused2MMP {
     pub k_tiling: Option<helper::TilingPattern>,
     pub p_tiling: Option<helper::TilingPattern>,
     pub activation_fun: Option<ActivationFunction>,
+    /// Fusion of the element-wise operations with the matrix products they apply to.
+    #[serde(default)]
+    pub fusion: Fusion,
 }
 
 impl Fused2MMP {
@@ -657,6 +849,7 @@ impl Fused2MMP {
             k_tiling: None,
             p_tiling: None,
             activation_fun: None,
+            fusion: Fusion::Free,
         }
     }
 
@@ -688,11 +881,24 @@ impl Fused2MMP {
         self
     }
 
+    pub fn fusion(mut self, fusion: Fusion) -> Self {
+        self.fusion = fusion;
+        self
+    }
+
     /// Inline the sizes in the generated code.
     pub fn static_sizes(mut self) -> Self {
         self.generic = false;
         self
     }
+
+    /// Ensures the sizes are positive and that the tiling patterns divide them.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_tiled_size("m", self.m, &self.m_tiling)?;
+        check_tiled_size("n", self.n, &self.n_tiling)?;
+        check_tiled_size("k", self.k, &self.k_tiling)?;
+        check_tiled_size("p", self.p, &self.p_tiling)
+    }
 }
 
 /// Computes `E = alpha*A.B.C + beta*D` and applies an activation
@@ -714,6 +920,10 @@ impl<'a, S: Scalar> Kernel<'a> for Fused2MM<'a, S> {
         "fused_2mm"
     }
 
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
     fn build_signature<AM>(params: Fused2MMP, builder: &mut SignatureBuilder<AM>) -> Self
     where
         AM: device::ArgMap<'a> + device::Context,
@@ -739,8 +949,8 @@ impl<'a, S: Scalar> Kernel<'a> for Fused2MM<'a, S> {
             .doif(params.transpose_d, |b| b.transpose(0, 1))
             .finish(builder);
 
-        builder.scalar("alpha", params.alpha);
-        builder.scalar("beta", params.beta);
+        builder.scalar("alpha", S::from(params.alpha).unwrap());
+        builder.scalar("beta", S::from(params.beta).unwrap());
 
         let e = builder.tensor::<S>("e", vec![m_size, p_size], false);
         Fused2MM {
@@ -772,13 +982,17 @@ impl<'a, S: Scalar> Kernel<'a> for Fused2MM<'a, S> {
         let c = self.c.load(vec![n_tiling, p_tiling.clone()], &mut builder);
         let d = self.d.load(vec![m_tiling, p_tiling], &mut builder);
 
-        let ab = matrix_matrix_multiply(&mut builder, &a, &b);
+        let fusion = self.params.fusion;
+        let ab = matrix_matrix_multiply::<S>(&mut builder, &a, &b);
         let aab = tensor_elementwise_mul(&mut builder, &"alpha", &ab);
-        let aabc = matrix_matrix_multiply(&mut builder, &aab, &c);
+        fuse(&mut builder, &ab, &aab, fusion);
+        let aabc = matrix_matrix_multiply::<S>(&mut builder, &aab, &c);
         let aabcpbd = tensor_mad(&mut builder, &d, &"beta", &aabc);
+        fuse(&mut builder, &aabc, &aabcpbd, fusion);
 
         if let Some(activation_fun) = &self.params.activation_fun {
             let res = activation_fun.apply::<S>(&mut builder, &aabcpbd);
+            fuse(&mut builder, &aabcpbd, &res, fusion);
             res.store(&self.e, &mut builder);
         } else {
             aabcpbd.store(&self.e, &mut builder);
@@ -821,17 +1035,1298 @@ impl<'a, S: Scalar> Kernel<'a> for Fused2MM<'a, S> {
         aabcpbd
     }
 
-    fn check_result(
+    fn check_result_within(
         &self,
         expected: &Self::ExpectedOutput,
         context: &dyn device::Context,
+        accuracy_budget: f64,
     ) -> Result<(), String> {
         let e_shape = (self.params.m as usize, self.params.p as usize);
         let e = unwrap!(self.e.read_to_host(context).into_shape(e_shape));
-        if let Err(invalid) = check_output(&e, expected) {
+        if let Err(invalid) = check_output(&e, expected, accuracy_budget) {
             Err(format!("Invalid fused_2mm output: {}", invalid))
         } else {
             Ok(())
         }
     }
 }
+
+/// The spatial geometry of a convolution: a filter slides over the padded input.
+#[derive(Copy, Clone)]
+struct SlidingWindow {
+    height: i32,
+    width: i32,
+    filter_height: i32,
+    filter_width: i32,
+    stride: i32,
+    padding: i32,
+}
+
+impl SlidingWindow {
+    fn padded_height(&self) -> i32 {
+        self.height + 2 * self.padding
+    }
+
+    fn padded_width(&self) -> i32 {
+        self.width + 2 * self.padding
+    }
+
+    fn out_height(&self) -> i32 {
+        out_size(self.padded_height(), self.filter_height, self.stride)
+    }
+
+    fn out_width(&self) -> i32 {
+        out_size(self.padded_width(), self.filter_width, self.stride)
+    }
+
+    /// Ensures the sizes and the stride are positive and that the filter fits in the
+    /// padded input.
+    fn validate(&self) -> Result<(), ParamsError> {
+        check_size("height", self.height)?;
+        check_size("width", self.width)?;
+        check_size("filter_height", self.filter_height)?;
+        check_size("filter_width", self.filter_width)?;
+        check_size("stride", self.stride)?;
+        if self.padding < 0 {
+            return Err(ParamsError::InvalidSize {
+                name: "padding",
+                value: self.padding,
+            });
+        }
+        check_size("out_height", self.out_height())?;
+        check_size("out_width", self.out_width())
+    }
+
+    /// Allocates the padded input, with `batch` images of `channels` channels. The border
+    /// of each image is filled with zeros.
+    fn build_input<'a, S, AM>(
+        &self,
+        name: &'a str,
+        batch: &DimSize<'a>,
+        channels: &DimSize<'a>,
+        builder: &mut SignatureBuilder<AM>,
+    ) -> Tensor<'a, S>
+    where
+        S: Scalar,
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let (padded_h, padded_w) = (self.padded_height(), self.padded_width());
+        let dims = vec![
+            batch.clone(),
+            channels.clone(),
+            (padded_h as u32).into(),
+            (padded_w as u32).into(),
+        ];
+        let len = dims
+            .iter()
+            .map(|s| s.eval(builder.context()) as usize)
+            .product::<usize>();
+        let array = builder.array::<S>(name, len);
+        if self.padding > 0 {
+            let (pad, h, w) = (self.padding, self.height, self.width);
+            let mut data = array.read::<S>();
+            for (i, value) in data.iter_mut().enumerate() {
+                let x = i as i32 % padded_w;
+                let y = i as i32 / padded_w % padded_h;
+                if y < pad || y >= pad + h || x < pad || x >= pad + w {
+                    *value = S::zero();
+                }
+            }
+            array.write(&data);
+        }
+        Tensor::new(name, dims, true, array)
+    }
+
+    /// Loads the windows of the input the filter is applied to. The dimensions of the
+    /// returned tensor are the batch, the channels, the rows and columns of the output
+    /// and the rows and columns of the filter.
+    fn load_windows<S: Scalar>(
+        &self,
+        name: &str,
+        batch: (&DimSize, helper::TilingPattern),
+        channels: (&DimSize, helper::TilingPattern),
+        builder: &mut Builder,
+    ) -> VirtualTensor {
+        let sizes = vec![
+            (batch.0.to_ir_size(builder), batch.1),
+            (channels.0.to_ir_size(builder), channels.1),
+            (
+                builder.cst_size(self.out_height() as u32),
+                Default::default(),
+            ),
+            (
+                builder.cst_size(self.out_width() as u32),
+                Default::default(),
+            ),
+            (
+                builder.cst_size(self.filter_height as u32),
+                Default::default(),
+            ),
+            (
+                builder.cst_size(self.filter_width as u32),
+                Default::default(),
+            ),
+        ];
+        let dims = sizes
+            .into_iter()
+            .map(|(size, tiling)| builder.open_tiled_dim(size, tiling))
+            .collect_vec();
+        // The output and filter positions both move along the spatial dimensions of the
+        // input.
+        let (h, w) = (self.padded_height() as u32, self.padded_width() as u32);
+        let stride = self.stride as u32;
+        let elem_size = unwrap!(S::t().len_byte());
+        let mut image_increment: DimSize = (h * w * elem_size).into();
+        image_increment.factor *= channels.0.factor;
+        image_increment
+            .params
+            .extend(channels.0.params.iter().cloned());
+        let increments = vec![
+            image_increment,
+            (h * w * elem_size).into(),
+            (stride * w * elem_size).into(),
+            (stride * elem_size).into(),
+            (w * elem_size).into(),
+            elem_size.into(),
+        ];
+        let increments = dims
+            .iter()
+            .zip_eq(increments)
+            .map(|(dim, increment)| (dim, increment.to_ir_size(builder)))
+            .collect_vec();
+        let ptr = builder.induction_var(&name, increments.clone());
+        let pattern = builder.tensor_access_pattern(None, increments);
+        let inst = builder.ld_nc(S::t(), &ptr, pattern);
+        for dim in &dims {
+            builder.close_dim(dim);
+        }
+        VirtualTensor::new(inst, dims)
+    }
+}
+
+/// Returns the number of positions of a filter of size `filter` along a dimension of size
+/// `size`, or 0 if the filter does not fit.
+fn out_size(size: i32, filter: i32, stride: i32) -> i32 {
+    if size < filter || stride <= 0 {
+        0
+    } else {
+        (size - filter) / stride + 1
+    }
+}
+
+/// Implements the methods that set and query the spatial geometry of convolution
+/// parameters. `$params` must have the fields of a `SlidingWindow` and a `generic` flag.
+macro_rules! sliding_window_params {
+    ($params:ident) => {
+        impl $params {
+            /// Sets the size of the filter.
+            pub fn filter(mut self, height: i32, width: i32) -> Self {
+                self.filter_height = height;
+                self.filter_width = width;
+                self
+            }
+
+            /// Sets the stride of the filter along both spatial dimensions.
+            pub fn stride(mut self, stride: i32) -> Self {
+                self.stride = stride;
+                self
+            }
+
+            /// Pads the input with `padding` zeros on each side of both spatial
+            /// dimensions.
+            pub fn padding(mut self, padding: i32) -> Self {
+                self.padding = padding;
+                self
+            }
+
+            /// Generate code that is only valid for the given batch size and numbers of
+            /// channels. The spatial sizes are always static.
+            pub fn static_sizes(mut self) -> Self {
+                self.generic = false;
+                self
+            }
+
+            fn window(&self) -> SlidingWindow {
+                SlidingWindow {
+                    height: self.height,
+                    width: self.width,
+                    filter_height: self.filter_height,
+                    filter_width: self.filter_width,
+                    stride: self.stride,
+                    padding: self.padding,
+                }
+            }
+
+            /// Returns the height of the input, including the padding.
+            pub fn padded_height(&self) -> i32 {
+                self.window().padded_height()
+            }
+
+            /// Returns the width of the input, including the padding.
+            pub fn padded_width(&self) -> i32 {
+                self.window().padded_width()
+            }
+
+            /// Returns the height of the output.
+            pub fn out_height(&self) -> i32 {
+                self.window().out_height()
+            }
+
+            /// Returns the width of the output.
+            pub fn out_width(&self) -> i32 {
+                self.window().out_width()
+            }
+        }
+    };
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct Conv2dP {
+    pub batch: i32,
+    pub in_channels: i32,
+    pub out_channels: i32,
+    pub height: i32,
+    pub width: i32,
+    pub filter_height: i32,
+    pub filter_width: i32,
+    pub stride: i32,
+    pub padding: i32,
+    pub generic: bool,
+}
+
+impl Conv2dP {
+    /// Creates the parameters of a convolution with a 3x3 filter, a stride of 1 and no
+    /// padding.
+    pub fn new(
+        batch: i32,
+        in_channels: i32,
+        out_channels: i32,
+        height: i32,
+        width: i32,
+    ) -> Self {
+        Conv2dP {
+            batch,
+            in_channels,
+            out_channels,
+            height,
+            width,
+            filter_height: 3,
+            filter_width: 3,
+            stride: 1,
+            padding: 0,
+            generic: true,
+        }
+    }
+
+    /// Ensures the sizes and the stride are positive and that the filter fits in the
+    /// padded input.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_size("batch", self.batch)?;
+        check_size("in_channels", self.in_channels)?;
+        check_size("out_channels", self.out_channels)?;
+        self.window().validate()
+    }
+}
+
+sliding_window_params!(Conv2dP);
+
+/// Computes a 2D convolution `O[n, k, y, x] = sum(I[n, c, y*stride + r, x*stride + s] *
+/// F[k, c, r, s])` on tensors in NCHW layout.
+///
+/// The input tensor holds the padded input: its spatial dimensions are
+/// `height + 2*padding` and `width + 2*padding`, and its border is filled with zeros when
+/// the signature is built. This avoids out-of-bounds accesses in the generated code.
+pub struct Conv2d<'a, S>
+where
+    S: Scalar,
+{
+    params: Conv2dP,
+    batch: DimSize<'a>,
+    in_channels: DimSize<'a>,
+    out_channels: DimSize<'a>,
+    input: Tensor<'a, S>,
+    filter: Tensor<'a, S>,
+    output: Tensor<'a, S>,
+}
+
+impl<'a, S: Scalar> Kernel<'a> for Conv2d<'a, S> {
+    type Parameters = Conv2dP;
+    type ExpectedOutput = Array4<S>;
+
+    fn name() -> &'static str {
+        "conv2d"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(params: Conv2dP, builder: &mut SignatureBuilder<AM>) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let batch = create_size(params.batch, "batch", params.generic, builder);
+        let in_channels =
+            create_size(params.in_channels, "in_channels", params.generic, builder);
+        let out_channels =
+            create_size(params.out_channels, "out_channels", params.generic, builder);
+        let input = params
+            .window()
+            .build_input("input", &batch, &in_channels, builder);
+        let filter_dims = vec![
+            out_channels.clone(),
+            in_channels.clone(),
+            (params.filter_height as u32).into(),
+            (params.filter_width as u32).into(),
+        ];
+        let filter = builder.tensor::<S>("filter", filter_dims, true);
+        let output_dims = vec![
+            batch.clone(),
+            out_channels.clone(),
+            (params.out_height() as u32).into(),
+            (params.out_width() as u32).into(),
+        ];
+        let output = builder.tensor::<S>("output", output_dims, false);
+        Conv2d {
+            params,
+            batch,
+            in_channels,
+            out_channels,
+            input,
+            filter,
+            output,
+        }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        let p = &self.params;
+        let batch_tiling = helper::TilingPattern::infer_pattern(p.batch as u32, &[32]);
+        let c_tiling = helper::TilingPattern::infer_pattern(p.in_channels as u32, &[32]);
+        let k_tiling = helper::TilingPattern::infer_pattern(p.out_channels as u32, &[32]);
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let filter_tiling = vec![
+            k_tiling,
+            c_tiling.clone(),
+            helper::TilingPattern::default(),
+            helper::TilingPattern::default(),
+        ];
+        let ld_filter = self.filter.load(filter_tiling, &mut builder);
+        let ld_input = p.window().load_windows::<S>(
+            "input",
+            (&self.batch, batch_tiling),
+            (&self.in_channels, c_tiling),
+            &mut builder,
+        );
+
+        let init_n = builder.open_mapped_dim(&ld_input[0]);
+        let init_k = builder.open_mapped_dim(&ld_filter[0]);
+        let init_y = builder.open_mapped_dim(&ld_input[2]);
+        let init_x = builder.open_mapped_dim(&ld_input[3]);
+        let acc_init = builder.mov(&S::zero());
+        let acc_n = builder.open_mapped_dim(&init_n);
+        let acc_k = builder.open_mapped_dim(&init_k);
+        let acc_y = builder.open_mapped_dim(&init_y);
+        let acc_x = builder.open_mapped_dim(&init_x);
+        let acc_c = builder.open_mapped_dim(&ld_input[1]);
+        let acc_r = builder.open_mapped_dim(&ld_input[4]);
+        let acc_s = builder.open_mapped_dim(&ld_input[5]);
+        let input_op = ld_input.dim_map(
+            &[&acc_n, &acc_c, &acc_y, &acc_x, &acc_r, &acc_s],
+            GlobalScope(()),
+            &mut builder,
+        );
+        let filter_op = ld_filter.dim_map(
+            &[&acc_k, &acc_c, &acc_r, &acc_s],
+            GlobalScope(()),
+            &mut builder,
+        );
+        let acc = builder.mad(&input_op, &filter_op, &helper::Reduce(acc_init));
+        builder.close_dim(&acc_c);
+        builder.close_dim(&acc_r);
+        builder.close_dim(&acc_s);
+
+        let acc = VirtualTensor::new(acc, vec![acc_n, acc_k, acc_y, acc_x]);
+        let st_output = acc.store(&self.output, &mut builder);
+
+        // Order for correctness.
+        builder.order(&st_output.inst(), &acc_c, Order::AFTER);
+        builder.order(&st_output.inst(), &acc_r, Order::AFTER);
+        builder.order(&st_output.inst(), &acc_s, Order::AFTER);
+        vec![build_candidate(builder.get(), ctx)]
+    }
+
+    fn get_expected_output(&self, context: &dyn device::Context) -> Array4<S> {
+        let p = &self.params;
+        let batch = self.batch.eval(context) as usize;
+        let c = self.in_channels.eval(context) as usize;
+        let k = self.out_channels.eval(context) as usize;
+        let (h, w) = (p.padded_height() as usize, p.padded_width() as usize);
+        let (fh, fw) = (p.filter_height as usize, p.filter_width as usize);
+        let (oh, ow) = (p.out_height() as usize, p.out_width() as usize);
+        let stride = p.stride as usize;
+        let input = unwrap!(self
+            .input
+            .read_to_host(context)
+            .into_shape((batch, c, h, w)));
+        let filter = unwrap!(self
+            .filter
+            .read_to_host(context)
+            .into_shape((k, c * fh * fw)));
+        let mut output = Array4::zeros((batch, k, oh, ow));
+        for (mut output, input) in output.outer_iter_mut().zip(input.outer_iter()) {
+            // Lay out the input window of each output position in a column, so that the
+            // convolution becomes a matrix-matrix product with the filter.
+            let cols = Array2::from_shape_fn((c * fh * fw, oh * ow), |(row, col)| {
+                let (channel, r, s) = (row / (fh * fw), row / fw % fh, row % fw);
+                let (y, x) = (col / ow, col % ow);
+                input[[channel, y * stride + r, x * stride + s]]
+            });
+            output.assign(&unwrap!(filter.dot(&cols).into_shape((k, oh, ow))));
+        }
+        output
+    }
+
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
+    ) -> Result<(), String> {
+        let output_shape = expected.dim();
+        let output = unwrap!(self.output.read_to_host(context).into_shape(output_shape));
+        if let Err(invalid) = check_output(&output, expected, accuracy_budget) {
+            Err(format!("Invalid conv2d output: {}", invalid))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct DepthwiseConvP {
+    pub batch: i32,
+    pub channels: i32,
+    pub height: i32,
+    pub width: i32,
+    pub filter_height: i32,
+    pub filter_width: i32,
+    pub stride: i32,
+    pub padding: i32,
+    pub generic: bool,
+}
+
+impl DepthwiseConvP {
+    /// Creates the parameters of a depthwise convolution with a 3x3 filter, a stride of 1
+    /// and no padding.
+    pub fn new(batch: i32, channels: i32, height: i32, width: i32) -> Self {
+        DepthwiseConvP {
+            batch,
+            channels,
+            height,
+            width,
+            filter_height: 3,
+            filter_width: 3,
+            stride: 1,
+            padding: 0,
+            generic: true,
+        }
+    }
+
+    /// Ensures the sizes and the stride are positive and that the filter fits in the
+    /// padded input.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_size("batch", self.batch)?;
+        check_size("channels", self.channels)?;
+        self.window().validate()
+    }
+}
+
+sliding_window_params!(DepthwiseConvP);
+
+/// Computes a depthwise 2D convolution, where each channel is convolved with its own
+/// filter: `O[n, c, y, x] = sum(I[n, c, y*stride + r, x*stride + s] * F[c, r, s])`, on
+/// tensors in NCHW layout. As for `Conv2d`, the input tensor holds the padded input.
+pub struct DepthwiseConv<'a, S>
+where
+    S: Scalar,
+{
+    params: DepthwiseConvP,
+    batch: DimSize<'a>,
+    channels: DimSize<'a>,
+    input: Tensor<'a, S>,
+    filter: Tensor<'a, S>,
+    output: Tensor<'a, S>,
+}
+
+impl<'a, S: Scalar> Kernel<'a> for DepthwiseConv<'a, S> {
+    type Parameters = DepthwiseConvP;
+    type ExpectedOutput = Array4<S>;
+
+    fn name() -> &'static str {
+        "depthwise_conv"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(
+        params: DepthwiseConvP,
+        builder: &mut SignatureBuilder<AM>,
+    ) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let batch = create_size(params.batch, "batch", params.generic, builder);
+        let channels = create_size(params.channels, "channels", params.generic, builder);
+        let input = params
+            .window()
+            .build_input("input", &batch, &channels, builder);
+        let filter_dims = vec![
+            channels.clone(),
+            (params.filter_height as u32).into(),
+            (params.filter_width as u32).into(),
+        ];
+        let filter = builder.tensor::<S>("filter", filter_dims, true);
+        let output_dims = vec![
+            batch.clone(),
+            channels.clone(),
+            (params.out_height() as u32).into(),
+            (params.out_width() as u32).into(),
+        ];
+        let output = builder.tensor::<S>("output", output_dims, false);
+        DepthwiseConv {
+            params,
+            batch,
+            channels,
+            input,
+            filter,
+            output,
+        }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        let p = &self.params;
+        let batch_tiling = helper::TilingPattern::infer_pattern(p.batch as u32, &[32]);
+        let c_tiling = helper::TilingPattern::infer_pattern(p.channels as u32, &[32]);
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let filter_tiling = vec![
+            c_tiling.clone(),
+            helper::TilingPattern::default(),
+            helper::TilingPattern::default(),
+        ];
+        let ld_filter = self.filter.load(filter_tiling, &mut builder);
+        let ld_input = p.window().load_windows::<S>(
+            "input",
+            (&self.batch, batch_tiling),
+            (&self.channels, c_tiling),
+            &mut builder,
+        );
+        let res = depthwise_conv::<S>(&mut builder, &ld_input, &ld_filter);
+        res.store(&self.output, &mut builder);
+
+        vec![build_candidate(builder.get(), ctx)]
+    }
+
+    fn get_expected_output(&self, context: &dyn device::Context) -> Array4<S> {
+        let p = &self.params;
+        let batch = self.batch.eval(context) as usize;
+        let c = self.channels.eval(context) as usize;
+        let (h, w) = (p.padded_height() as usize, p.padded_width() as usize);
+        let (fh, fw) = (p.filter_height as usize, p.filter_width as usize);
+        let (oh, ow) = (p.out_height() as usize, p.out_width() as usize);
+        let stride = p.stride as usize;
+        let input = unwrap!(self
+            .input
+            .read_to_host(context)
+            .into_shape((batch, c, h, w)));
+        let filter = unwrap!(self.filter.read_to_host(context).into_shape((c, fh, fw)));
+        Array4::from_shape_fn((batch, c, oh, ow), |(n, c, y, x)| {
+            let mut acc = S::zero();
+            for r in 0..fh {
+                for s in 0..fw {
+                    let value = input[[n, c, y * stride + r, x * stride + s]];
+                    acc += value * filter[[c, r, s]];
+                }
+            }
+            acc
+        })
+    }
+
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
+    ) -> Result<(), String> {
+        let output_shape = expected.dim();
+        let output = unwrap!(self.output.read_to_host(context).into_shape(output_shape));
+        if let Err(invalid) = check_output(&output, expected, accuracy_budget) {
+            Err(format!("Invalid depthwise_conv output: {}", invalid))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct PointwiseConvP {
+    pub batch: i32,
+    pub in_channels: i32,
+    pub out_channels: i32,
+    pub height: i32,
+    pub width: i32,
+    pub generic: bool,
+}
+
+impl PointwiseConvP {
+    pub fn new(
+        batch: i32,
+        in_channels: i32,
+        out_channels: i32,
+        height: i32,
+        width: i32,
+    ) -> Self {
+        PointwiseConvP {
+            batch,
+            in_channels,
+            out_channels,
+            height,
+            width,
+            generic: true,
+        }
+    }
+
+    /// Generate code that is only valid for the given batch size and numbers of channels.
+    /// The spatial sizes are always static.
+    pub fn static_sizes(mut self) -> Self {
+        self.generic = false;
+        self
+    }
+
+    /// Ensures the sizes are positive.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_size("batch", self.batch)?;
+        check_size("in_channels", self.in_channels)?;
+        check_size("out_channels", self.out_channels)?;
+        check_size("height", self.height)?;
+        check_size("width", self.width)
+    }
+}
+
+/// Computes a pointwise (1x1) convolution, which mixes the channels at each position:
+/// `O[n, k, y, x] = sum(I[n, c, y, x] * F[k, c])`, on tensors in NCHW layout.
+pub struct PointwiseConv<'a, S>
+where
+    S: Scalar,
+{
+    params: PointwiseConvP,
+    batch: DimSize<'a>,
+    in_channels: DimSize<'a>,
+    out_channels: DimSize<'a>,
+    input: Tensor<'a, S>,
+    filter: Tensor<'a, S>,
+    output: Tensor<'a, S>,
+}
+
+impl<'a, S: Scalar> Kernel<'a> for PointwiseConv<'a, S> {
+    type Parameters = PointwiseConvP;
+    type ExpectedOutput = Array4<S>;
+
+    fn name() -> &'static str {
+        "pointwise_conv"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(
+        params: PointwiseConvP,
+        builder: &mut SignatureBuilder<AM>,
+    ) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let batch = create_size(params.batch, "batch", params.generic, builder);
+        let in_channels =
+            create_size(params.in_channels, "in_channels", params.generic, builder);
+        let out_channels =
+            create_size(params.out_channels, "out_channels", params.generic, builder);
+        let (height, width) = (params.height as u32, params.width as u32);
+        let input_dims = vec![
+            batch.clone(),
+            in_channels.clone(),
+            height.into(),
+            width.into(),
+        ];
+        let input = builder.tensor::<S>("input", input_dims, true);
+        let filter_dims = vec![out_channels.clone(), in_channels.clone()];
+        let filter = builder.tensor::<S>("filter", filter_dims, true);
+        let output_dims = vec![
+            batch.clone(),
+            out_channels.clone(),
+            height.into(),
+            width.into(),
+        ];
+        let output = builder.tensor::<S>("output", output_dims, false);
+        PointwiseConv {
+            params,
+            batch,
+            in_channels,
+            out_channels,
+            input,
+            filter,
+            output,
+        }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        let p = &self.params;
+        let batch_tiling = helper::TilingPattern::infer_pattern(p.batch as u32, &[32]);
+        let c_tiling = helper::TilingPattern::infer_pattern(p.in_channels as u32, &[32]);
+        let k_tiling = helper::TilingPattern::infer_pattern(p.out_channels as u32, &[32]);
+        let h_tiling = helper::TilingPattern::infer_pattern(p.height as u32, &[32]);
+        let w_tiling = helper::TilingPattern::infer_pattern(p.width as u32, &[32]);
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let input_tiling = vec![batch_tiling, c_tiling.clone(), h_tiling, w_tiling];
+        let ld_input = self.input.load(input_tiling, &mut builder);
+        let ld_filter = self.filter.load(vec![k_tiling, c_tiling], &mut builder);
+        let res = pointwise_conv::<S>(&mut builder, &ld_input, &ld_filter);
+        res.store(&self.output, &mut builder);
+
+        vec![build_candidate(builder.get(), ctx)]
+    }
+
+    fn get_expected_output(&self, context: &dyn device::Context) -> Array4<S> {
+        let batch = self.batch.eval(context) as usize;
+        let c = self.in_channels.eval(context) as usize;
+        let k = self.out_channels.eval(context) as usize;
+        let (h, w) = (self.params.height as usize, self.params.width as usize);
+        let input =
+            unwrap!(self
+                .input
+                .read_to_host(context)
+                .into_shape((batch, c, h * w)));
+        let filter = unwrap!(self.filter.read_to_host(context).into_shape((k, c)));
+        let mut output = Array4::zeros((batch, k, h, w));
+        for (mut output, input) in output.outer_iter_mut().zip(input.outer_iter()) {
+            output.assign(&unwrap!(filter.dot(&input).into_shape((k, h, w))));
+        }
+        output
+    }
+
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
+    ) -> Result<(), String> {
+        let output_shape = expected.dim();
+        let output = unwrap!(self.output.read_to_host(context).into_shape(output_shape));
+        if let Err(invalid) = check_output(&output, expected, accuracy_budget) {
+            Err(format!("Invalid pointwise_conv output: {}", invalid))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Returns the number of lanes of the tree reduction of an axis of size `size`: the
+/// largest power of two, up to 32, that divides `size`.
+fn tree_width(size: i32) -> i32 {
+    (0..=5)
+        .rev()
+        .map(|i| 1 << i)
+        .find(|w| size % w == 0)
+        .unwrap()
+}
+
+/// Reduces a tensor along an axis split into two consecutive dimensions, `axis` for the
+/// blocks and `axis + 1` for the lanes, with a two-level tree: each lane first combines
+/// its blocks, then the partial results of the lanes are combined.
+fn tree_reduce<S: Scalar>(
+    builder: &mut Builder,
+    tensor: &VirtualTensor,
+    axis: usize,
+    op: ReduceOp,
+) -> VirtualTensor {
+    let partial = tensor_reduce::<S>(builder, tensor, axis, op);
+    tensor_reduce::<S>(builder, &partial, axis, op)
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct ReduceP {
+    pub op: ReduceOp,
+    pub outer: i32,
+    pub size: i32,
+    pub inner: i32,
+    pub generic: bool,
+}
+
+impl ReduceP {
+    /// Creates the parameters of the reduction with `op` of the axis `axis` of a tensor of
+    /// dimensions `shape`.
+    pub fn new(op: ReduceOp, shape: &[i32], axis: usize) -> Self {
+        ReduceP {
+            op,
+            outer: shape[..axis].iter().product(),
+            size: shape[axis],
+            inner: shape[axis + 1..].iter().product(),
+            generic: true,
+        }
+    }
+
+    /// Generate code that is only valid for the given sizes. The size of the reduced axis
+    /// is always static.
+    pub fn static_sizes(mut self) -> Self {
+        self.generic = false;
+        self
+    }
+
+    /// Ensures the sizes are positive.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_size("outer", self.outer)?;
+        check_size("size", self.size)?;
+        check_size("inner", self.inner)
+    }
+}
+
+/// Reduces an axis of a tensor with a sum or a maximum. The tensor is seen as a
+/// `outer x size x inner` tensor, where `size` is the reduced axis, `outer` the product
+/// of the dimensions before it and `inner` the product of the dimensions after it.
+pub struct Reduce<'a, S>
+where
+    S: Scalar,
+{
+    params: ReduceP,
+    outer: DimSize<'a>,
+    inner: DimSize<'a>,
+    input: Tensor<'a, S>,
+    output: Tensor<'a, S>,
+}
+
+impl<'a, S: Scalar> Kernel<'a> for Reduce<'a, S> {
+    type Parameters = ReduceP;
+    type ExpectedOutput = Array2<S>;
+
+    fn name() -> &'static str {
+        "reduce"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(params: ReduceP, builder: &mut SignatureBuilder<AM>) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let outer = create_size(params.outer, "outer", params.generic, builder);
+        let inner = create_size(params.inner, "inner", params.generic, builder);
+        // The reduced axis is split into the blocks and the lanes of the tree reduction.
+        let lanes = tree_width(params.size) as u32;
+        let blocks = params.size as u32 / lanes;
+        let input_dims = vec![outer.clone(), blocks.into(), lanes.into(), inner.clone()];
+        let input = builder.tensor::<S>("input", input_dims, true);
+        let output_dims = vec![outer.clone(), inner.clone()];
+        let output = builder.tensor::<S>("output", output_dims, false);
+        Reduce {
+            params,
+            outer,
+            inner,
+            input,
+            output,
+        }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        let p = &self.params;
+        let blocks = (p.size / tree_width(p.size)) as u32;
+        let outer_tiling = helper::TilingPattern::infer_pattern(p.outer as u32, &[32]);
+        let blocks_tiling = helper::TilingPattern::infer_pattern(blocks, &[32]);
+        let inner_tiling = helper::TilingPattern::infer_pattern(p.inner as u32, &[32]);
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let tiling = vec![
+            outer_tiling,
+            blocks_tiling,
+            Default::default(),
+            inner_tiling,
+        ];
+        let ld_input = self.input.load(tiling, &mut builder);
+        let res = tree_reduce::<S>(&mut builder, &ld_input, 1, p.op);
+        res.store(&self.output, &mut builder);
+
+        vec![build_candidate(builder.get(), ctx)]
+    }
+
+    fn get_expected_output(&self, context: &dyn device::Context) -> Array2<S> {
+        let outer = self.outer.eval(context) as usize;
+        let inner = self.inner.eval(context) as usize;
+        let size = self.params.size as usize;
+        let op = self.params.op;
+        let input = unwrap!(self
+            .input
+            .read_to_host(context)
+            .into_shape((outer, size, inner)));
+        input.fold_axis(Axis(1), op.identity(), |&acc, &x| op.combine(acc, x))
+    }
+
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
+    ) -> Result<(), String> {
+        let output =
+            unwrap!(self.output.read_to_host(context).into_shape(expected.dim()));
+        if let Err(invalid) = check_output(&output, expected, accuracy_budget) {
+            Err(format!("Invalid reduce output: {}", invalid))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct SoftmaxP {
+    pub m: i32,
+    pub n: i32,
+    pub generic: bool,
+}
+
+impl SoftmaxP {
+    pub fn new(m: i32, n: i32) -> Self {
+        SoftmaxP {
+            m,
+            n,
+            generic: true,
+        }
+    }
+
+    /// Generate code that is only valid for the given number of rows. The length of the
+    /// rows is always static.
+    pub fn static_sizes(mut self) -> Self {
+        self.generic = false;
+        self
+    }
+
+    /// Ensures the sizes are positive.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_size("m", self.m)?;
+        check_size("n", self.n)
+    }
+}
+
+/// Computes the softmax of each row of a `m x n` matrix:
+/// `Y[i, j] = exp(X[i, j] - max(X[i])) / sum(exp(X[i] - max(X[i])))`. Both the maximum
+/// and the sum are computed with a tree reduction.
+pub struct Softmax<'a, S>
+where
+    S: Scalar,
+{
+    params: SoftmaxP,
+    m: DimSize<'a>,
+    x: Tensor<'a, S>,
+    y: Tensor<'a, S>,
+}
+
+impl<'a, S: Scalar> Kernel<'a> for Softmax<'a, S> {
+    type Parameters = SoftmaxP;
+    type ExpectedOutput = Array2<S>;
+
+    fn name() -> &'static str {
+        "softmax"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(params: SoftmaxP, builder: &mut SignatureBuilder<AM>) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let m = create_size(params.m, "m", params.generic, builder);
+        // The rows are split into the blocks and the lanes of the tree reductions.
+        let lanes = tree_width(params.n) as u32;
+        let blocks = params.n as u32 / lanes;
+        let dims = vec![m.clone(), blocks.into(), lanes.into()];
+        let x = builder.tensor::<S>("x", dims.clone(), true);
+        let y = builder.tensor::<S>("y", dims, false);
+        Softmax { params, m, x, y }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        let p = &self.params;
+        let blocks = (p.n / tree_width(p.n)) as u32;
+        let m_tiling = helper::TilingPattern::infer_pattern(p.m as u32, &[32]);
+        let blocks_tiling = helper::TilingPattern::infer_pattern(blocks, &[32]);
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let tiling = vec![m_tiling, blocks_tiling, Default::default()];
+        let x = self.x.load(tiling, &mut builder);
+        let max = tree_reduce::<S>(&mut builder, &x, 1, ReduceOp::Max);
+        let exp = tensor_broadcast(&mut builder, &x, &max, &[1, 2], |x, max, builder| {
+            let shifted = builder.sub(x, max);
+            builder.exp(&shifted)
+        });
+        let sum = tree_reduce::<S>(&mut builder, &exp, 1, ReduceOp::Sum);
+        let y =
+            tensor_broadcast(&mut builder, &exp, &sum, &[1, 2], |exp, sum, builder| {
+                builder.div(exp, sum)
+            });
+        y.store(&self.y, &mut builder);
+
+        vec![build_candidate(builder.get(), ctx)]
+    }
+
+    fn get_expected_output(&self, context: &dyn device::Context) -> Array2<S> {
+        let m = self.m.eval(context) as usize;
+        let n = self.params.n as usize;
+        let mut y = unwrap!(self.x.read_to_host(context).into_shape((m, n)));
+        for mut row in y.outer_iter_mut() {
+            let max = row.fold(S::min_value(), |acc, &x| acc.max(x));
+            row.mapv_inplace(|x| (x - max).exp());
+            let sum = row.scalar_sum();
+            row.mapv_inplace(|x| x / sum);
+        }
+        y
+    }
+
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
+    ) -> Result<(), String> {
+        let y = unwrap!(self.y.read_to_host(context).into_shape(expected.dim()));
+        if let Err(invalid) = check_output(&y, expected, accuracy_budget) {
+            Err(format!("Invalid softmax output: {}", invalid))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub struct LayerNormP {
+    pub m: i32,
+    pub n: i32,
+    pub epsilon: f32,
+    pub bias: bool,
+    pub generic: bool,
+}
+
+impl LayerNormP {
+    /// Creates the parameters of the normalization of the rows of a `m x n` matrix, with
+    /// an epsilon of `1e-5` and a bias.
+    pub fn new(m: i32, n: i32) -> Self {
+        LayerNormP {
+            m,
+            n,
+            epsilon: 1e-5,
+            bias: true,
+            generic: true,
+        }
+    }
+
+    /// Sets the value added to the variance before taking its square root.
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Only scales the normalized values, without adding a bias.
+    pub fn without_bias(mut self) -> Self {
+        self.bias = false;
+        self
+    }
+
+    /// Generate code that is only valid for the given number of rows. The length of the
+    /// rows is always static.
+    pub fn static_sizes(mut self) -> Self {
+        self.generic = false;
+        self
+    }
+
+    /// Ensures the sizes are positive.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        check_size("m", self.m)?;
+        check_size("n", self.n)
+    }
+}
+
+/// Normalizes each row of a `m x n` matrix to a zero mean and a unit variance, then
+/// applies an affine transform:
+/// `Y[i, j] = (X[i, j] - mean(X[i])) / sqrt(var(X[i]) + epsilon) * gamma[j] + beta[j]`,
+/// where the bias `beta` is optional. The mean and the variance are computed with tree
+/// reductions.
+pub struct LayerNorm<'a, S>
+where
+    S: Scalar,
+{
+    params: LayerNormP,
+    m: DimSize<'a>,
+    x: Tensor<'a, S>,
+    gamma: Tensor<'a, S>,
+    beta: Option<Tensor<'a, S>>,
+    y: Tensor<'a, S>,
+}
+
+impl<'a, S: Scalar> Kernel<'a> for LayerNorm<'a, S> {
+    type Parameters = LayerNormP;
+    type ExpectedOutput = Array2<S>;
+
+    fn name() -> &'static str {
+        "layernorm"
+    }
+
+    fn validate_params(params: &Self::Parameters) -> Result<(), ParamsError> {
+        params.validate()
+    }
+
+    fn build_signature<AM>(params: LayerNormP, builder: &mut SignatureBuilder<AM>) -> Self
+    where
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let m = create_size(params.m, "m", params.generic, builder);
+        // The rows are split into the blocks and the lanes of the tree reductions.
+        let lanes = tree_width(params.n) as u32;
+        let blocks = params.n as u32 / lanes;
+        let dims = vec![m.clone(), blocks.into(), lanes.into()];
+        let x = builder.tensor::<S>("x", dims.clone(), true);
+        let row_dims = vec![blocks.into(), lanes.into()];
+        let gamma = builder.tensor::<S>("gamma", row_dims.clone(), true);
+        let beta = if params.bias {
+            Some(builder.tensor::<S>("beta", row_dims, true))
+        } else {
+            None
+        };
+        let y = builder.tensor::<S>("y", dims, false);
+        LayerNorm {
+            params,
+            m,
+            x,
+            gamma,
+            beta,
+            y,
+        }
+    }
+
+    fn build_body<'b>(
+        &self,
+        signature: Arc<ir::Signature>,
+        ctx: &'b dyn device::Context,
+    ) -> Vec<Candidate> {
+        let p = &self.params;
+        let blocks = (p.n / tree_width(p.n)) as u32;
+        let m_tiling = helper::TilingPattern::infer_pattern(p.m as u32, &[32]);
+        let blocks_tiling = helper::TilingPattern::infer_pattern(blocks, &[32]);
+        let inv_n = S::from(p.n).unwrap().recip();
+        let epsilon = S::from(p.epsilon).unwrap();
+        let mut builder = helper::Builder::new(signature, ctx.device());
+
+        let tiling = vec![m_tiling, blocks_tiling.clone(), Default::default()];
+        let x = self.x.load(tiling, &mut builder);
+        let row_tiling = vec![blocks_tiling, Default::default()];
+        let gamma = self.gamma.load(row_tiling.clone(), &mut builder);
+
+        let sum = tree_reduce::<S>(&mut builder, &x, 1, ReduceOp::Sum);
+        let mean = tensor_elementwise_mul(&mut builder, &inv_n, &sum);
+        let centered =
+            tensor_broadcast(&mut builder, &x, &mean, &[1, 2], |x, mean, b| {
+                b.sub(x, mean)
+            });
+        let squares = tensor_map(&mut builder, &centered, |x, b| b.mul(x, x));
+        let sum_squares = tree_reduce::<S>(&mut builder, &squares, 1, ReduceOp::Sum);
+        let inv_std = tensor_map(&mut builder, &sum_squares, |sum, b| {
+            let variance = b.mad(sum, &inv_n, &epsilon);
+            let std = b.sqrt(&variance);
+            b.div(&S::one(), &std)
+        });
+        let normalized = tensor_broadcast(
+            &mut builder,
+            &centered,
+            &inv_std,
+            &[1, 2],
+            |x, inv_std, b| b.mul(x, inv_std),
+        );
+        let mut y =
+            tensor_broadcast(&mut builder, &normalized, &gamma, &[0], |x, g, b| {
+                b.mul(x, g)
+            });
+        if let Some(beta) = &self.beta {
+            let beta = beta.load(row_tiling, &mut builder);
+            y = tensor_broadcast(&mut builder, &y, &beta, &[0], |x, beta, b| {
+                b.add(x, beta)
+            });
+        }
+        y.store(&self.y, &mut builder);
+
+        vec![build_candidate(builder.get(), ctx)]
+    }
+
+    fn get_expected_output(&self, context: &dyn device::Context) -> Array2<S> {
+        let m = self.m.eval(context) as usize;
+        let n = self.params.n as usize;
+        let epsilon = S::from(self.params.epsilon).unwrap();
+        let gamma = unwrap!(self.gamma.read_to_host(context).into_shape(n));
+        let beta = self
+            .beta
+            .as_ref()
+            .map(|beta| unwrap!(beta.read_to_host(context).into_shape(n)));
+        let mut y = unwrap!(self.x.read_to_host(context).into_shape((m, n)));
+        for mut row in y.outer_iter_mut() {
+            let mean = row.scalar_sum() / S::from(n).unwrap();
+            row.mapv_inplace(|x| x - mean);
+            let variance =
+                row.fold(S::zero(), |acc, &x| acc + x * x) / S::from(n).unwrap();
+            let inv_std = (variance + epsilon).sqrt().recip();
+            row.mapv_inplace(|x| x * inv_std);
+            row *= &gamma;
+            if let Some(beta) = &beta {
+                row += beta;
+            }
+        }
+        y
+    }
+
+    fn check_result_within(
+        &self,
+        expected: &Self::ExpectedOutput,
+        context: &dyn device::Context,
+        accuracy_budget: f64,
+    ) -> Result<(), String> {
+        let y = unwrap!(self.y.read_to_host(context).into_shape(expected.dim()));
+        if let Err(invalid) = check_output(&y, expected, accuracy_budget) {
+            Err(format!("Invalid layernorm output: {}", invalid))
+        } else {
+            Ok(())
+        }
+    }
+}
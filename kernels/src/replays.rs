@@ -0,0 +1,258 @@
+//! Selects interesting points of the search space of a kernel and records the actions
+//! leading to them, to use as regression fixtures.
+//!
+//! Replays are found by descending from the root of the search space with a seeded random
+//! number generator, so that the same seed and kernel lead to the same replays as long as
+//! the constraints and the performance model do not change.  A fixture records the
+//! deepest dead-ends along with the best and median implementations according to the
+//! performance model.  Changes to the constraints or to the model thus show up as
+//! differences when fixtures are generated again, and as failures of `Fixture::check`.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use rand::prng::XorShiftRng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use telamon::device;
+use telamon::explorer::choice::{self, ActionEx};
+use telamon::explorer::config::{ChoiceOrdering, NewNodeOrder};
+use telamon::explorer::Candidate;
+use telamon::helper::{seeded_rng, MemInit};
+use utils::cmp_f64;
+
+use crate::kernel::{Kernel, KernelBuilder};
+
+/// Maximal relative difference between the recorded and the actual bound of an
+/// implementation.
+const BOUND_RTOL: f64 = 1e-6;
+
+/// Indicates why a replay was selected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayKind {
+    /// The actions lead to a candidate whose next choice has no valid alternative.
+    Deadend,
+    /// The actions lead to the implementation with the lowest bound among the descents.
+    BestLeaf,
+    /// The actions lead to the implementation with the median bound among the descents.
+    MedianLeaf,
+}
+
+/// The actions leading to an interesting candidate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub kind: ReplayKind,
+    /// Index of the candidate of the kernel the actions apply to.
+    pub candidate: usize,
+    /// The actions to apply, in application order.
+    pub actions: Vec<ActionEx>,
+    /// The bound of the implementation, in nanoseconds.  `None` for dead-ends.
+    pub bound: Option<f64>,
+}
+
+impl Replay {
+    fn new(kind: ReplayKind, root: usize, candidate: &Candidate) -> Self {
+        let mut actions = candidate.actions.iter().cloned().collect::<Vec<_>>();
+        actions.reverse();
+        let bound = match kind {
+            ReplayKind::Deadend => None,
+            ReplayKind::BestLeaf | ReplayKind::MedianLeaf => {
+                Some(candidate.bound.value())
+            }
+        };
+        Replay {
+            kind,
+            candidate: root,
+            actions,
+            bound,
+        }
+    }
+
+    /// Replays the actions on `candidates` and ensures they still lead to a candidate of
+    /// the recorded kind.
+    fn check(
+        &self,
+        candidates: &[Candidate],
+        context: &dyn device::Context,
+    ) -> Result<(), String> {
+        let root = candidates
+            .get(self.candidate)
+            .ok_or_else(|| format!("no candidate with index {}", self.candidate))?;
+        let candidate = self
+            .actions
+            .iter()
+            .try_fold(root.clone(), |cand, action| {
+                cand.apply_decision(context, action.clone())
+            })
+            .map_err(|err| err.to_string())?;
+        let ordering = ChoiceOrdering::default();
+        let next_choice =
+            choice::list(ordering.at_depth(candidate.depth), &candidate.space).next();
+        match (self.kind, next_choice) {
+            (ReplayKind::Deadend, Some(choice)) => {
+                if candidate.apply_choice(context, choice).is_empty() {
+                    Ok(())
+                } else {
+                    Err("the dead-end has valid children".to_string())
+                }
+            }
+            (ReplayKind::Deadend, None) => {
+                Err("the dead-end is now a fully specified implementation".to_string())
+            }
+            (_, Some(_)) => Err("the implementation is not fully specified".to_string()),
+            (_, None) => {
+                let expected = self.bound.unwrap_or(std::f64::NAN);
+                let bound = candidate.bound.value();
+                if (bound - expected).abs() <= BOUND_RTOL * expected.abs() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "the bound changed from {:.4e}ns to {:.4e}ns",
+                        expected, bound
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Interesting replays of a kernel, found with a fixed seed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Fixture<P> {
+    /// The name of the kernel, as returned by `Kernel::name`.
+    pub kernel: String,
+    pub params: P,
+    /// The seed of the descents the replays were selected from.
+    pub seed: u64,
+    pub replays: Vec<Replay>,
+}
+
+impl<P> Fixture<P>
+where
+    P: Clone + Serialize + DeserializeOwned,
+{
+    /// Runs `num_descents` random descents in the search space of kernel `K` and keeps
+    /// the `num_deadends` deepest dead-ends along with the best and median
+    /// implementations according to the performance model.
+    pub fn generate<'a, K, AM>(
+        params: P,
+        seed: u64,
+        num_descents: usize,
+        num_deadends: usize,
+        context: &mut AM,
+    ) -> Self
+    where
+        K: Kernel<'a, Parameters = P>,
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let (signature, kernel, context) = KernelBuilder::new()
+            .mem_init(MemInit::Uninit)
+            .build::<K, AM>(params.clone(), context);
+        let candidates = kernel.build_body(Arc::new(signature), context);
+        let mut rng = seeded_rng(seed);
+        let mut leaves = vec![];
+        let mut deadends = vec![];
+        for _ in 0..num_descents {
+            match descend(&candidates, context, &mut rng) {
+                Some((root, Ok(leaf))) => leaves.push((root, leaf)),
+                Some((root, Err(deadend))) => deadends.push((root, deadend)),
+                None => break,
+            }
+        }
+        leaves
+            .sort_by(|(_, lhs), (_, rhs)| cmp_f64(lhs.bound.value(), rhs.bound.value()));
+        deadends.sort_by(|(_, lhs), (_, rhs)| rhs.depth.cmp(&lhs.depth));
+        let mut replays = Vec::new();
+        if let Some((root, leaf)) = leaves.first() {
+            replays.push(Replay::new(ReplayKind::BestLeaf, *root, leaf));
+        }
+        if let Some((root, leaf)) = leaves.get(leaves.len() / 2) {
+            replays.push(Replay::new(ReplayKind::MedianLeaf, *root, leaf));
+        }
+        let mut deadend_replays = Vec::<Replay>::new();
+        for (root, deadend) in &deadends {
+            if deadend_replays.len() >= num_deadends {
+                break;
+            }
+            // Distinct descents often end in the same dead-end.
+            let replay = Replay::new(ReplayKind::Deadend, *root, deadend);
+            if deadend_replays
+                .iter()
+                .all(|other| other.actions != replay.actions)
+            {
+                deadend_replays.push(replay);
+            }
+        }
+        replays.extend(deadend_replays);
+        Fixture {
+            kernel: K::name().to_string(),
+            params,
+            seed,
+            replays,
+        }
+    }
+
+    /// Ensures the replays still lead to candidates of the recorded kinds, with the same
+    /// bounds for implementations.
+    pub fn check<'a, K, AM>(&self, context: &mut AM) -> Result<(), String>
+    where
+        K: Kernel<'a, Parameters = P>,
+        AM: device::ArgMap<'a> + device::Context,
+    {
+        let (signature, kernel, context) = KernelBuilder::new()
+            .mem_init(MemInit::Uninit)
+            .build::<K, AM>(self.params.clone(), context);
+        let candidates = kernel.build_body(Arc::new(signature), context);
+        for (idx, replay) in self.replays.iter().enumerate() {
+            replay.check(&candidates, context).map_err(|err| {
+                format!(
+                    "replay {} ({:?}) of kernel {}: {}",
+                    idx, replay.kind, self.kernel, err
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Loads a fixture from a JSON file.
+    pub fn load<Q: AsRef<Path>>(path: Q) -> io::Result<Self> {
+        Ok(serde_json::from_reader(fs::File::open(path)?)?)
+    }
+
+    /// Writes the fixture to a JSON file, creating its directory if needed.
+    pub fn save<Q: AsRef<Path>>(&self, path: Q) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer_pretty(fs::File::create(path)?, self)?;
+        Ok(())
+    }
+}
+
+/// Descends from one of `candidates` to an implementation or to a dead-end, choosing
+/// among alternatives with `rng`.  Returns the index of the candidate the descent started
+/// from, or `None` if there are no candidates.
+fn descend(
+    candidates: &[Candidate],
+    context: &dyn device::Context,
+    rng: &mut XorShiftRng,
+) -> Option<(usize, Result<Candidate, Candidate>)> {
+    let order = NewNodeOrder::WeightedRandom;
+    let ordering = ChoiceOrdering::default();
+    let inf = std::f64::INFINITY;
+    let root = order.pick_candidate_with_rng(candidates, inf, rng)?;
+    let mut candidate = candidates[root].clone();
+    loop {
+        let choice =
+            choice::list(ordering.at_depth(candidate.depth), &candidate.space).next();
+        let choice = match choice {
+            Some(choice) => choice,
+            None => return Some((root, Ok(candidate))),
+        };
+        let mut children = candidate.apply_choice(context, choice);
+        match order.pick_candidate_with_rng(&children, inf, rng) {
+            Some(idx) => candidate = children.swap_remove(idx),
+            None => return Some((root, Err(candidate))),
+        }
+    }
+}
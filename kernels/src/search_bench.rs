@@ -0,0 +1,139 @@
+//! Hot paths of the search, exercised on representative kernels.
+//!
+//! This is shared between the `search` benchmark and the `tlcli self-bench` command, so that
+//! performance regressions in the search machinery itself can be caught without a GPU: all
+//! kernels are built in a fake context.
+use std::sync::mpsc;
+use std::thread;
+
+use telamon::device::fake;
+use telamon::explorer::{choice, config, local_selection, mcts, Candidate, Store};
+use telamon::model::{self, Bound};
+use telamon::search_space::SearchSpace;
+
+use crate::{linalg, Kernel, KernelBuilder};
+
+/// A kernel on which to benchmark the search.
+pub struct BenchKernel {
+    /// Name of the kernel, used to identify benchmarks.
+    pub name: &'static str,
+    /// The fake context in which the kernel was built.
+    pub context: fake::Context,
+    /// The root candidate of the kernel.
+    pub root: Candidate,
+}
+
+/// Builds the root candidate of a kernel in `context`.
+fn build_root<'a, K>(params: K::Parameters, context: &'a mut fake::Context) -> Candidate
+where
+    K: Kernel<'a> + 'a,
+{
+    let (signature, kernel, context) =
+        KernelBuilder::default().build::<K, _>(params, context);
+    kernel.build_body(signature.into(), context).swap_remove(0)
+}
+
+/// Returns the kernels the search is benchmarked on.
+pub fn kernels() -> Vec<BenchKernel> {
+    let mut context = fake::Context::default();
+    let params = linalg::FusedMMP::new(256, 256, 256);
+    let root = build_root::<linalg::FusedMM<f32>>(params, &mut context);
+    let matmul = BenchKernel {
+        name: "matmul_256_256_256",
+        context,
+        root,
+    };
+
+    let mut context = fake::Context::default();
+    let params = linalg::BatchMMP::new(32, 64, 64, 64);
+    let root = build_root::<linalg::BatchMM<f32>>(params, &mut context);
+    let batchmm = BenchKernel {
+        name: "batchmm_32_64_64_64",
+        context,
+        root,
+    };
+
+    vec![matmul, batchmm]
+}
+
+impl BenchKernel {
+    /// Clones the root candidate.
+    pub fn clone_root(&self) -> Candidate {
+        self.root.clone()
+    }
+
+    /// Applies all the actions of the first choice of the root candidate, computing the
+    /// bound of each resulting candidate.
+    pub fn apply_choice(&self) -> Vec<Candidate> {
+        let choice = choice::default_list(&self.root.space)
+            .next()
+            .unwrap_or_default();
+        self.root.apply_choice(&self.context, choice)
+    }
+
+    /// Computes the bound of the root candidate.
+    pub fn bound(&self) -> Bound {
+        model::bound(&self.root.space, &self.context)
+    }
+
+    /// Randomly descends to a fully specified implementation, retrying on dead-ends.
+    pub fn implementation(&self) -> Candidate {
+        loop {
+            let implementation = local_selection::descend(
+                &config::ChoiceOrdering::default(),
+                config::NewNodeOrder::Random,
+                &self.context,
+                self.root.clone(),
+                std::f64::INFINITY,
+            );
+            if let Some(implementation) = implementation {
+                return implementation;
+            }
+        }
+    }
+
+    /// Fixes the order of an implementation returned by `implementation`.
+    pub fn fix_order(&self, implementation: &Candidate) -> SearchSpace {
+        choice::fix_order(implementation.space.clone())
+    }
+
+    /// Creates a MCTS store rooted at the kernel.
+    pub fn mcts<'a>(&'a self, bandit_config: &'a config::BanditConfig) -> MctsBench<'a> {
+        let (sender, receiver) = mpsc::sync_channel(100);
+        // Drain the log messages: the thread exits when the store is dropped.
+        thread::spawn(move || receiver.into_iter().for_each(std::mem::drop));
+        let store = mcts::MctsStore::new(
+            self.root.space.clone(),
+            &self.context,
+            bandit_config,
+            Box::new(mcts::UCTPolicy::from(config::UCTConfig::default())),
+            Box::new(bandit_config.new_nodes_order),
+            sender,
+        );
+        MctsBench {
+            store,
+            context: &self.context,
+        }
+    }
+}
+
+/// A MCTS store, on which selection and backpropagation can be benchmarked.
+pub struct MctsBench<'a> {
+    store: mcts::MctsStore<'a, (), mcts::UCTStats>,
+    context: &'a fake::Context,
+}
+
+impl<'a> MctsBench<'a> {
+    /// Selects a new implementation in the tree and backpropagates its bound as if it was its
+    /// execution time.  Returns `false` if the tree was exhausted.
+    pub fn step(&self) -> bool {
+        if let Some((candidate, payload)) = self.store.explore(self.context) {
+            let eval = candidate.bound.value();
+            self.store
+                .commit_evaluation(&candidate.actions, payload, eval);
+            true
+        } else {
+            false
+        }
+    }
+}
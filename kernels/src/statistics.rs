@@ -52,6 +52,131 @@ pub fn estimate_mean(
     }
 }
 
+/// Computes the median of a data set. Returns NaN if the data set is empty.
+pub fn median(data: &[f64]) -> f64 {
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|&x, &y| cmp_f64(x, y));
+    let mid = sorted.len() / 2;
+    if sorted.is_empty() {
+        std::f64::NAN
+    } else if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Computes the mean of a data set after removing the `trim` fraction of its smallest
+/// values and the `trim` fraction of its largest values. `trim` must be between 0 and 0.5.
+pub fn trimmed_mean(data: &[f64], trim: f64) -> f64 {
+    assert!(0. <= trim && trim < 0.5);
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|&x, &y| cmp_f64(x, y));
+    let num_trimmed = (sorted.len() as f64 * trim).floor() as usize;
+    mean(&sorted[num_trimmed..sorted.len() - num_trimmed])
+}
+
+/// Computes the standard deviation of a data set.
+pub fn std_dev(data: &[f64]) -> f64 {
+    let mean = mean(data);
+    let variance = data.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+        / (data.len() as f64 - 1.).max(1.);
+    variance.sqrt()
+}
+
+/// Computes the median absolute deviation of a data set, scaled to be an estimator of the
+/// standard deviation for normally distributed data.
+pub fn mad(data: &[f64]) -> f64 {
+    let median = median(data);
+    let deviations = data.iter().map(|x| (x - median).abs()).collect::<Vec<_>>();
+    1.4826 * self::median(&deviations)
+}
+
+/// Number of median absolute deviations above which a sample is considered an outlier.
+const OUTLIER_THRESHOLD: f64 = 3.5;
+
+/// Indicates if `value` is an outlier for a data set with the given median and median
+/// absolute deviation. When the median absolute deviation is zero, more than half of the
+/// data set is equal to the median and the spread is unknown, so no value is an outlier.
+fn is_outlier(value: f64, median: f64, mad: f64) -> bool {
+    mad > 0. && (value - median).abs() > OUTLIER_THRESHOLD * mad
+}
+
+/// Returns the samples of the data set that are not outliers. Outliers are the samples
+/// further than 3.5 median absolute deviations from the median.
+pub fn remove_outliers(data: &[f64]) -> Vec<f64> {
+    let median = median(data);
+    let mad = mad(data);
+    data.iter()
+        .cloned()
+        .filter(|&x| !is_outlier(x, median, mad))
+        .collect()
+}
+
+/// Returns the number of samples at the start of a series that belong to a warm-up
+/// transient: the first samples that are all outliers compared to the second half of the
+/// series, which is assumed to be in a steady state. At most half of the series is
+/// considered to be a transient.
+pub fn warmup_len(series: &[f64]) -> usize {
+    let steady_state = &series[series.len() / 2..];
+    let median = median(steady_state);
+    let mad = mad(steady_state);
+    series
+        .iter()
+        .take(series.len() / 2)
+        .take_while(|&&x| is_outlier(x, median, mad))
+        .count()
+}
+
+/// Estimates of the mean of a series of runtimes, with and without the warm-up transient
+/// and the outliers.
+pub struct CleanedEstimate {
+    /// The estimate on all the samples.
+    pub raw: Estimate,
+    /// The estimate after removing the warm-up transient and the outliers.
+    pub cleaned: Estimate,
+    /// The median of all the samples.
+    pub median: f64,
+    /// The number of samples removed because they belong to the warm-up transient.
+    pub num_warmup: usize,
+    /// The number of samples removed because they are outliers.
+    pub num_outliers: usize,
+}
+
+impl std::fmt::Display for CleanedEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (raw: {}, median: {:.2e}{}, {} warm-up runs and {} outliers removed)",
+            self.cleaned,
+            self.raw,
+            self.median,
+            self.raw.unit,
+            self.num_warmup,
+            self.num_outliers
+        )
+    }
+}
+
+/// Same as `estimate_mean`, but also estimates the mean after removing the warm-up
+/// transient at the start of the series and the outliers.
+pub fn estimate_cleaned_mean(
+    series: Vec<f64>,
+    confidence: f64,
+    unit: &'static str,
+) -> CleanedEstimate {
+    let num_warmup = warmup_len(&series);
+    let steady = remove_outliers(&series[num_warmup..]);
+    let num_outliers = series.len() - num_warmup - steady.len();
+    CleanedEstimate {
+        median: median(&series),
+        cleaned: estimate_mean(steady, confidence, unit),
+        raw: estimate_mean(series, confidence, unit),
+        num_warmup,
+        num_outliers,
+    }
+}
+
 /// Computes the error margin of a ratio between answer of a binary choice given the
 /// number of samples with a 95% confidence interval.
 pub fn estimate_ratio(ratio: f64, num_samples: usize) -> Estimate {
@@ -0,0 +1,44 @@
+use telamon::device::fake;
+use telamon::ir::{self, Statement};
+use telamon::search_space::Order;
+use telamon_kernels::{linalg, Kernel, KernelBuilder};
+
+/// Ensures gesummv leaves the fusion of its element-wise operations with the products
+/// they apply to as a choice of the search space: the loops must be allowed, but not
+/// forced, to merge.
+#[test]
+fn gesummv_fusion_is_free() {
+    let mut context = fake::Context::<fake::Device>::default();
+    let (signature, kernel, context) = KernelBuilder::new()
+        .build::<linalg::Gesummv<f32>, _>((256, 256, false), &mut context);
+    let candidate = kernel.build_body(signature.into(), context).remove(0);
+
+    let space = &candidate.space;
+    let mut num_checked = 0;
+    for inst in space.ir_instance().insts() {
+        for operand in inst.operands() {
+            // Only consider the values produced by the products.
+            let dim_map = match operand {
+                ir::Operand::Inst(src, _, dim_map, _) => {
+                    match space.ir_instance().inst(*src).operator() {
+                        ir::Operator::Mad(..) => dim_map,
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            for &(lhs, rhs) in dim_map.iter() {
+                let lhs = space.ir_instance().dim(lhs).stmt_id();
+                let rhs = space.ir_instance().dim(rhs).stmt_id();
+                let order = space.domain().get_order(lhs, rhs);
+                assert!(order.contains(Order::MERGED), "{:?} cannot merge", order);
+                assert_ne!(order, Order::MERGED);
+                num_checked += 1;
+            }
+        }
+    }
+    assert!(
+        num_checked > 0,
+        "no product feeds an element-wise operation"
+    );
+}
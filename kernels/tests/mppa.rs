@@ -34,3 +34,51 @@ test_output!(
     100,
     linalg::FusedMMP::new(16, 16, 16).activation_fun(linalg::ActivationFunction::Sigmoid)
 );
+test_output!(
+    conv2d,
+    linalg::Conv2d<f32>,
+    100,
+    linalg::Conv2dP::new(1, 4, 4, 8, 8)
+);
+test_output!(
+    conv2d_strided,
+    linalg::Conv2d<f32>,
+    100,
+    linalg::Conv2dP::new(1, 4, 4, 8, 8).stride(2).padding(1)
+);
+test_output!(
+    depthwise_conv,
+    linalg::DepthwiseConv<f32>,
+    100,
+    linalg::DepthwiseConvP::new(1, 4, 8, 8)
+);
+test_output!(
+    pointwise_conv,
+    linalg::PointwiseConv<f32>,
+    100,
+    linalg::PointwiseConvP::new(1, 4, 8, 8, 8)
+);
+test_output!(
+    reduce_sum,
+    linalg::Reduce<f32>,
+    100,
+    linalg::ReduceP::new(linalg::ReduceOp::Sum, &[16, 64], 1)
+);
+test_output!(
+    reduce_max,
+    linalg::Reduce<f32>,
+    100,
+    linalg::ReduceP::new(linalg::ReduceOp::Max, &[4, 16, 8], 1)
+);
+test_output!(
+    softmax,
+    linalg::Softmax<f32>,
+    100,
+    linalg::SoftmaxP::new(16, 64)
+);
+test_output!(
+    layer_norm,
+    linalg::LayerNorm<f32>,
+    100,
+    linalg::LayerNormP::new(16, 64)
+);
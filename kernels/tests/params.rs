@@ -0,0 +1,62 @@
+use telamon::device::fake;
+use telamon::helper::SignatureBuilder;
+use telamon_kernels::{linalg, Kernel, ParamsError};
+
+#[test]
+fn matmul_unit_stride() {
+    assert_eq!(linalg::FusedMMP::new(16, 16, 16).validate(), Ok(()));
+    assert_eq!(
+        linalg::FusedMMP::new(16, 16, 16).stride_a(32).validate(),
+        Ok(())
+    );
+}
+
+#[test]
+fn matmul_zero_stride() {
+    let params = linalg::FusedMMP::new(16, 16, 16).stride_a(0);
+    let error = ParamsError::InvalidStride {
+        name: "a",
+        stride: 0,
+    };
+    assert_eq!(params.validate(), Err(error));
+}
+
+/// Ensures strides are rejected when the strided elements of `A` do not fit in the
+/// addressable range.
+#[test]
+fn matmul_overflowing_stride() {
+    let stride = 1 << 20;
+    let params = linalg::FusedMMP::new(1024, 1024, 16).stride_a(stride);
+    let error = ParamsError::InvalidStride { name: "a", stride };
+    assert_eq!(params.validate(), Err(error));
+}
+
+/// Ensures grid-stride tiling is rejected when the batch size is not a multiple of the
+/// number of tiles, as the last tile would otherwise skip the remainder of the batch.
+#[test]
+fn batchmm_grid_stride_remainder() {
+    let params = linalg::BatchMMP::new(64, 16, 16, 16);
+    assert_eq!(params.grid_stride(16).validate(), Ok(()));
+    let error = ParamsError::IndivisibleTiling {
+        name: "batch",
+        size: 64,
+        factor: 24,
+    };
+    assert_eq!(params.grid_stride(24).validate(), Err(error));
+}
+
+/// Ensures the search space is not built when the batch size is not a multiple of the
+/// number of grid-stride tiles, even if the parameters were not validated.
+#[test]
+#[should_panic(expected = "is not a multiple of its tiling factor")]
+fn batchmm_grid_stride_unchecked() {
+    let mut context = fake::Context::<fake::Device>::default();
+    let params = linalg::BatchMMP::new(64, 16, 16, 16).grid_stride(24);
+    let (signature, kernel);
+    {
+        let mut builder = SignatureBuilder::new("batch_mm", &mut context);
+        kernel = linalg::BatchMM::<f32>::build_signature(params, &mut builder);
+        signature = builder.get();
+    }
+    kernel.build_body(signature.into(), &context);
+}
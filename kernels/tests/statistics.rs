@@ -0,0 +1,31 @@
+use telamon_kernels::statistics::{mad, median, remove_outliers, warmup_len};
+
+#[test]
+fn median_of_empty_data_set() {
+    assert!(median(&[]).is_nan());
+    assert!(mad(&[]).is_nan());
+    assert!(remove_outliers(&[]).is_empty());
+    assert_eq!(warmup_len(&[]), 0);
+}
+
+#[test]
+fn median_of_data_set() {
+    assert_eq!(median(&[3., 1., 2.]), 2.);
+    assert_eq!(median(&[4., 1., 3., 2.]), 2.5);
+}
+
+/// Ensures samples are not flagged as outliers when most of them are equal.
+#[test]
+fn zero_mad_keeps_samples() {
+    let data = [1., 1., 1., 1., 2.];
+    assert_eq!(mad(&data), 0.);
+    assert_eq!(remove_outliers(&data), data.to_vec());
+    assert_eq!(warmup_len(&[2., 1., 1., 1.]), 0);
+}
+
+#[test]
+fn remove_outliers_with_spread() {
+    let data = [1., 1.1, 0.9, 1.05, 0.95, 10.];
+    assert_eq!(remove_outliers(&data), vec![1., 1.1, 0.9, 1.05, 0.95]);
+    assert_eq!(warmup_len(&[10., 1., 1.1, 0.9, 1.05, 0.95]), 1);
+}
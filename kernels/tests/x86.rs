@@ -1,14 +1,25 @@
 #![cfg(feature = "x86")]
 
-use telamon_kernels::{linalg, Kernel};
+use std::sync::Arc;
+
+use telamon::codegen;
+use telamon::device::{self, Context};
+use telamon::explorer::choice::{self, ActionEx};
+use telamon::helper::tensor::TensorBuilder;
+use telamon::helper::{self, MemInit, SignatureBuilder};
+use telamon_kernels::replays::Fixture;
+use telamon_kernels::{linalg, Kernel, KernelBuilder};
 use telamon_x86 as x86;
 
 macro_rules! test_dump {
     ($name:ident, $kernel:ty, $params:expr) => {
+        test_dump!($name, $kernel, $params, x86::Context::default());
+    };
+    ($name:ident, $kernel:ty, $params:expr, $context:expr) => {
         #[test]
         fn $name() {
             let _ = env_logger::try_init();
-            let mut context = x86::Context::default();
+            let mut context = $context;
             let path = format!("kernel_dump/x86/{}.json", stringify!($name));
             let mut file = std::fs::File::open(&path).unwrap();
             <$kernel>::execute_dump(&mut context, &mut file);
@@ -16,6 +27,17 @@ macro_rules! test_dump {
     };
 }
 
+macro_rules! test_output {
+    ($name:ident, $kernel:ty, $num_tests:expr, $params:expr) => {
+        #[test]
+        fn $name() {
+            let _ = env_logger::try_init();
+            let mut context = x86::Context::default();
+            <$kernel>::test_correctness($params, $num_tests, &mut context);
+        }
+    };
+}
+
 test_dump!(axpy, linalg::Axpy<f32>, (1 << 16, true));
 test_dump!(mv, linalg::MatVec<f32>, (1 << 4, 1 << 2, true));
 test_dump!(gesummv, linalg::Gesummv<f32>, (1 << 4, 1 << 4, true));
@@ -34,3 +56,160 @@ test_dump!(
     linalg::FusedMM<f32>,
     linalg::FusedMMP::new(16, 16, 16).activation_fun(linalg::ActivationFunction::Sigmoid)
 );
+
+test_output!(
+    conv2d,
+    linalg::Conv2d<f32>,
+    100,
+    linalg::Conv2dP::new(1, 4, 4, 8, 8)
+);
+test_output!(
+    conv2d_strided,
+    linalg::Conv2d<f32>,
+    100,
+    linalg::Conv2dP::new(1, 4, 4, 8, 8).stride(2).padding(1)
+);
+test_output!(
+    depthwise_conv,
+    linalg::DepthwiseConv<f32>,
+    100,
+    linalg::DepthwiseConvP::new(1, 4, 8, 8)
+);
+test_output!(
+    pointwise_conv,
+    linalg::PointwiseConv<f32>,
+    100,
+    linalg::PointwiseConvP::new(1, 4, 8, 8, 8)
+);
+test_output!(
+    reduce_sum,
+    linalg::Reduce<f32>,
+    100,
+    linalg::ReduceP::new(linalg::ReduceOp::Sum, &[16, 64], 1)
+);
+test_output!(
+    reduce_max,
+    linalg::Reduce<f32>,
+    100,
+    linalg::ReduceP::new(linalg::ReduceOp::Max, &[4, 16, 8], 1)
+);
+test_output!(
+    softmax,
+    linalg::Softmax<f32>,
+    100,
+    linalg::SoftmaxP::new(16, 64)
+);
+test_output!(
+    layer_norm,
+    linalg::LayerNorm<f32>,
+    100,
+    linalg::LayerNormP::new(16, 64)
+);
+
+/// Ensures replays generated with a seed are stable and can be checked against the kernel.
+#[test]
+fn replays() {
+    let _ = env_logger::try_init();
+    let mut context = x86::Context::default();
+    let params = linalg::FusedMMP::new(16, 16, 16);
+    let fixture = Fixture::generate::<linalg::FusedMM<f32>, _>(
+        params.clone(),
+        0,
+        20,
+        3,
+        &mut context,
+    );
+    let again =
+        Fixture::generate::<linalg::FusedMM<f32>, _>(params, 0, 20, 3, &mut context);
+    assert_eq!(
+        serde_json::to_string(&fixture).unwrap(),
+        serde_json::to_string(&again).unwrap()
+    );
+    fixture
+        .check::<linalg::FusedMM<f32>, _>(&mut context)
+        .unwrap_or_else(|err| panic!("{}", err));
+}
+
+/// Ensures an artifact exported from an implementation computes the correct result once
+/// loaded back from its serialized form.
+#[test]
+fn artifact() {
+    let _ = env_logger::try_init();
+    let mut context = x86::Context::default();
+    let dump = std::fs::File::open("kernel_dump/x86/axpy.json").unwrap();
+    let (params, actions): (_, Vec<ActionEx>) = serde_json::from_reader(dump).unwrap();
+    let (signature, kernel, context) =
+        KernelBuilder::new().build::<linalg::Axpy<f32>, _>(params, &mut context);
+    let expected = kernel.get_expected_output(context);
+    let candidate = kernel.build_body(Arc::new(signature), context).remove(0);
+    let implem = actions.into_iter().fold(candidate, |cand, action| {
+        cand.apply_decision(context, action).unwrap()
+    });
+    let function = codegen::Function::build(&implem.space);
+    let artifact = context.export_artifact(&function).unwrap();
+    let json = serde_json::to_string(&artifact).unwrap();
+    let artifact: codegen::Artifact = serde_json::from_str(&json).unwrap();
+    let mut loaded = context.load_artifact(&artifact).unwrap();
+    loaded.evaluate().unwrap();
+    kernel
+        .check_result(&expected, context)
+        .unwrap_or_else(|err| panic!("{}", err));
+}
+
+/// Ensures a tensor iterated on in reverse order is read from the end of its storage, by
+/// copying it to a tensor stored in the regular order.
+#[test]
+fn reversed_tensor() {
+    let _ = env_logger::try_init();
+    let mut context = x86::Context::default();
+    let (x, y, signature) = {
+        let mut builder = SignatureBuilder::new("reverse", &mut context);
+        builder.set_mem_init(MemInit::RandomFill);
+        let x = TensorBuilder::new("x", vec![16u32.into()])
+            .reverse(0)
+            .finish::<f32, _>(&mut builder);
+        let y = builder.tensor::<f32>("y", vec![16u32.into()], false);
+        (x, y, builder.get())
+    };
+    let mut builder = helper::Builder::new(Arc::new(signature), context.device());
+    let x_ld = x.load(vec![Default::default()], &mut builder);
+    x_ld.store(&y, &mut builder);
+    let mut space = builder.get();
+    while let Some(choice) = choice::default_list(&space).next() {
+        space = choice
+            .iter()
+            .find_map(|action| action.apply_to(space.clone()).ok())
+            .expect("no valid action");
+    }
+    let function = codegen::Function::build(&choice::fix_order(space));
+    context
+        .evaluate(&function, device::EvalMode::FindBest)
+        .unwrap();
+    assert_eq!(y.read_to_host(&context), x.read_to_host(&context));
+}
+
+/// Runs the same dumps with the code compiled by the LLVM JIT.
+#[cfg(feature = "x86_llvm")]
+mod llvm {
+    use super::*;
+
+    fn context() -> x86::Context {
+        x86::Context::with_compiler(x86::Compiler::Llvm)
+    }
+
+    test_dump!(axpy, linalg::Axpy<f32>, (1 << 16, true), context());
+    test_dump!(mv, linalg::MatVec<f32>, (1 << 4, 1 << 2, true), context());
+    test_dump!(
+        gesummv,
+        linalg::Gesummv<f32>,
+        (1 << 4, 1 << 4, true),
+        context()
+    );
+    test_dump!(
+        fused_mm_sigmoid,
+        linalg::FusedMM<f32>,
+        linalg::FusedMMP::new(16, 16, 16)
+            .activation_fun(linalg::ActivationFunction::Sigmoid),
+        context()
+    );
+}
@@ -277,6 +277,11 @@ fn main() -> io::Result<()> {
                     tw.write_candidate_evaluate_action(id, result_time, value)?;
                 }
             }
+
+            // Screening values are superseded by the final evaluation.
+            Message::Screening { .. } => (),
+            Message::Launch { .. } | Message::Source { .. } => (),
+            Message::Energy { .. } => (),
         }
     }
 
@@ -0,0 +1,171 @@
+//! Persistent cache of the best implementation found for each kernel, so that applications
+//! embedding Telamon do not run the search again each time they start.
+//!
+//! The cache is a directory with one file per kernel and device.  Kernels are identified
+//! by their signature, the sizes bound to their integer parameters and their body.  Each
+//! file holds the actions leading to the best implementation found, from the candidates
+//! the search started from.  Entries are only reused if the actions still lead to a fully
+//! specified implementation, so that entries produced by a previous version of Telamon
+//! are ignored instead of producing invalid code.
+//!
+//! Several processes may share the cache.  Entries are written to a temporary file that
+//! is then renamed over the entry, so that readers never see a partially written entry.
+//! When two searches for the same kernel finish concurrently, the last one to finish
+//! wins, which is no worse than running them one after the other.
+use crate::device;
+use crate::explorer::choice::{self, ActionEx};
+use crate::explorer::Candidate;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A directory in which the best implementations are cached.
+pub struct TuningCache {
+    dir: PathBuf,
+}
+
+/// Identifies a kernel running on a device in the cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey {
+    kernel: String,
+    device: String,
+    /// Sizes bound to the integer parameters of the kernel, in the order of the
+    /// signature.
+    sizes: Vec<(String, Option<u32>)>,
+    /// Hash of the body of the candidates the search starts from.
+    body: u64,
+}
+
+impl CacheKey {
+    /// Identifies the kernel implemented by `candidates` in `context`.
+    pub fn new(candidates: &[Candidate], context: &dyn device::Context) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for candidate in candidates {
+            candidate.space.ir_instance().to_string().hash(&mut hasher);
+        }
+        let signature = candidates
+            .first()
+            .map(|candidate| candidate.space.ir_instance().signature());
+        let sizes = signature
+            .into_iter()
+            .flat_map(|signature| &signature.params)
+            .filter(|param| param.elem_t.is_none() && param.t.is_integer())
+            .map(|param| (param.name.clone(), context.param_as_size(&param.name)))
+            .collect();
+        CacheKey {
+            kernel: signature.map(|s| s.name.clone()).unwrap_or_default(),
+            device: context.device().name().to_string(),
+            sizes,
+            body: hasher.finish(),
+        }
+    }
+}
+
+/// The best implementation found for a kernel.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    key: CacheKey,
+    /// Actions leading to the implementation, in application order.
+    actions: Vec<ActionEx>,
+}
+
+impl TuningCache {
+    /// Uses the cache stored in `dir`.  The directory is created on the first insertion.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        TuningCache { dir: dir.into() }
+    }
+
+    /// Returns the path of the entry for the given key.
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let escape = |name: &str| {
+            name.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+        };
+        self.dir.join(escape(&key.device)).join(format!(
+            "{}-{:016x}.json",
+            escape(&key.kernel),
+            hasher.finish()
+        ))
+    }
+
+    /// Returns the cached implementation of the kernel identified by `key`, built from
+    /// one of `candidates`.  Returns `None` if there is no entry, or if the entry cannot
+    /// be applied to the candidates anymore.
+    pub fn get(
+        &self,
+        key: &CacheKey,
+        candidates: &[Candidate],
+        context: &dyn device::Context,
+    ) -> Option<Candidate> {
+        let path = self.path(key);
+        let file = File::open(&path).ok()?;
+        let entry: Entry = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("ignoring invalid tuning cache {}: {}", path.display(), err);
+                return None;
+            }
+        };
+        // The name of the file only depends on a hash of the key.
+        if entry.key != *key {
+            return None;
+        }
+        let implementation = candidates.iter().find_map(|candidate| {
+            let leaf = entry
+                .actions
+                .iter()
+                .try_fold(candidate.clone(), |cand, action| {
+                    cand.apply_decision(context, action.clone())
+                })
+                .ok()?;
+            if choice::default_list(&leaf.space).next().is_some() {
+                None
+            } else {
+                Some(leaf)
+            }
+        });
+        if implementation.is_none() {
+            warn!("ignoring stale tuning cache {}", path.display());
+        }
+        implementation
+    }
+
+    /// Records `implementation` as the best implementation of the kernel identified by
+    /// `key`.
+    pub fn insert(&self, key: &CacheKey, implementation: &Candidate) -> io::Result<()> {
+        let mut actions = implementation.actions.iter().cloned().collect::<Vec<_>>();
+        actions.reverse();
+        let entry = Entry {
+            key: key.clone(),
+            actions,
+        };
+        let path = self.path(key);
+        fs::create_dir_all(path.parent().unwrap())?;
+        // The temporary file is unique to each insertion, even across processes, and
+        // lives in the same directory as the entry so that the rename is atomic.
+        static NUM_INSERTS: AtomicUsize = AtomicUsize::new(0);
+        let tmp_path = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            NUM_INSERTS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let write = || -> io::Result<()> {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer_pretty(&mut writer, &entry)?;
+            writer.into_inner()?.sync_all()?;
+            fs::rename(&tmp_path, &path)
+        };
+        write().map_err(|err| {
+            let _ = fs::remove_file(&tmp_path);
+            err
+        })
+    }
+}
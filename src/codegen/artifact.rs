@@ -0,0 +1,150 @@
+//! Self-contained description of the code generated for a function, to run tuned kernels
+//! without building them again.
+//!
+//! An artifact holds the source code printed by a backend along with everything needed to
+//! launch it: the name of the entry point, the launch configuration and the layout of the
+//! arguments.  Sizes are evaluated when the artifact is exported, so the artifact is only
+//! valid for the sizes of the context it was exported from.  Artifacts are loaded back with
+//! `device::Context::load_artifact`.
+use std::path::Path;
+use std::{error, fmt, fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::codegen::{Function, LaunchConfig, ParamVal};
+use crate::device;
+use crate::ir;
+
+/// Version of the artifact format.  Must be incremented when the format changes in a way
+/// that prevents loading older artifacts.
+pub const ARTIFACT_VERSION: u32 = 1;
+
+/// The code generated for a function, with the information needed to launch it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Version of the format the artifact was written with.
+    pub version: u32,
+    /// Name of the device the code was generated for, as returned by `Device::name`.
+    pub device: String,
+    /// Name of the function of `source` to call.
+    pub entry_point: String,
+    /// Source code, in the format expected by the backend of the device.
+    pub source: String,
+    pub launch_config: LaunchConfig,
+    /// Arguments of the entry point, in order.
+    pub params: Vec<ArtifactParam>,
+}
+
+/// An argument of the entry point of an artifact.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArtifactParam {
+    /// A parameter of the kernel, provided by the caller.  `t` is the type of the value
+    /// passed to the device, which is a pointer type for arrays.
+    External { name: String, t: ir::Type },
+    /// A size computed when the artifact was exported, passed as a 32-bit integer.
+    Size(u32),
+    /// A temporary array of the given size in bytes, allocated before each launch.
+    TmpArray(u32),
+}
+
+impl Artifact {
+    /// Creates the artifact of `function`, evaluating sizes in `context`.  `source` is the
+    /// code printed for `function` by the backend and `entry_point` the name of the
+    /// function to call in it.
+    pub fn new(
+        function: &Function,
+        entry_point: String,
+        source: String,
+        context: &dyn device::Context,
+    ) -> Self {
+        let params = function
+            .device_code_args()
+            .map(|arg| match arg {
+                ParamVal::External(param, t) => ArtifactParam::External {
+                    name: param.name.clone(),
+                    t: *t,
+                },
+                ParamVal::Size(size) => ArtifactParam::Size(context.eval_size(size)),
+                ParamVal::GlobalMem(_, size, _) => {
+                    ArtifactParam::TmpArray(context.eval_size(size))
+                }
+            })
+            .collect();
+        Artifact {
+            version: ARTIFACT_VERSION,
+            device: context.device().name().to_string(),
+            entry_point,
+            source,
+            launch_config: function.launch_config(context),
+            params,
+        }
+    }
+
+    /// Ensures the artifact can be loaded in `context`.
+    pub fn check_device(
+        &self,
+        context: &dyn device::Context,
+    ) -> Result<(), ArtifactError> {
+        let device = context.device();
+        if self.device == device.name() {
+            Ok(())
+        } else {
+            Err(ArtifactError::WrongDevice {
+                expected: self.device.clone(),
+                found: device.name().to_string(),
+            })
+        }
+    }
+
+    /// Loads an artifact from a JSON file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let artifact: Artifact = serde_json::from_reader(fs::File::open(path)?)?;
+        if artifact.version != ARTIFACT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "unsupported artifact version {} (expected {})",
+                    artifact.version, ARTIFACT_VERSION
+                ),
+            ));
+        }
+        Ok(artifact)
+    }
+
+    /// Writes the artifact to a JSON file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        serde_json::to_writer_pretty(fs::File::create(path)?, self)?;
+        Ok(())
+    }
+}
+
+/// Error returned when loading an artifact in a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtifactError {
+    /// The artifact was generated for another device.
+    WrongDevice { expected: String, found: String },
+    /// The context has no argument bound to a parameter of the artifact.
+    UnknownParam(String),
+    /// The backend does not support loading artifacts.
+    Unsupported(String),
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArtifactError::WrongDevice { expected, found } => write!(
+                fmt,
+                "the artifact was generated for device `{}`, not `{}`",
+                expected, found
+            ),
+            ArtifactError::UnknownParam(name) => {
+                write!(fmt, "no argument is bound to parameter `{}`", name)
+            }
+            ArtifactError::Unsupported(device) => {
+                write!(fmt, "device `{}` does not support artifacts", device)
+            }
+        }
+    }
+}
+
+impl error::Error for ArtifactError {}
@@ -49,6 +49,25 @@ impl<'a> Cfg<'a> {
         }
     }
 
+    /// Returns the number of instructions in the `Cfg` once unrolled loops are expanded.
+    /// Vectorized instructions count as a single instruction.
+    pub fn num_unrolled_insts(&self) -> u64 {
+        match self {
+            Cfg::Root(body) | Cfg::Threads(_, _, body) => {
+                body.iter().map(Cfg::num_unrolled_insts).sum()
+            }
+            Cfg::Loop(dim, body) => {
+                let num_insts = body.iter().map(Cfg::num_unrolled_insts).sum::<u64>();
+                if dim.kind() == DimKind::UNROLL {
+                    num_insts * u64::from(unwrap!(dim.size().as_int()))
+                } else {
+                    num_insts
+                }
+            }
+            Cfg::Instruction(..) => 1,
+        }
+    }
+
     /// Iterates over the induction levels in the `Cfg`.
     pub fn induction_levels(&self) -> impl Iterator<Item = &InductionLevel<'a>> {
         match *self {
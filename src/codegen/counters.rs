@@ -0,0 +1,84 @@
+//! Cross-checks the counters of the search space against the generated code.
+//!
+//! The search space tracks quantities such as the amount of shared memory or the number of
+//! instructions with counters, which constrain the candidates and feed the performance
+//! model.  The counters are defined independently of the code generation, so the two can
+//! silently diverge.  Once a candidate is fully specified, each counter must be equal to
+//! the value materialized by `codegen::Function`.
+use std::fmt;
+
+use crate::codegen::Function;
+use crate::search_space::Domain;
+
+/// A counter of the search space whose value differs from the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterMismatch {
+    /// Name of the counter, as defined in the search space description.
+    pub counter: &'static str,
+    /// Value of the counter in the search space.
+    pub search_space: u64,
+    /// Value materialized by the generated code.
+    pub codegen: u64,
+}
+
+impl fmt::Display for CounterMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "counter `{}` is {} in the search space but {} in the generated code",
+            self.counter, self.search_space, self.codegen
+        )
+    }
+}
+
+impl<'a> Function<'a> {
+    /// Compares the counters of the search space with the generated code and returns the
+    /// counters that differ.  The search space must be fully specified.
+    pub fn check_counters(&self) -> Vec<CounterMismatch> {
+        let domain = self.space().domain();
+        let counters = vec![
+            (
+                "shared_mem_used",
+                u64::from(domain.get_shared_mem_used().min),
+                u64::from(self.shared_mem_size()),
+            ),
+            (
+                "num_threads",
+                u64::from(domain.get_num_threads().min),
+                u64::from(self.num_threads()),
+            ),
+            (
+                "num_unrolled_insts",
+                u64::from(domain.get_num_unrolled_insts().min),
+                self.cfg().num_unrolled_insts(),
+            ),
+        ];
+        counters
+            .into_iter()
+            .filter(|&(_, search_space, codegen)| search_space != codegen)
+            .map(|(counter, search_space, codegen)| CounterMismatch {
+                counter,
+                search_space,
+                codegen,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_mismatch() {
+        let mismatch = CounterMismatch {
+            counter: "num_threads",
+            search_space: 32,
+            codegen: 64,
+        };
+        assert_eq!(
+            mismatch.to_string(),
+            "counter `num_threads` is 32 in the search space but 64 in the generated code"
+        );
+    }
+}
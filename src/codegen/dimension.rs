@@ -152,6 +152,8 @@ pub struct InductionLevel<'a> {
     /// Warning: The dimension here corresponds to the IR dimension represented by this level; it
     /// does *not* have the same size as the increment!
     pub increment: Option<(ir::DimId, codegen::Size)>,
+    /// Indicates the increment must be subtracted from the variable instead of added.
+    pub reversed: bool,
     /// The base of the induction, i.e. the value the variable is initialized to before the first
     /// iteration.
     pub base: InductionVarValue<'a>,
@@ -286,13 +288,18 @@ pub fn register_induction_vars<'a>(
     for (id, ind_var) in space.ir_instance().induction_vars() {
         let (const_levels, mut_levels) = get_ind_var_levels(ind_var, space);
         let mut outer_value = InductionVarValue::new(id, ind_var.base(), space);
+        // Loop-invariant bases are folded into the first level computed at the beginning
+        // of the thread, which then takes a single `mad`, instead of being added to each
+        // level updated in loops.
+        let fold_base = is_loop_invariant(ind_var.base());
         let precomputed = const_levels
             .into_iter()
             .map(|(dim, increment)| {
-                let base = outer_value.apply_level(dim, false);
+                let base = outer_value.apply_level(dim, fold_base);
                 InductionLevel {
                     ind_var: id,
                     increment: Some((dim, increment)),
+                    reversed: ind_var.is_reversed(dim),
                     base,
                 }
             })
@@ -301,6 +308,7 @@ pub fn register_induction_vars<'a>(
             let level = InductionLevel {
                 ind_var: id,
                 increment: Some((dim, increment)),
+                reversed: ind_var.is_reversed(dim),
                 base: outer_value.apply_level(dim, true),
             };
             ind_levels_map.insert(dim, level);
@@ -315,6 +323,7 @@ pub fn register_induction_vars<'a>(
             let level = InductionLevel {
                 ind_var: id,
                 increment: None,
+                reversed: false,
                 base: outer_value,
             };
             let dim = unwrap!(precomputed.last().and_then(|p| p.increment.as_ref())).0;
@@ -336,6 +345,14 @@ pub fn register_induction_vars<'a>(
     (ind_vars, precomputed_levels)
 }
 
+/// Indicates if an operand can be computed before entering any dimension.
+fn is_loop_invariant(operand: &ir::Operand) -> bool {
+    match operand {
+        ir::Operand::Int(..) | ir::Operand::Param(..) => true,
+        _ => false,
+    }
+}
+
 type IndVarIncrement = (ir::DimId, codegen::Size);
 
 /// Retrieves the list of induction levels that can be computed at the beginning of the
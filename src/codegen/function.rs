@@ -5,6 +5,7 @@ use std::sync::Arc;
 use crate::codegen::{
     self, cfg, dimension, Cfg, Dimension, InductionLevel, InductionVar,
 };
+use crate::device;
 use crate::ir::{self, IrDisplay};
 use crate::search_space::{self, DimKind, Domain, MemSpace, SearchSpace};
 use fxhash::FxHashSet;
@@ -12,6 +13,7 @@ use utils::*;
 
 use itertools::Itertools;
 use log::{debug, trace};
+use serde::{Deserialize, Serialize};
 
 /// A function ready to execute on a device, derived from a constrained IR instance.
 pub struct Function<'a> {
@@ -112,6 +114,34 @@ impl<'a> Function<'a> {
         self.device_code_args.iter()
     }
 
+    /// Returns the scalar parameters whose value can change between launches of the
+    /// compiled function: they are passed to the device as is and do not appear in the size
+    /// of dimensions or memory blocks.
+    pub fn updatable_params(&self) -> impl Iterator<Item = &ir::Parameter> {
+        let host_sizes = self.device_code_args.iter().filter_map(|arg| match arg {
+            ParamVal::Size(size) | ParamVal::GlobalMem(_, size, _) => Some(size),
+            ParamVal::External(..) => None,
+        });
+        let size_params = self
+            .dimensions()
+            .map(|dim| dim.size())
+            .chain(host_sizes)
+            .flat_map(|size| size.dividend())
+            .map(|param| &param.name[..])
+            .collect::<FxHashSet<_>>();
+        self.device_code_args
+            .iter()
+            .filter_map(move |arg| match arg {
+                ParamVal::External(param, _)
+                    if param.elem_t.is_none()
+                        && !size_params.contains(&param.name[..]) =>
+                {
+                    Some(&**param)
+                }
+                _ => None,
+            })
+    }
+
     /// Returns the control flow graph.
     pub fn cfg(&self) -> &Cfg<'a> {
         &self.cfg
@@ -132,6 +162,31 @@ impl<'a> Function<'a> {
         self.mem_blocks.iter()
     }
 
+    /// Returns the size in bytes of the shared memory statically allocated by the function.
+    pub fn shared_mem_size(&self) -> u32 {
+        self.mem_blocks
+            .iter()
+            .filter(|block| block.alloc_scheme() == AllocationScheme::Shared)
+            .map(|block| unwrap!(block.alloc_size().as_int()))
+            .sum()
+    }
+
+    /// Returns the launch configuration of the function.  Sizes that depend on parameters
+    /// are evaluated in `context`.
+    pub fn launch_config(&self, context: &dyn device::Context) -> LaunchConfig {
+        let eval_sizes = |dims: &[Dimension]| -> Vec<u32> {
+            dims.iter()
+                .map(|dim| context.eval_size(dim.size()))
+                .collect()
+        };
+        LaunchConfig {
+            block_dims: eval_sizes(&self.block_dims),
+            thread_dims: eval_sizes(&self.thread_dims),
+            shared_mem: self.shared_mem_size(),
+            num_variables: self.variables.len(),
+        }
+    }
+
     /// Returns the underlying implementation space.
     // TODO(cleanup): prefer access to the space from individual wrappers on ir objects.
     pub fn space(&self) -> &SearchSpace {
@@ -170,6 +225,33 @@ impl<'a> fmt::Display for Function<'a> {
     }
 }
 
+/// Describes how a function is launched on the device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchConfig {
+    /// Sizes of the block dimensions, that form the grid of thread blocks.
+    pub block_dims: Vec<u32>,
+    /// Sizes of the thread dimensions, that form each thread block.
+    pub thread_dims: Vec<u32>,
+    /// Size in bytes of the shared memory statically allocated by each thread block.
+    pub shared_mem: u32,
+    /// Number of variables declared by the function.  This is a hint of the register
+    /// pressure, as the number of registers is only known after register allocation.
+    pub num_variables: usize,
+}
+
+impl fmt::Display for LaunchConfig {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "blocks [{}], threads [{}], {}B of shared memory, {} variables",
+            self.block_dims.iter().format(", "),
+            self.thread_dims.iter().format(", "),
+            self.shared_mem,
+            self.num_variables,
+        )
+    }
+}
+
 /// Represents the value of a parameter passed to the kernel by the host.
 #[derive(Debug)]
 pub enum ParamVal {
@@ -403,6 +485,7 @@ pub struct Instruction<'a> {
     instruction: &'a ir::Instruction,
     instantiation_dims: Vec<(ir::DimId, u32)>,
     mem_flag: Option<search_space::InstFlag>,
+    approx: bool,
     t: Option<ir::Type>,
 }
 
@@ -424,6 +507,9 @@ impl<'a> Instruction<'a> {
         let mem_flag = instruction
             .as_mem_inst()
             .map(|inst| space.domain().get_inst_flag(inst.id()));
+        // Approximations are only used when explicitly chosen.
+        let approx = space.domain().get_precision(instruction.id())
+            == search_space::Precision::APPROX;
         let t = instruction
             .t()
             .map(|t| unwrap!(space.ir_instance().device().lower_type(t, space)));
@@ -431,6 +517,7 @@ impl<'a> Instruction<'a> {
             instruction,
             instantiation_dims,
             mem_flag,
+            approx,
             t,
         }
     }
@@ -483,6 +570,11 @@ impl<'a> Instruction<'a> {
         self.mem_flag
     }
 
+    /// Indicates if the instruction uses an approximate version of its operator.
+    pub fn is_approx(&self) -> bool {
+        self.approx
+    }
+
     /// Indicates if the instruction has observable side effects.
     pub fn has_side_effects(&self) -> bool {
         self.instruction.has_side_effects()
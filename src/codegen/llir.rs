@@ -324,8 +324,10 @@ impl<'a> OpVec<'a> {
 pub enum UnOp {
     Move { t: ir::Type },
     Cast { src_t: ir::Type, dst_t: ir::Type },
-    // Natural exponential
-    Exp { t: ir::Type },
+    // Natural exponential, possibly approximated
+    Exp { t: ir::Type, approx: bool },
+    // Square root
+    Sqrt { t: ir::Type },
 }
 
 impl fmt::Display for UnOp {
@@ -333,7 +335,9 @@ impl fmt::Display for UnOp {
         match self {
             UnOp::Move { t } => write!(fmt, "move.{}", t),
             UnOp::Cast { src_t, dst_t } => write!(fmt, "cast.{}.{}", dst_t, src_t),
-            UnOp::Exp { t } => write!(fmt, "exp.{}", t),
+            UnOp::Exp { t, approx: false } => write!(fmt, "exp.{}", t),
+            UnOp::Exp { t, approx: true } => write!(fmt, "exp.approx.{}", t),
+            UnOp::Sqrt { t } => write!(fmt, "sqrt.{}", t),
         }
     }
 }
@@ -353,6 +357,10 @@ impl UnOp {
             },
             ir::UnaryOp::Exp(t) => UnOp::Exp {
                 t: Self::unify_type(Some(t), [arg_t])?,
+                approx: false,
+            },
+            ir::UnaryOp::Sqrt(t) => UnOp::Sqrt {
+                t: Self::unify_type(Some(t), [arg_t])?,
             },
         })
     }
@@ -360,14 +368,20 @@ impl UnOp {
     /// The expected argument type for this operator.
     pub fn arg_t(self) -> [ir::Type; 1] {
         match self {
-            UnOp::Move { t } | UnOp::Cast { src_t: t, .. } | UnOp::Exp { t } => [t],
+            UnOp::Move { t }
+            | UnOp::Cast { src_t: t, .. }
+            | UnOp::Exp { t, .. }
+            | UnOp::Sqrt { t } => [t],
         }
     }
 
     /// The resulting type when this operator is applied.
     pub fn ret_t(self) -> ir::Type {
         match self {
-            UnOp::Move { t } | UnOp::Cast { dst_t: t, .. } | UnOp::Exp { t } => t,
+            UnOp::Move { t }
+            | UnOp::Cast { dst_t: t, .. }
+            | UnOp::Exp { t, .. }
+            | UnOp::Sqrt { t } => t,
         }
     }
 
@@ -375,6 +389,14 @@ impl UnOp {
         unify_type(d.into_iter().chain(a.iter().copied()))
     }
 
+    /// Returns the approximate version of the operator, if it has one.
+    pub fn approx(self) -> Self {
+        match self {
+            UnOp::Exp { t, .. } => UnOp::Exp { t, approx: true },
+            op => op,
+        }
+    }
+
     /// Create a `move` operator based on its destination and argument types.
     ///
     /// # Errors
@@ -409,7 +431,19 @@ impl UnOp {
         d: Option<ir::Type>,
         a: [ir::Type; 1],
     ) -> Result<Self, InstructionError> {
-        Ok(Self::unify_type(d, a).map(|t| UnOp::Exp { t })?)
+        Ok(Self::unify_type(d, a).map(|t| UnOp::Exp { t, approx: false })?)
+    }
+
+    /// Create a `sqrt` operator based on its destination and argument types.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `d` and `a` are different types.
+    pub fn infer_sqrt(
+        d: Option<ir::Type>,
+        a: [ir::Type; 1],
+    ) -> Result<Self, InstructionError> {
+        Ok(Self::unify_type(d, a).map(|t| UnOp::Sqrt { t })?)
     }
 }
 
@@ -570,6 +604,17 @@ impl BinOp {
         })
     }
 
+    /// Returns the approximate version of the operator, if it has one.
+    pub fn approx(self) -> Self {
+        match self {
+            BinOp::FDiv { t, .. } => BinOp::FDiv {
+                t,
+                rounding: FpRounding::Approx,
+            },
+            op => op,
+        }
+    }
+
     /// Create a new multiplication operator based on its rounding mode, argument types, and return
     /// type.
     ///
@@ -704,8 +749,20 @@ impl BinOp {
 /// A typed ternary operator (e.g. fma)
 #[derive(Debug, Copy, Clone)]
 pub enum TernOp {
-    IMad { arg_t: ir::Type, spec: MulSpec },
-    FFma { t: ir::Type, rounding: FpRounding },
+    IMad {
+        arg_t: ir::Type,
+        spec: MulSpec,
+    },
+    FFma {
+        t: ir::Type,
+        rounding: FpRounding,
+    },
+    /// Warp-level matrix multiply-accumulate on tensor cores, from `t` to `acc_t`.
+    Mma {
+        shape: ir::MmaShape,
+        t: ir::Type,
+        acc_t: ir::Type,
+    },
 }
 
 impl fmt::Display for TernOp {
@@ -713,6 +770,9 @@ impl fmt::Display for TernOp {
         match self {
             TernOp::IMad { arg_t, spec } => write!(fmt, "mad.{}.{}", spec, arg_t),
             TernOp::FFma { t, rounding } => write!(fmt, "fma.{}.{}", rounding, t),
+            TernOp::Mma { shape, t, acc_t } => {
+                write!(fmt, "mma.{}.{}.{}", shape, acc_t, t)
+            }
         }
     }
 }
@@ -797,6 +857,7 @@ impl TernOp {
         match self {
             TernOp::IMad { arg_t, spec } => [arg_t, arg_t, spec.ret_t(arg_t)],
             TernOp::FFma { t, .. } => [t, t, t],
+            TernOp::Mma { t, acc_t, .. } => [t, t, acc_t],
         }
     }
 
@@ -805,6 +866,7 @@ impl TernOp {
         match self {
             TernOp::IMad { arg_t, spec } => spec.ret_t(arg_t),
             TernOp::FFma { t, .. } => t,
+            TernOp::Mma { acc_t, .. } => acc_t,
         }
     }
 }
@@ -929,6 +991,7 @@ impl<'a> Instruction<'a> {
         mov(d, a), UnOp::infer_move, unary;
         cast[dst_t: ir::Type](d, a), UnOp::infer_cast, unary;
         exp(d, a), UnOp::infer_exp, unary;
+        sqrt(d, a), UnOp::infer_sqrt, unary;
     }
 
     /// Create a new binary instruction.
@@ -1150,6 +1213,8 @@ pub enum FpRounding {
     NegativeInfinite,
     /// Mantissa LSB rounds towards positive infinity
     PositiveInfinite,
+    /// Fast approximation, without guarantees on the rounding of the result
+    Approx,
 }
 
 impl fmt::Display for FpRounding {
@@ -1159,6 +1224,7 @@ impl fmt::Display for FpRounding {
             FpRounding::Zero => "rz",
             FpRounding::NegativeInfinite => "rm",
             FpRounding::PositiveInfinite => "rp",
+            FpRounding::Approx => "approx",
         })
     }
 }
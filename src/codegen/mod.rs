@@ -1,18 +1,24 @@
 //! Helpers to generate code from an IR instance and fully specified decisions.
+mod artifact;
 mod cfg;
+mod counters;
 mod dimension;
 mod function;
 pub mod llir;
 mod name_map;
 mod printer;
+mod schedule;
 mod size;
 mod variable;
 
+pub use self::artifact::{Artifact, ArtifactError, ArtifactParam, ARTIFACT_VERSION};
 pub use self::cfg::Cfg;
+pub use self::counters::CounterMismatch;
 pub use self::dimension::{Dimension, InductionLevel, InductionVar};
 pub use self::function::*;
 pub use self::name_map::{Interner, NameGenerator, NameMap, Operand};
-pub use self::printer::{IdentDisplay, InstPrinter, Printer};
+pub use self::printer::{CodeBuffer, CodeSizeError, IdentDisplay, InstPrinter, Printer};
+pub use self::schedule::{Buffer, Loop, Schedule, ScheduledInst};
 pub use self::size::Size;
 pub use self::variable::Variable;
 
@@ -5,8 +5,10 @@ use itertools::Itertools;
 
 use crate::codegen::llir::IntLiteral as _;
 use crate::codegen::*;
+use crate::device::vectorization;
 use crate::ir::{self, op, Type};
 use crate::search_space::*;
+use utils::unwrap;
 
 pub trait IdentDisplay {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result;
@@ -75,6 +77,104 @@ pub trait InstPrinter {
     fn print_label(&mut self, label: llir::Label<'_>);
 
     fn print_inst(&mut self, inst: llir::PredicatedInstruction<'_>);
+
+    /// Indicates that the printer can't accept more code, in which case printing is aborted.
+    fn is_full(&self) -> bool {
+        false
+    }
+}
+
+/// Error returned when the generated code exceeds the size limit of a `CodeBuffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeSizeError {
+    pub size_limit: usize,
+}
+
+impl fmt::Display for CodeSizeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "generated code exceeds the size limit of {} bytes",
+            self.size_limit
+        )
+    }
+}
+
+impl std::error::Error for CodeSizeError {}
+
+/// A buffer for generated code, with an optional limit on its size.
+///
+/// Once the limit is exceeded, the content of the buffer is discarded and further writes are
+/// ignored, so that printing huge candidates (e.g. with a lot of unrolling) doesn't use an
+/// unbounded amount of memory.  The error is reported when retrieving the content.
+#[derive(Debug, Default)]
+pub struct CodeBuffer {
+    buffer: String,
+    size_limit: Option<usize>,
+    overflowed: bool,
+}
+
+impl CodeBuffer {
+    /// Creates a buffer which can hold up to `size_limit` bytes.
+    pub fn with_size_limit(size_limit: usize) -> Self {
+        CodeBuffer {
+            size_limit: Some(size_limit),
+            ..CodeBuffer::default()
+        }
+    }
+
+    /// Appends a string to the buffer.
+    pub fn push_str(&mut self, s: &str) {
+        if self.overflowed {
+            return;
+        }
+        match self.size_limit {
+            Some(limit) if self.buffer.len() + s.len() > limit => {
+                self.overflowed = true;
+                self.buffer = String::new();
+            }
+            _ => self.buffer.push_str(s),
+        }
+    }
+
+    /// Indicates if the size limit was exceeded.
+    pub fn is_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// Writes the content of the buffer to `out` and clears it, keeping the allocated
+    /// memory for the next function.
+    pub fn write_to(&mut self, out: &mut dyn fmt::Write) -> Result<(), CodeSizeError> {
+        self.check_overflow()?;
+        unwrap!(out.write_str(&self.buffer));
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Returns an error and resets the buffer if the size limit was exceeded.
+    fn check_overflow(&mut self) -> Result<(), CodeSizeError> {
+        if self.overflowed {
+            self.overflowed = false;
+            Err(CodeSizeError {
+                size_limit: unwrap!(self.size_limit),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the content of the buffer and clears it.
+    pub fn take(&mut self) -> Result<String, CodeSizeError> {
+        self.check_overflow()?;
+        Ok(std::mem::replace(&mut self.buffer, String::new()))
+    }
+}
+
+impl fmt::Write for CodeBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
 }
 
 /// Helper struct to provide useful methods wrapping an `InstPrinter` instance.
@@ -94,6 +194,17 @@ impl<'a> InstPrinterHelper<'a> {
             .print_inst(llir::Instruction::iadd(result, lhs, rhs).unwrap().into())
     }
 
+    /// Prints a scalar subtraction on integers.
+    fn print_sub_int(
+        &mut self,
+        result: llir::Register<'_>,
+        lhs: llir::Operand<'_>,
+        rhs: llir::Operand<'_>,
+    ) {
+        self.inst_printer
+            .print_inst(llir::Instruction::isub(result, lhs, rhs).unwrap().into())
+    }
+
     /// Prints a scalar less-than on integers.
     fn print_lt_int(
         &mut self,
@@ -241,7 +352,11 @@ impl<'a, 'b> Printer<'a, 'b> {
         for (level, ind_var) in ind_levels.iter().zip_eq(ind_var_vec) {
             if let Some((_, ref increment)) = level.increment {
                 let step = self.namer.name_size(increment, level.t());
-                self.helper.print_add_int(ind_var, ind_var.into(), step);
+                if level.reversed {
+                    self.helper.print_sub_int(ind_var, ind_var.into(), step);
+                } else {
+                    self.helper.print_add_int(ind_var, ind_var.into(), step);
+                }
             };
         }
         self.helper
@@ -288,11 +403,13 @@ impl<'a, 'b> Printer<'a, 'b> {
             if i > 0 {
                 for &(level, ind_var, ref incr, ref base) in &incr_levels {
                     if let Some(step) = incr.as_int() {
-                        let stepxi = i32::try_from(step * i)
-                            .unwrap()
-                            .typed_int_literal(level.t())
-                            .unwrap();
+                        let stepxi = i32::try_from(step * i).unwrap();
+                        let stepxi = if level.reversed { -stepxi } else { stepxi };
+                        let stepxi = stepxi.typed_int_literal(level.t()).unwrap();
                         self.helper.print_add_int(ind_var, stepxi, base.clone());
+                    } else if level.reversed {
+                        let step = self.namer.name_size(incr, level.t());
+                        self.helper.print_sub_int(ind_var, ind_var.into(), step);
                     } else {
                         let step = self.namer.name_size(incr, level.t());
                         self.helper.print_add_int(ind_var, step, ind_var.into());
@@ -322,7 +439,35 @@ impl<'a, 'b> Printer<'a, 'b> {
             .collect_vec();
         if let Some((dim, ref increment)) = level.increment {
             let index = self.namer.name_index(dim).into_operand();
-            let step = self.namer.name_size(increment, Type::I(32));
+            let step = match increment.as_int() {
+                // Compute `base - index * step` as `base + index * -step`.
+                Some(step) if level.reversed => (-unwrap!(i32::try_from(step)))
+                    .typed_int_literal(Type::I(32))
+                    .unwrap(),
+                _ => self.namer.name_size(increment, Type::I(32)),
+            };
+            if level.reversed && increment.as_int().is_none() {
+                // Compute `base - index * step`.
+                let offset = self.namer.gen_name(level.t());
+                self.helper.inst_printer.print_inst(
+                    llir::Instruction::imul(offset, index, step).unwrap().into(),
+                );
+                let base = match base_components[..] {
+                    [] => 0i32.typed_int_literal(ind_var.t()).unwrap(),
+                    [ref base] => base.clone(),
+                    [ref lhs, ref rhs] => {
+                        let base = self.namer.gen_name(level.t());
+                        self.helper.print_add_int(base, lhs.clone(), rhs.clone());
+                        base.into()
+                    }
+                    _ => unreachable!(
+                        "an induction variable value has at most two components: \
+                         its outer level and an operand"
+                    ),
+                };
+                self.helper.print_sub_int(ind_var, base, offset.into());
+                return;
+            }
             match base_components[..] {
                 [] => self.helper.inst_printer.print_inst(
                     llir::Instruction::imul(ind_var, index, step)
@@ -367,6 +512,9 @@ impl<'a, 'b> Printer<'a, 'b> {
 
     /// Prints a cfg.
     pub fn cfg(&mut self, fun: &Function, c: &'b Cfg<'b>) {
+        if self.helper.inst_printer.is_full() {
+            return;
+        }
         match c {
             Cfg::Root(cfgs) => self.cfg_vec(fun, cfgs),
             Cfg::Loop(dim, cfgs) => self.gen_loop(fun, dim, cfgs),
@@ -414,6 +562,15 @@ impl<'a, 'b> Printer<'a, 'b> {
                 .map(|d| d.size().as_int().unwrap())
                 .product(),
         ];
+        // The search space only allows vectorization factors accepted by the rules of the
+        // device, which only depend on the operator.
+        if cfg!(debug_assertions) {
+            let rules = fun.space().ir_instance().device().vectorization_rules();
+            let op = inst.operator();
+            if let Err(err) = vectorization::check(rules, op, vector_factors) {
+                panic!("the search space allowed an invalid vectorization: {}", err)
+            }
+        }
         let helper = &mut self.helper;
         match inst.operator() {
             &op::BinOp(op, ref lhs, ref rhs, round) => helper.inst_printer.print_inst(
@@ -424,6 +581,7 @@ impl<'a, 'b> Printer<'a, 'b> {
                         lower_type(lhs.t(), fun),
                         lower_type(rhs.t(), fun),
                     )
+                    .map(|op| if inst.is_approx() { op.approx() } else { op })
                     .unwrap(),
                     self.namer.vector_inst(vector_levels, inst.id()),
                     self.namer.vector_operand(vector_levels, lhs),
@@ -469,16 +627,35 @@ impl<'a, 'b> Printer<'a, 'b> {
                     .into(),
                 )
             }
+            &op::Mma(ref lhs, ref rhs, ref acc, shape) => {
+                helper.inst_printer.print_inst(
+                    llir::Instruction::ternary(
+                        llir::TernOp::Mma {
+                            shape,
+                            t: lower_type(lhs.t(), fun),
+                            acc_t: lower_type(acc.t(), fun),
+                        },
+                        self.namer.vector_inst(vector_levels, inst.id()),
+                        self.namer.vector_operand(vector_levels, lhs),
+                        self.namer.vector_operand(vector_levels, rhs),
+                        self.namer.vector_operand(vector_levels, acc),
+                    )
+                    .unwrap()
+                    .into(),
+                )
+            }
             &op::UnaryOp(operator, ref operand) => {
                 // Need to lower inner types
                 let operator = match operator {
                     ir::UnaryOp::Cast(t) => ir::UnaryOp::Cast(lower_type(t, fun)),
                     ir::UnaryOp::Exp(t) => ir::UnaryOp::Exp(lower_type(t, fun)),
+                    ir::UnaryOp::Sqrt(t) => ir::UnaryOp::Sqrt(lower_type(t, fun)),
                     _ => operator,
                 };
                 helper.inst_printer.print_inst(
                     llir::Instruction::unary(
                         llir::UnOp::from_ir(operator, lower_type(operand.t(), fun))
+                            .map(|op| if inst.is_approx() { op.approx() } else { op })
                             .unwrap(),
                         self.namer.vector_inst(vector_levels, inst.id()),
                         self.namer.vector_operand(vector_levels, operand),
@@ -530,3 +707,44 @@ impl<'a, 'b> Printer<'a, 'b> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::*;
+
+    /// Ensures the content of a `CodeBuffer` is discarded once it exceeds its size limit.
+    #[test]
+    fn code_buffer_size_limit() {
+        let mut buffer = CodeBuffer::with_size_limit(8);
+        write!(buffer, "abcd").unwrap();
+        assert_eq!(buffer.take(), Ok("abcd".to_string()));
+
+        write!(buffer, "abcd").unwrap();
+        write!(buffer, "efghi").unwrap();
+        assert!(buffer.is_overflowed());
+        write!(buffer, "j").unwrap();
+        assert_eq!(buffer.take(), Err(CodeSizeError { size_limit: 8 }));
+    }
+
+    /// Ensures `CodeBuffer::write_to` appends the buffered code to the output, and
+    /// reports the overflows without writing anything.
+    #[test]
+    fn code_buffer_write_to() {
+        let mut buffer = CodeBuffer::with_size_limit(8);
+        let mut out = "int x;".to_string();
+        write!(buffer, "x = 0;").unwrap();
+        assert_eq!(buffer.write_to(&mut out), Ok(()));
+        assert_eq!(out, "int x;x = 0;");
+
+        write!(buffer, "x = 12345;").unwrap();
+        assert_eq!(
+            buffer.write_to(&mut out),
+            Err(CodeSizeError { size_limit: 8 })
+        );
+        assert_eq!(out, "int x;x = 0;");
+        write!(buffer, "x = 1;").unwrap();
+        assert_eq!(buffer.take(), Ok("x = 1;".to_string()));
+    }
+}
@@ -0,0 +1,134 @@
+//! Export of the decisions of a fully specified candidate in a neutral format.
+//!
+//! A `Schedule` describes the loops of a kernel, how they are nested and mapped to the
+//! hardware, and where temporary arrays are stored, without referring to the internal
+//! representation of the search space.  It is meant to compare implementations with the ones
+//! of other compilers, such as TVM schedules, and can be serialized to JSON.
+use crate::codegen;
+use crate::ir::Statement;
+use crate::search_space::{Domain, Order, SearchSpace};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// The decisions of a fully specified candidate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Name of the kernel.
+    pub name: String,
+    /// Loops of the kernel, one per dimension of the IR.
+    pub loops: Vec<Loop>,
+    /// Instructions of the kernel.
+    pub instructions: Vec<ScheduledInst>,
+    /// Temporary arrays allocated by the kernel.
+    pub buffers: Vec<Buffer>,
+}
+
+/// A loop of the kernel.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Loop {
+    /// ID of the dimension the loop implements.
+    pub id: u32,
+    /// ID of the logical dimension the loop is a tile of, if any.
+    pub logical_dim: Option<u32>,
+    /// Number of iterations of the loop, possibly depending on the kernel parameters.
+    pub size: String,
+    /// How the loop is implemented: `block`, `thread`, `loop`, `unroll`, `inner_vector` or
+    /// `outer_vector`.
+    pub kind: String,
+    /// ID of the loop in which this loop is directly nested, if any.
+    pub parent: Option<u32>,
+    /// IDs of the loops fused with this one.
+    pub fused_with: Vec<u32>,
+}
+
+/// An instruction of the kernel.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledInst {
+    /// ID of the instruction.
+    pub id: u32,
+    /// The operation computed by the instruction.
+    pub operator: String,
+    /// IDs of the loops the instruction is nested in, from the outermost to the innermost.
+    pub loops: Vec<u32>,
+    /// Cache directive of memory accesses.
+    pub cache: Option<String>,
+}
+
+/// A temporary array.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Buffer {
+    /// ID of the memory block.
+    pub id: u32,
+    /// Memory space in which the array is allocated.
+    pub space: String,
+}
+
+impl Schedule {
+    /// Extracts the schedule of a fully specified candidate.
+    pub fn from_space(space: &SearchSpace) -> Self {
+        let fun = space.ir_instance();
+        let domain = space.domain();
+        let order = |lhs: &dyn Statement<()>, rhs: &dyn Statement<()>| {
+            domain.get_order(lhs.stmt_id(), rhs.stmt_id())
+        };
+        // Loops outer to each statement, from the outermost to the innermost.  A loop outer
+        // to another has strictly less outer loops.
+        let outer_loops = |stmt: &dyn Statement<()>| {
+            fun.dims()
+                .filter(|dim| dim.stmt_id() != stmt.stmt_id())
+                .filter(|dim| order(*dim, stmt) == Order::OUTER)
+                .map(|dim| {
+                    let depth = fun
+                        .dims()
+                        .filter(|other| other.id() != dim.id())
+                        .filter(|other| order(*other, *dim) == Order::OUTER)
+                        .count();
+                    (depth, dim.id().0)
+                })
+                .sorted()
+                .map(|(_, id)| id)
+                .collect_vec()
+        };
+        let loops = fun
+            .dims()
+            .map(|dim| Loop {
+                id: dim.id().0,
+                logical_dim: dim.logical_dim().map(|id| id.0),
+                size: codegen::Size::from_ir(dim.size(), space).to_string(),
+                kind: format!("{:?}", domain.get_dim_kind(dim.id())).to_lowercase(),
+                parent: outer_loops(dim).last().cloned(),
+                fused_with: fun
+                    .dims()
+                    .filter(|other| other.id() != dim.id())
+                    .filter(|other| order(dim, *other) == Order::MERGED)
+                    .map(|other| other.id().0)
+                    .collect(),
+            })
+            .collect();
+        let instructions = fun
+            .insts()
+            .map(|inst| ScheduledInst {
+                id: inst.id().0,
+                operator: inst.operator().to_string(),
+                loops: outer_loops(inst),
+                cache: inst.as_mem_inst().map(|inst| {
+                    format!("{:?}", domain.get_inst_flag(inst.id())).to_lowercase()
+                }),
+            })
+            .collect();
+        let buffers = fun
+            .mem_blocks()
+            .map(|block| Buffer {
+                id: block.mem_id().0,
+                space: format!("{:?}", domain.get_mem_space(block.mem_id()))
+                    .to_lowercase(),
+            })
+            .collect();
+        Schedule {
+            name: fun.name().to_string(),
+            loops,
+            instructions,
+            buffers,
+        }
+    }
+}
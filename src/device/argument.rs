@@ -129,6 +129,20 @@ pub trait ArrayArgument: Send + Sync {
 
     /// Copies an array to the device from a slice of bytes.
     fn write_i8(&self, bytes: &[i8]);
+
+    /// Sets all the bytes of the array to zero.  Devices should override this to avoid
+    /// copying the array to the host and back.
+    fn zero(&self) {
+        let len = self.read_i8().len();
+        self.write_i8(&vec![0; len]);
+    }
+
+    /// Returns the address of the array on the device, if the array can be passed directly
+    /// to kernels.  This allows binding arrays allocated outside of a context to kernel
+    /// parameters.
+    fn raw_device_ptr(&self) -> Option<u64> {
+        None
+    }
 }
 
 pub trait ArrayArgumentExt: ArrayArgument {
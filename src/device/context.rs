@@ -6,7 +6,11 @@ use crate::ir;
 use itertools::{process_results, Itertools};
 use log::info;
 use num;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 use std::{cmp, fmt};
 use utils::{cmp_f64, unwrap};
 
@@ -22,6 +26,88 @@ pub trait KernelEvaluator: std::fmt::Display {
     /// Repeated runs should return an identical value and hence calls to `evaluate` should not
     /// have side-effects visible from the kernel.
     fn evaluate(&mut self) -> Option<f64>;
+
+    /// Evaluates the kernel runtime, in nanoseconds, and the energy consumed by an execution,
+    /// in nanojoules.  Returns `None` if the device does not support energy measurement.
+    fn evaluate_energy(&mut self) -> Option<(f64, f64)> {
+        None
+    }
+
+    /// Changes the value of a scalar parameter for the next evaluations, without compiling
+    /// the kernel again or going through the `ArgMap` of the context.  Only the parameters
+    /// returned by `codegen::Function::updatable_params` can be changed.
+    fn set_scalar(
+        &mut self,
+        name: &str,
+        _value: Box<dyn ScalarArgument>,
+    ) -> Result<(), ParamUpdateError> {
+        Err(ParamUpdateError::Unsupported(name.to_string()))
+    }
+}
+
+/// Error returned when changing a parameter of a compiled kernel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamUpdateError {
+    /// The kernel has no updatable parameter with this name.
+    UnknownParam(String),
+    /// The value does not have the type of the parameter.
+    WrongType {
+        name: String,
+        expected: ir::Type,
+        found: ir::Type,
+    },
+    /// The evaluator does not support changing parameters.
+    Unsupported(String),
+}
+
+impl fmt::Display for ParamUpdateError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamUpdateError::UnknownParam(name) => {
+                write!(
+                    fmt,
+                    "`{}` is not an updatable parameter of the kernel",
+                    name
+                )
+            }
+            ParamUpdateError::WrongType {
+                name,
+                expected,
+                found,
+            } => write!(
+                fmt,
+                "parameter `{}` has type {}, but the value has type {}",
+                name, expected, found
+            ),
+            ParamUpdateError::Unsupported(name) => write!(
+                fmt,
+                "cannot change parameter `{}`: the evaluator does not support it",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParamUpdateError {}
+
+/// Checks that `value` can be assigned to the updatable parameter `name` of `params`.
+pub fn check_param_update<'a>(
+    mut params: impl Iterator<Item = &'a ir::Parameter>,
+    name: &str,
+    value: &dyn ScalarArgument,
+) -> Result<(), ParamUpdateError> {
+    let param = params
+        .find(|param| param.name == name)
+        .ok_or_else(|| ParamUpdateError::UnknownParam(name.to_string()))?;
+    if param.t == value.get_type() {
+        Ok(())
+    } else {
+        Err(ParamUpdateError::WrongType {
+            name: name.to_string(),
+            expected: param.t,
+            found: value.get_type(),
+        })
+    }
 }
 
 pub trait AsyncCallbackFn {
@@ -52,13 +138,54 @@ pub trait Context: Sync {
     /// Compiles and benchmarks a functions. As opposed to `Self::evaluate`, the measured
     /// time contains potential startup times.
     fn benchmark(&self, space: &Function, num_samples: usize) -> Vec<f64>;
+    /// Compiles a function into an evaluator that can run it several times, possibly with
+    /// different values of its scalar parameters.
+    ///
+    /// The default implementation compiles the function again at each evaluation and does
+    /// not support changing parameters.
+    fn compile_kernel<'b>(
+        &'b self,
+        function: &'b Function<'b>,
+    ) -> Box<dyn KernelEvaluator + 'b> {
+        Box::new(RecompilingEvaluator {
+            context: self,
+            function,
+        })
+    }
+    /// Exports the code of a function as a self-contained artifact, that can be shipped
+    /// and loaded with `load_artifact` without building the function again.  Returns
+    /// `None` if the backend does not support artifacts.
+    fn export_artifact(&self, _function: &Function) -> Option<codegen::Artifact> {
+        None
+    }
+    /// Loads an artifact exported for the device of the context.  The parameters of the
+    /// artifact are bound to the arguments of the context.
+    fn load_artifact<'b>(
+        &'b self,
+        _artifact: &codegen::Artifact,
+    ) -> Result<Box<dyn KernelEvaluator + 'b>, codegen::ArtifactError> {
+        let device = self.device().name().to_string();
+        Err(codegen::ArtifactError::Unsupported(device))
+    }
     /// Calls the `inner` closure in parallel, and gives it a pointer to an `AsyncEvaluator`
     /// to evaluate candidates in the context. `skip_bad_bounds` indicates than candidates
     /// whose bound is aboive the best candidate should be skiped.
+    ///
+    /// Once `cancel` is cancelled, implementations must stop compiling and evaluating the
+    /// candidates they receive, and call the associated callbacks with a
+    /// `CancelledKernel` instead.  `CancellationToken::drain` implements this for
+    /// evaluation queues.  The `inner` closures are expected to check the token and
+    /// return as soon as possible.
+    ///
+    /// `num_compilers` is the number of threads compiling candidates ahead of their
+    /// evaluation.  If zero, candidates are compiled by the threads calling `inner`.
+    /// Backends that do not support compilation threads ignore it.
     fn async_eval<'b>(
         &self,
         num_workers: usize,
+        num_compilers: usize,
         mode: EvalMode,
+        cancel: &CancellationToken,
         inner: &(dyn Fn(&mut dyn AsyncEvaluator<'b>) + Sync),
     );
 
@@ -85,6 +212,45 @@ pub trait Context: Sync {
     fn stabilizer(&self) -> Stabilizer {
         Stabilizer::default()
     }
+
+    /// Reads the temperature and the clocks of the device.  Returns `None` if the device
+    /// does not expose such sensors, which is the default.
+    fn thermal_state(&self) -> Option<ThermalState> {
+        None
+    }
+}
+
+/// Temperature and clocks of a device at a given time.  Devices throttle their clocks when
+/// they heat up, so kernels running for a long time may be slower than when they are
+/// evaluated during the search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThermalState {
+    /// Temperature of the device, in degrees Celsius.
+    pub temperature: u32,
+    /// Clock of the multiprocessors, in MHz.
+    pub sm_clock: u32,
+    /// Clock of the memory, in MHz.
+    pub mem_clock: u32,
+}
+
+/// Evaluates a function by compiling it with `Context::evaluate` each time.
+struct RecompilingEvaluator<'b, C: ?Sized> {
+    context: &'b C,
+    function: &'b Function<'b>,
+}
+
+impl<'b, C: Context + ?Sized> fmt::Display for RecompilingEvaluator<'b, C> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.function)
+    }
+}
+
+impl<'b, C: Context + ?Sized> KernelEvaluator for RecompilingEvaluator<'b, C> {
+    fn evaluate(&mut self) -> Option<f64> {
+        self.context
+            .evaluate(self.function, EvalMode::TestEval)
+            .ok()
+    }
 }
 
 /// Binds the argument names to their values.
@@ -101,6 +267,23 @@ pub trait ArgMap<'a>: Context + 'a {
         t: ir::Type,
         len: usize,
     ) -> Arc<dyn ArrayArgument + 'a>;
+
+    /// Binds a parameter to an existing array instead of allocating a new one.  This allows
+    /// running kernels directly on buffers owned by the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the context cannot pass the array to its kernels.
+    fn bind_existing_array(
+        &mut self,
+        param: &ir::Parameter,
+        _array: Arc<dyn ArrayArgument + 'a>,
+    ) {
+        panic!(
+            "cannot bind parameter `{}` to an existing array on this device",
+            param.name
+        )
+    }
 }
 
 pub trait ArgMapExt<'a>: ArgMap<'a> {
@@ -139,6 +322,74 @@ impl<'b, 'c> dyn AsyncEvaluator<'b> + 'c {
     }
 }
 
+/// A token used to request the cancellation of an asynchronous evaluation.
+///
+/// Tokens are cheap to clone, and all clones share the same state: cancelling one of them
+/// cancels all of them.  Cancellation can't be undone.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token which is not cancelled.
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Requests cancellation of all the evaluations using this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Indicates whether cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Receives the candidates of an evaluation queue until it is closed, and passes them
+    /// to `evaluate` along with their payload and callback.  Once cancellation is
+    /// requested, candidates are reported to their callback as cancelled instead.
+    ///
+    /// The queue is drained even after cancellation, so that threads blocked on a full
+    /// queue can notice the cancellation.
+    pub fn drain<'b, T>(
+        &self,
+        queue: impl IntoIterator<Item = (Candidate, T, AsyncCallback<'b>)>,
+        mut evaluate: impl FnMut(Candidate, T, AsyncCallback<'b>),
+    ) {
+        for (candidate, payload, callback) in queue {
+            if self.is_cancelled() {
+                CancelledKernel::report(candidate, callback);
+            } else {
+                evaluate(candidate, payload, callback);
+            }
+        }
+    }
+}
+
+/// Stands for a candidate whose evaluation was cancelled.  Its evaluation always fails.
+pub struct CancelledKernel;
+
+impl CancelledKernel {
+    /// Calls the callback of a candidate that is not evaluated because of a cancellation.
+    pub fn report(candidate: Candidate, callback: AsyncCallback<'_>) {
+        callback.call(candidate, &mut CancelledKernel)
+    }
+}
+
+impl fmt::Display for CancelledKernel {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "<cancelled>")
+    }
+}
+
+impl KernelEvaluator for CancelledKernel {
+    fn evaluate(&mut self) -> Option<f64> {
+        None
+    }
+}
+
 /// Indicates how evaluation should be performed.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum EvalMode {
@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 use std::io::Write;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use fxhash::FxHashMap;
+use utils::unwrap;
 
 use crate::codegen;
 use crate::explorer::Candidate;
@@ -10,9 +11,10 @@ use crate::ir;
 use crate::model::{self, HwPressure};
 use crate::search_space::{DimKind, InstFlag, MemSpace, SearchSpace};
 
+use super::vectorization::VectorRule;
 use super::{
-    ArgMap, ArrayArgument, AsyncCallback, AsyncEvaluator, EvalMode, KernelEvaluator,
-    ScalarArgument,
+    ArgMap, ArrayArgument, AsyncCallback, AsyncEvaluator, CancellationToken,
+    CancelledKernel, EvalMode, KernelEvaluator, ScalarArgument,
 };
 
 /// A fake device.
@@ -59,10 +61,11 @@ impl super::Device for Device {
         }
     }
 
-    fn max_vectorization(&self, _: &ir::Operator) -> [u32; 2] {
+    fn vectorization_rules(&self) -> &[VectorRule] {
         // No need to discriminate on the operator since this is already handled by
         // `can_vectorize`.
-        [4, 8]
+        const RULES: &[VectorRule] = &[VectorRule::any([4, 8])];
+        RULES
     }
 
     fn has_vector_registers(&self) -> bool {
@@ -157,6 +160,14 @@ impl super::Device for Device {
         _: &mut HwPressure,
     ) {
     }
+
+    fn kernel_launch_overhead(&self) -> f64 {
+        10.0
+    }
+
+    fn global_mem_bandwidth(&self) -> f64 {
+        1.0
+    }
 }
 
 /// A fake context to use when we don't actually care about the
@@ -197,12 +208,16 @@ impl<D: super::Device> super::Context for Context<D> {
     fn async_eval<'c>(
         &self,
         _: usize,
+        _: usize,
         _: EvalMode,
+        cancel: &CancellationToken,
         inner: &(dyn Fn(&mut dyn AsyncEvaluator<'c>) + Sync),
     ) {
-        struct FakeEvaluator;
+        struct FakeEvaluator<'a> {
+            cancel: &'a CancellationToken,
+        }
 
-        impl<'b> AsyncEvaluator<'b> for FakeEvaluator {
+        impl<'a, 'b> AsyncEvaluator<'b> for FakeEvaluator<'a> {
             fn add_dyn_kernel(
                 &mut self,
                 candidate: Candidate,
@@ -210,6 +225,10 @@ impl<D: super::Device> super::Context for Context<D> {
             ) {
                 use std::fmt;
 
+                if self.cancel.is_cancelled() {
+                    return CancelledKernel::report(candidate, callback);
+                }
+
                 struct FakeCode;
 
                 impl fmt::Display for FakeCode {
@@ -229,7 +248,7 @@ impl<D: super::Device> super::Context for Context<D> {
             }
         }
 
-        inner(&mut FakeEvaluator);
+        inner(&mut FakeEvaluator { cancel });
     }
 }
 
@@ -247,20 +266,42 @@ impl<'a, D: super::Device + 'a> ArgMap<'a> for Context<D> {
     fn bind_erased_array(
         &mut self,
         _: &ir::Parameter,
-        _: ir::Type,
-        _: usize,
+        t: ir::Type,
+        len: usize,
     ) -> Arc<dyn ArrayArgument + 'a> {
-        Arc::new(FakeArray)
+        let size = len * unwrap!(t.len_byte()) as usize;
+        Arc::new(FakeArray::new(size))
+    }
+
+    fn bind_existing_array(
+        &mut self,
+        _param: &ir::Parameter,
+        _array: Arc<dyn ArrayArgument + 'a>,
+    ) {
     }
 }
 
-/// A fake array implementation which doesn't read or write anything.
-pub struct FakeArray;
+/// A fake array implementation which keeps its data on the host.  Kernels never write
+/// to it, but it holds the inputs so that expected outputs can be computed.
+pub struct FakeArray {
+    data: RwLock<Vec<i8>>,
+}
+
+impl FakeArray {
+    /// Creates an array of `size` bytes, initialized to zero.
+    pub fn new(size: usize) -> Self {
+        FakeArray {
+            data: RwLock::new(vec![0; size]),
+        }
+    }
+}
 
 impl ArrayArgument for FakeArray {
     fn read_i8(&self) -> Vec<i8> {
-        Vec::new()
+        unwrap!(self.data.read()).clone()
     }
 
-    fn write_i8(&self, _: &[i8]) {}
+    fn write_i8(&self, bytes: &[i8]) {
+        unwrap!(self.data.write()).copy_from_slice(bytes);
+    }
 }
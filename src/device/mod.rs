@@ -1,13 +1,17 @@
 //! Code generation and candidate evaluation for specific targets.
 pub mod fake;
+pub mod plugin;
+pub mod recorder;
+pub mod vectorization;
 
 mod argument;
 mod context;
 
 pub use self::argument::{ArrayArgument, ArrayArgumentExt, ScalarArgument};
 pub use self::context::{
-    ArgMap, ArgMapExt, AsyncCallback, AsyncEvaluator, Context, EvalMode, KernelEvaluator,
-    Stabilizer,
+    check_param_update, ArgMap, ArgMapExt, AsyncCallback, AsyncEvaluator,
+    CancellationToken, CancelledKernel, Context, EvalMode, KernelEvaluator,
+    ParamUpdateError, Stabilizer, ThermalState,
 };
 
 use crate::codegen::Function;
@@ -32,13 +36,25 @@ pub trait Device: Send + Sync + 'static {
     fn max_threads(&self) -> u32;
     /// Returns the maximal unrolling factor.
     fn max_unrolling(&self) -> u32;
+    /// Returns the maximal number of instructions in the generated code, once unrolled
+    /// loops are expanded.
+    fn max_unrolled_insts(&self) -> u32 {
+        1 << 16
+    }
     /// Indicates if the device uses vector registers or has imlicit gathers and scatters
     /// in vector instructions.
     fn has_vector_registers(&self) -> bool;
     /// Indicates if the operator can be vectorized along the dimension.
     fn can_vectorize(&self, dim: &ir::Dimension, op: &ir::Operator) -> bool;
-    /// Indicates the maximal vectorization factor for the given operator.
-    fn max_vectorization(&self, op: &ir::Operator) -> [u32; 2];
+    /// Returns the rules that give the maximal vectorization factors of each kind of
+    /// operator.  See `vectorization::max_factors`.
+    fn vectorization_rules(&self) -> &[vectorization::VectorRule];
+    /// Indicates the maximal outer and inner vectorization factors for the given
+    /// operator.  The search space relies on this bound, and the code generator checks
+    /// the factors it prints against the same rules.
+    fn max_vectorization(&self, op: &ir::Operator) -> [u32; 2] {
+        vectorization::max_factors(self.vectorization_rules(), op)
+    }
     /// Returns the amount of shared memory available for each thread block.
     fn shared_mem(&self) -> u32;
     /// Indicates the type of the pointer for the given memory space.
@@ -90,6 +106,36 @@ pub trait Device: Send + Sync + 'static {
     /// information is not yet specified.
     fn lower_type(&self, t: ir::Type, space: &SearchSpace) -> Option<ir::Type>;
 
+    /// Returns the minimal delay, in nanoseconds, between the end of a kernel and the
+    /// start of the next one when they are launched one after the other.
+    fn kernel_launch_overhead(&self) -> f64 {
+        0.
+    }
+
+    /// Returns the number of threads that execute in lockstep: a warp on NVIDIA GPUs or a
+    /// wavefront on AMD GPUs. Devices whose threads execute independently return `1`. The
+    /// width is never `0`.
+    fn simt_width(&self) -> u32 {
+        1
+    }
+
+    /// Returns the bandwidth of the global memory, in bytes per nanosecond.
+    fn global_mem_bandwidth(&self) -> f64 {
+        std::f64::INFINITY
+    }
+
+    /// Indicates if the operator has a faster, approximate version on the device.
+    fn supports_approx(&self, _op: &ir::Operator) -> bool {
+        false
+    }
+
+    /// Indicates if the device has tensor cores able to perform warp-level matrix
+    /// multiply-accumulates of the given shape, on inputs of type `t` and accumulators of
+    /// type `acc_t`.
+    fn supports_mma(&self, _shape: ir::MmaShape, _t: ir::Type, _acc_t: ir::Type) -> bool {
+        false
+    }
+
     /// Builds and outputs a constrained IR instance.
     fn gen_code(&self, implementation: &SearchSpace, out: &mut dyn Write) {
         let code = Function::build(implementation);
@@ -0,0 +1,520 @@
+//! ABI for backends distributed as shared libraries.
+//!
+//! A backend plugin is a shared library exporting a static `PluginVTable` under the
+//! `ENTRY_SYMBOL` name, usually through the `declare_backend_plugin!` macro.  The contexts it
+//! creates are only known to the host through an opaque `ContextHandle`, which is passed to
+//! the `extern "C"` functions of the vtable.  Rust trait objects never cross the boundary by
+//! value: the arguments of these functions are either primitive types or thin pointers to
+//! Telamon data structures.  The layout of these structures depends on the compiler and on
+//! the version of Telamon, so both are checked when loading the plugin.
+//!
+//! Panics in the plugin are caught at the boundary and reported as failures.
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+
+use crate::codegen::{Function, Size};
+use crate::device::{
+    ArgMap, ArrayArgument, AsyncEvaluator, CancellationToken, Device, EvalMode,
+    ScalarArgument, Stabilizer, ThermalState,
+};
+use crate::ir;
+
+/// Version of the plugin ABI.  Must be bumped whenever `PluginVTable` changes.
+pub const ABI_VERSION: u32 = 2;
+
+/// Name of the symbol plugins export their `PluginVTable` under.
+pub const ENTRY_SYMBOL: &[u8] = b"telamon_backend_plugin\0";
+
+/// Version of Telamon the plugin is built against, as a NUL-terminated string.
+pub const TELAMON_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Version of the compiler Telamon is built with, as a NUL-terminated string.
+pub const RUSTC_VERSION: &str = concat!(env!("TELAMON_RUSTC_VERSION"), "\0");
+
+/// Opaque handle on a context created by a plugin.  Only the plugin knows its layout.
+#[repr(C)]
+pub struct ContextHandle {
+    _private: [u8; 0],
+}
+
+/// A context exported by a backend plugin.
+pub trait PluginContext: ArgMap<'static> + Send + Sized {
+    /// Creates a new context.
+    fn new() -> Result<Self, String>;
+
+    /// Evaluates the reference implementation of the kernel named `kernel`, with the
+    /// parameters `params` serialized in JSON.  Returns its execution time in nanoseconds.
+    fn eval_reference(&self, kernel: &str, params: &str) -> Result<f64, String>;
+}
+
+/// The entry point of a backend plugin.
+///
+/// Functions returning a `bool` return `false` if the plugin panicked, in which case their
+/// output is left uninitialized.  Error messages are allocated by the plugin and must be
+/// freed with `free_error`.
+#[repr(C)]
+pub struct PluginVTable {
+    /// Version of the ABI the plugin implements.  Must be `ABI_VERSION`.
+    pub abi_version: u32,
+    /// Version of Telamon the plugin was built against.  Must be `TELAMON_VERSION`.
+    pub telamon_version: *const c_char,
+    /// Version of the compiler the plugin was built with.  Must be `RUSTC_VERSION`.
+    pub rustc_version: *const c_char,
+    /// Name of the backend, as a NUL-terminated string.
+    pub name: *const c_char,
+    /// Creates a new evaluation context.  Returns a null pointer and sets `error` on failure.
+    pub new_context: unsafe extern "C" fn(error: *mut *mut c_char) -> *mut ContextHandle,
+    /// Frees a context returned by `new_context`.
+    pub free_context: unsafe extern "C" fn(context: *mut ContextHandle),
+    /// Frees an error message returned by the plugin.
+    pub free_error: unsafe extern "C" fn(error: *mut c_char),
+    /// Writes the device of the context in `out`.
+    pub device: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        out: *mut Arc<dyn Device>,
+    ) -> bool,
+    /// Evaluates a function and writes its execution time in `out`.  Writes `None` if the
+    /// evaluation fails.
+    pub evaluate: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        function: *const Function,
+        mode: *const EvalMode,
+        out: *mut Option<f64>,
+    ) -> bool,
+    /// Benchmarks a function and writes the execution times in `out`.
+    pub benchmark: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        function: *const Function,
+        num_samples: usize,
+        out: *mut Vec<f64>,
+    ) -> bool,
+    /// Calls `inner` in parallel with evaluators of the context.
+    pub async_eval: for<'b, 'c> unsafe extern "C" fn(
+        context: *const ContextHandle,
+        num_workers: usize,
+        num_compilers: usize,
+        mode: *const EvalMode,
+        cancel: *const CancellationToken,
+        inner: *const &'c (dyn Fn(&mut dyn AsyncEvaluator<'b>) + Sync),
+    ) -> bool,
+    /// Writes the value of the parameter whose UTF-8 name is `name[..name_len]` in `out`,
+    /// interpreted as a size.
+    pub param_as_size: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        name: *const u8,
+        name_len: usize,
+        out: *mut Option<u32>,
+    ) -> bool,
+    /// Evaluates a size and writes it in `out`.
+    pub eval_size: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        size: *const Size,
+        out: *mut u32,
+    ) -> bool,
+    /// Writes the stabilizer of the context in `out`.
+    pub stabilizer:
+        unsafe extern "C" fn(context: *const ContextHandle, out: *mut Stabilizer) -> bool,
+    /// Writes the temperature and clocks of the device in `out`.
+    pub thermal_state: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        out: *mut Option<ThermalState>,
+    ) -> bool,
+    /// Binds a scalar parameter.  Takes ownership of `*value`.
+    pub bind_scalar: unsafe extern "C" fn(
+        context: *mut ContextHandle,
+        param: *const ir::Parameter,
+        value: *mut Box<dyn ScalarArgument>,
+    ) -> bool,
+    /// Allocates and binds an array parameter, and writes the array in `out`.
+    pub bind_array: unsafe extern "C" fn(
+        context: *mut ContextHandle,
+        param: *const ir::Parameter,
+        t: *const ir::Type,
+        len: usize,
+        out: *mut Arc<dyn ArrayArgument>,
+    ) -> bool,
+    /// Evaluates the reference implementation of a kernel, given its name and its
+    /// parameters serialized in JSON, and writes its execution time in `out`.  Returns
+    /// `false` and sets `error` on failure.
+    pub eval_reference: unsafe extern "C" fn(
+        context: *const ContextHandle,
+        kernel: *const c_char,
+        params: *const c_char,
+        out: *mut f64,
+        error: *mut *mut c_char,
+    ) -> bool,
+}
+
+// The vtable only contains pointers to static data and functions.
+unsafe impl Sync for PluginVTable {}
+
+impl PluginVTable {
+    /// Returns the name of the backend.
+    pub fn name(&self) -> &str {
+        unsafe { CStr::from_ptr(self.name) }
+            .to_str()
+            .unwrap_or("<invalid name>")
+    }
+
+    /// Checks that the plugin was built for the same ABI, Telamon version and compiler as
+    /// the host.
+    pub fn check_compatibility(&self) -> Result<(), String> {
+        if self.abi_version != ABI_VERSION {
+            return Err(format!(
+                "plugin `{}` uses ABI version {} but version {} is required",
+                self.name(),
+                self.abi_version,
+                ABI_VERSION
+            ));
+        }
+        let version = unsafe { CStr::from_ptr(self.telamon_version) };
+        if version.to_bytes_with_nul() != TELAMON_VERSION.as_bytes() {
+            return Err(format!(
+                "plugin `{}` was built against telamon {} but the host uses telamon {}",
+                self.name(),
+                version.to_string_lossy(),
+                &TELAMON_VERSION[..TELAMON_VERSION.len() - 1]
+            ));
+        }
+        let rustc = unsafe { CStr::from_ptr(self.rustc_version) };
+        if rustc.to_bytes_with_nul() != RUSTC_VERSION.as_bytes() {
+            return Err(format!(
+                "plugin `{}` was built with {} but the host was built with {}",
+                self.name(),
+                rustc.to_string_lossy(),
+                &RUSTC_VERSION[..RUSTC_VERSION.len() - 1]
+            ));
+        }
+        Ok(())
+    }
+
+    /// Takes ownership of an error message returned by the plugin.
+    ///
+    /// # Safety
+    ///
+    /// `error` must be null or an error message returned by a function of this vtable, that
+    /// was not freed yet.
+    pub unsafe fn take_error(&self, error: *mut c_char) -> String {
+        if error.is_null() {
+            return "unknown error".to_string();
+        }
+        let message = CStr::from_ptr(error).to_string_lossy().into_owned();
+        (self.free_error)(error);
+        message
+    }
+}
+
+/// Runs `f`, and returns `false` if it panics.
+fn guard<F: FnOnce()>(f: F) -> bool {
+    panic::catch_unwind(AssertUnwindSafe(f)).is_ok()
+}
+
+/// Converts an error message into a string that can be passed to the host.
+fn export_error(error: String) -> *mut c_char {
+    let error = error.replace('\0', "\u{FFFD}");
+    unwrap!(CString::new(error)).into_raw()
+}
+
+/// The functions of the vtable of a plugin whose contexts have type `C`.
+#[doc(hidden)]
+pub struct Exports<C>(PhantomData<C>);
+
+impl<C: PluginContext> Exports<C> {
+    /// The vtable of the plugin, without its name.
+    pub const VTABLE: PluginVTable = PluginVTable {
+        abi_version: ABI_VERSION,
+        telamon_version: TELAMON_VERSION.as_ptr() as *const c_char,
+        rustc_version: RUSTC_VERSION.as_ptr() as *const c_char,
+        name: ptr::null(),
+        new_context: Self::new_context,
+        free_context: Self::free_context,
+        free_error: Self::free_error,
+        device: Self::device,
+        evaluate: Self::evaluate,
+        benchmark: Self::benchmark,
+        async_eval: Self::async_eval,
+        param_as_size: Self::param_as_size,
+        eval_size: Self::eval_size,
+        stabilizer: Self::stabilizer,
+        thermal_state: Self::thermal_state,
+        bind_scalar: Self::bind_scalar,
+        bind_array: Self::bind_array,
+        eval_reference: Self::eval_reference,
+    };
+
+    unsafe fn context<'a>(context: *const ContextHandle) -> &'a C {
+        &*(context as *const C)
+    }
+
+    /// Implementation of `PluginVTable::new_context`.
+    unsafe extern "C" fn new_context(error: *mut *mut c_char) -> *mut ContextHandle {
+        let context = panic::catch_unwind(C::new)
+            .unwrap_or_else(|_| Err("the plugin panicked".to_string()));
+        match context {
+            Ok(context) => Box::into_raw(Box::new(context)) as *mut ContextHandle,
+            Err(err) => {
+                *error = export_error(err);
+                ptr::null_mut()
+            }
+        }
+    }
+
+    /// Implementation of `PluginVTable::free_context`.
+    unsafe extern "C" fn free_context(context: *mut ContextHandle) {
+        if !context.is_null() {
+            let context = Box::from_raw(context as *mut C);
+            guard(move || std::mem::drop(context));
+        }
+    }
+
+    /// Implementation of `PluginVTable::free_error`.
+    unsafe extern "C" fn free_error(error: *mut c_char) {
+        if !error.is_null() {
+            std::mem::drop(CString::from_raw(error));
+        }
+    }
+
+    /// Implementation of `PluginVTable::device`.
+    unsafe extern "C" fn device(
+        ctx: *const ContextHandle,
+        out: *mut Arc<dyn Device>,
+    ) -> bool {
+        guard(|| ptr::write(out, Self::context(ctx).device()))
+    }
+
+    /// Implementation of `PluginVTable::evaluate`.
+    unsafe extern "C" fn evaluate(
+        ctx: *const ContextHandle,
+        function: *const Function,
+        mode: *const EvalMode,
+        out: *mut Option<f64>,
+    ) -> bool {
+        guard(|| {
+            let eval = Self::context(ctx).evaluate(&*function, *mode);
+            ptr::write(out, eval.ok())
+        })
+    }
+
+    /// Implementation of `PluginVTable::benchmark`.
+    unsafe extern "C" fn benchmark(
+        ctx: *const ContextHandle,
+        function: *const Function,
+        num_samples: usize,
+        out: *mut Vec<f64>,
+    ) -> bool {
+        guard(|| ptr::write(out, Self::context(ctx).benchmark(&*function, num_samples)))
+    }
+
+    /// Implementation of `PluginVTable::async_eval`.
+    unsafe extern "C" fn async_eval<'b, 'c>(
+        ctx: *const ContextHandle,
+        num_workers: usize,
+        num_compilers: usize,
+        mode: *const EvalMode,
+        cancel: *const CancellationToken,
+        inner: *const &'c (dyn Fn(&mut dyn AsyncEvaluator<'b>) + Sync),
+    ) -> bool {
+        guard(|| {
+            Self::context(ctx).async_eval(
+                num_workers,
+                num_compilers,
+                *mode,
+                &*cancel,
+                *inner,
+            )
+        })
+    }
+
+    /// Implementation of `PluginVTable::param_as_size`.
+    unsafe extern "C" fn param_as_size(
+        ctx: *const ContextHandle,
+        name: *const u8,
+        name_len: usize,
+        out: *mut Option<u32>,
+    ) -> bool {
+        guard(|| {
+            let name = std::slice::from_raw_parts(name, name_len);
+            let name = unwrap!(std::str::from_utf8(name));
+            ptr::write(out, Self::context(ctx).param_as_size(name))
+        })
+    }
+
+    /// Implementation of `PluginVTable::eval_size`.
+    unsafe extern "C" fn eval_size(
+        ctx: *const ContextHandle,
+        size: *const Size,
+        out: *mut u32,
+    ) -> bool {
+        guard(|| ptr::write(out, Self::context(ctx).eval_size(&*size)))
+    }
+
+    /// Implementation of `PluginVTable::stabilizer`.
+    unsafe extern "C" fn stabilizer(
+        ctx: *const ContextHandle,
+        out: *mut Stabilizer,
+    ) -> bool {
+        guard(|| ptr::write(out, Self::context(ctx).stabilizer()))
+    }
+
+    /// Implementation of `PluginVTable::thermal_state`.
+    unsafe extern "C" fn thermal_state(
+        ctx: *const ContextHandle,
+        out: *mut Option<ThermalState>,
+    ) -> bool {
+        guard(|| ptr::write(out, Self::context(ctx).thermal_state()))
+    }
+
+    /// Implementation of `PluginVTable::bind_scalar`.
+    unsafe extern "C" fn bind_scalar(
+        ctx: *mut ContextHandle,
+        param: *const ir::Parameter,
+        value: *mut Box<dyn ScalarArgument>,
+    ) -> bool {
+        let value = ptr::read(value);
+        guard(|| (*(ctx as *mut C)).bind_erased_scalar(&*param, value))
+    }
+
+    /// Implementation of `PluginVTable::bind_array`.
+    unsafe extern "C" fn bind_array(
+        ctx: *mut ContextHandle,
+        param: *const ir::Parameter,
+        t: *const ir::Type,
+        len: usize,
+        out: *mut Arc<dyn ArrayArgument>,
+    ) -> bool {
+        guard(|| {
+            let array = (*(ctx as *mut C)).bind_erased_array(&*param, *t, len);
+            ptr::write(out, array)
+        })
+    }
+
+    /// Implementation of `PluginVTable::eval_reference`.
+    unsafe extern "C" fn eval_reference(
+        ctx: *const ContextHandle,
+        kernel: *const c_char,
+        params: *const c_char,
+        out: *mut f64,
+        error: *mut *mut c_char,
+    ) -> bool {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let kernel = CStr::from_ptr(kernel).to_string_lossy();
+            let params = CStr::from_ptr(params).to_string_lossy();
+            Self::context(ctx).eval_reference(&kernel, &params)
+        }))
+        .unwrap_or_else(|_| Err("the plugin panicked".to_string()));
+        match result {
+            Ok(time) => {
+                *out = time;
+                true
+            }
+            Err(err) => {
+                *error = export_error(err);
+                false
+            }
+        }
+    }
+}
+
+/// Declares the entry point of a backend plugin.
+///
+/// Takes the name of the backend and the type of its contexts, which must implement
+/// `PluginContext`.
+///
+/// ```ignore
+/// telamon::declare_backend_plugin!("my-dsp", my_dsp::Context);
+/// ```
+#[macro_export]
+macro_rules! declare_backend_plugin {
+    ($name:expr, $context:ty) => {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static telamon_backend_plugin: $crate::device::plugin::PluginVTable =
+            $crate::device::plugin::PluginVTable {
+                name: concat!($name, "\0").as_ptr() as *const ::std::os::raw::c_char,
+                ..$crate::device::plugin::Exports::<$context>::VTABLE
+            };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::fake;
+    use std::mem::ManuallyDrop;
+
+    impl PluginContext for fake::Context<fake::Device> {
+        fn new() -> Result<Self, String> {
+            Ok(Self::default())
+        }
+
+        fn eval_reference(&self, kernel: &str, params: &str) -> Result<f64, String> {
+            if kernel == "axpy" {
+                Ok(params.len() as f64)
+            } else {
+                Err(format!("no reference for {}", kernel))
+            }
+        }
+    }
+
+    static VTABLE: PluginVTable = PluginVTable {
+        name: b"fake\0" as *const u8 as *const c_char,
+        ..Exports::<fake::Context<fake::Device>>::VTABLE
+    };
+
+    /// Ensures contexts can be used through the functions of the vtable.
+    #[test]
+    fn call_through_vtable() {
+        assert_eq!(VTABLE.check_compatibility(), Ok(()));
+        unsafe {
+            let mut error = ptr::null_mut();
+            let context = (VTABLE.new_context)(&mut error);
+            assert!(!context.is_null());
+
+            let param = ir::Parameter {
+                name: "n".to_string(),
+                t: ir::Type::I(32),
+                elem_t: None,
+            };
+            let value: Box<dyn ScalarArgument> = Box::new(42i32);
+            let mut value = ManuallyDrop::new(value);
+            assert!((VTABLE.bind_scalar)(context, &param, &mut *value));
+            let mut size = None;
+            let name = "n";
+            assert!((VTABLE.param_as_size)(
+                context,
+                name.as_ptr(),
+                name.len(),
+                &mut size
+            ));
+            assert_eq!(size, Some(42));
+
+            let params = unwrap!(CString::new("[1024,true]"));
+            let mut time = 0.;
+            let kernel = unwrap!(CString::new("axpy"));
+            let eval_reference = VTABLE.eval_reference;
+            assert!(eval_reference(
+                context,
+                kernel.as_ptr(),
+                params.as_ptr(),
+                &mut time,
+                &mut error
+            ));
+            assert_eq!(time, 11.);
+            let kernel = unwrap!(CString::new("gemm"));
+            assert!(!eval_reference(
+                context,
+                kernel.as_ptr(),
+                params.as_ptr(),
+                &mut time,
+                &mut error
+            ));
+            assert_eq!(VTABLE.take_error(error), "no reference for gemm");
+
+            (VTABLE.free_context)(context);
+        }
+    }
+}
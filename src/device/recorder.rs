@@ -0,0 +1,292 @@
+//! Records the arguments bound to a context, so that an equivalent context can be rebuilt
+//! later, possibly on another machine or on a fake device.
+//!
+//! A `Recorder` wraps any `ArgMap` and records each binding in a `Manifest`. The manifest
+//! is serialized to JSON and can be bound to a new context with `Manifest::bind`. This
+//! allows bug reports to include the exact arguments a kernel was run with.
+use crate::codegen::{self, Function};
+use crate::device::{
+    ArgMap, ArrayArgument, AsyncEvaluator, CancellationToken, Context, Device, EvalMode,
+    KernelEvaluator, ScalarArgument, Stabilizer, ThermalState,
+};
+use crate::ir;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::{error, fmt};
+
+/// The arguments bound to a context.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The name of the device the arguments were bound on.
+    pub device: String,
+    /// The seed used to generate the inputs, if it was recorded.
+    pub seed: Option<u64>,
+    /// The values of the scalar parameters, printed in decimal.
+    pub scalars: Vec<(ir::Parameter, String)>,
+    /// The arrays bound to parameters.
+    pub arrays: Vec<ArrayBinding>,
+}
+
+/// An array bound to a parameter.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArrayBinding {
+    /// The parameter the array is bound to.
+    pub param: ir::Parameter,
+    /// The type of the elements of the array.
+    pub t: ir::Type,
+    /// The number of elements of the array.
+    pub len: usize,
+    /// The content of the array, if it was recorded.
+    pub data: Option<Vec<i8>>,
+}
+
+impl Manifest {
+    /// Loads a manifest saved with `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Saves the manifest to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Binds the recorded arguments to `context`. The content of the arrays is restored
+    /// when it was recorded.
+    pub fn bind<'a, AM: ArgMap<'a> + ?Sized>(
+        &self,
+        context: &mut AM,
+    ) -> Result<(), ManifestError> {
+        for (param, value) in &self.scalars {
+            context.bind_erased_scalar(param, parse_scalar(param, value)?);
+        }
+        for binding in &self.arrays {
+            let array = context.bind_erased_array(&binding.param, binding.t, binding.len);
+            if let Some(data) = &binding.data {
+                array.write_i8(data);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses the value of a scalar parameter.
+fn parse_scalar(
+    param: &ir::Parameter,
+    value: &str,
+) -> Result<Box<dyn ScalarArgument>, ManifestError> {
+    fn parse<S>(
+        param: &ir::Parameter,
+        value: &str,
+    ) -> Result<Box<dyn ScalarArgument>, ManifestError>
+    where
+        S: ScalarArgument + std::str::FromStr,
+    {
+        value
+            .parse::<S>()
+            .map(|value| Box::new(value) as Box<dyn ScalarArgument>)
+            .map_err(|_| ManifestError::InvalidValue {
+                name: param.name.clone(),
+                value: value.to_string(),
+            })
+    }
+
+    match param.t {
+        ir::Type::I(8) => parse::<i8>(param, value),
+        ir::Type::I(16) => parse::<i16>(param, value),
+        ir::Type::I(32) => parse::<i32>(param, value),
+        ir::Type::I(64) => parse::<i64>(param, value),
+        ir::Type::F(32) => parse::<f32>(param, value),
+        ir::Type::F(64) => parse::<f64>(param, value),
+        t => Err(ManifestError::UnsupportedType {
+            name: param.name.clone(),
+            t,
+        }),
+    }
+}
+
+/// Error returned when binding a manifest to a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    /// The value of a scalar parameter cannot be parsed.
+    InvalidValue { name: String, value: String },
+    /// Scalars of the type of the parameter cannot be rebuilt.
+    UnsupportedType { name: String, t: ir::Type },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestError::InvalidValue { name, value } => {
+                write!(fmt, "invalid value `{}` for parameter `{}`", value, name)
+            }
+            ManifestError::UnsupportedType { name, t } => write!(
+                fmt,
+                "cannot rebuild parameter `{}`: unsupported type {}",
+                name, t
+            ),
+        }
+    }
+}
+
+impl error::Error for ManifestError {}
+
+/// Wraps a context and records the arguments bound to it.
+pub struct Recorder<'a, C> {
+    inner: C,
+    seed: Option<u64>,
+    scalars: Vec<(ir::Parameter, String)>,
+    arrays: Vec<(ir::Parameter, ir::Type, usize, Arc<dyn ArrayArgument + 'a>)>,
+}
+
+impl<'a, C: ArgMap<'a>> Recorder<'a, C> {
+    /// Records the arguments bound to `inner`.
+    pub fn new(inner: C) -> Self {
+        Recorder {
+            inner,
+            seed: None,
+            scalars: vec![],
+            arrays: vec![],
+        }
+    }
+
+    /// Records the seed used to generate the inputs.
+    pub fn record_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// Returns the arguments bound so far. If `with_data` is true, the current content of
+    /// the arrays is copied to the host and included in the manifest.
+    pub fn manifest(&self, with_data: bool) -> Manifest {
+        let arrays = self
+            .arrays
+            .iter()
+            .map(|(param, t, len, array)| ArrayBinding {
+                param: param.clone(),
+                t: *t,
+                len: *len,
+                data: if with_data {
+                    Some(array.read_i8())
+                } else {
+                    None
+                },
+            })
+            .collect();
+        Manifest {
+            device: self.inner.device().name().to_string(),
+            seed: self.seed,
+            scalars: self.scalars.clone(),
+            arrays,
+        }
+    }
+
+    /// Returns the wrapped context.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Stops recording and returns the wrapped context.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<'a, C: Context> Context for Recorder<'a, C> {
+    fn device(&self) -> Arc<dyn Device> {
+        self.inner.device()
+    }
+
+    fn evaluate(&self, function: &Function, mode: EvalMode) -> Result<f64, ()> {
+        self.inner.evaluate(function, mode)
+    }
+
+    fn benchmark(&self, function: &Function, num_samples: usize) -> Vec<f64> {
+        self.inner.benchmark(function, num_samples)
+    }
+
+    fn compile_kernel<'b>(
+        &'b self,
+        function: &'b Function<'b>,
+    ) -> Box<dyn KernelEvaluator + 'b> {
+        self.inner.compile_kernel(function)
+    }
+
+    fn export_artifact(&self, function: &Function) -> Option<codegen::Artifact> {
+        self.inner.export_artifact(function)
+    }
+
+    fn load_artifact<'b>(
+        &'b self,
+        artifact: &codegen::Artifact,
+    ) -> Result<Box<dyn KernelEvaluator + 'b>, codegen::ArtifactError> {
+        self.inner.load_artifact(artifact)
+    }
+
+    fn async_eval<'b>(
+        &self,
+        num_workers: usize,
+        num_compilers: usize,
+        mode: EvalMode,
+        cancel: &CancellationToken,
+        inner: &(dyn Fn(&mut dyn AsyncEvaluator<'b>) + Sync),
+    ) {
+        self.inner.async_eval(num_workers, num_compilers, mode, cancel, inner)
+    }
+
+    fn param_as_size(&self, name: &str) -> Option<u32> {
+        self.inner.param_as_size(name)
+    }
+
+    fn eval_size(&self, size: &codegen::Size) -> u32 {
+        self.inner.eval_size(size)
+    }
+
+    fn stabilizer(&self) -> Stabilizer {
+        self.inner.stabilizer()
+    }
+
+    fn thermal_state(&self) -> Option<ThermalState> {
+        self.inner.thermal_state()
+    }
+}
+
+impl<'a, C: ArgMap<'a>> ArgMap<'a> for Recorder<'a, C> {
+    fn bind_erased_scalar(
+        &mut self,
+        param: &ir::Parameter,
+        value: Box<dyn ScalarArgument>,
+    ) {
+        self.scalars.push((param.clone(), value.to_string()));
+        self.inner.bind_erased_scalar(param, value)
+    }
+
+    fn bind_erased_array(
+        &mut self,
+        param: &ir::Parameter,
+        t: ir::Type,
+        len: usize,
+    ) -> Arc<dyn ArrayArgument + 'a> {
+        let array = self.inner.bind_erased_array(param, t, len);
+        self.arrays
+            .push((param.clone(), t, len, Arc::clone(&array)));
+        array
+    }
+
+    fn bind_existing_array(
+        &mut self,
+        param: &ir::Parameter,
+        array: Arc<dyn ArrayArgument + 'a>,
+    ) {
+        // The length of an existing array is only known from its content.
+        let t = param.elem_t.unwrap_or(param.t);
+        let len = array.read_i8().len() / (t.len_byte().unwrap_or(1) as usize);
+        self.arrays
+            .push((param.clone(), t, len, Arc::clone(&array)));
+        self.inner.bind_existing_array(param, array)
+    }
+}
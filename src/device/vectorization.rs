@@ -0,0 +1,181 @@
+//! Describes which operators a device can vectorize, and by which factors.
+//!
+//! Each device lists its vectorization rules in a single table.  The search space restricts
+//! vectorization factors with `Device::max_vectorization`, which is derived from the
+//! table.  Rules only depend on the operator, which is known when the search space is
+//! built, so the factors of any implementation are allowed by the table.  The code
+//! generator checks this in debug builds.
+use std::fmt;
+
+use crate::ir;
+
+/// The kind of an operator, as seen by vectorization rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpKind {
+    BinOp(ir::BinOp),
+    UnaryOp,
+    Mul,
+    Mad,
+    Mma,
+    Ld,
+    St,
+    TmpLd,
+    TmpSt,
+}
+
+impl OpKind {
+    /// Returns the kind of an operator.
+    pub fn of<L>(op: &ir::Operator<L>) -> Self {
+        match op {
+            ir::Operator::BinOp(op, ..) => OpKind::BinOp(*op),
+            ir::Operator::UnaryOp(..) => OpKind::UnaryOp,
+            ir::Operator::Mul(..) => OpKind::Mul,
+            ir::Operator::Mad(..) => OpKind::Mad,
+            ir::Operator::Mma(..) => OpKind::Mma,
+            ir::Operator::Ld(..) => OpKind::Ld,
+            ir::Operator::St(..) => OpKind::St,
+            ir::Operator::TmpLd(..) => OpKind::TmpLd,
+            ir::Operator::TmpSt(..) => OpKind::TmpSt,
+        }
+    }
+}
+
+/// Returns the type that vectorization rules match for an operator: the type of the value
+/// loaded or stored for memory accesses and the type of the first operand otherwise.
+fn rule_type<L>(op: &ir::Operator<L>) -> ir::Type {
+    match op {
+        ir::Operator::Ld(t, ..) | ir::Operator::TmpLd(t, ..) => *t,
+        ir::Operator::St(_, value, ..) | ir::Operator::TmpSt(value, ..) => value.t(),
+        ir::Operator::BinOp(_, lhs, ..)
+        | ir::Operator::UnaryOp(_, lhs)
+        | ir::Operator::Mul(lhs, ..)
+        | ir::Operator::Mad(lhs, ..)
+        | ir::Operator::Mma(lhs, ..) => lhs.t(),
+    }
+}
+
+/// A line of the vectorization table of a device.  A rule applies to the operators that
+/// match all of its fields that are set.
+#[derive(Clone, Copy, Debug)]
+pub struct VectorRule {
+    /// Kind of the operators the rule applies to.
+    pub op: Option<OpKind>,
+    /// Type of the operators the rule applies to.  See `rule_type`.
+    pub t: Option<ir::Type>,
+    /// Maximal outer and inner vectorization factors.
+    pub max_factors: [u32; 2],
+}
+
+impl VectorRule {
+    /// Creates a rule that applies to all operators.
+    pub const fn any(max_factors: [u32; 2]) -> Self {
+        VectorRule {
+            op: None,
+            t: None,
+            max_factors,
+        }
+    }
+
+    /// Indicates if the rule applies to the operator.
+    fn applies_to(&self, op: OpKind, t: ir::Type) -> bool {
+        self.op.map_or(true, |rule_op| rule_op == op)
+            && self.t.map_or(true, |rule_t| rule_t == t)
+    }
+}
+
+/// Returns the maximal vectorization factors of `op` according to `rules`.  The first rule
+/// that applies to the operator gives the factors.  Operators that no rule applies to
+/// cannot be vectorized.
+pub fn max_factors<L>(rules: &[VectorRule], op: &ir::Operator<L>) -> [u32; 2] {
+    let (kind, t) = (OpKind::of(op), rule_type(op));
+    rules
+        .iter()
+        .find(|rule| rule.applies_to(kind, t))
+        .map_or([1, 1], |rule| rule.max_factors)
+}
+
+/// Ensures the vectorization factors of `op` are allowed by `rules`.
+pub fn check<L>(
+    rules: &[VectorRule],
+    op: &ir::Operator<L>,
+    factors: [u32; 2],
+) -> Result<(), VectorizationError> {
+    let max_factors = max_factors(rules, op);
+    if factors[0] <= max_factors[0] && factors[1] <= max_factors[1] {
+        Ok(())
+    } else {
+        Err(VectorizationError {
+            op: OpKind::of(op),
+            t: rule_type(op),
+            factors,
+            max_factors,
+        })
+    }
+}
+
+/// Indicates that an instruction is vectorized beyond what its device allows.
+#[derive(Debug)]
+pub struct VectorizationError {
+    op: OpKind,
+    t: ir::Type,
+    factors: [u32; 2],
+    max_factors: [u32; 2],
+}
+
+impl fmt::Display for VectorizationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "vectorization factors {:?} of a {:?} on {} exceed the maximum of {:?}",
+            self.factors, self.op, self.t, self.max_factors
+        )
+    }
+}
+
+impl std::error::Error for VectorizationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::op::Rounding;
+    use num::{bigint::BigInt, rational::Ratio};
+
+    fn float(len: u16) -> ir::Operand<()> {
+        ir::Operand::new_float((Ratio::from_integer(BigInt::from(1)), len))
+    }
+
+    fn add(len: u16) -> ir::Operator<()> {
+        ir::Operator::BinOp(ir::BinOp::Add, float(len), float(len), Rounding::Nearest)
+    }
+
+    const RULES: &[VectorRule] = &[
+        VectorRule {
+            op: Some(OpKind::BinOp(ir::BinOp::Add)),
+            t: Some(ir::Type::F(16)),
+            max_factors: [1, 2],
+        },
+        VectorRule {
+            op: Some(OpKind::TmpLd),
+            t: None,
+            max_factors: [1, 8],
+        },
+        VectorRule::any([1, 4]),
+    ];
+
+    /// Ensures the first rule that applies gives the factors.
+    #[test]
+    fn first_rule_applies() {
+        let ld = ir::Operator::<()>::TmpLd(ir::Type::F(32), ir::MemId(0));
+        assert_eq!(max_factors(RULES, &add(16)), [1, 2]);
+        assert_eq!(max_factors(RULES, &add(32)), [1, 4]);
+        assert_eq!(max_factors(RULES, &ld), [1, 8]);
+        assert_eq!(max_factors(&[], &add(32)), [1, 1]);
+    }
+
+    #[test]
+    fn check_factors() {
+        assert!(check(RULES, &add(16), [1, 2]).is_ok());
+        assert!(check(RULES, &add(16), [1, 4]).is_err());
+        assert!(check(RULES, &add(32), [2, 1]).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Simulated annealing exploration of the search space.
+//!
+//! The store keeps a current implementation and proposes neighbours of it for evaluation.
+//! A neighbour is obtained by undoing the decisions taken after a random point of the path
+//! leading to the current implementation, taking a different decision at that point and
+//! then reapplying the undone decisions that are still valid. Remaining choices are
+//! decided randomly. Neighbours are accepted as the new current implementation with the
+//! Metropolis criterion: always if they are faster, and with a probability decreasing with
+//! their relative slowdown and with the temperature otherwise.
+use crate::device::Context;
+use crate::explorer::candidate::Candidate;
+use crate::explorer::choice::{self, ActionEx};
+use crate::explorer::config::AnnealingConfig;
+use crate::explorer::rng;
+use crate::explorer::store::Store;
+
+use log::{debug, info, warn};
+use rand::prelude::*;
+use rpds::List;
+use std::sync::Mutex;
+use utils::unwrap;
+
+/// Identifies an implementation by the actions applied to one of the roots of the store.
+#[derive(Clone)]
+pub struct Neighbour {
+    /// Index of the root in the store.
+    root: usize,
+    /// Actions leading from the root to the implementation, in application order.
+    actions: Vec<ActionEx>,
+}
+
+/// A `Store` implementing simulated annealing.
+pub struct AnnealingStore<'a> {
+    roots: Vec<Candidate>,
+    config: &'a AnnealingConfig,
+    state: Mutex<State>,
+    /// Generator from which each exploration step forks the generator of its random
+    /// choices.
+    rng: rng::SharedRng,
+}
+
+/// The mutable state of the annealing.
+struct State {
+    /// The implementation from which neighbours are generated, with its score.
+    current: Option<(Neighbour, f64)>,
+    /// The current temperature.
+    temperature: f64,
+    /// Candidates with a bound above the cut are pruned.
+    cut: f64,
+    /// Indicates the exploration was stopped.
+    stopped: bool,
+    /// Number of evaluations committed.
+    num_evals: usize,
+    /// Number of neighbours accepted as the current implementation.
+    num_accepted: usize,
+    /// Number of restarts from a random implementation.
+    num_restarts: usize,
+    /// Generator deciding if slower neighbours are accepted.
+    rng: rng::ExplorerRng,
+}
+
+impl<'a> AnnealingStore<'a> {
+    /// Creates a store exploring the implementations of `roots`.
+    pub fn new(roots: Vec<Candidate>, config: &'a AnnealingConfig) -> Self {
+        assert!(!roots.is_empty());
+        AnnealingStore {
+            roots,
+            config,
+            state: Mutex::new(State {
+                current: None,
+                temperature: config.initial_temperature,
+                cut: std::f64::INFINITY,
+                stopped: false,
+                num_evals: 0,
+                num_accepted: 0,
+                num_restarts: 0,
+                rng: rng::new(None),
+            }),
+            rng: rng::SharedRng::default(),
+        }
+    }
+
+    /// Draws the random choices of the annealing from `rng` instead of a generator seeded
+    /// from the entropy of the current thread.
+    pub fn with_rng(mut self, mut rng: rng::ExplorerRng) -> Self {
+        unwrap!(self.state.get_mut()).rng = rng::fork(&mut rng);
+        self.rng = rng::SharedRng::new(rng);
+        self
+    }
+
+    /// Generates a random neighbour of `current`, or returns `None` if the neighbourhood is
+    /// a dead-end.
+    fn neighbour(
+        &self,
+        context: &dyn Context,
+        current: &Neighbour,
+        cut: f64,
+        rng: &mut impl Rng,
+    ) -> Option<(Candidate, Neighbour)> {
+        let actions = &current.actions;
+        if actions.is_empty() {
+            return None;
+        }
+        let pos = rng.gen_range(0, actions.len());
+        let root = &self.roots[current.root];
+        let mut cand = root.clone();
+        let mut applied = Vec::with_capacity(actions.len());
+        for action in &actions[..pos] {
+            cand = cand.apply_decision(context, action.clone()).ok()?;
+            applied.push(action.clone());
+        }
+        // Undo the decision at `pos` by taking another one for the same choice.
+        let mut alternatives = choice::default_list(&cand.space).next()?;
+        alternatives.retain(|action| *action != actions[pos]);
+        let cand = apply_random(context, cand, alternatives, cut, rng)?;
+        applied.push(unwrap!(cand.actions.first()).clone());
+        let undone = &actions[pos + 1..];
+        self.descend(context, cand, applied, undone, cut, rng)
+            .map(|(leaf, actions)| {
+                let neighbour = Neighbour {
+                    root: current.root,
+                    actions,
+                };
+                (leaf, neighbour)
+            })
+    }
+
+    /// Generates a random implementation from a random root.
+    fn random(
+        &self,
+        context: &dyn Context,
+        cut: f64,
+        rng: &mut impl Rng,
+    ) -> Option<(Candidate, Neighbour)> {
+        let root = rng.gen_range(0, self.roots.len());
+        let cand = self.roots[root].clone();
+        if cand.bound.value() >= cut {
+            return None;
+        }
+        self.descend(context, cand, vec![], &[], cut, rng)
+            .map(|(leaf, actions)| (leaf, Neighbour { root, actions }))
+    }
+
+    /// Decides all the remaining choices of `cand`, reapplying the actions of `undone`
+    /// when they are part of a choice and picking a random action otherwise. `applied`
+    /// lists the actions already applied since the root.
+    fn descend(
+        &self,
+        context: &dyn Context,
+        mut cand: Candidate,
+        mut applied: Vec<ActionEx>,
+        undone: &[ActionEx],
+        cut: f64,
+        rng: &mut impl Rng,
+    ) -> Option<(Candidate, Vec<ActionEx>)> {
+        while let Some(choice) = choice::default_list(&cand.space).next() {
+            let reapplied = choice.iter().find(|action| undone.contains(action));
+            let next = reapplied.and_then(|action| {
+                cand.apply_decision(context, action.clone())
+                    .ok()
+                    .filter(|next| next.bound.value() < cut)
+            });
+            cand = match next {
+                Some(next) => next,
+                None => apply_random(context, cand, choice, cut, rng)?,
+            };
+            applied.push(unwrap!(cand.actions.first()).clone());
+        }
+        Some((cand, applied))
+    }
+}
+
+/// Applies a random action of `choice` to `cand`. Actions that are invalid or lead to a
+/// candidate with a bound above the cut are discarded. Returns `None` if no action is left.
+pub(super) fn apply_random(
+    context: &dyn Context,
+    cand: Candidate,
+    mut choice: Vec<ActionEx>,
+    cut: f64,
+    rng: &mut impl Rng,
+) -> Option<Candidate> {
+    while !choice.is_empty() {
+        let action = choice.swap_remove(rng.gen_range(0, choice.len()));
+        match cand.apply_decision(context, action) {
+            Ok(next) if next.bound.value() < cut => return Some(next),
+            Ok(_) => (),
+            Err(err) => debug!("invalid action encountered: {}", err),
+        }
+    }
+    None
+}
+
+impl<'a> Store for AnnealingStore<'a> {
+    type PayLoad = Neighbour;
+
+    type Event = ();
+
+    fn update_cut(&self, new_cut: f64) {
+        unwrap!(self.state.lock()).cut = new_cut;
+    }
+
+    fn stop_exploration(&self) {
+        unwrap!(self.state.lock()).stopped = true;
+    }
+
+    fn commit_evaluation(
+        &self,
+        _actions: &List<ActionEx>,
+        neighbour: Self::PayLoad,
+        eval: f64,
+    ) {
+        let mut state = unwrap!(self.state.lock());
+        state.num_evals += 1;
+        let accept = eval.is_finite()
+            && match state.current {
+                None => true,
+                Some((_, current)) if eval <= current => true,
+                Some((_, current)) => {
+                    let slowdown = (eval - current) / current;
+                    let threshold = (-slowdown / state.temperature).exp();
+                    state.rng.gen::<f64>() < threshold
+                }
+            };
+        if accept {
+            state.num_accepted += 1;
+            state.current = Some((neighbour, eval));
+        }
+        state.temperature = f64::max(
+            state.temperature * self.config.cooling_rate,
+            self.config.min_temperature,
+        );
+    }
+
+    fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)> {
+        let rng = &mut self.rng.fork();
+        let mut num_failures = 0;
+        loop {
+            let (current, cut) = {
+                let state = unwrap!(self.state.lock());
+                if state.stopped {
+                    return None;
+                }
+                let current = state.current.as_ref().map(|(current, _)| current.clone());
+                (current, state.cut)
+            };
+            let leaf = if let Some(current) = &current {
+                self.neighbour(context, current, cut, rng)
+            } else {
+                self.random(context, cut, rng)
+            };
+            if leaf.is_some() {
+                return leaf;
+            }
+            num_failures += 1;
+            if num_failures < self.config.max_attempts {
+                continue;
+            }
+            if current.is_none() {
+                warn!("no implementation found below the cut, stopping the annealing");
+                return None;
+            }
+            // The neighbourhood of the current implementation is pruned: restart from a
+            // random implementation.
+            info!("dead-end neighbourhood, restarting from a random implementation");
+            num_failures = 0;
+            let mut state = unwrap!(self.state.lock());
+            state.current = None;
+            state.num_restarts += 1;
+        }
+    }
+
+    fn print_stats(&self) {
+        let state = unwrap!(self.state.lock());
+        warn!(
+            "Annealing: {} evaluations, {} accepted, {} restarts, temperature {:.4e}",
+            state.num_evals, state.num_accepted, state.num_restarts, state.temperature
+        );
+    }
+
+    fn restart(&self) {
+        let mut state = unwrap!(self.state.lock());
+        state.current = None;
+        state.temperature = self.config.initial_temperature;
+    }
+}
@@ -1,6 +1,6 @@
 //! Exploration of the search space.
 use crate::device::Context;
-use crate::explorer::choice::{ActionError, ActionEx};
+use crate::explorer::choice::{self, ActionError, ActionEx, OpenOrders};
 use crate::model::{bound, Bound};
 use crate::search_space::SearchSpace;
 
@@ -10,6 +10,7 @@ use std;
 use std::cmp::{Ordering, PartialOrd};
 use std::io::{self, Write};
 use std::path::Path;
+use std::sync::Arc;
 
 use itertools::Itertools;
 use utils::unwrap;
@@ -25,12 +26,19 @@ pub struct Candidate {
     pub depth: usize,
     /// The list of actions already taken.
     pub actions: List<ActionEx>,
+    /// The orders left to fix before evaluation, if they were tracked from an ancestor.
+    pub open_orders: Option<Arc<OpenOrders>>,
 }
 
 impl Candidate {
-    /// Creates a new candidate, with depth 0.
+    /// Creates a new candidate, with depth 0.  The orders left to fix are tracked in its
+    /// descendants.
     pub fn new(space: SearchSpace, bound: Bound) -> Self {
-        Self::with_actions(space, bound, std::iter::empty())
+        let open_orders = Some(Arc::new(OpenOrders::new(&space)));
+        Candidate {
+            open_orders,
+            ..Self::with_actions(space, bound, std::iter::empty())
+        }
     }
 
     pub fn with_actions<II>(space: SearchSpace, bound: Bound, actions: II) -> Self
@@ -44,6 +52,16 @@ impl Candidate {
             bound,
             depth,
             actions,
+            open_orders: None,
+        }
+    }
+
+    /// Tracks the orders left to fix from `open_orders`, listed for the search space of
+    /// an ancestor of the candidate.
+    pub fn with_open_orders(self, open_orders: Arc<OpenOrders>) -> Self {
+        Candidate {
+            open_orders: Some(open_orders),
+            ..self
         }
     }
 
@@ -112,13 +130,31 @@ impl Candidate {
             );
         }
         let actions = self.actions.push_front(action);
+        let open_orders = self
+            .open_orders
+            .as_ref()
+            .map(|open_orders| Arc::new(open_orders.refine(&space)));
         Ok(Candidate {
             space,
             bound,
             depth: self.depth + 1,
             actions,
+            open_orders,
         })
     }
+
+    /// Fixes the order between statements that are left unconstrained by the decisions, so
+    /// that the candidate can be evaluated.  Only the orders that were still open in the
+    /// parent candidate are considered if they were tracked.
+    pub fn fix_order(self) -> Self {
+        let open_orders = self.open_orders.as_ref().map(|open_orders| &**open_orders);
+        let space = choice::fix_open_orders(self.space, open_orders);
+        Candidate {
+            space,
+            open_orders: None,
+            ..self
+        }
+    }
 }
 
 impl std::fmt::Display for Candidate {
@@ -13,6 +13,9 @@ use utils::unwrap;
 #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ActionEx {
     Action(Action),
+    /// Lowers the layout of a memory block.  `st_dims` and `ld_dims` list the pairs of mapped
+    /// dimensions, from the outermost to the innermost, used to index the block when storing
+    /// and loading.  Recording them explicitly allows replaying the lowering exactly.
     LowerLayout {
         mem: ir::MemId,
         st_dims: Vec<ir::DimId>,
@@ -99,6 +102,21 @@ pub fn list<'a>(
                         Action::DimKind(dim.id(), k)
                     })
                 })),
+                ChoiceGroup::GridStride => {
+                    Box::new(fun.logical_dims().flat_map(move |ldim| {
+                        // Only consider logical dimensions whose tiles can be mapped to
+                        // blocks.
+                        let has_block_tile = ldim.tile_dimensions().any(|tile| {
+                            let kinds = space.domain().get_dim_kind(tile);
+                            kinds.intersects(DimKind::BLOCK)
+                        });
+                        let dim = ldim.tiled_dimension().filter(|_| has_block_tile)?;
+                        let kinds = space.domain().get_dim_kind(dim);
+                        gen_choice(kinds.bisect(DimKind::LOOP), &|k| {
+                            Action::DimKind(dim, k)
+                        })
+                    }))
+                }
                 ChoiceGroup::DimMap => {
                     Box::new(fun.static_dims().enumerate().flat_map(move |(i, lhs)| {
                         fun.static_dims().take(i).flat_map(move |rhs| {
@@ -187,6 +205,10 @@ pub fn list<'a>(
                         gen_choice(flags, &|f| Action::InstFlag(inst.id(), f))
                     }))
                 }
+                ChoiceGroup::Precision => Box::new(fun.insts().flat_map(move |inst| {
+                    let precisions = space.domain().get_precision(inst.id()).list();
+                    gen_choice(precisions, &|p| Action::Precision(inst.id(), p))
+                })),
             }
         })
         .flatten()
@@ -221,19 +243,24 @@ where
 // TODO(search_space): fix order has currently no effect. Should we remove it ?
 // It is unused because inst-dim and dim-dim decisions are fixed by the explorer. We
 // cannot make them free as we might end-up in a dead-end.
-pub fn fix_order(mut space: SearchSpace) -> SearchSpace {
+pub fn fix_order(space: SearchSpace) -> SearchSpace {
+    fix_open_orders(space, None)
+}
+
+/// Fixes the order between the pairs of `open_orders` that are still unconstrained in
+/// `space`.  `open_orders` must have been listed for `space` or one of its ancestors.  If
+/// it is `None`, all the pairs of `space` are considered.
+pub fn fix_open_orders(
+    mut space: SearchSpace,
+    open_orders: Option<&OpenOrders>,
+) -> SearchSpace {
     // TODO(search_space): make fix_order useless with a differential model
     trace!("adding arbitrary constraints to the order");
-    // Fix the order between instructions and dimensions.
-    let pairs = space
-        .ir_instance()
-        .statements()
-        .cartesian_product(space.ir_instance().dims())
-        .map(|(lhs, rhs)| (lhs.stmt_id(), rhs.stmt_id()))
-        .filter(|&(lhs, rhs)| lhs != rhs)
-        .filter(|&(lhs, rhs)| !space.domain().get_order(lhs, rhs).is_constrained())
-        .collect_vec();
-    for (lhs, rhs) in pairs {
+    let open_orders = match open_orders {
+        Some(open_orders) => open_orders.refine(&space),
+        None => OpenOrders::new(&space),
+    };
+    for &(lhs, rhs) in &open_orders.pairs {
         let order = space.domain().get_order(lhs, rhs);
         if order.is_constrained() {
             continue;
@@ -254,6 +281,61 @@ pub fn fix_order(mut space: SearchSpace) -> SearchSpace {
     space
 }
 
+/// The pairs of a statement and a dimension whose order is not yet constrained, in the
+/// order in which `fix_order` considers them.
+///
+/// Decisions only constrain orders further, so the pairs open in a candidate are a subset
+/// of the pairs open in its parent.  Refining the list of the parent is thus cheaper than
+/// listing all the pairs of the function again.
+#[derive(Clone, Debug)]
+pub struct OpenOrders {
+    /// Number of instructions and dimensions in the function when the pairs were listed.
+    /// Lowering may add statements, in which case the pairs must be listed again.
+    num_stmts: (usize, usize),
+    pairs: Vec<(ir::StmtId, ir::StmtId)>,
+}
+
+impl OpenOrders {
+    /// Lists the unconstrained pairs of `space`.
+    pub fn new(space: &SearchSpace) -> Self {
+        let fun = space.ir_instance();
+        let pairs = fun
+            .statements()
+            .cartesian_product(fun.dims())
+            .map(|(lhs, rhs)| (lhs.stmt_id(), rhs.stmt_id()))
+            .filter(|&(lhs, rhs)| lhs != rhs)
+            .filter(|&(lhs, rhs)| !space.domain().get_order(lhs, rhs).is_constrained())
+            .collect();
+        OpenOrders {
+            num_stmts: Self::num_stmts(space),
+            pairs,
+        }
+    }
+
+    /// Restricts the pairs to the ones still unconstrained in `space`, a descendant of the
+    /// search space the pairs were listed for.
+    pub fn refine(&self, space: &SearchSpace) -> Self {
+        if Self::num_stmts(space) != self.num_stmts {
+            return Self::new(space);
+        }
+        let pairs = self
+            .pairs
+            .iter()
+            .cloned()
+            .filter(|&(lhs, rhs)| !space.domain().get_order(lhs, rhs).is_constrained())
+            .collect();
+        OpenOrders {
+            num_stmts: self.num_stmts,
+            pairs,
+        }
+    }
+
+    fn num_stmts(space: &SearchSpace) -> (usize, usize) {
+        let fun = space.ir_instance();
+        (fun.insts().count(), fun.dims().count())
+    }
+}
+
 /// Generates the different ways to lower a layout.
 fn lower_layout_choice(space: &SearchSpace, mem: ir::MemId) -> Vec<ActionEx> {
     let mem_block = space.ir_instance().mem_block(mem);
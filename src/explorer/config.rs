@@ -6,6 +6,7 @@ extern crate toml;
 
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
+use std::net::TcpStream;
 use std::path::{Path, PathBuf};
 use std::{self, error, fmt, str::FromStr};
 
@@ -18,6 +19,10 @@ use utils::{tfrecord, unwrap};
 use crate::explorer::eventlog::EventLog;
 
 /// Stores the configuration of the exploration.
+///
+/// New options are added over time, so configurations should be created from the
+/// default one, for instance with `Config { ..Default::default() }`, or by parsing a
+/// file.
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(default)]
 #[serde(deny_unknown_fields)]
@@ -30,14 +35,53 @@ pub struct Config {
     /// Name of the file in which to store the binary event log.  If none is provided, the event
     /// log is not saved.
     pub event_log: Option<String>,
+    /// If true, the launch configuration and the source code of each evaluated candidate
+    /// are recorded in the event log.  This generates the code of each candidate a second
+    /// time, which slows down the search.
+    pub log_code: bool,
+    /// Name of the file in which to periodically save the state of the exploration, so that
+    /// it can be resumed after an interruption.  If none is provided, no checkpoint is saved.
+    ///
+    /// Only supported by the MCTS search algorithm.
+    pub checkpoint_file: Option<String>,
+    /// Number of evaluations between two checkpoints.  If zero, the checkpoint is only
+    /// saved at the end of the search.
+    pub checkpoint_interval: usize,
+    /// Path to a checkpoint from which to resume the exploration.  The evaluations of the
+    /// checkpoint count towards `max_evaluations`.
+    pub resume: Option<String>,
+    /// Number of messages that can be waiting for the logger before the search threads block.
+    pub log_channel_size: usize,
+    /// Number of messages the logger keeps in memory when they arrive faster than it can
+    /// write them.  Further messages are stored in a temporary file until the logger
+    /// catches up.
+    pub log_buffer_size: usize,
+    /// Interval, in seconds, between two flushes of the logs to disk.  This bounds the
+    /// amount of events lost if the search is interrupted.  Must be positive.
+    pub log_flush_interval: f64,
+    /// Number of evaluation results that can be waiting for the monitor before the evaluation
+    /// callbacks block.
+    pub monitor_channel_size: usize,
     /// Number of exploration threads.
     pub num_workers: usize,
+    /// Number of threads compiling candidates ahead of their evaluation, so that the device
+    /// does not wait for the compiler.  If zero, the exploration threads compile the
+    /// candidates themselves.  Only supported by the CUDA backend.
+    pub num_compilers: usize,
+    /// Seed of the random choices of the exploration.  If set, two searches with the same
+    /// seed and a single worker make the same choices, as long as the evaluations return
+    /// the same results.  Otherwise, the choices depend on the entropy of the system.
+    pub seed: Option<u64>,
     /// Indicates the search must be stopped if a candidate with an execution time better
     /// than the bound (in ns) is found.
     pub stop_bound: Option<f64>,
     /// If true, check all implementations found for correctness.  Otherwise (the default) only the
     /// best candidates are checked.
     pub check_all: bool,
+    /// If true, the counters of the search space are compared with the generated code for
+    /// each implementation, and mismatches are logged as errors.  This is a debugging aid
+    /// for the search space description and slows down the search.
+    pub check_counters: bool,
     /// Indicates the search must be stopped after the given number of minutes.
     pub timeout: Option<u64>,
     /// Indicates the search must be stopped after the given number of
@@ -51,6 +95,48 @@ pub struct Config {
     ///
     /// Only supported by the MCTS search algorithm.
     pub restart_every_n_evals: Option<usize>,
+    /// Quantity to minimize.  Objectives other than the execution time require a device that
+    /// supports energy measurement, and disable the pruning of candidates by their bound.
+    pub objective: Objective,
+    /// If true, candidates are neither compiled nor evaluated and their bound is used in place
+    /// of their execution time.  This allows quickly estimating the cost of a search and
+    /// checking how candidates are pruned.
+    pub dry_run: bool,
+    /// If true, the sizes of static dimensions that are invalid for the device whatever the
+    /// kind of the dimension are removed before the search starts.  See
+    /// `explorer::restrict`.
+    pub restrict_sizes: bool,
+    /// If set, the address on which to wait for workers. The descents in the tree are then
+    /// completed and evaluated by the workers that connect to it instead of locally. See
+    /// `explorer::distributed`.
+    ///
+    /// Only supported by the MCTS search algorithm, without ensembles nor screening.
+    /// Workers must provide the token in `explorer::distributed::TOKEN_VAR`.
+    pub coordinator: Option<String>,
+    /// If set, the address on which the metrics of the search are served over HTTP, in the
+    /// text format of Prometheus.  See `explorer::monitor::Metrics`.
+    pub metrics_address: Option<String>,
+    /// Directory in which the best implementation found for each kernel, parameters and
+    /// device is cached.  When set, `find_best` reuses the cached implementation instead
+    /// of running the search again.  See `telamon::cache`.  Unlike other paths, this is not relative to
+    /// `output_dir`, so that the cache can be shared between runs.
+    pub tuning_cache: Option<String>,
+    /// If set, candidates are first screened with a few runs and only the most promising ones
+    /// are evaluated with the number of runs of the device stabilizer.  The tree is updated with
+    /// the values of the precise evaluation when there is one.
+    pub screening: Option<ScreeningConfig>,
+    /// Additional conditions under which the search is stopped before the search space is
+    /// exhausted.
+    pub stop_conditions: StopConditions,
+    /// If set, the MCTS search runs several trees on subsets of the workers instead of a
+    /// single one.  Requires the UCT tree policy.
+    pub ensemble: Option<EnsembleConfig>,
+    /// If set, the best implementation found by the search is refined with a local
+    /// hill-climbing on its tile sizes and cache flags.  See `explorer::refine`.  Only
+    /// applies when minimizing the execution time.
+    pub refine: Option<RefineConfig>,
+    /// Structured logs of the search, meant to be consumed by external tools.
+    pub logging: LoggingConfig,
     /// Exploration algorithm to use. Needs to be last for TOML serialization, because it is a table.
     pub algorithm: SearchAlgorithm,
 }
@@ -86,7 +172,9 @@ impl Config {
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, config::ConfigError> {
         let mut parser = Self::create_parser();
         parser.merge(config::File::from(path.as_ref()))?;
-        parser.try_into::<Self>()
+        let config = parser.try_into::<Self>()?;
+        config.validate()?;
+        Ok(config)
     }
 
     /// Parse the configuration from a JSON string. Primary user is
@@ -94,7 +182,63 @@ impl Config {
     pub fn from_json(json: &str) -> Result<Self, config::ConfigError> {
         let mut parser = Self::create_parser();
         parser.merge(config::File::from_str(json, config::FileFormat::Json))?;
-        parser.try_into::<Self>()
+        let config = parser.try_into::<Self>()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that the parameters of the configuration are consistent.  This is done when
+    /// parsing a configuration, but must be done explicitly for configurations built or
+    /// modified in code.
+    pub fn validate(&self) -> Result<(), config::ConfigError> {
+        if !(self.log_flush_interval > 0. && self.log_flush_interval.is_finite()) {
+            return Err(config::ConfigError::Message(format!(
+                "the log flush interval must be a positive number of seconds, got {}",
+                self.log_flush_interval
+            )));
+        }
+        match &self.algorithm {
+            SearchAlgorithm::Mcts(bandit_config) => bandit_config.validate()?,
+            SearchAlgorithm::SimulatedAnnealing(annealing_config) => {
+                annealing_config.validate()?
+            }
+            SearchAlgorithm::Evolution(evolution_config) => {
+                evolution_config.validate()?
+            }
+            _ => (),
+        }
+        if let Some(ensemble_config) = &self.ensemble {
+            ensemble_config.validate()?;
+            match &self.algorithm {
+                SearchAlgorithm::Mcts(BanditConfig {
+                    tree_policy: TreePolicy::UCT(..),
+                    ..
+                }) => (),
+                SearchAlgorithm::Mcts(..) => {
+                    return Err(config::ConfigError::Message(
+                        "an ensemble of trees requires the UCT tree policy".to_string(),
+                    ));
+                }
+                _ => (),
+            }
+        }
+        if self.coordinator.is_some() {
+            let unsupported = match &self.algorithm {
+                SearchAlgorithm::Mcts(..) if self.ensemble.is_some() => Some("ensembles"),
+                SearchAlgorithm::Mcts(..) if self.screening.is_some() => {
+                    Some("screening")
+                }
+                SearchAlgorithm::Mcts(..) => None,
+                _ => Some("search algorithms other than MCTS"),
+            };
+            if let Some(unsupported) = unsupported {
+                return Err(config::ConfigError::Message(format!(
+                    "distributed searches do not support {}",
+                    unsupported
+                )));
+            }
+        }
+        Ok(())
     }
 
     pub fn output_path<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
@@ -110,6 +254,20 @@ impl Config {
         Ok(BufWriter::new(f))
     }
 
+    /// Opens the sinks of the JSON log enabled by `logging`.
+    pub fn create_json_logs(&self) -> io::Result<Vec<Box<dyn Write + Send>>> {
+        let mut sinks: Vec<Box<dyn Write + Send>> = vec![];
+        if let Some(json_file) = &self.logging.json_file {
+            let file = File::create(self.output_path(json_file)?)?;
+            sinks.push(Box::new(BufWriter::new(file)));
+        }
+        if let Some(json_socket) = &self.logging.json_socket {
+            let stream = TcpStream::connect(json_socket)?;
+            sinks.push(Box::new(BufWriter::new(stream)));
+        }
+        Ok(sinks)
+    }
+
     pub fn create_eventlog(&self) -> io::Result<Option<tfrecord::Writer<EventLog>>> {
         if let Some(event_log) = &self.event_log {
             EventLog::create(self.output_path(event_log)?).map(Some)
@@ -131,18 +289,190 @@ impl Default for Config {
             output_dir: ".".to_string(),
             log_file: "watch.log".to_string(),
             event_log: None,
+            log_code: false,
+            checkpoint_file: None,
+            checkpoint_interval: 100,
+            resume: None,
+            log_channel_size: 100,
+            log_buffer_size: 10_000,
+            log_flush_interval: 1.,
+            monitor_channel_size: 100,
             check_all: false,
+            check_counters: false,
             num_workers: num_cpus::get(),
+            num_compilers: 0,
+            seed: None,
             algorithm: SearchAlgorithm::default(),
             stop_bound: None,
             timeout: None,
             max_evaluations: None,
             distance_to_best: None,
             restart_every_n_evals: None,
+            objective: Objective::default(),
+            dry_run: false,
+            restrict_sizes: false,
+            coordinator: None,
+            metrics_address: None,
+            screening: None,
+            tuning_cache: None,
+            stop_conditions: StopConditions::default(),
+            ensemble: None,
+            refine: None,
+            logging: LoggingConfig::default(),
+        }
+    }
+}
+
+/// Configuration of the JSON log, which holds the evaluations, the new best candidates and
+/// the dead-ends of the search as newline-delimited JSON records.  See
+/// `explorer::JsonRecord` for the format of the records.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Name of the file in which to write the JSON log.
+    pub json_file: Option<String>,
+    /// Address of a TCP socket to which the JSON log is sent, for instance
+    /// `"127.0.0.1:9000"`.  The socket must be listening when the search starts.
+    pub json_socket: Option<String>,
+}
+
+/// Configuration of the screening stage of the evaluation.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct ScreeningConfig {
+    /// Number of runs used to screen a candidate.
+    pub num_runs: usize,
+    /// Fraction of the screened candidates promoted to a precise evaluation.  A candidate is
+    /// promoted if its screening value is among the best `promote_fraction` of the values seen
+    /// so far, or if it may beat the best candidate.
+    pub promote_fraction: f64,
+    /// Number of runs used to confirm the value of a promoted candidate.  Defaults to the
+    /// number of runs used by the context when screening is disabled.
+    pub confirm_runs: Option<usize>,
+}
+
+impl Default for ScreeningConfig {
+    fn default() -> Self {
+        ScreeningConfig {
+            num_runs: 1,
+            promote_fraction: 0.1,
+            confirm_runs: None,
+        }
+    }
+}
+
+/// Conditions under which the search is stopped early.  The search stops as soon as one of
+/// the conditions holds.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct StopConditions {
+    /// Stop the search if the best candidate was not improved for the given number of
+    /// evaluations.
+    pub max_stale_evaluations: Option<usize>,
+    /// Stop the search once the best candidate is within the given percentage of the
+    /// performance model bound of the initial candidates.  As the bound is a lower bound of
+    /// the execution time, this is only used when minimizing the execution time.
+    pub bound_gap: Option<f64>,
+    /// Stop the search after the given number of implementations were received by the
+    /// monitor.  Unlike `max_evaluations`, this also counts the implementations that were
+    /// cut before being evaluated.
+    pub max_implementations: Option<usize>,
+}
+
+/// Configuration of an ensemble of MCTS trees, which differ by their exploration
+/// constant.  The workers are split evenly between the trees.  The trees share the cut and
+/// periodically import the best candidate found by the other trees, so that trees that
+/// explore more aggressively can benefit from the findings of the greedier ones and
+/// conversely.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct EnsembleConfig {
+    /// Exploration constant of each tree.  Other parameters of the tree policy are taken
+    /// from the UCT configuration of the search algorithm.
+    pub exploration_constants: Vec<f64>,
+    /// Number of evaluations between two exchanges of the best candidate.
+    pub exchange_interval: usize,
+}
+
+impl Default for EnsembleConfig {
+    fn default() -> Self {
+        EnsembleConfig {
+            exploration_constants: vec![0.5, 2f64.sqrt(), 4.],
+            exchange_interval: 100,
         }
     }
 }
 
+impl EnsembleConfig {
+    fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.exploration_constants.is_empty() {
+            return Err(config::ConfigError::Message(
+                "an ensemble must have at least one exploration constant".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Configuration of the refinement of the best implementation after the search.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct RefineConfig {
+    /// Maximal number of mutations to evaluate.
+    pub max_evaluations: usize,
+}
+
+impl Default for RefineConfig {
+    fn default() -> Self {
+        RefineConfig {
+            max_evaluations: 100,
+        }
+    }
+}
+
+/// Quantity minimized by the search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// The execution time, in nanoseconds.
+    Time,
+    /// The energy consumed by an execution, in nanojoules.
+    Energy,
+    /// The energy-delay product, in nanojoules times nanoseconds.
+    Edp,
+}
+
+impl Objective {
+    /// Indicates if the objective requires measuring the energy consumption of candidates.
+    pub fn needs_energy(self) -> bool {
+        match self {
+            Objective::Time => false,
+            Objective::Energy | Objective::Edp => true,
+        }
+    }
+
+    /// Computes the score of a candidate from its runtime and its energy consumption.
+    /// Returns `None` if the energy is required but was not measured.
+    pub fn score(self, runtime: f64, energy: Option<f64>) -> Option<f64> {
+        match self {
+            Objective::Time => Some(runtime),
+            Objective::Energy => energy,
+            Objective::Edp => energy.map(|energy| energy * runtime),
+        }
+    }
+}
+
+impl Default for Objective {
+    fn default() -> Self {
+        Objective::Time
+    }
+}
+
 /// Exploration algorithm to use.
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -152,6 +482,11 @@ pub enum SearchAlgorithm {
     BoundOrder,
     /// Use a MCTS algorithm
     Mcts(BanditConfig),
+    /// Use simulated annealing, perturbing fully specified implementations.
+    SimulatedAnnealing(AnnealingConfig),
+    /// Use an evolutionary algorithm, recombining and mutating a population of
+    /// implementations.
+    Evolution(EvolutionConfig),
 }
 
 impl Default for SearchAlgorithm {
@@ -160,6 +495,92 @@ impl Default for SearchAlgorithm {
     }
 }
 
+/// Configuration parameters specific to the simulated annealing algorithm.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct AnnealingConfig {
+    /// Initial temperature.  A neighbour slower than the current implementation by a
+    /// relative `slowdown` is accepted with probability `exp(-slowdown / temperature)`.
+    pub initial_temperature: f64,
+    /// Factor by which the temperature is multiplied after each evaluation.  Must be
+    /// strictly between 0 and 1.
+    pub cooling_rate: f64,
+    /// Temperature below which the temperature is not decreased anymore.
+    pub min_temperature: f64,
+    /// Number of consecutive dead-ends after which the neighbourhood of the current
+    /// implementation is abandoned for a random implementation.  The exploration stops if
+    /// no random implementation can be found in as many attempts.
+    pub max_attempts: usize,
+}
+
+impl Default for AnnealingConfig {
+    fn default() -> Self {
+        AnnealingConfig {
+            initial_temperature: 0.1,
+            cooling_rate: 0.995,
+            min_temperature: 1e-3,
+            max_attempts: 100,
+        }
+    }
+}
+
+impl AnnealingConfig {
+    /// Ensures the temperature decreases at each step of the schedule.
+    fn validate(&self) -> Result<(), config::ConfigError> {
+        if !(self.cooling_rate > 0. && self.cooling_rate < 1.) {
+            return Err(config::ConfigError::Message(format!(
+                "the cooling rate must be strictly between 0 and 1, got {}",
+                self.cooling_rate
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Configuration parameters specific to the evolutionary algorithm.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct EvolutionConfig {
+    /// Number of individuals in a generation.
+    pub population_size: usize,
+    /// Probability to decide a choice randomly instead of as in one of the parents.
+    pub mutation_rate: f64,
+    /// Number of best individuals of a generation kept unchanged in the next one.  Must be
+    /// smaller than the population size.
+    pub elitism: usize,
+    /// Number of individuals competing to be selected as a parent.
+    pub tournament_size: usize,
+    /// Number of consecutive dead-ends after which the exploration stops.
+    pub max_attempts: usize,
+}
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        EvolutionConfig {
+            population_size: 50,
+            mutation_rate: 0.05,
+            elitism: 5,
+            tournament_size: 2,
+            max_attempts: 100,
+        }
+    }
+}
+
+impl EvolutionConfig {
+    /// Ensures each generation has room for individuals other than the elites.
+    fn validate(&self) -> Result<(), config::ConfigError> {
+        if self.elitism >= self.population_size {
+            return Err(config::ConfigError::Message(format!(
+                "the elitism must be smaller than the population size, got {} and {}",
+                self.elitism, self.population_size
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Configuration parameters specific to the multi-armed bandit algorithm.
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -176,8 +597,115 @@ pub struct BanditConfig {
     pub new_nodes_order: NewNodeOrder,
     /// Order in which the different choices are going to be determined
     pub choice_ordering: ChoiceOrdering,
+    /// Time (in milliseconds) above which a bound computation is considered pathological.  The
+    /// actions leading to the offending candidate are then logged to help debugging the
+    /// performance model.
+    pub bound_time_alarm: Option<f64>,
+    /// Maximal number of infeasible sets of actions to cache.  When set, propagation failures
+    /// are analyzed by replaying actions from the root to find which of them conflict with the
+    /// failing action, so that the conflict is detected without propagation in other subtrees.
+    /// The analysis is costly, hence this is disabled by default.
+    pub conflict_cache_size: Option<usize>,
+    /// Restart the search after the given number of descents ending in a dead-end since
+    /// the previous restart.  As with `Config::restart_every_n_evals`, a restart resets the
+    /// statistics of the tree but keeps the cut, and thus the best candidate found so far.
+    pub restart_after_deadends: Option<usize>,
     /// Indicates how to choose between nodes with at least one children evaluated.
     pub tree_policy: TreePolicy,
+    /// Priors on the actions, which weight the choices of the default policy.  This biases
+    /// the search towards actions that experts expect to perform well without removing
+    /// other actions.
+    ///
+    /// Priors are serialized as an array of tables, which must not be written as an empty
+    /// array after the tables above.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub priors: Vec<ChoicePrior>,
+    /// Learned model used to order new nodes instead of their bound, if any.  Nodes are
+    /// still pruned with their bound.
+    pub cost_model: Option<CostModelConfig>,
+    /// If set, the subtrees in which all evaluations are bad are excluded from the search
+    /// each time it restarts.
+    pub tabu: Option<TabuConfig>,
+}
+
+impl BanditConfig {
+    fn validate(&self) -> Result<(), config::ConfigError> {
+        for prior in &self.priors {
+            if !(prior.weight > 0. && prior.weight.is_finite()) {
+                return Err(config::ConfigError::Message(format!(
+                    "the weight of the prior on {} must be positive, got {}",
+                    prior.action, prior.weight
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A prior on an action of the bandit search.  For instance, the following prefers mapping
+/// the dimension `%0` to threads:
+///
+/// ```toml
+/// [[algorithm.priors]]
+/// action = "DimKind(%0, THREAD)"
+/// weight = 4.0
+/// ```
+///
+/// With the `random` and `weighted_random` orders, the probability to pick the action is
+/// multiplied by `weight`.  With the `bound` order, the bound of the action is divided by
+/// `weight` when comparing it to other actions.  The `api` order ignores priors.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChoicePrior {
+    /// The action, as printed in the logs.
+    pub action: String,
+    /// Weight of the action.  Weights above one favor the action and weights below one
+    /// penalize it.  Must be positive.
+    pub weight: f64,
+}
+
+/// Configuration of the tabu list of the bandit search, which holds the prefixes of
+/// actions known to lead to bad candidates.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct TabuConfig {
+    /// Number of actions in the prefixes.  Must be at least one.
+    pub depth: usize,
+    /// Minimal number of evaluations in the subtree of a prefix before it can be declared
+    /// bad.
+    pub min_evaluations: usize,
+    /// A prefix is bad if the best evaluation in its subtree is above `threshold` times the
+    /// best evaluation overall.
+    pub threshold: f64,
+}
+
+impl Default for TabuConfig {
+    fn default() -> Self {
+        TabuConfig {
+            depth: 2,
+            min_evaluations: 10,
+            threshold: 2.,
+        }
+    }
+}
+
+/// Configuration of a learned cost model.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CostModelConfig {
+    /// Path to the model, serialized in JSON.
+    pub path: String,
+    /// Weight of the model's estimate when blended with the bound of the performance
+    /// model.  `1` only uses the estimate while `0` only uses the bound.
+    #[serde(default = "CostModelConfig::default_weight")]
+    pub weight: f64,
+}
+
+impl CostModelConfig {
+    fn default_weight() -> f64 {
+        1.
+    }
 }
 
 /// Tree policy configuration
@@ -258,6 +786,11 @@ pub struct UCTConfig {
     pub reward: Reward,
     /// Formula to use for the exploration term.
     pub formula: Formula,
+    /// Weight of the coverage bonus, which favors the `DimKind`, `Order` and `InstFlag`
+    /// values that were rarely part of an evaluated implementation, regardless of the node
+    /// they appear in.  The bonus is scaled like the exploration term and decays as
+    /// `1 / sqrt(1 + count)`.  A weight of 0 disables it.
+    pub coverage_weight: f64,
 }
 
 impl Default for UCTConfig {
@@ -271,6 +804,7 @@ impl Default for UCTConfig {
             value_reduction: ValueReduction::Best,
             reward: Reward::Speed,
             formula: Formula::Uct,
+            coverage_weight: 0.,
         }
     }
 }
@@ -320,6 +854,12 @@ impl Default for BanditConfig {
             tree_policy: TreePolicy::default(),
             choice_ordering: ChoiceOrdering::default(),
             backtrack_deadends: false,
+            bound_time_alarm: None,
+            conflict_cache_size: None,
+            restart_after_deadends: None,
+            priors: vec![],
+            cost_model: None,
+            tabu: None,
         }
     }
 }
@@ -370,6 +910,16 @@ pub enum ChoiceGroup {
     /// nested (explicitly sets Order::INNER, Order::OUTER or
     /// eliminates these two orders)
     DimNesting,
+
+    /// Exposes choices defining whether the runtime-sized dimension of a tiled logical
+    /// dimension is a loop, which is iterated over by each block when the tiling dimensions
+    /// are mapped to blocks (grid-stride loop).
+    GridStride,
+
+    /// Exposes choices between the accurate and approximate versions of operations.  This
+    /// is not part of the default ordering: approximations are only used if this group is
+    /// explicitly listed.
+    Precision,
 }
 
 impl fmt::Display for ChoiceGroup {
@@ -388,6 +938,8 @@ impl fmt::Display for ChoiceGroup {
             ThreadSize => "thread_size",
             DimFusion => "dim_fusion",
             DimNesting => "dim_nesting",
+            GridStride => "grid_stride",
+            Precision => "precision",
         })
     }
 }
@@ -422,21 +974,113 @@ impl FromStr for ChoiceGroup {
             "thread_size" => ThreadSize,
             "dim_fusion" => DimFusion,
             "dim_nesting" => DimNesting,
+            "grid_stride" => GridStride,
+            "precision" => Precision,
             _ => return Err(ParseChoiceGroupError(s.to_string())),
         })
     }
 }
 
 /// A list of ChoiceGroup representing the order in which we want to determine choices
+///
+/// The ordering can be refined depending on the depth in the search tree.  In the
+/// configuration file, it is either a plain list of groups, used at all depths, or a table
+/// such as:
+///
+/// ```toml
+/// [algorithm.choice_ordering]
+/// groups = ["lower_layout", "size", "dim_kind", "dim_map", "mem_space", "order", "inst_flag"]
+///
+/// [[algorithm.choice_ordering.depths]]
+/// from_depth = 0
+/// groups = ["dim_kind", "order"]
+///
+/// [[algorithm.choice_ordering.depths]]
+/// from_depth = 10
+/// groups = ["size"]
+/// ```
+///
+/// At a given depth, the groups of the last entry of `depths` whose `from_depth` is lower or
+/// equal to the depth are considered first, followed by `groups`.  Since `groups` is always
+/// considered, the per-depth entries only prioritize choices and cannot hide them.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ChoiceOrdering(Vec<ChoiceGroup>);
+#[serde(from = "ChoiceOrderingRepr", into = "ChoiceOrderingRepr")]
+pub struct ChoiceOrdering {
+    groups: Vec<ChoiceGroup>,
+    /// Groups to consider first from a given depth on, sorted by increasing depth.
+    depths: Vec<DepthOrdering>,
+}
+
+/// Groups of choices to consider first from a given depth in the search tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepthOrdering {
+    /// Depth from which the groups are used.
+    pub from_depth: usize,
+    /// Groups to consider first, in order.
+    pub groups: Vec<ChoiceGroup>,
+}
+
+/// Serialized representation of a `ChoiceOrdering`, which accepts a plain list of groups for
+/// backward compatibility.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum ChoiceOrderingRepr {
+    Flat(Vec<ChoiceGroup>),
+    ByDepth {
+        groups: Vec<ChoiceGroup>,
+        #[serde(default)]
+        depths: Vec<DepthOrdering>,
+    },
+}
+
+impl From<ChoiceOrderingRepr> for ChoiceOrdering {
+    fn from(repr: ChoiceOrderingRepr) -> Self {
+        match repr {
+            ChoiceOrderingRepr::Flat(groups) => ChoiceOrdering {
+                groups,
+                depths: vec![],
+            },
+            ChoiceOrderingRepr::ByDepth { groups, mut depths } => {
+                depths.sort_by_key(|ordering| ordering.from_depth);
+                ChoiceOrdering { groups, depths }
+            }
+        }
+    }
+}
+
+impl From<ChoiceOrdering> for ChoiceOrderingRepr {
+    fn from(ordering: ChoiceOrdering) -> Self {
+        if ordering.depths.is_empty() {
+            ChoiceOrderingRepr::Flat(ordering.groups)
+        } else {
+            ChoiceOrderingRepr::ByDepth {
+                groups: ordering.groups,
+                depths: ordering.depths,
+            }
+        }
+    }
+}
+
+impl ChoiceOrdering {
+    /// Returns the groups of choices to consider at the given depth of the search tree.
+    pub fn at_depth(&self, depth: usize) -> impl Iterator<Item = &ChoiceGroup> + '_ {
+        self.depths
+            .iter()
+            .rev()
+            .find(|ordering| ordering.from_depth <= depth)
+            .into_iter()
+            .flat_map(|ordering| &ordering.groups)
+            .chain(&self.groups)
+    }
+}
 
 impl<'a> IntoIterator for &'a ChoiceOrdering {
     type Item = &'a ChoiceGroup;
     type IntoIter = std::slice::Iter<'a, ChoiceGroup>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.iter()
+        self.groups.iter()
     }
 }
 
@@ -452,13 +1096,16 @@ pub(super) const DEFAULT_ORDERING: [ChoiceGroup; 7] = [
 
 impl Default for ChoiceOrdering {
     fn default() -> Self {
-        ChoiceOrdering(DEFAULT_ORDERING.to_vec())
+        ChoiceOrdering {
+            groups: DEFAULT_ORDERING.to_vec(),
+            depths: vec![],
+        }
     }
 }
 
 impl fmt::Display for ChoiceOrdering {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let Some((first, rest)) = self.0.split_first() {
+        if let Some((first, rest)) = self.groups.split_first() {
             write!(f, "{:?}", first)?;
 
             for elem in rest {
@@ -474,10 +1121,177 @@ impl FromStr for ChoiceOrdering {
     type Err = ParseChoiceGroupError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(ChoiceOrdering(
-            s.split(',')
+        Ok(ChoiceOrdering {
+            groups: s
+                .split(',')
                 .map(str::parse)
                 .collect::<Result<Vec<_>, _>>()?,
-        ))
+            depths: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializes a configuration to TOML and parses it back.
+    fn round_trip(config: &Config) -> Config {
+        let toml = unwrap!(toml::to_string(config));
+        unwrap!(toml::from_str(&toml))
+    }
+
+    /// Ensures the default configuration can be written in the logs.
+    #[test]
+    fn default_round_trip() {
+        round_trip(&Config::default());
+    }
+
+    #[test]
+    fn priors_round_trip() {
+        let bandit_config = BanditConfig {
+            priors: vec![ChoicePrior {
+                action: "DimKind(%0, THREAD)".to_string(),
+                weight: 4.,
+            }],
+            ..BanditConfig::default()
+        };
+        let config = Config {
+            algorithm: SearchAlgorithm::Mcts(bandit_config),
+            ..Config::default()
+        };
+        match round_trip(&config).algorithm {
+            SearchAlgorithm::Mcts(bandit_config) => {
+                assert_eq!(bandit_config.priors.len(), 1);
+                assert_eq!(bandit_config.priors[0].weight, 4.);
+            }
+            _ => panic!("expected the MCTS algorithm"),
+        }
+    }
+
+    /// Ensures the top-level values are written before the tables.
+    #[test]
+    fn screening_round_trip() {
+        let config = Config {
+            screening: Some(ScreeningConfig::default()),
+            tuning_cache: Some("cache".to_string()),
+            ..Config::default()
+        };
+        let config = round_trip(&config);
+        assert!(config.screening.is_some());
+        assert_eq!(config.tuning_cache.as_ref().map(|s| &s[..]), Some("cache"));
+    }
+
+    /// Ensures bandit options can be set without breaking the serialization of the
+    /// configuration, which requires values to be declared before tables.
+    #[test]
+    fn bandit_options_round_trip() {
+        let bandit_config = BanditConfig {
+            conflict_cache_size: Some(1000),
+            restart_after_deadends: Some(10),
+            ..BanditConfig::default()
+        };
+        let config = Config {
+            algorithm: SearchAlgorithm::Mcts(bandit_config),
+            ..Config::default()
+        };
+        match round_trip(&config).algorithm {
+            SearchAlgorithm::Mcts(bandit_config) => {
+                assert_eq!(bandit_config.conflict_cache_size, Some(1000));
+                assert_eq!(bandit_config.restart_after_deadends, Some(10));
+            }
+            _ => panic!("expected the MCTS algorithm"),
+        }
+    }
+
+    #[test]
+    fn reject_non_positive_priors() {
+        for &weight in &[0., -1., std::f64::NAN] {
+            let bandit_config = BanditConfig {
+                priors: vec![ChoicePrior {
+                    action: "DimKind(%0, THREAD)".to_string(),
+                    weight,
+                }],
+                ..BanditConfig::default()
+            };
+            let config = Config {
+                algorithm: SearchAlgorithm::Mcts(bandit_config),
+                ..Config::default()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn reject_invalid_cooling_rate() {
+        for &cooling_rate in &[0., -0.5, 1., 1.5, std::f64::NAN] {
+            let annealing_config = AnnealingConfig {
+                cooling_rate,
+                ..AnnealingConfig::default()
+            };
+            let config = Config {
+                algorithm: SearchAlgorithm::SimulatedAnnealing(annealing_config),
+                ..Config::default()
+            };
+            assert!(config.validate().is_err());
+        }
+        let config = Config {
+            algorithm: SearchAlgorithm::SimulatedAnnealing(AnnealingConfig::default()),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_elitism() {
+        let evolution_config = |population_size, elitism| Config {
+            algorithm: SearchAlgorithm::Evolution(EvolutionConfig {
+                population_size,
+                elitism,
+                ..EvolutionConfig::default()
+            }),
+            ..Config::default()
+        };
+        assert!(evolution_config(10, 5).validate().is_ok());
+        assert!(evolution_config(10, 10).validate().is_err());
+        assert!(evolution_config(0, 0).validate().is_err());
+        assert!(Config {
+            algorithm: SearchAlgorithm::Evolution(EvolutionConfig::default()),
+            ..Config::default()
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_ensemble() {
+        let ensemble_config = |tree_policy, exploration_constants| Config {
+            algorithm: SearchAlgorithm::Mcts(BanditConfig {
+                tree_policy,
+                ..BanditConfig::default()
+            }),
+            ensemble: Some(EnsembleConfig {
+                exploration_constants,
+                ..EnsembleConfig::default()
+            }),
+            ..Config::default()
+        };
+        let uct = || TreePolicy::UCT(UCTConfig::default());
+        assert!(ensemble_config(uct(), vec![1.]).validate().is_ok());
+        assert!(ensemble_config(uct(), vec![]).validate().is_err());
+        assert!(ensemble_config(TreePolicy::Bound, vec![1.])
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn reject_invalid_log_flush_interval() {
+        for &log_flush_interval in &[0., -1., std::f64::NAN, std::f64::INFINITY] {
+            let config = Config {
+                log_flush_interval,
+                ..Config::default()
+            };
+            assert!(config.validate().is_err());
+        }
     }
 }
@@ -0,0 +1,139 @@
+//! Caching of infeasible combinations of actions.
+//!
+//! When propagation fails while applying an action to a candidate, the actions leading to the
+//! candidate are replayed from the root to extract a smaller set of actions that is still
+//! incompatible with the failing action.  This conflict set is cached so that the same failure
+//! can be detected without propagation in other subtrees where the same actions were taken.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use fxhash::{FxHashMap, FxHashSet};
+
+use crate::explorer::choice::ActionEx as Action;
+use crate::search_space::SearchSpace;
+
+/// A bounded cache of sets of actions known to be infeasible.
+pub struct ConflictCache {
+    /// The root of the search space, from which actions are replayed to extract conflicts.
+    root: SearchSpace,
+    /// Maximal number of conflict sets to keep.  The oldest sets are evicted first.
+    capacity: usize,
+    inner: RwLock<Conflicts>,
+    lookups: AtomicUsize,
+    hits: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Conflicts {
+    /// Conflict sets, indexed by the action whose application failed.
+    by_action: FxHashMap<Action, Vec<Arc<FxHashSet<Action>>>>,
+    /// Conflict sets in insertion order.
+    queue: VecDeque<(Action, Arc<FxHashSet<Action>>)>,
+}
+
+impl ConflictCache {
+    /// Creates an empty cache for the search space rooted at `root`.
+    pub fn new(root: SearchSpace, capacity: usize) -> Self {
+        ConflictCache {
+            root,
+            capacity,
+            inner: RwLock::new(Conflicts::default()),
+            lookups: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    /// Indicates if applying `action` after `actions` is known to be infeasible.
+    pub fn is_infeasible(&self, actions: &FxHashSet<Action>, action: &Action) -> bool {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        let hit = self
+            .inner
+            .read()
+            .expect("conflicts: poisoned")
+            .by_action
+            .get(action)
+            .map(|sets| sets.iter().any(|set| set.is_subset(actions)))
+            .unwrap_or(false);
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        hit
+    }
+
+    /// Records that applying `action` after `actions` failed.
+    pub fn record_failure(&self, actions: &[Action], action: &Action) {
+        if self.capacity == 0 {
+            return;
+        }
+        // Lowering a layout is not monotonic with regard to the previous decisions: it may only
+        // be possible after some of them were taken.
+        if let Action::LowerLayout { .. } = action {
+            return;
+        }
+        let conflict = extract_conflict(&self.root, actions, action);
+        let conflict = Arc::new(conflict.into_iter().collect());
+        let mut inner = self.inner.write().expect("conflicts: poisoned");
+        if inner.queue.len() >= self.capacity {
+            if let Some((old_action, old_set)) = inner.queue.pop_front() {
+                let is_empty = inner
+                    .by_action
+                    .get_mut(&old_action)
+                    .map(|sets| {
+                        sets.retain(|set| !Arc::ptr_eq(set, &old_set));
+                        sets.is_empty()
+                    })
+                    .unwrap_or(false);
+                if is_empty {
+                    inner.by_action.remove(&old_action);
+                }
+            }
+        }
+        inner
+            .by_action
+            .entry(action.clone())
+            .or_insert_with(Vec::new)
+            .push(Arc::clone(&conflict));
+        inner.queue.push_back((action.clone(), conflict));
+    }
+}
+
+/// Removes the actions that are not needed for `action` to fail after `actions`, by replaying
+/// the remaining ones from `root`.  The result is minimal: removing any other action makes
+/// `action` applicable.  The actions are kept in their original order.
+pub(crate) fn extract_conflict(
+    root: &SearchSpace,
+    actions: &[Action],
+    action: &Action,
+) -> Vec<Action> {
+    let mut conflict = actions.to_vec();
+    for idx in (0..conflict.len()).rev() {
+        let removed = conflict.remove(idx);
+        if !fails(root, &conflict, action) {
+            conflict.insert(idx, removed);
+        }
+    }
+    conflict
+}
+
+/// Indicates if `actions` can be applied to `root` but `action` cannot be applied after them.
+fn fails(root: &SearchSpace, actions: &[Action], action: &Action) -> bool {
+    actions
+        .iter()
+        .try_fold(root.clone(), |space, action| action.clone().apply_to(space))
+        .map(|space| action.clone().apply_to(space).is_err())
+        .unwrap_or(false)
+}
+
+impl fmt::Display for ConflictCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits out of {} lookups, {} conflict sets cached",
+            self.hits.load(Ordering::Relaxed),
+            self.lookups.load(Ordering::Relaxed),
+            self.inner.read().expect("conflicts: poisoned").queue.len()
+        )
+    }
+}
@@ -0,0 +1,142 @@
+//! Learned models estimating the execution time of candidates.
+//!
+//! Models are trained offline on the features of evaluated candidates, as returned by
+//! `FeatureVector::values`, and loaded from disk to guide the exploration.
+use crate::device::Context;
+use crate::explorer::features::{self, FeatureVector};
+use crate::model::{Bound, CostEstimator};
+use crate::search_space::SearchSpace;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+/// An ensemble of regression trees, as produced by gradient boosting.  The prediction is
+/// the sum of `base_score` and of the values of the leaves reached in each tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TreeEnsemble {
+    /// Initial value of the prediction.
+    #[serde(default)]
+    pub base_score: f64,
+    /// Indicates the model predicts the logarithm of the execution time.
+    #[serde(default)]
+    pub log_target: bool,
+    /// The trees of the ensemble.
+    pub trees: Vec<Tree>,
+}
+
+/// A regression tree.  The first node is the root.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tree {
+    pub nodes: Vec<TreeNode>,
+}
+
+/// A node of a regression tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeNode {
+    /// Goes to `left` if the feature is below `threshold` and to `right` otherwise.
+    /// Missing features, encoded as `NaN`, go to `right`.
+    Split {
+        feature: usize,
+        threshold: f64,
+        left: usize,
+        right: usize,
+    },
+    /// Returns a value.
+    Leaf { value: f64 },
+}
+
+impl TreeEnsemble {
+    /// Loads a model serialized in JSON.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let model: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        model.check()?;
+        Ok(model)
+    }
+
+    /// Ensures the trees are well-formed, so that `predict` cannot loop or go out of
+    /// bounds.
+    fn check(&self) -> io::Result<()> {
+        let num_features = FeatureVector::names().len();
+        for (tree_id, tree) in self.trees.iter().enumerate() {
+            if tree.nodes.is_empty() {
+                return Err(invalid_model(format!("tree {} is empty", tree_id)));
+            }
+            for (node_id, node) in tree.nodes.iter().enumerate() {
+                if let TreeNode::Split {
+                    feature,
+                    left,
+                    right,
+                    ..
+                } = *node
+                {
+                    if feature >= num_features {
+                        return Err(invalid_model(format!(
+                            "tree {} uses unknown feature {}",
+                            tree_id, feature
+                        )));
+                    }
+                    let num_nodes = tree.nodes.len();
+                    let is_child = |child: usize| node_id < child && child < num_nodes;
+                    if !is_child(left) || !is_child(right) {
+                        return Err(invalid_model(format!(
+                            "node {} of tree {} has invalid children",
+                            node_id, tree_id
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Predicts the execution time, in nanoseconds, from the values of the features.
+    pub fn predict(&self, features: &[f64]) -> f64 {
+        let sum = self
+            .trees
+            .iter()
+            .map(|tree| tree.predict(features))
+            .fold(self.base_score, |lhs, rhs| lhs + rhs);
+        if self.log_target {
+            sum.exp()
+        } else {
+            sum
+        }
+    }
+}
+
+impl Tree {
+    /// Returns the value of the leaf reached by `features`.
+    fn predict(&self, features: &[f64]) -> f64 {
+        let mut node = 0;
+        loop {
+            match self.nodes[node] {
+                TreeNode::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                } => {
+                    node = if features[feature] < threshold {
+                        left
+                    } else {
+                        right
+                    }
+                }
+                TreeNode::Leaf { value } => return value,
+            }
+        }
+    }
+}
+
+impl CostEstimator for TreeEnsemble {
+    fn estimate(&self, space: &SearchSpace, bound: &Bound, context: &dyn Context) -> f64 {
+        let features = features::extract_with_model(space, Some(bound), context);
+        self.predict(&features.values())
+    }
+}
+
+fn invalid_model(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
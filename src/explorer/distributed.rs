@@ -0,0 +1,649 @@
+//! Distributes the descents in the search tree and the evaluation of candidates to workers
+//! running on other machines.
+//!
+//! The coordinator owns the MCTS tree.  Each descent in the tree stops at the node it
+//! expands and the actions leading to that node from the root are sent to a worker.  The
+//! worker rebuilds the partial candidate from its own copy of the search space, completes
+//! the descent with a rollout, evaluates the implementation it reaches on its device and
+//! sends back the actions of the implementation along with its score.  The coordinator
+//! then commits the score to the tree.  Workers must thus build the search space of the
+//! exact same kernel as the coordinator, with the same configuration.
+//!
+//! Messages are exchanged over TCP, encoded in JSON with one message per line.  A worker
+//! opens one connection per evaluation thread, and each connection has at most one
+//! descent in progress at any given time.  Each connection starts with the token of the
+//! worker, which must match the token of the coordinator.  Both read it from the
+//! `TELAMON_WORKER_TOKEN` environment variable.  The token is sent in clear: on untrusted
+//! networks, connections must go through an encrypted tunnel.
+use crate::device::{
+    AsyncEvaluator, CancellationToken, Context, EvalMode, KernelEvaluator, Stabilizer,
+};
+use crate::explorer::choice::{ActionError, ActionEx};
+use crate::explorer::config::{BanditConfig, SearchAlgorithm};
+use crate::explorer::local_selection::Rollout;
+use crate::explorer::monitor::{get_new_cut, Backlog, Measurement, MonitorMessage};
+use crate::explorer::{restrict, Candidate, CheckResultFn, Config, Objective, Store};
+use crate::model::bound;
+
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use utils::unwrap;
+
+/// Environment variable holding the token shared by the coordinator and its workers.
+pub const TOKEN_VAR: &str = "TELAMON_WORKER_TOKEN";
+
+/// Delay between two checks for new workers.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Delay after which the coordinator closes connections that did not send a token.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The first message sent by a worker on a new connection.
+#[derive(Serialize, Deserialize)]
+struct Hello {
+    token: String,
+}
+
+/// A message sent by the coordinator to a worker.
+#[derive(Serialize, Deserialize)]
+enum Request {
+    /// Completes the descent from the partial candidate obtained by applying `actions` to
+    /// the root of the search space, and evaluates the implementation it reaches.  The
+    /// descent prunes the candidates whose bound is above `cut`.  `best` is the best
+    /// score found so far.  Both are `None` when no candidate was evaluated yet or when
+    /// scores cannot be compared with bounds.
+    Descend {
+        actions: Vec<ActionEx>,
+        cut: Option<f64>,
+        best: Option<f64>,
+    },
+    /// Closes the connection.
+    Stop,
+    /// Closes the connection because the token of the worker is invalid.
+    Rejected,
+}
+
+/// A message sent by a worker to the coordinator.
+#[derive(Serialize, Deserialize)]
+struct Response {
+    /// The actions of the implementation reached by the worker, from the root.  `None` if
+    /// the descent reached a dead-end.
+    actions: Option<Vec<ActionEx>>,
+    /// The score of the implementation, `None` if it could not be evaluated.
+    eval: Option<f64>,
+    /// The execution time and the energy consumed by the implementation, if the objective
+    /// of the search required measuring the energy.
+    #[serde(default)]
+    energy: Option<(f64, f64)>,
+}
+
+impl Response {
+    /// The response for a descent that did not reach an implementation.
+    fn deadend() -> Self {
+        Response {
+            actions: None,
+            eval: None,
+            energy: None,
+        }
+    }
+
+    /// The response for an implementation that could not be evaluated.
+    fn failure(actions: Vec<ActionEx>) -> Self {
+        Response {
+            actions: Some(actions),
+            eval: None,
+            energy: None,
+        }
+    }
+}
+
+/// Reads the token shared by the coordinator and its workers.
+fn token() -> io::Result<String> {
+    match std::env::var(TOKEN_VAR) {
+        Ok(ref token) if token.is_empty() => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is empty", TOKEN_VAR),
+        )),
+        Ok(token) => Ok(token),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("distributed searches require a token in {}", TOKEN_VAR),
+        )),
+    }
+}
+
+/// Compares two tokens in a time that does not depend on the position of the first
+/// difference.
+fn same_token(lhs: &str, rhs: &str) -> bool {
+    lhs.len() == rhs.len()
+        && lhs
+            .bytes()
+            .zip(rhs.bytes())
+            .fold(0, |diff, (lhs, rhs)| diff | (lhs ^ rhs))
+            == 0
+}
+
+/// Writes a message on its own line.
+fn send<M: Serialize>(writer: &mut impl Write, message: &M) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Reads the next message, or returns `None` if the connection was closed.
+fn receive<M: DeserializeOwned>(reader: &mut impl BufRead) -> io::Result<Option<M>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+/// Returns the MCTS configuration of a distributed search.
+fn bandit_config(config: &Config) -> io::Result<&BanditConfig> {
+    match &config.algorithm {
+        SearchAlgorithm::Mcts(bandit_config) => Ok(bandit_config),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "distributed searches require the MCTS search algorithm",
+        )),
+    }
+}
+
+/// Listens for workers on `addr`.  The listener does not block, so that the coordinator
+/// can notice cancellations while waiting for workers.
+fn listen(addr: &str) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Explores the candidate store and dispatches the descents to the workers connecting to
+/// `addr`.  Fails if the coordinator cannot wait for workers, for instance because `addr`
+/// is invalid or already in use.
+pub(super) fn explore_remote<T>(
+    config: &Config,
+    candidate_store: &T,
+    eval_sender: futures::sync::mpsc::Sender<MonitorMessage<T>>,
+    backlog: &Backlog,
+    context: &dyn Context,
+    cancel: &CancellationToken,
+    addr: &str,
+) -> io::Result<()>
+where
+    T: Store,
+{
+    let token = &token()?;
+    let initial_cut = match &config.algorithm {
+        SearchAlgorithm::Mcts(bandit_config) => bandit_config.initial_cut,
+        _ => None,
+    };
+    let listener = listen(addr)?;
+    info!("waiting for workers on {}", addr);
+    let best = &Mutex::new(None);
+    let exhausted = &AtomicBool::new(false);
+    crossbeam::scope(|scope| {
+        while !cancel.is_cancelled() && !exhausted.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    let eval_sender = eval_sender.clone();
+                    scope.spawn(move |_| {
+                        let worker = RemoteWorker {
+                            config,
+                            candidate_store,
+                            eval_sender,
+                            backlog,
+                            context,
+                            cancel,
+                            initial_cut,
+                            best,
+                            exhausted,
+                        };
+                        if let Err(err) = worker.run(stream, token) {
+                            warn!("lost worker {}: {}", peer, err);
+                        }
+                    });
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL)
+                }
+                Err(err) => {
+                    error!("cannot accept workers: {}", err);
+                    break;
+                }
+            }
+        }
+    })
+    .unwrap();
+    Ok(())
+}
+
+/// Feeds descents to a worker connected to the coordinator.
+struct RemoteWorker<'a, T: Store> {
+    config: &'a Config,
+    candidate_store: &'a T,
+    eval_sender: futures::sync::mpsc::Sender<MonitorMessage<T>>,
+    backlog: &'a Backlog,
+    context: &'a dyn Context,
+    cancel: &'a CancellationToken,
+    initial_cut: Option<f64>,
+    best: &'a Mutex<Option<f64>>,
+    exhausted: &'a AtomicBool,
+}
+
+impl<'a, T: Store> RemoteWorker<'a, T> {
+    fn run(&self, stream: TcpStream, token: &str) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        authenticate(&mut reader, &mut writer, token)?;
+        info!("worker {} connected", writer.peer_addr()?);
+        loop {
+            let (actions, prefix, payload) =
+                match self.candidate_store.explore_prefix(self.context) {
+                    Some(prefix) => prefix,
+                    None => {
+                        self.exhausted.store(true, Ordering::SeqCst);
+                        break;
+                    }
+                };
+            if self.cancel.is_cancelled() {
+                break;
+            }
+
+            // Bounds are only comparable with scores when minimizing the execution time.
+            let (cut, best) = match self.config.objective {
+                Objective::Time => {
+                    let best = *self.best.lock().unwrap();
+                    let cut = best.map(|best| get_new_cut(self.config, best));
+                    let cut = match (cut, self.initial_cut) {
+                        (Some(cut), Some(initial_cut)) => Some(cut.min(initial_cut)),
+                        (cut, initial_cut) => cut.or(initial_cut),
+                    };
+                    (cut, best)
+                }
+                Objective::Energy | Objective::Edp => (None, None),
+            };
+            let request = Request::Descend {
+                actions: actions.clone(),
+                cut,
+                best,
+            };
+            let response = send(&mut writer, &request).and_then(|()| {
+                receive::<Response>(&mut reader)?.ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "worker disconnected")
+                })
+            });
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    // The store expects an evaluation for each candidate it returns.
+                    self.backlog.send(
+                        self.eval_sender.clone(),
+                        (prefix, std::f64::INFINITY, Measurement::Confirmed, payload),
+                    );
+                    return Err(err);
+                }
+            };
+
+            let (cand, eval) = self.complete(prefix, &actions, response, &payload);
+            {
+                let mut best = self.best.lock().unwrap();
+                if eval.is_finite() && best.map_or(true, |best| eval < best) {
+                    *best = Some(eval);
+                }
+            }
+            self.backlog.send(
+                self.eval_sender.clone(),
+                (cand, eval, Measurement::Confirmed, payload),
+            );
+        }
+        send(&mut writer, &Request::Stop)
+    }
+
+    /// Returns the implementation reached by the worker from `prefix` along with its
+    /// score.  Returns `prefix` with an infinite score if the worker did not reach an
+    /// implementation.
+    fn complete(
+        &self,
+        prefix: Candidate,
+        prefix_actions: &[ActionEx],
+        response: Response,
+        payload: &T::PayLoad,
+    ) -> (Candidate, f64) {
+        let actions = match response.actions {
+            Some(actions) => actions,
+            None => return (prefix, std::f64::INFINITY),
+        };
+        if !actions.starts_with(prefix_actions) {
+            warn!("the worker did not complete the descent it was sent");
+            return (prefix, std::f64::INFINITY);
+        }
+        // Only the actions of the rollout are applied: this is much cheaper than the
+        // rollout itself, which computes the bounds of all the children it considers.
+        let space = actions[prefix_actions.len()..]
+            .iter()
+            .try_fold(prefix.space.clone(), |space, action| action.apply_to(space));
+        let space = match space {
+            Ok(space) => space,
+            Err(err) => {
+                warn!("cannot apply the actions sent by the worker: {}", err);
+                return (prefix, std::f64::INFINITY);
+            }
+        };
+        if let Some((runtime, energy)) = response.energy {
+            self.candidate_store.commit_energy(payload, runtime, energy);
+        }
+        let bound = bound(&space, self.context);
+        let eval = response.eval.unwrap_or(std::f64::INFINITY);
+        (Candidate::with_actions(space, bound, actions), eval)
+    }
+}
+
+/// Checks the token sent by a worker on a new connection.  Connections with an invalid
+/// token are rejected with an error.
+fn authenticate(
+    reader: &mut impl BufRead,
+    writer: &mut TcpStream,
+    token: &str,
+) -> io::Result<()> {
+    writer.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let hello = receive::<Hello>(reader)?;
+    writer.set_read_timeout(None)?;
+    if hello.map_or(false, |hello| same_token(&hello.token, token)) {
+        Ok(())
+    } else {
+        send(writer, &Request::Rejected)?;
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "invalid token",
+        ))
+    }
+}
+
+/// Completes the descents sent by the coordinator listening on `addr` and evaluates the
+/// implementations they reach, until the coordinator closes the connection.  `root` must
+/// be the root of the search space explored by the coordinator.
+///
+/// The worker opens `config.num_workers` connections to the coordinator, so that
+/// descents can run while candidates are compiled and evaluated.
+pub fn work(
+    config: &Config,
+    context: &dyn Context,
+    root: Candidate,
+    addr: &str,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+) -> io::Result<()> {
+    let token = &token()?;
+    let bandit_config = bandit_config(config)?;
+    // The coordinator restricts the sizes of its root the same way.
+    let root = &if config.restrict_sizes {
+        restrict::restrict_sizes(root, context).0
+    } else {
+        root
+    };
+    let stabilizer = &context.stabilizer().skip_bad_candidates(true);
+    let cancel = &CancellationToken::new();
+    let first_error = &Mutex::new(None);
+    context.async_eval(
+        config.num_workers,
+        config.num_compilers,
+        EvalMode::FindBest,
+        cancel,
+        &|evaluator| {
+            let worker = LocalWorker {
+                config,
+                bandit_config,
+                context,
+                root,
+                stabilizer,
+                check_result_fn,
+            };
+            if let Err(err) = worker.serve(addr, token, evaluator) {
+                cancel.cancel();
+                first_error.lock().unwrap().get_or_insert(err);
+            }
+        },
+    );
+    match first_error.lock().unwrap().take() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Completes and evaluates the descents received on a connection to the coordinator.
+struct LocalWorker<'b> {
+    config: &'b Config,
+    bandit_config: &'b BanditConfig,
+    context: &'b dyn Context,
+    root: &'b Candidate,
+    stabilizer: &'b Stabilizer,
+    check_result_fn: Option<&'b CheckResultFn<'b>>,
+}
+
+impl<'b> LocalWorker<'b> {
+    fn serve(
+        &self,
+        addr: &str,
+        token: &str,
+        evaluator: &mut dyn AsyncEvaluator<'b>,
+    ) -> io::Result<()> {
+        let stream = TcpStream::connect(addr)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        send(
+            &mut stream.try_clone()?,
+            &Hello {
+                token: token.to_string(),
+            },
+        )?;
+        while let Some(request) = receive(&mut reader)? {
+            let (actions, cut, best) = match request {
+                Request::Descend { actions, cut, best } => (actions, cut, best),
+                Request::Stop => break,
+                Request::Rejected => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "the coordinator rejected the token",
+                    ));
+                }
+            };
+            let mut writer = stream.try_clone()?;
+            let (actions, leaf) = match self.descend(actions, cut) {
+                Some(descent) => descent,
+                None => {
+                    send(&mut writer, &Response::deadend())?;
+                    continue;
+                }
+            };
+            let config = self.config;
+            let context = self.context;
+            let stabilizer = self.stabilizer;
+            let check_result_fn = self.check_result_fn;
+            evaluator.add_kernel(leaf.fix_order(), move |leaf, compiled| {
+                let response = match evaluate(
+                    config,
+                    context,
+                    stabilizer,
+                    check_result_fn,
+                    best,
+                    &leaf,
+                    compiled,
+                ) {
+                    Some((eval, energy)) => Response {
+                        actions: Some(actions),
+                        eval: Some(eval),
+                        energy,
+                    },
+                    None => Response::failure(actions),
+                };
+                if let Err(err) = send(&mut writer, &response) {
+                    error!("cannot send evaluation to the coordinator: {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the partial candidate obtained by applying `actions` to the root and
+    /// completes its descent with a rollout.  Returns the implementation reached along with
+    /// the actions leading to it from the root, or `None` if the descent reached a
+    /// dead-end.
+    fn descend(
+        &self,
+        mut actions: Vec<ActionEx>,
+        cut: Option<f64>,
+    ) -> Option<(Vec<ActionEx>, Candidate)> {
+        let prefix = match rebuild(self.context, self.root, &actions) {
+            Ok(prefix) => prefix,
+            Err(err) => {
+                warn!(
+                    "cannot rebuild the candidate sent by the coordinator: {}",
+                    err
+                );
+                return None;
+            }
+        };
+        let rollout = Rollout {
+            choice_order: &self.bandit_config.choice_ordering,
+            node_order: &self.bandit_config.new_nodes_order,
+            context: self.context,
+            cut: cut.unwrap_or(std::f64::INFINITY),
+        };
+        let prefix_depth = prefix.depth;
+        let leaf = if self.bandit_config.backtrack_deadends {
+            rollout.descend_backtrack(prefix)
+        } else {
+            rollout.descend(prefix)
+        }?;
+        // `Candidate::actions` lists the last action first.
+        let mut rollout_actions = leaf
+            .actions
+            .iter()
+            .take(leaf.depth - prefix_depth)
+            .cloned()
+            .collect::<Vec<_>>();
+        rollout_actions.reverse();
+        actions.extend(rollout_actions);
+        Some((actions, leaf))
+    }
+}
+
+/// Applies `actions` to `root`.
+fn rebuild(
+    context: &dyn Context,
+    root: &Candidate,
+    actions: &[ActionEx],
+) -> Result<Candidate, ActionError> {
+    actions.iter().try_fold(root.clone(), |cand, action| {
+        cand.apply_decision(context, action.clone())
+    })
+}
+
+/// Computes the score of a compiled candidate, along with its energy consumption if the
+/// objective requires it, and checks its results if it may be the best one.  Returns
+/// `None` if the candidate could not be evaluated or its results are invalid.
+fn evaluate(
+    config: &Config,
+    context: &dyn Context,
+    stabilizer: &Stabilizer,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+    best: Option<f64>,
+    leaf: &Candidate,
+    compiled: &mut dyn KernelEvaluator,
+) -> Option<(f64, Option<(f64, f64)>)> {
+    let result = stabilizer
+        .wrap(compiled)
+        .bound(Some(leaf.bound.value()))
+        .best(best)
+        .evaluate();
+    let mut eval = match result {
+        Some(eval) => eval,
+        None => {
+            error!(
+                "evaluation failed for actions {:?}, with kernel {}",
+                leaf.actions, compiled
+            );
+            return None;
+        }
+    };
+
+    let mut energy = None;
+    if config.objective.needs_energy() {
+        let runtime = eval;
+        let measured = compiled.evaluate_energy().map(|(_, e)| e);
+        eval = match config.objective.score(runtime, measured) {
+            Some(score) => score,
+            None => {
+                error!("the device does not support energy measurement");
+                return None;
+            }
+        };
+        energy = measured.map(|measured| (runtime, measured));
+    }
+
+    if let Some(check_result_fn) = check_result_fn {
+        if config.check_all || best.map_or(true, |best| eval < best) {
+            if let Err(err) = check_result_fn(leaf, context) {
+                error!(
+                    "Invalid results (score {:.4e}ns) for {}: {}",
+                    eval, leaf, err
+                );
+                return None;
+            }
+        }
+    }
+    Some((eval, energy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures tokens only match when they are equal.
+    #[test]
+    fn compare_tokens() {
+        assert!(same_token("secret", "secret"));
+        assert!(!same_token("secret", "secreT"));
+        assert!(!same_token("secret", "secret2"));
+        assert!(!same_token("", "secret"));
+    }
+
+    /// Ensures the coordinator reports invalid and busy addresses instead of panicking.
+    #[test]
+    fn listen_errors() {
+        assert!(listen("not an address").is_err());
+        let listener = unwrap!(listen("127.0.0.1:0"));
+        let addr = unwrap!(listener.local_addr()).to_string();
+        assert!(listen(&addr).is_err());
+    }
+
+    /// Ensures workers with an invalid token are rejected.
+    #[test]
+    fn reject_invalid_token() {
+        let listener = unwrap!(TcpListener::bind("127.0.0.1:0"));
+        let addr = unwrap!(listener.local_addr());
+        let client = std::thread::spawn(move || {
+            let stream = unwrap!(TcpStream::connect(addr));
+            let mut reader = BufReader::new(unwrap!(stream.try_clone()));
+            let hello = Hello {
+                token: "wrong".to_string(),
+            };
+            unwrap!(send(&mut unwrap!(stream.try_clone()), &hello));
+            unwrap!(receive::<Request>(&mut reader))
+        });
+        let (mut stream, _) = unwrap!(listener.accept());
+        let mut reader = BufReader::new(unwrap!(stream.try_clone()));
+        let err = authenticate(&mut reader, &mut stream, "secret").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        match unwrap!(client.join()) {
+            Some(Request::Rejected) => (),
+            _ => panic!("the worker was not notified of the rejection"),
+        }
+    }
+}
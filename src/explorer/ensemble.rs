@@ -0,0 +1,291 @@
+//! Runs several MCTS trees with different exploration constants on subsets of the workers.
+//!
+//! Each worker thread is assigned to a tree the first time it asks for a candidate.  When
+//! there are fewer workers than trees, workers instead explore the trees in turn so that
+//! every tree is explored.  The trees share the cut through `update_cut`.  Every `exchange_interval` evaluations, the
+//! best candidate found so far is sent to the trees that did not find it, which import it
+//! the next time one of their workers asks for a candidate.
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+use fxhash::FxHashMap;
+use log::{info, warn};
+use rpds::List;
+
+use crate::codegen::LaunchConfig;
+use crate::device::Context;
+use crate::explorer::candidate::Candidate;
+use crate::explorer::choice::ActionEx as Action;
+use crate::explorer::config::EnsembleConfig;
+use crate::explorer::mcts::{self, MctsStore, Reset};
+use crate::explorer::store::Store;
+
+/// The best candidate found by the ensemble.
+struct Best {
+    /// Index of the tree that found the candidate.
+    tree: usize,
+    actions: Vec<Action>,
+    eval: f64,
+}
+
+/// Tracks the candidates exchanged between the trees.
+struct Exchange {
+    num_evals: usize,
+    best: Option<Best>,
+    /// Evaluation of the last candidate sent to each tree.
+    sent: Vec<f64>,
+}
+
+/// A store that dispatches workers between several MCTS trees.
+pub struct EnsembleStore<'a, N, E> {
+    trees: Vec<MctsStore<'a, N, E>>,
+    config: &'a EnsembleConfig,
+    /// Tree explored by each worker thread.
+    workers: Mutex<FxHashMap<ThreadId, usize>>,
+    /// Indicates if workers explore the trees in turn instead of being assigned to one.
+    rotate: bool,
+    /// Number of candidates requested to the ensemble when workers explore the trees in
+    /// turn.
+    num_requests: AtomicUsize,
+    exchange: Mutex<Exchange>,
+    /// Candidate to import in each tree, with its evaluation.
+    pending: Vec<Mutex<Option<(Vec<Action>, f64)>>>,
+    num_imports: AtomicUsize,
+}
+
+impl<'a, N, E> EnsembleStore<'a, N, E> {
+    /// Creates an ensemble from trees built with the exploration constants of `config`, in
+    /// the same order, explored by `num_workers` threads.
+    pub fn new(
+        trees: Vec<MctsStore<'a, N, E>>,
+        config: &'a EnsembleConfig,
+        num_workers: usize,
+    ) -> Self {
+        assert!(!trees.is_empty(), "an ensemble must have at least one tree");
+        let num_trees = trees.len();
+        EnsembleStore {
+            trees,
+            config,
+            workers: Mutex::new(FxHashMap::default()),
+            rotate: num_workers < num_trees,
+            num_requests: AtomicUsize::new(0),
+            exchange: Mutex::new(Exchange {
+                num_evals: 0,
+                best: None,
+                sent: vec![std::f64::INFINITY; num_trees],
+            }),
+            pending: (0..num_trees).map(|_| Mutex::new(None)).collect(),
+            num_imports: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the tree explored by the current thread.
+    fn tree(&self) -> usize {
+        if self.rotate {
+            return self.num_requests.fetch_add(1, Ordering::Relaxed) % self.trees.len();
+        }
+        let mut workers = self.workers.lock().unwrap();
+        let tree = workers.len() % self.trees.len();
+        *workers.entry(thread::current().id()).or_insert(tree)
+    }
+
+    /// Schedules the import of the best candidate in the trees that did not find it.
+    fn share_best(&self, exchange: &mut Exchange) {
+        let best = if let Some(best) = &exchange.best {
+            best
+        } else {
+            return;
+        };
+        for (tree, sent) in exchange.sent.iter_mut().enumerate() {
+            if tree != best.tree && best.eval < *sent {
+                *sent = best.eval;
+                *self.pending[tree].lock().unwrap() =
+                    Some((best.actions.clone(), best.eval));
+            }
+        }
+    }
+}
+
+impl<'a, N, E> Store for EnsembleStore<'a, N, E>
+where
+    N: Send + Sync + Debug + Default + Reset,
+    E: Send + Sync + Debug + Default + Reset,
+{
+    type PayLoad = (usize, mcts::Payload<N, E>);
+
+    type Event = mcts::Message;
+
+    fn update_cut(&self, new_cut: f64) {
+        for tree in &self.trees {
+            tree.update_cut(new_cut);
+        }
+    }
+
+    fn stop_exploration(&self) {
+        for tree in &self.trees {
+            tree.stop_exploration();
+        }
+    }
+
+    fn commit_evaluation(
+        &self,
+        actions: &List<Action>,
+        (tree, payload): Self::PayLoad,
+        eval: f64,
+    ) {
+        self.trees[tree].commit_evaluation(actions, payload, eval);
+
+        let mut exchange = self.exchange.lock().unwrap();
+        exchange.num_evals += 1;
+        if eval.is_finite()
+            && exchange.best.as_ref().map_or(true, |best| eval < best.eval)
+        {
+            // `actions` lists the last action first.
+            let mut actions = actions.iter().cloned().collect::<Vec<_>>();
+            actions.reverse();
+            exchange.best = Some(Best {
+                tree,
+                actions,
+                eval,
+            });
+        }
+        if self.config.exchange_interval > 0
+            && exchange.num_evals % self.config.exchange_interval == 0
+        {
+            self.share_best(&mut exchange);
+        }
+    }
+
+    fn commit_provisional_evaluation(
+        &self,
+        actions: &List<Action>,
+        (tree, payload): Self::PayLoad,
+        eval: f64,
+    ) {
+        self.trees[tree].commit_provisional_evaluation(actions, payload, eval);
+
+        // Provisional values are too noisy to be shared with the other trees.
+        let mut exchange = self.exchange.lock().unwrap();
+        exchange.num_evals += 1;
+        if self.config.exchange_interval > 0
+            && exchange.num_evals % self.config.exchange_interval == 0
+        {
+            self.share_best(&mut exchange);
+        }
+    }
+
+    fn commit_screening(&self, (tree, payload): &Self::PayLoad, eval: f64) {
+        self.trees[*tree].commit_screening(payload, eval)
+    }
+
+    fn commit_launch(&self, (tree, payload): &Self::PayLoad, launch: &LaunchConfig) {
+        self.trees[*tree].commit_launch(payload, launch)
+    }
+
+    fn commit_source(&self, (tree, payload): &Self::PayLoad, source: &str) {
+        self.trees[*tree].commit_source(payload, source)
+    }
+
+    fn commit_energy(&self, (tree, payload): &Self::PayLoad, runtime: f64, energy: f64) {
+        self.trees[*tree].commit_energy(payload, runtime, energy)
+    }
+
+    fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)> {
+        let tree = self.tree();
+        let pending = self.pending[tree].lock().unwrap().take();
+        if let Some((actions, eval)) = pending {
+            if self.trees[tree].import(context, &actions, eval) {
+                info!("tree {} imported a candidate of {:.4e}ns", tree, eval);
+                self.num_imports.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.trees[tree]
+            .explore(context)
+            .map(|(candidate, payload)| (candidate, (tree, payload)))
+    }
+
+    fn print_stats(&self) {
+        for (tree, constant) in self.trees.iter().zip(&self.config.exploration_constants)
+        {
+            warn!("Tree with exploration constant {}:", constant);
+            tree.print_stats();
+        }
+        warn!(
+            "Candidates imported from other trees: {}",
+            self.num_imports.load(Ordering::Relaxed)
+        );
+    }
+
+    fn deadends(&self) -> Vec<(&'static str, usize)> {
+        let mut deadends = self.trees[0].deadends();
+        for tree in &self.trees[1..] {
+            for (total, (_, count)) in deadends.iter_mut().zip(tree.deadends()) {
+                total.1 += count;
+            }
+        }
+        deadends
+    }
+
+    fn restart(&self) {
+        for tree in &self.trees {
+            tree.restart();
+        }
+    }
+
+    fn needs_restart(&self) -> bool {
+        self.trees.iter().any(Store::needs_restart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Arc};
+
+    use crate::device::fake;
+    use crate::explorer::config::{BanditConfig, UCTConfig};
+    use crate::helper;
+    use crate::ir;
+
+    /// Ensures every tree is explored when there are fewer workers than trees.
+    #[test]
+    fn distribute_workers() {
+        let context = &fake::Context::<fake::Device>::default();
+        let signature = Arc::new(ir::Signature::new("test"));
+        let mut builder = helper::Builder::new(signature, context.device());
+        builder.mov(&0f32);
+        let space = builder.get();
+        let bandit_config = &BanditConfig::default();
+        let ensemble_config = &EnsembleConfig::default();
+        let (log_sender, _log_receiver) = mpsc::sync_channel(100);
+        let new_ensemble = move |num_workers| {
+            let trees = ensemble_config
+                .exploration_constants
+                .iter()
+                .map(|_| {
+                    MctsStore::<(), mcts::UCTStats>::new(
+                        space.clone(),
+                        context,
+                        bandit_config,
+                        Box::new(mcts::UCTPolicy::from(UCTConfig::default())),
+                        Box::new(bandit_config.new_nodes_order),
+                        log_sender.clone(),
+                    )
+                })
+                .collect();
+            EnsembleStore::new(trees, ensemble_config, num_workers)
+        };
+        let num_trees = ensemble_config.exploration_constants.len();
+
+        let ensemble = new_ensemble(1);
+        let trees = (0..num_trees).map(|_| ensemble.tree()).collect::<Vec<_>>();
+        assert_eq!(trees, (0..num_trees).collect::<Vec<_>>());
+
+        // With enough workers, each worker sticks to its tree.
+        let ensemble = new_ensemble(num_trees);
+        let tree = ensemble.tree();
+        assert!((0..num_trees).all(|_| ensemble.tree() == tree));
+    }
+}
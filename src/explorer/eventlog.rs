@@ -2,6 +2,8 @@ use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
 use flate2::{read, write, Compression};
 use utils::tfrecord;
@@ -46,6 +48,25 @@ impl EventLog {
         )))
     }
 
+    /// Opens an eventlog that may still be written to.  Instead of reaching the end of the
+    /// log, reads wait for new records, checking the file every `poll_interval`.
+    pub fn follow<P: AsRef<Path>>(
+        path: P,
+        poll_interval: Duration,
+    ) -> io::Result<tfrecord::Reader<Box<dyn Read + Send>>> {
+        let extension = path.as_ref().extension().and_then(OsStr::to_str);
+        let file = Follow {
+            file: File::open(path.as_ref())?,
+            poll_interval,
+        };
+        let reader: Box<dyn Read + Send> = match extension {
+            Some("gz") => Box::new(read::GzDecoder::new(file)),
+            Some("zz") => Box::new(read::ZlibDecoder::new(file)),
+            _ => Box::new(file),
+        };
+        Ok(tfrecord::Reader::from_reader(reader))
+    }
+
     pub fn create<P: AsRef<Path>>(path: P) -> io::Result<tfrecord::Writer<Self>> {
         let extension = path
             .as_ref()
@@ -95,3 +116,21 @@ impl Write for EventLog {
         }
     }
 }
+
+/// A file that waits for more data at its end, as `tail -f` does.
+struct Follow {
+    file: File,
+    poll_interval: Duration,
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let num_read = self.file.read(buf)?;
+            if num_read > 0 || buf.is_empty() {
+                return Ok(num_read);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Evolutionary exploration of the search space.
+//!
+//! The store maintains a population of implementations, each represented by the sequence of
+//! actions leading to it from a root of the search space. Offspring are generated by
+//! descending from the root of two parents selected by tournament: each choice is decided
+//! as in one of the parents, picked at random, or mutated to a random action with the
+//! configured probability. Actions that fail to apply or lead to a candidate whose bound
+//! is above the cut are rejected, so offspring are always valid implementations that may
+//! improve on the best one.
+//!
+//! Evaluated offspring are accumulated in a new generation. Once the new generation is
+//! complete, it replaces the population, except for the best individuals of the previous
+//! generation which are kept as-is.
+use crate::device::Context;
+use crate::explorer::annealing::apply_random;
+use crate::explorer::candidate::Candidate;
+use crate::explorer::choice::{self, ActionEx};
+use crate::explorer::config::EvolutionConfig;
+use crate::explorer::rng;
+use crate::explorer::store::Store;
+
+use fxhash::FxHashSet;
+use log::{info, warn};
+use rand::prelude::*;
+use rpds::List;
+use std::sync::Mutex;
+use utils::{cmp_f64, unwrap};
+
+/// An implementation, given by the actions applied to one of the roots of the store.
+#[derive(Clone)]
+pub struct Individual {
+    /// Index of the root in the store.
+    root: usize,
+    /// Actions leading from the root to the implementation, in application order.
+    actions: Vec<ActionEx>,
+}
+
+/// A `Store` implementing an evolutionary algorithm.
+pub struct EvolutionStore<'a> {
+    roots: Vec<Candidate>,
+    config: &'a EvolutionConfig,
+    state: Mutex<State>,
+    /// Generator from which each exploration step forks the generator of its random
+    /// choices.
+    rng: rng::SharedRng,
+}
+
+/// The mutable state of the evolution.
+struct State {
+    /// The current population, with the score of its individuals, sorted by score.
+    population: Vec<(Individual, f64)>,
+    /// The evaluated individuals of the next generation.
+    offspring: Vec<(Individual, f64)>,
+    /// Candidates with a bound above the cut are pruned.
+    cut: f64,
+    /// Indicates the exploration was stopped.
+    stopped: bool,
+    /// Number of generations replaced.
+    generation: usize,
+}
+
+impl<'a> EvolutionStore<'a> {
+    /// Creates a store exploring the implementations of `roots`.
+    pub fn new(roots: Vec<Candidate>, config: &'a EvolutionConfig) -> Self {
+        assert!(!roots.is_empty());
+        assert!(config.population_size > config.elitism);
+        EvolutionStore {
+            roots,
+            config,
+            state: Mutex::new(State {
+                population: vec![],
+                offspring: vec![],
+                cut: std::f64::INFINITY,
+                stopped: false,
+                generation: 0,
+            }),
+            rng: rng::SharedRng::default(),
+        }
+    }
+
+    /// Draws the random choices of the evolution from `rng` instead of a generator seeded
+    /// from the entropy of the current thread.
+    pub fn with_rng(mut self, rng: rng::ExplorerRng) -> Self {
+        self.rng = rng::SharedRng::new(rng);
+        self
+    }
+
+    /// Generates a child of `parents`, or returns `None` if the descent reaches a dead-end.
+    /// The parents must have the same root. If `parents` is empty, generates a random
+    /// implementation from a random root.
+    fn offspring(
+        &self,
+        context: &dyn Context,
+        parents: &[Individual],
+        cut: f64,
+        rng: &mut impl Rng,
+    ) -> Option<(Candidate, Individual)> {
+        let root = parents
+            .first()
+            .map_or_else(|| rng.gen_range(0, self.roots.len()), |parent| parent.root);
+        let genes = parents
+            .iter()
+            .map(|parent| parent.actions.iter().collect::<FxHashSet<_>>())
+            .collect::<Vec<_>>();
+        let mut cand = self.roots[root].clone();
+        if cand.bound.value() >= cut {
+            return None;
+        }
+        let mut actions = vec![];
+        while let Some(choice) = choice::default_list(&cand.space).next() {
+            let mut inherited = genes
+                .iter()
+                .flat_map(|genes| choice.iter().find(|action| genes.contains(action)))
+                .cloned()
+                .collect::<Vec<_>>();
+            if rng.gen::<f64>() < self.config.mutation_rate {
+                inherited.clear();
+            }
+            rng.shuffle(&mut inherited);
+            let next = inherited.into_iter().find_map(|action| {
+                cand.apply_decision(context, action)
+                    .ok()
+                    .filter(|next| next.bound.value() < cut)
+            });
+            cand = match next {
+                Some(next) => next,
+                None => apply_random(context, cand, choice, cut, rng)?,
+            };
+            actions.push(unwrap!(cand.actions.first()).clone());
+        }
+        Some((cand, Individual { root, actions }))
+    }
+
+    /// Selects an individual of the population by tournament, among the individuals
+    /// accepted by `filter`.
+    fn select<'b>(
+        &self,
+        population: &'b [(Individual, f64)],
+        filter: impl Fn(&Individual) -> bool,
+        rng: &mut impl Rng,
+    ) -> Option<&'b Individual> {
+        let candidates = population
+            .iter()
+            .filter(|(individual, _)| filter(individual))
+            .collect::<Vec<_>>();
+        (0..self.config.tournament_size)
+            .flat_map(|_| rng.choose(&candidates).cloned())
+            .min_by(|lhs, rhs| cmp_f64(lhs.1, rhs.1))
+            .map(|(individual, _)| individual)
+    }
+
+    /// Selects the parents of the next child. Returns an empty list while the population
+    /// is too small to select parents from.
+    fn select_parents(
+        &self,
+        population: &[(Individual, f64)],
+        rng: &mut impl Rng,
+    ) -> Vec<Individual> {
+        if population.len() < 2 {
+            return vec![];
+        }
+        let lhs = unwrap!(self.select(population, |_| true, rng));
+        let rhs = self
+            .select(population, |individual| individual.root == lhs.root, rng)
+            .unwrap_or(lhs);
+        vec![lhs.clone(), rhs.clone()]
+    }
+}
+
+impl<'a> Store for EvolutionStore<'a> {
+    type PayLoad = Individual;
+
+    type Event = ();
+
+    fn update_cut(&self, new_cut: f64) {
+        unwrap!(self.state.lock()).cut = new_cut;
+    }
+
+    fn stop_exploration(&self) {
+        unwrap!(self.state.lock()).stopped = true;
+    }
+
+    fn commit_evaluation(
+        &self,
+        _actions: &List<ActionEx>,
+        individual: Self::PayLoad,
+        eval: f64,
+    ) {
+        // Individuals without a meaningful score cannot be compared with the others.
+        if eval.is_nan() {
+            warn!("ignoring an individual with a NaN score");
+            return;
+        }
+        let mut state = unwrap!(self.state.lock());
+        state.offspring.push((individual, eval));
+        let population_size = self.config.population_size;
+        let num_elites = std::cmp::min(self.config.elitism, state.population.len());
+        if state.offspring.len() + num_elites < population_size {
+            return;
+        }
+        let mut population = std::mem::replace(&mut state.offspring, vec![]);
+        population.extend(state.population.drain(..num_elites));
+        population.sort_by(|lhs, rhs| cmp_f64(lhs.1, rhs.1));
+        population.truncate(population_size);
+        state.population = population;
+        state.generation += 1;
+        info!(
+            "generation {}: best score {:.4e}",
+            state.generation, state.population[0].1
+        );
+    }
+
+    fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)> {
+        let rng = &mut self.rng.fork();
+        let mut num_failures = 0;
+        loop {
+            let (parents, cut) = {
+                let state = unwrap!(self.state.lock());
+                if state.stopped {
+                    return None;
+                }
+                (self.select_parents(&state.population, rng), state.cut)
+            };
+            if let Some(child) = self.offspring(context, &parents, cut, rng) {
+                return Some(child);
+            }
+            num_failures += 1;
+            if num_failures >= self.config.max_attempts {
+                warn!("no offspring found below the cut, stopping the evolution");
+                return None;
+            }
+        }
+    }
+
+    fn print_stats(&self) {
+        let state = unwrap!(self.state.lock());
+        warn!(
+            "Evolution: {} generations, best score {:?}",
+            state.generation,
+            state.population.first().map(|(_, eval)| eval)
+        );
+    }
+
+    fn restart(&self) {
+        let mut state = unwrap!(self.state.lock());
+        state.population.clear();
+        state.offspring.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::fake;
+    use crate::helper;
+    use crate::ir;
+    use crate::model::bound;
+    use rand::XorShiftRng;
+    use std::sync::Arc;
+
+    /// Returns a root candidate with a single instruction.
+    fn root() -> Candidate {
+        let context = fake::Context::<fake::Device>::default();
+        let signature = Arc::new(ir::Signature::new("test".to_string()));
+        let mut builder = helper::Builder::new(signature, context.device());
+        builder.mov(&0f32);
+        let space = builder.get();
+        let bound = bound(&space, &context);
+        Candidate::new(space, bound)
+    }
+
+    /// Returns an individual of the root with index `root`.
+    fn individual(root: usize) -> Individual {
+        Individual {
+            root,
+            actions: vec![],
+        }
+    }
+
+    /// Ensures NaN scores are ignored instead of breaking the ordering of the population.
+    #[test]
+    fn ignore_nan_scores() {
+        let config = EvolutionConfig {
+            population_size: 2,
+            elitism: 1,
+            ..EvolutionConfig::default()
+        };
+        let store = EvolutionStore::new(vec![root()], &config);
+        for &eval in &[2., std::f64::NAN, 1.] {
+            store.commit_evaluation(&List::new(), individual(0), eval);
+        }
+        let state = unwrap!(store.state.lock());
+        assert_eq!(state.generation, 1);
+        let scores = state
+            .population
+            .iter()
+            .map(|&(_, eval)| eval)
+            .collect::<Vec<_>>();
+        assert_eq!(scores, vec![1., 2.]);
+    }
+
+    /// Ensures the tournament selects the best individual among the ones it draws, and
+    /// only draws individuals accepted by the filter.
+    #[test]
+    fn tournament_selection() {
+        let config = EvolutionConfig {
+            tournament_size: 32,
+            ..EvolutionConfig::default()
+        };
+        let store = EvolutionStore::new(vec![root()], &config);
+        let population = vec![
+            (individual(0), 3.),
+            (individual(1), 1.),
+            (individual(0), 2.),
+        ];
+        let rng = &mut XorShiftRng::from_seed([1; 16]);
+        let best = unwrap!(store.select(&population, |_| true, rng));
+        assert!(std::ptr::eq(best, &population[1].0));
+        let best = unwrap!(store.select(&population, |ind| ind.root == 0, rng));
+        assert!(std::ptr::eq(best, &population[2].0));
+        assert!(store
+            .select(&population, |ind| ind.root == 2, rng)
+            .is_none());
+    }
+}
@@ -0,0 +1,334 @@
+//! Summary of a candidate as a fixed set of numeric features.
+//!
+//! Features describe both partially and fully specified candidates: decisions that are
+//! still open are counted separately.  The schema only grows by appending fields, so that
+//! datasets and models built on previous versions remain readable.
+use std::collections::BTreeMap;
+
+use crate::device::Context;
+use crate::explorer::Candidate;
+use crate::ir::Statement;
+use crate::model::{self, BottleneckLevel, Bound, Origin};
+use crate::search_space::{DimKind, Domain, MemSpace, Order, SearchSpace};
+use serde::{Deserialize, Serialize};
+use utils::cmp_f64;
+
+/// Features of a candidate.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeatureVector {
+    /// Number of instructions in the kernel.
+    pub num_insts: usize,
+    /// Number of memory instructions in the kernel.
+    pub num_mem_insts: usize,
+    /// Number of dimensions in the kernel.
+    pub num_dims: usize,
+    /// Number of dimensions of each kind.
+    pub dim_kinds: DimKindCounts,
+    /// Maximal number of dimensions nested outside an instruction.
+    pub max_nesting_depth: usize,
+    /// Average number of dimensions nested outside an instruction.
+    pub mean_nesting_depth: f64,
+    /// Number of dimensions that are vectorized.
+    pub num_vector_dims: usize,
+    /// Largest possible size of a vectorized dimension, or zero if none is vectorized.
+    pub max_vector_width: u64,
+    /// Number of memory blocks in each memory space.
+    pub mem_spaces: MemSpaceCounts,
+    /// Features of the lower bound of the performance model, if it is known.
+    pub bound: Option<BoundFeatures>,
+    /// Pressure on the resources of the device according to the performance model, if it
+    /// is known.
+    pub pressure: Option<PressureFeatures>,
+}
+
+/// Number of dimensions of each kind.  Dimensions whose kind is not yet fixed are counted
+/// in `undecided`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DimKindCounts {
+    pub loops: usize,
+    pub unroll: usize,
+    pub inner_vector: usize,
+    pub outer_vector: usize,
+    pub block: usize,
+    pub thread: usize,
+    pub undecided: usize,
+}
+
+/// Number of memory blocks in each memory space.  Blocks whose memory space is not yet
+/// fixed are counted in `undecided`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemSpaceCounts {
+    pub global: usize,
+    pub shared: usize,
+    pub undecided: usize,
+}
+
+/// Features of the lower bound of the performance model.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoundFeatures {
+    /// Value of the bound, in nanoseconds.
+    pub value: f64,
+    /// The resource that limits the bound: either `latency` or the name of a hardware
+    /// bottleneck.
+    pub limiting_resource: Option<String>,
+    /// The level at which the limiting bottleneck is computed, if the bound is caused by a
+    /// bottleneck.
+    pub limiting_level: Option<BottleneckLevel>,
+}
+
+/// Pressure of a candidate on the resources of the whole device, expressed as the time the
+/// device needs to absorb it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PressureFeatures {
+    /// Time spent waiting on the latency of instructions, in nanoseconds.
+    pub latency: f64,
+    /// Time spent on each bottleneck of the device, in nanoseconds, indexed by the name of
+    /// the bottleneck.
+    pub bottlenecks: BTreeMap<String, f64>,
+}
+
+impl FeatureVector {
+    /// Returns the names of the values returned by `values`, in the same order.
+    pub fn names() -> Vec<&'static str> {
+        vec![
+            "num_insts",
+            "num_mem_insts",
+            "num_dims",
+            "dim_kinds.loops",
+            "dim_kinds.unroll",
+            "dim_kinds.inner_vector",
+            "dim_kinds.outer_vector",
+            "dim_kinds.block",
+            "dim_kinds.thread",
+            "dim_kinds.undecided",
+            "max_nesting_depth",
+            "mean_nesting_depth",
+            "num_vector_dims",
+            "max_vector_width",
+            "mem_spaces.global",
+            "mem_spaces.shared",
+            "mem_spaces.undecided",
+            "bound",
+            "pressure.latency",
+            "pressure.max_bottleneck",
+            "pressure.total_bottleneck",
+        ]
+    }
+
+    /// Flattens the numeric features into a vector, as expected by learned models.  The
+    /// values of the bound and of the pressure are `NaN` if they are unknown.
+    pub fn values(&self) -> Vec<f64> {
+        let kinds = &self.dim_kinds;
+        let spaces = &self.mem_spaces;
+        let pressure = self.pressure.as_ref();
+        vec![
+            self.num_insts as f64,
+            self.num_mem_insts as f64,
+            self.num_dims as f64,
+            kinds.loops as f64,
+            kinds.unroll as f64,
+            kinds.inner_vector as f64,
+            kinds.outer_vector as f64,
+            kinds.block as f64,
+            kinds.thread as f64,
+            kinds.undecided as f64,
+            self.max_nesting_depth as f64,
+            self.mean_nesting_depth,
+            self.num_vector_dims as f64,
+            self.max_vector_width as f64,
+            spaces.global as f64,
+            spaces.shared as f64,
+            spaces.undecided as f64,
+            self.bound
+                .as_ref()
+                .map_or(std::f64::NAN, |bound| bound.value),
+            pressure.map_or(std::f64::NAN, |pressure| pressure.latency),
+            pressure.map_or(std::f64::NAN, |pressure| {
+                pressure.bottlenecks.values().cloned().fold(0., f64::max)
+            }),
+            pressure.map_or(std::f64::NAN, |pressure| {
+                pressure.bottlenecks.values().sum()
+            }),
+        ]
+    }
+}
+
+/// Summarizes a search space.
+pub fn extract(space: &SearchSpace) -> FeatureVector {
+    let fun = space.ir_instance();
+    let domain = space.domain();
+    let mut features = FeatureVector::default();
+    features.num_insts = fun.insts().count();
+    features.num_mem_insts = fun
+        .insts()
+        .filter(|inst| inst.as_mem_inst().is_some())
+        .count();
+    features.num_dims = fun.dims().count();
+    for dim in fun.dims() {
+        let kind = domain.get_dim_kind(dim.id());
+        let counts = &mut features.dim_kinds;
+        match kind {
+            DimKind::LOOP => counts.loops += 1,
+            DimKind::UNROLL => counts.unroll += 1,
+            DimKind::INNER_VECTOR => counts.inner_vector += 1,
+            DimKind::OUTER_VECTOR => counts.outer_vector += 1,
+            DimKind::BLOCK => counts.block += 1,
+            DimKind::THREAD => counts.thread += 1,
+            _ => counts.undecided += 1,
+        }
+        if DimKind::VECTOR.contains(kind) {
+            features.num_vector_dims += 1;
+            if dim.possible_sizes().is_some() {
+                let width = model::size::dim_bounds(dim.id(), space).max;
+                features.max_vector_width =
+                    std::cmp::max(features.max_vector_width, width);
+            }
+        }
+    }
+    let depths = fun
+        .insts()
+        .map(|inst| {
+            fun.dims()
+                .filter(|dim| {
+                    domain.get_order(dim.stmt_id(), inst.stmt_id()) == Order::OUTER
+                })
+                .count()
+        })
+        .collect::<Vec<_>>();
+    features.max_nesting_depth = depths.iter().cloned().max().unwrap_or(0);
+    if !depths.is_empty() {
+        let total = depths.iter().sum::<usize>();
+        features.mean_nesting_depth = total as f64 / depths.len() as f64;
+    }
+    for block in fun.mem_blocks() {
+        let counts = &mut features.mem_spaces;
+        match domain.get_mem_space(block.mem_id()) {
+            MemSpace::GLOBAL => counts.global += 1,
+            MemSpace::SHARED => counts.shared += 1,
+            _ => counts.undecided += 1,
+        }
+    }
+    features
+}
+
+/// Summarizes a search space, including the pressure it puts on the device of `context`.
+/// `bound` is the lower bound of the performance model for `space`, if it is known.
+pub fn extract_with_model(
+    space: &SearchSpace,
+    bound: Option<&Bound>,
+    context: &dyn Context,
+) -> FeatureVector {
+    FeatureVector {
+        bound: bound.map(BoundFeatures::from_bound),
+        pressure: Some(PressureFeatures::compute(space, context)),
+        ..extract(space)
+    }
+}
+
+/// Summarizes a candidate, including its lower bound and the pressure it puts on the
+/// device of `context`.
+pub fn extract_candidate(candidate: &Candidate, context: &dyn Context) -> FeatureVector {
+    extract_with_model(&candidate.space, Some(&candidate.bound), context)
+}
+
+impl BoundFeatures {
+    /// Extracts the features of a bound.
+    pub fn from_bound(bound: &Bound) -> Self {
+        let limit = limiting_resource(bound.origin());
+        let (limiting_resource, limiting_level) = match limit {
+            Some((name, level)) => (Some(name), level),
+            None => (None, None),
+        };
+        BoundFeatures {
+            value: bound.value(),
+            limiting_resource,
+            limiting_level,
+        }
+    }
+}
+
+impl PressureFeatures {
+    /// Computes the pressure of `space` with the performance model of `context`.
+    pub fn compute(space: &SearchSpace, context: &dyn Context) -> Self {
+        let device = context.device();
+        let pressure = model::global_pressure(space, context);
+        let rates = device.total_rates();
+        let bottlenecks = device
+            .bottlenecks()
+            .iter()
+            .zip(pressure.bottlenecks().iter().zip(rates.bottlenecks()))
+            .map(|(name, (&pressure, &rate))| (name.to_string(), pressure / rate))
+            .collect();
+        PressureFeatures {
+            latency: pressure.latency() / rates.latency(),
+            bottlenecks,
+        }
+    }
+}
+
+/// Finds the resource that limits a bound, along with the level of the bottleneck.
+fn limiting_resource(origin: &Origin) -> Option<(String, Option<BottleneckLevel>)> {
+    match origin {
+        Origin::Latency => Some(("latency".to_string(), None)),
+        Origin::Bottleneck(name, level) => Some((name.to_string(), Some(*level))),
+        Origin::Loop { inner, .. } | Origin::Scale { inner, .. } => {
+            limiting_resource(inner)
+        }
+        Origin::Chain { before, after, .. } => {
+            largest_limiting_resource(vec![&**before, &**after])
+        }
+        Origin::Fission { kernels, .. } => largest_limiting_resource(kernels),
+        Origin::HardwareEvaluation => None,
+    }
+}
+
+/// Finds the resource that limits the largest of `bounds`.
+fn largest_limiting_resource<'a, IT>(
+    bounds: IT,
+) -> Option<(String, Option<BottleneckLevel>)>
+where
+    IT: IntoIterator<Item = &'a Bound>,
+{
+    bounds
+        .into_iter()
+        .max_by(|lhs, rhs| cmp_f64(lhs.value(), rhs.value()))
+        .and_then(|bound| limiting_resource(bound.origin()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::fake;
+    use crate::helper;
+    use crate::ir;
+    use std::sync::Arc;
+
+    /// Ensures the pressure is reported for each bottleneck of the device and that the
+    /// flattened features match their names.
+    #[test]
+    fn extract_pressure() {
+        let context = fake::Context::<fake::Device>::default();
+        let signature = Arc::new(ir::Signature::new("features".to_string()));
+        let mut builder = helper::Builder::new(signature, context.device());
+        builder.mov(&0f32);
+        let space = builder.get();
+        let candidate = Candidate::new(space.clone(), model::bound(&space, &context));
+
+        let features = extract(&space);
+        assert_eq!(features.values().len(), FeatureVector::names().len());
+        assert!(features.values().last().unwrap().is_nan());
+
+        let features = extract_candidate(&candidate, &context);
+        assert_eq!(features.values().len(), FeatureVector::names().len());
+        let pressure = features.pressure.as_ref().unwrap();
+        let names = pressure
+            .bottlenecks
+            .keys()
+            .map(|s| &s[..])
+            .collect::<Vec<_>>();
+        let mut expected = context.device().bottlenecks().to_vec();
+        expected.sort();
+        assert_eq!(names, expected);
+        assert!(pressure.latency >= 0.);
+    }
+}
@@ -0,0 +1,247 @@
+//! Explores several root candidates, with one store per root.
+//!
+//! The forest acts as a virtual root above the roots: each call to `explore` asks the
+//! next store, in round-robin order, for a candidate and falls back to the other stores
+//! when it is exhausted.  The stores share the cut through `update_cut`.
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::warn;
+use rpds::List;
+
+use crate::codegen::LaunchConfig;
+use crate::device::Context;
+use crate::explorer::candidate::Candidate;
+use crate::explorer::choice::ActionEx as Action;
+use crate::explorer::store::{Checkpoint, Store};
+
+/// A store that dispatches the descents between the stores of several roots.
+pub struct ForestStore<S> {
+    trees: Vec<S>,
+    /// Index of the store to explore next, modulo the number of stores.
+    next: AtomicUsize,
+}
+
+impl<S> ForestStore<S> {
+    /// Creates a forest from the stores of each root.
+    pub fn new(trees: Vec<S>) -> Self {
+        assert!(!trees.is_empty(), "a forest must have at least one tree");
+        ForestStore {
+            trees,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the store when there is a single root.
+    fn single(&self) -> Option<&S> {
+        if self.trees.len() == 1 {
+            self.trees.first()
+        } else {
+            None
+        }
+    }
+}
+
+fn unsupported_checkpoint() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "checkpoints are not supported with several root candidates",
+    )
+}
+
+impl<S: Store> Store for ForestStore<S> {
+    type PayLoad = (usize, S::PayLoad);
+
+    type Event = S::Event;
+
+    fn update_cut(&self, new_cut: f64) {
+        for tree in &self.trees {
+            tree.update_cut(new_cut);
+        }
+    }
+
+    fn stop_exploration(&self) {
+        for tree in &self.trees {
+            tree.stop_exploration();
+        }
+    }
+
+    fn commit_evaluation(
+        &self,
+        actions: &List<Action>,
+        (tree, payload): Self::PayLoad,
+        eval: f64,
+    ) {
+        self.trees[tree].commit_evaluation(actions, payload, eval)
+    }
+
+    fn commit_provisional_evaluation(
+        &self,
+        actions: &List<Action>,
+        (tree, payload): Self::PayLoad,
+        eval: f64,
+    ) {
+        self.trees[tree].commit_provisional_evaluation(actions, payload, eval)
+    }
+
+    fn commit_screening(&self, (tree, payload): &Self::PayLoad, eval: f64) {
+        self.trees[*tree].commit_screening(payload, eval)
+    }
+
+    fn commit_launch(&self, (tree, payload): &Self::PayLoad, launch: &LaunchConfig) {
+        self.trees[*tree].commit_launch(payload, launch)
+    }
+
+    fn commit_source(&self, (tree, payload): &Self::PayLoad, source: &str) {
+        self.trees[*tree].commit_source(payload, source)
+    }
+
+    fn commit_energy(&self, (tree, payload): &Self::PayLoad, runtime: f64, energy: f64) {
+        self.trees[*tree].commit_energy(payload, runtime, energy)
+    }
+
+    fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)> {
+        let num_trees = self.trees.len();
+        let first = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..num_trees)
+            .map(|offset| (first + offset) % num_trees)
+            .filter_map(|tree| {
+                self.trees[tree]
+                    .explore(context)
+                    .map(|(candidate, payload)| (candidate, (tree, payload)))
+            })
+            .next()
+    }
+
+    fn explore_prefix(
+        &self,
+        context: &dyn Context,
+    ) -> Option<(Vec<Action>, Candidate, Self::PayLoad)> {
+        // Workers only know the root of a single tree.
+        self.single()?
+            .explore_prefix(context)
+            .map(|(actions, candidate, payload)| (actions, candidate, (0, payload)))
+    }
+
+    fn print_stats(&self) {
+        if let Some(tree) = self.single() {
+            return tree.print_stats();
+        }
+        for (index, tree) in self.trees.iter().enumerate() {
+            warn!("Tree of root candidate {}:", index);
+            tree.print_stats();
+        }
+    }
+
+    fn deadends(&self) -> Vec<(&'static str, usize)> {
+        let mut deadends = self.trees[0].deadends();
+        for tree in &self.trees[1..] {
+            for (total, (_, count)) in deadends.iter_mut().zip(tree.deadends()) {
+                total.1 += count;
+            }
+        }
+        deadends
+    }
+
+    fn restart(&self) {
+        for tree in &self.trees {
+            tree.restart();
+        }
+    }
+
+    fn needs_restart(&self) -> bool {
+        self.trees.iter().any(Store::needs_restart)
+    }
+
+    fn checkpoint(&self, since: usize) -> io::Result<Checkpoint> {
+        self.single()
+            .ok_or_else(unsupported_checkpoint)
+            .and_then(|tree| tree.checkpoint(since))
+    }
+
+    fn restore(
+        &self,
+        context: &dyn Context,
+        checkpoint: &Checkpoint,
+    ) -> io::Result<Vec<(Candidate, f64, bool, Self::PayLoad)>> {
+        let tree = self.single().ok_or_else(unsupported_checkpoint)?;
+        Ok(tree
+            .restore(context, checkpoint)?
+            .into_iter()
+            .map(|(candidate, eval, provisional, payload)| {
+                (candidate, eval, provisional, (0, payload))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::fake;
+    use crate::helper;
+    use crate::ir;
+    use crate::model::bound;
+    use crate::search_space::SearchSpace;
+    use std::sync::{Arc, Mutex};
+
+    /// A store that returns its root `remaining` times.
+    struct Countdown {
+        space: SearchSpace,
+        remaining: Mutex<usize>,
+    }
+
+    impl Countdown {
+        fn new(remaining: usize) -> Self {
+            let device = Arc::new(fake::Device::default());
+            let signature = Arc::new(ir::Signature::new("test".to_string()));
+            let mut builder = helper::Builder::new(signature, device);
+            builder.mov(&0f32);
+            Countdown {
+                space: builder.get(),
+                remaining: Mutex::new(remaining),
+            }
+        }
+    }
+
+    impl Store for Countdown {
+        type PayLoad = ();
+
+        type Event = ();
+
+        fn update_cut(&self, _: f64) {}
+
+        fn commit_evaluation(&self, _: &List<Action>, _: (), _: f64) {}
+
+        fn explore(&self, context: &dyn Context) -> Option<(Candidate, ())> {
+            let mut remaining = self.remaining.lock().unwrap();
+            if *remaining == 0 {
+                return None;
+            }
+            *remaining -= 1;
+            let bound = bound(&self.space, context);
+            Some((Candidate::new(self.space.clone(), bound), ()))
+        }
+    }
+
+    /// Ensures the forest alternates between the roots and falls back to the roots that
+    /// are not exhausted.
+    #[test]
+    fn explore_all_roots() {
+        let _ = ::env_logger::try_init();
+        let context = fake::Context::<fake::Device>::default();
+        let forest = ForestStore::new(vec![Countdown::new(1), Countdown::new(3)]);
+        let mut trees = Vec::new();
+        while let Some((_, (tree, ()))) = forest.explore(&context) {
+            trees.push(tree);
+        }
+        assert_eq!(trees, vec![0, 1, 1, 1]);
+    }
+
+    /// Ensures checkpoints are only supported with a single root.
+    #[test]
+    fn checkpoint_several_roots() {
+        let forest = ForestStore::new(vec![Countdown::new(1), Countdown::new(1)]);
+        assert!(forest.checkpoint(0).is_err());
+    }
+}
@@ -32,7 +32,8 @@ impl<'a> Rollout<'a> {
     /// Repeatedly perform rollout steps on the `candidate` until it is fully specified,
     /// backtracking when deadends are reached.  Returns `None` if the whole subtree is dead.
     pub fn descend_backtrack(&self, candidate: Candidate) -> Option<Candidate> {
-        let choice = choice::list(self.choice_order, &candidate.space).next();
+        let groups = self.choice_order.at_depth(candidate.depth);
+        let choice = choice::list(groups, &candidate.space).next();
         if let Some(choice) = choice {
             let mut children = choice
                 .into_iter()
@@ -78,7 +79,8 @@ impl<'a> Rollout<'a> {
     /// Perform one rollout step: select a set of actions according to the choice ordering, apply
     /// them, and select among the resulting candidates according to the rollout policy.
     fn step(&self, candidate: &Candidate) -> Result<Candidate, RolloutError> {
-        if let Some(choice) = choice::list(self.choice_order, &candidate.space).next() {
+        let groups = self.choice_order.at_depth(candidate.depth);
+        if let Some(choice) = choice::list(groups, &candidate.space).next() {
             let mut children = candidate.apply_choice(self.context, choice);
             if let Some(idx) = self.node_order.pick_candidate(&children, self.cut) {
                 Ok(children.swap_remove(idx))
@@ -148,21 +150,45 @@ impl NewNodeOrder {
     /// Called in montecarlo_descend, dispatch the choice of the next candidate according to our
     /// configuration
     pub fn pick_candidate(self, new_nodes: &[Candidate], cut: f64) -> Option<usize> {
+        self.pick_candidate_with_rng(new_nodes, cut, &mut thread_rng())
+    }
+
+    /// Same as `pick_candidate`, but draws random numbers from `rng`.
+    pub fn pick_candidate_with_rng<R: Rng>(
+        self,
+        new_nodes: &[Candidate],
+        cut: f64,
+        rng: &mut R,
+    ) -> Option<usize> {
         let items = new_nodes.iter().map(|c| c.bound.value()).enumerate();
-        self.pick_index(items, cut)
+        self.pick_index_with_rng(items, cut, rng)
     }
 
     /// Returns the index of the next candidate to consider.
     pub fn pick_index<IT>(self, nodes: IT, cut: f64) -> Option<usize>
     where
         IT: Iterator<Item = (usize, f64)> + Clone,
+    {
+        self.pick_index_with_rng(nodes, cut, &mut thread_rng())
+    }
+
+    /// Same as `pick_index`, but draws random numbers from `rng`.
+    pub fn pick_index_with_rng<IT, R>(
+        self,
+        nodes: IT,
+        cut: f64,
+        rng: &mut R,
+    ) -> Option<usize>
+    where
+        IT: Iterator<Item = (usize, f64)> + Clone,
+        R: Rng,
     {
         let mut nodes = nodes.filter(|&(_, b)| b < cut);
         match self {
             NewNodeOrder::Api => nodes.next().map(|(idx, _)| idx),
-            NewNodeOrder::WeightedRandom => choose_cand_weighted(nodes, cut),
+            NewNodeOrder::WeightedRandom => choose_cand_weighted(nodes, cut, rng),
             NewNodeOrder::Bound => choose_cand_best(nodes),
-            NewNodeOrder::Random => choose_cand_rand(nodes),
+            NewNodeOrder::Random => choose_cand_rand(nodes, rng),
         }
     }
 }
@@ -177,26 +203,27 @@ where
 }
 
 /// Given a vector of candidate reference, just choose randomly the next candidate
-fn choose_cand_rand<IT>(mut nodes: IT) -> Option<usize>
+fn choose_cand_rand<IT, R>(mut nodes: IT, rng: &mut R) -> Option<usize>
 where
     IT: Iterator<Item = (usize, f64)> + Clone,
+    R: Rng,
 {
     let len = nodes.clone().count();
     if len == 0 {
         None
     } else {
-        nodes.nth(thread_rng().gen_range(0, len)).map(|x| x.0)
+        nodes.nth(rng.gen_range(0, len)).map(|x| x.0)
     }
 }
 
 /// Given a vector of candidate references, returns the index of a weighted sort on the candidate
 /// bounds
-fn choose_cand_weighted<IT>(nodes: IT, cut: f64) -> Option<usize>
+fn choose_cand_weighted<IT, R>(nodes: IT, cut: f64, rng: &mut R) -> Option<usize>
 where
     IT: Iterator<Item = (usize, f64)> + Clone,
+    R: Rng,
 {
     let mut weighted_items = vec![];
-    let mut rng = thread_rng();
     let max_bound = nodes
         .clone()
         .max_by(|&x1, &x2| cmp_f64(x1.1, x2.1))
@@ -223,6 +250,6 @@ where
     if weighted_items.is_empty() {
         None
     } else {
-        Some(WeightedChoice::new(&mut weighted_items).sample(&mut rng))
+        Some(WeightedChoice::new(&mut weighted_items).sample(rng))
     }
 }
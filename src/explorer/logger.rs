@@ -1,13 +1,20 @@
-use std::fs::File;
-use std::io::{self, BufWriter, Write};
-use std::sync::mpsc;
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::explorer::config::Config;
 use crate::explorer::monitor;
 use bincode;
+use crossbeam;
 use failure::Fail;
+use log::warn;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use utils::unwrap;
 
 #[derive(Serialize, Deserialize)]
 pub enum LogMessage<E> {
@@ -24,6 +31,53 @@ pub enum LogMessage<E> {
     },
 }
 
+/// A record of the JSON log.  Records are written one per line so that external tools can
+/// follow the progress of the search without decoding the binary event log.  Timestamps are
+/// in seconds since the start of the search and runtimes in nanoseconds.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JsonRecord {
+    /// A candidate was evaluated.  `runtime` is `None` if the evaluation failed or the
+    /// candidate was cut.
+    Evaluation {
+        id: u64,
+        runtime: Option<f64>,
+        timestamp: f64,
+    },
+    /// The energy consumed by a candidate was measured, in nanojoules.  The score of the
+    /// candidate is given by the `Evaluation` record with the same `id`.
+    Energy {
+        id: u64,
+        runtime: f64,
+        energy: f64,
+        timestamp: f64,
+    },
+    /// A new best candidate was found.
+    NewBest {
+        runtime: f64,
+        num_evaluations: usize,
+        timestamp: f64,
+    },
+    /// A node of the search was found to be a dead-end.
+    Deadend { cause: &'static str, timestamp: f64 },
+    /// The search stopped.
+    Finished {
+        reason: String,
+        num_evaluations: usize,
+        timestamp: f64,
+    },
+}
+
+/// An event of the search that can be summarized in the JSON log.
+pub trait StructuredEvent {
+    /// Returns the records describing the event in the JSON log.
+    fn json_records(&self) -> Vec<JsonRecord> {
+        vec![]
+    }
+}
+
+impl StructuredEvent for () {}
+
 #[derive(Debug, Fail)]
 pub enum LogError {
     #[fail(display = "{}", _0)]
@@ -52,17 +106,88 @@ impl From<mpsc::RecvError> for LogError {
     }
 }
 
-pub fn log<E: Send + Serialize>(
+/// Writes the messages received on `recv` to the log files until all senders are dropped.
+///
+/// Events are written to disk as they are received so that the memory used by the logger does
+/// not grow with the length of the search.  The logs are flushed every
+/// `config.log_flush_interval` seconds, even if messages keep coming.  If `config.logging`
+/// enables it, events are also written as JSON records.
+///
+/// A dedicated thread empties `recv` as soon as messages arrive, so that the search
+/// threads do not wait for the log files to be written.  Messages are buffered in
+/// memory, up to `config.log_buffer_size` of them, and in a temporary file past that.
+pub fn log<E>(
     config: &Config,
     recv: mpsc::Receiver<LogMessage<E>>,
-) -> Result<(), LogError> {
+) -> Result<(), LogError>
+where
+    E: Send + Serialize + DeserializeOwned + StructuredEvent,
+{
+    if !(config.log_flush_interval > 0. && config.log_flush_interval.is_finite()) {
+        let message =
+            format!("invalid log flush interval: {}", config.log_flush_interval);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+    let queue = SpillQueue::new(config.log_buffer_size);
+    crossbeam::scope(|scope| {
+        scope.spawn(|_| {
+            for message in recv {
+                if !queue.push(message) {
+                    break;
+                }
+            }
+            queue.close();
+        });
+        let result = write_messages(config, &queue);
+        // Stop buffering messages if the logs cannot be written anymore.
+        queue.close();
+        result
+    })
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Writes the messages of `queue` to the log files until it is closed.
+fn write_messages<E>(
+    config: &Config,
+    queue: &SpillQueue<LogMessage<E>>,
+) -> Result<(), LogError>
+where
+    E: Serialize + DeserializeOwned + StructuredEvent,
+{
+    let flush_interval = Duration::from_secs_f64(config.log_flush_interval);
     let mut record_writer = config.create_eventlog()?;
     let mut write_buffer = config.create_log()?;
-    while let Ok(message) = recv.recv() {
+    let mut json_sinks = config.create_json_logs()?;
+    let mut next_flush = Instant::now() + flush_interval;
+    let mut needs_flush = false;
+    loop {
+        let now = Instant::now();
+        if now >= next_flush {
+            if needs_flush {
+                if let Some(writer) = &mut record_writer {
+                    writer.flush()?;
+                }
+                flush_json(&mut json_sinks);
+                needs_flush = false;
+            }
+            next_flush = now + flush_interval;
+        }
+        let message = match queue.pop(next_flush)? {
+            Pop::Message(message) => message,
+            Pop::Timeout => continue,
+            Pop::Closed => break,
+        };
         match message {
             LogMessage::Event(event) => {
                 if let Some(writer) = &mut record_writer {
                     writer.write_record(&bincode::serialize(&event)?)?;
+                    needs_flush = true;
+                }
+                if !json_sinks.is_empty() {
+                    for record in event.json_records() {
+                        write_json(&mut json_sinks, &record)?;
+                        needs_flush = true;
+                    }
                 }
             }
             LogMessage::NewBest {
@@ -71,6 +196,13 @@ pub fn log<E: Send + Serialize>(
                 timestamp,
             } => {
                 log_monitor(score, cpt, timestamp, &mut write_buffer);
+                let record = JsonRecord::NewBest {
+                    runtime: score,
+                    num_evaluations: cpt,
+                    timestamp: timestamp.as_secs_f64(),
+                };
+                write_json(&mut json_sinks, &record)?;
+                flush_json(&mut json_sinks);
             }
             LogMessage::Finished {
                 reason,
@@ -92,6 +224,13 @@ pub fn log<E: Send + Serialize>(
                     num_evaluations as f64 / (timestamp.as_nanos() as f64 * 1e-9),
                 )?;
                 writeln!(write_buffer, "{}", reason)?;
+                let record = JsonRecord::Finished {
+                    reason: reason.to_string(),
+                    num_evaluations,
+                    timestamp: timestamp.as_secs_f64(),
+                };
+                write_json(&mut json_sinks, &record)?;
+                flush_json(&mut json_sinks);
             }
         }
         // Flush after writing a message to ensure the log file does not end up empty in case of a
@@ -105,9 +244,202 @@ pub fn log<E: Send + Serialize>(
             .finish()?
             .flush()?;
     }
+    flush_json(&mut json_sinks);
     Ok(())
 }
 
+/// Writes a record on a line of each JSON log.
+fn write_json(
+    sinks: &mut Vec<Box<dyn Write + Send>>,
+    record: &JsonRecord,
+) -> io::Result<()> {
+    if sinks.is_empty() {
+        return Ok(());
+    }
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    retain_json(sinks, |sink| sink.write_all(&line));
+    Ok(())
+}
+
+fn flush_json(sinks: &mut Vec<Box<dyn Write + Send>>) {
+    retain_json(sinks, |sink| sink.flush());
+}
+
+/// Applies `op` to each JSON log and drops the logs on which it fails, so that an external
+/// consumer closing its socket does not stop the search.
+fn retain_json<F>(sinks: &mut Vec<Box<dyn Write + Send>>, mut op: F)
+where
+    F: FnMut(&mut dyn Write) -> io::Result<()>,
+{
+    let mut idx = 0;
+    while idx < sinks.len() {
+        if let Err(err) = op(&mut *sinks[idx]) {
+            warn!("dropping a JSON log after an error: {}", err);
+            sinks.remove(idx);
+        } else {
+            idx += 1;
+        }
+    }
+}
+
+/// The result of `SpillQueue::pop`.
+enum Pop<T> {
+    /// The oldest message of the queue.
+    Message(T),
+    /// No message arrived before the deadline.
+    Timeout,
+    /// The queue is closed and empty.
+    Closed,
+}
+
+/// A FIFO queue that keeps up to `capacity` messages in memory and spills the others to a
+/// temporary file, so that pushing a message never waits for the consumer.
+struct SpillQueue<T> {
+    state: Mutex<SpillState<T>>,
+    not_empty: Condvar,
+}
+
+struct SpillState<T> {
+    /// The oldest messages of the queue.
+    memory: VecDeque<T>,
+    capacity: usize,
+    /// The messages pushed once `memory` was full, in order.  While the file is not
+    /// empty, new messages are appended to it to preserve the order of the queue.
+    spill: Option<SpillFile>,
+    /// An error that occured while spilling messages, reported by `pop`.
+    error: Option<LogError>,
+    closed: bool,
+}
+
+impl<T: Serialize + DeserializeOwned> SpillQueue<T> {
+    fn new(capacity: usize) -> Self {
+        SpillQueue {
+            state: Mutex::new(SpillState {
+                memory: VecDeque::new(),
+                capacity,
+                spill: None,
+                error: None,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+        }
+    }
+
+    /// Adds a message at the end of the queue.  Returns `false` if the queue is closed or
+    /// if the message could not be spilled, in which case the error is reported by `pop`.
+    fn push(&self, message: T) -> bool {
+        let mut state = unwrap!(self.state.lock());
+        if state.closed {
+            return false;
+        }
+        if state.spill.is_none() && state.memory.len() < state.capacity {
+            state.memory.push_back(message);
+        } else if let Err(err) = state.spill(&message) {
+            state.error = Some(err);
+            state.closed = true;
+        }
+        self.not_empty.notify_one();
+        !state.closed
+    }
+
+    /// Removes the oldest message of the queue, waiting for one until `deadline`.
+    fn pop(&self, deadline: Instant) -> Result<Pop<T>, LogError> {
+        let mut state = unwrap!(self.state.lock());
+        loop {
+            if let Some(message) = state.memory.pop_front() {
+                return Ok(Pop::Message(message));
+            }
+            if state.spill.is_some() {
+                state.unspill()?;
+                continue;
+            }
+            if let Some(err) = state.error.take() {
+                return Err(err);
+            }
+            if state.closed {
+                return Ok(Pop::Closed);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(Pop::Timeout);
+            }
+            state = unwrap!(self.not_empty.wait_timeout(state, deadline - now)).0;
+        }
+    }
+
+    /// Prevents new messages from being pushed.  The messages already in the queue can
+    /// still be popped.
+    fn close(&self) {
+        unwrap!(self.state.lock()).closed = true;
+        self.not_empty.notify_one();
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SpillState<T> {
+    /// Appends a message to the spill file, creating it if needed.
+    fn spill(&mut self, message: &T) -> Result<(), LogError> {
+        if self.spill.is_none() {
+            self.spill = Some(SpillFile::create()?);
+        }
+        let spill = unwrap!(self.spill.as_mut());
+        bincode::serialize_into(&mut spill.writer, message)?;
+        spill.len += 1;
+        Ok(())
+    }
+
+    /// Moves the oldest spilled messages back to memory.  The spill file is removed once
+    /// all its messages are read.
+    fn unspill(&mut self) -> Result<(), LogError> {
+        let spill = unwrap!(self.spill.as_mut());
+        spill.writer.flush()?;
+        let num_messages = std::cmp::min(spill.len, std::cmp::max(self.capacity, 1));
+        for _ in 0..num_messages {
+            self.memory
+                .push_back(bincode::deserialize_from(&mut spill.reader)?);
+        }
+        spill.len -= num_messages;
+        if spill.len == 0 {
+            self.spill = None;
+        }
+        Ok(())
+    }
+}
+
+/// A temporary file holding serialized messages.  The file is removed when dropped.
+struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    /// Number of messages written but not yet read.
+    len: usize,
+}
+
+impl SpillFile {
+    fn create() -> io::Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let name = format!("telamon-log-{}-{}", std::process::id(), id);
+        let path = std::env::temp_dir().join(name);
+        let writer = BufWriter::new(File::create(&path)?);
+        let reader = BufReader::new(File::open(&path)?);
+        Ok(SpillFile {
+            path,
+            writer,
+            reader,
+            len: 0,
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("cannot remove {}: {}", self.path.display(), err);
+        }
+    }
+}
+
 fn log_monitor(
     score: f64,
     cpt: usize,
@@ -125,3 +457,124 @@ fn log_monitor(
     );
     write_buffer.write_all(message.as_bytes()).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A JSON log that keeps the written bytes in memory.
+    #[derive(Clone, Default)]
+    struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedSink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A JSON log whose consumer went away.
+    struct ClosedSink;
+
+    impl Write for ClosedSink {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    /// Ensures a failing JSON log is dropped without affecting the other logs.
+    #[test]
+    fn drop_failed_json_sink() {
+        let shared = SharedSink::default();
+        let mut sinks: Vec<Box<dyn Write + Send>> =
+            vec![Box::new(ClosedSink), Box::new(shared.clone())];
+        let record = JsonRecord::NewBest {
+            runtime: 1.,
+            num_evaluations: 1,
+            timestamp: 1.,
+        };
+        write_json(&mut sinks, &record).unwrap();
+        assert_eq!(sinks.len(), 1);
+        write_json(&mut sinks, &record).unwrap();
+        flush_json(&mut sinks);
+        assert_eq!(sinks.len(), 1);
+        let output = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(output.lines().count(), 2);
+    }
+
+    /// Ensures messages are popped in order when they are spilled to disk, and that the
+    /// spill file is removed once read.
+    #[test]
+    fn spill_queue_order() {
+        let queue = SpillQueue::new(4);
+        let deadline = Instant::now();
+        for i in 0..10u32 {
+            assert!(queue.push(i));
+        }
+        assert!(queue.state.lock().unwrap().spill.is_some());
+        for i in 0..7 {
+            match queue.pop(deadline).unwrap() {
+                Pop::Message(message) => assert_eq!(message, i),
+                _ => panic!("missing message {}", i),
+            }
+        }
+        // Messages pushed while the spill file is not empty are not reordered.
+        assert!(queue.push(10));
+        queue.close();
+        assert!(!queue.push(11));
+        for i in 7..11 {
+            match queue.pop(deadline).unwrap() {
+                Pop::Message(message) => assert_eq!(message, i),
+                _ => panic!("missing message {}", i),
+            }
+        }
+        assert!(queue.state.lock().unwrap().spill.is_none());
+        match queue.pop(deadline).unwrap() {
+            Pop::Closed => (),
+            _ => panic!("the queue should be closed"),
+        }
+    }
+
+    /// Ensures a burst of messages larger than the channel and the in-memory buffer is
+    /// logged entirely.
+    #[test]
+    fn log_burst() {
+        const NUM_MESSAGES: usize = 1000;
+        let output_dir = std::env::temp_dir()
+            .join(format!("telamon-log-burst-{}", std::process::id()));
+        let config = Config {
+            output_dir: output_dir.to_str().unwrap().to_string(),
+            log_channel_size: 1,
+            log_buffer_size: 10,
+            ..Config::default()
+        };
+        let (sender, receiver) = mpsc::sync_channel(config.log_channel_size);
+        let messages = std::thread::spawn(move || {
+            for cpt in 0..NUM_MESSAGES {
+                let message = LogMessage::<()>::NewBest {
+                    score: 1.,
+                    cpt,
+                    timestamp: Duration::from_secs(0),
+                };
+                sender.send(message).unwrap();
+            }
+        });
+        log(&config, receiver).unwrap();
+        messages.join().unwrap();
+        let output = fs::read_to_string(output_dir.join(&config.log_file)).unwrap();
+        fs::remove_dir_all(&output_dir).unwrap();
+        let num_logged = output
+            .lines()
+            .filter(|line| line.starts_with("New best candidate"))
+            .count();
+        assert_eq!(num_logged, NUM_MESSAGES);
+    }
+}
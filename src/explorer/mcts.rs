@@ -14,28 +14,34 @@
 use std::cell::RefCell;
 use std::cmp::PartialEq;
 use std::fmt::{self, Debug, Display};
+use std::io;
 use std::sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
-    mpsc, Arc, RwLock, Weak,
+    mpsc, Arc, Mutex, RwLock, Weak,
 };
 use std::{cmp, iter, ops, slice};
 
+use fxhash::FxHashMap;
+use log::{info, warn};
 use rand::distributions::{Weighted, WeightedChoice};
 use rand::prelude::*;
 use rpds::List;
 use serde::{Deserialize, Serialize};
 use utils::cmp_f64;
 
+use crate::codegen;
 use crate::device::Context;
 use crate::explorer::{
     candidate::Candidate,
     choice::{self, ActionEx as Action},
-    config::{self, BanditConfig, ChoiceOrdering, NewNodeOrder},
-    logger::LogMessage,
-    store::Store,
+    config::{self, BanditConfig, ChoiceOrdering, NewNodeOrder, TabuConfig},
+    conflicts::ConflictCache,
+    logger::{JsonRecord, LogMessage, StructuredEvent},
+    rng,
+    store::{Checkpoint, Store},
 };
-use crate::model::{bound, Bound};
-use crate::search_space::SearchSpace;
+use crate::model::{bound, Bound, CostEstimator};
+use crate::search_space::{self, DimKind, InstFlag, SearchSpace};
 
 /// Newtype wrapper to represent a node identifier.  Node identifiers should be unique inside a
 /// tree.  We use a fixed-size representation for consistency of the serialization format.
@@ -55,6 +61,12 @@ impl From<NodeId> for u64 {
     }
 }
 
+impl From<u64> for NodeId {
+    fn from(v: u64) -> Self {
+        NodeId(v)
+    }
+}
+
 /// Newtype wrapper to represent an edge index.  Like `NodeId`, we use a fixed-size representation
 /// for consistency of the serialization format.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
@@ -67,6 +79,12 @@ impl Display for EdgeIndex {
     }
 }
 
+impl From<u16> for EdgeIndex {
+    fn from(v: u16) -> Self {
+        EdgeIndex(v)
+    }
+}
+
 impl From<EdgeIndex> for u16 {
     fn from(v: EdgeIndex) -> Self {
         v.0
@@ -84,6 +102,151 @@ pub enum CauseOfDeath {
     Backtrack,
 }
 
+/// Counts the nodes killed for each cause of death.
+#[derive(Default)]
+struct DeadendStats {
+    constraints: AtomicUsize,
+    perf_model: AtomicUsize,
+    backtrack: AtomicUsize,
+}
+
+impl DeadendStats {
+    /// Records a node killed for the given cause.
+    fn record(&self, cause: CauseOfDeath) {
+        let counter = match cause {
+            CauseOfDeath::Constraints => &self.constraints,
+            CauseOfDeath::PerfModel { .. } => &self.perf_model,
+            CauseOfDeath::Backtrack => &self.backtrack,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of nodes killed for each cause of death.
+    fn counts(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("constraints", self.constraints.load(Ordering::Relaxed)),
+            ("perf_model", self.perf_model.load(Ordering::Relaxed)),
+            ("backtrack", self.backtrack.load(Ordering::Relaxed)),
+        ]
+    }
+}
+
+impl fmt::Display for DeadendStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} killed by constraints, {} by the performance model, {} by backtracking",
+            self.constraints.load(Ordering::Relaxed),
+            self.perf_model.load(Ordering::Relaxed),
+            self.backtrack.load(Ordering::Relaxed)
+        )
+    }
+}
+
+/// Number of buckets in the histogram of bound computation durations.  Bucket `i` holds the
+/// durations between `2^i` and `2^(i+1)` microseconds, except for the first and the last
+/// buckets which also hold the shorter and longer durations.
+const NUM_DURATION_BUCKETS: usize = 32;
+
+/// Records the time spent computing bounds from the performance model.
+struct BoundStats {
+    /// Summary of the bound computation durations.  Durations are aggregated rather than
+    /// stored, so that the memory used does not grow with the number of bounds computed.
+    durations: Mutex<DurationSummary>,
+    /// Duration (in seconds) above which a bound computation is reported.
+    alarm: Option<f64>,
+}
+
+/// Aggregated durations of bound computations.
+#[derive(Default)]
+struct DurationSummary {
+    count: u64,
+    /// Sum of the durations, in seconds.
+    total: f64,
+    /// Longest duration, in seconds.
+    max: f64,
+    buckets: [u64; NUM_DURATION_BUCKETS],
+}
+
+impl DurationSummary {
+    /// Returns the bucket holding a duration, in seconds.
+    fn bucket(secs: f64) -> usize {
+        let micros = secs * 1e6;
+        if micros < 2. {
+            0
+        } else {
+            std::cmp::min(micros.log2() as usize, NUM_DURATION_BUCKETS - 1)
+        }
+    }
+
+    fn push(&mut self, secs: f64) {
+        self.count += 1;
+        self.total += secs;
+        self.max = self.max.max(secs);
+        self.buckets[Self::bucket(secs)] += 1;
+    }
+
+    /// Returns an upper bound of the given quantile of the durations, in seconds.
+    fn quantile_upper_bound(&self, quantile: f64) -> f64 {
+        let rank = (quantile * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return f64::min(2f64.powi(bucket as i32 + 1) * 1e-6, self.max);
+            }
+        }
+        self.max
+    }
+}
+
+impl BoundStats {
+    fn new(alarm_ms: Option<f64>) -> Self {
+        BoundStats {
+            durations: Mutex::new(DurationSummary::default()),
+            alarm: alarm_ms.map(|ms| ms * 1e-3),
+        }
+    }
+
+    /// Records the duration of a bound computation.  If the duration exceeds the alarm
+    /// threshold, logs the actions leading to the candidate, as returned by `actions`.
+    fn record<F>(&self, duration: std::time::Duration, actions: F)
+    where
+        F: FnOnce() -> Vec<Action>,
+    {
+        let secs = duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9;
+        if self.alarm.map(|alarm| secs > alarm).unwrap_or(false) {
+            warn!(
+                "bound computation took {:.2}ms for actions {:?}",
+                secs * 1e3,
+                actions()
+            );
+        }
+        self.durations
+            .lock()
+            .expect("durations: poisoned")
+            .push(secs);
+    }
+}
+
+impl fmt::Display for BoundStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let durations = self.durations.lock().expect("durations: poisoned");
+        if durations.count == 0 {
+            return write!(f, "no bound computed");
+        }
+        let mean = durations.total / durations.count as f64;
+        write!(
+            f,
+            "{} computed, mean {:.3}ms, p99 below {:.3}ms, max {:.3}ms",
+            durations.count,
+            mean * 1e3,
+            durations.quantile_upper_bound(0.99) * 1e3,
+            durations.max * 1e3
+        )
+    }
+}
+
 pub trait Reset {
     fn reset(&self);
 }
@@ -114,6 +277,10 @@ struct NodeInner<N, E> {
     /// and was never live.
     bound: Option<Box<Bound>>,
 
+    /// Estimated execution time, if a cost estimator is used.  The estimate is only used to
+    /// order nodes and is not a bound: it must never be used to prune nodes.
+    estimate: Option<f64>,
+
     /// Whether the node is dead.
     dead: AtomicBool,
 
@@ -194,6 +361,11 @@ impl<N, E> Node<N, E> {
         self.inner.bound.as_ref().map(Box::as_ref)
     }
 
+    /// Estimated execution time of the node's implementations, if a cost estimator is used.
+    pub fn estimate(&self) -> Option<f64> {
+        self.inner.estimate
+    }
+
     /// Returns whether the node is still live.
     pub fn is_live(&self) -> bool {
         self.inner.bound.is_some() && !self.inner.dead.load(Ordering::SeqCst)
@@ -372,6 +544,8 @@ pub struct Env<'a> {
     choice_ordering: &'a ChoiceOrdering,
     /// The context to use for constraint propagation.
     context: &'a dyn Context,
+    /// Estimates the execution time of candidates to order new nodes, if any.
+    estimator: Option<&'a dyn CostEstimator>,
 }
 
 impl<'a> Env<'a> {
@@ -380,15 +554,24 @@ impl<'a> Env<'a> {
         Env {
             choice_ordering,
             context,
+            estimator: None,
         }
     }
 
+    /// Uses `estimator` to estimate the execution time of new nodes.
+    pub fn with_estimator(mut self, estimator: Option<&'a dyn CostEstimator>) -> Self {
+        self.estimator = estimator;
+        self
+    }
+
     /// List the available actions for a candidate.
     ///
     /// This includes all actions, even those that may be removed by further propagation.  Hence,
     /// the resulting vector is empty only when the candidate is a fully-specified implementation.
-    pub fn list_actions(&self, candidate: &SearchSpace) -> Vec<Action> {
-        choice::list(self.choice_ordering, candidate)
+    /// `depth` is the depth of the candidate in the search tree, which selects the choice
+    /// ordering to use.
+    pub fn list_actions(&self, candidate: &SearchSpace, depth: usize) -> Vec<Action> {
+        choice::list(self.choice_ordering.at_depth(depth), candidate)
             .next()
             .unwrap_or_default()
     }
@@ -396,27 +579,27 @@ impl<'a> Env<'a> {
     /// Apply an action to an existing candidate, consuming the existing candidate.
     pub fn apply_action(
         &self,
-        mut candidate: SearchSpace,
+        candidate: SearchSpace,
         action: Action,
     ) -> Option<SearchSpace> {
-        if let Ok(()) = match action {
-            Action::Action(action) => candidate.apply_decisions(vec![action]),
-            Action::LowerLayout {
-                mem,
-                st_dims,
-                ld_dims,
-            } => candidate.lower_layout(mem, &st_dims, &ld_dims),
-        } {
-            Some(candidate)
-        } else {
-            None
-        }
+        // Go through `ActionEx::apply_to` so that actions are applied exactly as in replays.
+        action.apply_to(candidate).ok()
     }
 
     /// Compute the performance model bound for a candidate.
     pub fn bound(&self, candidate: &SearchSpace) -> Bound {
         bound(candidate, self.context)
     }
+
+    /// Estimates the execution time of a candidate with the cost estimator, if any.  The
+    /// estimate is clamped to the bound, below which no implementation can run, so that
+    /// negative or `NaN` estimates do not break the orders relying on their inverse.
+    pub fn estimate(&self, candidate: &SearchSpace, bound: &Bound) -> Option<f64> {
+        self.estimator.map(|estimator| {
+            let estimate = estimator.estimate(candidate, bound, self.context);
+            estimate.max(bound.value())
+        })
+    }
 }
 
 /// The types of policy used.
@@ -477,7 +660,8 @@ pub enum Message {
     },
 
     /// Sequence of actions (moves in the tree) performed by a specific thread.  Starts at the root
-    /// of the tree.
+    /// of the tree, which is selected by the first event so that traces can be replayed when
+    /// the log contains several trees.
     Trace {
         /// The thread performing the descent.  There can be multiple traces per thread, and they
         /// will share the `thread` field.
@@ -496,6 +680,146 @@ pub enum Message {
         /// Time at which the evaluation results were made available and backpropagation started.
         result_time: std::time::Duration,
     },
+
+    /// A node was screened with a few runs, before its final evaluation.  The final value is
+    /// reported by a later `Evaluation` message for the same node.
+    Screening {
+        /// Identifier of the screened node
+        id: NodeId,
+        /// Screening result.  If `None`, the evaluation failed.
+        value: Option<f64>,
+        /// Time at which the screening results were made available.
+        result_time: std::time::Duration,
+    },
+
+    /// The launch configuration of a node compiled for evaluation.
+    Launch {
+        /// Identifier of the compiled node
+        id: NodeId,
+        /// Launch configuration of the generated code.
+        launch: codegen::LaunchConfig,
+        /// Time at which the node was compiled.
+        result_time: std::time::Duration,
+    },
+
+    /// The source code generated for a node compiled for evaluation.
+    Source {
+        /// Identifier of the compiled node
+        id: NodeId,
+        /// Actions leading to the node from the root, in order.
+        actions: Vec<Action>,
+        /// Code generated by the device for the node.
+        source: String,
+        /// Time at which the node was compiled.
+        result_time: std::time::Duration,
+    },
+
+    /// The energy consumed by a node, measured before its evaluation when the objective of the
+    /// search depends on it.  The score of the node is reported by a later `Evaluation`
+    /// message.
+    Energy {
+        /// Identifier of the measured node
+        id: NodeId,
+        /// Execution time, in nanoseconds.
+        runtime: f64,
+        /// Energy consumed by an execution, in nanojoules.
+        energy: f64,
+        /// Time at which the energy was measured.
+        result_time: std::time::Duration,
+    },
+}
+
+impl Message {
+    /// Returns the time at which the logged event happened, relative to the start of the
+    /// search.  Traces are timed by their first event.
+    pub fn timestamp(&self) -> std::time::Duration {
+        match self {
+            Message::Node { discovery_time, .. } => *discovery_time,
+            Message::Trace { events, .. } => events
+                .first()
+                .map(|event| event.start_time)
+                .unwrap_or_default(),
+            Message::Evaluation { result_time, .. }
+            | Message::Screening { result_time, .. }
+            | Message::Launch { result_time, .. }
+            | Message::Source { result_time, .. }
+            | Message::Energy { result_time, .. } => *result_time,
+        }
+    }
+
+    /// Renames the nodes referenced by the message.
+    pub fn map_node_ids<F>(&mut self, mut f: F)
+    where
+        F: FnMut(NodeId) -> NodeId,
+    {
+        match self {
+            Message::Node { id, parent, .. } => {
+                *id = f(*id);
+                if let Some((parent, _)) = parent {
+                    *parent = f(*parent);
+                }
+            }
+            Message::Trace { events, .. } => {
+                for event in events {
+                    if let Event::SelectNode(id) = &mut event.value {
+                        *id = f(*id);
+                    }
+                }
+            }
+            Message::Evaluation { id, .. }
+            | Message::Screening { id, .. }
+            | Message::Launch { id, .. }
+            | Message::Source { id, .. }
+            | Message::Energy { id, .. } => *id = f(*id),
+        }
+    }
+}
+
+impl StructuredEvent for Message {
+    fn json_records(&self) -> Vec<JsonRecord> {
+        match self {
+            Message::Evaluation {
+                id,
+                value,
+                result_time,
+            } => vec![JsonRecord::Evaluation {
+                id: u64::from(*id),
+                runtime: *value,
+                timestamp: result_time.as_secs_f64(),
+            }],
+            Message::Energy {
+                id,
+                runtime,
+                energy,
+                result_time,
+            } => vec![JsonRecord::Energy {
+                id: u64::from(*id),
+                runtime: *runtime,
+                energy: *energy,
+                timestamp: result_time.as_secs_f64(),
+            }],
+            Message::Trace { events, .. } => events
+                .iter()
+                .filter_map(|event| match event.value {
+                    Event::Kill(cause) | Event::KillChild(_, cause) => {
+                        Some(JsonRecord::Deadend {
+                            cause: match cause {
+                                CauseOfDeath::Constraints => "constraints",
+                                CauseOfDeath::PerfModel { .. } => "perf_model",
+                                CauseOfDeath::Backtrack => "backtrack",
+                            },
+                            timestamp: event.start_time.as_secs_f64(),
+                        })
+                    }
+                    _ => None,
+                })
+                .collect(),
+            Message::Node { .. }
+            | Message::Screening { .. }
+            | Message::Launch { .. }
+            | Message::Source { .. } => vec![],
+        }
+    }
 }
 
 /// A path in the tree.
@@ -520,6 +844,10 @@ pub struct Tree<'a> {
     logger: &'a mpsc::SyncSender<LogMessage<Message>>,
     /// Time at which exploration started.  Used as an epoch for timestamps.
     epoch: std::time::Instant,
+    /// Statistics on the time spent computing bounds.
+    bound_stats: &'a BoundStats,
+    /// Cache of infeasible sets of actions, if enabled.
+    conflicts: Option<&'a ConflictCache>,
 }
 
 impl<'a> Tree<'a> {
@@ -529,13 +857,45 @@ impl<'a> Tree<'a> {
         id_counter: &'a AtomicUsize,
         logger: &'a mpsc::SyncSender<LogMessage<Message>>,
         epoch: std::time::Instant,
+        bound_stats: &'a BoundStats,
+        conflicts: Option<&'a ConflictCache>,
     ) -> Self {
         Tree {
             env,
             id_counter,
             logger,
             epoch,
+            bound_stats,
+            conflicts,
+        }
+    }
+
+    /// Applies the action of the child edge `index` of `node` to the node's `candidate`.
+    ///
+    /// When a conflict cache is used, it is consulted before propagating the action and
+    /// updated if propagation fails.
+    fn apply_child_action<N, E>(
+        &self,
+        node: &Node<N, E>,
+        index: EdgeIndex,
+        candidate: &SearchSpace,
+    ) -> Option<SearchSpace> {
+        let action = node[index].action();
+        let conflicts = if let Some(conflicts) = self.conflicts {
+            conflicts
+        } else {
+            return self.env.apply_action(candidate.clone(), action.clone());
+        };
+
+        let actions = node.actions();
+        if conflicts.is_infeasible(&actions.iter().cloned().collect(), action) {
+            return None;
+        }
+        let child = self.env.apply_action(candidate.clone(), action.clone());
+        if child.is_none() {
+            conflicts.record_failure(&actions, action);
         }
+        child
     }
 
     /// Create a new node.
@@ -553,11 +913,12 @@ impl<'a> Tree<'a> {
     {
         assert!(parent.is_some() || candidate.is_some());
 
-        let (children, bound);
+        let depth = parent.map(|(parent, _)| parent.depth() + 1).unwrap_or(0);
+        let (children, bound, estimate);
         if let Some(candidate) = candidate {
             children = self
                 .env
-                .list_actions(candidate)
+                .list_actions(candidate, depth)
                 .into_iter()
                 .enumerate()
                 .map(|(ix, action)| Edge {
@@ -569,10 +930,24 @@ impl<'a> Tree<'a> {
                     }),
                 })
                 .collect();
+            let start = std::time::Instant::now();
             bound = Some(self.env.bound(candidate));
+            self.bound_stats.record(start.elapsed(), || {
+                parent
+                    .map(|(parent, index)| {
+                        let mut actions = parent.actions();
+                        actions.push(parent[index].action().clone());
+                        actions
+                    })
+                    .unwrap_or_default()
+            });
+            estimate = bound
+                .as_ref()
+                .and_then(|bound| self.env.estimate(candidate, bound));
         } else {
             children = Vec::new();
             bound = None;
+            estimate = None;
         }
 
         let id = NodeId(self.id_counter.fetch_add(1, Ordering::Relaxed) as u64);
@@ -589,11 +964,12 @@ impl<'a> Tree<'a> {
         Node {
             inner: Arc::new(NodeInner {
                 id,
-                depth: parent.map(|(parent, _)| parent.depth() + 1).unwrap_or(0),
+                depth,
                 parent: parent.map(|(parent, index)| (parent.downgrade(), index)),
                 children,
                 dead: AtomicBool::new(bound.is_none()),
                 bound: bound.map(Box::new),
+                estimate,
                 data: N::default(),
                 candidate: RwLock::new(None),
                 expanded: RwLock::new(false),
@@ -730,10 +1106,9 @@ where
                         };
                     }
 
-                    let child = self
-                        .tree
-                        .env
-                        .apply_action(candidate.clone(), edge.action().clone());
+                    let child =
+                        self.tree
+                            .apply_child_action(&self.node, edge.index(), candidate);
                     let child_node = self
                         .tree
                         .node(Some((&self.node, edge.index())), child.as_ref());
@@ -766,6 +1141,7 @@ where
         F: FnOnce(CauseOfDeath) -> Event,
     {
         // TODO: Do not overwrite cause if there already is one?
+        self.helper.deadends.record(cause);
         self.event(self.tree.epoch.elapsed(), event_fn(cause));
 
         node.kill();
@@ -808,6 +1184,31 @@ where
         }
     }
 
+    /// Ends the descent at the pointed-to node, which is not necessarily an
+    /// implementation: the descent is completed outside of the tree, for instance by a
+    /// worker of a distributed search.
+    pub fn stop_descent(
+        self,
+        candidate: SearchSpace,
+    ) -> Result<(SearchSpace, Trace<N, E>), Self> {
+        if self.cut() {
+            Err(self)
+        } else {
+            self.tree.log(Message::Trace {
+                thread: format!("{:?}", std::thread::current().id()),
+                events: self.events.into_inner(),
+            });
+
+            Ok((
+                candidate,
+                Trace {
+                    path: self.path,
+                    node: self.node,
+                },
+            ))
+        }
+    }
+
     pub fn deadend(self) {
         self.tree.log(Message::Trace {
             thread: format!("{:?}", std::thread::current().id()),
@@ -947,10 +1348,12 @@ impl<'a, N, E> Display for Error<'a, N, E> {
 impl<'a, N, E> std::error::Error for Error<'a, N, E> {}
 
 pub trait TreePolicy<N, E>: Send + Sync {
+    /// Picks a child among `children`, drawing random choices from `rng`.
     fn pick_child(
         &'_ self,
         cut: f64,
         children: &NodeView<'_, N, E>,
+        rng: &mut dyn RngCore,
     ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)>;
 
     fn backpropagate(
@@ -960,6 +1363,11 @@ pub trait TreePolicy<N, E>: Send + Sync {
         _eval: Option<f64>,
     ) {
     }
+
+    /// Called once for each evaluated implementation, with the full list of actions that were
+    /// applied to obtain it.  This is called even when the implementation was reached through
+    /// the default policy.
+    fn observe_evaluation(&'_ self, _actions: &List<Action>, _eval: Option<f64>) {}
 }
 
 #[derive(Copy, Clone)]
@@ -967,12 +1375,15 @@ struct WalkHelper<'a> {
     stop: &'a AtomicBool,
     cut: &'a RwLock<f64>,
     cut_epoch: &'a AtomicUsize,
+    deadends: &'a DeadendStats,
     config: &'a BanditConfig,
 }
 
 /// Helper structure to walk the tree following a specific policy.
 struct PolicyWalker<'a, N, E> {
     policy: &'a dyn TreePolicy<N, E>,
+    /// Generator of the random choices of the descent.
+    rng: &'a RefCell<rng::ExplorerRng>,
 }
 
 impl<'a, N, E> PolicyWalker<'a, N, E>
@@ -1008,10 +1419,11 @@ where
                             })
                             .unzip();
 
-                        if let Some((index, selector)) = self
-                            .policy
-                            .pick_child(cursor.cut, &NodeView::new(&cursor.node, &edges))
-                        {
+                        if let Some((index, selector)) = self.policy.pick_child(
+                            cursor.cut,
+                            &NodeView::new(&cursor.node, &edges),
+                            &mut *self.rng.borrow_mut(),
+                        ) {
                             let (edge, node) = edges.swap_remove(usize::from(index));
                             let child_candidate =
                                 candidates.swap_remove(usize::from(index));
@@ -1054,6 +1466,8 @@ struct MctsWalker<'a, N, E> {
     default_walker: PolicyWalker<'a, N, E>,
     /// The policy to use in the explicit tree where statistics are available.
     tree_policy: &'a dyn TreePolicy<N, E>,
+    /// If false, descents stop at the node they expand instead of performing a rollout.
+    rollout: bool,
 }
 
 impl<'a, N, E> MctsWalker<'a, N, E>
@@ -1061,13 +1475,17 @@ where
     N: Send + Sync + Debug + Default,
     E: Send + Sync + Debug + Default,
 {
-    /// Evaluate the underlying node
+    /// Evaluate the underlying node, or stops at it if rollouts are disabled.
     fn evaluate(
         &self,
         cursor: NodeCursor<'a, N, E>,
         candidate: SearchSpace,
     ) -> Result<(SearchSpace, Trace<N, E>), Error<'a, N, E>> {
-        self.default_walker.walk(cursor, candidate)
+        if self.rollout || cursor.node.is_implementation() {
+            self.default_walker.walk(cursor, candidate)
+        } else {
+            cursor.stop_descent(candidate).map_err(Error::DeadEnd)
+        }
     }
 
     /// Select a child in the explicit tree.  The node pointed to by the cursor must already be
@@ -1093,6 +1511,7 @@ where
             cursor = match cursor.check_stop()?.checkpoint(|cursor| {
                 cursor
                     .select_child(|cursor| {
+                        let mut rng = self.default_walker.rng.borrow_mut();
                         let mut expanded = Vec::with_capacity(cursor.node.edges().len());
                         let mut unexpanded = Vec::new();
                         for (edge, node) in cursor
@@ -1110,6 +1529,7 @@ where
                             self.default_walker.policy.pick_child(
                                 cursor.cut,
                                 &NodeView::new(&cursor.node, &unexpanded),
+                                &mut *rng,
                             )
                         {
                             let (edge, node) = unexpanded.swap_remove(usize::from(index));
@@ -1132,6 +1552,7 @@ where
                             if let Some((index, selector)) = self.tree_policy.pick_child(
                                 cursor.cut,
                                 &NodeView::new(&cursor.node, &expanded),
+                                &mut *rng,
                             ) {
                                 let (edge, node) =
                                     expanded.swap_remove(usize::from(index));
@@ -1191,6 +1612,9 @@ pub struct MctsStore<'a, N, E> {
 
     space: SearchSpace,
 
+    /// The orders left to fix in `space`, refined when the order of candidates is fixed.
+    open_orders: Arc<choice::OpenOrders>,
+
     default_policy: Box<dyn TreePolicy<N, E>>,
 
     tree_policy: Box<dyn TreePolicy<N, E>>,
@@ -1205,8 +1629,26 @@ pub struct MctsStore<'a, N, E> {
     /// Whether evaluation should be stopped
     stop: AtomicBool,
 
-    /// Counter for the node IDs
-    id_counter: AtomicUsize,
+    /// Counter for the node IDs, possibly shared with other trees.  See `with_node_ids`.
+    id_counter: Arc<AtomicUsize>,
+
+    /// Number of nodes killed for each cause of death.
+    deadends: DeadendStats,
+
+    /// Number of descents that ended in a dead-end since the last restart.
+    deadends_since_restart: AtomicUsize,
+
+    /// Prefixes of actions excluded from the search.
+    tabu: Mutex<Vec<Vec<Action>>>,
+
+    /// Time spent computing bounds.
+    bound_stats: BoundStats,
+
+    /// Cache of infeasible sets of actions, if enabled.
+    conflicts: Option<ConflictCache>,
+
+    /// Estimates the execution time of new nodes, if enabled.
+    estimator: Option<Arc<dyn CostEstimator>>,
 
     /// Sender to the log queue
     logger: mpsc::SyncSender<LogMessage<Message>>,
@@ -1216,6 +1658,15 @@ pub struct MctsStore<'a, N, E> {
 
     /// Time at which the search started.  Used as an epoch for timestamps.
     epoch: std::time::Instant,
+
+    /// Evaluations committed so far, in order, to save in checkpoints.
+    evaluations: Mutex<Vec<(Vec<Action>, Option<f64>, bool)>>,
+
+    /// Actions lists that guide the first descents, in reverse order.  See `with_warm_start`.
+    warm_start: Mutex<Vec<Vec<Action>>>,
+
+    /// Generator from which each descent forks the generator of its random choices.
+    rng: rng::SharedRng,
 }
 
 impl<'a, N, E> MctsStore<'a, N, E>
@@ -1230,21 +1681,50 @@ where
         tree_policy: Box<dyn TreePolicy<N, E>>,
         default_policy: Box<dyn TreePolicy<N, E>>,
         logger: mpsc::SyncSender<LogMessage<Message>>,
+    ) -> Self {
+        Self::with_node_ids(
+            space,
+            context,
+            config,
+            tree_policy,
+            default_policy,
+            logger,
+            Arc::default(),
+        )
+    }
+
+    /// Same as `new`, but allocates the node identifiers from `id_counter`.  Trees that
+    /// log to the same event log must share their counter, so that their nodes have
+    /// distinct identifiers.
+    pub fn with_node_ids(
+        space: SearchSpace,
+        context: &dyn Context,
+        config: &'a BanditConfig,
+        tree_policy: Box<dyn TreePolicy<N, E>>,
+        default_policy: Box<dyn TreePolicy<N, E>>,
+        logger: mpsc::SyncSender<LogMessage<Message>>,
+        id_counter: Arc<AtomicUsize>,
     ) -> Self {
         let epoch = std::time::Instant::now();
 
-        let id_counter = AtomicUsize::new(0);
+        let bound_stats = BoundStats::new(config.bound_time_alarm);
+        let conflicts = config
+            .conflict_cache_size
+            .map(|size| ConflictCache::new(space.clone(), size));
         let root = Tree::new(
             Env::new(&config.choice_ordering, context),
             &id_counter,
             &logger,
             epoch,
+            &bound_stats,
+            conflicts.as_ref(),
         )
         .node(None, Some(&space));
         root.store_candidate(space.clone());
 
         MctsStore {
             root,
+            open_orders: Arc::new(choice::OpenOrders::new(&space)),
             space,
             default_policy,
             tree_policy,
@@ -1253,115 +1733,106 @@ where
             restart_id: AtomicUsize::new(0),
             stop: AtomicBool::new(false),
             id_counter,
+            deadends: DeadendStats::default(),
+            deadends_since_restart: AtomicUsize::new(0),
+            tabu: Mutex::new(Vec::new()),
+            bound_stats,
+            conflicts,
+            estimator: None,
             logger,
             config,
             epoch,
+            evaluations: Mutex::new(Vec::new()),
+            warm_start: Mutex::new(Vec::new()),
+            rng: rng::SharedRng::default(),
         }
     }
 
+    /// Draws the random choices of the descents from `rng` instead of a generator seeded
+    /// from the entropy of the current thread.
+    pub fn with_rng(mut self, rng: rng::ExplorerRng) -> Self {
+        self.rng = rng::SharedRng::new(rng);
+        self
+    }
+
+    /// Uses `estimator` to order the new nodes explored by the `NewNodeOrder` policies.
+    /// Nodes are still pruned with the bound only.
+    pub fn with_estimator(mut self, estimator: Arc<dyn CostEstimator>) -> Self {
+        self.estimator = Some(estimator);
+        self
+    }
+
     fn cursor<'b>(&'b self, context: &'b dyn Context) -> NodeCursor<'b, N, E> {
+        let start_time = self.epoch.elapsed();
         NodeCursor {
-            events: Vec::new().into(),
+            events: vec![Timed {
+                start_time,
+                end_time: start_time,
+                value: Event::SelectNode(self.root.id()),
+            }]
+            .into(),
             cut: *self.cut.read().expect("cut: poisoned"),
             cut_epoch: self.cut_epoch.load(Ordering::Relaxed),
             path: Vec::new(),
             node: self.root.clone(),
             tree: Tree::new(
-                Env::new(&self.config.choice_ordering, context),
+                Env::new(&self.config.choice_ordering, context)
+                    .with_estimator(self.estimator.as_ref().map(|e| &**e)),
                 &self.id_counter,
                 &self.logger,
                 self.epoch,
+                &self.bound_stats,
+                self.conflicts.as_ref(),
             ),
             helper: WalkHelper {
                 stop: &self.stop,
                 cut: &self.cut,
                 cut_epoch: &self.cut_epoch,
+                deadends: &self.deadends,
                 config: self.config,
             },
         }
     }
 
-    fn walker(&self) -> MctsWalker<'_, N, E> {
+    fn walker<'b>(
+        &'b self,
+        rng: &'b RefCell<rng::ExplorerRng>,
+        rollout: bool,
+    ) -> MctsWalker<'b, N, E> {
         MctsWalker {
             default_walker: PolicyWalker {
                 policy: self.default_policy.as_ref(),
+                rng,
             },
             tree_policy: self.tree_policy.as_ref(),
+            rollout,
         }
     }
-}
-
-pub struct Payload<N, E> {
-    trace: Trace<N, E>,
-    restart_id: usize,
-}
-
-impl<'a, N, E> Store for MctsStore<'a, N, E>
-where
-    N: Send + Sync + Debug + Default + Reset,
-    E: Send + Sync + Debug + Default + Reset,
-{
-    type PayLoad = Payload<N, E>;
-
-    type Event = Message;
-
-    fn update_cut(&self, new_cut: f64) {
-        // If an initial cut was specified in the configuration file, `update_cut` will be called
-        // with the first implementation found, even if it is not better than the previous cut.
-        //
-        // When this happens, we should keep using the provided initial cut instead of blindly
-        // using the new cut.
-        let mut cut_mut = self.cut.write().expect("cut: poisoned");
-        *cut_mut = new_cut.min(*cut_mut);
-
-        self.cut_epoch.fetch_add(1, Ordering::Relaxed);
-
-        // TODO: trim the tree?
-    }
 
-    fn commit_evaluation(
+    /// Descends in the tree to find a candidate to evaluate.  If `rollout` is false, the
+    /// descent stops at the node it expands, so the candidate may not be an
+    /// implementation.  Returns `None` if the tree is exhausted or the search stopped.
+    fn descend(
         &self,
-        _actions: &List<choice::ActionEx>,
-        payload: Self::PayLoad,
-        eval: f64,
-    ) {
-        // Discard old evaluations that were meant for a previous restart
-        if self.restart_id.load(Ordering::SeqCst) > payload.restart_id {
-            return;
-        }
-
-        let trace = payload.trace;
-        let result_time = self.epoch.elapsed();
-        let id = trace.node.id();
-        let eval = if eval.is_finite() { Some(eval) } else { None };
-
-        // Backpropagate only when the parent is expanded
-        for (policy, parent, index) in trace.path {
-            match policy {
-                Policy::Bandit => {
-                    if let Some(parent) = parent.upgrade() {
-                        if parent.is_expanded() {
-                            self.tree_policy.backpropagate(&parent, index, eval);
-                        }
-                    }
-                }
-                Policy::Default => {}
+        context: &dyn Context,
+        rollout: bool,
+    ) -> Option<(SearchSpace, Trace<N, E>)> {
+        loop {
+            let actions = if let Some(actions) = self.warm_start.lock().unwrap().pop() {
+                actions
+            } else {
+                break;
+            };
+            match self.warm_start_descent(context, &actions) {
+                Some(descent) => return Some(descent),
+                None => warn!("the warm start actions {:?} lead to a dead-end", actions),
             }
         }
 
-        self.logger
-            .send(LogMessage::Event(Message::Evaluation {
-                id,
-                result_time,
-                value: eval,
-            }))
-            .expect("sending message");
-    }
-
-    fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)> {
+        let rng = RefCell::new(self.rng.fork());
         loop {
             let cursor = self.cursor(context);
-            let walker = self.walker();
+            let walker = self.walker(&rng, rollout);
 
             // Stop if the root node is dead.
             if cursor.cut() {
@@ -1386,40 +1857,466 @@ where
             match walker.select_intree(cursor) {
                 Ok((candidate, trace)) => break Some((candidate, trace)),
                 Err(Error::DeadEnd(cursor)) => {
+                    self.deadends_since_restart.fetch_add(1, Ordering::Relaxed);
                     cursor.deadend();
                     continue;
                 }
                 Err(_err) => break None,
             }
         }
-        .map(|(candidate, trace)| {
-            (
-                Candidate::with_actions(
-                    candidate,
-                    trace.node.bound().unwrap().clone(),
-                    trace.node.actions(),
-                ),
-                Payload {
-                    trace,
-                    restart_id: self.restart_id.load(Ordering::SeqCst),
-                },
-            )
-        })
     }
 
-    fn restart(&self) {
-        // Update the restart_id before resetting to ensure that the monitor thread doesn't go
-        // updating the wrong tree.
-        self.restart_id.fetch_add(1, Ordering::SeqCst);
-        self.root.reset();
-        self.root.store_candidate(self.space.clone());
+    /// Guides the first descents with the given lists of actions, typically the actions of
+    /// the best implementations found by previous searches on similar problems.  Each list
+    /// is followed as far as possible: at each node, the descent picks a child whose action
+    /// is in the list or, if there is none, the child with the lowest bound.  Nodes are
+    /// expanded along the way so that the evaluations are backpropagated to the tree.
+    pub fn with_warm_start(self, warm_start: Vec<Vec<Action>>) -> Self {
+        *self.warm_start.lock().unwrap() = warm_start.into_iter().rev().collect();
+        self
+    }
+
+    /// Descends from the root following `actions` as explained in `with_warm_start`.
+    /// Returns `None` if the descent reaches a dead-end.
+    fn warm_start_descent(
+        &self,
+        context: &dyn Context,
+        actions: &[Action],
+    ) -> Option<(SearchSpace, Trace<N, E>)> {
+        let mut cursor = self.cursor(context);
+        let mut candidate = self.space.clone();
+        while !cursor.node.is_implementation() {
+            cursor.expand();
+            let (next, child_candidate) = cursor
+                .select_child(|cursor| {
+                    let mut children = cursor
+                        .live_children_iter_with_candidates(&candidate)
+                        .collect::<Vec<_>>();
+                    let index = children
+                        .iter()
+                        .position(|(edge, _, _)| actions.contains(edge.action()))
+                        .or_else(|| {
+                            (0..children.len()).min_by(|&lhs, &rhs| {
+                                let bound =
+                                    |idx: usize| children[idx].1.bound().unwrap().value();
+                                cmp_f64(bound(lhs), bound(rhs))
+                            })
+                        })?;
+                    let (edge, node, child_candidate) = children.swap_remove(index);
+                    let child_candidate = child_candidate.unwrap_or_else(|| {
+                        cursor
+                            .tree
+                            .env
+                            .apply_action(candidate.clone(), edge.action().clone())
+                            .unwrap()
+                    });
+                    Some((
+                        Policy::Bandit,
+                        Selector::Exact {
+                            value: edge.index(),
+                        },
+                        edge.index(),
+                        node,
+                        child_candidate,
+                    ))
+                })
+                .ok()?;
+            cursor = next;
+            candidate = child_candidate;
+        }
+        let (candidate, trace) = cursor.evaluate(candidate).ok()?;
+        trace.node.kill();
+        Some((candidate, trace))
+    }
+
+    /// Follows `actions` from the root, growing the tree as the descent that reached the
+    /// same implementation did.  Returns the implementation and the trace leading to it, or
+    /// `None` if the actions do not lead to a live implementation.
+    fn replay(
+        &self,
+        context: &dyn Context,
+        actions: &[Action],
+    ) -> Option<(SearchSpace, Trace<N, E>)> {
+        let mut cursor = self.cursor(context);
+        if !cursor.node.is_expanded() {
+            cursor.expand()?;
+        }
+
+        let mut candidate = self.space.clone();
+        // A descent expands the first node it reaches outside of the explicit tree.
+        let mut expanded = false;
+        for action in actions {
+            let (next, child_candidate) = cursor
+                .select_child(|cursor| {
+                    cursor
+                        .live_children_iter_with_candidates(&candidate)
+                        .find(|(edge, _, _)| edge.action() == action)
+                        .map(|(edge, node, child_candidate)| {
+                            let policy = if node.is_expanded() {
+                                Policy::Bandit
+                            } else {
+                                Policy::Default
+                            };
+                            let child_candidate = child_candidate.unwrap_or_else(|| {
+                                cursor
+                                    .tree
+                                    .env
+                                    .apply_action(candidate.clone(), action.clone())
+                                    .unwrap()
+                            });
+                            (
+                                policy,
+                                Selector::Exact {
+                                    value: edge.index(),
+                                },
+                                edge.index(),
+                                node,
+                                child_candidate,
+                            )
+                        })
+                })
+                .ok()?;
+            cursor = next;
+            candidate = child_candidate;
+            if !expanded && !cursor.node.is_expanded() {
+                cursor.expand();
+                expanded = true;
+            }
+        }
+
+        if !cursor.node.is_implementation() {
+            return None;
+        }
+        let (candidate, trace) = cursor.evaluate(candidate).ok()?;
+        trace.node.kill();
+        Some((candidate, trace))
+    }
+
+    /// Adds to the tree an implementation evaluated outside of the store, as if a descent
+    /// had found it, so that its evaluation is backpropagated.  Returns `false` if the
+    /// actions do not lead to a live implementation of the tree.
+    pub fn import(&self, context: &dyn Context, actions: &[Action], eval: f64) -> bool
+    where
+        N: Reset,
+        E: Reset,
+    {
+        match self.replay(context, actions) {
+            Some((candidate, trace)) => {
+                let (candidate, payload) = self.candidate(candidate, trace);
+                self.commit_evaluation(&candidate.actions, payload, eval);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Weights the choices of the default policy with `priors`, which replace the priors
+    /// of the configuration.
+    pub fn with_priors(mut self, priors: &[config::ChoicePrior]) -> Self {
+        self.default_policy =
+            Box::new(PriorPolicy::new(self.config.new_nodes_order, priors));
+        self
+    }
+
+    /// Returns the node reached by following `actions` from the root, if it was created.
+    fn find_node(&self, actions: &[Action]) -> Option<Node<N, E>> {
+        let mut node = self.root.clone();
+        for action in actions {
+            let edge = node.edges().iter().find(|edge| edge.action() == action)?;
+            node = edge.try_with_node(Node::clone)?;
+        }
+        Some(node)
+    }
+
+    /// Adds to the tabu list the prefixes whose evaluations are all bad and kills the
+    /// corresponding nodes.  Evaluations that failed are considered bad.
+    fn update_tabu(&self, config: &TabuConfig) {
+        let evaluations = self.evaluations.lock().unwrap();
+        let mut tabu = self.tabu.lock().unwrap();
+        for (prefix, num_evals, prefix_best) in bad_prefixes(&evaluations, config) {
+            if tabu.iter().any(|actions| &actions[..] == prefix) {
+                continue;
+            }
+            info!(
+                "tabu prefix {:?}: best of {} evaluations is {:.4e}ns",
+                prefix, num_evals, prefix_best
+            );
+            if let Some(node) = self.find_node(prefix) {
+                node.kill();
+            }
+            tabu.push(prefix.to_vec());
+        }
+    }
+
+    /// Builds the candidate to evaluate at the end of `trace`.
+    fn candidate(
+        &self,
+        candidate: SearchSpace,
+        trace: Trace<N, E>,
+    ) -> (Candidate, Payload<N, E>) {
+        (
+            Candidate::with_actions(
+                candidate,
+                trace.node.bound().unwrap().clone(),
+                trace.node.actions(),
+            )
+            .with_open_orders(self.open_orders.clone()),
+            Payload {
+                trace,
+                restart_id: self.restart_id.load(Ordering::SeqCst),
+            },
+        )
+    }
+
+    /// Commits an evaluation to the tree.  `provisional` indicates the evaluation is a
+    /// screening value that was not confirmed by a precise evaluation.
+    fn commit(
+        &self,
+        actions: &List<choice::ActionEx>,
+        payload: Payload<N, E>,
+        eval: f64,
+        provisional: bool,
+    ) {
+        // Discard old evaluations that were meant for a previous restart
+        if self.restart_id.load(Ordering::SeqCst) > payload.restart_id {
+            return;
+        }
+
+        let trace = payload.trace;
+        let result_time = self.epoch.elapsed();
+        let id = trace.node.id();
+        let eval = if eval.is_finite() { Some(eval) } else { None };
+
+        self.tree_policy.observe_evaluation(actions, eval);
+        // The descent may have been completed outside of the tree, in which case `actions`
+        // extends the actions of the trace.
+        self.evaluations.lock().unwrap().push((
+            actions.iter().cloned().collect(),
+            eval,
+            provisional,
+        ));
+
+        // Backpropagate only when the parent is expanded
+        for (policy, parent, index) in trace.path {
+            match policy {
+                Policy::Bandit => {
+                    if let Some(parent) = parent.upgrade() {
+                        if parent.is_expanded() {
+                            self.tree_policy.backpropagate(&parent, index, eval);
+                        }
+                    }
+                }
+                Policy::Default => {}
+            }
+        }
+
+        self.logger
+            .send(LogMessage::Event(Message::Evaluation {
+                id,
+                result_time,
+                value: eval,
+            }))
+            .expect("sending message");
+    }
+}
+
+pub struct Payload<N, E> {
+    trace: Trace<N, E>,
+    restart_id: usize,
+}
+
+impl<'a, N, E> Store for MctsStore<'a, N, E>
+where
+    N: Send + Sync + Debug + Default + Reset,
+    E: Send + Sync + Debug + Default + Reset,
+{
+    type PayLoad = Payload<N, E>;
+
+    type Event = Message;
+
+    fn update_cut(&self, new_cut: f64) {
+        // If an initial cut was specified in the configuration file, `update_cut` will be called
+        // with the first implementation found, even if it is not better than the previous cut.
+        //
+        // When this happens, we should keep using the provided initial cut instead of blindly
+        // using the new cut.
+        let mut cut_mut = self.cut.write().expect("cut: poisoned");
+        *cut_mut = new_cut.min(*cut_mut);
+
+        self.cut_epoch.fetch_add(1, Ordering::Relaxed);
+
+        // TODO: trim the tree?
+    }
+
+    fn commit_evaluation(
+        &self,
+        actions: &List<choice::ActionEx>,
+        payload: Self::PayLoad,
+        eval: f64,
+    ) {
+        self.commit(actions, payload, eval, false)
+    }
+
+    fn commit_provisional_evaluation(
+        &self,
+        actions: &List<choice::ActionEx>,
+        payload: Self::PayLoad,
+        eval: f64,
+    ) {
+        self.commit(actions, payload, eval, true)
+    }
+
+    fn commit_screening(&self, payload: &Self::PayLoad, eval: f64) {
+        if self.restart_id.load(Ordering::SeqCst) > payload.restart_id {
+            return;
+        }
+
+        self.logger
+            .send(LogMessage::Event(Message::Screening {
+                id: payload.trace.node.id(),
+                result_time: self.epoch.elapsed(),
+                value: if eval.is_finite() { Some(eval) } else { None },
+            }))
+            .expect("sending message");
+    }
+
+    fn commit_launch(&self, payload: &Self::PayLoad, launch: &codegen::LaunchConfig) {
+        if self.restart_id.load(Ordering::SeqCst) > payload.restart_id {
+            return;
+        }
+
+        self.logger
+            .send(LogMessage::Event(Message::Launch {
+                id: payload.trace.node.id(),
+                launch: launch.clone(),
+                result_time: self.epoch.elapsed(),
+            }))
+            .expect("sending message");
+    }
+
+    fn commit_source(&self, payload: &Self::PayLoad, source: &str) {
+        if self.restart_id.load(Ordering::SeqCst) > payload.restart_id {
+            return;
+        }
+
+        self.logger
+            .send(LogMessage::Event(Message::Source {
+                id: payload.trace.node.id(),
+                actions: payload.trace.node.actions(),
+                source: source.to_string(),
+                result_time: self.epoch.elapsed(),
+            }))
+            .expect("sending message");
+    }
+
+    fn commit_energy(&self, payload: &Self::PayLoad, runtime: f64, energy: f64) {
+        if self.restart_id.load(Ordering::SeqCst) > payload.restart_id {
+            return;
+        }
+
+        self.logger
+            .send(LogMessage::Event(Message::Energy {
+                id: payload.trace.node.id(),
+                runtime,
+                energy,
+                result_time: self.epoch.elapsed(),
+            }))
+            .expect("sending message");
+    }
+
+    fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)> {
+        self.descend(context, true)
+            .map(|(candidate, trace)| self.candidate(candidate, trace))
+    }
+
+    fn explore_prefix(
+        &self,
+        context: &dyn Context,
+    ) -> Option<(Vec<Action>, Candidate, Self::PayLoad)> {
+        self.descend(context, false).map(|(candidate, trace)| {
+            let actions = trace.node.actions();
+            let (candidate, payload) = self.candidate(candidate, trace);
+            (actions, candidate, payload)
+        })
+    }
+
+    fn restart(&self) {
+        // Update the restart_id before resetting to ensure that the monitor thread doesn't go
+        // updating the wrong tree.
+        self.restart_id.fetch_add(1, Ordering::SeqCst);
+        self.root.reset();
+        self.root.store_candidate(self.space.clone());
+        self.deadends_since_restart.store(0, Ordering::Relaxed);
+        // Nodes are not revived by `reset`, so killing the nodes of the tabu prefixes
+        // excludes them from the following descents.
+        if let Some(tabu) = &self.config.tabu {
+            self.update_tabu(tabu);
+        }
+    }
+
+    fn needs_restart(&self) -> bool {
+        deadends_need_restart(
+            self.config.restart_after_deadends,
+            self.deadends_since_restart.load(Ordering::Relaxed),
+        )
     }
 
     fn stop_exploration(&self) {
         self.stop.store(true, Ordering::Relaxed)
     }
 
-    fn print_stats(&self) {}
+    fn checkpoint(&self, since: usize) -> io::Result<Checkpoint> {
+        let evaluations = self.evaluations.lock().unwrap();
+        Ok(Checkpoint {
+            evaluations: evaluations.iter().skip(since).cloned().collect(),
+        })
+    }
+
+    fn restore(
+        &self,
+        context: &dyn Context,
+        checkpoint: &Checkpoint,
+    ) -> io::Result<Vec<(Candidate, f64, bool, Self::PayLoad)>> {
+        // Rebuild all the traces before committing any evaluation, so that the cut does not
+        // prune the implementations evaluated before the best one.
+        Ok(checkpoint
+            .evaluations
+            .iter()
+            .filter_map(|&(ref actions, eval, provisional)| {
+                let replay = self.replay(context, actions);
+                if replay.is_none() {
+                    warn!("cannot replay actions {:?} from the checkpoint", actions);
+                }
+                replay.map(|(candidate, trace)| {
+                    let (candidate, payload) = self.candidate(candidate, trace);
+                    let eval = eval.unwrap_or(std::f64::INFINITY);
+                    (candidate, eval, provisional, payload)
+                })
+            })
+            .collect())
+    }
+
+    fn print_stats(&self) {
+        let num_nodes = self.id_counter.load(Ordering::Relaxed);
+        let duration = self.epoch.elapsed();
+        let duration_secs =
+            duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9;
+        warn!(
+            "Created {} nodes in {:.2}s ({:.1} nodes/s)",
+            num_nodes,
+            duration_secs,
+            num_nodes as f64 / duration_secs
+        );
+        warn!("Deadends: {}", self.deadends);
+        warn!("Bounds: {}", self.bound_stats);
+        if let Some(conflicts) = &self.conflicts {
+            warn!("Conflicts: {}", conflicts);
+        }
+    }
+
+    fn deadends(&self) -> Vec<(&'static str, usize)> {
+        self.deadends.counts()
+    }
 }
 
 impl NewNodeOrder {
@@ -1456,26 +2353,150 @@ impl NewNodeOrder {
             }
         }
     }
-}
 
-impl<N, E> TreePolicy<N, E> for NewNodeOrder {
-    fn pick_child(
-        &'_ self,
+    /// Returns the selector used to pick one of `children`, or `None` if all the children
+    /// are cut.
+    fn view_selector<N, E>(
+        self,
         cut: f64,
         children: &NodeView<'_, N, E>,
-    ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)> {
-        self.into_selector(
-            cut,
-            children.iter().filter_map(|(idx, _edge, node)| {
+    ) -> Option<Selector<EdgeViewIndex>> {
+        let live = children
+            .iter()
+            .filter_map(|(idx, _edge, node)| {
                 let b = node.bound().unwrap().value();
                 if b < cut {
-                    Some((idx, b))
+                    Some((idx, b, node.estimate()))
                 } else {
                     None
                 }
-            }),
-        )
-        .map(|selector| children.select_with(selector))
+            })
+            .collect::<Vec<_>>();
+        // Children are pruned with their bound above, but ordered with their estimate when
+        // it is available.  Estimates are not bounds and can be above the cut.
+        if live.iter().all(|&(_, _, estimate)| estimate.is_some()) {
+            let estimates = live
+                .into_iter()
+                .map(|(idx, _, estimate)| (idx, estimate.unwrap()));
+            self.into_selector(std::f64::INFINITY, estimates)
+        } else {
+            self.into_selector(cut, live.into_iter().map(|(idx, b, _)| (idx, b)))
+        }
+    }
+}
+
+impl<N, E> TreePolicy<N, E> for NewNodeOrder {
+    fn pick_child(
+        &'_ self,
+        cut: f64,
+        children: &NodeView<'_, N, E>,
+        rng: &mut dyn RngCore,
+    ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)> {
+        self.view_selector(cut, children)
+            .map(|selector| children.select_with(selector, rng))
+    }
+}
+
+/// Returns the prefixes of `config.depth` actions whose evaluations are all bad, along with
+/// the number of evaluations in their subtree and the best of them.  Evaluations that
+/// failed are considered bad.
+fn bad_prefixes<'a, A>(
+    evaluations: &'a [(Vec<A>, Option<f64>, bool)],
+    config: &TabuConfig,
+) -> Vec<(&'a [A], usize, f64)>
+where
+    A: Eq + std::hash::Hash,
+{
+    if config.depth == 0 {
+        return vec![];
+    }
+    let best = evaluations
+        .iter()
+        .filter_map(|&(_, eval, _)| eval)
+        .fold(std::f64::INFINITY, f64::min);
+    let mut prefixes = FxHashMap::<&[A], (usize, f64)>::default();
+    for (actions, eval, _) in evaluations {
+        if actions.len() <= config.depth {
+            continue;
+        }
+        let entry = prefixes
+            .entry(&actions[..config.depth])
+            .or_insert((0, std::f64::INFINITY));
+        entry.0 += 1;
+        entry.1 = entry.1.min(eval.unwrap_or(std::f64::INFINITY));
+    }
+    prefixes
+        .into_iter()
+        // The strict comparison keeps prefixes when no evaluation succeeded at all.
+        .filter(|&(_, (num_evals, prefix_best))| {
+            num_evals >= config.min_evaluations && prefix_best > config.threshold * best
+        })
+        .map(|(prefix, (num_evals, prefix_best))| (prefix, num_evals, prefix_best))
+        .collect()
+}
+
+/// Indicates if enough dead-ends were encountered since the last restart to restart the
+/// search, given the `restart_after_deadends` parameter.
+fn deadends_need_restart(restart_after_deadends: Option<usize>, deadends: usize) -> bool {
+    restart_after_deadends.map_or(false, |limit| deadends >= limit)
+}
+
+/// A default policy that weights the choices of a `NewNodeOrder` with user-provided priors.
+/// See `config::ChoicePrior`.
+pub struct PriorPolicy {
+    order: NewNodeOrder,
+    priors: FxHashMap<String, f64>,
+}
+
+impl PriorPolicy {
+    /// Creates a policy that weights `order` with `priors`.  The weights of priors that
+    /// apply to the same action are multiplied.
+    pub fn new(order: NewNodeOrder, priors: &[config::ChoicePrior]) -> Self {
+        let mut weights = FxHashMap::default();
+        for prior in priors {
+            *weights.entry(prior.action.clone()).or_insert(1.) *= prior.weight;
+        }
+        PriorPolicy {
+            order,
+            priors: weights,
+        }
+    }
+
+    /// Returns the weight of an action, which is one if no prior applies to the action.
+    fn weight(&self, action: &Action) -> f64 {
+        self.priors
+            .get(&format!("{:?}", action))
+            .cloned()
+            .unwrap_or(1.)
+    }
+}
+
+impl<N, E> TreePolicy<N, E> for PriorPolicy {
+    fn pick_child(
+        &'_ self,
+        cut: f64,
+        children: &NodeView<'_, N, E>,
+        rng: &mut dyn RngCore,
+    ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)> {
+        let weight = |idx: EdgeViewIndex| self.weight(children[idx].0.action());
+        let selector = match self.order.view_selector(cut, children)? {
+            Selector::Random { weights } => Selector::Random {
+                weights: weights
+                    .into_iter()
+                    .map(|(idx, w)| (idx, w * weight(idx)))
+                    .collect(),
+            },
+            // Scores are negated bounds or estimates, which are non-negative.
+            Selector::Maximum { scores } => Selector::Maximum {
+                scores: scores
+                    .into_iter()
+                    .map(|(idx, score)| (idx, score / weight(idx)))
+                    .collect(),
+            },
+            // The API order is deterministic and does not account for priors.
+            selector @ Selector::Exact { .. } => selector,
+        };
+        Some(children.select_with(selector, rng))
     }
 }
 
@@ -1491,6 +2512,8 @@ pub struct UCTPolicy {
     value_reduction: config::ValueReduction,
     reward: config::Reward,
     formula: config::Formula,
+    coverage_weight: f64,
+    coverage: ChoiceCoverage,
 }
 
 impl From<config::UCTConfig> for UCTPolicy {
@@ -1501,6 +2524,7 @@ impl From<config::UCTConfig> for UCTPolicy {
             value_reduction,
             reward,
             formula,
+            coverage_weight,
         } = config;
         UCTPolicy {
             exploration_constant,
@@ -1508,6 +2532,8 @@ impl From<config::UCTConfig> for UCTPolicy {
             value_reduction,
             reward,
             formula,
+            coverage_weight,
+            coverage: ChoiceCoverage::default(),
         }
     }
 }
@@ -1524,6 +2550,19 @@ impl UCTPolicy {
         }
     }
 
+    /// Bonus given to under-visited choice values, independently of where they appear in the
+    /// tree.  Actions which are not tracked by the coverage table get no bonus.
+    fn coverage_term(&self, cut: f64, action: &Action) -> f64 {
+        if self.coverage_weight == 0. {
+            return 0.;
+        }
+
+        self.coverage.count(action).map_or(0., |count| {
+            self.coverage_weight * self.exploration_factor(cut)
+                / (1. + count as f64).sqrt()
+        })
+    }
+
     fn exploration_term(
         &self,
         cut: f64,
@@ -1603,9 +2642,10 @@ impl<'a, N, E> NodeView<'a, N, E> {
     fn select_with(
         &self,
         selector: Selector<EdgeViewIndex>,
+        rng: &mut dyn RngCore,
     ) -> (EdgeViewIndex, Selector<EdgeIndex>) {
         (
-            selector.select(),
+            selector.select(rng),
             selector.map(|index| self[index].0.index()),
         )
     }
@@ -1681,7 +2721,8 @@ impl<T> Selector<T> {
 }
 
 impl<T: Clone> Selector<T> {
-    pub fn select(&self) -> T {
+    /// Selects a value, drawing random choices from `rng`.
+    pub fn select<R: Rng + ?Sized>(&self, rng: &mut R) -> T {
         match self {
             Selector::Random { weights } => {
                 let resolution = f64::from(u32::max_value() / weights.len() as u32);
@@ -1696,7 +2737,7 @@ impl<T: Clone> Selector<T> {
                         })
                         .collect::<Vec<_>>(),
                 )
-                .sample(&mut thread_rng());
+                .sample(rng);
                 weights[index].0.clone()
             }
             Selector::Maximum { scores } => scores
@@ -1715,6 +2756,7 @@ impl<N> TreePolicy<N, UCTStats> for UCTPolicy {
         &'_ self,
         cut: f64,
         children: &NodeView<'_, N, UCTStats>,
+        rng: &mut dyn RngCore,
     ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)> {
         let stats = children
             .iter()
@@ -1725,6 +2767,10 @@ impl<N> TreePolicy<N, UCTStats> for UCTPolicy {
                 )
             })
             .collect::<Vec<_>>();
+        let coverage = children
+            .iter()
+            .map(|(_index, edge, _node)| self.coverage_term(cut, edge.action()))
+            .collect::<Vec<_>>();
 
         // If there are unvisited nodes, pick from them
         NewNodeOrder::WeightedRandom
@@ -1750,7 +2796,8 @@ impl<N> TreePolicy<N, UCTStats> for UCTPolicy {
                 Selector::try_maximum(
                     stats
                         .into_iter()
-                        .map(|(idx, (_bound, (value, visits)))| {
+                        .zip(coverage)
+                        .map(|((idx, (_bound, (value, visits))), coverage)| {
                             (
                                 idx,
                                 value
@@ -1759,14 +2806,15 @@ impl<N> TreePolicy<N, UCTStats> for UCTPolicy {
                                         visits as f64,
                                         total_visits,
                                         num_children,
-                                    ),
+                                    )
+                                    + coverage,
                             )
                         })
                         .collect(),
                 )
             })
             .map(|selector| {
-                let (index, selector) = children.select_with(selector);
+                let (index, selector) = children.select_with(selector, rng);
                 children[index].0.data().down();
                 (index, selector)
             })
@@ -1782,6 +2830,59 @@ impl<N> TreePolicy<N, UCTStats> for UCTPolicy {
             parent[index].data().up(self.reward(eval))
         }
     }
+
+    fn observe_evaluation(&'_ self, actions: &List<Action>, _eval: Option<f64>) {
+        if self.coverage_weight != 0. {
+            self.coverage.record(actions.iter());
+        }
+    }
+}
+
+/// A choice value, independent of the objects it applies to.  Used to track which scheduling
+/// decisions have been explored across the whole tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum CoverageKey {
+    DimKind(DimKind),
+    Order(search_space::Order),
+    InstFlag(InstFlag),
+}
+
+impl CoverageKey {
+    fn from_action(action: &Action) -> Option<Self> {
+        use crate::search_space::Action as A;
+
+        match action {
+            Action::Action(A::DimKind(_, kind)) => Some(CoverageKey::DimKind(*kind)),
+            Action::Action(A::Order(_, _, order)) => Some(CoverageKey::Order(*order)),
+            Action::Action(A::InstFlag(_, flag)) => Some(CoverageKey::InstFlag(*flag)),
+            _ => None,
+        }
+    }
+}
+
+/// Counts how many evaluated implementations used each choice value.
+#[derive(Debug, Default)]
+struct ChoiceCoverage {
+    counts: RwLock<FxHashMap<CoverageKey, usize>>,
+}
+
+impl ChoiceCoverage {
+    /// Records the choice values of an evaluated implementation.
+    fn record<'a>(&self, actions: impl IntoIterator<Item = &'a Action>) {
+        let mut counts = self.counts.write().expect("coverage: poisoned");
+        for key in actions.into_iter().filter_map(CoverageKey::from_action) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    /// Number of evaluated implementations using the choice value of `action`, or `None` if the
+    /// action is not tracked.
+    fn count(&self, action: &Action) -> Option<usize> {
+        CoverageKey::from_action(action).map(|key| {
+            let counts = self.counts.read().expect("coverage: poisoned");
+            counts.get(&key).cloned().unwrap_or(0)
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -1929,6 +3030,7 @@ impl<N> TreePolicy<N, TAGStats> for TAGPolicy {
         &'_ self,
         cut: f64,
         children: &NodeView<'_, N, TAGStats>,
+        rng: &mut dyn RngCore,
     ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)> {
         // Ignore cut children.  Also, we compute the number of visits beforehand to ensure that it
         // doesn't get changed by concurrent accesses.
@@ -2015,7 +3117,7 @@ impl<N> TreePolicy<N, TAGStats> for TAGPolicy {
                 )
             })
             .map(|selector| {
-                let (index, selector) = children.select_with(selector);
+                let (index, selector) = children.select_with(selector, rng);
                 children[index].0.data().down();
                 (index, selector)
             })
@@ -2144,6 +3246,7 @@ impl<N> TreePolicy<N, CommonStats> for RoundRobinPolicy {
         &'_ self,
         _cut: f64,
         view: &NodeView<'_, N, CommonStats>,
+        rng: &mut dyn RngCore,
     ) -> Option<(EdgeViewIndex, Selector<EdgeIndex>)> {
         Selector::try_maximum(
             view.iter()
@@ -2151,7 +3254,7 @@ impl<N> TreePolicy<N, CommonStats> for RoundRobinPolicy {
                 .collect(),
         )
         .map(|selector| {
-            let (index, selector) = view.select_with(selector);
+            let (index, selector) = view.select_with(selector, rng);
             view[index].0.data().down();
             (index, selector)
         })
@@ -2165,3 +3268,117 @@ impl<N> TreePolicy<N, CommonStats> for RoundRobinPolicy {
     ) {
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::fake;
+    use crate::helper;
+    use crate::ir;
+
+    fn tabu_config(depth: usize, min_evaluations: usize) -> TabuConfig {
+        TabuConfig {
+            depth,
+            min_evaluations,
+            threshold: 2.,
+        }
+    }
+
+    #[test]
+    fn bad_prefixes_threshold() {
+        let evaluations = vec![
+            (vec![0, 1, 2], Some(1.), false),
+            (vec![0, 1, 3], Some(1.5), false),
+            (vec![1, 0, 2], Some(3.), false),
+            (vec![1, 0, 3], None, false),
+            (vec![2, 0, 1], Some(1.9), false),
+            (vec![2, 0, 2], Some(4.), false),
+        ];
+        let prefixes = bad_prefixes(&evaluations, &tabu_config(2, 1));
+        assert_eq!(prefixes, vec![(&[1, 0][..], 2, 3.)]);
+    }
+
+    #[test]
+    fn bad_prefixes_min_evaluations() {
+        let evaluations = vec![
+            (vec![0, 1, 2], Some(1.), false),
+            (vec![1, 0, 2], Some(3.), false),
+            (vec![1, 0, 3], Some(4.), false),
+            (vec![2, 0, 1], Some(5.), false),
+        ];
+        let prefixes = bad_prefixes(&evaluations, &tabu_config(2, 2));
+        assert_eq!(prefixes, vec![(&[1, 0][..], 2, 3.)]);
+    }
+
+    #[test]
+    fn bad_prefixes_ignores_short_traces() {
+        let evaluations = vec![(vec![0], Some(1.), false), (vec![1, 0], Some(3.), false)];
+        assert!(bad_prefixes(&evaluations, &tabu_config(2, 1)).is_empty());
+        assert!(bad_prefixes(&evaluations, &tabu_config(0, 1)).is_empty());
+    }
+
+    /// Prefixes are not tabu when no evaluation succeeded, since there is nothing to
+    /// compare them with.
+    #[test]
+    fn bad_prefixes_without_success() {
+        let evaluations =
+            vec![(vec![0, 1, 2], None, false), (vec![1, 0, 2], None, false)];
+        assert!(bad_prefixes(&evaluations, &tabu_config(1, 1)).is_empty());
+    }
+
+    #[test]
+    fn restart_after_deadends() {
+        assert!(!deadends_need_restart(None, 1000));
+        assert!(!deadends_need_restart(Some(10), 9));
+        assert!(deadends_need_restart(Some(10), 10));
+        assert!(deadends_need_restart(Some(0), 0));
+    }
+
+    /// An estimator predicting a negative execution time.
+    struct NegativeEstimator;
+
+    impl CostEstimator for NegativeEstimator {
+        fn estimate(&self, _: &SearchSpace, _: &Bound, _: &dyn Context) -> f64 {
+            -1.
+        }
+    }
+
+    /// Ensures estimates below the bound of the performance model are clamped.
+    #[test]
+    fn clamp_estimates() {
+        let context = fake::Context::<fake::Device>::default();
+        let signature = Arc::new(ir::Signature::new("test".to_string()));
+        let mut builder = helper::Builder::new(signature, context.device());
+        builder.mov(&0f32);
+        let space = builder.get();
+        let choice_ordering = ChoiceOrdering::default();
+        let env =
+            Env::new(&choice_ordering, &context).with_estimator(Some(&NegativeEstimator));
+        let bound = env.bound(&space);
+        assert_eq!(env.estimate(&space, &bound), Some(bound.value()));
+    }
+
+    /// Ensures the energy measured for a node is reported in the JSON log.
+    #[test]
+    fn energy_json_record() {
+        let mut message = Message::Energy {
+            id: NodeId::from(3),
+            runtime: 2.,
+            energy: 5.,
+            result_time: std::time::Duration::from_secs(1),
+        };
+        message.map_node_ids(|id| NodeId::from(u64::from(id) + 1));
+        let records = message.json_records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            serde_json::to_value(&records[0]).unwrap(),
+            serde_json::json!({
+                "event": "energy",
+                "id": 4,
+                "runtime": 2.,
+                "energy": 5.,
+                "timestamp": 1.,
+            }),
+        );
+    }
+}
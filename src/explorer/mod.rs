@@ -1,43 +1,69 @@
 //! exploration of the search space.
+mod annealing;
 mod candidate;
+pub(crate) mod conflicts;
+mod ensemble;
+mod evolution;
+mod forest;
 mod logger;
 mod monitor;
 mod parallel_list;
+mod rng;
 mod store;
 
 pub mod choice;
 pub mod config;
+pub mod cost_model;
+pub mod distributed;
 pub mod eventlog;
+pub mod features;
 pub mod local_selection;
 pub mod mcts;
+pub mod refine;
+pub mod registry;
+pub mod restrict;
 
 pub use self::candidate::Candidate;
-pub use self::config::{BanditConfig, Config, SearchAlgorithm};
-pub use self::logger::LogMessage;
+pub use self::config::{
+    AnnealingConfig, BanditConfig, ChoicePrior, Config, EnsembleConfig, EvolutionConfig,
+    LoggingConfig, Objective, RefineConfig, ScreeningConfig, SearchAlgorithm, TabuConfig,
+};
+pub use self::logger::{JsonRecord, LogMessage, StructuredEvent};
+pub use self::store::{Checkpoint, Store};
 
-use self::choice::fix_order;
-use self::monitor::{monitor, MonitorMessage};
+use self::annealing::AnnealingStore;
+use self::cost_model::TreeEnsemble;
+use self::ensemble::EnsembleStore;
+use self::evolution::EvolutionStore;
+use self::forest::ForestStore;
+use self::monitor::{monitor, Backlog, Measurement, MonitorMessage};
 use self::parallel_list::ParallelCandidateList;
-use self::store::Store;
+use self::registry::Registration;
 
-use crate::device::{Context, EvalMode};
-use crate::model::bound;
+use crate::cache::{CacheKey, TuningCache};
+use crate::codegen;
+use crate::device::{CancellationToken, Context, EvalMode};
+use crate::model::{bound, Blend, CostEstimator};
 use crate::search_space::SearchSpace;
 
 use crossbeam;
-use futures::executor;
-use futures::prelude::*;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
+use std::collections::BinaryHeap;
 use std::sync::{
     self,
     atomic::{AtomicUsize, Ordering},
-    mpsc, Mutex,
+    mpsc, Arc, Mutex,
 };
-use utils::unwrap;
+use std::time::Instant;
+use utils::{cmp_f64, unwrap};
 
 pub type CheckResultFn<'a> =
     dyn Fn(&Candidate, &dyn Context) -> Result<(), String> + Sync + 'a;
 
+/// A function called with each new best candidate found by the search and its score.  The
+/// search stops if it returns `false`.
+pub type NewBestFn<'a> = dyn Fn(&Candidate, f64) -> bool + Sync + 'a;
+
 // TODO(cc_perf): To improve performances, the following should be considered:
 // * choices should be ranked once and then reused for multiple steps.
 // * empty and unitary choices should be applied a soon as they are detected.
@@ -65,53 +91,57 @@ pub fn find_best(
             })
             .collect(),
         check_result_fn,
+        &[],
     )
     .map(|c| c.space)
 }
 
-struct MctsBuilder<'a> {
-    space: SearchSpace,
+/// The parameters of a search that do not depend on the search algorithm.
+#[derive(Clone, Copy)]
+struct Search<'a> {
     config: &'a Config,
-    bandit_config: &'a BanditConfig,
     context: &'a dyn Context,
     check_result_fn: Option<&'a CheckResultFn<'a>>,
+    on_new_best: Option<&'a NewBestFn<'a>>,
+    registration: &'a Registration,
+    lower_bound: f64,
+    /// The root candidates, from which the best implementation is refined if
+    /// `config.refine` is set.
+    roots: &'a [Candidate],
+    /// Stops the search when cancelled.
+    cancel: &'a CancellationToken,
 }
 
-impl<'a> MctsBuilder<'a> {
-    fn search<N, E>(
-        self,
-        tree_policy: Box<dyn mcts::TreePolicy<N, E>>,
-        default_policy: Box<dyn mcts::TreePolicy<N, E>>,
-    ) -> Option<Candidate>
+impl<'a> Search<'a> {
+    /// Runs the search with the store returned by `new_store`, which is given the channel
+    /// to the logger and the random number generator of the search, seeded from
+    /// `config.seed`.  The best implementation is then refined if `config.refine` is set.
+    fn run<S, F>(self, new_store: F) -> Option<Candidate>
     where
-        N: Sync + Send + std::fmt::Debug + Default + mcts::Reset,
-        E: Sync + Send + std::fmt::Debug + Default + mcts::Reset,
+        S: Store + Send,
+        F: FnOnce(&mpsc::SyncSender<LogMessage<S::Event>>, &mut rng::ExplorerRng) -> S,
     {
-        let MctsBuilder {
-            space,
+        let Search {
             config,
-            bandit_config,
             context,
             check_result_fn,
+            on_new_best,
+            registration,
+            lower_bound,
+            cancel,
+            ..
         } = self;
-
         crossbeam::scope(|scope| {
-            let (log_sender, log_receiver) = mpsc::sync_channel(100);
+            let (log_sender, log_receiver) = mpsc::sync_channel(config.log_channel_size);
             unwrap!(scope
                 .builder()
                 .name("Telamon - Logger".to_string())
                 .spawn(|_| unwrap!(logger::log(config, log_receiver))));
 
-            let store = mcts::MctsStore::new(
-                space,
-                context,
-                bandit_config,
-                tree_policy,
-                default_policy,
-                log_sender.clone(),
-            );
-
-            unwrap!(scope
+            let store = new_store(&log_sender, &mut rng::new(config.seed));
+            let refine_log = log_sender.clone();
+            let start_time = Instant::now();
+            let best = unwrap!(scope
                 .builder()
                 .name("Telamon - Search".to_string())
                 .spawn(move |_| launch_search(
@@ -119,99 +149,451 @@ impl<'a> MctsBuilder<'a> {
                     store,
                     context,
                     log_sender,
-                    check_result_fn
+                    check_result_fn,
+                    on_new_best,
+                    registration,
+                    lower_bound,
+                    cancel
                 ))
                 .unwrap()
-                .join())
+                .join());
+            best.map(|(best, num_evaluations)| {
+                self.refine(best, num_evaluations, start_time, &refine_log)
+            })
         })
         .unwrap()
     }
+
+    /// Refines `best` if `config.refine` is set.  Improvements are reported as new best
+    /// candidates, counting the evaluations of the refinement after the `num_evaluations`
+    /// of the search.
+    fn refine<E>(
+        self,
+        best: Candidate,
+        num_evaluations: usize,
+        start_time: Instant,
+        log_sender: &mpsc::SyncSender<LogMessage<E>>,
+    ) -> Candidate {
+        let config = self.config;
+        let refine_config = config
+            .refine
+            .as_ref()
+            .filter(|_| config.objective == Objective::Time && !config.dry_run);
+        let refine_config = match refine_config {
+            Some(refine_config) => refine_config,
+            None => return best,
+        };
+        let on_new_best = &mut |cand: &Candidate, eval: f64, num_refined: usize| {
+            let num_evaluations = num_evaluations + num_refined;
+            unwrap!(log_sender.send(LogMessage::NewBest {
+                score: eval,
+                cpt: num_evaluations,
+                timestamp: start_time.elapsed(),
+            }));
+            self.registration.evaluated(num_evaluations);
+            self.registration.new_best(cand, eval);
+            self.on_new_best
+                .map_or(true, |on_new_best| on_new_best(cand, eval))
+        };
+        refine::refine(
+            refine_config,
+            self.context,
+            self.roots,
+            best,
+            self.check_result_fn,
+            on_new_best,
+        )
+    }
+}
+
+struct MctsBuilder<'a> {
+    /// The root candidates.  Each root is explored by its own tree.
+    spaces: Vec<SearchSpace>,
+    search: Search<'a>,
+    bandit_config: &'a BanditConfig,
+    estimator: Option<Arc<dyn CostEstimator>>,
+    warm_start: &'a [Vec<choice::ActionEx>],
+}
+
+impl<'a> MctsBuilder<'a> {
+    /// Runs the search with one tree per root candidate, each using the tree policy
+    /// returned by `tree_policy`.
+    fn search<N, E>(
+        self,
+        tree_policy: &dyn Fn() -> Box<dyn mcts::TreePolicy<N, E>>,
+    ) -> Option<Candidate>
+    where
+        N: Sync + Send + std::fmt::Debug + Default + mcts::Reset,
+        E: Sync + Send + std::fmt::Debug + Default + mcts::Reset,
+    {
+        let MctsBuilder {
+            spaces,
+            search,
+            bandit_config,
+            estimator,
+            warm_start,
+        } = self;
+        let context = search.context;
+
+        search.run(|log_sender, rng| {
+            // The trees log to the same event log.
+            let node_ids = Arc::new(AtomicUsize::new(0));
+            let trees = spaces
+                .into_iter()
+                .map(|space| {
+                    let mut store = mcts::MctsStore::with_node_ids(
+                        space,
+                        context,
+                        bandit_config,
+                        tree_policy(),
+                        Box::new(bandit_config.new_nodes_order),
+                        log_sender.clone(),
+                        Arc::clone(&node_ids),
+                    )
+                    .with_rng(rng::fork(rng));
+                    if let Some(estimator) = &estimator {
+                        store = store.with_estimator(Arc::clone(estimator));
+                    }
+                    if !bandit_config.priors.is_empty() {
+                        store = store.with_priors(&bandit_config.priors);
+                    }
+                    if !warm_start.is_empty() {
+                        store = store.with_warm_start(warm_start.to_vec());
+                    }
+                    store
+                })
+                .collect();
+            ForestStore::new(trees)
+        })
+    }
+}
+
+impl<'a> MctsBuilder<'a> {
+    /// Runs an ensemble of UCT trees for each root candidate, configured by
+    /// `config.ensemble`, that differ by their exploration constant.
+    fn search_ensemble(self, uct_config: &config::UCTConfig) -> Option<Candidate> {
+        let MctsBuilder {
+            spaces,
+            search,
+            bandit_config,
+            estimator,
+            warm_start,
+        } = self;
+        let (config, context) = (search.config, search.context);
+        let ensemble_config = unwrap!(config.ensemble.as_ref());
+
+        search.run(|log_sender, rng| {
+            // The trees log to the same event log.
+            let node_ids = Arc::new(AtomicUsize::new(0));
+            let ensembles = spaces
+                .iter()
+                .map(|space| {
+                    let trees = ensemble_config
+                        .exploration_constants
+                        .iter()
+                        .enumerate()
+                        .map(|(index, &exploration_constant)| {
+                            let tree_policy = mcts::UCTPolicy::from(config::UCTConfig {
+                                exploration_constant,
+                                ..uct_config.clone()
+                            });
+                            let store =
+                                mcts::MctsStore::<(), mcts::UCTStats>::with_node_ids(
+                                    space.clone(),
+                                    context,
+                                    bandit_config,
+                                    Box::new(tree_policy),
+                                    Box::new(bandit_config.new_nodes_order),
+                                    log_sender.clone(),
+                                    Arc::clone(&node_ids),
+                                )
+                                .with_rng(rng::fork(rng));
+                            let store = match &estimator {
+                                Some(estimator) => {
+                                    store.with_estimator(Arc::clone(estimator))
+                                }
+                                None => store,
+                            };
+                            let store = if bandit_config.priors.is_empty() {
+                                store
+                            } else {
+                                store.with_priors(&bandit_config.priors)
+                            };
+                            // Other trees benefit from the warm start through the exchanges.
+                            if index == 0 {
+                                store.with_warm_start(warm_start.to_vec())
+                            } else {
+                                store
+                            }
+                        })
+                        .collect();
+                    EnsembleStore::new(trees, ensemble_config, config.num_workers)
+                })
+                .collect();
+            ForestStore::new(ensembles)
+        })
+    }
+}
+
+/// Loads the learned cost model described by `cost_model`, blended with the bound of the
+/// performance model.
+fn load_estimator(
+    cost_model: &config::CostModelConfig,
+) -> Result<Arc<dyn CostEstimator>, String> {
+    let model = TreeEnsemble::load(&cost_model.path)
+        .map_err(|err| format!("{}: {}", cost_model.path, err))?;
+    Ok(Arc::new(Blend {
+        estimator: model,
+        weight: cost_model.weight,
+    }))
 }
 
 /// Same as `find_best`, but allows to specify pre-existing actions and also returns the
 /// actions for the best candidate.
+///
+/// The search starts by following each list of actions in `warm_start`, typically the
+/// actions of the best implementations found by previous searches on similar problem
+/// sizes.  Actions that do not apply to the search space are ignored.  Only the MCTS
+/// search algorithm supports warm starts.
+///
+/// If `config.tuning_cache` is set, the implementation found by a previous search for the
+/// same kernel on the same device is returned when it exists, and the result of the
+/// search is recorded otherwise.
 pub fn find_best_ex(
     config: &Config,
     context: &dyn Context,
     candidates: Vec<Candidate>,
     check_result_fn: Option<&CheckResultFn<'_>>,
+    warm_start: &[Vec<choice::ActionEx>],
+) -> Option<Candidate> {
+    find_best_candidate(
+        config,
+        context,
+        candidates,
+        check_result_fn,
+        None,
+        warm_start,
+        &CancellationToken::new(),
+    )
+}
+
+/// Same as `find_best_ex` without warm start, but calls `on_new_best` each time the
+/// search finds a better candidate, so that callers can report progress or stop the search
+/// early.  The callback is called from the thread that collects evaluations and should
+/// return quickly.
+pub fn find_best_with_progress(
+    config: &Config,
+    context: &dyn Context,
+    candidates: Vec<Candidate>,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+    on_new_best: Option<&NewBestFn<'_>>,
+) -> Option<Candidate> {
+    find_best_candidate(
+        config,
+        context,
+        candidates,
+        check_result_fn,
+        on_new_best,
+        &[],
+        &CancellationToken::new(),
+    )
+}
+
+/// Same as `find_best_ex` without warm start, but stops the search once `cancel` is
+/// cancelled, for instance from another thread.  The best candidate found until then is
+/// returned.  The search also cancels the token when it stops for another reason.
+pub fn find_best_cancellable(
+    config: &Config,
+    context: &dyn Context,
+    candidates: Vec<Candidate>,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+    cancel: &CancellationToken,
+) -> Option<Candidate> {
+    find_best_candidate(
+        config,
+        context,
+        candidates,
+        check_result_fn,
+        None,
+        &[],
+        cancel,
+    )
+}
+
+/// Implements `find_best_ex`, `find_best_with_progress` and `find_best_cancellable`.
+fn find_best_candidate(
+    config: &Config,
+    context: &dyn Context,
+    candidates: Vec<Candidate>,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+    on_new_best: Option<&NewBestFn<'_>>,
+    warm_start: &[Vec<choice::ActionEx>],
+    cancel: &CancellationToken,
 ) -> Option<Candidate> {
+    if let Err(err) = config.validate() {
+        error!("invalid configuration: {}", err);
+        return None;
+    }
+    if config.coordinator.is_some() && candidates.len() != 1 {
+        error!("distributed searches require a single root candidate");
+        return None;
+    }
+    let candidates = if config.restrict_sizes {
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                let (candidate, report) = restrict::restrict_sizes(candidate, context);
+                info!("{}", report);
+                candidate
+            })
+            .collect()
+    } else {
+        candidates
+    };
+    let cache = config.tuning_cache.as_ref().map(|dir| {
+        let key = CacheKey::new(&candidates, context);
+        (TuningCache::new(dir), key)
+    });
+    if let Some((cache, key)) = &cache {
+        if let Some(best) = cache.get(key, &candidates, context) {
+            info!("using the cached implementation");
+            return Some(best);
+        }
+    }
+    // The refinement replays the actions of the best implementation from the roots.
+    let roots = if config.refine.is_some() {
+        candidates.clone()
+    } else {
+        vec![]
+    };
+    let estimator = match &config.algorithm {
+        config::SearchAlgorithm::Mcts(bandit_config) => {
+            match bandit_config.cost_model.as_ref().map(load_estimator) {
+                Some(Ok(estimator)) => Some(estimator),
+                Some(Err(err)) => {
+                    error!("cannot load cost model: {}", err);
+                    return None;
+                }
+                None => None,
+            }
+        }
+        _ => None,
+    };
+    let registration = &Registration::new(config);
+    let search = Search {
+        config,
+        context,
+        check_result_fn,
+        on_new_best,
+        registration,
+        lower_bound: candidates
+            .iter()
+            .map(|cand| cand.bound.value())
+            .fold(std::f64::INFINITY, f64::min),
+        roots: &roots,
+        cancel,
+    };
+    if config.seed.is_some() && config.num_workers > 1 {
+        warn!("the search is only deterministic with a single worker");
+    }
     match config.algorithm {
+        config::SearchAlgorithm::Mcts(..) => (),
+        _ if !warm_start.is_empty() => {
+            warn!("warm starts are only supported by the MCTS search algorithm")
+        }
+        _ => (),
+    }
+    let best = match config.algorithm {
         config::SearchAlgorithm::Mcts(ref bandit_config) => {
-            assert!(candidates.len() == 1);
-
             let builder = MctsBuilder {
-                space: candidates.into_iter().next().unwrap().space,
-                config,
+                spaces: candidates.into_iter().map(|c| c.space).collect(),
+                search,
                 bandit_config,
-                context,
-                check_result_fn,
+                estimator,
+                warm_start,
             };
 
-            let default_policy = Box::new(bandit_config.new_nodes_order);
-
             match &bandit_config.tree_policy {
+                config::TreePolicy::UCT(uct_config) if config.ensemble.is_some() => {
+                    builder.search_ensemble(uct_config)
+                }
+                _ if config.ensemble.is_some() => {
+                    unreachable!("an ensemble of trees requires the UCT tree policy")
+                }
                 config::TreePolicy::UCT(uct_config) => builder
-                    .search::<(), mcts::UCTStats>(
-                        Box::new(mcts::UCTPolicy::from(uct_config.clone())),
-                        default_policy,
-                    ),
+                    .search::<(), mcts::UCTStats>(&|| {
+                        Box::new(mcts::UCTPolicy::from(uct_config.clone()))
+                    }),
                 config::TreePolicy::TAG(tag_config) => builder
-                    .search::<(), mcts::TAGStats>(
-                        Box::new(mcts::TAGPolicy::from(tag_config.clone())),
-                        default_policy,
-                    ),
-                config::TreePolicy::Bound => builder.search::<(), ()>(
-                    Box::new(config::NewNodeOrder::Bound),
-                    default_policy,
-                ),
-                config::TreePolicy::WeightedRandom => builder.search::<(), ()>(
-                    Box::new(config::NewNodeOrder::WeightedRandom),
-                    default_policy,
-                ),
+                    .search::<(), mcts::TAGStats>(&|| {
+                        Box::new(mcts::TAGPolicy::from(tag_config.clone()))
+                    }),
+                config::TreePolicy::Bound => {
+                    builder.search::<(), ()>(&|| Box::new(config::NewNodeOrder::Bound))
+                }
+                config::TreePolicy::WeightedRandom => builder
+                    .search::<(), ()>(&|| Box::new(config::NewNodeOrder::WeightedRandom)),
                 config::TreePolicy::RoundRobin => builder
-                    .search::<(), mcts::CommonStats>(
-                        Box::new(mcts::RoundRobinPolicy),
-                        default_policy,
-                    ),
+                    .search::<(), mcts::CommonStats>(&|| {
+                        Box::new(mcts::RoundRobinPolicy)
+                    }),
             }
         }
-        config::SearchAlgorithm::BoundOrder => crossbeam::scope(|scope| {
-            let (log_sender, log_receiver) = sync::mpsc::sync_channel(100);
-            unwrap!(scope
-                .builder()
-                .name("Telamon - Logger".to_string())
-                .spawn(|_| (unwrap!(logger::log(config, log_receiver)))));
-
+        config::SearchAlgorithm::BoundOrder => search.run(|_, _| {
             let candidate_list = ParallelCandidateList::new(config.num_workers);
             candidate_list.insert_many(candidates);
-            unwrap!(scope
-                .builder()
-                .name("Telamon - Search".to_string())
-                .spawn(move |_| launch_search(
-                    config,
-                    candidate_list,
-                    context,
-                    log_sender,
-                    check_result_fn
-                ))
-                .unwrap()
-                .join())
-        })
-        .unwrap(),
+            candidate_list
+        }),
+        config::SearchAlgorithm::SimulatedAnnealing(ref annealing_config) => {
+            search.run(|_, rng| {
+                AnnealingStore::new(candidates, annealing_config).with_rng(rng::fork(rng))
+            })
+        }
+        config::SearchAlgorithm::Evolution(ref evolution_config) => {
+            search.run(|_, rng| {
+                EvolutionStore::new(candidates, evolution_config).with_rng(rng::fork(rng))
+            })
+        }
+    };
+    registration.finish();
+    if let (Some((cache, key)), Some(best)) = (&cache, &best) {
+        if let Err(err) = cache.insert(key, best) {
+            warn!("cannot cache the best implementation: {}", err);
+        }
     }
+    best
 }
 
 /// Launch all threads needed for the search. wait for each one of them to finish. Monitor is
-/// supposed to return the best candidate found
+/// supposed to return the best candidate found, along with the number of evaluations
 fn launch_search<T: Store>(
     config: &Config,
     candidate_store: T,
     context: &dyn Context,
     log_sender: sync::mpsc::SyncSender<LogMessage<T::Event>>,
     check_result_fn: Option<&CheckResultFn<'_>>,
-) -> Option<Candidate> {
-    let (monitor_sender, monitor_receiver) = futures::sync::mpsc::channel(100);
+    on_new_best: Option<&NewBestFn<'_>>,
+    registration: &Registration,
+    lower_bound: f64,
+    cancel: &CancellationToken,
+) -> Option<(Candidate, usize)> {
+    let (monitor_sender, monitor_receiver) =
+        futures::sync::mpsc::channel(config.monitor_channel_size);
+    let backlog = &Backlog::new(config.monitor_channel_size);
+    // Restore the checkpoint before starting the search, so that the search does not start
+    // from scratch when the checkpoint is invalid.
+    let resumed = match &config.resume {
+        Some(path) => match Checkpoint::load(path)
+            .and_then(|checkpoint| candidate_store.restore(context, &checkpoint))
+        {
+            Ok(evaluations) => evaluations,
+            Err(err) => {
+                error!("cannot resume from checkpoint {}: {}", path, err);
+                return None;
+            }
+        },
+        None => vec![],
+    };
     let maybe_candidate = crossbeam::scope(|scope| {
         let best_cand_opt = scope
             .builder()
@@ -223,16 +605,48 @@ fn launch_search<T: Store>(
                     &candidate_store,
                     monitor_receiver,
                     log_sender,
+                    backlog,
+                    cancel,
+                    on_new_best,
+                    registration,
+                    lower_bound,
                 )
             })
             .unwrap();
-        explore_space(
-            config,
-            &candidate_store,
-            monitor_sender,
-            context,
-            check_result_fn,
-        );
+        resume(resumed, monitor_sender.clone(), backlog);
+        if config.dry_run {
+            dry_run_space(
+                config,
+                &candidate_store,
+                monitor_sender,
+                backlog,
+                context,
+                cancel,
+            );
+        } else if let Some(addr) = &config.coordinator {
+            let res = distributed::explore_remote(
+                config,
+                &candidate_store,
+                monitor_sender,
+                backlog,
+                context,
+                cancel,
+                addr,
+            );
+            if let Err(err) = res {
+                error!("cannot wait for workers on {}: {}", addr, err);
+            }
+        } else {
+            explore_space(
+                config,
+                &candidate_store,
+                monitor_sender,
+                backlog,
+                context,
+                cancel,
+                check_result_fn,
+            );
+        }
         unwrap!(best_cand_opt.join())
     })
     .unwrap();
@@ -243,13 +657,80 @@ fn launch_search<T: Store>(
     maybe_candidate
 }
 
+/// Sends the evaluations restored from a checkpoint to the monitor, as if the candidates
+/// were just evaluated.
+fn resume<T: Store>(
+    evaluations: Vec<(Candidate, f64, bool, T::PayLoad)>,
+    eval_sender: futures::sync::mpsc::Sender<MonitorMessage<T>>,
+    backlog: &Backlog,
+) {
+    if evaluations.is_empty() {
+        return;
+    }
+    info!("resuming from {} evaluations", evaluations.len());
+    for (cand, eval, provisional, payload) in evaluations {
+        let measurement = if provisional {
+            Measurement::Provisional
+        } else {
+            Measurement::Confirmed
+        };
+        backlog.send(eval_sender.clone(), (cand, eval, measurement, payload));
+    }
+}
+
+/// Explores the search space without compiling nor evaluating candidates: the bound of each
+/// implementation is reported to the monitor as its execution time.
+fn dry_run_space<T>(
+    config: &Config,
+    candidate_store: &T,
+    eval_sender: futures::sync::mpsc::Sender<MonitorMessage<T>>,
+    backlog: &Backlog,
+    context: &dyn Context,
+    cancel: &CancellationToken,
+) where
+    T: Store,
+{
+    crossbeam::scope(|scope| {
+        for _ in 0..config.num_workers {
+            let eval_sender = eval_sender.clone();
+            scope.spawn(move |_| {
+                while let Some((cand, payload)) = candidate_store.explore(context) {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    if config.check_counters {
+                        check_counters(&cand);
+                    }
+                    let bound = cand.bound.value();
+                    backlog.send(
+                        eval_sender.clone(),
+                        (cand, bound, Measurement::Confirmed, payload),
+                    );
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+/// Logs the counters of the search space of an implementation that differ from the
+/// generated code.
+fn check_counters(cand: &Candidate) {
+    let function = codegen::Function::build(&cand.space);
+    for mismatch in function.check_counters() {
+        error!("{} for actions {:?}", mismatch, cand.actions);
+    }
+}
+
 /// Defines the work that explorer threads will do in a closure that will be passed to
 /// context.async_eval. Also defines a callback that will be executed by the evaluator
 fn explore_space<T>(
     config: &Config,
     candidate_store: &T,
     eval_sender: futures::sync::mpsc::Sender<MonitorMessage<T>>,
+    backlog: &Backlog,
     context: &dyn Context,
+    cancel: &CancellationToken,
     check_result_fn: Option<&CheckResultFn<'_>>,
 ) where
     T: Store,
@@ -259,41 +740,161 @@ fn explore_space<T>(
     let n_restarts = AtomicUsize::new(0);
     let is_leader = AtomicUsize::new(0);
     let stabilizer = &context.stabilizer().skip_bad_candidates(true);
+    let confirmer = &config
+        .screening
+        .as_ref()
+        .and_then(|screening| screening.confirm_runs)
+        .map_or_else(
+            || stabilizer.clone(),
+            |num_runs| stabilizer.clone().num_evals(num_runs),
+        );
+    let screening = &config.screening.as_ref().map(|screening| {
+        let screener = context
+            .stabilizer()
+            .skip_bad_candidates(true)
+            .num_evals(screening.num_runs)
+            .num_outliers(0);
+        (
+            screener,
+            Mutex::new(Screened::new(screening.promote_fraction)),
+        )
+    });
     let barrier = std::sync::Barrier::new(config.num_workers);
 
-    context.async_eval(config.num_workers, EvalMode::FindBest, &|evaluator| {
-        while let Some((cand, payload)) = candidate_store.explore(context) {
-            let space = fix_order(cand.space);
-            let eval_sender = eval_sender.clone();
-            evaluator.add_kernel(Candidate { space, ..cand }, move |leaf, compiled| {
-                let mut best = best_mutex.lock().unwrap();
-                let n_evals = n_evals.fetch_add(1, Ordering::SeqCst);
-
-                let mut eval = match stabilizer
-                    .wrap(compiled)
-                    .bound(Some(leaf.bound.value()))
-                    .best(*best)
-                    .evaluate()
-                {
-                    Some(eval) => eval,
-                    None => {
-                        error!(
-                            "evaluation failed for actions {:?}, with kernel {}",
-                            leaf.actions, compiled
-                        );
-
-                        std::f64::INFINITY
-                    }
-                };
+    context.async_eval(
+        config.num_workers,
+        config.num_compilers,
+        EvalMode::FindBest,
+        cancel,
+        &|evaluator| {
+            while let Some((cand, payload)) = candidate_store.explore(context) {
+                if cancel.is_cancelled() {
+                    break;
+                }
 
-                if let Some(check_result_fn) = check_result_fn {
-                    if eval.is_finite()
-                        && (config.check_all || best.is_none() || Some(eval) < *best)
-                    {
+                let open_orders = cand.open_orders.as_ref().map(|orders| &**orders);
+                let space = choice::fix_open_orders(cand.space, open_orders);
+                let eval_sender = eval_sender.clone();
+                evaluator.add_kernel(
+                    Candidate {
+                        space,
+                        open_orders: None,
+                        ..cand
+                    },
+                    move |leaf, compiled| {
+                        // Candidates cancelled before their evaluation are not reported
+                        // to the monitor, which stops along with the search.
+                        if cancel.is_cancelled() {
+                            debug!("evaluation of {} cancelled", leaf);
+                            return;
+                        }
+                        if config.check_counters {
+                            check_counters(&leaf);
+                        }
+                        // The launch configuration and the source code are only recorded
+                        // in the event log.
+                        if config.log_code && config.event_log.is_some() {
+                            let function = codegen::Function::build(&leaf.space);
+                            let launch = function.launch_config(context);
+                            candidate_store.commit_launch(&payload, &launch);
+                            let mut source = Vec::new();
+                            context.device().print(&function, &mut source);
+                            let source = String::from_utf8_lossy(&source);
+                            candidate_store.commit_source(&payload, &source);
+                        }
+
+                        let mut best = best_mutex.lock().unwrap();
+                        let n_evals = n_evals.fetch_add(1, Ordering::SeqCst);
+
+                        // Bounds and runtimes are only comparable with scores when
+                        // minimizing the execution time.
+                        let best_runtime = match config.objective {
+                            Objective::Time => *best,
+                            Objective::Energy | Objective::Edp => None,
+                        };
+
+                        let bound = Some(leaf.bound.value());
+                        let screen = screening.as_ref().map(|(screener, screened)| {
+                            let screen = screener
+                                .wrap(compiled)
+                                .bound(bound)
+                                .best(best_runtime)
+                                .evaluate();
+                            let screen_value = screen.unwrap_or(std::f64::INFINITY);
+                            candidate_store.commit_screening(&payload, screen_value);
+                            // Candidates that may beat the best one are always promoted,
+                            // so that the final pick is based on precise values.
+                            let promoted = unwrap!(screened.lock()).promote(screen_value)
+                                || (config.objective == Objective::Time
+                                    && screen_value.is_finite()
+                                    && best_runtime
+                                        .map_or(true, |best| screen_value < best));
+                            (screen, promoted)
+                        });
+                        // Promoted candidates are measured again with more runs before
+                        // their value is committed to the store.
+                        let (result, measurement) = match screen {
+                            Some((screen, false)) => (screen, Measurement::Provisional),
+                            Some((_, true)) => (
+                                confirmer
+                                    .wrap(compiled)
+                                    .bound(bound)
+                                    .best(best_runtime)
+                                    .evaluate(),
+                                Measurement::Confirmed,
+                            ),
+                            None => (
+                                stabilizer
+                                    .wrap(compiled)
+                                    .bound(bound)
+                                    .best(best_runtime)
+                                    .evaluate(),
+                                Measurement::Confirmed,
+                            ),
+                        };
+                        let mut eval = match result {
+                            Some(eval) => eval,
+                            None => {
+                                error!(
+                                    "evaluation failed for actions {:?}, with kernel {}",
+                                    leaf.actions, compiled
+                                );
+
+                                std::f64::INFINITY
+                            }
+                        };
+
+                        if config.objective.needs_energy() && eval.is_finite() {
+                            let energy = compiled.evaluate_energy().map(|(_, e)| e);
+                            info!(
+                                "evaluation #{}: runtime {:.4e}ns, energy {:.4e}nJ",
+                                n_evals,
+                                eval,
+                                energy.unwrap_or(std::f64::NAN)
+                            );
+                            if let Some(energy) = energy {
+                                candidate_store.commit_energy(&payload, eval, energy);
+                            }
+                            eval = match config.objective.score(eval, energy) {
+                                Some(score) => score,
+                                None => {
+                                    error!(
+                                        "the device does not support energy measurement"
+                                    );
+                                    std::f64::INFINITY
+                                }
+                            };
+                        }
+
+                        let needs_check = eval.is_finite()
+                            && (config.check_all || best.is_none() || Some(eval) < *best);
                         // The values computed by the kernel are kept in the context, so we
-                        // need to do this *now* before the evaluator runs any other version of
-                        // the kernel.
-                        if let Err(err) = check_result_fn(&leaf, context) {
+                        // need to do this *now* before the evaluator runs any other version
+                        // of the kernel.
+                        let check = check_result_fn
+                            .filter(|_| needs_check)
+                            .map(|check_result_fn| check_result_fn(&leaf, context));
+                        if let Some(Err(err)) = check {
                             error!(
                                 "Invalid results (score {:.4e}ns) at #{} for {}: {}",
                                 eval, n_evals, leaf, err
@@ -308,41 +909,108 @@ fn explore_space<T>(
 
                             eval = std::f64::INFINITY;
                         }
-                    }
-                }
 
-                // Only update best if the check passed!
-                if eval.is_finite() && (best.is_none() || Some(eval) < *best) {
-                    *best = Some(eval);
-                }
+                        // Only update best if the check passed!  Provisional values are
+                        // too noisy to become the best one.
+                        if measurement == Measurement::Confirmed
+                            && eval.is_finite()
+                            && (best.is_none() || Some(eval) < *best)
+                        {
+                            *best = Some(eval);
+                        }
+
+                        backlog.send(eval_sender, (leaf, eval, measurement, payload));
+                    },
+                );
 
-                if let Err(err) =
-                    executor::spawn(eval_sender.send((leaf, eval, payload)).map(|_| ()))
-                        .wait_future()
+                if config
+                    .restart_every_n_evals
+                    .map(|restart_every| {
+                        n_evals.load(Ordering::SeqCst)
+                            > restart_every * n_restarts.load(Ordering::SeqCst)
+                    })
+                    .unwrap_or(false)
+                    || candidate_store.needs_restart()
                 {
-                    warn!("Got disconnected , {:?}", err);
+                    is_leader.fetch_add(1, Ordering::SeqCst);
+                    barrier.wait();
+                    if is_leader.fetch_sub(1, Ordering::SeqCst) == config.num_workers {
+                        info!("Performing restart");
+                        candidate_store.restart();
+                        n_restarts.fetch_add(1, Ordering::SeqCst);
+                    }
+                    barrier.wait();
                 }
-            });
+            }
+        },
+    );
+}
 
-            if config
-                .restart_every_n_evals
-                .map(|restart_every| {
-                    n_evals.load(Ordering::SeqCst)
-                        > restart_every * n_restarts.load(Ordering::SeqCst)
-                })
-                .unwrap_or(false)
-            {
-                is_leader.fetch_add(1, Ordering::SeqCst);
-                barrier.wait();
-                if is_leader.fetch_sub(1, Ordering::SeqCst) == config.num_workers {
-                    info!("Performing restart");
-                    candidate_store.restart();
-                    n_restarts.fetch_add(1, Ordering::SeqCst);
-                }
-                barrier.wait();
+/// The screening values that decide which candidates are promoted to a precise evaluation.
+/// Only the best `fraction` of the values screened so far are kept.
+struct Screened {
+    fraction: f64,
+    num_screened: usize,
+    /// The best values, with the worst of them on top.
+    best: BinaryHeap<ScreenValue>,
+}
+
+impl Screened {
+    fn new(fraction: f64) -> Self {
+        Screened {
+            fraction,
+            num_screened: 0,
+            best: BinaryHeap::new(),
+        }
+    }
+
+    /// Records the screening value of a candidate and indicates if it must be promoted
+    /// because its value is among the best `fraction` of the values screened so far.
+    ///
+    /// Values that fall out of the best ones are forgotten, so the candidate following an
+    /// increase of the number of promoted values may be promoted even if a forgotten value
+    /// was better.  This only promotes more candidates than necessary.
+    fn promote(&mut self, value: f64) -> bool {
+        if !value.is_finite() {
+            return false;
+        }
+        self.num_screened += 1;
+        let num_promoted = (self.fraction * self.num_screened as f64).ceil() as usize;
+        let promoted = self.best.len() < num_promoted
+            || self.best.peek().map_or(false, |worst| value < worst.0);
+        if promoted {
+            self.best.push(ScreenValue(value));
+            while self.best.len() > num_promoted {
+                self.best.pop();
             }
         }
-    });
+        promoted
+    }
+}
+
+/// A screening value, ordered with `utils::cmp_f64`.  Screening values are always finite,
+/// since `Screened::promote` ignores the others.
+#[derive(Clone, Copy)]
+struct ScreenValue(f64);
+
+impl PartialEq for ScreenValue {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cmp(rhs) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for ScreenValue {}
+
+impl PartialOrd for ScreenValue {
+    fn partial_cmp(&self, rhs: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+impl Ord for ScreenValue {
+    fn cmp(&self, rhs: &Self) -> std::cmp::Ordering {
+        cmp_f64(self.0, rhs.0)
+    }
 }
 
 /// Explores the full search space.
@@ -370,9 +1038,31 @@ pub fn gen_space<F, G>(
             on_node(&candidate);
             stack.extend(candidate.apply_choice(context, choice));
         } else {
-            let space = fix_order(candidate.space);
-            on_leaf(&Candidate { space, ..candidate });
+            on_leaf(&candidate.fix_order());
         }
     }
     info!("{} candidates explored", total);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures candidates are promoted when their screening value is among the best
+    /// fraction of the values screened so far, and that only the best values are kept.
+    #[test]
+    fn promote_best_screened() {
+        let mut screened = Screened::new(0.25);
+        let values = [1., 2., 3., 4., 0.5, std::f64::NAN, 5., 6., 7.];
+        let promoted = values
+            .iter()
+            .map(|&value| screened.promote(value))
+            .collect::<Vec<_>>();
+        let expected = [true, false, false, false, true, false, false, false, false];
+        assert_eq!(promoted, expected);
+        assert_eq!(screened.num_screened, 8);
+        let mut best = screened.best.into_iter().map(|v| v.0).collect::<Vec<_>>();
+        best.sort_by(|&lhs, &rhs| cmp_f64(lhs, rhs));
+        assert_eq!(best, vec![0.5, 1.]);
+    }
+}
@@ -1,26 +1,249 @@
 //! This file exposes a single function, monitor, that is launched in a special
 //! thread and pulls the evaluations results, store them and then updates the
 //! Store accordingly.
-use crate::device::Context;
+use crate::device::{CancellationToken, Context};
 use crate::explorer::candidate::Candidate;
-use crate::explorer::config::Config;
+use crate::explorer::config::{Config, Objective};
 use crate::explorer::logger::LogMessage;
+use crate::explorer::registry::Registration;
 use crate::explorer::store::Store;
+use crate::explorer::NewBestFn;
 use futures::prelude::*;
 use futures::{executor, future, task, Async};
-use log::warn;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
-use std::io::Write;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
 use std::sync::{
     self,
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     Arc,
 };
 use std::time::{Duration, Instant};
 use std::{self, thread};
 use utils::unwrap;
 
-pub type MonitorMessage<T> = (Candidate, f64, <T as Store>::PayLoad);
+pub type MonitorMessage<T> = (Candidate, f64, Measurement, <T as Store>::PayLoad);
+
+/// Indicates how precisely the value of a candidate sent to the monitor was measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measurement {
+    /// The screening value of a candidate that was not promoted to a precise evaluation.
+    /// Provisional values update the store, but never make a candidate the best one.
+    Provisional,
+    /// The value of a candidate evaluated with the full number of runs.
+    Confirmed,
+}
+
+/// Tracks the evaluation results waiting to be processed by the monitor.
+///
+/// Evaluation callbacks block when the monitor channel is full, which in turn blocks the
+/// evaluation of the next candidates.  This records how often that happens.
+pub struct Backlog {
+    capacity: usize,
+    pending: AtomicUsize,
+    max_pending: AtomicUsize,
+    /// Time spent blocked on sending results to the monitor, in nanoseconds.
+    blocked_time: AtomicUsize,
+}
+
+impl Backlog {
+    /// Creates a backlog for a monitor channel with the given capacity.
+    pub fn new(capacity: usize) -> Self {
+        Backlog {
+            capacity,
+            pending: AtomicUsize::new(0),
+            max_pending: AtomicUsize::new(0),
+            blocked_time: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sends an evaluation result to the monitor, blocking if the channel is full.
+    pub fn send<T: Store>(
+        &self,
+        sender: futures::sync::mpsc::Sender<MonitorMessage<T>>,
+        message: MonitorMessage<T>,
+    ) {
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut max_pending = self.max_pending.load(Ordering::Relaxed);
+        while pending > max_pending {
+            match self.max_pending.compare_exchange_weak(
+                max_pending,
+                pending,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(current) => max_pending = current,
+            }
+        }
+        if pending > self.capacity {
+            debug!("monitor backlog is full: {} pending results", pending);
+        }
+        let t0 = Instant::now();
+        let sent = executor::spawn(sender.send(message).map(|_| ())).wait_future();
+        if let Err(err) = sent {
+            warn!("Got disconnected , {:?}", err);
+        }
+        let blocked = t0.elapsed();
+        self.blocked_time.fetch_add(
+            blocked.as_secs() as usize * 1_000_000_000 + blocked.subsec_nanos() as usize,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Records that the monitor received a result.
+    fn received(&self) {
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Display for Backlog {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "at most {} results waiting for the monitor (capacity {}), {:.4e}ns blocked",
+            self.max_pending.load(Ordering::Relaxed),
+            self.capacity,
+            self.blocked_time.load(Ordering::Relaxed) as f64
+        )
+    }
+}
+
+/// Metrics of a running search, served over HTTP in the text format of Prometheus when
+/// `config.metrics_address` is set, so that searches running on clusters can be monitored
+/// with standard tools.
+pub struct Metrics {
+    start_time: Instant,
+    num_evaluations: AtomicUsize,
+    num_provisional: AtomicUsize,
+    /// Bits of the best runtime found so far, infinite if none was found.
+    best: AtomicU64,
+}
+
+impl Metrics {
+    fn new(start_time: Instant) -> Self {
+        Metrics {
+            start_time,
+            num_evaluations: AtomicUsize::new(0),
+            num_provisional: AtomicUsize::new(0),
+            best: AtomicU64::new(std::f64::INFINITY.to_bits()),
+        }
+    }
+
+    /// Renders the metrics in the text format of Prometheus.
+    fn render<T: Store>(&self, candidate_store: &T, backlog: &Backlog) -> String {
+        let mut text = String::new();
+        let mut metric =
+            |name: &str, kind: &str, help: &str, values: Vec<(String, f64)>| {
+                text.push_str(&format!("# HELP telamon_{} {}\n", name, help));
+                text.push_str(&format!("# TYPE telamon_{} {}\n", name, kind));
+                for (labels, value) in values {
+                    text.push_str(&format!("telamon_{}{} {}\n", name, labels, value));
+                }
+            };
+        let elapsed = self.start_time.elapsed();
+        metric(
+            "search_duration_seconds",
+            "gauge",
+            "Time since the start of the search.",
+            vec![(
+                String::new(),
+                elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9,
+            )],
+        );
+        metric(
+            "evaluations_total",
+            "counter",
+            "Number of candidates evaluated.",
+            vec![(
+                String::new(),
+                self.num_evaluations.load(Ordering::Relaxed) as f64,
+            )],
+        );
+        metric(
+            "provisional_evaluations_total",
+            "counter",
+            "Number of candidates only screened, whose value was not confirmed.",
+            vec![(
+                String::new(),
+                self.num_provisional.load(Ordering::Relaxed) as f64,
+            )],
+        );
+        let best = f64::from_bits(self.best.load(Ordering::Relaxed));
+        metric(
+            "best_runtime_nanoseconds",
+            "gauge",
+            "Score of the best candidate found so far.",
+            Some(best)
+                .filter(|best| best.is_finite())
+                .map(|best| (String::new(), best))
+                .into_iter()
+                .collect(),
+        );
+        metric(
+            "deadends_total",
+            "counter",
+            "Number of dead-ends encountered by the search, by cause.",
+            candidate_store
+                .deadends()
+                .into_iter()
+                .map(|(cause, count)| (format!("{{cause=\"{}\"}}", cause), count as f64))
+                .collect(),
+        );
+        metric(
+            "evaluation_queue_depth",
+            "gauge",
+            "Number of evaluation results waiting for the monitor.",
+            vec![(
+                String::new(),
+                backlog.pending.load(Ordering::Relaxed) as f64,
+            )],
+        );
+        text
+    }
+}
+
+/// Answers the requests received on `listener` with the metrics of the search, until
+/// `running` is cleared.
+fn serve_metrics<T: Store>(
+    listener: &TcpListener,
+    metrics: &Metrics,
+    candidate_store: &T,
+    backlog: &Backlog,
+    running: &AtomicBool,
+) {
+    while running.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let body = metrics.render(candidate_store, backlog);
+                if let Err(err) = answer_metrics_request(stream, &body) {
+                    debug!("error while serving metrics: {}", err);
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(100))
+            }
+            Err(err) => warn!("error while accepting a metrics connection: {}", err),
+        }
+    }
+}
+
+/// Reads an HTTP request on `stream` and answers it with `body`, whatever the request is.
+fn answer_metrics_request(mut stream: TcpStream, body: &str) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request = [0; 1024];
+    let _ = stream.read(&mut request)?;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
 
 /// Indicates why the exploration was terminated.
 #[derive(Serialize, Deserialize)]
@@ -29,6 +252,14 @@ pub enum TerminationReason {
     MaxEvaluations,
     /// The timeout was reached.
     Timeout,
+    /// The caller asked to stop the search when it was notified of a new best candidate.
+    Interrupted,
+    /// The best candidate was not improved for `max_stale_evaluations` evaluations.
+    NoImprovement,
+    /// The best candidate is within `bound_gap` percents of the lower bound.
+    CloseToBound,
+    /// The maximal number of implementations was reached.
+    MaxImplementations,
 }
 
 impl std::fmt::Display for TerminationReason {
@@ -40,6 +271,16 @@ impl std::fmt::Display for TerminationReason {
             TerminationReason::Timeout => {
                 write!(f, "the maximum exploration time was reached")
             }
+            TerminationReason::Interrupted => write!(f, "the search was interrupted"),
+            TerminationReason::NoImprovement => {
+                write!(f, "the best candidate was not improved for too long")
+            }
+            TerminationReason::CloseToBound => {
+                write!(f, "the best candidate is close enough to the lower bound")
+            }
+            TerminationReason::MaxImplementations => {
+                write!(f, "the maximum number of implementations was reached")
+            }
         }
     }
 }
@@ -47,6 +288,14 @@ impl std::fmt::Display for TerminationReason {
 struct Status {
     best_candidate: Option<(Candidate, f64)>,
     num_evaluations: usize,
+    /// Number of evaluations when the best candidate was last improved.
+    last_improvement: usize,
+    /// Number of implementations received, including the ones that were not evaluated.
+    num_implementations: usize,
+    /// Number of evaluations that were only provisional.
+    num_provisional: usize,
+    /// Number of evaluations saved in the checkpoint file.
+    num_checkpointed: usize,
 }
 
 impl Default for Status {
@@ -54,69 +303,148 @@ impl Default for Status {
         Status {
             best_candidate: None,
             num_evaluations: 0,
+            last_improvement: 0,
+            num_implementations: 0,
+            num_provisional: 0,
+            num_checkpointed: 0,
         }
     }
 }
 
+impl Status {
+    /// Counts an evaluation.  `improved` indicates the evaluation is the new best.
+    fn record_evaluation(&mut self, improved: bool) {
+        self.num_evaluations += 1;
+        if improved {
+            self.last_improvement = self.num_evaluations;
+        }
+    }
+
+    /// Indicates if the last `max_stale` evaluations did not improve the best candidate.
+    fn is_stale(&self, max_stale: usize) -> bool {
+        self.num_evaluations - self.last_improvement >= max_stale
+    }
+}
+
 /// This function is an interface supposed to make a connection between the
 /// Store and the evaluator. Retrieve evaluations, retains the results and
 /// update the store accordingly.
+///
+/// `lower_bound` is the bound of the initial candidates of the search, used to decide when
+/// the best candidate is close enough to the optimum.  Returns the best candidate found,
+/// along with the number of evaluations performed.
 pub fn monitor<T, E>(
     config: &Config,
     context: &dyn Context,
     candidate_store: &T,
     recv: futures::sync::mpsc::Receiver<MonitorMessage<T>>,
     log_sender: sync::mpsc::SyncSender<LogMessage<E>>,
-) -> Option<Candidate>
+    backlog: &Backlog,
+    cancel: &CancellationToken,
+    on_new_best: Option<&NewBestFn<'_>>,
+    registration: &Registration,
+    lower_bound: f64,
+) -> Option<(Candidate, usize)>
 where
     T: Store,
 {
     warn!("Monitor waiting for evaluation results");
     let t0 = Instant::now();
     let mut status = Status::default();
+    let metrics = Metrics::new(t0);
+    let metrics_listener = config.metrics_address.as_ref().and_then(|address| {
+        TcpListener::bind(address)
+            .and_then(|listener| {
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .map_err(|err| warn!("cannot serve metrics on {}: {}", address, err))
+            .ok()
+    });
+    let metrics_running = AtomicBool::new(true);
 
-    let res = {
-        let log_sender_ref = &log_sender;
-        let status_mut = &mut status;
-        let mut future: Box<dyn Future<Item = _, Error = _>> =
-            Box::new(recv.map_err(|()| unreachable!()).for_each(move |message| {
-                handle_message(
-                    config,
-                    context,
-                    message,
-                    t0,
+    // Dumping the best candidates requires generating their code, which is done in a separate
+    // thread to avoid blocking the evaluation callbacks.
+    let (dump_sender, dump_receiver) = sync::mpsc::channel();
+    let res = crossbeam::scope(|scope| {
+        unwrap!(scope
+            .builder()
+            .name("Telamon - Dumper".to_string())
+            .spawn(move |_| dump_candidates(config, context, dump_receiver)));
+        if let Some(listener) = &metrics_listener {
+            let (metrics, running) = (&metrics, &metrics_running);
+            unwrap!(scope.builder().name("Telamon - Metrics".to_string()).spawn(
+                move |_| serve_metrics(
+                    listener,
+                    metrics,
                     candidate_store,
-                    log_sender_ref,
-                    status_mut,
+                    backlog,
+                    running
                 )
-            }));
-
-        if let Some(timeout_mins) = config.timeout {
-            future = Box::new(
-                future
-                    .select(timeout(Duration::from_secs(timeout_mins * 60)))
-                    .map(|((), _)| ())
-                    .map_err(|(err, _)| err),
-            );
+            ));
         }
 
-        executor::spawn(future).wait_future()
-    };
+        let res = {
+            let log_sender_ref = &log_sender;
+            let metrics_ref = &metrics;
+            let dump_sender_ref = &dump_sender;
+            let status_mut = &mut status;
+            let mut future: Box<dyn Future<Item = _, Error = _>> =
+                Box::new(recv.map_err(|()| unreachable!()).for_each(move |message| {
+                    backlog.received();
+                    handle_message(
+                        config,
+                        message,
+                        t0,
+                        candidate_store,
+                        log_sender_ref,
+                        dump_sender_ref,
+                        status_mut,
+                        metrics_ref,
+                        on_new_best,
+                        registration,
+                        lower_bound,
+                    )
+                }));
+
+            if let Some(timeout_mins) = config.timeout {
+                future = Box::new(
+                    future
+                        .select(timeout(Duration::from_secs(timeout_mins * 60)))
+                        .map(|((), _)| ())
+                        .map_err(|(err, _)| err),
+                );
+            }
+
+            executor::spawn(future).wait_future()
+        };
+        std::mem::drop(dump_sender);
+        metrics_running.store(false, Ordering::Relaxed);
+        res
+    })
+    .unwrap();
 
     let duration = t0.elapsed();
     let duration_secs =
         duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9;
     warn!(
-        "Exploration finished in {}s with {} candidates evaluated (avg {} candidate/s).",
+        "Exploration finished in {}s with {} candidates evaluated ({} provisionally, \
+         avg {} candidate/s).",
         duration_secs,
         status.num_evaluations,
+        status.num_provisional,
         status.num_evaluations as f64 / duration_secs
     );
+    warn!("Monitor backlog: {}", backlog);
+    save_checkpoint(config, candidate_store, &mut status.num_checkpointed);
     match res {
         Ok(_) => warn!("No candidates to try anymore"),
         Err(reason) => {
             warn!("exploration stopped because {}", reason);
             candidate_store.stop_exploration();
+            // Stop in-flight compilations and evaluations instead of waiting for the workers
+            // to drain the queues.
+            cancel.cancel();
             unwrap!(log_sender.send(LogMessage::Finished {
                 reason,
                 timestamp: duration,
@@ -124,12 +452,15 @@ where
             }));
         }
     }
-    status.best_candidate.map(|x| x.0)
+    let num_evaluations = status.num_evaluations;
+    status
+        .best_candidate
+        .map(|(cand, _)| (cand, num_evaluations))
 }
 
 /// Depending on the value of the evaluation we just did, computes the new cut
 /// value for the store Can be 0 if we decide to stop the search
-fn get_new_cut(config: &Config, eval: f64) -> f64 {
+pub(super) fn get_new_cut(config: &Config, eval: f64) -> f64 {
     if let Some(bound) = config.stop_bound {
         if eval < bound {
             return 0.;
@@ -146,17 +477,21 @@ fn get_new_cut(config: &Config, eval: f64) -> f64 {
 /// the best cand if needed, logging, committing back to candidate_store
 fn handle_message<T, E>(
     config: &Config,
-    context: &dyn Context,
     message: MonitorMessage<T>,
     start_time: Instant,
     candidate_store: &T,
     log_sender: &sync::mpsc::SyncSender<LogMessage<E>>,
+    dump_sender: &sync::mpsc::Sender<(Candidate, usize)>,
     status: &mut Status,
+    metrics: &Metrics,
+    on_new_best: Option<&NewBestFn<'_>>,
+    registration: &Registration,
+    lower_bound: f64,
 ) -> Result<(), TerminationReason>
 where
     T: Store,
 {
-    let (cand, eval, payload) = message;
+    let (cand, eval, measurement, payload) = message;
 
     let wall = start_time.elapsed();
     warn!("Got a new evaluation after {}, bound: {:.4e} score: {:.4e}, current best: {:.4e}",
@@ -165,39 +500,51 @@ where
           eval,
           status.best_candidate.as_ref().map_or(std::f64::INFINITY, |best:
                                                 &(Candidate, f64)| best.1 ));
-    candidate_store.commit_evaluation(&cand.actions, payload, eval);
+    let change = match measurement {
+        Measurement::Provisional => {
+            candidate_store.commit_provisional_evaluation(&cand.actions, payload, eval);
+            false
+        }
+        Measurement::Confirmed => {
+            candidate_store.commit_evaluation(&cand.actions, payload, eval);
+            status
+                .best_candidate
+                .as_ref()
+                .map(|&(_, time)| time > eval)
+                .unwrap_or(true)
+        }
+    };
+    status.num_implementations += 1;
 
-    let change = status
-        .best_candidate
-        .as_ref()
-        .map(|&(_, time)| time > eval)
-        .unwrap_or(true);
     if change {
         warn!("Got a new best candidate, score: {:.3e}, {}", eval, cand);
-        candidate_store.update_cut(get_new_cut(config, eval));
+        // Bounds are lower bounds of the execution time, and cannot prune candidates when
+        // minimizing another quantity.
+        if config.objective == Objective::Time {
+            candidate_store.update_cut(get_new_cut(config, eval));
+        }
         let log_message = LogMessage::NewBest {
             score: eval,
             cpt: status.num_evaluations,
             timestamp: wall,
         };
         unwrap!(log_sender.send(log_message));
-
-        config
-            .output_path(format!("best_{}", status.num_evaluations))
-            .and_then(|output_path| {
-                std::fs::create_dir_all(&output_path)?;
-
-                write!(
-                    std::fs::File::create(output_path.join("actions.json"))?,
-                    "{}",
-                    serde_json::to_string(&cand.actions).unwrap()
-                )?;
-
-                cand.space.dump_code(context, output_path.join("code"))
-            })
-            .unwrap_or_else(|err| warn!("Error while dumping candidate: {}", err));
+        unwrap!(dump_sender.send((cand.clone(), status.num_evaluations)));
+        registration.new_best(&cand, eval);
+        metrics.best.store(eval.to_bits(), Ordering::Relaxed);
+        let keep_going = on_new_best.map_or(true, |on_new_best| on_new_best(&cand, eval));
 
         status.best_candidate = Some((cand, eval));
+        if !keep_going {
+            return Err(TerminationReason::Interrupted);
+        }
+        if let Some(gap) = config.stop_conditions.bound_gap {
+            if config.objective == Objective::Time
+                && eval <= (1. + gap / 100.) * lower_bound
+            {
+                return Err(TerminationReason::CloseToBound);
+            }
+        }
     }
 
     // Note that it is possible that we actually didn't make an
@@ -209,17 +556,92 @@ where
     // implementation of the search algorithm would not have selected
     // this candidate since it would get cut).
     if !eval.is_infinite() {
-        status.num_evaluations += 1;
+        status.record_evaluation(change);
+        metrics
+            .num_evaluations
+            .store(status.num_evaluations, Ordering::Relaxed);
+        if measurement == Measurement::Provisional {
+            status.num_provisional += 1;
+            metrics
+                .num_provisional
+                .store(status.num_provisional, Ordering::Relaxed);
+        }
+        registration.evaluated(status.num_evaluations);
+        if config.checkpoint_interval > 0
+            && status.num_evaluations % config.checkpoint_interval == 0
+        {
+            save_checkpoint(config, candidate_store, &mut status.num_checkpointed);
+        }
         if let Some(max_evaluations) = config.max_evaluations {
             if status.num_evaluations >= max_evaluations {
                 return Err(TerminationReason::MaxEvaluations);
             }
         }
+        if let Some(max_stale) = config.stop_conditions.max_stale_evaluations {
+            if status.is_stale(max_stale) {
+                return Err(TerminationReason::NoImprovement);
+            }
+        }
+    }
+    if let Some(max_implementations) = config.stop_conditions.max_implementations {
+        if status.num_implementations >= max_implementations {
+            return Err(TerminationReason::MaxImplementations);
+        }
     }
 
     Ok(())
 }
 
+/// Appends the evaluations committed since the last checkpoint to the checkpoint file, if
+/// there is one.  `num_saved` is the number of evaluations already saved in the file.
+fn save_checkpoint<T: Store>(
+    config: &Config,
+    candidate_store: &T,
+    num_saved: &mut usize,
+) {
+    if let Some(checkpoint_file) = &config.checkpoint_file {
+        let res = config.output_path(checkpoint_file).and_then(|path| {
+            let checkpoint = candidate_store.checkpoint(*num_saved)?;
+            // The first save overwrites the checkpoints of previous searches.
+            checkpoint.save(path, *num_saved > 0)?;
+            Ok(checkpoint.evaluations.len())
+        });
+        match res {
+            Ok(num_new) => *num_saved += num_new,
+            Err(err) => {
+                warn!("Error while saving checkpoint: {}", err);
+                // The file may end with a partial evaluation, so it is rewritten next time.
+                *num_saved = 0;
+            }
+        }
+    }
+}
+
+/// Dumps the actions and the code of the candidates received on `recv`, until the channel
+/// is closed.
+fn dump_candidates(
+    config: &Config,
+    context: &dyn Context,
+    recv: sync::mpsc::Receiver<(Candidate, usize)>,
+) {
+    for (cand, num_evaluations) in recv {
+        config
+            .output_path(format!("best_{}", num_evaluations))
+            .and_then(|output_path| {
+                std::fs::create_dir_all(&output_path)?;
+
+                write!(
+                    std::fs::File::create(output_path.join("actions.json"))?,
+                    "{}",
+                    serde_json::to_string(&cand.actions).unwrap()
+                )?;
+
+                cand.space.dump_code(context, output_path.join("code"))
+            })
+            .unwrap_or_else(|err| warn!("Error while dumping candidate: {}", err));
+    }
+}
+
 struct TimeoutWorker {
     running: Arc<AtomicBool>,
     thread: thread::Thread,
@@ -280,3 +702,99 @@ fn timeout(duration: Duration) -> impl Future<Item = (), Error = TerminationReas
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explorer::choice::ActionEx;
+    use rpds::List;
+
+    /// A store without candidates, which reports fixed dead-end counts.
+    struct DeadendStore;
+
+    impl Store for DeadendStore {
+        type PayLoad = ();
+
+        type Event = ();
+
+        fn update_cut(&self, _: f64) {}
+
+        fn commit_evaluation(&self, _: &List<ActionEx>, _: (), _: f64) {}
+
+        fn explore(&self, _: &dyn Context) -> Option<(Candidate, ())> {
+            None
+        }
+
+        fn deadends(&self) -> Vec<(&'static str, usize)> {
+            vec![("constraints", 3), ("perf_model", 1)]
+        }
+    }
+
+    /// Ensures the metrics are rendered in the text format of Prometheus, and that the best
+    /// runtime is only reported once there is one.
+    #[test]
+    fn render_metrics() {
+        let metrics = Metrics::new(Instant::now());
+        let backlog = Backlog::new(4);
+        let text = metrics.render(&DeadendStore, &backlog);
+        assert!(text.contains("telamon_evaluations_total 0\n"));
+        assert!(!text.contains("telamon_best_runtime_nanoseconds "));
+        assert!(text.contains("telamon_deadends_total{cause=\"constraints\"} 3\n"));
+        assert!(text.contains("telamon_deadends_total{cause=\"perf_model\"} 1\n"));
+        assert!(text.contains("telamon_evaluation_queue_depth 0\n"));
+        for line in text.lines() {
+            assert!(
+                line.starts_with("telamon_")
+                    || line.starts_with("# HELP telamon_")
+                    || line.starts_with("# TYPE telamon_"),
+                "invalid line: {}",
+                line
+            );
+        }
+
+        metrics.num_evaluations.store(2, Ordering::Relaxed);
+        metrics.best.store(1.5f64.to_bits(), Ordering::Relaxed);
+        let text = metrics.render(&DeadendStore, &backlog);
+        assert!(text.contains("telamon_evaluations_total 2\n"));
+        assert!(text.contains("telamon_best_runtime_nanoseconds 1.5\n"));
+    }
+
+    /// Ensures the metrics are served over HTTP, and that the server stops with `running`.
+    #[test]
+    fn serve_metrics_over_http() {
+        let listener = unwrap!(TcpListener::bind("127.0.0.1:0"));
+        unwrap!(listener.set_nonblocking(true));
+        let address = unwrap!(listener.local_addr());
+        let metrics = Metrics::new(Instant::now());
+        let backlog = Backlog::new(4);
+        let running = AtomicBool::new(true);
+        let response = unwrap!(crossbeam::scope(|scope| {
+            let (listener, metrics, backlog, running) =
+                (&listener, &metrics, &backlog, &running);
+            scope.spawn(move |_| {
+                serve_metrics(listener, metrics, &DeadendStore, backlog, running)
+            });
+            let mut stream = unwrap!(TcpStream::connect(address));
+            unwrap!(stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n"));
+            let mut response = String::new();
+            unwrap!(stream.read_to_string(&mut response));
+            running.store(false, Ordering::Relaxed);
+            response
+        }));
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("telamon_evaluations_total 0\n"));
+    }
+
+    /// Ensures the search is only stale after `max_stale` evaluations without improvement.
+    #[test]
+    fn stale_after_improvement() {
+        let mut status = Status::default();
+        status.record_evaluation(true);
+        assert!(!status.is_stale(1));
+        status.record_evaluation(false);
+        assert!(status.is_stale(1));
+        assert!(!status.is_stale(2));
+        status.record_evaluation(true);
+        assert!(!status.is_stale(1));
+    }
+}
@@ -0,0 +1,290 @@
+//! Refines the best implementation found by the search with a local hill-climbing.
+//!
+//! The search rarely evaluates the implementations that only differ from the best one by a
+//! single decision.  The refinement enumerates the mutations of the best implementation
+//! that replace one of its decisions by a neighbouring one: the next smaller or larger
+//! tile size of a dimension, or another cache flag for a memory instruction.  The actions
+//! taken after the mutated one are reapplied when they are still valid and the remaining
+//! choices are decided with the lowest bound.  The best improving mutation becomes the new
+//! implementation, until no mutation improves it.
+use crate::codegen;
+use crate::device::{Context, EvalMode};
+use crate::explorer::candidate::Candidate;
+use crate::explorer::choice::{self, ActionEx};
+use crate::explorer::config::RefineConfig;
+use crate::explorer::CheckResultFn;
+use crate::search_space::Action;
+
+use itertools::Itertools;
+use log::{debug, info, warn};
+use utils::cmp_f64;
+
+/// Refines `best`, which was found by exploring one of the `roots`.  Returns the best
+/// implementation found, which is `best` if no mutation improves it.
+///
+/// `on_new_best` is called with each improvement, its runtime and the number of mutations
+/// evaluated so far.  The refinement stops if it returns `false`.
+pub fn refine(
+    config: &RefineConfig,
+    context: &dyn Context,
+    roots: &[Candidate],
+    best: Candidate,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+    on_new_best: &mut dyn FnMut(&Candidate, f64, usize) -> bool,
+) -> Candidate {
+    let mut actions = best.actions.iter().cloned().collect_vec();
+    actions.reverse();
+    let root = if let Some(root) = roots
+        .iter()
+        .find(|root| rebuild(context, root, &actions, std::f64::INFINITY).is_some())
+    {
+        root
+    } else {
+        warn!("cannot refine: the best implementation does not derive from the roots");
+        return best;
+    };
+    let mut current = match evaluate(context, &best, None) {
+        Some(eval) => (best, actions, eval),
+        None => {
+            warn!("cannot refine: the evaluation of the best implementation failed");
+            return best;
+        }
+    };
+    info!("refining an implementation of {:.4e}ns", current.2);
+
+    let mut num_evals = 0;
+    loop {
+        let mut improvement = None;
+        for mutant in mutations(root, &current.1) {
+            if num_evals >= config.max_evaluations {
+                break;
+            }
+            let cut = improvement.as_ref().map_or(current.2, |&(_, _, eval)| eval);
+            let cand = if let Some(cand) = rebuild(context, root, &mutant, cut) {
+                cand
+            } else {
+                continue;
+            };
+            num_evals += 1;
+            if let Some(eval) = evaluate(context, &cand, check_result_fn) {
+                debug!("mutation evaluated at {:.4e}ns", eval);
+                if eval < cut {
+                    let mut actions = cand.actions.iter().cloned().collect_vec();
+                    actions.reverse();
+                    improvement = Some((cand, actions, eval));
+                }
+            }
+        }
+        match improvement {
+            Some(improvement) => {
+                info!("refinement improved the runtime to {:.4e}ns", improvement.2);
+                let keep_going = on_new_best(&improvement.0, improvement.2, num_evals);
+                current = improvement;
+                if !keep_going {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    info!("refinement done after {} evaluations", num_evals);
+    current.0
+}
+
+/// Lists the action lists obtained by replacing one action of `actions` by a neighbouring
+/// one.
+fn mutations<'a>(
+    root: &'a Candidate,
+    actions: &'a [ActionEx],
+) -> impl Iterator<Item = Vec<ActionEx>> + 'a {
+    actions.iter().enumerate().flat_map(move |(pos, action)| {
+        neighbours(root, action).into_iter().map(move |neighbour| {
+            let mut mutant = actions.to_vec();
+            mutant[pos] = neighbour;
+            mutant
+        })
+    })
+}
+
+/// Returns the actions that can replace `action`.  Sizes are replaced by the next smaller
+/// and larger sizes allowed in `root` and cache flags by the other flags allowed in `root`.
+fn neighbours(root: &Candidate, action: &ActionEx) -> Vec<ActionEx> {
+    let domain = root.space.domain();
+    match *action {
+        ActionEx::Action(Action::Size(dim, size)) => {
+            let sizes = domain.get_size(dim).list().collect_vec();
+            let pos = if let Some(pos) = sizes.iter().position(|&s| s == size) {
+                pos
+            } else {
+                return vec![];
+            };
+            let previous = pos.checked_sub(1).map(|pos| sizes[pos]);
+            let next = sizes.get(pos + 1).cloned();
+            previous
+                .into_iter()
+                .chain(next)
+                .map(|size| ActionEx::Action(Action::Size(dim, size)))
+                .collect()
+        }
+        ActionEx::Action(Action::InstFlag(inst, flag)) => domain
+            .get_inst_flag(inst)
+            .list()
+            .filter(|&other| other != flag)
+            .map(|other| ActionEx::Action(Action::InstFlag(inst, other)))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Applies `actions` to `root`, skipping the actions that are no longer valid, and decides
+/// the remaining choices with the action of lowest bound.  Returns `None` if no
+/// implementation with a bound below `cut` is found this way.
+fn rebuild(
+    context: &dyn Context,
+    root: &Candidate,
+    actions: &[ActionEx],
+    cut: f64,
+) -> Option<Candidate> {
+    let mut cand = root.clone();
+    for action in actions {
+        if let Ok(next) = cand.apply_decision(context, action.clone()) {
+            cand = next;
+        }
+    }
+    while let Some(choice) = choice::default_list(&cand.space).next() {
+        cand = choice
+            .into_iter()
+            .filter_map(|action| cand.apply_decision(context, action).ok())
+            .min_by(|lhs, rhs| cmp_f64(lhs.bound.value(), rhs.bound.value()))?;
+    }
+    if cand.bound.value() < cut {
+        Some(cand)
+    } else {
+        None
+    }
+}
+
+/// Evaluates an implementation and checks its results if `check_result_fn` is provided.
+/// Returns `None` if the evaluation or the check fails.
+fn evaluate(
+    context: &dyn Context,
+    cand: &Candidate,
+    check_result_fn: Option<&CheckResultFn<'_>>,
+) -> Option<f64> {
+    let cand = cand.clone().fix_order();
+    let function = codegen::Function::build(&cand.space);
+    let eval = context.evaluate(&function, EvalMode::FindBest).ok()?;
+    if let Some(check_result_fn) = check_result_fn {
+        // The outputs of the kernel are only valid until the next evaluation.
+        if let Err(err) = check_result_fn(&cand, context) {
+            warn!("invalid results for a refined implementation: {}", err);
+            return None;
+        }
+    }
+    Some(eval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{fake, AsyncEvaluator, CancellationToken, Device};
+    use crate::helper::{self, TilingPattern};
+    use crate::ir::{self, Size};
+    use crate::model::bound;
+    use crate::search_space::SearchSpace;
+    use std::sync::Arc;
+    use utils::unwrap;
+
+    /// A context where implementations run faster with larger tiles.
+    struct TileContext {
+        inner: fake::Context,
+        tile: ir::DimId,
+    }
+
+    impl TileContext {
+        /// Returns the size of the tile in `space`.
+        fn tile_size(&self, space: &SearchSpace) -> u32 {
+            let universe = unwrap!(space.ir_instance().dim(self.tile).possible_sizes());
+            unwrap!(space.domain().get_size(self.tile).as_constrained(universe))
+        }
+    }
+
+    impl Context for TileContext {
+        fn device(&self) -> Arc<dyn Device> {
+            self.inner.device()
+        }
+
+        fn evaluate(&self, function: &codegen::Function, _: EvalMode) -> Result<f64, ()> {
+            Ok(1e9 / f64::from(self.tile_size(function.space())))
+        }
+
+        fn benchmark(
+            &self,
+            function: &codegen::Function,
+            num_samples: usize,
+        ) -> Vec<f64> {
+            let eval = unwrap!(self.evaluate(function, EvalMode::TestBound));
+            vec![eval; num_samples]
+        }
+
+        fn param_as_size(&self, name: &str) -> Option<u32> {
+            self.inner.param_as_size(name)
+        }
+
+        fn async_eval<'b>(
+            &self,
+            num_workers: usize,
+            num_compilers: usize,
+            mode: EvalMode,
+            cancel: &CancellationToken,
+            inner: &(dyn Fn(&mut dyn AsyncEvaluator<'b>) + Sync),
+        ) {
+            self.inner
+                .async_eval(num_workers, num_compilers, mode, cancel, inner)
+        }
+    }
+
+    /// Ensures the refinement reports each improvement and returns the last one.
+    #[test]
+    fn report_improvements() {
+        let _ = env_logger::try_init();
+        let inner = fake::Context::<fake::Device>::default();
+        let signature = Arc::new(ir::Signature::new("refine"));
+        let mut builder = helper::Builder::new(signature, inner.device());
+        let dim = builder
+            .open_tiled_dim(Size::new_const(64), TilingPattern::divisors(64, 1, 16));
+        builder.mov(&0f32);
+        builder.close_dim(&dim);
+        let tile = unwrap!(dim.tile_dims().next()).id();
+        let space = builder.get();
+        let context = TileContext { inner, tile };
+        let root = Candidate::new(space.clone(), bound(&space, &context));
+
+        // Start from the smallest tile.
+        let smallest = unwrap!(space.domain().get_size(tile).list().next());
+        let action = ActionEx::Action(Action::Size(tile, smallest));
+        let best = unwrap!(rebuild(&context, &root, &[action], std::f64::INFINITY));
+        let initial_size = context.tile_size(&best.space);
+
+        let mut reports = vec![];
+        let config = RefineConfig::default();
+        let refined = refine(
+            &config,
+            &context,
+            &[root],
+            best,
+            None,
+            &mut |cand, eval, _| {
+                reports.push((context.tile_size(&cand.space), eval));
+                true
+            },
+        );
+        let refined_size = context.tile_size(&refined.space);
+        assert!(refined_size > initial_size);
+        assert!(reports.windows(2).all(|pair| pair[1].1 < pair[0].1));
+        assert_eq!(
+            reports.last(),
+            Some(&(refined_size, 1e9 / f64::from(refined_size)))
+        );
+    }
+}
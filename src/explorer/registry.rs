@@ -0,0 +1,203 @@
+//! Registry of the searches started in the process, for applications that embed Telamon
+//! and tune many kernels concurrently. The registry is only maintained when the
+//! `search_registry` feature is enabled.
+//!
+//! Each call to `find_best_ex` registers itself when it starts, records its progress after
+//! each evaluation and is marked as finished when it returns. The functions of this module
+//! return snapshots of the registered searches. Searches stay in the registry once they are
+//! over, until `remove_finished` is called.
+#[cfg(not(feature = "search_registry"))]
+use crate::explorer::{Candidate, Config};
+
+#[cfg(feature = "search_registry")]
+pub use self::enabled::*;
+
+#[cfg(feature = "search_registry")]
+mod enabled {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, SystemTime};
+
+    use lazy_static::lazy_static;
+    use utils::unwrap;
+
+    use crate::explorer::choice::ActionEx;
+    use crate::explorer::{Candidate, Config};
+
+    lazy_static! {
+        static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+    }
+
+    #[derive(Default)]
+    struct Registry {
+        next_id: usize,
+        searches: BTreeMap<SearchId, Arc<Mutex<Entry>>>,
+    }
+
+    /// Identifies a search in the registry. Searches are numbered in the order they
+    /// started.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct SearchId(usize);
+
+    /// Indicates if a search is over.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum SearchState {
+        Running,
+        Finished,
+        /// The search panicked.
+        Aborted,
+    }
+
+    /// A snapshot of a search.
+    #[derive(Clone)]
+    pub struct SearchInfo {
+        pub id: SearchId,
+        /// The configuration the search was started with.
+        pub config: Config,
+        pub state: SearchState,
+        /// The time at which the search started.
+        pub start_time: SystemTime,
+        /// The time spent searching so far, or in total if the search is over.
+        pub duration: Duration,
+        /// The number of candidates evaluated so far.
+        pub num_evaluations: usize,
+        /// The score of the best candidate found so far.
+        pub best_score: Option<f64>,
+        /// The actions leading to the best candidate found so far.
+        pub best_actions: Option<Vec<ActionEx>>,
+    }
+
+    struct Entry {
+        info: SearchInfo,
+        start: Instant,
+    }
+
+    impl Entry {
+        fn snapshot(&self) -> SearchInfo {
+            let mut info = self.info.clone();
+            if info.state == SearchState::Running {
+                info.duration = self.start.elapsed();
+            }
+            info
+        }
+
+        fn finish(&mut self, state: SearchState) {
+            if self.info.state == SearchState::Running {
+                self.info.state = state;
+                self.info.duration = self.start.elapsed();
+            }
+        }
+    }
+
+    /// Returns the searches of the process, in the order they started.
+    pub fn searches() -> Vec<SearchInfo> {
+        let registry = unwrap!(REGISTRY.lock());
+        registry
+            .searches
+            .values()
+            .map(|entry| unwrap!(entry.lock()).snapshot())
+            .collect()
+    }
+
+    /// Returns the searches that are still running, in the order they started.
+    pub fn running_searches() -> Vec<SearchInfo> {
+        searches()
+            .into_iter()
+            .filter(|info| info.state == SearchState::Running)
+            .collect()
+    }
+
+    /// Returns the search with the given identifier, if it is still in the registry.
+    pub fn search(id: SearchId) -> Option<SearchInfo> {
+        let registry = unwrap!(REGISTRY.lock());
+        registry
+            .searches
+            .get(&id)
+            .map(|entry| unwrap!(entry.lock()).snapshot())
+    }
+
+    /// Removes the searches that are over from the registry and returns them. Long-running
+    /// processes should call this regularly, since finished searches are otherwise kept
+    /// forever.
+    pub fn remove_finished() -> Vec<SearchInfo> {
+        let mut registry = unwrap!(REGISTRY.lock());
+        let finished = registry
+            .searches
+            .iter()
+            .map(|(&id, entry)| (id, unwrap!(entry.lock()).snapshot()))
+            .filter(|(_, info)| info.state != SearchState::Running)
+            .collect::<Vec<_>>();
+        for (id, _) in &finished {
+            registry.searches.remove(id);
+        }
+        finished.into_iter().map(|(_, info)| info).collect()
+    }
+
+    /// Records the progress of a search in the registry. The search is marked as aborted
+    /// if the registration is dropped before `finish` is called.
+    pub(crate) struct Registration {
+        entry: Arc<Mutex<Entry>>,
+    }
+
+    impl Registration {
+        pub fn new(config: &Config) -> Self {
+            let mut registry = unwrap!(REGISTRY.lock());
+            let id = SearchId(registry.next_id);
+            registry.next_id += 1;
+            let entry = Arc::new(Mutex::new(Entry {
+                info: SearchInfo {
+                    id,
+                    config: config.clone(),
+                    state: SearchState::Running,
+                    start_time: SystemTime::now(),
+                    duration: Duration::default(),
+                    num_evaluations: 0,
+                    best_score: None,
+                    best_actions: None,
+                },
+                start: Instant::now(),
+            }));
+            registry.searches.insert(id, Arc::clone(&entry));
+            Registration { entry }
+        }
+
+        pub fn new_best(&self, candidate: &Candidate, score: f64) {
+            let mut entry = unwrap!(self.entry.lock());
+            entry.info.best_score = Some(score);
+            entry.info.best_actions = Some(candidate.actions.iter().cloned().collect());
+        }
+
+        pub fn evaluated(&self, num_evaluations: usize) {
+            unwrap!(self.entry.lock()).info.num_evaluations = num_evaluations;
+        }
+
+        pub fn finish(&self) {
+            unwrap!(self.entry.lock()).finish(SearchState::Finished);
+        }
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            if let Ok(mut entry) = self.entry.lock() {
+                entry.finish(SearchState::Aborted);
+            }
+        }
+    }
+}
+
+/// Stands for the registration of a search when the registry is disabled.
+#[cfg(not(feature = "search_registry"))]
+pub(crate) struct Registration;
+
+#[cfg(not(feature = "search_registry"))]
+impl Registration {
+    pub fn new(_: &Config) -> Self {
+        Registration
+    }
+
+    pub fn new_best(&self, _: &Candidate, _: f64) {}
+
+    pub fn evaluated(&self, _: usize) {}
+
+    pub fn finish(&self) {}
+}
@@ -0,0 +1,112 @@
+//! Restricts the sizes of static dimensions before the exploration starts.
+//!
+//! Many size alternatives are only ruled out by the device limits once the kind of the
+//! dimension is decided: an unrolled dimension cannot exceed `max_unrolling`, a thread
+//! dimension cannot exceed `max_threads` together with the other thread dimensions, a
+//! vector dimension is bounded by the vectorization factors, ... When a size is invalid
+//! with every kind the dimension can still take, it can never lead to a valid
+//! implementation and is removed upfront, so that the search does not spend time on it.
+use crate::device::Context;
+use crate::explorer::candidate::Candidate;
+use crate::explorer::choice::ActionEx;
+use crate::ir;
+use crate::search_space::Action;
+
+use itertools::Itertools;
+use log::{debug, warn};
+use std::fmt;
+
+/// The number of size alternatives of each static dimension before and after the
+/// restriction.
+#[derive(Clone, Debug, Default)]
+pub struct SizeRestriction {
+    /// The dimensions whose sizes were restricted, with their number of alternatives
+    /// before and after the restriction.
+    pub dims: Vec<(ir::DimId, usize, usize)>,
+}
+
+impl SizeRestriction {
+    /// Returns the factor by which the number of size combinations shrank.
+    pub fn shrink_factor(&self) -> f64 {
+        self.dims
+            .iter()
+            .map(|&(_, before, after)| before as f64 / after as f64)
+            .product()
+    }
+}
+
+impl fmt::Display for SizeRestriction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let before: usize = self.dims.iter().map(|&(_, before, _)| before).sum();
+        let after: usize = self.dims.iter().map(|&(_, _, after)| after).sum();
+        write!(
+            fmt,
+            "restricted the sizes of {} dimensions, from {} to {} alternatives \
+             ({:.1}x fewer size combinations)",
+            self.dims.len(),
+            before,
+            after,
+            self.shrink_factor()
+        )
+    }
+}
+
+/// Removes the sizes that are invalid with all the kinds each static dimension of
+/// `candidate` can take.
+pub fn restrict_sizes(
+    candidate: Candidate,
+    context: &dyn Context,
+) -> (Candidate, SizeRestriction) {
+    let mut candidate = candidate;
+    let mut report = SizeRestriction::default();
+    let dims = candidate
+        .space
+        .ir_instance()
+        .static_dims()
+        .map(|dim| dim.id())
+        .collect_vec();
+    for dim in dims {
+        let space = &candidate.space;
+        let sizes = space.domain().get_size(dim);
+        let kinds = space.domain().get_dim_kind(dim);
+        let num_sizes = sizes.list().count();
+        // A size is valid if the dimension can have it with at least one of its kinds.
+        let valid_sizes = sizes
+            .list()
+            .filter(|&size| {
+                kinds.list().any(|kind| {
+                    let actions =
+                        vec![Action::Size(dim, size), Action::DimKind(dim, kind)];
+                    space.clone().apply_decisions(actions).is_ok()
+                })
+            })
+            .collect_vec();
+        if valid_sizes.len() == num_sizes {
+            continue;
+        }
+        let restricted = match valid_sizes.into_iter().fold1(|mut lhs, rhs| {
+            lhs.insert(rhs);
+            lhs
+        }) {
+            Some(restricted) => restricted,
+            None => {
+                warn!("no valid size for dimension {:?}", dim);
+                continue;
+            }
+        };
+        let action = ActionEx::Action(Action::Size(dim, restricted));
+        match candidate.apply_decision(context, action) {
+            Ok(restricted_candidate) => {
+                let num_restricted = restricted.list().count();
+                debug!(
+                    "restricted dimension {:?} from {} to {} sizes",
+                    dim, num_sizes, num_restricted
+                );
+                report.dims.push((dim, num_sizes, num_restricted));
+                candidate = restricted_candidate;
+            }
+            Err(err) => warn!("cannot restrict the sizes of {:?}: {}", dim, err),
+        }
+    }
+    (candidate, report)
+}
@@ -0,0 +1,68 @@
+//! Random number generation for the exploration.
+//!
+//! Each search draws from its own generator, created from `config.seed` when the search
+//! starts and handed to the stores it builds, so that concurrent searches do not interfere.
+//! Stores fork a generator for each exploration step from their shared generator.  The
+//! random choices of a seeded search are thus reproducible as long as the stores are
+//! explored in the same order, which is the case when a single explorer thread is used.
+use std::sync::Mutex;
+
+use rand::prelude::*;
+use rand::XorShiftRng;
+use utils::unwrap;
+
+use crate::helper;
+
+/// A random number generator used by the exploration.
+pub type ExplorerRng = XorShiftRng;
+
+/// Creates the generator of a search.  If `seed` is `None`, the generator is seeded from
+/// `thread_rng`.
+pub fn new(seed: Option<u64>) -> ExplorerRng {
+    match seed {
+        Some(seed) => helper::seeded_rng(seed),
+        None => fork(&mut thread_rng()),
+    }
+}
+
+/// Creates a generator drawing numbers independent from the next numbers of `rng`.
+pub fn fork<R: Rng + ?Sized>(rng: &mut R) -> ExplorerRng {
+    unwrap!(XorShiftRng::from_rng(rng))
+}
+
+/// A generator shared by the explorer threads of a store.
+pub struct SharedRng(Mutex<ExplorerRng>);
+
+impl SharedRng {
+    pub fn new(rng: ExplorerRng) -> Self {
+        SharedRng(Mutex::new(rng))
+    }
+
+    /// Returns a generator for an exploration step.  Generators returned by successive
+    /// calls draw different numbers.
+    pub fn fork(&self) -> ExplorerRng {
+        fork(&mut *unwrap!(self.0.lock()))
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        SharedRng::new(new(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures generators created from the same seed draw the same numbers.
+    #[test]
+    fn same_seed() {
+        let lhs = SharedRng::new(new(Some(42)));
+        let rhs = SharedRng::new(new(Some(42)));
+        for _ in 0..4 {
+            assert_eq!(lhs.fork().gen::<u64>(), rhs.fork().gen::<u64>());
+        }
+        assert_ne!(new(Some(1)).gen::<u64>(), new(Some(2)).gen::<u64>());
+    }
+}
@@ -1,8 +1,14 @@
+use crate::codegen::LaunchConfig;
 use crate::device::Context;
 use crate::explorer::candidate::Candidate;
 use crate::explorer::choice::ActionEx;
+use crate::explorer::logger::StructuredEvent;
 use rpds::List;
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
 
 /// A Trait defining a structure containing the candidates, meant to explore the
 /// search space
@@ -11,7 +17,7 @@ pub trait Store: Sync {
     /// evaluated.
     type PayLoad: Send;
     /// The type of events this store can emit during search.
-    type Event: Send + Serialize;
+    type Event: Send + Serialize + DeserializeOwned + StructuredEvent;
     /// Updates the value that will be used to prune the search space
     fn update_cut(&self, new_cut: f64);
     /// Immediately stops the exploration.
@@ -25,10 +31,169 @@ pub trait Store: Sync {
         payload: Self::PayLoad,
         eval: f64,
     );
+    /// Commits a provisional evaluation: the screening value of a candidate that was not
+    /// promoted to a precise evaluation.  Provisional values are noisier than the values
+    /// committed with `commit_evaluation` and must not be mixed with them.  Stores that
+    /// cannot keep them apart ignore them, which is the default.
+    fn commit_provisional_evaluation(
+        &self,
+        _actions: &List<ActionEx>,
+        _payload: Self::PayLoad,
+        _eval: f64,
+    ) {
+    }
+    /// Records the result of the screening of a candidate, before it is either promoted to a
+    /// precise evaluation or committed as a provisional evaluation.  This does not update
+    /// the store.
+    fn commit_screening(&self, _payload: &Self::PayLoad, _eval: f64) {}
+    /// Records the launch configuration of a candidate compiled for evaluation.  This does
+    /// not update the store.
+    fn commit_launch(&self, _payload: &Self::PayLoad, _launch: &LaunchConfig) {}
+    /// Records the source code generated for a candidate compiled for evaluation.  This
+    /// does not update the store.
+    fn commit_source(&self, _payload: &Self::PayLoad, _source: &str) {}
+    /// Records the runtime and the energy consumption measured for a candidate, before its
+    /// evaluation is committed.  This does not update the store.
+    fn commit_energy(&self, _payload: &Self::PayLoad, _runtime: f64, _energy: f64) {}
     /// Retrieve a Candidate for evaluation, returns `None` if no candidate remains.
     fn explore(&self, context: &dyn Context) -> Option<(Candidate, Self::PayLoad)>;
+    /// Retrieves a partial candidate whose descent is completed by a worker of a
+    /// distributed search, along with the actions leading to it from the root in the order
+    /// they were applied.  The evaluation of the implementation found by the worker is
+    /// committed with the returned payload.  Returns `None` if no candidate remains.
+    ///
+    /// Stores that cannot leave their descents to workers return `None`, which is the
+    /// default.  See `explorer::distributed`.
+    fn explore_prefix(
+        &self,
+        _context: &dyn Context,
+    ) -> Option<(Vec<ActionEx>, Candidate, Self::PayLoad)> {
+        None
+    }
     /// Displays statistics about the candidate store.
     fn print_stats(&self) {}
+    /// Returns the number of dead-ends encountered for each cause, if the store tracks them.
+    fn deadends(&self) -> Vec<(&'static str, usize)> {
+        vec![]
+    }
     /// Resets the store to restart evaluation.
     fn restart(&self) {}
+    /// Indicates if the store should be restarted, independently of the number of
+    /// evaluations.
+    fn needs_restart(&self) -> bool {
+        false
+    }
+    /// Returns the evaluations committed to the store after the first `since` ones, so that
+    /// they can be appended to the previous checkpoint.  The store can be rebuilt from all
+    /// the evaluations with `restore`.
+    fn checkpoint(&self, _since: usize) -> io::Result<Checkpoint> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the search algorithm does not support checkpoints",
+        ))
+    }
+    /// Rebuilds the state saved in `checkpoint`.  Returns the candidates evaluated in the
+    /// checkpoint, with their evaluation, whether the evaluation is provisional and the
+    /// payload to commit them with.
+    fn restore(
+        &self,
+        _context: &dyn Context,
+        _checkpoint: &Checkpoint,
+    ) -> io::Result<Vec<(Candidate, f64, bool, Self::PayLoad)>> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "the search algorithm does not support checkpoints",
+        ))
+    }
+}
+
+/// The state of an exploration, from which it can be resumed.
+///
+/// Checkpoints are saved with one evaluation per line, so that saving a checkpoint only
+/// appends the evaluations committed since the previous one to the file.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The evaluations committed to the store, in order.  Each evaluation is given by the
+    /// actions leading to the implementation, its score, `None` if it failed, and whether
+    /// the score is provisional: a screening value that was not confirmed by a precise
+    /// evaluation.
+    pub evaluations: Vec<(Vec<ActionEx>, Option<f64>, bool)>,
+}
+
+impl Checkpoint {
+    /// Loads a checkpoint saved with `save`.  The last evaluation is ignored if it was not
+    /// completely written, which happens when the search is interrupted during a save.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        let mut lines = content.split('\n').collect::<Vec<_>>();
+        // The last line is empty unless it was interrupted.
+        lines.pop();
+        let evaluations = lines
+            .into_iter()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Checkpoint { evaluations })
+    }
+
+    /// Writes the evaluations of the checkpoint to `path`.  If `append` is true, the
+    /// evaluations are added after the ones already saved in the file.  Otherwise, the
+    /// file is overwritten.
+    pub fn save<P: AsRef<Path>>(&self, path: P, append: bool) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        let mut writer = BufWriter::new(file);
+        for evaluation in &self.evaluations {
+            serde_json::to_writer(&mut writer, evaluation)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir;
+    use crate::search_space::{Action, Order};
+
+    /// Ensures saved evaluations are appended to the checkpoint with their provisional
+    /// status, and that an evaluation interrupted during a save is ignored.
+    #[test]
+    fn append_checkpoint() {
+        let path = std::env::temp_dir()
+            .join(format!("telamon-append-checkpoint-{}", std::process::id()));
+        let action = ActionEx::Action(Action::Order(
+            ir::StmtId::from(ir::InstId(0)),
+            ir::StmtId::from(ir::InstId(1)),
+            Order::BEFORE,
+        ));
+        let first = Checkpoint {
+            evaluations: vec![(vec![action.clone()], Some(1.0), true)],
+        };
+        let second = Checkpoint {
+            evaluations: vec![(vec![], None, false)],
+        };
+        first.save(&path, false).unwrap();
+        second.save(&path, true).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[[], 2.").unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(
+            loaded.evaluations,
+            vec![(vec![action], Some(1.0), true), (vec![], None, false)]
+        );
+        // Saving without appending overwrites the previous evaluations.
+        second.save(&path, false).unwrap();
+        assert_eq!(
+            Checkpoint::load(&path).unwrap().evaluations,
+            vec![(vec![], None, false)]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
 }
@@ -4,10 +4,11 @@ use crate::helper::{AutoOperand, LogicalDim, MetaStatement, TilingPattern};
 use crate::ir::{self, op, Parameter, Type};
 use crate::ir::{AccessPattern, Function, InstId, Operand, Operator, Signature};
 use crate::search_space::{Action, DimKind, InstFlag, MemSpace, Order, SearchSpace};
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use itertools::Itertools;
 use log::debug;
 use std::borrow::Borrow;
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use utils::*;
 
@@ -15,6 +16,7 @@ use utils::*;
 pub struct Builder {
     function: Function<()>,
     open_dims: FxHashMap<ir::DimId, ir::DimId>,
+    dim_names: FxHashMap<String, LogicalDim>,
     actions: Vec<Action>,
 }
 
@@ -24,14 +26,21 @@ impl Builder {
         Builder {
             function: Function::new(signature, device),
             open_dims: FxHashMap::default(),
+            dim_names: FxHashMap::default(),
             actions: Vec::new(),
         }
     }
 
     /// Returns the function created by the builder
     pub fn get(self) -> SearchSpace {
+        self.try_get().expect("invalid IR instance")
+    }
+
+    /// Returns the function created by the builder, or an error if the actions applied
+    /// to the function contradict each other.
+    pub fn try_get(self) -> Result<SearchSpace, ()> {
         debug!("{:?}", self.actions);
-        SearchSpace::new(self.function, self.actions).expect("invalid IR instance")
+        SearchSpace::new(self.function, self.actions)
     }
 
     /// Returns the function created by the builder
@@ -40,6 +49,15 @@ impl Builder {
         SearchSpace::new(function, self.actions.clone()).expect("invalid IR instance")
     }
 
+    /// Returns the function created by the builder, with the filters of the initial domain
+    /// computed sequentially.  See `SearchSpace::new_sequential`.
+    #[cfg(feature = "parallel_propagation")]
+    pub fn get_sequential(&self) -> SearchSpace {
+        let function = self.function.clone();
+        SearchSpace::new_sequential(function, self.actions.clone())
+            .expect("invalid IR instance")
+    }
+
     /// Returns an operand from an `AutoOperand`.
     fn get_op(&mut self, op: &dyn AutoOperand) -> Operand<()> {
         op.get(self)
@@ -107,6 +125,21 @@ impl Builder {
         self.inst(op)
     }
 
+    /// Adds a warp-level matrix multiply-accumulate to the function. The operands are the
+    /// fragments of the tiles held by each thread, see `ir::Operator::Mma`.
+    pub fn mma(
+        &mut self,
+        lhs: &dyn AutoOperand,
+        rhs: &dyn AutoOperand,
+        acc: &dyn AutoOperand,
+        shape: ir::MmaShape,
+    ) -> InstId {
+        let lhs_op = self.get_op(lhs);
+        let rhs_op = self.get_op(rhs);
+        let acc_op = self.get_op(acc);
+        self.inst(op::Mma(lhs_op, rhs_op, acc_op, shape))
+    }
+
     /// Adds a `Max` instruction to the fuction.
     pub fn max(&mut self, lhs: &dyn AutoOperand, rhs: &dyn AutoOperand) -> InstId {
         let lhs_op = self.get_op(lhs);
@@ -134,6 +167,13 @@ impl Builder {
         self.inst(op::UnaryOp(ir::UnaryOp::Exp(t), arg_op))
     }
 
+    /// Adds a `Sqrt` instruction to the fuction.
+    pub fn sqrt(&mut self, arg: &dyn AutoOperand) -> InstId {
+        let arg_op = self.get_op(arg);
+        let t = arg_op.t();
+        self.inst(op::UnaryOp(ir::UnaryOp::Sqrt(t), arg_op))
+    }
+
     /// Adds a coherent load from global memory instruction to the function.
     pub fn ld(
         &mut self,
@@ -343,6 +383,43 @@ impl Builder {
         }
     }
 
+    /// Opens a new dimension that is closed when the returned guard is dropped.
+    ///
+    /// The guard dereferences to the builder, so that instructions can be added inside the
+    /// dimension through it.
+    pub fn scoped_dim(
+        &mut self,
+        size: ir::Size,
+        tiling_pattern: TilingPattern,
+    ) -> DimGuard<'_> {
+        let dim = self.open_tiled_dim(size, tiling_pattern);
+        DimGuard { builder: self, dim }
+    }
+
+    /// Opens a new dimension mapped to an existing one, that is closed when the returned
+    /// guard is dropped.
+    pub fn scoped_mapped_dim(&mut self, old_dim: &LogicalDim) -> DimGuard<'_> {
+        let dim = self.open_mapped_dim(old_dim);
+        DimGuard { builder: self, dim }
+    }
+
+    /// Registers a name for a dimension, so that it can be retrieved with `named_dim`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is already used.  Names identify dimensions while building a
+    /// kernel, so reusing one is a bug in the kernel rather than an error to recover
+    /// from.  Use `named_dim` to check if a name is already registered.
+    pub fn name_dim(&mut self, name: &str, dim: &LogicalDim) {
+        let old = self.dim_names.insert(name.to_string(), dim.clone());
+        assert!(old.is_none(), "dimension name `{}` is already used", name);
+    }
+
+    /// Returns the dimension registered with the given name, if any.
+    pub fn named_dim(&self, name: &str) -> Option<&LogicalDim> {
+        self.dim_names.get(name)
+    }
+
     /// Returns a constant size.
     pub fn cst_size(&self, size: u32) -> ir::Size {
         ir::Size::new_const(size)
@@ -384,7 +461,12 @@ impl Builder {
         let dims = increments.iter().cloned().collect();
         let ind_var = unwrap!(ir::InductionVar::new(increments, base));
         let ind_var_id = self.function.add_ind_var(ind_var);
-        (ind_var_id, AccessPattern::Tensor { mem_id, dims })
+        let pattern = AccessPattern::Tensor {
+            mem_id,
+            dims,
+            reversed: FxHashSet::default(),
+        };
+        (ind_var_id, pattern)
     }
 
     /// Generates the access pattern corresponding to accessing a tensor of the given
@@ -393,11 +475,24 @@ impl Builder {
         &self,
         mem: Option<ir::MemId>,
         increments: Vec<(&LogicalDim, ir::Size)>,
+    ) -> AccessPattern {
+        self.tensor_access_pattern_ex(mem, increments, &[])
+    }
+
+    /// Generates the access pattern corresponding to accessing a tensor, with negative
+    /// strides along the `reversed` dimensions.  `increments` holds the absolute values of
+    /// the strides.
+    pub fn tensor_access_pattern_ex(
+        &self,
+        mem: Option<ir::MemId>,
+        increments: Vec<(&LogicalDim, ir::Size)>,
+        reversed: &[&LogicalDim],
     ) -> AccessPattern {
         let dims = self.logical_to_real_increments(increments);
         AccessPattern::Tensor {
             mem_id: mem,
             dims: dims.into_iter().collect(),
+            reversed: self.real_dims(reversed),
         }
     }
 
@@ -406,11 +501,31 @@ impl Builder {
         &mut self,
         base: &dyn AutoOperand,
         dims: Vec<(&LogicalDim, ir::Size)>,
+    ) -> ir::IndVarId {
+        self.induction_var_ex(base, dims, &[])
+    }
+
+    /// Builds an induction variable that is decremented along the `reversed` dimensions.
+    /// `dims` holds the absolute values of the increments.
+    pub fn induction_var_ex(
+        &mut self,
+        base: &dyn AutoOperand,
+        dims: Vec<(&LogicalDim, ir::Size)>,
+        reversed: &[&LogicalDim],
     ) -> ir::IndVarId {
         let base = self.get_op(base);
         let dims = self.logical_to_real_increments(dims);
-        self.function
-            .add_ind_var(unwrap!(ir::InductionVar::new(dims, base)))
+        let reversed = self.real_dims(reversed);
+        let ind_var = unwrap!(ir::InductionVar::new_reversed(dims, reversed, base));
+        self.function.add_ind_var(ind_var)
+    }
+
+    /// Returns the real dimensions implementing the given logical dimensions.  Reversing a
+    /// logical dimension amounts to reversing each of its tiles.
+    fn real_dims(&self, dims: &[&LogicalDim]) -> FxHashSet<ir::DimId> {
+        dims.iter()
+            .flat_map(|dim| self.function.logical_dim(dim.id()).dimensions())
+            .collect()
     }
 
     /// Converts increments on logical dimensions to increment on real dimensions.
@@ -501,3 +616,41 @@ fn default_rounding(t: Type) -> op::Rounding {
         op::Rounding::Nearest
     }
 }
+
+/// A dimension opened with `Builder::scoped_dim`, that is closed when dropped.
+///
+/// Dimensions that are no longer open when the guard is dropped, for instance because a mapped
+/// dimension was opened from them, are ignored.
+pub struct DimGuard<'a> {
+    builder: &'a mut Builder,
+    dim: LogicalDim,
+}
+
+impl<'a> DimGuard<'a> {
+    /// Returns the dimension opened by the guard.
+    pub fn dim(&self) -> &LogicalDim {
+        &self.dim
+    }
+}
+
+impl<'a> Deref for DimGuard<'a> {
+    type Target = Builder;
+
+    fn deref(&self) -> &Builder {
+        self.builder
+    }
+}
+
+impl<'a> DerefMut for DimGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Builder {
+        self.builder
+    }
+}
+
+impl<'a> Drop for DimGuard<'a> {
+    fn drop(&mut self) {
+        for id in &self.dim {
+            self.builder.open_dims.remove(&id);
+        }
+    }
+}
@@ -5,9 +5,9 @@ mod signature;
 
 pub mod tensor;
 
-pub use self::builder::Builder;
+pub use self::builder::{Builder, DimGuard};
 pub use self::operand::{AutoOperand, Reduce, TmpArray};
-pub use self::signature::{Builder as SignatureBuilder, MemInit};
+pub use self::signature::{seeded_rng, Builder as SignatureBuilder, MemInit};
 
 use crate::ir;
 use serde::{Deserialize, Serialize};
@@ -15,6 +15,10 @@ use std;
 use utils::*;
 
 /// A groups of dimensions that act as a single logical dimension.
+///
+/// The dimensions of the group have distinct handle types, depending on their role:
+/// `TiledDim` for the dimension iterating over the tiles and `TileDim` for the dimensions
+/// iterating inside a tile.  Indexing the group directly returns untyped IDs.
 #[derive(Clone)]
 pub struct LogicalDim {
     logical_id: ir::LogicalDimId,
@@ -33,6 +37,29 @@ impl LogicalDim {
         self.logical_id
     }
 
+    /// Returns the outermost dimension, that iterates over the tiles.
+    pub fn tiled_dim(&self) -> TiledDim {
+        TiledDim(self.real_ids[0])
+    }
+
+    /// Iterates on the tiling dimensions, from the outermost to the innermost.
+    pub fn tile_dims(&self) -> impl Iterator<Item = TileDim> + '_ {
+        self.real_ids[1..].iter().cloned().map(TileDim)
+    }
+
+    /// Returns the only dimension of an untiled logical dimension.
+    ///
+    /// Panics if the logical dimension is tiled.
+    pub fn single(&self) -> ir::DimId {
+        assert_eq!(
+            self.real_ids.len(),
+            1,
+            "logical dimension {:?} is tiled",
+            self.logical_id
+        );
+        self.real_ids[0]
+    }
+
     /// Returns true if the size of `other` is equal to the
     /// dimension's own size
     pub fn size_eq<T>(&self, other: &Self, function: &ir::Function<T>) -> bool {
@@ -43,6 +70,47 @@ impl LogicalDim {
     }
 }
 
+/// The dimension of a `LogicalDim` that iterates over its tiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TiledDim(ir::DimId);
+
+impl TiledDim {
+    /// Returns the ID of the dimension.
+    pub fn id(self) -> ir::DimId {
+        self.0
+    }
+}
+
+/// A tiling dimension of a `LogicalDim`, that iterates inside a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileDim(ir::DimId);
+
+impl TileDim {
+    /// Returns the ID of the dimension.
+    pub fn id(self) -> ir::DimId {
+        self.0
+    }
+}
+
+macro_rules! dim_handle_into {
+    ($handle:ident) => {
+        impl From<$handle> for ir::DimId {
+            fn from(dim: $handle) -> ir::DimId {
+                dim.0
+            }
+        }
+
+        impl From<$handle> for ir::StmtId {
+            fn from(dim: $handle) -> ir::StmtId {
+                dim.0.into()
+            }
+        }
+    };
+}
+
+dim_handle_into!(TiledDim);
+dim_handle_into!(TileDim);
+
 impl std::ops::Index<usize> for LogicalDim {
     type Output = ir::DimId;
 
@@ -135,6 +203,40 @@ impl TilingPattern {
             tile_sizes,
         }
     }
+
+    /// Creates a pattern with `num_levels` tiling dimensions whose sizes can be any
+    /// divisor of `gcd_size` up to `max_tile_size`, leaving the choice of the tile sizes
+    /// to the explorer.  The dimension must have a size multiple of `gcd_size`.
+    pub fn divisors(gcd_size: u32, num_levels: usize, max_tile_size: u32) -> Self {
+        let divisors: VecSet<_> = (2..=std::cmp::min(gcd_size, max_tile_size))
+            .filter(|x| (gcd_size % x) == 0)
+            .collect();
+        let mut tiling_factors = VecSet::new(vec![1]);
+        for _ in 0..num_levels {
+            tiling_factors = tiling_factors
+                .iter()
+                .flat_map(|&factor| {
+                    let remaining = gcd_size / factor;
+                    divisors
+                        .iter()
+                        .filter(move |&&d| (remaining % d) == 0)
+                        .map(move |&d| factor * d)
+                })
+                .collect();
+        }
+        TilingPattern {
+            tiling_factors,
+            tile_sizes: vec![divisors; num_levels],
+        }
+    }
+
+    /// Returns the first tiling factor of the pattern that does not divide `size`, if any.
+    pub fn non_dividing_factor(&self, size: u32) -> Option<u32> {
+        self.tiling_factors
+            .iter()
+            .cloned()
+            .find(|&f| f == 0 || size % f != 0)
+    }
 }
 
 impl<'a> From<&'a [u32]> for TilingPattern {
@@ -1,16 +1,18 @@
 //! Helper functions to create a function signature and bind parameters.
-use crate::device::{self, ArgMapExt, ArrayArgumentExt, ScalarArgument};
+use crate::device::{self, ArgMap, ArgMapExt, ArrayArgumentExt, ScalarArgument};
 use crate::helper::tensor::{DimSize, Tensor};
 use crate::ir::Signature;
 use itertools::Itertools;
 use rand::prelude::*;
+use std::collections::HashMap;
 use std::sync::Arc;
 use utils::unwrap;
 
 /// Memory initialization strategies.
 #[derive(Debug, Copy, Clone)]
 pub enum MemInit {
-    /// Memory is randomly filled.  This is the default behavior.
+    /// Memory is randomly filled.  This is the default behavior.  The data only depends
+    /// on the seed of the builder, set with `Builder::set_seed`.
     RandomFill,
     /// Memory is left uninitialized
     Uninit,
@@ -31,6 +33,7 @@ where
     rng: rand::XorShiftRng,
     context: &'a mut AM,
     signature: Signature,
+    existing_arrays: HashMap<String, Arc<dyn device::ArrayArgument>>,
 }
 
 impl<'a, AM> Builder<'a, AM>
@@ -43,12 +46,13 @@ where
             name: name.to_string(),
             params: vec![],
         };
-        let rng = rand::XorShiftRng::from_seed(Default::default());
+        let rng = seeded_rng(0);
         Builder {
             mem_init: MemInit::Uninit,
             context,
             signature,
             rng,
+            existing_arrays: HashMap::default(),
         }
     }
 
@@ -57,6 +61,29 @@ where
         self.mem_init = mem_init;
     }
 
+    /// Sets the seed used to generate random data, so that two builders with the same seed
+    /// fill their arrays with the same values.  Defaults to zero.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seeded_rng(seed);
+    }
+
+    /// Returns the random number generator used to fill arrays.  Kernels should use it to
+    /// generate their random scalar arguments, so that they also depend on the seed only.
+    pub fn rng(&mut self) -> &mut impl Rng {
+        &mut self.rng
+    }
+
+    /// Binds the array parameter with the given name to an existing array instead of
+    /// allocating a new one.  The array is left untouched by the memory initialization
+    /// strategy.  The caller is responsible for providing an array of the right size.
+    pub fn set_existing_array(
+        &mut self,
+        name: &str,
+        array: Arc<dyn device::ArrayArgument>,
+    ) {
+        self.existing_arrays.insert(name.to_string(), array);
+    }
+
     /// Creates a new parameter and binds it to the given value.
     pub fn scalar<'b, T: ScalarArgument>(&mut self, name: &str, arg: T)
     where
@@ -81,7 +108,8 @@ where
         }
     }
 
-    /// Creates a new parameter and binds it to a freshly allocated an array.
+    /// Creates a new parameter and binds it to a freshly allocated an array, or to the
+    /// existing array registered with `set_existing_array`.
     pub fn array<'b, S: ScalarArgument>(
         &mut self,
         name: &str,
@@ -93,6 +121,10 @@ where
         self.signature
             .add_array(&*self.context.device(), name.to_string(), S::t());
         let param = unwrap!(self.signature.params.last());
+        if let Some(array) = self.existing_arrays.get(name) {
+            self.context.bind_existing_array(param, Arc::clone(array));
+            return Arc::clone(array);
+        }
         let array = self.context.bind_array::<S>(param, size);
         let rng = &mut self.rng;
         match self.mem_init {
@@ -133,3 +165,11 @@ where
         self.context
     }
 }
+
+/// Creates a random number generator from a seed.  Generators created from the same seed
+/// draw the same numbers, on any platform.
+pub fn seeded_rng(seed: u64) -> rand::XorShiftRng {
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    rand::XorShiftRng::from_seed(bytes)
+}
@@ -1,6 +1,6 @@
 //! Utilities to allocate and operate on tensors.
 use crate::device::{ArgMap, ArrayArgument, ArrayArgumentExt, Context, ScalarArgument};
-use crate::helper::{Builder, LogicalDim, SignatureBuilder, TilingPattern};
+use crate::helper::{AutoOperand, Builder, LogicalDim, SignatureBuilder, TilingPattern};
 use crate::ir;
 use crate::search_space::InstFlag;
 use ::ndarray::{self, ArrayD};
@@ -37,6 +37,15 @@ impl<'a> DimSize<'a> {
             * self.factor
     }
 
+    /// Builds the instructions computing the size in the kernel.
+    fn build_value(&self, builder: &mut Builder) -> ir::Operand<()> {
+        let factor = (self.factor as i32).as_operand();
+        self.params.iter().fold(factor, |value, param| {
+            let value = builder.mul(&value, param);
+            value.get(builder)
+        })
+    }
+
     /// Creates a new size equals to the given parameter.
     pub fn new_param(param: &'a str, max_size: u32) -> Self {
         DimSize {
@@ -57,12 +66,37 @@ impl<'a> From<u32> for DimSize<'a> {
     }
 }
 
+/// A logical dimension of a tensor, that iterates on one of its storage dimensions.
+#[derive(Clone)]
+struct ExposedDim<'a> {
+    storage_dim: usize,
+    /// Number of iterations, if it differs from the size of the storage dimension.
+    size: Option<DimSize<'a>>,
+    /// Number of storage elements between two consecutive iterations.
+    step: u32,
+    /// Indicates if the dimension is iterated on in reverse order.
+    reversed: bool,
+}
+
+impl<'a> ExposedDim<'a> {
+    /// Creates a logical dimension that iterates on all the elements of a storage
+    /// dimension.
+    fn new(storage_dim: usize) -> Self {
+        ExposedDim {
+            storage_dim,
+            size: None,
+            step: 1,
+            reversed: false,
+        }
+    }
+}
+
 /// An helper to build a tensor.
 pub struct TensorBuilder<'a> {
     name: &'a str,
     read_only: bool,
     storage_dims: Vec<DimSize<'a>>,
-    exposed_dims: Vec<usize>,
+    exposed_dims: Vec<ExposedDim<'a>>,
 }
 
 impl<'a> BuilderTrait for TensorBuilder<'a> {}
@@ -70,7 +104,7 @@ impl<'a> BuilderTrait for TensorBuilder<'a> {}
 impl<'a> TensorBuilder<'a> {
     /// Start building a `Tensor` with the given logical layout.
     pub fn new(name: &'a str, storage_dims: Vec<DimSize<'a>>) -> Self {
-        let exposed_dims = (0..storage_dims.len()).collect();
+        let exposed_dims = (0..storage_dims.len()).map(ExposedDim::new).collect();
         TensorBuilder {
             name,
             storage_dims,
@@ -82,9 +116,16 @@ impl<'a> TensorBuilder<'a> {
     /// Swap two dimensions in the memory layout of the tensor. Keeps the logical layout
     /// untouched.
     pub fn transpose(&mut self, lhs: usize, rhs: usize) -> &mut Self {
-        self.storage_dims
-            .swap(self.exposed_dims[lhs], self.exposed_dims[rhs]);
-        self.exposed_dims.swap(lhs, rhs);
+        let lhs = self.exposed_dims[lhs].storage_dim;
+        let rhs = self.exposed_dims[rhs].storage_dim;
+        self.storage_dims.swap(lhs, rhs);
+        for dim in &mut self.exposed_dims {
+            if dim.storage_dim == lhs {
+                dim.storage_dim = rhs;
+            } else if dim.storage_dim == rhs {
+                dim.storage_dim = lhs;
+            }
+        }
         self
     }
 
@@ -94,6 +135,40 @@ impl<'a> TensorBuilder<'a> {
         self
     }
 
+    /// Reverses the order in which a logical dimension is iterated on: index `i` of the
+    /// dimension corresponds to index `size - 1 - i` in the storage.
+    pub fn reverse(&mut self, dim: usize) -> &mut Self {
+        self.exposed_dims[dim].reversed = !self.exposed_dims[dim].reversed;
+        self
+    }
+
+    /// Only iterates on one element out of `stride` of a logical dimension, `size`
+    /// times: index `i` of the dimension corresponds to index `i * stride` in the
+    /// storage, which must thus hold at least `(size - 1) * stride + 1` elements.
+    pub fn strided(&mut self, dim: usize, size: DimSize<'a>, stride: u32) -> &mut Self {
+        assert!(stride > 0);
+        let dim = &mut self.exposed_dims[dim];
+        dim.size = Some(size);
+        dim.step *= stride;
+        self
+    }
+
+    /// Adds a logical dimension of size `size` after the existing ones, that iterates on
+    /// the same storage dimension as `dim` with `dilation` elements between consecutive
+    /// iterations. Indexes `i` of `dim` and `j` of the new dimension correspond to index
+    /// `i * stride + j * dilation` in the storage, where `stride` is the stride of `dim`.
+    /// Combined with `strided`, this describes the sliding windows of a convolution.
+    pub fn window(&mut self, dim: usize, size: DimSize<'a>, dilation: u32) -> &mut Self {
+        assert!(dilation > 0);
+        let storage_dim = self.exposed_dims[dim].storage_dim;
+        self.exposed_dims.push(ExposedDim {
+            size: Some(size),
+            step: dilation,
+            ..ExposedDim::new(storage_dim)
+        });
+        self
+    }
+
     /// Allows writing to the tensor.
     pub fn enable_writes(&mut self) -> &mut Self {
         self.read_only = false;
@@ -128,11 +203,19 @@ impl<'a> TensorBuilder<'a> {
         let iter_dims = self
             .exposed_dims
             .iter()
-            .map(|&i| (self.storage_dims[i].clone(), strides[i].clone()))
+            .map(|dim| {
+                let storage_size = &self.storage_dims[dim.storage_dim];
+                let size = dim.size.as_ref().unwrap_or(storage_size).clone();
+                let mut stride = strides[dim.storage_dim].clone();
+                stride.factor *= dim.step;
+                (size, stride)
+            })
             .collect();
+        let reversed = self.exposed_dims.iter().map(|dim| dim.reversed).collect();
         Tensor {
             array,
             iter_dims,
+            reversed,
             read_only: self.read_only,
             name: self.name,
             s: std::marker::PhantomData,
@@ -145,6 +228,8 @@ pub struct Tensor<'a, S: ScalarArgument> {
     name: &'a str,
     array: std::sync::Arc<dyn ArrayArgument + 'a>,
     iter_dims: Vec<(DimSize<'a>, DimSize<'a>)>,
+    /// Indicates which of the dimensions are iterated on in reverse order.
+    reversed: Vec<bool>,
     read_only: bool,
     s: std::marker::PhantomData<S>,
 }
@@ -172,9 +257,11 @@ where
             })
             .collect_vec();
         iter_dims.reverse();
+        let reversed = vec![false; iter_dims.len()];
         Tensor {
             name,
             iter_dims,
+            reversed,
             read_only,
             array,
             s: std::marker::PhantomData,
@@ -187,6 +274,7 @@ where
         tiling: Vec<TilingPattern>,
         builder: &mut Builder,
     ) -> VirtualTensor {
+        let base = self.base_address(builder);
         let dims = self
             .iter_dims
             .iter()
@@ -203,8 +291,14 @@ where
                 .zip_eq(&self.iter_dims)
                 .map(|(dim, (_, stride))| (dim, stride.to_ir_size(builder)))
                 .collect_vec();
-            ptr = builder.induction_var(&self.name, increments.clone());
-            pattern = builder.tensor_access_pattern(None, increments);
+            let reversed = dims
+                .iter()
+                .zip_eq(&self.reversed)
+                .filter(|&(_, &is_reversed)| is_reversed)
+                .map(|(dim, _)| dim)
+                .collect_vec();
+            ptr = builder.induction_var_ex(&base, increments.clone(), &reversed);
+            pattern = builder.tensor_access_pattern_ex(None, increments, &reversed);
         };
         let flag = if self.read_only {
             InstFlag::ALL
@@ -218,7 +312,27 @@ where
         VirtualTensor { inst, dims }
     }
 
-    /// Reads the tensor value in the context and copies it on the host.
+    /// Returns the address of the first element accessed, which is the last element of the
+    /// storage along reversed dimensions.
+    fn base_address(&self, builder: &mut Builder) -> ir::Operand<()> {
+        let mut base = AutoOperand::get(&self.name, builder);
+        let reversed_dims = self
+            .iter_dims
+            .iter()
+            .zip_eq(&self.reversed)
+            .filter(|&(_, &is_reversed)| is_reversed);
+        for ((size, stride), _) in reversed_dims {
+            let size = size.build_value(builder);
+            let last_index = builder.sub(&size, &1i32);
+            let stride = stride.build_value(builder);
+            base = builder.mad(&last_index, &stride, &base).get(builder);
+        }
+        base
+    }
+
+    /// Reads the tensor value in the context and copies it on the host. Panics if
+    /// multiple elements of the tensor share the same storage, as with the windows
+    /// created by `TensorBuilder::window`.
     pub fn read_to_host(&self, context: &dyn Context) -> ArrayD<S> {
         use ndarray::ShapeBuilder;
         let mut raw = self.array.as_ref().read::<S>();
@@ -230,12 +344,24 @@ where
                 (l.eval(context) as usize, (s.eval(context) / s_len) as usize)
             })
             .unzip();
-        let len = unwrap!(sizes.iter().zip_eq(&strides).map(|(&l, &s)| l * s).max());
+        // An empty tensor has no last element to compute the storage length from.
+        if sizes.contains(&0) {
+            return unwrap!(ArrayD::from_shape_vec(sizes, vec![]));
+        }
+        let len = 1 + sizes
+            .iter()
+            .zip_eq(&strides)
+            .map(|(&l, &s)| (l - 1) * s)
+            .sum::<usize>();
         raw.split_off(len);
-        unwrap!(ndarray::ArrayBase::from_shape_vec(
+        let mut array = unwrap!(ndarray::ArrayBase::from_shape_vec(
             sizes.strides(strides),
             raw
-        ))
+        ));
+        for (axis, _) in self.reversed.iter().enumerate().filter(|&(_, &rev)| rev) {
+            array.invert_axis(ndarray::Axis(axis));
+        }
+        array
     }
 }
 
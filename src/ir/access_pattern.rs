@@ -22,7 +22,11 @@ pub enum AccessPattern {
     /// dimensions should not overlap.
     Tensor {
         mem_id: Option<ir::MemId>,
+        /// The absolute value of the stride on each dimension.
         dims: FxHashMap<ir::DimId, ir::PartialSize>,
+        /// Dimensions with a negative stride, along which addresses decrease.
+        #[serde(default)]
+        reversed: FxHashSet<ir::DimId>,
     },
 }
 
@@ -31,6 +35,7 @@ impl AccessPattern {
     pub fn is_consecutive(&self, dim: ir::DimId, t: ir::Type) -> bool {
         match self {
             AccessPattern::Unknown(..) => false,
+            AccessPattern::Tensor { reversed, .. } if reversed.contains(&dim) => false,
             AccessPattern::Tensor { dims, .. } => dims
                 .get(&dim)
                 .and_then(|stride| stride.as_int())
@@ -39,6 +44,14 @@ impl AccessPattern {
         }
     }
 
+    /// Indicates if addresses decrease along the given dimension.
+    pub fn is_reversed(&self, dim: ir::DimId) -> bool {
+        match self {
+            AccessPattern::Unknown(..) => false,
+            AccessPattern::Tensor { reversed, .. } => reversed.contains(&dim),
+        }
+    }
+
     /// Returns the id of the memory block accessed.
     pub fn mem_block(&self) -> Option<ir::MemId> {
         match *self {
@@ -53,7 +66,7 @@ impl AccessPattern {
     pub fn check(&self, iter_dims: &FxHashSet<ir::DimId>) -> Result<(), ir::Error> {
         match self {
             AccessPattern::Unknown(..) => Ok(()),
-            AccessPattern::Tensor { dims, .. } => {
+            AccessPattern::Tensor { dims, reversed, .. } => {
                 // Ensures all dimensions referenced in the pattern are nested outside
                 // the access pattern.
                 for (&dim, _) in dims.iter() {
@@ -61,6 +74,11 @@ impl AccessPattern {
                         return Err(ir::Error::MissingIterationDim { dim });
                     }
                 }
+                for &dim in reversed {
+                    if !dims.contains_key(&dim) {
+                        return Err(ir::Error::MissingIncrement { dim });
+                    }
+                }
                 Ok(())
             }
         }
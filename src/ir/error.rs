@@ -22,6 +22,12 @@ pub enum TypeError {
     },
     #[fail(display = "unexpected type `{}`", t)]
     UnexpectedType { t: ir::Type },
+    #[fail(display = "unsupported {} mma from `{}` to `{}`", shape, t, acc_t)]
+    UnsupportedMma {
+        shape: ir::MmaShape,
+        t: ir::Type,
+        acc_t: ir::Type,
+    },
 }
 
 impl TypeError {
@@ -98,6 +104,8 @@ pub enum Error {
     InvalidDimSize,
     #[fail(display = "dimension {} appears twice in the increment list", dim)]
     DuplicateIncrement { dim: ir::DimId },
+    #[fail(display = "dimension {} is reversed but has no increment", dim)]
+    MissingIncrement { dim: ir::DimId },
     #[fail(display = "the operator needs to be nested in dimension {}", dim)]
     MissingIterationDim { dim: ir::DimId },
     #[fail(display = "no mapping found between dimensions {} and {}", lhs, rhs)]
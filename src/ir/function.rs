@@ -416,6 +416,7 @@ impl<L> Function<L> {
         let pattern = ir::AccessPattern::Tensor {
             mem_id: Some(mem),
             dims: increments.iter().cloned().collect(),
+            reversed: Default::default(),
         };
         let ind_var = unwrap!(ir::InductionVar::new(increments, base_addr));
         let ind_var = self.add_ind_var(ind_var);
@@ -15,6 +15,9 @@ pub struct IndVarId(pub u32);
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InductionVar<L = ir::LoweringMap> {
     dims: Vec<(ir::DimId, ir::PartialSize)>,
+    /// Dimensions along which the variable is decremented instead of incremented.
+    #[serde(default)]
+    reversed: FxHashSet<ir::DimId>,
     base: ir::Operand<L>,
 }
 
@@ -47,7 +50,30 @@ impl<L> InductionVar<L> {
             }
             _ => (),
         }
-        Ok(InductionVar { dims, base })
+        Ok(InductionVar {
+            dims,
+            reversed: FxHashSet::default(),
+            base,
+        })
+    }
+
+    /// Creates a new induction var that is decremented, rather than incremented, along the
+    /// `reversed` dimensions.  The increments in `dims` are the absolute values of the
+    /// increments.
+    pub fn new_reversed(
+        dims: Vec<(ir::DimId, ir::PartialSize)>,
+        reversed: FxHashSet<ir::DimId>,
+        base: ir::Operand<L>,
+    ) -> Result<Self, ir::Error> {
+        for &dim in &reversed {
+            if dims.iter().all(|&(other, _)| other != dim) {
+                return Err(ir::Error::MissingIncrement { dim });
+            }
+        }
+        Ok(InductionVar {
+            reversed,
+            ..Self::new(dims, base)?
+        })
     }
 
     /// Renames a dimension.
@@ -64,12 +90,18 @@ impl<L> InductionVar<L> {
     pub fn dims(&self) -> &[(ir::DimId, ir::PartialSize)] {
         &self.dims
     }
+
+    /// Indicates if the variable is decremented along the given dimension.
+    pub fn is_reversed(&self, dim: ir::DimId) -> bool {
+        self.reversed.contains(&dim)
+    }
 }
 
 impl InductionVar<()> {
     pub fn freeze(self, cnt: &mut ir::Counter) -> InductionVar {
         InductionVar {
             dims: self.dims,
+            reversed: self.reversed,
             base: self.base.freeze(cnt),
         }
     }
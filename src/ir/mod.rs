@@ -30,7 +30,7 @@ pub use self::induction_var::{IndVarId, InductionVar};
 pub use self::instruction::{InstId, Instruction};
 pub use self::mem::MemId;
 pub use self::operand::{DimMapScope, FloatLiteral, IntLiteral, LoweringMap, Operand};
-pub use self::operator::{BinOp, Operator, UnaryOp};
+pub use self::operator::{BinOp, MmaShape, Operator, UnaryOp};
 pub use self::size::{PartialSize, Size};
 pub use self::statement::{Statement, StmtId};
 pub use self::types::Type;
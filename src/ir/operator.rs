@@ -47,7 +47,7 @@ impl Rounding {
 }
 
 /// Represents binary arithmetic operators.
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(C)]
 pub enum BinOp {
     /// Adds two operands.
@@ -119,12 +119,15 @@ pub enum UnaryOp {
     Cast(ir::Type),
     /// Calculates exp(x)
     Exp(ir::Type),
+    /// Calculates sqrt(x)
+    Sqrt(ir::Type),
 }
 
 impl fmt::Display for UnaryOp {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
             UnaryOp::Exp(..) => fmt.write_str("exp"),
+            UnaryOp::Sqrt(..) => fmt.write_str("sqrt"),
             UnaryOp::Mov => fmt.write_str("mov"),
             UnaryOp::Cast(t) => write!(fmt, "cast({})", t),
         }
@@ -135,12 +138,39 @@ impl UnaryOp {
     /// Gives the return type of the operand given its input type.
     fn t(self, op_type: ir::Type) -> ir::Type {
         match self {
-            UnaryOp::Mov | UnaryOp::Exp(..) => op_type,
+            UnaryOp::Mov | UnaryOp::Exp(..) | UnaryOp::Sqrt(..) => op_type,
             UnaryOp::Cast(t) => t,
         }
     }
 }
 
+/// The shape of a warp-level matrix multiply-accumulate: a `m x k` tile is multiplied by
+/// a `k x n` tile and added to a `m x n` tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MmaShape {
+    pub m: u32,
+    pub n: u32,
+    pub k: u32,
+}
+
+impl fmt::Display for MmaShape {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "m{}n{}k{}", self.m, self.n, self.k)
+    }
+}
+
+impl MmaShape {
+    /// Returns the number of elements of the `lhs`, `rhs` and accumulator tiles held by
+    /// each thread of a warp of `warp_size` threads.
+    pub fn fragment_sizes(self, warp_size: u32) -> [u32; 3] {
+        [
+            self.m * self.k / warp_size,
+            self.k * self.n / warp_size,
+            self.m * self.n / warp_size,
+        ]
+    }
+}
+
 /// The operation performed by an instruction.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Operator<L = LoweringMap> {
@@ -153,6 +183,13 @@ pub enum Operator<L = LoweringMap> {
     /// Performs s multiplication between the first two operands and adds the
     /// result to the third.
     Mad(Operand<L>, Operand<L>, Operand<L>, Rounding),
+    /// Performs a warp-level matrix multiply-accumulate `lhs * rhs + acc` on tiles of the
+    /// given shape. The tiles are distributed across the threads of a warp: each operand
+    /// is the fragment of the tile held by the current thread, vectorized along the
+    /// vector dimensions of the instruction. The instruction is vectorized by the size of
+    /// the accumulator fragment, and the fragments of `lhs` and `rhs` are the distinct
+    /// values they take along the vector dimensions, from the outermost to the innermost.
+    Mma(Operand<L>, Operand<L>, Operand<L>, MmaShape),
     /// Loads a value of the given type from the given address.
     Ld(Type, Operand<L>, AccessPattern),
     /// Stores the second operand at the address given by the first.
@@ -216,6 +253,16 @@ impl<L> Operator<L> {
                     (_, t) => Err(ir::TypeError::UnexpectedType { t })?,
                 }
             }
+            Mma(ref lhs, ref rhs, ref acc, shape) => {
+                ir::TypeError::check_equals(lhs.t(), rhs.t())?;
+                if !fun.device().supports_mma(shape, lhs.t(), acc.t()) {
+                    Err(ir::TypeError::UnsupportedMma {
+                        shape,
+                        t: lhs.t(),
+                        acc_t: acc.t(),
+                    })?;
+                }
+            }
             Ld(_, ref addr, ref pattern) => {
                 pattern.check(iter_dims)?;
                 let pointer_type = pattern.pointer_type(fun.device());
@@ -234,7 +281,7 @@ impl<L> Operator<L> {
     /// Returns the type of the value produced.
     pub fn t(&self) -> Option<Type> {
         match self {
-            Mad(_, _, op, _) => Some(op.t()),
+            Mad(_, _, op, _) | Mma(_, _, op, _) => Some(op.t()),
             Ld(t, ..) | TmpLd(t, _) | Mul(.., t) => Some(*t),
             BinOp(operator, lhs, ..) => Some(operator.t(lhs.t())),
             UnaryOp(operator, operand) => Some(operator.t(operand.t())),
@@ -248,7 +295,9 @@ impl<L> Operator<L> {
             BinOp(_, lhs, rhs, _) | Mul(lhs, rhs, _, _) | St(lhs, rhs, _, _) => {
                 vec![lhs, rhs]
             }
-            Mad(mul_lhs, mul_rhs, add_rhs, _) => vec![mul_lhs, mul_rhs, add_rhs],
+            Mad(mul_lhs, mul_rhs, add_rhs, _) | Mma(mul_lhs, mul_rhs, add_rhs, _) => {
+                vec![mul_lhs, mul_rhs, add_rhs]
+            }
             UnaryOp(_, op) | Ld(_, op, _) | TmpSt(op, _) => vec![op],
             TmpLd(..) => vec![],
         }
@@ -260,7 +309,9 @@ impl<L> Operator<L> {
             BinOp(_, lhs, rhs, _) | Mul(lhs, rhs, _, _) | St(lhs, rhs, _, _) => {
                 vec![lhs, rhs]
             }
-            Mad(mul_lhs, mul_rhs, add_rhs, _) => vec![mul_lhs, mul_rhs, add_rhs],
+            Mad(mul_lhs, mul_rhs, add_rhs, _) | Mma(mul_lhs, mul_rhs, add_rhs, _) => {
+                vec![mul_lhs, mul_rhs, add_rhs]
+            }
             UnaryOp(_, op, ..) | Ld(_, op, ..) | TmpSt(op, _) => vec![op],
             TmpLd(..) => vec![],
         }
@@ -270,8 +321,26 @@ impl<L> Operator<L> {
     pub fn has_side_effects(&self) -> bool {
         match self {
             St(_, _, b, _) => *b,
-            BinOp(..) | UnaryOp(..) | Mul(..) | Mad(..) | Ld(..) | TmpLd(..)
-            | TmpSt(..) => false,
+            BinOp(..) | UnaryOp(..) | Mul(..) | Mad(..) | Mma(..) | Ld(..)
+            | TmpLd(..) | TmpSt(..) => false,
+        }
+    }
+
+    /// Indicates if the operator is a warp-level matrix multiply-accumulate.
+    pub fn is_mma(&self) -> bool {
+        match self {
+            Mma(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the vectorization factor required by the operator on a device with warps
+    /// of `warp_size` threads: the size of the accumulator fragment for warp-level matrix
+    /// multiply-accumulates, and 1 for other operators.
+    pub fn mma_vector_factor(&self, warp_size: u32) -> u32 {
+        match self {
+            Mma(.., shape) => shape.fragment_sizes(warp_size)[2],
+            _ => 1,
         }
     }
 
@@ -330,6 +399,12 @@ impl<L> Operator<L> {
                 let oper3 = f(oper3);
                 Mad(oper1, oper2, oper3, rounding)
             }
+            Mma(oper1, oper2, oper3, shape) => {
+                let oper1 = f(oper1);
+                let oper2 = f(oper2);
+                let oper3 = f(oper3);
+                Mma(oper1, oper2, oper3, shape)
+            }
             Ld(t, oper1, ap) => {
                 let oper1 = f(oper1);
                 Ld(t, oper1, ap)
@@ -372,6 +447,14 @@ impl<L> ir::IrDisplay<L> for Operator<L> {
                 arg1.display(function),
                 arg2.display(function)
             ),
+            Mma(arg0, arg1, arg2, shape) => write!(
+                fmt,
+                "mma.{}({}, {}, {})",
+                shape,
+                arg0.display(function),
+                arg1.display(function),
+                arg2.display(function)
+            ),
             Ld(_t, arg, _ap) => write!(fmt, "load({})", arg.display(function)),
             St(dst, src, _side_effects, _ap) => write!(
                 fmt,
@@ -400,6 +483,9 @@ impl<L> std::fmt::Display for Operator<L> {
             Mad(arg0, arg1, arg2, rnd) => {
                 write!(fmt, "Mad[{}]({}, {}, {})", rnd, arg0, arg1, arg2)
             }
+            Mma(arg0, arg1, arg2, shape) => {
+                write!(fmt, "Mma<{}>({}, {}, {})", shape, arg0, arg1, arg2)
+            }
             Ld(_t, arg, _ap) => write!(fmt, "Load({})", arg),
             St(dst, src, _side_effects, _ap) => write!(fmt, "Store({}, {})", dst, src),
             TmpLd(_t, mem) => write!(fmt, "TempLoad({})", mem),
@@ -15,6 +15,7 @@
 #![warn(clippy::all)]
 #![allow(clippy::block_in_if_condition_stmt)]
 
+pub mod cache;
 pub mod codegen;
 #[macro_use]
 pub mod helper;
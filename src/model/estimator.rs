@@ -0,0 +1,42 @@
+//! Estimators of the execution time of candidates, used to guide the search.
+use crate::device::Context;
+use crate::model::Bound;
+use crate::search_space::SearchSpace;
+
+/// Estimates the execution time of the implementations of a search space.
+///
+/// As opposed to the lower bound computed by `model::bound`, an estimate may be above the
+/// execution time of some implementations of the search space.  Estimates must thus only be
+/// used to choose which candidates to explore first, and never to prune candidates.
+pub trait CostEstimator: Send + Sync {
+    /// Estimates the execution time of the implementations in `space`, in nanoseconds,
+    /// when executed in `context`.  `bound` is the lower bound of the performance model
+    /// for `space`.
+    fn estimate(&self, space: &SearchSpace, bound: &Bound, context: &dyn Context) -> f64;
+}
+
+/// Uses the lower bound of the performance model as the estimate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoundEstimator;
+
+impl CostEstimator for BoundEstimator {
+    fn estimate(&self, _: &SearchSpace, bound: &Bound, _: &dyn Context) -> f64 {
+        bound.value()
+    }
+}
+
+/// Blends the estimate of another estimator with the lower bound of the performance model,
+/// as `weight * estimate + (1 - weight) * bound`.
+#[derive(Clone, Debug)]
+pub struct Blend<E> {
+    pub estimator: E,
+    /// Weight of the estimate, between 0 and 1.
+    pub weight: f64,
+}
+
+impl<E: CostEstimator> CostEstimator for Blend<E> {
+    fn estimate(&self, space: &SearchSpace, bound: &Bound, context: &dyn Context) -> f64 {
+        let estimate = self.estimator.estimate(space, bound, context);
+        self.weight * estimate + (1. - self.weight) * bound.value()
+    }
+}
@@ -32,6 +32,11 @@ impl<ORIGIN> ExplainedBound<ORIGIN> {
         self.value
     }
 
+    /// Returns the explanation of the bound.
+    pub fn origin(&self) -> &ORIGIN {
+        &self.origin
+    }
+
     /// Indicates if the bound should be used instead of another.
     pub fn is_better_than(&self, other: &ExplainedBound<ORIGIN>) -> bool {
         const F: f64 = 1.0 + 1.0e-6;
@@ -161,6 +166,36 @@ impl Bound {
             size: 1,
         }
     }
+
+    /// Creates a bound caused by a bottleneck.
+    pub fn from_bottleneck(
+        value: f64,
+        name: Cow<'static, str>,
+        level: BottleneckLevel,
+    ) -> Self {
+        Bound {
+            value,
+            origin: Origin::Bottleneck(name, level),
+            size: 1,
+        }
+    }
+
+    /// Combines the bounds of kernels executed one after the other, with
+    /// `launch_overhead` nanoseconds between each kernel.
+    pub fn fission(kernels: Vec<Bound>, launch_overhead: f64) -> Self {
+        let num_launches = kernels.len().saturating_sub(1) as f64;
+        let value =
+            kernels.iter().map(|k| k.value).sum::<f64>() + num_launches * launch_overhead;
+        let size = kernels.iter().map(|k| k.size).sum::<usize>() + 1;
+        Bound {
+            value,
+            origin: Origin::Fission {
+                kernels,
+                launch_overhead,
+            },
+            size,
+        }
+    }
 }
 
 impl fmt::Display for Bound {
@@ -224,6 +259,17 @@ impl fmt::Display for BottleneckLevel {
     }
 }
 
+/// Computes the ratio between the number of threads the hardware runs and the number of
+/// threads `num_threads` of a block. Threads execute in lockstep groups of
+/// `device.simt_width()` threads, so the hardware pads the last group with inactive
+/// threads and the ratio may be `>1`.
+pub fn simt_waste_ratio(device: &dyn Device, num_threads: u64) -> f64 {
+    let simt_width = u64::from(device.simt_width());
+    assert!(simt_width > 0, "{} has a SIMT width of 0", device.name());
+    let num_groups = (num_threads + simt_width - 1) / simt_width;
+    (num_groups * simt_width) as f64 / num_threads as f64
+}
+
 impl FastOrigin {
     /// Converts the origin so it does not use information internal to the performance
     /// model.
@@ -332,6 +378,11 @@ pub enum Origin {
     Scale { inner: Box<Origin>, factor: f64 },
     /// The bound was measured on hardware.
     HardwareEvaluation,
+    /// The computation is split into multiple kernels launched one after the other.
+    Fission {
+        kernels: Vec<Bound>,
+        launch_overhead: f64,
+    },
 }
 
 impl Origin {
@@ -341,7 +392,8 @@ impl Origin {
         match self {
             x @ Origin::Latency
             | x @ Origin::Bottleneck(..)
-            | x @ Origin::HardwareEvaluation => (false, x, false),
+            | x @ Origin::HardwareEvaluation
+            | x @ Origin::Fission { .. } => (false, x, false),
             Origin::Loop { iterations: 0, .. } => (true, Origin::Latency, true),
             Origin::Loop {
                 dims,
@@ -409,6 +461,16 @@ impl fmt::Display for Origin {
                 write!(f, "the pressure on {} at the {}", name, level)
             }
             Origin::HardwareEvaluation => write!(f, "the evaluation on the hardware"),
+            Origin::Fission {
+                ref kernels,
+                launch_overhead,
+            } => write!(
+                f,
+                "{} kernels launched sequentially with {:.2e}ns of launch overhead: [{}]",
+                kernels.len(),
+                launch_overhead,
+                kernels.iter().format("; ")
+            ),
             Origin::Loop {
                 ref dims,
                 iterations,
@@ -548,6 +610,16 @@ impl HwPressure {
         self.bottlenecks[index]
     }
 
+    /// Returns the pressure on the latency.
+    pub fn latency(&self) -> f64 {
+        self.latency
+    }
+
+    /// Returns the pressure on each bottleneck, in the order of `Device::bottlenecks`.
+    pub fn bottlenecks(&self) -> &[f64] {
+        &self.bottlenecks
+    }
+
     /// Pointwise multiplication of the pressure on each resource.
     pub fn multiply(&mut self, other: &HwPressure) {
         self.latency *= other.latency;
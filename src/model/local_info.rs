@@ -109,7 +109,7 @@ fn add_indvar_pressure(
     dim_overhead: &mut FxHashMap<ir::DimId, (HwPressure, HwPressure)>,
     thread_overhead: &mut HwPressure,
 ) {
-    for &(dim, _) in indvar.dims() {
+    for &(dim, ref increment) in indvar.dims() {
         let dim_kind = space.domain().get_dim_kind(dim);
         if dim_kind.intersects(DimKind::VECTOR) {
             continue;
@@ -125,7 +125,13 @@ fn add_indvar_pressure(
             // - both the conditions are also true for an inner dimension.
             device.additive_indvar_pressure(&t)
         } else {
-            device.multiplicative_indvar_pressure(&t)
+            let mut overhead = device.multiplicative_indvar_pressure(&t);
+            // Reversed levels with a constant increment take a single `mad` with the
+            // negated increment. Otherwise, the product is subtracted from the base.
+            if indvar.is_reversed(dim) && !increment.factors().1.is_empty() {
+                overhead.add_sequential(&device.additive_indvar_pressure(&t));
+            }
+            overhead
         };
         let size = dim_sizes[&dim].min;
         if dim_kind.intersects(DimKind::THREAD | DimKind::BLOCK) {
@@ -2,13 +2,17 @@
 mod code_point;
 mod cuda_tests;
 mod dependency_map;
+mod estimator;
 mod hw_pressure;
 mod level;
 mod local_info;
 
 pub mod size;
 
-pub use self::hw_pressure::{BottleneckLevel, Bound, HwPressure};
+pub use self::estimator::{Blend, BoundEstimator, CostEstimator};
+pub use self::hw_pressure::{
+    simt_waste_ratio, BottleneckLevel, Bound, HwPressure, Origin,
+};
 pub use self::local_info::Nesting;
 
 use log::{debug, trace};
@@ -129,6 +133,51 @@ pub fn bound(space: &SearchSpace, context: &dyn Context) -> Bound {
     bound.explain(&*context.device(), &levels, code_points.dag.nodes())
 }
 
+/// Returns the pressure the implementations of `space` put on the resources of the whole
+/// device.  This is the pressure `bound` derives the throughput bound from.
+pub fn global_pressure(space: &SearchSpace, context: &dyn Context) -> HwPressure {
+    let local_info = LocalInfo::compute(space, context);
+    sum_pressure(
+        context,
+        space,
+        &local_info,
+        BottleneckLevel::Global,
+        &[],
+        &ir::PartialSize::default(),
+    )
+}
+
+/// Returns a lower bound on the execution time of a computation split into multiple
+/// kernels launched one after the other, when executed in `context`.
+///
+/// `intermediate_bytes[i]` is the size of the buffer through which the `i`-th kernel
+/// passes its results to the next one: it is written to the global memory by the `i`-th
+/// kernel and read back by the next one.
+pub fn fission_bound(
+    kernels: &[&SearchSpace],
+    intermediate_bytes: &[u64],
+    context: &dyn Context,
+) -> Bound {
+    assert_eq!(kernels.len(), intermediate_bytes.len() + 1);
+    let device = context.device();
+    let bandwidth = device.global_mem_bandwidth();
+    let bounds = kernels
+        .iter()
+        .enumerate()
+        .map(|(id, space)| {
+            let stored = intermediate_bytes.get(id).cloned().unwrap_or(0);
+            let loaded = id.checked_sub(1).map_or(0, |pred| intermediate_bytes[pred]);
+            let round_trip = Bound::from_bottleneck(
+                (stored + loaded) as f64 / bandwidth,
+                "global memory round trips".into(),
+                BottleneckLevel::Global,
+            );
+            cmp::max(bound(space, context), round_trip)
+        })
+        .collect();
+    Bound::fission(bounds, device.kernel_launch_overhead())
+}
+
 /// Populates the dependency maps and the levels with dependency edges and back-edges.
 fn populate(
     space: &SearchSpace,
@@ -371,3 +420,48 @@ fn apply_dim_map(
         level_dag.add_dependency(to_map, pred, dst_point, &latency);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::fake;
+    use crate::helper;
+    use std::sync::Arc;
+
+    /// Returns the search space of a kernel that only moves a constant.
+    fn mov_kernel(context: &dyn Context) -> SearchSpace {
+        let signature = Arc::new(ir::Signature::new("fission".to_string()));
+        let mut builder = helper::Builder::new(signature, context.device());
+        builder.mov(&0f32);
+        builder.get()
+    }
+
+    /// Ensures the launch overhead is paid between each pair of kernels.
+    #[test]
+    fn fission_launch_overhead() {
+        let context = fake::Context::<fake::Device>::default();
+        let (first, second) = (mov_kernel(&context), mov_kernel(&context));
+        let sum = bound(&first, &context).value() + bound(&second, &context).value();
+        let fission = fission_bound(&[&first, &second], &[0], &context);
+        assert_eq!(fission.value(), sum + 10.0);
+        match fission.origin() {
+            Origin::Fission { kernels, .. } => assert_eq!(kernels.len(), 2),
+            origin => panic!("unexpected origin: {}", origin),
+        }
+        // A single kernel is launched without overhead.
+        let single = fission_bound(&[&first], &[], &context);
+        assert_eq!(single.value(), bound(&first, &context).value());
+    }
+
+    /// Ensures the intermediate buffers are stored and loaded through the global memory.
+    #[test]
+    fn fission_round_trips() {
+        let context = fake::Context::<fake::Device>::default();
+        let kernels = (0..3).map(|_| mov_kernel(&context)).collect::<Vec<_>>();
+        let kernels = kernels.iter().collect::<Vec<_>>();
+        let fission = fission_bound(&kernels, &[1000, 100], &context);
+        // The fake device moves one byte per nanosecond: the first kernel stores 1000
+        // bytes, the second loads them and stores 100 bytes that the last one loads.
+        assert!(fission.value() >= 1000.0 + 1100.0 + 100.0 + 2.0 * 10.0);
+    }
+}
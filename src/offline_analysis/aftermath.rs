@@ -300,7 +300,10 @@ impl TraceWriter {
 
     /// Writes all candidates of a candidate tree to the output file
     pub fn write_candidates(&mut self, t: &CandidateTree) -> Result<(), io::Error> {
-        self.write_candidates_rec(&t.get_root(), 0)
+        for root in t.roots() {
+            self.write_candidates_rec(&root, 0)?;
+        }
+        Ok(())
     }
 
     /// Writes a data structure to the output file that declares a
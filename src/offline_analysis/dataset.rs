@@ -0,0 +1,188 @@
+//! Exports the candidates evaluated during a search as a dataset, to train cost models
+//! outside of Telamon.
+//!
+//! Each evaluated candidate of an eventlog is rebuilt by replaying its actions on the root
+//! of the search space.  A sample then holds the features of the candidate, as computed by
+//! `explorer::features`, the value of each decision of the candidate and the measured
+//! runtime.  Samples are written either in CSV, with one column per feature and per
+//! decision, or in JSON with one sample per line.
+use crate::device::Context;
+use crate::explorer::choice::ActionEx as Action;
+use crate::explorer::features::{self, FeatureVector};
+use crate::explorer::mcts::{Message, NodeId};
+use crate::model;
+use crate::offline_analysis::tree::CandidateTree;
+use crate::search_space::SearchSpace;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// An evaluated candidate.
+#[derive(Clone, Debug, Serialize)]
+pub struct Sample {
+    /// Identifier of the candidate in the eventlog.
+    pub id: NodeId,
+    /// Measured runtime, in nanoseconds.  `None` if the candidate was cut or its evaluation
+    /// failed.
+    pub runtime: Option<f64>,
+    /// Features of the candidate, including its bound and its pressure on the device.
+    pub features: FeatureVector,
+    /// Value of each decision, indexed by the name of the decision.
+    pub decisions: BTreeMap<String, String>,
+}
+
+/// The samples extracted from an eventlog.
+#[derive(Clone, Debug, Default)]
+pub struct Dataset {
+    pub samples: Vec<Sample>,
+}
+
+/// Format in which a dataset is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One row per sample and one column per feature and per decision.
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "csv" => Format::Csv,
+            "json" => Format::Json,
+            _ => return Err(format!("invalid dataset format: {}", s)),
+        })
+    }
+}
+
+impl Dataset {
+    /// Extracts the evaluated candidates of an eventlog.  `root` must be the root of the
+    /// search space explored by the search that produced the eventlog, and `context` the
+    /// context it was explored in.
+    pub fn from_eventlog<P: AsRef<Path>>(
+        path: P,
+        root: &SearchSpace,
+        context: &dyn Context,
+    ) -> io::Result<Self> {
+        let mut dataset = Dataset::default();
+        CandidateTree::new().replay_eventlog(path, |tree, message| {
+            if let Message::Evaluation { id, value, .. } = message {
+                let node = tree.get_node(id);
+                let space = rebuild(root, &node.actions())?;
+                let features =
+                    features::extract_with_model(&space, node.bound().as_ref(), context);
+                dataset.samples.push(Sample {
+                    id,
+                    runtime: value,
+                    features,
+                    decisions: decisions(&space),
+                });
+            }
+            Ok(())
+        })?;
+        Ok(dataset)
+    }
+
+    /// Writes the dataset in the given format.
+    pub fn write<W: Write>(&self, format: Format, writer: W) -> io::Result<()> {
+        match format {
+            Format::Csv => self.write_csv(writer),
+            Format::Json => self.write_json(writer),
+        }
+    }
+
+    /// Writes the dataset in CSV.  Decisions that do not exist in a sample, for instance
+    /// because they apply to dimensions created by a lowering, are left empty.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let decisions = self
+            .samples
+            .iter()
+            .flat_map(|sample| sample.decisions.keys())
+            .collect::<BTreeSet<_>>();
+        let mut writer = csv::Writer::from_writer(writer);
+        let mut header = vec!["id", "runtime"];
+        header.extend(FeatureVector::names());
+        header.extend(&["limiting_resource", "limiting_level"]);
+        header.extend(decisions.iter().map(|name| name.as_str()));
+        writer.write_record(&header)?;
+        for sample in &self.samples {
+            let bound = sample.features.bound.as_ref();
+            let limiting_resource = bound.and_then(|b| b.limiting_resource.clone());
+            let limiting_level = bound
+                .and_then(|b| b.limiting_level)
+                .map(|level| format!("{:?}", level));
+            let record = vec![
+                u64::from(sample.id).to_string(),
+                sample.runtime.map(|r| r.to_string()).unwrap_or_default(),
+            ]
+            .into_iter()
+            .chain(sample.features.values().into_iter().map(|v| v.to_string()))
+            .chain(vec![
+                limiting_resource.unwrap_or_default(),
+                limiting_level.unwrap_or_default(),
+            ])
+            .chain(
+                decisions
+                    .iter()
+                    .map(|&name| sample.decisions.get(name).cloned().unwrap_or_default()),
+            );
+            writer.write_record(record)?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the dataset in JSON, with one sample per line.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for sample in &self.samples {
+            serde_json::to_writer(&mut writer, sample)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+/// Applies `actions` to `root`.
+fn rebuild(root: &SearchSpace, actions: &[Action]) -> io::Result<SearchSpace> {
+    actions.iter().try_fold(root.clone(), |space, action| {
+        action
+            .apply_to(space)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    })
+}
+
+/// Lists the value of the decisions of a candidate: the kind and size of each dimension,
+/// the memory space of each memory block and the flag of each memory instruction.
+pub fn decisions(space: &SearchSpace) -> BTreeMap<String, String> {
+    let fun = space.ir_instance();
+    let domain = space.domain();
+    let mut decisions = BTreeMap::new();
+    for dim in fun.dims() {
+        let kind = format!("{:?}", domain.get_dim_kind(dim.id()));
+        decisions.insert(format!("dim_kind.{}", dim.id().0), kind.to_lowercase());
+        if dim.possible_sizes().is_some() {
+            let size = model::size::dim_bounds(dim.id(), space);
+            if size.min == size.max {
+                decisions.insert(format!("size.{}", dim.id().0), size.min.to_string());
+            }
+        }
+    }
+    for block in fun.mem_blocks() {
+        let mem_space = format!("{:?}", domain.get_mem_space(block.mem_id()));
+        decisions.insert(
+            format!("mem_space.{}", block.mem_id().0),
+            mem_space.to_lowercase(),
+        );
+    }
+    for inst in fun.insts() {
+        if inst.as_mem_inst().is_some() {
+            let flag = format!("{:?}", domain.get_inst_flag(inst.id()));
+            decisions.insert(format!("inst_flag.{}", inst.id().0), flag.to_lowercase());
+        }
+    }
+    decisions
+}
@@ -0,0 +1,179 @@
+//! Explains why a candidate is a dead-end.
+//!
+//! A candidate is a dead-end when none of the actions of its next choice can be applied.
+//! For each action of the choice, the actions leading to the candidate are replayed from
+//! the root to extract a minimal set of them that conflicts with it: removing any action
+//! from the set makes the action applicable again.  Conflicts are rendered as a graph whose
+//! nodes are statements and whose edges are order decisions, so that conflicting orders and
+//! dimension kinds can be spotted at a glance.
+use crate::explorer::choice::{ActionError, ActionEx as Action};
+use crate::explorer::conflicts::extract_conflict;
+use crate::ir::{self, IrDisplay};
+use crate::search_space::{self, SearchSpace};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+/// The reason why an action of a choice cannot be applied.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    /// The action that cannot be applied.
+    pub action: Action,
+    /// A minimal set of the previous actions that conflicts with `action`.  If empty,
+    /// `action` conflicts with the definition of the kernel itself.
+    pub cause: Vec<Action>,
+}
+
+/// Explains why the actions of a choice cannot be applied to a candidate.
+pub struct DeadendExplanation {
+    /// The search space of the kernel, used to display the actions.
+    space: SearchSpace,
+    /// The conflicts of the actions that cannot be applied.
+    pub conflicts: Vec<Conflict>,
+    /// The actions of the choice that can be applied.  If not empty, the candidate is not
+    /// a dead-end for this choice.
+    pub applicable: Vec<Action>,
+}
+
+impl DeadendExplanation {
+    /// Explains why the actions of `choice` cannot be applied after `actions`, which are
+    /// applied to `root` in order.  Returns an error if `actions` cannot be applied.
+    pub fn new(
+        root: &SearchSpace,
+        actions: &[Action],
+        choice: &[Action],
+    ) -> Result<Self, ActionError> {
+        let space = actions
+            .iter()
+            .try_fold(root.clone(), |space, action| action.apply_to(space))?;
+        let mut conflicts = vec![];
+        let mut applicable = vec![];
+        for action in choice {
+            if action.apply_to(space.clone()).is_ok() {
+                applicable.push(action.clone());
+            } else {
+                conflicts.push(Conflict {
+                    action: action.clone(),
+                    cause: extract_conflict(root, actions, action),
+                });
+            }
+        }
+        Ok(DeadendExplanation {
+            space,
+            conflicts,
+            applicable,
+        })
+    }
+
+    /// Indicates if none of the actions of the choice can be applied.
+    pub fn is_deadend(&self) -> bool {
+        self.applicable.is_empty()
+    }
+
+    /// Writes the conflicts as a graph in the DOT format, with one cluster per conflict.
+    /// Order decisions are drawn as edges between statements and dimension kinds are
+    /// shown in the label of dimensions.  Other decisions are listed in a separate node.
+    /// The action that cannot be applied is drawn in red.
+    pub fn write_dot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let fun = self.space.ir_instance();
+        writeln!(writer, "digraph deadend {{")?;
+        for (idx, conflict) in self.conflicts.iter().enumerate() {
+            writeln!(writer, "  subgraph cluster_{} {{", idx)?;
+            let title = format!("cannot apply {}", conflict.action.display(fun));
+            writeln!(writer, "    label={:?};", title)?;
+            let mut stmts = BTreeMap::new();
+            let mut others = vec![];
+            let actions = conflict.cause.iter().map(|action| (action, false));
+            for (action, failed) in actions.chain(Some((&conflict.action, true))) {
+                let color = if failed { "red" } else { "black" };
+                match action {
+                    Action::Action(search_space::Action::Order(lhs, rhs, order)) => {
+                        stmts.entry(*lhs).or_insert_with(Vec::new);
+                        stmts.entry(*rhs).or_insert_with(Vec::new);
+                        writeln!(
+                            writer,
+                            "    c{}_{} -> c{}_{} [label=\"{:?}\", color={}, fontcolor={}];",
+                            idx,
+                            stmt_node(*lhs),
+                            idx,
+                            stmt_node(*rhs),
+                            order,
+                            color,
+                            color
+                        )?;
+                    }
+                    Action::Action(search_space::Action::DimKind(dim, kind)) => stmts
+                        .entry(ir::StmtId::from(*dim))
+                        .or_insert_with(Vec::new)
+                        .push((format!("{:?}", kind), failed)),
+                    _ => others.push((action.display(fun).to_string(), failed)),
+                }
+            }
+            for (stmt, kinds) in stmts {
+                let label = Some(stmt.to_string())
+                    .into_iter()
+                    .chain(kinds.iter().map(|(kind, _)| kind.clone()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let failed = kinds.iter().any(|&(_, failed)| failed);
+                let color = if failed { "red" } else { "black" };
+                writeln!(
+                    writer,
+                    "    c{}_{} [label={:?}, color={}];",
+                    idx,
+                    stmt_node(stmt),
+                    label,
+                    color
+                )?;
+            }
+            if !others.is_empty() {
+                let label = others
+                    .iter()
+                    .map(|(action, _)| action.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let failed = others.iter().any(|&(_, failed)| failed);
+                let color = if failed { "red" } else { "black" };
+                writeln!(
+                    writer,
+                    "    c{}_others [shape=note, label={:?}, color={}];",
+                    idx, label, color
+                )?;
+            }
+            writeln!(writer, "  }}")?;
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+/// Returns an identifier for the node of a statement in the DOT graph.
+fn stmt_node(stmt: ir::StmtId) -> String {
+    match stmt {
+        ir::StmtId::Inst(inst) => format!("inst{}", inst.0),
+        ir::StmtId::Dim(dim) => format!("dim{}", dim.0),
+    }
+}
+
+impl fmt::Display for DeadendExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fun = self.space.ir_instance();
+        for action in &self.applicable {
+            writeln!(f, "{} can be applied", action.display(fun))?;
+        }
+        for conflict in &self.conflicts {
+            if conflict.cause.is_empty() {
+                writeln!(
+                    f,
+                    "{} conflicts with the kernel definition",
+                    conflict.action.display(fun)
+                )?;
+                continue;
+            }
+            writeln!(f, "{} conflicts with:", conflict.action.display(fun))?;
+            for action in &conflict.cause {
+                writeln!(f, "  {}", action.display(fun))?;
+            }
+        }
+        Ok(())
+    }
+}
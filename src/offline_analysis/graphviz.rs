@@ -0,0 +1,173 @@
+//! Renders a candidate tree reconstructed from an eventlog in the DOT format.
+//!
+//! Each node is annotated with the bound of the performance model, the number of traces
+//! that went through it and the result of its evaluation, if any.  Edges are labelled
+//! with the action they apply.  Search trees quickly grow beyond what Graphviz can lay
+//! out, so the rendered tree can be truncated to a maximal depth and to the nodes visited
+//! a minimal number of times.  Nodes whose children are hidden are drawn with dashed
+//! borders.
+use crate::offline_analysis::tree::{CandidateNode, CandidateTree};
+use std::io::{self, Write};
+
+/// Limits the part of the tree that is rendered.
+#[derive(Clone, Debug, Default)]
+pub struct Truncation {
+    /// Maximal depth of the rendered nodes, the root being at depth 0.
+    pub max_depth: Option<usize>,
+    /// Minimal number of visits of the rendered nodes.  The roots are always rendered.
+    pub min_visits: usize,
+}
+
+impl Truncation {
+    /// Indicates if the children of a node at the given depth may be rendered.
+    fn expands(&self, depth: usize) -> bool {
+        self.max_depth.map_or(true, |max_depth| depth < max_depth)
+    }
+}
+
+/// Writes the part of `tree` allowed by `truncation` in the DOT format.
+pub fn write_dot<W: Write>(
+    tree: &CandidateTree,
+    truncation: &Truncation,
+    mut writer: W,
+) -> io::Result<()> {
+    writeln!(writer, "digraph candidates {{")?;
+    writeln!(writer, "  node [shape=box, fontsize=10];")?;
+    writeln!(writer, "  edge [fontsize=8];")?;
+    let mut stack = tree.roots().map(|root| (root, 0)).collect::<Vec<_>>();
+    while let Some((node, depth)) = stack.pop() {
+        let mut truncated = false;
+        for child in node.children().filter_map(|child| child) {
+            if !truncation.expands(depth) || child.visits() < truncation.min_visits {
+                truncated = true;
+                continue;
+            }
+            writeln!(
+                writer,
+                "  n{} -> n{} [label={:?}];",
+                node.id(),
+                child.id(),
+                child.action_str()
+            )?;
+            stack.push((child, depth + 1));
+        }
+        write_node(&mut writer, &node, truncated)?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// Writes the declaration of a node, with its annotations.
+fn write_node<W: Write>(
+    writer: &mut W,
+    node: &CandidateNode,
+    truncated: bool,
+) -> io::Result<()> {
+    let mut label = format!("#{}\nvisits: {}", node.id(), node.visits());
+    if let Some(bound) = &*node.bound() {
+        label.push_str(&format!("\nbound: {:.4e}ns", bound.value()));
+    }
+    if let Some(score) = node.score() {
+        label.push_str(&format!("\neval: {:.4e}ns", score));
+    }
+    let color = if node.is_deadend() {
+        "red"
+    } else if node.is_implementation() {
+        "darkgreen"
+    } else {
+        "black"
+    };
+    let style = if truncated { "dashed" } else { "solid" };
+    writeln!(
+        writer,
+        "  n{} [label={:?}, color={}, style={}];",
+        node.id(),
+        label,
+        color,
+        style
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explorer::choice::ActionEx;
+    use crate::explorer::mcts::{EdgeIndex, NodeId};
+    use crate::ir;
+    use crate::search_space::{Action, Order};
+    use std::time::Duration;
+
+    /// Returns an action ordering two instructions.
+    fn order(lhs: u32, rhs: u32) -> ActionEx {
+        ActionEx::Action(Action::Order(
+            ir::StmtId::from(ir::InstId(lhs)),
+            ir::StmtId::from(ir::InstId(rhs)),
+            Order::BEFORE,
+        ))
+    }
+
+    /// Builds a tree whose root has two children, the first of which has a child.  The
+    /// first child is visited twice and the others once.
+    fn tree() -> CandidateTree {
+        let mut tree = CandidateTree::new();
+        let time = Duration::from_millis(1);
+        let node = |id: u64, index: u16| Some((NodeId::from(id), EdgeIndex::from(index)));
+        tree.extend(
+            NodeId::from(0),
+            time,
+            None,
+            None,
+            &mut vec![order(0, 1), order(1, 0)],
+        );
+        tree.extend(
+            NodeId::from(1),
+            time,
+            node(0, 0),
+            None,
+            &mut vec![order(0, 2)],
+        );
+        tree.extend(NodeId::from(2), time, node(0, 1), None, &mut vec![]);
+        tree.extend(NodeId::from(3), time, node(1, 0), None, &mut vec![]);
+        for &id in &[0, 1, 1, 2, 3] {
+            tree.get_node(NodeId::from(id)).add_visit();
+        }
+        tree.get_node(NodeId::from(3)).set_score(2.);
+        tree
+    }
+
+    fn render(truncation: &Truncation) -> String {
+        let mut dot = Vec::new();
+        write_dot(&tree(), truncation, &mut dot).unwrap();
+        String::from_utf8(dot).unwrap()
+    }
+
+    #[test]
+    fn write_full_tree() {
+        let dot = render(&Truncation::default());
+        assert!(dot.starts_with("digraph candidates {"));
+        assert!(dot.trim_end().ends_with('}'));
+        for edge in &["n0 -> n1", "n0 -> n2", "n1 -> n3"] {
+            assert!(dot.contains(edge), "missing edge {}", edge);
+        }
+        for id in 0..4 {
+            assert!(dot.contains(&format!("  n{} [label=", id)));
+        }
+        assert!(dot.contains("eval: 2.0000e0ns"));
+        assert!(!dot.contains("dashed"));
+    }
+
+    /// Ensures truncated nodes are hidden and their parents drawn with dashed borders.
+    #[test]
+    fn write_truncated_tree() {
+        let truncation = Truncation {
+            max_depth: Some(1),
+            min_visits: 2,
+        };
+        let dot = render(&truncation);
+        assert!(dot.contains("n0 -> n1"));
+        assert!(!dot.contains("n0 -> n2"));
+        assert!(!dot.contains("n1 -> n3"));
+        assert!(!dot.contains("  n2 [label="));
+        assert!(!dot.contains("  n3 [label="));
+        assert_eq!(dot.matches("style=dashed").count(), 2);
+    }
+}
@@ -0,0 +1,155 @@
+//! Merges the event logs of several searches into a single event log.
+use crate::explorer::mcts::{Event, Message, NodeId, Timed};
+use fxhash::FxHashMap;
+
+/// Renumbers the nodes referenced by the messages of several event logs, so that they are
+/// unique in the merged log.  The merged log has one root per tree of the merged logs.
+#[derive(Default)]
+pub struct LogMerger {
+    /// Identifier in the merged log of each node, given by its log and its identifier in
+    /// the log.
+    ids: FxHashMap<(usize, NodeId), NodeId>,
+    /// First root of each log, in the numbering of the log.
+    roots: FxHashMap<usize, NodeId>,
+}
+
+impl LogMerger {
+    /// Creates a merger with no nodes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renumbers the nodes referenced by `message`, read from the log with index `log`.
+    /// The messages of each log must be renumbered in order.
+    ///
+    /// Older logs start their traces from the root implicitly, which is ambiguous once the
+    /// logs are merged.  Their traces are made to select the root of their log first.
+    pub fn rename(&mut self, log: usize, message: &mut Message) {
+        match message {
+            Message::Node {
+                id, parent: None, ..
+            } => {
+                self.roots.entry(log).or_insert(*id);
+            }
+            Message::Trace { events, .. } => {
+                let selects_root = if let Some(Timed {
+                    value: Event::SelectNode(_),
+                    ..
+                }) = events.first()
+                {
+                    true
+                } else {
+                    false
+                };
+                if let (false, Some(&root)) = (selects_root, self.roots.get(&log)) {
+                    let start_time = events
+                        .first()
+                        .map(|event| event.start_time)
+                        .unwrap_or_default();
+                    events.insert(
+                        0,
+                        Timed {
+                            start_time,
+                            end_time: start_time,
+                            value: Event::SelectNode(root),
+                        },
+                    );
+                }
+            }
+            _ => (),
+        }
+        let ids = &mut self.ids;
+        message.map_node_ids(|id| {
+            let num_ids = ids.len();
+            *ids.entry((log, id))
+                .or_insert_with(|| NodeId::from(num_ids as u64))
+        });
+    }
+
+    /// Returns the number of nodes in the merged log.
+    pub fn num_nodes(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::offline_analysis::tree::CandidateTree;
+    use std::time::Duration;
+
+    /// Returns the messages of a search that found its root to be an implementation,
+    /// without selecting the root at the start of the trace.
+    fn log(value: f64) -> Vec<Message> {
+        let time = Duration::from_millis(1);
+        vec![
+            Message::Node {
+                id: NodeId::from(0),
+                parent: None,
+                children: vec![],
+                bound: None,
+                discovery_time: time,
+            },
+            Message::Trace {
+                thread: "ThreadId(1)".to_string(),
+                events: vec![Timed {
+                    start_time: time,
+                    end_time: time,
+                    value: Event::Implementation,
+                }],
+            },
+            Message::Evaluation {
+                id: NodeId::from(0),
+                value: Some(value),
+                result_time: time,
+            },
+        ]
+    }
+
+    /// Ensures merged logs can be replayed into a tree with one root per log.
+    #[test]
+    fn merge_and_replay() {
+        let mut merger = LogMerger::new();
+        let mut tree = CandidateTree::new();
+        let logs = vec![log(1.0), log(2.0)];
+        for (index, log) in logs.into_iter().enumerate() {
+            for mut message in log {
+                merger.rename(index, &mut message);
+                match message {
+                    Message::Node {
+                        id,
+                        parent,
+                        mut children,
+                        bound,
+                        discovery_time,
+                    } => tree.extend(id, discovery_time, parent, bound, &mut children),
+                    Message::Trace { events, .. } => {
+                        let mut node = tree.get_root();
+                        for event in events {
+                            match event.value {
+                                Event::SelectNode(id) => node = tree.get_node(id),
+                                Event::Implementation => {
+                                    node.declare_implementation(event.start_time)
+                                }
+                                _ => panic!("unexpected event"),
+                            }
+                        }
+                    }
+                    Message::Evaluation { id, value, .. } => {
+                        tree.get_node(id).set_score(value.unwrap())
+                    }
+                    _ => panic!("unexpected message"),
+                }
+            }
+        }
+
+        assert_eq!(merger.num_nodes(), 2);
+        let roots = tree.roots().collect::<Vec<_>>();
+        assert_eq!(roots.len(), 2);
+        for (root, score) in roots.iter().zip(&[1.0, 2.0]) {
+            assert!(root.is_root());
+            assert!(root.is_implementation());
+            assert_eq!(root.score(), Some(*score));
+        }
+    }
+}
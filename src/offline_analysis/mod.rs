@@ -1,2 +1,8 @@
 pub mod aftermath;
+pub mod dataset;
+pub mod deadend;
+pub mod graphviz;
+pub mod merge;
+pub mod report;
+pub mod space_stats;
 pub mod tree;
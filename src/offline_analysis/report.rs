@@ -0,0 +1,437 @@
+//! Generates a standalone HTML report from an eventlog.
+//!
+//! The report plots the runtime of the evaluated candidates against the time at which they
+//! were evaluated, along with the best runtime found so far, and the runtime of the
+//! candidates against their bound, which shows how tight the performance model is.  It
+//! then lists the best candidates with the actions leading to them.  Plots are inlined as
+//! SVG so that the page can be opened without network access.
+use crate::explorer::choice::ActionEx as Action;
+use crate::explorer::mcts::{Message, NodeId};
+use crate::offline_analysis::tree::CandidateTree;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+use utils::cmp_f64;
+
+/// Width of the plots, in pixels.
+const PLOT_WIDTH: f64 = 640.;
+/// Height of the plots, in pixels.
+const PLOT_HEIGHT: f64 = 400.;
+/// Space left around the plots for the axes, in pixels.
+const PLOT_MARGIN: f64 = 60.;
+
+/// A candidate evaluated during the search.
+#[derive(Clone, Debug)]
+pub struct Evaluation {
+    /// Identifier of the candidate in the eventlog.
+    pub id: NodeId,
+    /// Time at which the evaluation finished, relative to the start of the search.
+    pub time: Duration,
+    /// Measured runtime, in nanoseconds.
+    pub runtime: f64,
+    /// Bound of the performance model, in nanoseconds.
+    pub bound: Option<f64>,
+    /// Actions leading to the candidate, in order.
+    pub actions: Vec<Action>,
+}
+
+/// The evaluations of a search, as extracted from its eventlog.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// Successful evaluations, in the order they finished.
+    pub evaluations: Vec<Evaluation>,
+    /// Number of evaluations that failed or were cut.
+    pub num_failed: usize,
+}
+
+impl Report {
+    /// Extracts the evaluations of an eventlog.
+    pub fn from_eventlog<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut report = Report::default();
+        CandidateTree::new().replay_eventlog(path, |tree, message| {
+            match message {
+                Message::Evaluation {
+                    id,
+                    value: Some(runtime),
+                    result_time,
+                } => {
+                    let node = tree.get_node(id);
+                    let bound = node.bound().as_ref().map(|bound| bound.value());
+                    report.evaluations.push(Evaluation {
+                        id,
+                        time: result_time,
+                        runtime,
+                        bound,
+                        actions: node.actions(),
+                    });
+                }
+                Message::Evaluation { value: None, .. } => report.num_failed += 1,
+                Message::Node { .. }
+                | Message::Trace { .. }
+                | Message::Screening { .. }
+                | Message::Launch { .. }
+                | Message::Source { .. }
+                | Message::Energy { .. } => (),
+            }
+            Ok(())
+        })?;
+        Ok(report)
+    }
+
+    /// Returns the `k` fastest evaluations, from the fastest.
+    pub fn top(&self, k: usize) -> Vec<&Evaluation> {
+        let mut evaluations = self.evaluations.iter().collect::<Vec<_>>();
+        evaluations.sort_by(|lhs, rhs| cmp_f64(lhs.runtime, rhs.runtime));
+        evaluations.truncate(k);
+        evaluations
+    }
+
+    /// Writes the report as a standalone HTML page, listing the `top_k` best candidates.
+    pub fn write_html<W: Write>(&self, top_k: usize, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "<!DOCTYPE html>")?;
+        writeln!(writer, "<html><head><meta charset=\"utf-8\">")?;
+        writeln!(writer, "<title>Telamon search report</title>")?;
+        writeln!(writer, "<style>{}</style>", STYLE)?;
+        writeln!(writer, "</head><body>")?;
+        writeln!(writer, "<h1>Search report</h1>")?;
+        writeln!(
+            writer,
+            "<p>{} successful evaluations, {} failed or cut.</p>",
+            self.evaluations.len(),
+            self.num_failed
+        )?;
+        if let Some(best) = self.top(1).first() {
+            writeln!(
+                writer,
+                "<p>Best runtime: {:.4e}ns, found after {:.1}s.</p>",
+                best.runtime,
+                seconds(best.time)
+            )?;
+        }
+
+        writeln!(writer, "<h2>Runtime over time</h2>")?;
+        writeln!(writer, "{}", self.runtime_plot())?;
+        writeln!(writer, "<h2>Runtime against bound</h2>")?;
+        writeln!(writer, "{}", self.bound_plot())?;
+
+        writeln!(writer, "<h2>Best candidates</h2>")?;
+        writeln!(writer, "<table>")?;
+        writeln!(
+            writer,
+            "<tr><th>Rank</th><th>Id</th><th>Runtime (ns)</th><th>Bound (ns)</th>\
+             <th>Time (s)</th><th>Actions</th></tr>"
+        )?;
+        for (rank, evaluation) in self.top(top_k).into_iter().enumerate() {
+            let bound = evaluation
+                .bound
+                .map(|bound| format!("{:.4e}", bound))
+                .unwrap_or_default();
+            let actions = evaluation
+                .actions
+                .iter()
+                .map(|action| escape(&format!("{:?}", action)))
+                .collect::<Vec<_>>()
+                .join("<br>");
+            writeln!(
+                writer,
+                "<tr><td>{}</td><td>{}</td><td>{:.4e}</td><td>{}</td><td>{:.1}</td>\
+                 <td><details><summary>{} actions</summary><code>{}</code></details>\
+                 </td></tr>",
+                rank + 1,
+                evaluation.id,
+                evaluation.runtime,
+                bound,
+                seconds(evaluation.time),
+                evaluation.actions.len(),
+                actions
+            )?;
+        }
+        writeln!(writer, "</table>")?;
+        writeln!(writer, "</body></html>")
+    }
+
+    /// Plots the runtime of the evaluations against time, with the best runtime so far.
+    fn runtime_plot(&self) -> String {
+        let points = self
+            .evaluations
+            .iter()
+            .map(|eval| (seconds(eval.time), eval.runtime))
+            .collect::<Vec<_>>();
+        let mut best_curve: Vec<(f64, f64)> = Vec::new();
+        for &(time, runtime) in &points {
+            match best_curve.last().cloned() {
+                Some((_, best)) if best <= runtime => (),
+                Some((_, best)) => {
+                    best_curve.push((time, best));
+                    best_curve.push((time, runtime));
+                }
+                None => best_curve.push((time, runtime)),
+            }
+        }
+        if let (Some((_, best)), Some(&(end, _))) =
+            (best_curve.last().cloned(), points.last())
+        {
+            best_curve.push((end, best));
+        }
+        let mut plot = Plot::new(
+            Axis::fit("time (s)", false, points.iter().map(|p| p.0)),
+            Axis::fit("runtime (ns)", true, points.iter().map(|p| p.1)),
+        );
+        plot.scatter(&points, "#8888cc");
+        plot.line(&best_curve, "#cc3333");
+        plot.finish()
+    }
+
+    /// Plots the runtime of the evaluations against their bound.  Points above the
+    /// diagonal have a bound lower than their runtime, as expected.
+    fn bound_plot(&self) -> String {
+        let points = self
+            .evaluations
+            .iter()
+            .filter_map(|eval| eval.bound.map(|bound| (bound, eval.runtime)))
+            .collect::<Vec<_>>();
+        let values = points.iter().flat_map(|&(x, y)| vec![x, y]);
+        let axis = Axis::fit("", true, values);
+        let mut plot = Plot::new(
+            Axis {
+                label: "bound (ns)",
+                ..axis
+            },
+            Axis {
+                label: "runtime (ns)",
+                ..axis
+            },
+        );
+        plot.line(&[(axis.min, axis.min), (axis.max, axis.max)], "#999999");
+        plot.scatter(&points, "#8888cc");
+        plot.finish()
+    }
+}
+
+/// An axis of a plot.
+#[derive(Clone, Copy, Debug)]
+struct Axis {
+    label: &'static str,
+    /// Indicates if the axis has a logarithmic scale.
+    log: bool,
+    min: f64,
+    max: f64,
+}
+
+impl Axis {
+    /// Creates an axis that spans `values`.
+    fn fit(label: &'static str, log: bool, values: impl Iterator<Item = f64>) -> Self {
+        let values = values.filter(|&v| v.is_finite() && (!log || v > 0.));
+        let (min, max) = values.fold((std::f64::INFINITY, 0f64), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        let (min, max) = match (min.is_finite(), log) {
+            (false, _) => (1., 10.),
+            (true, true) if min >= max => (min / 2., max * 2.),
+            (true, false) if min >= max => (0., max + 1.),
+            (true, true) => (min, max),
+            (true, false) => (0f64.min(min), max),
+        };
+        Axis {
+            label,
+            log,
+            min,
+            max,
+        }
+    }
+
+    /// Returns the position of `value` on an axis of length `length`.
+    fn position(&self, value: f64, length: f64) -> f64 {
+        let ratio = if self.log {
+            (value.log10() - self.min.log10()) / (self.max.log10() - self.min.log10())
+        } else {
+            (value - self.min) / (self.max - self.min)
+        };
+        ratio * length
+    }
+}
+
+/// An SVG plot.
+struct Plot {
+    x: Axis,
+    y: Axis,
+    svg: String,
+}
+
+impl Plot {
+    /// Creates a plot with the given axes.
+    fn new(x: Axis, y: Axis) -> Self {
+        let mut svg = String::new();
+        let (width, height) = (
+            PLOT_WIDTH + 2. * PLOT_MARGIN,
+            PLOT_HEIGHT + 2. * PLOT_MARGIN,
+        );
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+            width, height
+        );
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{m}\" y=\"{m}\" width=\"{}\" height=\"{}\" fill=\"none\" \
+             stroke=\"black\"/>",
+            PLOT_WIDTH,
+            PLOT_HEIGHT,
+            m = PLOT_MARGIN
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            PLOT_MARGIN + PLOT_WIDTH / 2.,
+            height - 10.,
+            x.label
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"15\" y=\"{y}\" text-anchor=\"middle\" \
+             transform=\"rotate(-90 15 {y})\">{}</text>",
+            y.label,
+            y = PLOT_MARGIN + PLOT_HEIGHT / 2.
+        );
+        for &(value, anchor) in &[(x.min, "start"), (x.max, "end")] {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"{}\" font-size=\"12\">{}</text>",
+                PLOT_MARGIN + x.position(value, PLOT_WIDTH),
+                PLOT_MARGIN + PLOT_HEIGHT + 15.,
+                anchor,
+                tick(value, x.log)
+            );
+        }
+        for &value in &[y.min, y.max] {
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"end\" font-size=\"12\">{}</text>",
+                PLOT_MARGIN - 5.,
+                PLOT_MARGIN + PLOT_HEIGHT - y.position(value, PLOT_HEIGHT),
+                tick(value, y.log)
+            );
+        }
+        Plot { x, y, svg }
+    }
+
+    /// Returns the coordinates of a point in the SVG.
+    fn coordinates(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            PLOT_MARGIN + self.x.position(x, PLOT_WIDTH),
+            PLOT_MARGIN + PLOT_HEIGHT - self.y.position(y, PLOT_HEIGHT),
+        )
+    }
+
+    /// Draws a point for each of `points`.
+    fn scatter(&mut self, points: &[(f64, f64)], color: &str) {
+        for &point in points {
+            let (x, y) = self.coordinates(point);
+            if x.is_finite() && y.is_finite() {
+                let _ = writeln!(
+                    self.svg,
+                    "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\"/>",
+                    x, y, color
+                );
+            }
+        }
+    }
+
+    /// Draws a line joining `points`.
+    fn line(&mut self, points: &[(f64, f64)], color: &str) {
+        let points = points
+            .iter()
+            .map(|&point| self.coordinates(point))
+            .filter(|&(x, y)| x.is_finite() && y.is_finite())
+            .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let _ = writeln!(
+            self.svg,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>",
+            points, color
+        );
+    }
+
+    /// Returns the SVG code of the plot.
+    fn finish(mut self) -> String {
+        self.svg.push_str("</svg>");
+        self.svg
+    }
+}
+
+/// Formats the label of a tick.
+fn tick(value: f64, log: bool) -> String {
+    if log {
+        format!("{:.2e}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+/// Converts a duration in seconds.
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) * 1e-9
+}
+
+/// Escapes the special characters of HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; } \
+                     table { border-collapse: collapse; } \
+                     td, th { border: 1px solid #ccc; padding: 4px 8px; \
+                     vertical-align: top; text-align: left; } \
+                     code { font-size: 11px; }";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(log: bool, values: &[f64]) -> (f64, f64) {
+        let axis = Axis::fit("", log, values.iter().cloned());
+        (axis.min, axis.max)
+    }
+
+    /// Ensures linear axes start at zero unless they span negative values.
+    #[test]
+    fn fit_linear_axis() {
+        assert_eq!(bounds(false, &[2., 3.]), (0., 3.));
+        assert_eq!(bounds(false, &[-2., 3.]), (-2., 3.));
+        assert_eq!(bounds(false, &[5., 5.]), (0., 6.));
+        assert_eq!(
+            bounds(false, &[1., 4., std::f64::NAN, std::f64::INFINITY]),
+            (0., 4.)
+        );
+    }
+
+    /// Ensures logarithmic axes ignore values they cannot display.
+    #[test]
+    fn fit_log_axis() {
+        assert_eq!(bounds(true, &[10., 100.]), (10., 100.));
+        assert_eq!(
+            bounds(true, &[0., -1., 10., 100., std::f64::INFINITY]),
+            (10., 100.)
+        );
+        assert_eq!(bounds(true, &[5.]), (2.5, 10.));
+    }
+
+    /// Ensures axes without values have a valid range.
+    #[test]
+    fn fit_empty_axis() {
+        assert_eq!(bounds(false, &[]), (1., 10.));
+        assert_eq!(bounds(true, &[0., std::f64::NAN]), (1., 10.));
+    }
+
+    #[test]
+    fn axis_position() {
+        let linear = Axis::fit("", false, vec![0., 10.].into_iter());
+        assert_eq!(linear.position(5., 100.), 50.);
+        let log = Axis::fit("", true, vec![1., 100.].into_iter());
+        assert_eq!(log.position(10., 100.), 50.);
+    }
+}
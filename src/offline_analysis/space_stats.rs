@@ -0,0 +1,222 @@
+//! Statistics on the search space of a kernel, sampled without evaluating any candidate.
+//!
+//! The choice tree is sampled with uniformly random descents.  At each choice, all the
+//! alternatives are applied to count the ones rejected by constraint propagation.  The size
+//! of the space is estimated with Knuth's estimator: the product of the number of valid
+//! alternatives along a descent is an unbiased estimate of the number of implementations.
+use crate::device::Context;
+use crate::explorer::choice::{default_list, ActionEx as Action, Choice};
+use crate::explorer::config::NewNodeOrder;
+use crate::explorer::Candidate;
+use std::collections::BTreeMap;
+
+/// Statistics on the choices of a given kind, accumulated over the descents.
+#[derive(Clone, Debug, Default)]
+pub struct ChoiceKindStats {
+    /// Number of choices of this kind encountered.
+    pub num_choices: usize,
+    /// Number of alternatives of these choices.
+    pub num_alternatives: usize,
+    /// Number of alternatives rejected by constraint propagation.
+    pub num_pruned: usize,
+}
+
+/// A random descent that reached an implementation.
+#[derive(Clone, Copy, Debug)]
+pub struct Descent {
+    /// Number of choices taken.
+    pub depth: usize,
+    /// Base 10 logarithm of the product of the number of valid alternatives.
+    pub log_size: f64,
+}
+
+/// Statistics on the search space of a kernel.
+#[derive(Clone, Debug, Default)]
+pub struct SpaceStats {
+    /// Number of descents sampled, including the ones that reached a dead-end.
+    pub num_descents: usize,
+    /// The descents that reached an implementation.
+    pub descents: Vec<Descent>,
+    /// Statistics on the choices encountered, by kind of choice.
+    pub choices: BTreeMap<String, ChoiceKindStats>,
+}
+
+impl SpaceStats {
+    /// Samples `num_descents` random descents from `root`.
+    pub fn sample(root: &Candidate, context: &dyn Context, num_descents: usize) -> Self {
+        let mut stats = SpaceStats {
+            num_descents,
+            descents: Vec::with_capacity(num_descents),
+            choices: BTreeMap::new(),
+        };
+        for _ in 0..num_descents {
+            if let Some(descent) = stats.random_descent(root, context) {
+                stats.descents.push(descent);
+            }
+        }
+        stats
+    }
+
+    /// Descends to a random implementation and accumulates statistics on the choices
+    /// encountered on the way.  Returns `None` if the descent reached a dead-end.
+    fn random_descent(
+        &mut self,
+        root: &Candidate,
+        context: &dyn Context,
+    ) -> Option<Descent> {
+        let mut candidate = root.clone();
+        let mut descent = Descent {
+            depth: 0,
+            log_size: 0.,
+        };
+        while let Some(choice) = default_list(&candidate.space).next() {
+            let kind_stats = self.choices.entry(choice_kind(&choice)).or_default();
+            let num_alternatives = choice.len();
+            let mut children = choice
+                .into_iter()
+                .filter_map(|action| candidate.apply_decision(context, action).ok())
+                .collect::<Vec<_>>();
+            kind_stats.num_choices += 1;
+            kind_stats.num_alternatives += num_alternatives;
+            kind_stats.num_pruned += num_alternatives - children.len();
+            let idx =
+                NewNodeOrder::Random.pick_candidate(&children, std::f64::INFINITY)?;
+            descent.depth += 1;
+            descent.log_size += (children.len() as f64).log10();
+            candidate = children.swap_remove(idx);
+        }
+        Some(descent)
+    }
+
+    /// Returns the number of descents that reached a dead-end.
+    pub fn num_deadends(&self) -> usize {
+        self.num_descents - self.descents.len()
+    }
+
+    /// Returns the base 10 logarithm of the estimated number of implementations, or `None`
+    /// if no descent reached an implementation.
+    pub fn log_size(&self) -> Option<f64> {
+        if self.descents.is_empty() {
+            return None;
+        }
+        // Knuth's estimator averages the sizes, dead-ends counting as empty spaces.  The
+        // sizes are averaged in the log domain to avoid overflows.
+        let max_log_size = self
+            .descents
+            .iter()
+            .map(|descent| descent.log_size)
+            .fold(std::f64::NEG_INFINITY, f64::max);
+        let sum = self
+            .descents
+            .iter()
+            .map(|descent| 10f64.powf(descent.log_size - max_log_size))
+            .sum::<f64>();
+        Some(max_log_size + (sum / self.num_descents as f64).log10())
+    }
+
+    /// Returns the fraction of the alternatives rejected by constraint propagation, or
+    /// `None` if no choice was encountered.
+    pub fn pruned_ratio(&self) -> Option<f64> {
+        let (num_alternatives, num_pruned) =
+            self.choices
+                .values()
+                .fold((0, 0), |(alternatives, pruned), stats| {
+                    (
+                        alternatives + stats.num_alternatives,
+                        pruned + stats.num_pruned,
+                    )
+                });
+        if num_alternatives == 0 {
+            None
+        } else {
+            Some(num_pruned as f64 / num_alternatives as f64)
+        }
+    }
+}
+
+/// Returns the kind of the actions of a choice, such as `DimKind` or `Order`.
+pub fn choice_kind(choice: &Choice) -> String {
+    match choice.first() {
+        Some(Action::Action(action)) => {
+            let action = format!("{:?}", action);
+            action.split('(').next().unwrap_or_default().to_string()
+        }
+        Some(Action::LowerLayout { .. }) => "LowerLayout".to_string(),
+        None => "Empty".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explorer::choice::ActionEx;
+    use crate::ir;
+    use crate::search_space::{Action, Order};
+
+    fn stats(num_descents: usize, log_sizes: &[f64]) -> SpaceStats {
+        SpaceStats {
+            num_descents,
+            descents: log_sizes
+                .iter()
+                .map(|&log_size| Descent { depth: 1, log_size })
+                .collect(),
+            choices: BTreeMap::new(),
+        }
+    }
+
+    /// Dead-ends count as empty spaces in the estimation of the size.
+    #[test]
+    fn log_size_with_deadends() {
+        let stats = stats(4, &[2., 2.]);
+        assert_eq!(stats.num_deadends(), 2);
+        assert!((stats.log_size().unwrap() - 50f64.log10()).abs() < 1e-9);
+    }
+
+    /// Large sizes do not overflow.
+    #[test]
+    fn log_size_large() {
+        let stats = stats(2, &[400., 400.]);
+        assert!((stats.log_size().unwrap() - 400.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn log_size_only_deadends() {
+        let stats = stats(3, &[]);
+        assert_eq!(stats.num_deadends(), 3);
+        assert!(stats.log_size().is_none());
+        assert!(stats.pruned_ratio().is_none());
+    }
+
+    #[test]
+    fn pruned_ratio() {
+        let mut stats = stats(1, &[0.]);
+        stats.choices.insert(
+            "Order".to_string(),
+            ChoiceKindStats {
+                num_choices: 2,
+                num_alternatives: 6,
+                num_pruned: 3,
+            },
+        );
+        stats.choices.insert(
+            "DimKind".to_string(),
+            ChoiceKindStats {
+                num_choices: 1,
+                num_alternatives: 2,
+                num_pruned: 1,
+            },
+        );
+        assert!((stats.pruned_ratio().unwrap() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn choice_kinds() {
+        let order = Action::Order(
+            ir::StmtId::from(ir::InstId(0)),
+            ir::StmtId::from(ir::InstId(1)),
+            Order::BEFORE,
+        );
+        assert_eq!(choice_kind(&vec![ActionEx::Action(order)]), "Order");
+        assert_eq!(choice_kind(&vec![]), "Empty");
+    }
+}
@@ -1,11 +1,14 @@
 ///! Data structures and function that allow for the recreation of a
 ///! candidate tree from a log file
 use crate::explorer::choice::ActionEx as Action;
-use crate::explorer::mcts::{EdgeIndex, NodeId};
+use crate::explorer::eventlog::EventLog;
+use crate::explorer::mcts::{EdgeIndex, Message, NodeId};
 use crate::model::Bound;
 use fxhash::FxHashMap;
 use std::borrow::Cow;
 use std::cell::{Ref, RefCell};
+use std::io;
+use std::path::Path;
 use std::rc::{Rc, Weak};
 use std::time::Duration;
 
@@ -64,6 +67,9 @@ struct CandidateNodeInner {
 
     /// Score from the evaluation
     score: Option<f64>,
+
+    /// Number of traces that went through this node
+    visits: usize,
 }
 
 trait ReplaceDurationIfLower {
@@ -124,6 +130,11 @@ impl CandidateNode {
         self.inner.borrow().score
     }
 
+    /// Returns the number of traces that went through this node
+    pub fn visits(&self) -> usize {
+        self.inner.borrow().visits
+    }
+
     /// Returns the parent node or None if this is the root node
     pub fn parent(&self) -> Option<CandidateNode> {
         self.inner
@@ -187,9 +198,9 @@ impl CandidateNode {
         self.inner.borrow().id
     }
 
-    /// Indicates whether this is the virtual root node
+    /// Indicates whether this is a root node
     pub fn is_root(&self) -> bool {
-        u64::from(self.id()) == 0
+        self.inner.borrow().incoming_edge.is_none()
     }
 
     /// Returns the action associated to the edge from the parent of
@@ -308,6 +319,11 @@ impl CandidateNode {
             .replace_if_lower(timestamp);
     }
 
+    /// Records that a trace went through this node
+    pub fn add_visit(&mut self) {
+        self.inner.borrow_mut().visits += 1;
+    }
+
     /// Sets the score from an evaluation
     ///
     /// # Panics
@@ -322,11 +338,12 @@ impl CandidateNode {
     }
 }
 
-/// A reconstructed tree
+/// A reconstructed tree.  The tree may have several roots, when the search explored
+/// several root candidates or when the log merges several searches.
 #[derive(Default)]
 pub struct CandidateTree {
-    /// Virtual root node of the reconstructed tree
-    root: Option<Weak<RefCell<CandidateNodeInner>>>,
+    /// Root nodes of the reconstructed tree, in the order they were created
+    roots: Vec<Weak<RefCell<CandidateNodeInner>>>,
 
     /// Mapping node ID -> Candidate nodes
     nodes: FxHashMap<NodeId, Rc<RefCell<CandidateNodeInner>>>,
@@ -367,19 +384,28 @@ impl CandidateTree {
                 .collect(),
             id: node_id,
             score: None,
+            visits: 0,
         }
     }
 
-    /// Returns the root node
+    /// Returns the first root node.  Traces implicitly start from this node unless they
+    /// start by selecting another node.
     ///
     /// # Panics
     /// Panics if no root has been created beforehand
     pub fn get_root(&self) -> CandidateNode {
         CandidateNode {
-            inner: self.root.as_ref().unwrap().upgrade().unwrap(),
+            inner: self.roots[0].upgrade().unwrap(),
         }
     }
 
+    /// Returns the root nodes, in the order they were created
+    pub fn roots(&self) -> impl Iterator<Item = CandidateNode> + '_ {
+        self.roots.iter().map(|root| CandidateNode {
+            inner: root.upgrade().unwrap(),
+        })
+    }
+
     /// Returns the node with the given `id`
     ///
     /// # Panics
@@ -397,24 +423,9 @@ impl CandidateTree {
         self.nodes.contains_key(&id)
     }
 
-    /// Sets the root node to `root`
-    ///
-    /// # Panics
-    /// Panics if the root has been set beforehand or if the root's ID is not 0.
-    fn set_root(&mut self, new_root: Weak<RefCell<CandidateNodeInner>>) {
-        let new_id = new_root.upgrade().unwrap().borrow().id;
-
-        assert!(self.root.is_none(),
-                "Attempting to add second root node with id {}, but already set to node with id {}",
-                new_id,
-                self.root.as_ref().unwrap().upgrade().unwrap().borrow().id);
-
-        assert!(
-            u64::from(new_id) == 0,
-            "Attempting to add root node with an ID != 0"
-        );
-
-        self.root = Some(new_root);
+    /// Adds `new_root` to the root nodes
+    fn add_root(&mut self, new_root: Weak<RefCell<CandidateNodeInner>>) {
+        self.roots.push(new_root);
     }
 
     /// Adds a new mapping from the given `id` to the node `n`
@@ -440,8 +451,8 @@ impl CandidateTree {
     /// its children and may be empty if this node does not have any
     /// children in the final recreated tree.
     ///
-    /// Automatically sets the root of the tree to the newly created
-    /// node if `parent` is None.
+    /// Automatically adds the newly created node to the roots of the
+    /// tree if `parent` is None.
     ///
     /// # Panics
     /// Panics If `parent` is not None and the ID provided for the
@@ -479,9 +490,36 @@ impl CandidateTree {
                 parent_out_edge.child = Some(Rc::downgrade(&new_node));
             }
         } else {
-            self.set_root(Rc::downgrade(&new_node));
+            self.add_root(Rc::downgrade(&new_node));
         }
 
         self.add_node_mapping(node_id, new_node);
     }
+
+    /// Reads the messages of an eventlog in order, and calls `f` with each of them.  The
+    /// node described by a `Node` message is added to the tree before the message is
+    /// passed to `f`, at which point the message no longer holds the actions of the
+    /// children.
+    pub fn replay_eventlog<P, F>(&mut self, path: P, mut f: F) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&CandidateTree, Message) -> io::Result<()>,
+    {
+        for record_bytes in EventLog::open(path)?.records() {
+            let mut message: Message = bincode::deserialize(&record_bytes?)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            if let Message::Node {
+                id,
+                parent,
+                children,
+                bound,
+                discovery_time,
+            } = &mut message
+            {
+                self.extend(*id, *discovery_time, *parent, bound.clone(), children);
+            }
+            f(self, message)?;
+        }
+        Ok(())
+    }
 }
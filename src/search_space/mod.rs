@@ -10,15 +10,16 @@ use std::sync::Arc;
 
 mod dim_map;
 mod operand;
+pub mod trace;
 use utils::generated_file;
 generated_file!(choices);
 
 pub use self::choices::{
     Action, Bool, Choice, DimKind, Domain, DomainStore, InstFlag, MemSpace, NumSet,
-    Order, ThreadMapping,
+    Order, Precision, ThreadMapping,
 };
 
-use self::choices::{apply_action, init_domain, DomainDiff};
+use self::choices::{apply_action, init_domain_with, DomainDiff};
 
 /// A partially specified implementation.
 #[derive(Clone)]
@@ -29,9 +30,27 @@ pub struct SearchSpace {
 
 impl SearchSpace {
     /// Creates a new `SearchSpace` for the given `ir_instance`.
-    pub fn new(
+    pub fn new(ir_instance: ir::Function<()>, actions: Vec<Action>) -> Result<Self, ()> {
+        Self::with_filtering(ir_instance, actions, true)
+    }
+
+    /// Same as `new`, but computes the filters of the initial domain sequentially even if
+    /// the `parallel_propagation` feature is enabled.  This is used to check that both
+    /// filtering modes compute the same domain.
+    #[cfg(feature = "parallel_propagation")]
+    pub fn new_sequential(
+        ir_instance: ir::Function<()>,
+        actions: Vec<Action>,
+    ) -> Result<Self, ()> {
+        Self::with_filtering(ir_instance, actions, false)
+    }
+
+    /// Creates a new `SearchSpace`, filtering the initial domain in parallel if `parallel`
+    /// is true and the `parallel_propagation` feature is enabled.
+    fn with_filtering(
         ir_instance: ir::Function<()>,
         mut actions: Vec<Action>,
+        parallel: bool,
     ) -> Result<Self, ()> {
         // Pre-allocate IDs for future lowerings.
         let mut ir_instance = ir_instance.freeze();
@@ -45,7 +64,7 @@ impl SearchSpace {
         for action in actions {
             apply_action(action, &mut domain, &mut unused_diff)?;
         }
-        let actions = init_domain(&mut domain, &mut ir_instance)?;
+        let actions = init_domain_with(&mut domain, &mut ir_instance, parallel)?;
         let mut space = SearchSpace {
             ir_instance: Arc::new(ir_instance),
             domain,
@@ -71,16 +90,28 @@ impl SearchSpace {
 
     /// Applies a list of decisions to the domain and propagate constraints.
     pub fn apply_decisions(&mut self, actions: Vec<Action>) -> Result<(), ()> {
-        choices::apply_decisions(actions, &mut self.ir_instance, &mut self.domain)
+        let res =
+            choices::apply_decisions(actions, &mut self.ir_instance, &mut self.domain);
+        #[cfg(feature = "trace_propagation")]
+        trace::flush();
+        res
     }
 
     /// Triggers a layout lowering.
+    ///
+    /// Fails if the layout of `mem` is not ready to be lowered, or if `st_dims` and `ld_dims`
+    /// are not an ordering of the dimensions mapped by the layout.  This happens when replaying
+    /// a lowering on an incompatible search space.
     pub fn lower_layout(
         &mut self,
         mem: ir::MemId,
         st_dims: &[ir::DimId],
         ld_dims: &[ir::DimId],
     ) -> Result<(), ()> {
+        if !self.is_valid_layout(mem, st_dims, ld_dims) {
+            debug!("invalid layout lowering for {:?}", mem);
+            return Err(());
+        }
         let actions = {
             let ir_instance = Arc::make_mut(&mut self.ir_instance);
             dim_map::lower_layout(ir_instance, mem, st_dims, ld_dims, &self.domain)?
@@ -88,6 +119,25 @@ impl SearchSpace {
         self.apply_decisions(actions)
     }
 
+    /// Indicates if `st_dims` and `ld_dims` describe a valid lowering of the layout of `mem`.
+    fn is_valid_layout(
+        &self,
+        mem: ir::MemId,
+        st_dims: &[ir::DimId],
+        ld_dims: &[ir::DimId],
+    ) -> bool {
+        if !self.ir_instance.layouts_to_lower().contains(&mem) {
+            return false;
+        }
+        let mapped_dims = self.ir_instance.mem_block(mem).mapped_dims();
+        st_dims.len() == mapped_dims.len()
+            && ld_dims.len() == mapped_dims.len()
+            && st_dims
+                .iter()
+                .zip(ld_dims)
+                .all(|(&st, &ld)| mapped_dims.contains(&(st, ld)))
+    }
+
     /// Dump the code associated with this candidate.
     pub fn dump_code<P: AsRef<Path>>(
         &self,
@@ -0,0 +1,284 @@
+//! Traces of the constraint propagation.
+//!
+//! When the `trace_propagation` feature is enabled, the generated propagation code records
+//! the actions it applies, the `on_change` callbacks it runs and the filters it fires.  The
+//! events are appended to the file named by the `TELAMON_PROPAGATION_TRACE` environment
+//! variable, as a sequence of bincode-encoded `Event`s.  Nothing is recorded if the variable
+//! is not set.
+//!
+//! `Cascade::build` reconstructs from a trace the propagation each action triggered.
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::{fmt, fs, path::Path};
+
+/// Name of the environment variable that holds the path of the trace.
+pub const TRACE_VAR: &str = "TELAMON_PROPAGATION_TRACE";
+
+/// An event of the propagation.  Arguments and values are stored in their `Debug` format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    /// An action restricts the domain of a choice.
+    Action {
+        thread: usize,
+        choice: String,
+        args: String,
+        value: String,
+    },
+    /// The `on_change` callback of a choice starts.
+    OnChange {
+        thread: usize,
+        choice: String,
+        args: String,
+        old: String,
+        new: String,
+    },
+    /// The last `on_change` callback started by the thread returns.
+    EndOnChange { thread: usize },
+    /// A filter restricts the domain of a choice.
+    Filter {
+        thread: usize,
+        choice: String,
+        args: String,
+        values: String,
+    },
+}
+
+impl Event {
+    /// Returns the identifier of the thread that recorded the event.
+    pub fn thread(&self) -> usize {
+        match *self {
+            Event::Action { thread, .. }
+            | Event::OnChange { thread, .. }
+            | Event::EndOnChange { thread }
+            | Event::Filter { thread, .. } => thread,
+        }
+    }
+
+    /// Returns the instance of the choice the event relates to, if any.
+    fn choice_instance(&self) -> Option<(&str, &str)> {
+        match self {
+            Event::Action { choice, args, .. }
+            | Event::OnChange { choice, args, .. }
+            | Event::Filter { choice, args, .. } => Some((choice, args)),
+            Event::EndOnChange { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Action {
+                choice,
+                args,
+                value,
+                ..
+            } => write!(fmt, "action {}{} = {}", choice, args, value),
+            Event::OnChange {
+                choice,
+                args,
+                old,
+                new,
+                ..
+            } => write!(fmt, "on_change {}{}: {} -> {}", choice, args, old, new),
+            Event::EndOnChange { .. } => write!(fmt, "end of on_change"),
+            Event::Filter {
+                choice,
+                args,
+                values,
+                ..
+            } => write!(fmt, "filter {}{} to {}", choice, args, values),
+        }
+    }
+}
+
+/// Reads all the events of a trace.  A truncated last event is ignored.
+pub fn read_trace<P: AsRef<Path>>(path: P) -> io::Result<Vec<Event>> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    let mut events = Vec::new();
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(event) => events.push(event),
+            Err(err) => match *err {
+                bincode::ErrorKind::Io(ref io_err)
+                    if io_err.kind() == io::ErrorKind::UnexpectedEof =>
+                {
+                    break
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            },
+        }
+    }
+    Ok(events)
+}
+
+/// The events caused by an event of the propagation.
+#[derive(Debug, Clone)]
+pub struct Cascade {
+    pub event: Event,
+    pub children: Vec<Cascade>,
+}
+
+impl Cascade {
+    /// Reconstructs the propagation cascades from the events of a trace.  Each root is an
+    /// event that was not caused by the propagation, usually an action applied by the
+    /// explorer.
+    ///
+    /// An `on_change` callback is attributed to the last action or filter of the same thread
+    /// that restricted the same choice instance, and the events recorded while the callback
+    /// runs are attributed to the callback.
+    pub fn build(events: &[Event]) -> Vec<Cascade> {
+        let mut parents = vec![None; events.len()];
+        let mut open_callbacks: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+        let mut last_restrict = FxHashMap::default();
+        for (id, event) in events.iter().enumerate() {
+            let thread = event.thread();
+            let stack = open_callbacks.entry(thread).or_insert_with(Vec::new);
+            match event {
+                Event::EndOnChange { .. } => {
+                    stack.pop();
+                    continue;
+                }
+                Event::OnChange { .. } => {
+                    let instance = event.choice_instance();
+                    parents[id] = last_restrict.get(&(thread, instance)).cloned();
+                    stack.push(id);
+                }
+                Event::Action { .. } | Event::Filter { .. } => {
+                    parents[id] = stack.last().cloned();
+                    last_restrict.insert((thread, event.choice_instance()), id);
+                }
+            }
+        }
+        let mut children = vec![vec![]; events.len()];
+        let mut roots = vec![];
+        for (id, event) in events.iter().enumerate() {
+            if let Event::EndOnChange { .. } = event {
+                continue;
+            }
+            match parents[id] {
+                Some(parent) => children[parent].push(id),
+                None => roots.push(id),
+            }
+        }
+        roots
+            .into_iter()
+            .map(|id| Self::from_ids(id, events, &children))
+            .collect()
+    }
+
+    fn from_ids(id: usize, events: &[Event], children: &[Vec<usize>]) -> Self {
+        Cascade {
+            event: events[id].clone(),
+            children: children[id]
+                .iter()
+                .map(|&child| Self::from_ids(child, events, children))
+                .collect(),
+        }
+    }
+
+    /// Returns the number of events in the cascade, including its root.
+    pub fn num_events(&self) -> usize {
+        1 + self.children.iter().map(Cascade::num_events).sum::<usize>()
+    }
+
+    /// Writes the cascade, indenting events by their depth.
+    pub fn write<W: io::Write>(&self, out: &mut W, depth: usize) -> io::Result<()> {
+        writeln!(out, "{:indent$}{}", "", self.event, indent = 2 * depth)?;
+        for child in &self.children {
+            child.write(out, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "trace_propagation")]
+pub use self::recorder::*;
+
+/// Records events from the generated code.
+#[cfg(feature = "trace_propagation")]
+mod recorder {
+    use super::{Event, TRACE_VAR};
+    use lazy_static::lazy_static;
+    use parking_lot::Mutex;
+    use std::fmt::Debug;
+    use std::io::{BufWriter, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use utils::unwrap;
+
+    lazy_static! {
+        static ref WRITER: Option<Mutex<BufWriter<std::fs::File>>> =
+            std::env::var_os(TRACE_VAR).map(|path| {
+                let file = unwrap!(std::fs::File::create(path));
+                Mutex::new(BufWriter::new(file))
+            });
+    }
+
+    static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        static THREAD_ID: usize = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record<F: FnOnce(usize) -> Event>(event: F) {
+        if let Some(writer) = &*WRITER {
+            let event = event(THREAD_ID.with(|id| *id));
+            unwrap!(bincode::serialize_into(&mut *writer.lock(), &event));
+        }
+    }
+
+    /// Records that an action restricts `choice`.
+    pub fn action(choice: &str, args: &dyn Debug, value: &dyn Debug) {
+        record(|thread| Event::Action {
+            thread,
+            choice: choice.to_string(),
+            args: format!("{:?}", args),
+            value: format!("{:?}", value),
+        })
+    }
+
+    /// Records that a filter restricts `choice`.
+    pub fn filter(choice: &str, args: &dyn Debug, values: &dyn Debug) {
+        record(|thread| Event::Filter {
+            thread,
+            choice: choice.to_string(),
+            args: format!("{:?}", args),
+            values: format!("{:?}", values),
+        })
+    }
+
+    /// Records the start of an `on_change` callback.  The end of the callback is recorded
+    /// when the returned guard is dropped.
+    pub fn on_change(
+        choice: &str,
+        args: &dyn Debug,
+        old: &dyn Debug,
+        new: &dyn Debug,
+    ) -> OnChangeGuard {
+        record(|thread| Event::OnChange {
+            thread,
+            choice: choice.to_string(),
+            args: format!("{:?}", args),
+            old: format!("{:?}", old),
+            new: format!("{:?}", new),
+        });
+        OnChangeGuard(())
+    }
+
+    /// Records the end of an `on_change` callback when dropped.
+    pub struct OnChangeGuard(());
+
+    impl Drop for OnChangeGuard {
+        fn drop(&mut self) {
+            record(|thread| Event::EndOnChange { thread })
+        }
+    }
+
+    /// Writes the buffered events to the trace.
+    pub fn flush() {
+        if let Some(writer) = &*WRITER {
+            unwrap!(writer.lock().flush());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Stable entry points to Telamon.
+//!
+//! This crate re-exports the subset of Telamon needed to describe kernels, search for
+//! their best implementation and replay the decisions leading to it. The modules of the
+//! `telamon` crate change freely: `search_space::choices`, for instance, is generated from
+//! the constraints description and changes with every edit of it. The items exported here
+//! follow semantic versioning instead, and are only removed or changed in incompatible
+//! ways with a new major version of this crate. `tests/api.rs` names each of them so that
+//! such a change cannot go unnoticed.
+//!
+//! New options and variants are added to `Config` and the enums it contains in minor
+//! versions. A `Config` should thus be created from the default one, for instance with
+//! `Config { ..Default::default() }`, or parsed from a file, and matches on its enums
+//! should have a wildcard arm.
+//!
+//! A typical search builds a kernel with a `KernelBuilder`, binds its arguments to a
+//! context of one of the backends, and passes the resulting search space to `find_best`:
+//!
+//! ```no_run
+//! # #[cfg(feature = "x86")] {
+//! use std::sync::Arc;
+//! use telamon_api::{find_best, linalg, x86, Config, Kernel, KernelBuilder};
+//!
+//! let mut context = x86::Context::default();
+//! let params = linalg::FusedMMP::new(256, 256, 256);
+//! let (signature, kernel, context) = KernelBuilder::default()
+//!     .build::<linalg::FusedMM<f32>, _>(params, &mut context);
+//! let candidates = kernel.build_body(Arc::new(signature), context);
+//! let spaces = candidates.into_iter().map(|c| c.space).collect();
+//! let best = find_best(&Config::default(), context, spaces, None);
+//! # }
+//! ```
+#![deny(missing_docs, bare_trait_objects)]
+
+pub use telamon::device::{
+    ArgMap, ArgMapExt, ArrayArgument, ArrayArgumentExt, Context, Device, EvalMode,
+    ScalarArgument,
+};
+pub use telamon::explorer::{
+    find_best, find_best_ex, Candidate, CheckResultFn, Config, Objective, SearchAlgorithm,
+};
+pub use telamon::helper::MemInit;
+pub use telamon::search_space::SearchSpace;
+pub use telamon_kernels::{linalg, Kernel, KernelBuilder, ParamsError, Scalar};
+
+/// Records and replays the decisions leading to an implementation, and the arguments it
+/// was evaluated with.
+pub mod replay {
+    pub use telamon::device::recorder::{
+        ArrayBinding, Manifest, ManifestError, Recorder,
+    };
+    pub use telamon::explorer::choice::{ActionError, ActionEx as Action};
+
+    use std::fs::File;
+    use std::io::{self, BufReader};
+    use std::path::Path;
+
+    use crate::SearchSpace;
+
+    /// Loads the actions saved in JSON at `path`, in the format written by the debugger
+    /// and by `tlcli`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Action>> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    /// Applies `actions` to `space`, in order.
+    pub fn apply(
+        space: SearchSpace,
+        actions: &[Action],
+    ) -> Result<SearchSpace, ActionError> {
+        actions
+            .iter()
+            .try_fold(space, |space, action| action.apply_to(space))
+    }
+}
+
+/// Evaluation contexts for x86 CPUs. `Context::default()` creates a context for the host.
+#[cfg(feature = "x86")]
+pub mod x86 {
+    pub use telamon_x86::{Compiler, Context, Cpu};
+}
+
+/// Evaluation contexts for CUDA GPUs. A `Context` is created with `Context::new` from an
+/// `Executor`, itself created with `Executor::init`.
+#[cfg(feature = "cuda")]
+pub mod cuda {
+    pub use telamon_cuda::{Context, Executor};
+}
@@ -0,0 +1,173 @@
+//! Names each item of the stable API, so that removing one of them or changing its
+//! signature breaks the build of this test.
+use std::io;
+use std::path::Path;
+
+use telamon::device::fake::{Context as FakeContext, Device as FakeDevice};
+use telamon_api::replay::{self, Action, ActionError, Recorder};
+use telamon_api::*;
+
+#[test]
+fn functions() {
+    let _: fn(
+        &Config,
+        &dyn Context,
+        Vec<SearchSpace>,
+        Option<&CheckResultFn<'_>>,
+    ) -> Option<SearchSpace> = find_best;
+    let _: fn(
+        &Config,
+        &dyn Context,
+        Vec<Candidate>,
+        Option<&CheckResultFn<'_>>,
+        &[Vec<Action>],
+    ) -> Option<Candidate> = find_best_ex;
+    let _: fn(&Path) -> io::Result<Vec<Action>> = replay::load::<&Path>;
+    let _: fn(SearchSpace, &[Action]) -> Result<SearchSpace, ActionError> = replay::apply;
+}
+
+/// Names each field of the configuration with its type.  Fields can be added in minor
+/// versions, but not removed, renamed nor changed.
+#[test]
+fn config_fields() {
+    use telamon::explorer::config::{
+        EnsembleConfig, LoggingConfig, RefineConfig, ScreeningConfig, StopConditions,
+    };
+
+    let Config {
+        output_dir,
+        log_file,
+        event_log,
+        log_code,
+        checkpoint_file,
+        checkpoint_interval,
+        resume,
+        log_channel_size,
+        log_buffer_size,
+        log_flush_interval,
+        monitor_channel_size,
+        num_workers,
+        num_compilers,
+        seed,
+        stop_bound,
+        check_all,
+        check_counters,
+        timeout,
+        max_evaluations,
+        distance_to_best,
+        restart_every_n_evals,
+        objective,
+        dry_run,
+        restrict_sizes,
+        coordinator,
+        metrics_address,
+        tuning_cache,
+        screening,
+        stop_conditions,
+        ensemble,
+        refine,
+        logging,
+        algorithm,
+        ..
+    } = Config::default();
+    let _: [String; 2] = [output_dir, log_file];
+    let _: [Option<String>; 6] = [
+        event_log,
+        checkpoint_file,
+        resume,
+        coordinator,
+        metrics_address,
+        tuning_cache,
+    ];
+    let _: [bool; 5] = [log_code, check_all, check_counters, dry_run, restrict_sizes];
+    let _: [usize; 6] = [
+        checkpoint_interval,
+        log_channel_size,
+        log_buffer_size,
+        monitor_channel_size,
+        num_workers,
+        num_compilers,
+    ];
+    let _: f64 = log_flush_interval;
+    let _: [Option<u64>; 2] = [seed, timeout];
+    let _: [Option<f64>; 2] = [stop_bound, distance_to_best];
+    let _: [Option<usize>; 2] = [max_evaluations, restart_every_n_evals];
+    let _: Objective = objective;
+    let _: Option<ScreeningConfig> = screening;
+    let _: StopConditions = stop_conditions;
+    let _: Option<EnsembleConfig> = ensemble;
+    let _: Option<RefineConfig> = refine;
+    let _: LoggingConfig = logging;
+    let _: SearchAlgorithm = algorithm;
+}
+
+/// Ensures configuration files using the stable option names can still be parsed.
+#[test]
+fn config_file() {
+    let config: Config = serde_json::from_str(
+        r#"{
+            "output_dir": "out",
+            "log_file": "watch.log",
+            "event_log": "eventlog.tfrecord.gz",
+            "num_workers": 2,
+            "seed": 42,
+            "timeout": 10,
+            "max_evaluations": 100,
+            "objective": "edp",
+            "algorithm": { "type": "bound_order" }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(config.output_dir, "out");
+    assert_eq!(config.num_workers, 2);
+    assert_eq!(config.seed, Some(42));
+    assert_eq!(config.objective, Objective::Edp);
+    match config.algorithm {
+        SearchAlgorithm::BoundOrder => (),
+        _ => panic!("unexpected search algorithm"),
+    }
+}
+
+#[test]
+fn types() {
+    let _ = [Objective::Time, Objective::Energy, Objective::Edp];
+    let _ = SearchAlgorithm::BoundOrder;
+    let _ = [MemInit::RandomFill, MemInit::Uninit];
+    let _ = [EvalMode::FindBest, EvalMode::TestEval, EvalMode::TestBound];
+    let _ = KernelBuilder::new().mem_init(MemInit::Uninit);
+    let _ = replay::Manifest::default();
+    let _: Option<ParamsError> = None;
+    let _: Option<replay::ManifestError> = None;
+    let _: Option<replay::ArrayBinding> = None;
+}
+
+#[test]
+fn traits() {
+    fn kernel<'a, K: Kernel<'a>>() {}
+    fn context<C: Context>() {}
+    fn arg_map<'a, A: ArgMap<'a> + ArgMapExt<'a>>() {}
+    fn scalar<S: Scalar + ScalarArgument>() {}
+    fn device<D: Device>() {}
+    fn array<A: ArrayArgument + ArrayArgumentExt + ?Sized>() {}
+
+    kernel::<linalg::FusedMM<f32>>();
+    scalar::<f32>();
+    array::<dyn ArrayArgument>();
+    context::<Recorder<'static, FakeContext>>();
+    arg_map::<Recorder<'static, FakeContext>>();
+    device::<FakeDevice>();
+}
+
+#[cfg(feature = "x86")]
+#[test]
+fn x86_context() {
+    let _: x86::Context = Default::default();
+    let _: fn(x86::Compiler) -> x86::Context = x86::Context::with_compiler;
+    let _ = std::marker::PhantomData::<x86::Cpu>;
+}
+
+#[cfg(feature = "cuda")]
+#[test]
+fn cuda_context() {
+    let _: for<'a> fn(&'a cuda::Executor) -> cuda::Context<'a> = cuda::Context::new;
+}
@@ -59,6 +59,14 @@ typedef enum {
     DeviceId_Cuda,
 } DeviceId;
 
+/*
+ * Types of the elements kernels operate on.
+ */
+typedef enum {
+    ElementType_F32,
+    ElementType_F64,
+} ElementType;
+
 /*
  * The rounding mode of an arithmetic operation.
  */
@@ -93,6 +101,11 @@ typedef enum {
     TelamonStatus_Fail,
 } TelamonStatus;
 
+/*
+ * Opaque type that wraps the builder of a function.
+ */
+typedef struct Builder Builder;
+
 /*
  * Stores the configuration of the exploration.
  */
@@ -124,6 +137,12 @@ typedef struct Function Function;
  */
 typedef struct KernelParameters KernelParameters;
 
+/*
+ * Opaque type that represents a logical dimension: a dimension along with the dimensions
+ * that implement its tiling.
+ */
+typedef struct LogicalDim LogicalDim;
+
 /*
  * Opaque type that abstracts away the lifetime parameter of `ir::Operand` so that
  * cbindgen can generate bindings.
@@ -136,6 +155,11 @@ typedef struct Operand Operand;
  */
 typedef struct Operator Operator;
 
+/*
+ * A search started by `kernel_optimize_async`.
+ */
+typedef struct OptimizeTask OptimizeTask;
+
 /*
  * Represents an argument of a function.
  */
@@ -171,6 +195,12 @@ typedef struct String String;
  */
 typedef struct Type Type;
 
+/*
+ * Opaque type that holds a CPU evaluation context, which maps the parameters of kernels
+ * to actual values.
+ */
+typedef struct X86Environment X86Environment;
+
 /*
  * Provides a unique identifier for iteration dimensions.
  */
@@ -509,11 +539,60 @@ typedef struct {
     };
 } Action;
 
+/*
+ * Called by `kernel_optimize_async` each time the search finds a better
+ * implementation, with the `user_data` pointer given to `kernel_optimize_async`,
+ * the runtime of the implementation in nanoseconds and the actions leading to it,
+ * in the order they were applied, as a JSON-encoded string. The string is only
+ * valid during the call. The search stops if the callback returns `false`.
+ */
+typedef bool (*NewBestCallback)(void *user_data, double runtime, const char *actions);
+
 /*
  * Initializes the logger.
  */
 void env_logger_try_init(void);
 
+/*
+ * Instanciate a new kernel computing `z = alpha*x + y` on vectors of size
+ * `n`. The caller is responsible for deallocating the returned pointer using
+ * kernel_free. If `generic` is set, the size is passed as a parameter of the
+ * generated code instead of being a constant.
+ */
+KernelParameters *kernel_axpy_new(int n, int generic);
+
+/*
+ * Instanciate a new kernel computing `batch` matrix-matrix multiplications
+ * `C[i] = A[i].B[i]`, where `A[i]` is `m x k` and `B[i]` is `k x n`. If
+ * `batch_b` is not set, the same `B` matrix is used for the whole batch. The
+ * caller is responsible for deallocating the returned pointer using
+ * kernel_free.
+ */
+KernelParameters *kernel_batchmm_new(int batch,
+                                     int m,
+                                     int n,
+                                     int k,
+                                     int transpose_a,
+                                     int transpose_b,
+                                     int batch_b,
+                                     int generic);
+
+/*
+ * Returns the code generated for the best implementation found by the last
+ * call to `kernel_optimize`, as a null-terminated string. Returns null if no
+ * implementation was found or if the device cannot export its code. The
+ * string is owned by `params` and is only valid until the next call to
+ * `kernel_optimize` or `kernel_free` on `params`.
+ */
+const char *kernel_best_code(const KernelParameters *params);
+
+/*
+ * Writes in `runtime` the execution time, in nanoseconds, of the best
+ * implementation found by the last call to `kernel_optimize`. Returns `false`
+ * and leaves `runtime` untouched if no implementation was found.
+ */
+bool kernel_best_runtime(const KernelParameters *params, double *runtime);
+
 /*
  * Deallocates kernel parameters created through one of the `kernel_*_new`
  * functions. The `params` pointer becomes invalid and must not be used again
@@ -521,6 +600,42 @@ void env_logger_try_init(void);
  */
 void kernel_free(KernelParameters *params);
 
+/*
+ * Instanciate a new kernel computing `E = alpha*A.B.C + beta*D` with two
+ * fused matrix-matrix multiplications, where `A` is `m x k`, `B` is `k x n`,
+ * `C` is `n x p` and `D` is `m x p`. The caller is responsible for
+ * deallocating the returned pointer using kernel_free. The tile_m, tile_n,
+ * tile_k and tile_p parameters are read from during the call, but no pointer
+ * to the corresponding data is kept afterwards.
+ */
+KernelParameters *kernel_fused2mm_new(int m,
+                                      int n,
+                                      int k,
+                                      int p,
+                                      float alpha,
+                                      float beta,
+                                      int transpose_a,
+                                      int transpose_b,
+                                      int transpose_c,
+                                      int transpose_d,
+                                      int generic,
+                                      const uint32_t *tile_m,
+                                      size_t tile_m_len,
+                                      const uint32_t *tile_n,
+                                      size_t tile_n_len,
+                                      const uint32_t *tile_k,
+                                      size_t tile_k_len,
+                                      const uint32_t *tile_p,
+                                      size_t tile_p_len);
+
+/*
+ * Instanciate a new kernel computing `y = (alpha*A + beta*B).x` where `A` and
+ * `B` are `m x n` matrices. The caller is responsible for deallocating the
+ * returned pointer using kernel_free. If `generic` is set, the sizes are
+ * passed as parameters of the generated code instead of being constants.
+ */
+KernelParameters *kernel_gesummv_new(int m, int n, int generic);
+
 /*
  * Instanciate a new kernel for matrix-matrix multiplication. The
  * caller is responsible for deallocating the returned pointer using
@@ -542,16 +657,182 @@ KernelParameters *kernel_matmul_new(int m,
                                     const uint32_t *tile_k,
                                     size_t tile_k_len);
 
+/*
+ * Same as `kernel_optimize`, but runs the search on a new thread and calls
+ * `callback` each time the search finds a better implementation, so that the
+ * caller can report progress or stop the search early. Returns immediately a
+ * task to pass to `kernel_optimize_wait`, or null if the configuration is
+ * invalid. `params` must not be used until `kernel_optimize_wait` returns.
+ *
+ * The callback is called from a thread of the explorer and must return
+ * quickly. It is not called if the best implementation is found in the tuning
+ * cache.
+ */
+OptimizeTask *kernel_optimize_async(KernelParameters *params,
+                                    DeviceId device,
+                                    const char *config_data,
+                                    size_t config_len,
+                                    NewBestCallback callback,
+                                    void *user_data);
+
+/*
+ * Waits for the end of a search started by `kernel_optimize_async` and frees
+ * `task`. Returns `false` if no valid implementation was found, if the device
+ * is not supported or if the search panicked, in which case `telamon_strerror`
+ * describes the error.
+ */
+bool kernel_optimize_wait(OptimizeTask *task);
+
+/*
+ * Sets the type of the elements the kernel operates on. Kernels created through the
+ * `kernel_*_new` functions operate on single precision elements by default.
+ */
+void kernel_set_element_type(KernelParameters *params, ElementType element_type);
+
 /*
  * Optimize a kernel on a given device. `config_data` points to a JSON-encoded
  * string of length `config_len` containing the configuration parameters for
- * the explorer.
+ * the explorer. Returns `false` if no valid implementation was found, if the
+ * device is not supported or if the configuration is invalid, in which case
+ * `telamon_strerror` describes the error. The best implementation can then be
+ * retrieved with `kernel_best_runtime` and `kernel_best_code`.
  */
 bool kernel_optimize(KernelParameters *params,
                      DeviceId device,
                      const char *config_data,
                      size_t config_len);
 
+/*
+ * Adds an instruction applying a binary operator in the open dimensions. Takes the
+ * ownership of the operands and writes the id of the instruction in `inst_id`.
+ */
+TelamonStatus telamon_builder_binop(Builder *builder,
+                                    BinOp binop,
+                                    Operand *lhs,
+                                    Operand *rhs,
+                                    InstId *inst_id);
+
+/*
+ * Closes a dimension opened by the builder. Instructions created afterwards are placed
+ * outside of the dimension.
+ */
+void telamon_builder_close_dim(Builder *builder, const LogicalDim *dim);
+
+/*
+ * Frees a builder allocated with `telamon_builder_new`.
+ */
+void telamon_builder_free(Builder *builder);
+
+/*
+ * Creates the search space of the function built by `builder`. Takes the ownership of
+ * `builder`, which must not be used again. Returns `null` if the function is invalid.
+ * The search space must be freed with `telamon_search_space_free`.
+ */
+SearchSpace *telamon_builder_get(Builder *builder);
+
+/*
+ * Adds an instruction computing `mul_lhs * mul_rhs + add_rhs` in the open dimensions.
+ * Takes the ownership of the operands and writes the id of the instruction in `inst_id`.
+ */
+TelamonStatus telamon_builder_mad(Builder *builder,
+                                  Operand *mul_lhs,
+                                  Operand *mul_rhs,
+                                  Operand *add_rhs,
+                                  InstId *inst_id);
+
+/*
+ * Adds a `mov` instruction in the open dimensions. Takes the ownership of `operand` and
+ * writes the id of the instruction in `inst_id`.
+ */
+TelamonStatus telamon_builder_mov(Builder *builder, Operand *operand, InstId *inst_id);
+
+/*
+ * Creates a builder for a function with the given signature. The signature must have all
+ * its parameters when the builder is created. The builder must be freed with
+ * `telamon_builder_free` or consumed with `telamon_builder_get`. Does not take the
+ * ownership of `signature` or `device`.
+ */
+Builder *telamon_builder_new(const Signature *signature, const Device *device);
+
+/*
+ * Opens a dimension of constant size `size`, tiled with the tile sizes in `tile_sizes`,
+ * from the outermost to the innermost. `tile_sizes` may be null if `num_tiles` is 0.
+ * The product of the tile sizes must divide `size`. The returned dimension must be freed
+ * with `telamon_logical_dim_free`.
+ */
+LogicalDim *telamon_builder_open_dim(Builder *builder,
+                                     uint32_t size,
+                                     const uint32_t *tile_sizes,
+                                     uintptr_t num_tiles);
+
+/*
+ * Opens a dimension with the size and the tiling of `dim`, to which it is mapped. `dim`
+ * is closed if it is open. The returned dimension must be freed with
+ * `telamon_logical_dim_free`.
+ */
+LogicalDim *telamon_builder_open_mapped_dim(Builder *builder, const LogicalDim *dim);
+
+/*
+ * Opens a dimension whose size is the value of the integer parameter `param`, which is
+ * at most `max_size`. Otherwise behaves like `telamon_builder_open_dim`. Returns `null`
+ * if the signature has no parameter named `param`.
+ */
+LogicalDim *telamon_builder_open_param_dim(Builder *builder,
+                                           const char *param,
+                                           uint32_t max_size,
+                                           const uint32_t *tile_sizes,
+                                           uintptr_t num_tiles);
+
+/*
+ * Creates an operand that holds the current index along `dim`.
+ */
+Operand *telamon_builder_operand_index(Builder *builder, const LogicalDim *dim);
+
+/*
+ * Creates an operand that references the value produced by `inst`, mapped to the open
+ * dimensions. The operand must be passed to a function that takes its ownership.
+ */
+Operand *telamon_builder_operand_inst(Builder *builder, InstId inst);
+
+/*
+ * Creates an operand that holds the value of the parameter `param`. Returns `null` if the
+ * signature has no parameter named `param`.
+ */
+Operand *telamon_builder_operand_param(Builder *builder, const char *param);
+
+/*
+ * Creates an operand that takes the value produced by `init` the first time it is
+ * encountered and then the value produced by the instruction that uses the operand,
+ * thus reducing along the open dimensions `init` is not nested in.
+ */
+Operand *telamon_builder_operand_reduce(Builder *builder, InstId init);
+
+/*
+ * Adds an instruction that loads an element of type `t` from the array parameter
+ * `array`, in the open dimensions. The array is accessed as a tensor stored in row-major
+ * order, whose dimensions are `dims`. Does not keep references to `t` or `dims` and
+ * writes the id of the instruction in `inst_id`.
+ */
+TelamonStatus telamon_builder_tensor_load(Builder *builder,
+                                          const char *array,
+                                          const Type *t,
+                                          const const LogicalDim * *dims,
+                                          uintptr_t num_dims,
+                                          InstId *inst_id);
+
+/*
+ * Adds an instruction that stores `value` in the array parameter `array`, in the open
+ * dimensions. The array is accessed as in `telamon_builder_tensor_load`, with elements of
+ * the type of `value`. Takes the ownership of `value` and writes the id of the
+ * instruction in `inst_id`.
+ */
+TelamonStatus telamon_builder_tensor_store(Builder *builder,
+                                           const char *array,
+                                           const const LogicalDim * *dims,
+                                           uintptr_t num_dims,
+                                           Operand *value,
+                                           InstId *inst_id);
+
 /*
  * Frees an explorer configuration.
  */
@@ -879,6 +1160,12 @@ Type *telamon_ir_type_new_float(uint16_t num_bits);
  */
 Type *telamon_ir_type_new_int(uint16_t num_bits);
 
+/*
+ * Frees a dimension returned by one of the `telamon_builder_open_*` functions. This does
+ * not close the dimension.
+ */
+void telamon_logical_dim_free(LogicalDim *dim);
+
 /*
  * Apply a sequence of actions to a search space.
  *
@@ -940,4 +1227,53 @@ char *telamon_strerror(void);
  */
 TelamonStatus telamon_string_copy(String *dst, const char *src);
 
+/*
+ * Allocates and binds an array to the given parameter. `size` is given in bytes.
+ *
+ * The allocated array is managed by the context and doesn't need to be explicitly
+ * destroyed.
+ */
+void telamon_x86_bind_array(X86Environment *env, const Parameter *param, size_t size);
+
+/*
+ * Binds a `double` to a parameter.
+ */
+void telamon_x86_bind_double(X86Environment *env, const Parameter *param, double value);
+
+/*
+ * Binds a `float` to a parameter.
+ */
+void telamon_x86_bind_float(X86Environment *env, const Parameter *param, float value);
+
+/*
+ * Binds an `int32_t` to a parameter.
+ */
+void telamon_x86_bind_int32(X86Environment *env, const Parameter *param, int32_t value);
+
+/*
+ * Binds an `int64_t` to a parameter.
+ */
+void telamon_x86_bind_int64(X86Environment *env, const Parameter *param, int64_t value);
+
+/*
+ * Destroys a CPU environment.
+ */
+void telamon_x86_environment_free(X86Environment *env);
+
+/*
+ * Returns a pointer to a CPU environment. The caller is responsible for deallocating the
+ * pointer by calling `telamon_x86_environment_free`.
+ */
+X86Environment *telamon_x86_environment_new(void);
+
+/*
+ * Returns a pointer to the evaluation context, which lives as long as `env`.
+ */
+const Context *telamon_x86_get_context(const X86Environment *env);
+
+/*
+ * Returns a pointer to the description of the CPU, which lives as long as `env`.
+ */
+const Device *telamon_x86_get_device(const X86Environment *env);
+
 #endif /* TELAMON_CAPI_H */
@@ -0,0 +1,290 @@
+//! C API wrappers to build kernels with `telamon::helper::Builder`.
+//!
+//! The builder keeps track of the open dimensions: instructions are placed in the
+//! dimensions open when they are created, and operands referencing other instructions are
+//! automatically mapped to the open dimensions.  This makes it possible to build kernels
+//! without manipulating dimension ids and dimension maps directly, as the functions of
+//! `ir.rs` require.
+use std::ffi::CStr;
+
+use libc::c_char;
+use telamon::helper::{self, AutoOperand, Reduce};
+use telamon::ir;
+
+use super::error::{Error, TelamonStatus};
+use super::ir::Operand;
+use super::search_space::SearchSpace;
+use super::{c_tiling_pattern, clone_arc, Device};
+
+/// Opaque type that wraps the builder of a function.
+pub struct Builder(helper::Builder);
+
+/// Opaque type that represents a logical dimension: a dimension along with the dimensions
+/// that implement its tiling.
+pub struct LogicalDim(helper::LogicalDim);
+
+/// Creates a builder for a function with the given signature. The signature must have all
+/// its parameters when the builder is created. The builder must be freed with
+/// `telamon_builder_free` or consumed with `telamon_builder_get`. Does not take the
+/// ownership of `signature` or `device`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_new(
+    signature: *const ir::Signature,
+    device: *const Device,
+) -> *mut Builder {
+    let builder = helper::Builder::new(clone_arc(signature), clone_arc((*device).0));
+    Box::into_raw(Box::new(Builder(builder)))
+}
+
+/// Frees a builder allocated with `telamon_builder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_free(builder: *mut Builder) {
+    std::mem::drop(Box::from_raw(builder));
+}
+
+/// Creates the search space of the function built by `builder`. Takes the ownership of
+/// `builder`, which must not be used again. Returns `null` if the function is invalid.
+/// The search space must be freed with `telamon_search_space_free`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_get(builder: *mut Builder) -> *mut SearchSpace {
+    let builder = Box::from_raw(builder).0;
+    let search_space = unwrap_or_exit!(builder.try_get(), null);
+    Box::into_raw(Box::new(SearchSpace(search_space)))
+}
+
+/// Opens a dimension of constant size `size`, tiled with the tile sizes in `tile_sizes`,
+/// from the outermost to the innermost. `tile_sizes` may be null if `num_tiles` is 0.
+/// The product of the tile sizes must divide `size`. The returned dimension must be freed
+/// with `telamon_logical_dim_free`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_open_dim(
+    builder: *mut Builder,
+    size: u32,
+    tile_sizes: *const u32,
+    num_tiles: usize,
+) -> *mut LogicalDim {
+    let size = (*builder).0.cst_size(size);
+    open_tiled_dim(builder, size, tile_sizes, num_tiles)
+}
+
+/// Opens a dimension whose size is the value of the integer parameter `param`, which is
+/// at most `max_size`. Otherwise behaves like `telamon_builder_open_dim`. Returns `null`
+/// if the signature has no parameter named `param`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_open_param_dim(
+    builder: *mut Builder,
+    param: *const c_char,
+    max_size: u32,
+    tile_sizes: *const u32,
+    num_tiles: usize,
+) -> *mut LogicalDim {
+    let param = unwrap_or_exit!(find_param(builder, param), null);
+    let size = (*builder).0.param_size(param, max_size);
+    open_tiled_dim(builder, size, tile_sizes, num_tiles)
+}
+
+/// Opens a dimension with the size and the tiling of `dim`, to which it is mapped. `dim`
+/// is closed if it is open. The returned dimension must be freed with
+/// `telamon_logical_dim_free`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_open_mapped_dim(
+    builder: *mut Builder,
+    dim: *const LogicalDim,
+) -> *mut LogicalDim {
+    let dim = (*builder).0.open_mapped_dim(&(*dim).0);
+    Box::into_raw(Box::new(LogicalDim(dim)))
+}
+
+/// Closes a dimension opened by the builder. Instructions created afterwards are placed
+/// outside of the dimension.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_close_dim(
+    builder: *mut Builder,
+    dim: *const LogicalDim,
+) {
+    (*builder).0.close_dim(&(*dim).0);
+}
+
+/// Frees a dimension returned by one of the `telamon_builder_open_*` functions. This does
+/// not close the dimension.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_logical_dim_free(dim: *mut LogicalDim) {
+    std::mem::drop(Box::from_raw(dim));
+}
+
+/// Creates an operand that references the value produced by `inst`, mapped to the open
+/// dimensions. The operand must be passed to a function that takes its ownership.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_operand_inst(
+    builder: *mut Builder,
+    inst: ir::InstId,
+) -> *mut Operand {
+    let operand = AutoOperand::get(&inst, &mut (*builder).0);
+    Box::into_raw(Box::new(Operand(operand)))
+}
+
+/// Creates an operand that takes the value produced by `init` the first time it is
+/// encountered and then the value produced by the instruction that uses the operand,
+/// thus reducing along the open dimensions `init` is not nested in.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_operand_reduce(
+    builder: *mut Builder,
+    init: ir::InstId,
+) -> *mut Operand {
+    let operand = AutoOperand::get(&Reduce(init), &mut (*builder).0);
+    Box::into_raw(Box::new(Operand(operand)))
+}
+
+/// Creates an operand that holds the value of the parameter `param`. Returns `null` if the
+/// signature has no parameter named `param`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_operand_param(
+    builder: *mut Builder,
+    param: *const c_char,
+) -> *mut Operand {
+    let param = unwrap_or_exit!(find_param(builder, param), null);
+    let operand = AutoOperand::get(&param, &mut (*builder).0);
+    Box::into_raw(Box::new(Operand(operand)))
+}
+
+/// Creates an operand that holds the current index along `dim`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_operand_index(
+    builder: *mut Builder,
+    dim: *const LogicalDim,
+) -> *mut Operand {
+    let operand = AutoOperand::get(&(*dim).0, &mut (*builder).0);
+    Box::into_raw(Box::new(Operand(operand)))
+}
+
+/// Adds a `mov` instruction in the open dimensions. Takes the ownership of `operand` and
+/// writes the id of the instruction in `inst_id`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_mov(
+    builder: *mut Builder,
+    operand: *mut Operand,
+    inst_id: *mut ir::InstId,
+) -> TelamonStatus {
+    let operand = Box::from_raw(operand).0;
+    *inst_id = (*builder).0.mov(&operand);
+    TelamonStatus::Ok
+}
+
+/// Adds an instruction applying a binary operator in the open dimensions. Takes the
+/// ownership of the operands and writes the id of the instruction in `inst_id`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_binop(
+    builder: *mut Builder,
+    binop: ir::BinOp,
+    lhs: *mut Operand,
+    rhs: *mut Operand,
+    inst_id: *mut ir::InstId,
+) -> TelamonStatus {
+    let lhs = Box::from_raw(lhs).0;
+    let rhs = Box::from_raw(rhs).0;
+    *inst_id = (*builder).0.binop(binop, &lhs, &rhs);
+    TelamonStatus::Ok
+}
+
+/// Adds an instruction computing `mul_lhs * mul_rhs + add_rhs` in the open dimensions.
+/// Takes the ownership of the operands and writes the id of the instruction in `inst_id`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_mad(
+    builder: *mut Builder,
+    mul_lhs: *mut Operand,
+    mul_rhs: *mut Operand,
+    add_rhs: *mut Operand,
+    inst_id: *mut ir::InstId,
+) -> TelamonStatus {
+    let mul_lhs = Box::from_raw(mul_lhs).0;
+    let mul_rhs = Box::from_raw(mul_rhs).0;
+    let add_rhs = Box::from_raw(add_rhs).0;
+    *inst_id = (*builder).0.mad(&mul_lhs, &mul_rhs, &add_rhs);
+    TelamonStatus::Ok
+}
+
+/// Adds an instruction that loads an element of type `t` from the array parameter
+/// `array`, in the open dimensions. The array is accessed as a tensor stored in row-major
+/// order, whose dimensions are `dims`. Does not keep references to `t` or `dims` and
+/// writes the id of the instruction in `inst_id`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_tensor_load(
+    builder: *mut Builder,
+    array: *const c_char,
+    t: *const ir::Type,
+    dims: *const *const LogicalDim,
+    num_dims: usize,
+    inst_id: *mut ir::InstId,
+) -> TelamonStatus {
+    let array = unwrap_or_exit!(find_param(builder, array));
+    let dims = logical_dims(dims, num_dims);
+    let builder = &mut (*builder).0;
+    let (ptr, pattern) = builder.tensor_access(&array, None, *t, &dims);
+    *inst_id = builder.ld(*t, &ptr, pattern);
+    TelamonStatus::Ok
+}
+
+/// Adds an instruction that stores `value` in the array parameter `array`, in the open
+/// dimensions. The array is accessed as in `telamon_builder_tensor_load`, with elements of
+/// the type of `value`. Takes the ownership of `value` and writes the id of the
+/// instruction in `inst_id`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_builder_tensor_store(
+    builder: *mut Builder,
+    array: *const c_char,
+    dims: *const *const LogicalDim,
+    num_dims: usize,
+    value: *mut Operand,
+    inst_id: *mut ir::InstId,
+) -> TelamonStatus {
+    let value = Box::from_raw(value).0;
+    let array = unwrap_or_exit!(find_param(builder, array));
+    let dims = logical_dims(dims, num_dims);
+    let builder = &mut (*builder).0;
+    let (ptr, pattern) = builder.tensor_access(&array, None, value.t(), &dims);
+    *inst_id = builder.st(&ptr, &value, pattern);
+    TelamonStatus::Ok
+}
+
+/// Opens a tiled dimension of the given size.
+unsafe fn open_tiled_dim(
+    builder: *mut Builder,
+    size: ir::Size,
+    tile_sizes: *const u32,
+    num_tiles: usize,
+) -> *mut LogicalDim {
+    let tiling = c_tiling_pattern(tile_sizes, num_tiles).unwrap_or_default();
+    let dim = (*builder).0.open_tiled_dim(size, tiling);
+    Box::into_raw(Box::new(LogicalDim(dim)))
+}
+
+/// Returns the name of a parameter of the function built by `builder`, ensuring it exists.
+unsafe fn find_param<'a>(
+    builder: *const Builder,
+    name: *const c_char,
+) -> Result<&'a str, Error> {
+    let name = CStr::from_ptr(name).to_str()?;
+    let signature = (*builder).0.function().signature();
+    if signature.params.iter().any(|param| param.name == name) {
+        Ok(name)
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "no parameter named `{}`",
+            name
+        )))
+    }
+}
+
+/// Collects the dimensions of a C array. Does not keep references to the array.
+unsafe fn logical_dims<'a>(
+    dims: *const *const LogicalDim,
+    num_dims: usize,
+) -> Vec<&'a helper::LogicalDim> {
+    if num_dims == 0 {
+        return vec![];
+    }
+    std::slice::from_raw_parts(dims, num_dims)
+        .iter()
+        .map(|&dim| &(*dim).0)
+        .collect()
+}
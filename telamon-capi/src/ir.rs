@@ -1,5 +1,5 @@
 //! C API wrappers to create Telamon Kernels.
-use crate::Device;
+use crate::{clone_arc, Device};
 use libc;
 use num::rational::Ratio;
 use std::{self, sync::Arc};
@@ -95,8 +95,8 @@ pub unsafe extern "C" fn telamon_ir_function_new(
     device: *const Device,
 ) -> *mut Function {
     Box::into_raw(Box::new(Function(ir::Function::new(
-        Arc::clone(&Arc::from_raw(signature)),
-        Arc::clone(&Arc::from_raw((*device).0)),
+        clone_arc(signature),
+        clone_arc((*device).0),
     ))))
 }
 
@@ -155,7 +155,7 @@ pub unsafe extern "C" fn telamon_ir_function_add_dimensions(
 
 /// Opaque type that abstracts away the lifetime parameter of `ir::Size` so cbindgen
 /// can generate bindings.
-pub struct Size(ir::Size);
+pub struct Size(pub(crate) ir::Size);
 
 /// Create a size equal to:
 /// ```
@@ -220,7 +220,7 @@ pub unsafe extern "C" fn telamon_ir_size_mul(
 
 /// Opaque type that abstracts away the lifetime parameter of `ir::Operand` so that
 /// cbindgen can generate bindings.
-pub struct Operand(ir::Operand<()>);
+pub struct Operand(pub(crate) ir::Operand<()>);
 
 /// Create a constant integer operand. The provided type must be an integer type.
 /// Returns `null` if an error is encountered.
@@ -497,6 +497,7 @@ unsafe fn tensor_access(
             Some(*array_id)
         },
         dims,
+        reversed: Default::default(),
     };
     Ok((address, access_pattern))
 }
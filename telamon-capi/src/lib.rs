@@ -7,16 +7,22 @@
 #[macro_use]
 pub mod error;
 
+pub mod builder;
 pub mod explorer;
 pub mod ir;
 pub mod search_space;
+pub mod x86;
 
-use libc::{c_char, c_int, c_uint, size_t};
-use telamon::device;
+use libc::{c_char, c_double, c_float, c_int, c_uint, c_void, size_t};
+use std::ffi::CString;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 use telamon::explorer::config::Config;
+use telamon::explorer::{Candidate, NewBestFn};
 use telamon::helper::{MemInit, TilingPattern};
-pub use telamon_kernels::{linalg, Kernel};
-use telamon_x86 as x86;
+use telamon::{codegen, device, explorer};
+use telamon_kernels::KernelBuilder;
+pub use telamon_kernels::{linalg, Kernel, Scalar};
 
 // Pointers to `device::Context` and `device::Device` are not C-like pointers.
 // Instead, they are fat pointers containing both a regular pointer to the
@@ -44,34 +50,159 @@ pub enum DeviceId {
     Cuda,
 }
 
+/// Types of the elements kernels operate on.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum ElementType {
+    F32,
+    F64,
+}
+
 /// Supported kernels.
 #[derive(Clone)]
-pub enum KernelParameters {
+pub struct KernelParameters {
+    kind: KernelKind,
+    element_type: ElementType,
+    /// Result of the last call to `kernel_optimize`, if it found an implementation.
+    result: Option<KernelResult>,
+}
+
+#[derive(Clone)]
+enum KernelKind {
+    /// Computes `z = alpha*x + y` on vectors of size `n`.
+    Axpy { n: i32, generic: bool },
+    /// Computes `y = (alpha*A + beta*B).x` where `A` and `B` are `m x n` matrices.
+    Gesummv { m: i32, n: i32, generic: bool },
     /// A matrix-matrix multiplication kernel.
     MatMul(linalg::FusedMMP),
+    /// A batch of matrix-matrix multiplications.
+    BatchMM(linalg::BatchMMP),
+    /// Two chained matrix-matrix multiplications, `E = alpha*A.B.C + beta*D`.
+    Fused2MM(linalg::Fused2MMP),
+}
+
+/// The best implementation of a kernel found by the explorer.
+#[derive(Clone)]
+struct KernelResult {
+    /// Execution time of the implementation, in nanoseconds.
+    runtime: f64,
+    /// Code generated for the implementation, if the device can export it.
+    code: Option<CString>,
 }
 
 impl KernelParameters {
-    /// Runs the search for a best candidate.
+    /// Creates the parameters of a kernel operating on single precision elements.
+    fn new(kind: KernelKind) -> Self {
+        KernelParameters {
+            kind,
+            element_type: ElementType::F32,
+            result: None,
+        }
+    }
+
+    /// Runs the search for a best candidate and stores it in `self.result`.
     fn optimize_kernel<'a, C: device::ArgMap<'a> + device::Context>(
-        &self,
+        &mut self,
         config: &Config,
+        on_new_best: Option<&NewBestFn<'_>>,
         context: &mut C,
     ) {
-        match self {
-            KernelParameters::MatMul(params) => {
-                linalg::FusedMM::<f32>::benchmark(
-                    config,
-                    params.clone(),
-                    0,
-                    MemInit::RandomFill,
-                    context,
-                );
+        let kind = &self.kind;
+        self.result = match self.element_type {
+            ElementType::F32 => {
+                kind.optimize_kernel::<f32, _>(config, on_new_best, context)
+            }
+            ElementType::F64 => {
+                kind.optimize_kernel::<f64, _>(config, on_new_best, context)
+            }
+        };
+    }
+}
+
+impl KernelKind {
+    /// Runs the search for a best candidate, with elements of type `S`.
+    fn optimize_kernel<'a, S: Scalar, C: device::ArgMap<'a> + device::Context>(
+        &self,
+        config: &Config,
+        on_new_best: Option<&NewBestFn<'_>>,
+        context: &mut C,
+    ) -> Option<KernelResult> {
+        match *self {
+            KernelKind::Axpy { n, generic } => find_best::<linalg::Axpy<S>, _>(
+                config,
+                (n, generic),
+                on_new_best,
+                context,
+            ),
+            KernelKind::Gesummv { m, n, generic } => find_best::<linalg::Gesummv<S>, _>(
+                config,
+                (m, n, generic),
+                on_new_best,
+                context,
+            ),
+            KernelKind::MatMul(ref params) => find_best::<linalg::FusedMM<S>, _>(
+                config,
+                params.clone(),
+                on_new_best,
+                context,
+            ),
+            KernelKind::BatchMM(params) => {
+                find_best::<linalg::BatchMM<S>, _>(config, params, on_new_best, context)
             }
+            KernelKind::Fused2MM(ref params) => find_best::<linalg::Fused2MM<S>, _>(
+                config,
+                params.clone(),
+                on_new_best,
+                context,
+            ),
         }
     }
 }
 
+/// Searches for the best implementation of the kernel `K` and evaluates it. Returns `None`
+/// if the parameters are invalid or if no implementation passes the correctness checks.
+fn find_best<'a, 'b, K, C>(
+    config: &Config,
+    params: K::Parameters,
+    on_new_best: Option<&NewBestFn<'_>>,
+    context: &'b mut C,
+) -> Option<KernelResult>
+where
+    K: Kernel<'a> + 'b,
+    C: device::ArgMap<'a> + device::Context,
+{
+    let (signature, kernel, context) = KernelBuilder::new()
+        .mem_init(MemInit::RandomFill)
+        .try_build::<K, C>(params, context)
+        .ok()?;
+    let candidates = kernel.build_body(Arc::new(signature), context);
+    let expected = kernel.get_expected_output(context);
+    let best = explorer::find_best_with_progress(
+        config,
+        context,
+        candidates,
+        Some(&|_, context| kernel.check_result(&expected, context)),
+        on_new_best,
+    )?;
+    let function = codegen::Function::build(&best.space);
+    let runtime = context
+        .evaluate(&function, device::EvalMode::FindBest)
+        .ok()?;
+    let code = context
+        .export_artifact(&function)
+        .and_then(|artifact| CString::new(artifact.source).ok());
+    Some(KernelResult { runtime, code })
+}
+
+/// Returns a new reference to the object `ptr` points to, without taking the ownership of
+/// `ptr`. `ptr` must have been obtained from an `Arc`.
+unsafe fn clone_arc<T: ?Sized>(ptr: *const T) -> Arc<T> {
+    let arc = Arc::from_raw(ptr);
+    let clone = Arc::clone(&arc);
+    std::mem::forget(arc);
+    clone
+}
+
 /// Helper function to create a TilingPattern from a buffer of u32
 /// values without transferring ownership (it performs a copy).
 /// Returns None when data is null.
@@ -83,6 +214,37 @@ unsafe fn c_tiling_pattern(data: *const u32, len: usize) -> Option<TilingPattern
     }
 }
 
+/// Instanciate a new kernel computing `z = alpha*x + y` on vectors of size
+/// `n`. The caller is responsible for deallocating the returned pointer using
+/// kernel_free. If `generic` is set, the size is passed as a parameter of the
+/// generated code instead of being a constant.
+#[no_mangle]
+pub extern "C" fn kernel_axpy_new(n: c_int, generic: c_int) -> *mut KernelParameters {
+    let kind = KernelKind::Axpy {
+        n: n as i32,
+        generic: generic == 1,
+    };
+    Box::into_raw(Box::new(KernelParameters::new(kind)))
+}
+
+/// Instanciate a new kernel computing `y = (alpha*A + beta*B).x` where `A` and
+/// `B` are `m x n` matrices. The caller is responsible for deallocating the
+/// returned pointer using kernel_free. If `generic` is set, the sizes are
+/// passed as parameters of the generated code instead of being constants.
+#[no_mangle]
+pub extern "C" fn kernel_gesummv_new(
+    m: c_int,
+    n: c_int,
+    generic: c_int,
+) -> *mut KernelParameters {
+    let kind = KernelKind::Gesummv {
+        m: m as i32,
+        n: n as i32,
+        generic: generic == 1,
+    };
+    Box::into_raw(Box::new(KernelParameters::new(kind)))
+}
+
 /// Instanciate a new kernel for matrix-matrix multiplication. The
 /// caller is responsible for deallocating the returned pointer using
 /// kernel_free. The tile_m, tile_n and tile_k parameters are read
@@ -104,7 +266,7 @@ pub unsafe extern "C" fn kernel_matmul_new(
     tile_k: *const u32,
     tile_k_len: size_t,
 ) -> *mut KernelParameters {
-    Box::into_raw(Box::new(KernelParameters::MatMul(linalg::FusedMMP {
+    let params = linalg::FusedMMP {
         m: m as i32,
         n: n as i32,
         k: k as i32,
@@ -116,7 +278,89 @@ pub unsafe extern "C" fn kernel_matmul_new(
         n_tiling: c_tiling_pattern(tile_n, tile_n_len),
         k_tiling: c_tiling_pattern(tile_k, tile_k_len),
         activation_fun: None,
-    })))
+        search_tiling: false,
+        bias: false,
+    };
+    let kind = KernelKind::MatMul(params);
+    Box::into_raw(Box::new(KernelParameters::new(kind)))
+}
+
+/// Instanciate a new kernel computing `batch` matrix-matrix multiplications
+/// `C[i] = A[i].B[i]`, where `A[i]` is `m x k` and `B[i]` is `k x n`. If
+/// `batch_b` is not set, the same `B` matrix is used for the whole batch. The
+/// caller is responsible for deallocating the returned pointer using
+/// kernel_free.
+#[no_mangle]
+pub extern "C" fn kernel_batchmm_new(
+    batch: c_int,
+    m: c_int,
+    n: c_int,
+    k: c_int,
+    transpose_a: c_int,
+    transpose_b: c_int,
+    batch_b: c_int,
+    generic: c_int,
+) -> *mut KernelParameters {
+    let mut params = linalg::BatchMMP::new(batch as i32, m as i32, n as i32, k as i32);
+    params.transpose_a = transpose_a == 1;
+    params.transpose_b = transpose_b == 1;
+    params.batch_b = batch_b == 1;
+    params.generic = generic == 1;
+    let kind = KernelKind::BatchMM(params);
+    Box::into_raw(Box::new(KernelParameters::new(kind)))
+}
+
+/// Instanciate a new kernel computing `E = alpha*A.B.C + beta*D` with two
+/// fused matrix-matrix multiplications, where `A` is `m x k`, `B` is `k x n`,
+/// `C` is `n x p` and `D` is `m x p`. The caller is responsible for
+/// deallocating the returned pointer using kernel_free. The tile_m, tile_n,
+/// tile_k and tile_p parameters are read from during the call, but no pointer
+/// to the corresponding data is kept afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_fused2mm_new(
+    m: c_int,
+    n: c_int,
+    k: c_int,
+    p: c_int,
+    alpha: c_float,
+    beta: c_float,
+    transpose_a: c_int,
+    transpose_b: c_int,
+    transpose_c: c_int,
+    transpose_d: c_int,
+    generic: c_int,
+    tile_m: *const u32,
+    tile_m_len: size_t,
+    tile_n: *const u32,
+    tile_n_len: size_t,
+    tile_k: *const u32,
+    tile_k_len: size_t,
+    tile_p: *const u32,
+    tile_p_len: size_t,
+) -> *mut KernelParameters {
+    let mut params =
+        linalg::Fused2MMP::new(m as i32, n as i32, k as i32, p as i32, alpha, beta);
+    params.transpose_a = transpose_a == 1;
+    params.transpose_b = transpose_b == 1;
+    params.transpose_c = transpose_c == 1;
+    params.transpose_d = transpose_d == 1;
+    params.generic = generic == 1;
+    params.m_tiling = c_tiling_pattern(tile_m, tile_m_len);
+    params.n_tiling = c_tiling_pattern(tile_n, tile_n_len);
+    params.k_tiling = c_tiling_pattern(tile_k, tile_k_len);
+    params.p_tiling = c_tiling_pattern(tile_p, tile_p_len);
+    let kind = KernelKind::Fused2MM(params);
+    Box::into_raw(Box::new(KernelParameters::new(kind)))
+}
+
+/// Sets the type of the elements the kernel operates on. Kernels created through the
+/// `kernel_*_new` functions operate on single precision elements by default.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_set_element_type(
+    params: *mut KernelParameters,
+    element_type: ElementType,
+) {
+    (*params).element_type = element_type;
 }
 
 /// Deallocates kernel parameters created through one of the `kernel_*_new`
@@ -129,7 +373,10 @@ pub unsafe extern "C" fn kernel_free(params: *mut KernelParameters) {
 
 /// Optimize a kernel on a given device. `config_data` points to a JSON-encoded
 /// string of length `config_len` containing the configuration parameters for
-/// the explorer.
+/// the explorer. Returns `false` if no valid implementation was found, if the
+/// device is not supported or if the configuration is invalid, in which case
+/// `telamon_strerror` describes the error. The best implementation can then be
+/// retrieved with `kernel_best_runtime` and `kernel_best_code`.
 #[no_mangle]
 pub unsafe extern "C" fn kernel_optimize(
     params: *mut KernelParameters,
@@ -137,26 +384,182 @@ pub unsafe extern "C" fn kernel_optimize(
     config_data: *const c_char,
     config_len: size_t,
 ) -> bool {
-    let config = {
-        let config_str = {
-            let slice = std::slice::from_raw_parts(config_data as *const u8, config_len);
-            std::str::from_utf8(slice).expect("Invalid configuration string")
-        };
-        // TODO: Should not unwrap here.
-        Config::from_json(config_str).unwrap()
+    let config = unwrap_or_exit!(parse_config(config_data, config_len), false);
+    optimize(params, device, &config, None)
+}
+
+/// Called by `kernel_optimize_async` each time the search finds a better
+/// implementation, with the `user_data` pointer given to `kernel_optimize_async`,
+/// the runtime of the implementation in nanoseconds and the actions leading to it,
+/// in the order they were applied, as a JSON-encoded string. The string is only
+/// valid during the call. The search stops if the callback returns `false`.
+pub type NewBestCallback = extern "C" fn(
+    user_data: *mut c_void,
+    runtime: c_double,
+    actions: *const c_char,
+) -> bool;
+
+/// A search started by `kernel_optimize_async`.
+pub struct OptimizeTask(JoinHandle<bool>);
+
+/// Same as `kernel_optimize`, but runs the search on a new thread and calls
+/// `callback` each time the search finds a better implementation, so that the
+/// caller can report progress or stop the search early. Returns immediately a
+/// task to pass to `kernel_optimize_wait`, or null if the configuration is
+/// invalid. `params` must not be used until `kernel_optimize_wait` returns.
+///
+/// The callback is called from a thread of the explorer and must return
+/// quickly. It is not called if the best implementation is found in the tuning
+/// cache.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_optimize_async(
+    params: *mut KernelParameters,
+    device: DeviceId,
+    config_data: *const c_char,
+    config_len: size_t,
+    callback: NewBestCallback,
+    user_data: *mut c_void,
+) -> *mut OptimizeTask {
+    exit_if_null!(params, null);
+    let config = unwrap_or_exit!(parse_config(config_data, config_len), null);
+    let params = SendPtr(params);
+    let reporter = ProgressReporter {
+        callback,
+        user_data,
     };
+    let handle = thread::Builder::new()
+        .name("Telamon - Optimize".to_string())
+        .spawn(move || {
+            let on_new_best =
+                |candidate: &Candidate, score: f64| reporter.new_best(candidate, score);
+            optimize(params.0, device, &config, Some(&on_new_best))
+        });
+    let handle = unwrap_or_exit!(handle.map_err(|_| error::Error::UnknownError), null);
+    Box::into_raw(Box::new(OptimizeTask(handle)))
+}
+
+/// Waits for the end of a search started by `kernel_optimize_async` and frees
+/// `task`. Returns `false` if no valid implementation was found, if the device
+/// is not supported or if the search panicked, in which case `telamon_strerror`
+/// describes the error.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_optimize_wait(task: *mut OptimizeTask) -> bool {
+    exit_if_null!(task, false);
+    let OptimizeTask(handle) = *Box::from_raw(task);
+    match handle.join() {
+        Ok(found) => found,
+        Err(_) => exit!(error::Error::UnknownError, false),
+    }
+}
+
+/// A pointer sent to the thread running a search.
+struct SendPtr<T>(*mut T);
+
+// The caller of `kernel_optimize_async` does not use the pointer until the
+// search ends.
+unsafe impl<T> Send for SendPtr<T> {}
+
+/// Forwards the new best candidates of a search to a C callback.
+struct ProgressReporter {
+    callback: NewBestCallback,
+    user_data: *mut c_void,
+}
+
+// The callback is only called from one thread at a time, and the caller of
+// `kernel_optimize_async` is responsible for `user_data` being usable from it.
+unsafe impl Send for ProgressReporter {}
+unsafe impl Sync for ProgressReporter {}
+
+impl ProgressReporter {
+    fn new_best(&self, candidate: &Candidate, score: f64) -> bool {
+        // `candidate.actions` lists the last action first.
+        let mut actions = candidate.actions.iter().collect::<Vec<_>>();
+        actions.reverse();
+        // Actions always serialize to a JSON string, which has no null
+        // character. The search goes on without notifying the caller otherwise.
+        match serde_json::to_string(&actions)
+            .ok()
+            .and_then(|s| CString::new(s).ok())
+        {
+            Some(actions) => (self.callback)(self.user_data, score, actions.as_ptr()),
+            None => true,
+        }
+    }
+}
+
+/// Parses the JSON-encoded configuration of length `config_len` pointed to by
+/// `config_data`.
+unsafe fn parse_config(
+    config_data: *const c_char,
+    config_len: size_t,
+) -> Result<Config, error::Error> {
+    if config_data.is_null() {
+        return Err(error::Error::NullPointer);
+    }
+    let slice = std::slice::from_raw_parts(config_data as *const u8, config_len);
+    let config_str = std::str::from_utf8(slice)?;
+    Config::from_json(config_str).map_err(|err| {
+        error::Error::InvalidArgument(format!("invalid configuration: {}", err))
+    })
+}
+
+/// Runs the search on the given device. Returns `false` if no implementation
+/// was found or if the device is not supported.
+unsafe fn optimize(
+    params: *mut KernelParameters,
+    device: DeviceId,
+    config: &Config,
+    on_new_best: Option<&NewBestFn<'_>>,
+) -> bool {
     match device {
-        DeviceId::X86 => (*params).optimize_kernel(&config, &mut x86::Context::default()),
+        DeviceId::X86 => (*params).optimize_kernel(
+            config,
+            on_new_best,
+            &mut telamon_x86::Context::default(),
+        ),
         DeviceId::Cuda => {
             #[cfg(feature = "cuda")]
             {
                 let executor = ::telamon_cuda::Executor::init();
                 let mut context = ::telamon_cuda::Context::new(&executor);
-                (*params).optimize_kernel(&config, &mut context);
+                (*params).optimize_kernel(config, on_new_best, &mut context);
             }
             #[cfg(not(feature = "cuda"))]
             return false;
         }
     };
-    true
+    (*params).result.is_some()
+}
+
+/// Writes in `runtime` the execution time, in nanoseconds, of the best
+/// implementation found by the last call to `kernel_optimize`. Returns `false`
+/// and leaves `runtime` untouched if no implementation was found.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_best_runtime(
+    params: *const KernelParameters,
+    runtime: *mut c_double,
+) -> bool {
+    match (*params).result {
+        Some(ref result) => {
+            *runtime = result.runtime;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the code generated for the best implementation found by the last
+/// call to `kernel_optimize`, as a null-terminated string. Returns null if no
+/// implementation was found or if the device cannot export its code. The
+/// string is owned by `params` and is only valid until the next call to
+/// `kernel_optimize` or `kernel_free` on `params`.
+#[no_mangle]
+pub unsafe extern "C" fn kernel_best_code(
+    params: *const KernelParameters,
+) -> *const c_char {
+    (*params)
+        .result
+        .as_ref()
+        .and_then(|result| result.code.as_ref())
+        .map_or(std::ptr::null(), |code| code.as_ptr())
 }
@@ -0,0 +1,111 @@
+//! C API wrappers for evaluating kernels on the CPU.
+use std::sync::Arc;
+
+use libc;
+use telamon::device::{self, ArgMapExt};
+use telamon::ir;
+use telamon_x86 as x86;
+
+use super::{Context, Device};
+
+/// Opaque type that holds a CPU evaluation context, which maps the parameters of kernels
+/// to actual values.
+pub struct X86Environment {
+    // The context is boxed so that `context_ref` stays valid when the environment moves.
+    context: Box<x86::Context>,
+    context_ref: Context,
+    // Keeps alive the device `device_ref` points to.
+    _device: Arc<dyn device::Device>,
+    device_ref: Device,
+}
+
+/// Returns a pointer to a CPU environment. The caller is responsible for deallocating the
+/// pointer by calling `telamon_x86_environment_free`.
+#[no_mangle]
+pub extern "C" fn telamon_x86_environment_new() -> *mut X86Environment {
+    let context = Box::new(x86::Context::default());
+    let context_ref = Context(&*context as &dyn device::Context);
+    let device = device::Context::device(&*context);
+    let device_ref = Device(&*device);
+    Box::into_raw(Box::new(X86Environment {
+        context,
+        context_ref,
+        _device: device,
+        device_ref,
+    }))
+}
+
+/// Destroys a CPU environment.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_environment_free(env: *mut X86Environment) {
+    std::mem::drop(Box::from_raw(env));
+}
+
+/// Returns a pointer to the evaluation context, which lives as long as `env`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_get_context(
+    env: *const X86Environment,
+) -> *const Context {
+    &(*env).context_ref
+}
+
+/// Returns a pointer to the description of the CPU, which lives as long as `env`.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_get_device(
+    env: *const X86Environment,
+) -> *const Device {
+    &(*env).device_ref
+}
+
+/// Allocates and binds an array to the given parameter. `size` is given in bytes.
+///
+/// The allocated array is managed by the context and doesn't need to be explicitly
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_bind_array(
+    env: *mut X86Environment,
+    param: *const ir::Parameter,
+    size: libc::size_t,
+) {
+    (*env).context.bind_array::<i8>(&*param, size);
+}
+
+/// Binds an `int32_t` to a parameter.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_bind_int32(
+    env: *mut X86Environment,
+    param: *const ir::Parameter,
+    value: libc::int32_t,
+) {
+    (*env).context.bind_scalar::<i32>(&*param, value);
+}
+
+/// Binds an `int64_t` to a parameter.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_bind_int64(
+    env: *mut X86Environment,
+    param: *const ir::Parameter,
+    value: libc::int64_t,
+) {
+    (*env).context.bind_scalar::<i64>(&*param, value);
+}
+
+/// Binds a `float` to a parameter.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_bind_float(
+    env: *mut X86Environment,
+    param: *const ir::Parameter,
+    value: libc::c_float,
+) {
+    (*env).context.bind_scalar::<f32>(&*param, value);
+}
+
+/// Binds a `double` to a parameter.
+#[no_mangle]
+pub unsafe extern "C" fn telamon_x86_bind_double(
+    env: *mut X86Environment,
+    param: *const ir::Parameter,
+    value: libc::c_double,
+) {
+    (*env).context.bind_scalar::<f64>(&*param, value);
+}
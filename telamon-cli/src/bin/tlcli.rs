@@ -1,12 +1,15 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::io::{self, BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic;
+use std::time::Duration;
 
 use itertools::*;
+use serde::Serialize;
 use serde_json;
 use structopt::StructOpt;
 
@@ -18,12 +21,26 @@ use telamon::explorer::{
     eventlog::EventLog,
     mcts, Candidate,
 };
+use telamon::ir;
 use telamon::model::{bound, Bound};
-use telamon::offline_analysis::tree::CandidateTree;
-use telamon::search_space::SearchSpace;
-use telamon_kernels::statistics::estimate_mean;
+use telamon::offline_analysis::{
+    dataset, deadend::DeadendExplanation, graphviz, merge::LogMerger, report,
+    space_stats, tree::CandidateTree,
+};
+use telamon::search_space::{trace, SearchSpace};
+use telamon_kernels::{
+    search_bench,
+    statistics::{self, estimate_cleaned_mean, estimate_mean},
+};
 
-use telamon_cli::{Bench, CommonOpt, KernelBundle, KernelParam, Platform, ReplayPath};
+use telamon_cli::golden::Golden;
+use telamon_cli::plugin::BackendPlugin;
+use telamon_cli::soak;
+use telamon_cli::watch;
+use telamon_cli::{
+    Bench, CommonOpt, DType, KernelBundle, KernelParam, Platform, PlatformContextBuilder,
+    ReplayPath,
+};
 
 /// Run a full search for a given kernel
 #[derive(StructOpt)]
@@ -43,16 +60,42 @@ struct Search {
     /// Number of times to run the generated code to evaluate its performance.
     #[structopt(long = "num-code-runs", default_value = "40")]
     num_code_runs: usize,
+
+    /// Runs the best implementation continuously for this many minutes after the search
+    ///
+    /// The runtime and the temperature and clocks of the device are sampled during the
+    /// run, and the steady-state runtime is compared to the cold-start runtime measured
+    /// right after the search.  Samples are written to `soak.json`.
+    #[structopt(long = "soak")]
+    soak_minutes: Option<f64>,
+
+    /// Interval between two samples of the soak, in seconds
+    #[structopt(long = "soak-interval", default_value = "1")]
+    soak_interval: f64,
+
+    /// Replay file whose actions guide the first descents of the search
+    ///
+    /// Typically the best implementation found by a search on a similar problem size.
+    /// Actions that do not apply to the searched kernel are ignored.  May be repeated.
+    /// Only supported by the MCTS search algorithm.
+    #[structopt(parse(from_os_str), long = "warm-start")]
+    warm_start: Vec<ReplayPath>,
 }
 
 impl Search {
-    fn run(&self, _args: &Opt) -> io::Result<()> {
-        let builder = self.platform.to_builder();
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
         let mut config = self.common.config().unwrap().clone();
         let output_base = std::path::Path::new(&config.output_dir).to_owned();
+        let mut results = vec![Vec::with_capacity(self.repeat); self.kernels.len()];
+        let warm_start = self
+            .warm_start
+            .iter()
+            .map(ReplayPath::load)
+            .collect::<io::Result<Vec<_>>>()?;
 
         for idx in 0..self.repeat {
-            for kernel in &self.kernels {
+            for (kernel, results) in self.kernels.iter().zip(&mut results) {
                 config.output_dir = output_base
                     .join(kernel.to_string())
                     .join(idx.to_string())
@@ -60,8 +103,13 @@ impl Search {
                     .unwrap()
                     .to_string();
 
-                let mut context = builder.build_context();
-                let (bundle, context) = context.kernel_bundle(kernel);
+                let mut context = builder.build_context()?;
+                let (bundle, context) = context.kernel_bundle(
+                    kernel,
+                    args.dtype,
+                    args.seed,
+                    args.accuracy_budget,
+                );
 
                 let best = explorer::find_best_ex(
                     &config,
@@ -71,6 +119,7 @@ impl Search {
                         let check_fn = &bundle.check_fn;
                         &move |_, context| check_fn(context)
                     }),
+                    &warm_start,
                 )
                 .unwrap_or_else(|| panic!("no candidates found for kernel {}", kernel));
 
@@ -84,25 +133,198 @@ impl Search {
                 let mut f =
                     std::fs::File::create(config.output_path("benchmark.txt").unwrap())
                         .unwrap();
+                writeln!(f, "seed: {}", args.seed).unwrap();
                 writeln!(f, "runtimes: {:?}", runtime).unwrap();
-                let mean = estimate_mean(runtime, 0.95, "ns");
-                let ref_mean = estimate_mean(ref_runtime, 0.95, "ns");
+                writeln!(f, "launch: {}", best_fn.launch_config(context)).unwrap();
+                let mean = estimate_cleaned_mean(runtime, 0.95, "ns");
+                let ref_mean = estimate_cleaned_mean(ref_runtime, 0.95, "ns");
                 writeln!(
                     f,
                     "{}: {}, reference: {}, speedup: {:.2}",
                     kernel,
                     mean,
                     ref_mean,
-                    ref_mean.value / mean.value
+                    ref_mean.cleaned.value / mean.cleaned.value
                 )
                 .unwrap();
+
+                if let Some(minutes) = self.soak_minutes {
+                    let duration = Duration::from_millis((minutes * 60e3) as u64);
+                    let interval =
+                        Duration::from_millis((self.soak_interval * 1e3) as u64);
+                    let cold = mean.cleaned.value;
+                    match soak::soak(context, &best_fn, cold, duration, interval) {
+                        Some(report) => {
+                            writeln!(
+                                f,
+                                "soak: {:.4e}ns after {} minutes, {:.2}x the cold-start \
+                                 runtime (max temperature: {:?}C, min SM clock: {:?}MHz)",
+                                report.steady_runtime,
+                                minutes,
+                                report.slowdown,
+                                report.max_temperature,
+                                report.min_sm_clock,
+                            )?;
+                            serde_json::to_writer_pretty(
+                                fs::File::create(config.output_path("soak.json")?)?,
+                                &report,
+                            )?;
+                            println!(
+                                "{}: {:.4e}ns cold, {:.4e}ns under soak ({:.2}x)",
+                                kernel, cold, report.steady_runtime, report.slowdown
+                            );
+                        }
+                        None => eprintln!("Soak of {} failed to run the kernel", kernel),
+                    }
+                }
+
+                results.push(RepeatResult {
+                    runtime: mean.cleaned.value,
+                    reference: ref_mean.cleaned.value,
+                    actions: replay_actions(&best),
+                });
             }
         }
 
+        for (kernel, results) in self.kernels.iter().zip(results) {
+            if results.is_empty() {
+                continue;
+            }
+            let summary = SearchSummary::new(kernel, args.seed, &results);
+            let kernel_dir = output_base.join(kernel.to_string());
+            let best_dir = kernel_dir.join("best");
+            fs::create_dir_all(&best_dir)?;
+            let actions = &results[summary.best_repeat].actions;
+            write!(
+                fs::File::create(best_dir.join("actions.json"))?,
+                "{}",
+                serde_json::to_string(actions)?
+            )?;
+            serde_json::to_writer_pretty(
+                fs::File::create(kernel_dir.join("summary.json"))?,
+                &summary,
+            )?;
+            println!(
+                "{}: {:.2e}ns (+/-{:.2e}ns) over {} repeats, best: {:.2e}ns (repeat {}), \
+                 median speedup: {:.2}",
+                kernel,
+                summary.mean,
+                summary.std_dev,
+                summary.runtimes.len(),
+                summary.runtimes[summary.best_repeat],
+                summary.best_repeat,
+                summary.median_speedup,
+            );
+        }
+
         Ok(())
     }
 }
 
+/// Outcome of a single repeat of `search` on a kernel.
+#[derive(Clone)]
+struct RepeatResult {
+    /// Cleaned mean runtime of the best implementation, in nanoseconds.
+    runtime: f64,
+    /// Cleaned mean runtime of the reference implementation, in nanoseconds.
+    reference: f64,
+    /// Actions leading to the best implementation.
+    actions: Vec<Action>,
+}
+
+/// Aggregates the repeats of `search` on a kernel.  It is written to
+/// `<output_dir>/<kernel>/summary.json`, next to the actions of the best implementation
+/// across all repeats in `<output_dir>/<kernel>/best/actions.json`.
+#[derive(Serialize)]
+struct SearchSummary {
+    kernel: String,
+    /// Seed of the random data the kernel was checked against.
+    seed: u64,
+    /// Runtime of the best implementation found by each repeat, in nanoseconds.
+    runtimes: Vec<f64>,
+    mean: f64,
+    std_dev: f64,
+    /// Index of the repeat that found the fastest implementation.
+    best_repeat: usize,
+    /// Speedup of the best implementation of each repeat over the reference.
+    speedups: Vec<f64>,
+    min_speedup: f64,
+    median_speedup: f64,
+    max_speedup: f64,
+}
+
+impl SearchSummary {
+    fn new(kernel: &KernelParam, seed: u64, results: &[RepeatResult]) -> Self {
+        let runtimes = results.iter().map(|r| r.runtime).collect::<Vec<_>>();
+        let speedups = results
+            .iter()
+            .map(|r| r.reference / r.runtime)
+            .collect::<Vec<_>>();
+        let best_repeat = (0..runtimes.len())
+            .min_by(|&lhs, &rhs| telamon_utils::cmp_f64(runtimes[lhs], runtimes[rhs]))
+            .expect("no repeats to summarize");
+        SearchSummary {
+            kernel: kernel.to_string(),
+            seed,
+            mean: statistics::mean(&runtimes),
+            std_dev: statistics::std_dev(&runtimes),
+            best_repeat,
+            min_speedup: speedups.iter().cloned().fold(std::f64::INFINITY, f64::min),
+            median_speedup: statistics::median(&speedups),
+            max_speedup: speedups.iter().cloned().fold(0., f64::max),
+            runtimes,
+            speedups,
+        }
+    }
+}
+
+/// Complete and evaluate descents on behalf of a search started with `--listen`
+///
+/// The worker and the coordinator must share the same token in `TELAMON_WORKER_TOKEN`.
+#[derive(StructOpt)]
+struct Worker {
+    #[structopt(flatten)]
+    common: CommonOpt,
+
+    /// Kernel searched by the coordinator.
+    #[structopt(short = "k", long = "kernel")]
+    kernel: KernelParam,
+
+    #[structopt(long = "platform", default_value = "cuda")]
+    platform: Platform,
+
+    /// Address of the coordinator, as `host:port`.
+    #[structopt(long = "coordinator")]
+    coordinator: String,
+}
+
+impl Worker {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let config = self.common.config()?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
+        assert!(
+            bundle.candidates.len() == 1,
+            "Multi-candidates bundle not supported"
+        );
+
+        let check_fn = &bundle.check_fn;
+        explorer::distributed::work(
+            &config,
+            context,
+            bundle.candidates.into_iter().next().unwrap(),
+            &self.coordinator,
+            Some(&move |_, context| check_fn(context)),
+        )
+    }
+}
+
 /// Compute the bound for a given candidate.
 #[derive(StructOpt)]
 struct ComputeBound {
@@ -119,10 +341,15 @@ struct ComputeBound {
 }
 
 impl ComputeBound {
-    fn run(&self, _args: &Opt) -> io::Result<()> {
-        let builder = self.platform.to_builder();
-        let mut context = builder.build_context();
-        let (bundle, context) = context.kernel_bundle(&self.kernel);
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
         let mut candidates = bundle.candidates;
 
         assert!(candidates.len() == 1);
@@ -222,7 +449,9 @@ impl Bounds {
         let stabilizer = &context.stabilizer();
         context.async_eval(
             num_cpus::get(),
+            0,
             device::EvalMode::TestBound,
+            &device::CancellationToken::new(),
             &|evaluator| loop {
                 // We want to keep the collapsible if to make the order in which `fetch_add` and
                 // `fetch_sub` explicit.
@@ -264,10 +493,15 @@ impl Bounds {
         );
     }
 
-    fn run(&self, _args: &Opt) -> io::Result<()> {
-        let builder = self.platform.to_builder();
-        let mut context = builder.build_context();
-        let (bundle, context) = context.kernel_bundle(&self.kernel);
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
         let stdout = std::io::stdout();
         self.test_bound(bundle.candidates, context, |(runtime, bounds)| {
             let mut handle = stdout.lock();
@@ -299,10 +533,15 @@ struct Codegen {
 }
 
 impl Codegen {
-    fn run(&self, _args: &Opt) -> io::Result<()> {
-        let builder = self.platform.to_builder();
-        let mut context = builder.build_context();
-        let (bundle, context) = context.kernel_bundle(&self.kernel);
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
         let mut candidates = bundle.candidates;
         assert!(candidates.len() == 1);
 
@@ -320,6 +559,39 @@ impl Codegen {
     }
 }
 
+/// Returns the actions leading to `candidate` in the order they were applied, which is the
+/// order expected by replay files.  `Candidate::actions` lists the last action first.
+fn replay_actions(candidate: &Candidate) -> Vec<Action> {
+    let mut actions = candidate.actions.iter().cloned().collect::<Vec<_>>();
+    actions.reverse();
+    actions
+}
+
+/// Applies `actions` to the candidate of a bundle, and ensures the resulting candidate is
+/// fully specified.
+fn build_fixed(bundle: &KernelBundle<'_>, actions: &[Action]) -> io::Result<SearchSpace> {
+    assert!(
+        bundle.candidates.len() == 1,
+        "Multi-candidates bundle not supported"
+    );
+
+    let mut candidate = bundle.candidates[0].space.clone();
+    for action in actions {
+        candidate = action
+            .apply_to(candidate)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    }
+
+    if default_list(&candidate).next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "Final candidate is not fixed",
+        ));
+    }
+
+    Ok(candidate)
+}
+
 #[derive(StructOpt)]
 struct Benchmark {
     #[structopt(parse(from_os_str))]
@@ -351,26 +623,7 @@ impl Benchmark {
         bundle: &KernelBundle<'_>,
         replay: &ReplayPath,
     ) -> io::Result<SearchSpace> {
-        assert!(
-            bundle.candidates.len() == 1,
-            "Multi-candidates bundle not supported"
-        );
-
-        let mut candidate = bundle.candidates[0].space.clone();
-        for action in &replay.load()? {
-            candidate = action
-                .apply_to(candidate)
-                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-        }
-
-        if default_list(&candidate).next().is_some() {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Final candidate is not fixed",
-            ));
-        }
-
-        Ok(candidate)
+        build_fixed(bundle, &replay.load()?)
     }
 
     fn iter_replays(&self) -> impl Iterator<Item = io::Result<ReplayPath>> + '_ {
@@ -400,10 +653,15 @@ impl Benchmark {
         })
     }
 
-    fn run(&self, _args: &Opt) -> io::Result<()> {
-        let builder = self.platform.to_builder();
-        let mut context = builder.build_context();
-        let (bundle, context) = context.kernel_bundle(&self.kernel);
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
         assert!(bundle.candidates.len() == 1);
 
         let reference = Bench::default()
@@ -416,7 +674,7 @@ impl Benchmark {
             println!("{},{}", self.reference_name, reference.iter().format(","));
         };
 
-        let reference_estimate = estimate_mean(reference, 0.95, "ns");
+        let reference_estimate = estimate_cleaned_mean(reference, 0.95, "ns");
 
         let mut failed = false;
         for replay in self.iter_replays() {
@@ -455,9 +713,11 @@ impl Benchmark {
             } else {
                 let bound = bound(&candidate, context);
                 println!("bound: {}", bound);
+                println!("launch: {}", code.launch_config(context));
 
-                let self_estimate = estimate_mean(runtimes, 0.95, "ns");
-                let speedup = reference_estimate.value / self_estimate.value;
+                let self_estimate = estimate_cleaned_mean(runtimes, 0.95, "ns");
+                let speedup =
+                    reference_estimate.cleaned.value / self_estimate.cleaned.value;
                 println!(
                     "runtime: {}, reference: {} (speedup: {:.2})",
                     self_estimate, reference_estimate, speedup,
@@ -521,7 +781,11 @@ impl Rebuild {
                     bound,
                     discovery_time,
                 } => tree.extend(id, discovery_time, parent, bound, &mut children),
-                mcts::Message::Trace { .. } => (),
+                mcts::Message::Trace { .. }
+                | mcts::Message::Screening { .. }
+                | mcts::Message::Launch { .. }
+                | mcts::Message::Source { .. }
+                | mcts::Message::Energy { .. } => (),
                 mcts::Message::Evaluation { id, value, .. } => {
                     if let Some(score) = value {
                         if Some(nevals) == target.last().cloned() {
@@ -664,6 +928,10 @@ impl Stats {
                         evalns.push(value.log(10.));
                     }
                 }
+                mcts::Message::Screening { .. }
+                | mcts::Message::Launch { .. }
+                | mcts::Message::Source { .. }
+                | mcts::Message::Energy { .. } => (),
             }
 
             if self.limit.map(|limit| nimpl >= limit).unwrap_or(false) {
@@ -720,53 +988,1678 @@ impl Stats {
     }
 }
 
+/// Output format for reports.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ReportFormat {
+    Markdown,
+    Csv,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "markdown" | "md" => ReportFormat::Markdown,
+            "csv" => ReportFormat::Csv,
+            _ => return Err(format!("invalid report format: {}", s)),
+        })
+    }
+}
+
+/// Summary of a search, computed from its eventlog.
+#[derive(Default)]
+struct LogSummary {
+    /// Number of evaluations so far and best evaluation, each time the best is improved.
+    best_curve: Vec<(usize, f64)>,
+    num_evaluations: usize,
+    num_implementations: usize,
+    num_deadends: usize,
+    num_nodes: usize,
+    /// Discovery time of the last node.
+    duration: Duration,
+    /// Number of evaluated implementations using each choice value, indexed by choice and
+    /// value.
+    choice_values: BTreeMap<(&'static str, String), usize>,
+}
+
+impl LogSummary {
+    fn from_eventlog(path: &Path) -> io::Result<Self> {
+        let mut summary = LogSummary::default();
+        CandidateTree::new().replay_eventlog(path, |tree, message| {
+            match message {
+                mcts::Message::Node { discovery_time, .. } => {
+                    summary.num_nodes += 1;
+                    summary.duration = discovery_time;
+                }
+                mcts::Message::Trace { events, .. } => {
+                    for event in &events {
+                        match event.value {
+                            mcts::Event::Kill(_) | mcts::Event::KillChild(..) => {
+                                summary.num_deadends += 1
+                            }
+                            mcts::Event::Implementation => {
+                                summary.num_implementations += 1
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                mcts::Message::Evaluation { id, value, .. } => {
+                    summary.num_evaluations += 1;
+                    for action in tree.get_node(id).actions() {
+                        if let Some(key) = Self::choice_value(&action) {
+                            *summary.choice_values.entry(key).or_insert(0) += 1;
+                        }
+                    }
+                    if let Some(value) = value {
+                        if summary.best().map(|best| value < best).unwrap_or(true) {
+                            summary.best_curve.push((summary.num_evaluations, value));
+                        }
+                    }
+                }
+                mcts::Message::Screening { .. }
+                | mcts::Message::Launch { .. }
+                | mcts::Message::Source { .. }
+                | mcts::Message::Energy { .. } => (),
+            }
+            Ok(())
+        })?;
+        Ok(summary)
+    }
+
+    /// Returns the choice and value of an action, for the choices we track the distribution of.
+    fn choice_value(action: &Action) -> Option<(&'static str, String)> {
+        use telamon::search_space::Action as A;
+
+        match action {
+            Action::Action(A::DimKind(_, kind)) => {
+                Some(("dim_kind", format!("{:?}", kind)))
+            }
+            Action::Action(A::Order(_, _, order)) => {
+                Some(("order", format!("{:?}", order)))
+            }
+            Action::Action(A::InstFlag(_, flag)) => {
+                Some(("inst_flag", format!("{:?}", flag)))
+            }
+            Action::Action(A::MemSpace(_, space)) => {
+                Some(("mem_space", format!("{:?}", space)))
+            }
+            Action::Action(A::Precision(_, precision)) => {
+                Some(("precision", format!("{:?}", precision)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The best evaluation of the search.
+    fn best(&self) -> Option<f64> {
+        self.best_after(self.num_evaluations)
+    }
+
+    /// The best evaluation found in the first `num_evaluations` evaluations.
+    fn best_after(&self, num_evaluations: usize) -> Option<f64> {
+        self.best_curve
+            .iter()
+            .take_while(|&&(num_evals, _)| num_evals <= num_evaluations)
+            .last()
+            .map(|&(_, best)| best)
+    }
+
+    /// Ratio of deadends among the descents, if there was any descent.
+    fn deadend_rate(&self) -> Option<f64> {
+        Self::ratio(
+            self.num_deadends,
+            self.num_deadends + self.num_implementations,
+        )
+    }
+
+    /// Number of nodes discovered per second, if the log spans some time.
+    fn nodes_per_second(&self) -> Option<f64> {
+        let duration = self.duration.as_secs() as f64
+            + f64::from(self.duration.subsec_nanos()) * 1e-9;
+        if duration > 0. {
+            Some(self.num_nodes as f64 / duration)
+        } else {
+            None
+        }
+    }
+
+    /// Ratio of the evaluated implementations using a given choice value, if there was any
+    /// evaluation.
+    fn choice_value_ratio(&self, key: &(&'static str, String)) -> Option<f64> {
+        let count = self.choice_values.get(key).cloned().unwrap_or(0);
+        Self::ratio(count, self.num_evaluations)
+    }
+
+    /// Returns `num / denom`, or `None` if `denom` is zero.
+    fn ratio(num: usize, denom: usize) -> Option<f64> {
+        if denom == 0 {
+            None
+        } else {
+            Some(num as f64 / denom as f64)
+        }
+    }
+}
+
+/// Compare two searches from their eventlogs.
+///
+/// This aligns the best-so-far curves by number of evaluations, and compares the final bests,
+/// deadend rates, exploration speed and distribution of the values of some choices among the
+/// evaluated implementations.  This is intended to evaluate changes to search heuristics.
+#[derive(StructOpt)]
+struct CompareLogs {
+    /// Path to the eventlog of the baseline search.
+    #[structopt(parse(from_os_str))]
+    baseline: PathBuf,
+
+    /// Path to the eventlog of the search to compare with the baseline.
+    #[structopt(parse(from_os_str))]
+    other: PathBuf,
+
+    /// Format of the report.  Either `markdown` or `csv`.
+    #[structopt(long = "format", default_value = "markdown")]
+    format: ReportFormat,
+
+    /// Number of points to display on the best-so-far curves.
+    #[structopt(long = "num-points", default_value = "10")]
+    num_points: usize,
+}
+
+impl CompareLogs {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let baseline = LogSummary::from_eventlog(&self.baseline)?;
+        let other = LogSummary::from_eventlog(&self.other)?;
+
+        // Statistics that are not defined, for instance on an empty log, are left blank.
+        let fmt_best =
+            |best: Option<f64>| best.map(|b| format!("{:.4e}", b)).unwrap_or_default();
+        let fmt_ratio = |ratio: Option<f64>, precision: usize| {
+            ratio
+                .map(|ratio| format!("{:.*}", precision, ratio))
+                .unwrap_or_default()
+        };
+        let mut rows = vec![
+            (
+                "summary",
+                "evaluations".to_string(),
+                baseline.num_evaluations.to_string(),
+                other.num_evaluations.to_string(),
+            ),
+            (
+                "summary",
+                "best (ns)".to_string(),
+                fmt_best(baseline.best()),
+                fmt_best(other.best()),
+            ),
+            (
+                "summary",
+                "deadend rate".to_string(),
+                fmt_ratio(baseline.deadend_rate(), 3),
+                fmt_ratio(other.deadend_rate(), 3),
+            ),
+            (
+                "summary",
+                "nodes/s".to_string(),
+                fmt_ratio(baseline.nodes_per_second(), 1),
+                fmt_ratio(other.nodes_per_second(), 1),
+            ),
+        ];
+
+        let max_evaluations = cmp::max(baseline.num_evaluations, other.num_evaluations);
+        let num_points = cmp::min(self.num_points, max_evaluations);
+        for point in 1..=num_points {
+            let num_evaluations = point * max_evaluations / num_points;
+            rows.push((
+                "best_so_far",
+                num_evaluations.to_string(),
+                fmt_best(baseline.best_after(num_evaluations)),
+                fmt_best(other.best_after(num_evaluations)),
+            ));
+        }
+
+        let keys = baseline
+            .choice_values
+            .keys()
+            .chain(other.choice_values.keys())
+            .collect::<BTreeSet<_>>();
+        for key in keys {
+            rows.push((
+                "choice_values",
+                format!("{} = {}", key.0, key.1),
+                fmt_ratio(baseline.choice_value_ratio(key), 3),
+                fmt_ratio(other.choice_value_ratio(key), 3),
+            ));
+        }
+
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        match self.format {
+            ReportFormat::Csv => {
+                writeln!(out, "section,key,baseline,other")?;
+                for (section, key, lhs, rhs) in rows {
+                    writeln!(out, "{},\"{}\",{},{}", section, key, lhs, rhs)?;
+                }
+            }
+            ReportFormat::Markdown => {
+                let headers = [
+                    ("summary", "Metric"),
+                    ("best_so_far", "Evaluations"),
+                    ("choice_values", "Choice value (ratio of evaluations)"),
+                ];
+                for (section, header) in &headers {
+                    writeln!(out, "## {}\n", section)?;
+                    writeln!(out, "| {} | baseline | other |", header)?;
+                    writeln!(out, "|---|---|---|")?;
+                    for (_, key, lhs, rhs) in rows.iter().filter(|row| row.0 == *section)
+                    {
+                        writeln!(out, "| {} | {} | {} |", key, lhs, rhs)?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Benchmark the hot paths of the search itself on representative kernels.
+///
+/// Kernels are built in a fake context, so no GPU is needed.
 #[derive(StructOpt)]
-enum Command {
-    #[structopt(name = "benchmark")]
-    Benchmark(Benchmark),
+struct SelfBench {
+    /// Number of runs for each benchmark.
+    #[structopt(long = "num-runs", default_value = "100")]
+    num_runs: usize,
+}
 
-    #[structopt(name = "codegen")]
-    Codegen(Codegen),
+impl SelfBench {
+    /// Returns the time taken by `f`, in nanoseconds.
+    fn time<T, F: FnOnce() -> T>(f: F) -> f64 {
+        let start = std::time::Instant::now();
+        std::mem::drop(f());
+        let duration = start.elapsed();
+        duration.as_secs() as f64 * 1e9 + f64::from(duration.subsec_nanos())
+    }
 
-    #[structopt(name = "rebuild")]
-    Rebuild(Rebuild),
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let bench = Bench::default().runs(self.num_runs);
+        for kernel in search_bench::kernels() {
+            let report = |path: &str, runtimes: Vec<f64>| {
+                println!(
+                    "{} {}: {}",
+                    kernel.name,
+                    path,
+                    estimate_mean(runtimes, 0.95, "ns")
+                );
+            };
 
-    #[structopt(name = "bounds")]
-    Bounds(Bounds),
+            report(
+                "clone",
+                bench.benchmark_fn(|| Self::time(|| kernel.clone_root())),
+            );
+            report(
+                "apply_choice",
+                bench.benchmark_fn(|| Self::time(|| kernel.apply_choice())),
+            );
+            report(
+                "bound",
+                bench.benchmark_fn(|| Self::time(|| kernel.bound())),
+            );
 
-    #[structopt(name = "stats")]
-    Stats(Stats),
+            let implementation = kernel.implementation();
+            report(
+                "fix_order",
+                bench.benchmark_fn(|| Self::time(|| kernel.fix_order(&implementation))),
+            );
 
-    #[structopt(name = "bound")]
-    Bound(ComputeBound),
+            let config = config::BanditConfig::default();
+            let mcts = kernel.mcts(&config);
+            let exhausted = atomic::AtomicBool::new(false);
+            let runtimes = bench.benchmark_fn(|| {
+                Self::time(|| {
+                    if !mcts.step() {
+                        exhausted.store(true, atomic::Ordering::Relaxed);
+                    }
+                })
+            });
+            if exhausted.load(atomic::Ordering::Relaxed) {
+                println!("{} mcts step: search tree exhausted", kernel.name);
+            } else {
+                report("mcts step", runtimes);
+            }
+        }
 
-    #[structopt(name = "search")]
-    Search(Search),
+        Ok(())
+    }
 }
 
+/// Prints the schedule of a candidate to stdout, in JSON format.
+///
+/// The schedule lists the loops, their nesting and mapping to the hardware, and the memory
+/// spaces of temporary arrays, in a format independent of Telamon's internal representation.
 #[derive(StructOpt)]
-#[structopt(name = "telamon")]
-struct Opt {
-    #[structopt(subcommand)]
-    command: Command,
+struct ExportSchedule {
+    /// Path to the replay file of the candidate.  Must be compatible with the provided kernel.
+    #[structopt(parse(from_os_str))]
+    replay: ReplayPath,
+
+    /// Kernel specification to use.
+    #[structopt(short = "k", long = "kernel")]
+    kernel: KernelParam,
+
+    #[structopt(long = "platform", short = "p", default_value = "cuda")]
+    platform: Platform,
 }
 
-fn main() {
-    let args = Opt::from_args();
-    env_logger::init();
+impl ExportSchedule {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, _) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
+        let candidate = build_fixed(&bundle, &self.replay.load()?)?;
 
-    let result = match &args.command {
-        Command::Benchmark(benchmark) => benchmark.run(&args),
-        Command::Codegen(codegen) => codegen.run(&args),
-        Command::Rebuild(rebuild) => rebuild.run(&args),
-        Command::Bounds(bounds) => bounds.run(&args),
-        Command::Stats(stats) => stats.run(&args),
-        Command::Bound(bound) => bound.run(&args),
-        Command::Search(search) => search.run(&args),
-    };
+        let schedule = telamon::codegen::Schedule::from_space(&candidate);
+        serde_json::to_writer_pretty(io::stdout(), &schedule)?;
+        println!();
 
-    match result {
-        Ok(()) => (),
-        Err(err) => panic!("An error occured: {}", err),
+        Ok(())
+    }
+}
+
+/// Benchmarks a single schedule across kernels of different sizes.
+///
+/// The actions from the replay file are applied to each kernel, and the runtime of the
+/// resulting implementation is printed in CSV format.  This characterizes how far a schedule
+/// tuned for a given size generalizes to other sizes.  Kernels on which the actions cannot be
+/// applied are reported as invalid.
+///
+/// With `--scalar name=v1,v2,...`, each kernel is compiled once and then run with each
+/// value of the scalar parameter `name`, without compiling it again.  The correctness check
+/// is skipped in that case, as the reference uses the original value of the parameter.
+#[derive(StructOpt)]
+struct Sweep {
+    /// Path to the replay file containing the schedule.
+    #[structopt(parse(from_os_str), short = "r", long = "replay")]
+    replay: ReplayPath,
+
+    /// Kernel specifications to sweep over.  They should only differ by their sizes.
+    #[structopt(short = "k", long = "kernel")]
+    kernels: Vec<KernelParam>,
+
+    #[structopt(long = "platform", short = "p", default_value = "cuda")]
+    platform: Platform,
+
+    /// Number of times to run each benchmark.
+    #[structopt(long = "bench-runs", default_value = "40")]
+    num_bench_runs: usize,
+
+    /// Values of a scalar parameter to run the compiled kernels with, as `name=v1,v2,...`.
+    #[structopt(long = "scalar")]
+    scalar: Option<ScalarValues>,
+}
+
+/// A list of values for a scalar parameter.
+struct ScalarValues {
+    name: String,
+    values: Vec<String>,
+}
+
+impl std::str::FromStr for ScalarValues {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pos = s
+            .find('=')
+            .ok_or_else(|| format!("expected `name=v1,v2,...`, got {}", s))?;
+        let (name, values) = (&s[..pos], &s[pos + 1..]);
+        Ok(ScalarValues {
+            name: name.to_string(),
+            values: values.split(',').map(str::to_string).collect(),
+        })
+    }
+}
+
+/// Parses a value for a scalar parameter of type `t`.
+fn parse_scalar(
+    t: ir::Type,
+    value: &str,
+) -> Result<Box<dyn device::ScalarArgument>, String> {
+    fn parse<T>(value: &str) -> Result<Box<dyn device::ScalarArgument>, String>
+    where
+        T: device::ScalarArgument + std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let value = value.parse::<T>().map_err(|err| err.to_string())?;
+        Ok(Box::new(value))
+    }
+
+    match t {
+        ir::Type::F(32) => parse::<f32>(value),
+        ir::Type::F(64) => parse::<f64>(value),
+        ir::Type::I(8) => parse::<i8>(value),
+        ir::Type::I(16) => parse::<i16>(value),
+        ir::Type::I(32) => parse::<i32>(value),
+        ir::Type::I(64) => parse::<i64>(value),
+        t => Err(format!("unsupported parameter type: {}", t)),
+    }
+}
+
+impl Sweep {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let actions = self.replay.load()?;
+
+        println!("kernel,status,bound,runtime,interval");
+        for kernel in &self.kernels {
+            let mut context = builder.build_context()?;
+            let (bundle, context) = context.kernel_bundle(
+                kernel,
+                args.dtype,
+                args.seed,
+                args.accuracy_budget,
+            );
+
+            let candidate = match build_fixed(&bundle, &actions) {
+                Ok(candidate) => candidate,
+                Err(err) => {
+                    eprintln!("Unable to apply the schedule to {}: {}", kernel, err);
+                    println!("{},invalid,,,", kernel);
+                    continue;
+                }
+            };
+
+            let bound = bound(&candidate, context);
+            let code = telamon::codegen::Function::build(&candidate);
+            if let Some(scalar) = &self.scalar {
+                self.sweep_scalar(kernel, scalar, &code, context, bound.value())?;
+                continue;
+            }
+
+            let runtimes = context.benchmark(&code, self.num_bench_runs);
+            if let Err(err) = (bundle.check_fn)(context) {
+                eprintln!("Check error for {}: {}", kernel, err);
+                println!("{},incorrect,{},,", kernel, bound.value());
+                continue;
+            }
+
+            let estimate = estimate_mean(runtimes, 0.95, "ns");
+            println!(
+                "{},ok,{},{},{}",
+                kernel,
+                bound.value(),
+                estimate.value,
+                estimate.interval
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Runs `code` with each value of `scalar`, compiling it only once.
+    fn sweep_scalar(
+        &self,
+        kernel: &KernelParam,
+        scalar: &ScalarValues,
+        code: &telamon::codegen::Function,
+        context: &dyn device::Context,
+        bound: f64,
+    ) -> io::Result<()> {
+        let t = code
+            .updatable_params()
+            .find(|param| param.name == scalar.name)
+            .map(|param| param.t)
+            .ok_or_else(|| {
+                let err = device::ParamUpdateError::UnknownParam(scalar.name.clone());
+                io::Error::new(io::ErrorKind::Other, err)
+            })?;
+        let mut evaluator = context.compile_kernel(code);
+        for value in &scalar.values {
+            let arg = parse_scalar(t, value)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            evaluator
+                .set_scalar(&scalar.name, arg)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            let label = format!("{}:{}={}", kernel, scalar.name, value);
+            let runtimes = (0..self.num_bench_runs)
+                .map(|_| evaluator.evaluate())
+                .collect::<Option<Vec<_>>>();
+            if let Some(runtimes) = runtimes {
+                let estimate = estimate_mean(runtimes, 0.95, "ns");
+                println!(
+                    "{},ok,{},{},{}",
+                    label, bound, estimate.value, estimate.interval
+                );
+            } else {
+                println!("{},failed,{},,", label, bound);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns `kernels`, or the kernels listed in `golden_dir` if `kernels` is empty.
+fn golden_kernels(
+    kernels: &[KernelParam],
+    golden_dir: &Path,
+) -> io::Result<Vec<KernelParam>> {
+    if kernels.is_empty() {
+        Golden::kernels(golden_dir)
+    } else {
+        Ok(kernels.to_vec())
+    }
+}
+
+/// Records the best implementation of small kernels, found by an exhaustive search.
+///
+/// All the candidates that cannot be pruned are evaluated, so this is only tractable for tiny
+/// problem instances.  The best implementation of each kernel is stored in a golden file,
+/// which `golden-check` uses as a reference.
+#[derive(StructOpt)]
+struct GoldenRecord {
+    /// Defaults to the kernels listed in the golden directory.
+    #[structopt(short = "k", long = "kernel")]
+    kernels: Vec<KernelParam>,
+
+    #[structopt(long = "platform", default_value = "cuda")]
+    platform: Platform,
+
+    /// Directory containing the golden files.
+    #[structopt(parse(from_os_str), long = "golden-dir", default_value = "golden")]
+    golden_dir: PathBuf,
+
+    /// Number of times to run the generated code to evaluate its performance.
+    #[structopt(long = "num-code-runs", default_value = "40")]
+    num_code_runs: usize,
+}
+
+impl GoldenRecord {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut config = config::Config::default();
+        config.algorithm = config::SearchAlgorithm::BoundOrder;
+
+        for kernel in &golden_kernels(&self.kernels, &self.golden_dir)? {
+            let mut context = builder.build_context()?;
+            let (bundle, context) = context.kernel_bundle(
+                kernel,
+                args.dtype,
+                args.seed,
+                args.accuracy_budget,
+            );
+            let best = explorer::find_best_ex(
+                &config,
+                context,
+                bundle.candidates,
+                Some({
+                    let check_fn = &bundle.check_fn;
+                    &move |_, context| check_fn(context)
+                }),
+                &[],
+            )
+            .unwrap_or_else(|| panic!("no candidates found for kernel {}", kernel));
+
+            let best_fn = telamon::codegen::Function::build(&best.space);
+            let runtimes = context.benchmark(&best_fn, self.num_code_runs);
+            let golden = Golden {
+                kernel: kernel.to_string(),
+                device: context.device().name().to_string(),
+                runtime: estimate_mean(runtimes, 0.95, "ns").value,
+                actions: replay_actions(&best),
+            };
+            let path = Golden::path(&self.golden_dir, &golden.kernel, &golden.device);
+            golden.save(&path)?;
+            println!(
+                "{}: {:.4e}ns, saved to {}",
+                kernel,
+                golden.runtime,
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the search finds implementations close to the ones recorded by
+/// `golden-record`.
+///
+/// The search should be bounded, for instance with `--max-evaluations`, so that the check
+/// guards against regressions of the search quality under a fixed budget.  The golden
+/// implementation is benchmarked again when it can still be built, so that the comparison
+/// does not depend on the state of the machine when it was recorded.
+#[derive(StructOpt)]
+struct GoldenCheck {
+    #[structopt(flatten)]
+    common: CommonOpt,
+
+    /// Defaults to the kernels listed in the golden directory.
+    #[structopt(short = "k", long = "kernel")]
+    kernels: Vec<KernelParam>,
+
+    #[structopt(long = "platform", default_value = "cuda")]
+    platform: Platform,
+
+    /// Directory containing the golden files.
+    #[structopt(parse(from_os_str), long = "golden-dir", default_value = "golden")]
+    golden_dir: PathBuf,
+
+    /// Maximal slowdown compared to the golden implementation, in percents.
+    #[structopt(long = "tolerance", default_value = "10")]
+    tolerance: f64,
+
+    /// Number of times to run the generated code to evaluate its performance.
+    #[structopt(long = "num-code-runs", default_value = "40")]
+    num_code_runs: usize,
+}
+
+impl GoldenCheck {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let config = self.common.config()?;
+
+        let mut num_regressions = 0;
+        for kernel in &golden_kernels(&self.kernels, &self.golden_dir)? {
+            let mut context = builder.build_context()?;
+            let (bundle, context) = context.kernel_bundle(
+                kernel,
+                args.dtype,
+                args.seed,
+                args.accuracy_budget,
+            );
+            let device = context.device();
+            let path = Golden::path(&self.golden_dir, &kernel.to_string(), device.name());
+            let golden = Golden::load(&path).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("unable to load {}: {}", path.display(), err),
+                )
+            })?;
+
+            let reference = match build_fixed(&bundle, &golden.actions) {
+                Ok(space) => {
+                    let code = telamon::codegen::Function::build(&space);
+                    let runtimes = context.benchmark(&code, self.num_code_runs);
+                    estimate_mean(runtimes, 0.95, "ns").value
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Unable to rebuild the golden implementation of {}, using the \
+                         recorded runtime: {}",
+                        kernel, err
+                    );
+                    golden.runtime
+                }
+            };
+
+            let best = explorer::find_best_ex(
+                &config,
+                context,
+                bundle.candidates,
+                Some({
+                    let check_fn = &bundle.check_fn;
+                    &move |_, context| check_fn(context)
+                }),
+                &[],
+            )
+            .unwrap_or_else(|| panic!("no candidates found for kernel {}", kernel));
+            let best_fn = telamon::codegen::Function::build(&best.space);
+            let runtimes = context.benchmark(&best_fn, self.num_code_runs);
+            let runtime = estimate_mean(runtimes, 0.95, "ns").value;
+
+            let slowdown = (runtime / reference - 1.) * 100.;
+            let status = if slowdown <= self.tolerance {
+                "ok"
+            } else {
+                num_regressions += 1;
+                "REGRESSION"
+            };
+            println!(
+                "{}: {:.4e}ns, golden {:.4e}ns ({:+.2}%) {}",
+                kernel, runtime, reference, slowdown, status
+            );
+        }
+
+        if num_regressions > 0 {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "{} kernel(s) more than {}% slower than their golden implementation",
+                    num_regressions, self.tolerance
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Prints the propagation cascades recorded in a trace.
+///
+/// Traces are written by binaries built with the `trace_propagation` feature of `telamon`
+/// when the `TELAMON_PROPAGATION_TRACE` environment variable is set.  Each cascade starts
+/// with an event that was not caused by the propagation, usually an action of the explorer,
+/// and lists the callbacks and filters it triggered.
+#[derive(StructOpt)]
+struct PropagationTrace {
+    /// Path to the trace.
+    #[structopt(parse(from_os_str))]
+    trace: PathBuf,
+
+    /// Only print the cascades whose root event contains this string.
+    #[structopt(long = "action")]
+    action: Option<String>,
+}
+
+impl PropagationTrace {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let events = trace::read_trace(&self.trace)?;
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        for cascade in trace::Cascade::build(&events) {
+            if let Some(action) = &self.action {
+                if !cascade.event.to_string().contains(action.as_str()) {
+                    continue;
+                }
+            }
+            writeln!(stdout, "{} events:", cascade.num_events())?;
+            cascade.write(&mut stdout, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Searches implementations for the layers of an ONNX model.
+///
+/// The layers are imported as by `onnx_kernels`, and each of them is searched as with
+/// `search`.  The actions of the best implementation of the layer at position `i` are
+/// written to `<output_dir>/layer<i>/best/actions.json`.
+#[derive(StructOpt)]
+struct Onnx {
+    #[structopt(flatten)]
+    common: CommonOpt,
+
+    /// Path to the ONNX model.
+    #[structopt(parse(from_os_str))]
+    model: PathBuf,
+
+    #[structopt(long = "platform", default_value = "cuda")]
+    platform: Platform,
+
+    /// Number of times to run the generated code to evaluate its performance.
+    #[structopt(long = "num-code-runs", default_value = "40")]
+    num_code_runs: usize,
+}
+
+impl Onnx {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let import = telamon_onnx::import_file(&self.model).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("cannot import {}: {}", self.model.display(), err),
+            )
+        })?;
+        for skipped in &import.skipped {
+            eprintln!(
+                "skipped node `{}` ({}): {}",
+                skipped.node, skipped.op_type, skipped.reason
+            );
+        }
+
+        let builder = args.context_builder(self.platform)?;
+        let mut config = self.common.config()?;
+        let output_base = Path::new(&config.output_dir).to_owned();
+        let mut total_runtime = 0.;
+        for (idx, layer) in import.layers.iter().enumerate() {
+            let name = layer.nodes.join("+");
+            let layer_dir = output_base.join(format!("layer{}", idx));
+            config.output_dir = layer_dir.to_str().unwrap().to_string();
+
+            let mut context = builder.build_context()?;
+            let (bundle, context) = context.kernel_bundle(
+                &layer.kernel,
+                args.dtype,
+                args.seed,
+                args.accuracy_budget,
+            );
+            let best = explorer::find_best_ex(
+                &config,
+                context,
+                bundle.candidates,
+                Some({
+                    let check_fn = &bundle.check_fn;
+                    &move |_, context| check_fn(context)
+                }),
+                &[],
+            )
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("no candidates found for layer {} ({})", idx, name),
+                )
+            })?;
+
+            let best_fn = telamon::codegen::Function::build(&best.space);
+            let runtimes = context.benchmark(&best_fn, self.num_code_runs);
+            let runtime = estimate_mean(runtimes, 0.95, "ns").value;
+            total_runtime += runtime;
+
+            let best_dir = layer_dir.join("best");
+            fs::create_dir_all(&best_dir)?;
+            serde_json::to_writer(
+                fs::File::create(best_dir.join("actions.json"))?,
+                &replay_actions(&best),
+            )?;
+            println!("layer {} ({}): {:.4e}ns", idx, name, runtime);
+        }
+        println!("{} layers: {:.4e}ns", import.layers.len(), total_runtime);
+
+        Ok(())
+    }
+}
+
+/// Merges the event logs of several searches into a single event log.
+///
+/// This is meant to combine the results of searches that explored different parts of the same
+/// search space, e.g. on several machines.  Node identifiers are renumbered so that they are
+/// unique in the merged log, and messages are interleaved by timestamp.  Timestamps are kept
+/// relative to the start of each search.  The merged log has one root per merged tree.
+#[derive(StructOpt)]
+struct MergeLogs {
+    /// Path to the merged event log.
+    #[structopt(parse(from_os_str))]
+    output: PathBuf,
+
+    /// Paths to the event logs to merge.
+    #[structopt(parse(from_os_str), required = true)]
+    inputs: Vec<PathBuf>,
+}
+
+impl MergeLogs {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let mut readers = self
+            .inputs
+            .iter()
+            .map(EventLog::open)
+            .collect::<io::Result<Vec<_>>>()?
+            .into_iter()
+            .map(|reader| reader.records())
+            .collect::<Vec<_>>();
+        let mut heads = readers
+            .iter_mut()
+            .map(Self::next_message)
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut writer = EventLog::create(&self.output)?;
+        let mut merger = LogMerger::new();
+        let mut num_evaluations = 0;
+        let mut best: Option<(f64, mcts::NodeId, usize)> = None;
+        loop {
+            // Take the oldest message among the heads of the logs.  This keeps the order of the
+            // messages of each log, so that nodes are still declared before being referenced.
+            let index = heads
+                .iter()
+                .enumerate()
+                .filter_map(|(index, head)| {
+                    head.as_ref().map(|message| (message.timestamp(), index))
+                })
+                .min()
+                .map(|(_, index)| index);
+            let index = match index {
+                Some(index) => index,
+                None => break,
+            };
+            let mut message = heads[index].take().unwrap();
+            heads[index] = Self::next_message(&mut readers[index])?;
+
+            merger.rename(index, &mut message);
+            if let mcts::Message::Evaluation { id, value, .. } = message {
+                num_evaluations += 1;
+                if let Some(value) = value {
+                    if best.map(|(best, _, _)| value < best).unwrap_or(true) {
+                        best = Some((value, id, index));
+                    }
+                }
+            }
+            writer.write_record(
+                &bincode::serialize(&message)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
+            )?;
+        }
+        writer
+            .into_inner()
+            .map_err(io::Error::from)?
+            .finish()?
+            .flush()?;
+
+        println!(
+            "Merged {} nodes and {} evaluations from {} logs",
+            merger.num_nodes(),
+            num_evaluations,
+            self.inputs.len()
+        );
+        if let Some((value, id, index)) = best {
+            println!(
+                "Best: {:.4e}ns (node {}, from {})",
+                value,
+                id,
+                self.inputs[index].display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads the next message of an event log.
+    fn next_message<I>(records: &mut I) -> io::Result<Option<mcts::Message>>
+    where
+        I: Iterator<Item = io::Result<Vec<u8>>>,
+    {
+        records
+            .next()
+            .map(|bytes| {
+                bincode::deserialize(&bytes?)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            })
+            .transpose()
+    }
+}
+
+/// Format of the implementations written by `export`.
+enum ExportFormat {
+    /// A single JSON array.
+    Json,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "json" => ExportFormat::Json,
+            "jsonl" => ExportFormat::JsonLines,
+            _ => return Err(format!("invalid export format: {}", s)),
+        })
+    }
+}
+
+/// An evaluated implementation, as exported by `export`.
+#[derive(Serialize)]
+struct ExportedImplementation {
+    id: mcts::NodeId,
+    /// Score of the implementation, if its evaluation succeeded.
+    value: Option<f64>,
+    /// Actions leading to the implementation, in order.
+    actions: Vec<Action>,
+    launch: Option<telamon::codegen::LaunchConfig>,
+    /// Generated source code, if it was recorded in the eventlog.
+    source: Option<String>,
+}
+
+/// Exports the implementations evaluated during a search, with the actions leading to them
+/// and the code generated for them.
+///
+/// The generated code is only available if the search recorded it, with the `log_code`
+/// option; it is omitted otherwise.
+#[derive(StructOpt)]
+struct Export {
+    /// Path to the eventlog to export.
+    #[structopt(
+        parse(from_os_str),
+        short = "i",
+        long = "input",
+        default_value = "eventlog.tfrecord.gz"
+    )]
+    eventlog: PathBuf,
+
+    /// Path to the exported file.  The implementations are written to the standard output
+    /// if omitted.
+    #[structopt(parse(from_os_str), short = "o", long = "output")]
+    output: Option<PathBuf>,
+
+    /// Output format: `json` for a single array or `jsonl` for one implementation per line.
+    #[structopt(long = "format", default_value = "json")]
+    format: ExportFormat,
+}
+
+impl Export {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let mut compiled = HashMap::new();
+        let mut implementations = Vec::new();
+        CandidateTree::new().replay_eventlog(&self.eventlog, |tree, message| {
+            match message {
+                mcts::Message::Launch { id, launch, .. } => {
+                    compiled.entry(id).or_insert((None, None)).0 = Some(launch);
+                }
+                mcts::Message::Source {
+                    id,
+                    actions,
+                    source,
+                    ..
+                } => {
+                    compiled.entry(id).or_insert((None, None)).1 =
+                        Some((actions, source));
+                }
+                mcts::Message::Evaluation { id, value, .. } => {
+                    let (launch, source) = compiled.remove(&id).unwrap_or((None, None));
+                    let (actions, source) = match source {
+                        Some((actions, source)) => (actions, Some(source)),
+                        None => (tree.get_node(id).actions(), None),
+                    };
+                    implementations.push(ExportedImplementation {
+                        id,
+                        value,
+                        actions,
+                        launch,
+                        source,
+                    });
+                }
+                mcts::Message::Node { .. }
+                | mcts::Message::Trace { .. }
+                | mcts::Message::Screening { .. }
+                | mcts::Message::Energy { .. } => (),
+            }
+            Ok(())
+        })?;
+
+        let mut writer: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+        match self.format {
+            ExportFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, &implementations)?;
+                writeln!(writer)?;
+            }
+            ExportFormat::JsonLines => {
+                for implementation in &implementations {
+                    serde_json::to_writer(&mut writer, implementation)?;
+                    writeln!(writer)?;
+                }
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// Explains why a candidate is a dead-end.
+///
+/// The actions of the replay file are applied to the kernel, and each action of the next
+/// choice that cannot be applied is printed along with a minimal set of the replayed actions
+/// it conflicts with.
+#[derive(StructOpt)]
+struct ExplainDeadend {
+    /// Path to the replay file leading to the candidate.
+    #[structopt(parse(from_os_str))]
+    replay: ReplayPath,
+
+    /// Kernel specification to use.
+    #[structopt(short = "k", long = "kernel")]
+    kernel: KernelParam,
+
+    #[structopt(long = "platform", short = "p", default_value = "cuda")]
+    platform: Platform,
+
+    /// Path to which the conflicts are written as a graph in the DOT format.
+    #[structopt(parse(from_os_str), long = "dot")]
+    dot: Option<PathBuf>,
+}
+
+impl ExplainDeadend {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, _) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
+        let mut candidates = bundle.candidates;
+        assert!(candidates.len() == 1);
+        let root = candidates.swap_remove(0).space;
+
+        let actions = self.replay.load()?;
+        let to_io_error = |err| io::Error::new(io::ErrorKind::Other, err);
+        let space = actions
+            .iter()
+            .try_fold(root.clone(), |space, action| action.apply_to(space))
+            .map_err(to_io_error)?;
+        let choice = match default_list(&space).next() {
+            Some(choice) => choice,
+            None => {
+                println!("The candidate is fully specified");
+                return Ok(());
+            }
+        };
+        let explanation =
+            DeadendExplanation::new(&root, &actions, &choice).map_err(to_io_error)?;
+        print!("{}", explanation);
+        if !explanation.is_deadend() {
+            println!("The candidate is not a dead-end");
+        }
+        if let Some(path) = &self.dot {
+            explanation.write_dot(fs::File::create(path)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Exports the candidates evaluated during a search as a dataset to train cost models.
+///
+/// Each evaluated candidate is rebuilt from the kernel to extract its features and
+/// decisions, which are written along with the measured runtime.  The kernel must thus be
+/// the one the eventlog was produced for.
+#[derive(StructOpt)]
+struct ExportDataset {
+    /// Path to the eventlog to export.
+    #[structopt(
+        parse(from_os_str),
+        short = "i",
+        long = "input",
+        default_value = "eventlog.tfrecord.gz"
+    )]
+    eventlog: PathBuf,
+
+    /// Kernel specification the eventlog was produced for.
+    #[structopt(short = "k", long = "kernel")]
+    kernel: KernelParam,
+
+    #[structopt(long = "platform", short = "p", default_value = "cuda")]
+    platform: Platform,
+
+    /// Path to the dataset.  The dataset is written to the standard output if omitted.
+    #[structopt(parse(from_os_str), short = "o", long = "output")]
+    output: Option<PathBuf>,
+
+    /// Format of the dataset: `csv` or `json`.
+    #[structopt(long = "format", default_value = "csv")]
+    format: dataset::Format,
+}
+
+impl ExportDataset {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
+        let mut candidates = bundle.candidates;
+        assert!(candidates.len() == 1);
+        let root = candidates.swap_remove(0).space;
+
+        let dataset = dataset::Dataset::from_eventlog(&self.eventlog, &root, context)?;
+        match &self.output {
+            Some(path) => dataset.write(self.format, fs::File::create(path)?)?,
+            None => dataset.write(self.format, io::stdout())?,
+        }
+        eprintln!("Exported {} candidates", dataset.samples.len());
+        Ok(())
+    }
+}
+
+/// Prints statistics on the search space of a kernel, without evaluating any candidate.
+///
+/// The choice tree is sampled with uniformly random descents.  At each choice, all the
+/// alternatives are applied to count the ones rejected by constraint propagation.  The size
+/// of the space is estimated with Knuth's estimator: the product of the number of valid
+/// alternatives along a descent is an unbiased estimate of the number of implementations.
+#[derive(StructOpt)]
+struct SpaceStats {
+    /// Kernel specification to use.
+    #[structopt(short = "k", long = "kernel")]
+    kernel: KernelParam,
+
+    #[structopt(long = "platform", short = "p", default_value = "cuda")]
+    platform: Platform,
+
+    /// Number of random descents to sample.
+    #[structopt(short = "n", long = "num-descents", default_value = "100")]
+    num_descents: usize,
+}
+
+impl SpaceStats {
+    fn run(&self, args: &Opt) -> io::Result<()> {
+        let builder = args.context_builder(self.platform)?;
+        let mut context = builder.build_context()?;
+        let (bundle, context) = context.kernel_bundle(
+            &self.kernel,
+            args.dtype,
+            args.seed,
+            args.accuracy_budget,
+        );
+        let mut candidates = bundle.candidates;
+        assert!(candidates.len() == 1);
+        let root = candidates.swap_remove(0);
+        let stats = space_stats::SpaceStats::sample(&root, context, self.num_descents);
+
+        println!(
+            "{} descents, {} reached a dead-end",
+            stats.num_descents,
+            stats.num_deadends()
+        );
+        match stats.log_size() {
+            None => println!("Estimated size of the space: 0"),
+            Some(log_size) => {
+                let depths = stats.descents.iter().map(|descent| descent.depth);
+                println!(
+                    "Choices per implementation: {} to {} ({:.1} on average)",
+                    depths.clone().min().unwrap(),
+                    depths.clone().max().unwrap(),
+                    depths.sum::<usize>() as f64 / stats.descents.len() as f64,
+                );
+                println!("Estimated size of the space: 10^{:.2}", log_size);
+            }
+        }
+
+        println!(
+            "{:<16} {:>10} {:>12} {:>10}",
+            "choice", "count", "branching", "pruned"
+        );
+        for (kind, kind_stats) in &stats.choices {
+            println!(
+                "{:<16} {:>10} {:>12.2} {:>9.1}%",
+                kind,
+                kind_stats.num_choices,
+                kind_stats.num_alternatives as f64 / kind_stats.num_choices as f64,
+                100. * kind_stats.num_pruned as f64 / kind_stats.num_alternatives as f64,
+            );
+        }
+        if let Some(ratio) = stats.pruned_ratio() {
+            println!(
+                "Alternatives pruned by constraint propagation: {:.1}%",
+                100. * ratio
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Renders the candidate tree of an eventlog in the DOT format.
+#[derive(StructOpt)]
+struct TreeDump {
+    /// Path to the eventlog to render.
+    #[structopt(
+        parse(from_os_str),
+        short = "i",
+        long = "input",
+        default_value = "eventlog.tfrecord.gz"
+    )]
+    eventlog: PathBuf,
+
+    /// Path to the DOT file.  The graph is written to the standard output if omitted.
+    #[structopt(parse(from_os_str), short = "o", long = "output")]
+    output: Option<PathBuf>,
+
+    /// Maximal depth of the rendered nodes.
+    #[structopt(long = "max-depth")]
+    max_depth: Option<usize>,
+
+    /// Minimal number of visits of the rendered nodes.
+    #[structopt(long = "min-visits", default_value = "0")]
+    min_visits: usize,
+}
+
+impl TreeDump {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let mut tree = CandidateTree::new();
+        tree.replay_eventlog(&self.eventlog, |tree, message| {
+            match message {
+                mcts::Message::Trace { events, .. } => {
+                    // Traces may backtrack to nodes they already went through, which must
+                    // only be counted once.
+                    let mut visited = HashSet::new();
+                    let mut node = tree.get_root();
+                    for event in &events {
+                        match event.value {
+                            mcts::Event::SelectNode(id) => node = tree.get_node(id),
+                            mcts::Event::SelectChild(index, ..) => {
+                                node = node
+                                    .child(index.into())
+                                    .unwrap_or_else(|| panic!("no child"));
+                            }
+                            mcts::Event::Kill(_) => {
+                                node.declare_deadend(event.start_time)
+                            }
+                            mcts::Event::KillChild(index, _) => {
+                                if let Some(mut child) = node.child(index.into()) {
+                                    child.declare_deadend(event.start_time);
+                                }
+                            }
+                            mcts::Event::Implementation => {
+                                node.declare_implementation(event.start_time)
+                            }
+                            mcts::Event::Expand => (),
+                        }
+                        if visited.insert(node.id()) {
+                            node.add_visit();
+                        }
+                    }
+                }
+                mcts::Message::Evaluation { id, value, .. } => {
+                    let mut node = tree.get_node(id);
+                    if let (Some(value), None) = (value, node.score()) {
+                        node.set_score(value);
+                    }
+                }
+                mcts::Message::Node { .. }
+                | mcts::Message::Screening { .. }
+                | mcts::Message::Launch { .. }
+                | mcts::Message::Source { .. }
+                | mcts::Message::Energy { .. } => (),
+            }
+            Ok(())
+        })?;
+
+        let truncation = graphviz::Truncation {
+            max_depth: self.max_depth,
+            min_visits: self.min_visits,
+        };
+        match &self.output {
+            Some(path) => {
+                graphviz::write_dot(&tree, &truncation, fs::File::create(path)?)
+            }
+            None => graphviz::write_dot(&tree, &truncation, io::stdout()),
+        }
+    }
+}
+
+/// Displays a live dashboard of a running search.
+#[derive(StructOpt)]
+struct Watch {
+    /// Path to the eventlog written by the search.
+    #[structopt(
+        parse(from_os_str),
+        short = "i",
+        long = "input",
+        default_value = "eventlog.tfrecord.gz"
+    )]
+    eventlog: PathBuf,
+
+    /// Refresh interval of the dashboard, in milliseconds.
+    #[structopt(long = "refresh", default_value = "500")]
+    refresh: u64,
+}
+
+impl Watch {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        watch::watch(&self.eventlog, Duration::from_millis(self.refresh))
+    }
+}
+
+/// Generates an HTML report from an eventlog.
+#[derive(StructOpt)]
+struct Report {
+    /// Path to the eventlog to report on.
+    #[structopt(
+        parse(from_os_str),
+        short = "i",
+        long = "input",
+        default_value = "eventlog.tfrecord.gz"
+    )]
+    eventlog: PathBuf,
+
+    /// Path to the HTML report.
+    #[structopt(
+        parse(from_os_str),
+        short = "o",
+        long = "output",
+        default_value = "report.html"
+    )]
+    output: PathBuf,
+
+    /// Number of candidates listed in the report.
+    #[structopt(long = "top", default_value = "10")]
+    top: usize,
+}
+
+impl Report {
+    fn run(&self, _args: &Opt) -> io::Result<()> {
+        let report = report::Report::from_eventlog(&self.eventlog)?;
+        report.write_html(
+            self.top,
+            io::BufWriter::new(fs::File::create(&self.output)?),
+        )?;
+        eprintln!(
+            "Reported {} evaluations in {}",
+            report.evaluations.len(),
+            self.output.display()
+        );
+        Ok(())
+    }
+}
+
+#[derive(StructOpt)]
+enum Command {
+    #[structopt(name = "benchmark")]
+    Benchmark(Benchmark),
+
+    #[structopt(name = "codegen")]
+    Codegen(Codegen),
+
+    #[structopt(name = "rebuild")]
+    Rebuild(Rebuild),
+
+    #[structopt(name = "bounds")]
+    Bounds(Bounds),
+
+    #[structopt(name = "stats")]
+    Stats(Stats),
+
+    #[structopt(name = "compare-logs")]
+    CompareLogs(CompareLogs),
+
+    #[structopt(name = "bound")]
+    Bound(ComputeBound),
+
+    #[structopt(name = "search")]
+    Search(Search),
+
+    #[structopt(name = "worker")]
+    Worker(Worker),
+
+    #[structopt(name = "self-bench")]
+    SelfBench(SelfBench),
+
+    #[structopt(name = "sweep")]
+    Sweep(Sweep),
+
+    #[structopt(name = "export-schedule")]
+    ExportSchedule(ExportSchedule),
+
+    #[structopt(name = "merge-logs")]
+    MergeLogs(MergeLogs),
+
+    #[structopt(name = "golden-record")]
+    GoldenRecord(GoldenRecord),
+
+    #[structopt(name = "golden-check")]
+    GoldenCheck(GoldenCheck),
+
+    #[structopt(name = "onnx")]
+    Onnx(Onnx),
+
+    #[structopt(name = "propagation-trace")]
+    PropagationTrace(PropagationTrace),
+
+    #[structopt(name = "dataset")]
+    ExportDataset(ExportDataset),
+
+    #[structopt(name = "explain-deadend")]
+    ExplainDeadend(ExplainDeadend),
+
+    #[structopt(name = "export")]
+    Export(Export),
+
+    #[structopt(name = "space-stats")]
+    SpaceStats(SpaceStats),
+
+    #[structopt(name = "tree-dump")]
+    TreeDump(TreeDump),
+
+    #[structopt(name = "watch")]
+    Watch(Watch),
+
+    #[structopt(name = "report")]
+    Report(Report),
+}
+
+#[derive(StructOpt)]
+#[structopt(name = "telamon")]
+struct Opt {
+    /// Path to a shared library implementing a backend.
+    ///
+    /// If provided, the backend from the plugin is used instead of the one selected with
+    /// `--platform`.
+    #[structopt(parse(from_os_str), long = "backend-plugin")]
+    backend_plugin: Option<PathBuf>,
+
+    /// Path to a GPU description, as stored by the characterization of the CUDA backend.
+    ///
+    /// If provided, kernels are built for the described GPU without initializing CUDA, so
+    /// that the code and the bounds of candidates can be inspected on machines without a
+    /// GPU.  Evaluations always return 1ns in this mode.
+    #[structopt(parse(from_os_str), long = "gpu-desc")]
+    gpu_desc: Option<PathBuf>,
+
+    /// Type of the elements of the kernels: f16, f32 or f64. Kernels on f16 elements
+    /// accumulate matrix products in f32.
+    #[structopt(long = "dtype", default_value = "f32")]
+    dtype: DType,
+
+    /// Seed of the random data the arguments of the kernels are filled with. Runs with the
+    /// same seed check the kernels against the same data.
+    #[structopt(long = "seed", default_value = "0")]
+    seed: u64,
+
+    /// Factor applied to the tolerances of the checks of the kernels' outputs. Raising it
+    /// accepts the larger errors of approximate operations, which the search explores
+    /// when the `precision` choice group is listed in the configuration.
+    #[structopt(long = "accuracy-budget", default_value = "1")]
+    accuracy_budget: f64,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+impl Opt {
+    /// Returns the context builder for the backend plugin or the GPU description if there
+    /// is one, and for `platform` otherwise.
+    fn context_builder(&self, platform: Platform) -> io::Result<PlatformContextBuilder> {
+        Ok(match (&self.backend_plugin, &self.gpu_desc) {
+            (Some(path), _) => PlatformContextBuilder::Plugin(BackendPlugin::load(path)?),
+            (None, Some(path)) => PlatformContextBuilder::from_gpu_desc(path)?,
+            (None, None) => platform.to_builder(),
+        })
+    }
+}
+
+fn main() {
+    let args = Opt::from_args();
+    env_logger::init();
+
+    let result = match &args.command {
+        Command::Benchmark(benchmark) => benchmark.run(&args),
+        Command::Codegen(codegen) => codegen.run(&args),
+        Command::Rebuild(rebuild) => rebuild.run(&args),
+        Command::Bounds(bounds) => bounds.run(&args),
+        Command::Stats(stats) => stats.run(&args),
+        Command::CompareLogs(compare_logs) => compare_logs.run(&args),
+        Command::Bound(bound) => bound.run(&args),
+        Command::Search(search) => search.run(&args),
+        Command::Worker(worker) => worker.run(&args),
+        Command::SelfBench(self_bench) => self_bench.run(&args),
+        Command::Sweep(sweep) => sweep.run(&args),
+        Command::ExportSchedule(export) => export.run(&args),
+        Command::MergeLogs(merge_logs) => merge_logs.run(&args),
+        Command::GoldenRecord(record) => record.run(&args),
+        Command::GoldenCheck(check) => check.run(&args),
+        Command::Onnx(onnx) => onnx.run(&args),
+        Command::PropagationTrace(trace) => trace.run(&args),
+        Command::ExportDataset(export) => export.run(&args),
+        Command::ExplainDeadend(explain) => explain.run(&args),
+        Command::Export(export) => export.run(&args),
+        Command::SpaceStats(stats) => stats.run(&args),
+        Command::TreeDump(dump) => dump.run(&args),
+        Command::Watch(watch) => watch.run(&args),
+        Command::Report(report) => report.run(&args),
+    };
+
+    match result {
+        Ok(()) => (),
+        Err(err) => panic!("An error occured: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use telamon::device::{fake, Context};
+    use telamon::helper;
+    use telamon::ir::Size;
+
+    /// Ensures the actions written to `best/actions.json` rebuild the best implementation
+    /// when they are replayed.
+    #[test]
+    fn replay_round_trip() {
+        let context = fake::Context::<fake::Device>::default();
+        let signature = Arc::new(ir::Signature::new("replay_round_trip"));
+        let mut builder = helper::Builder::new(signature, context.device());
+        let dim0 = builder.open_dim(Size::new_const(64));
+        builder.mov(&0i32);
+        let dim1 = builder.open_dim(Size::new_const(16));
+        builder.mov(&1i32);
+        builder.close_dim(&dim1);
+        builder.close_dim(&dim0);
+        let space = builder.get();
+
+        let dir = std::env::temp_dir()
+            .join(format!("telamon-replay-round-trip-{}", std::process::id()));
+        let mut config = explorer::Config::default();
+        config.output_dir = dir.to_string_lossy().into_owned();
+        config.num_workers = 1;
+        config.max_evaluations = Some(4);
+        let candidate = Candidate::new(space.clone(), bound(&space, &context));
+        let best = explorer::find_best_ex(&config, &context, vec![candidate], None, &[])
+            .expect("no candidate found");
+
+        let path = dir.join("actions.json");
+        fs::write(
+            &path,
+            serde_json::to_string(&replay_actions(&best)).unwrap(),
+        )
+        .unwrap();
+        let actions = ReplayPath::from(path.as_os_str()).load().unwrap();
+        let mut replayed = Candidate::new(space.clone(), bound(&space, &context));
+        for action in actions {
+            replayed = replayed.apply_decision(&context, action).unwrap();
+        }
+        assert_eq!(replayed.actions, best.actions);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Ensures the statistics of an empty log are undefined rather than NaN.
+    #[test]
+    fn empty_log_summary() {
+        let summary = LogSummary::default();
+        assert_eq!(summary.best(), None);
+        assert_eq!(summary.deadend_rate(), None);
+        assert_eq!(summary.nodes_per_second(), None);
+        assert_eq!(
+            summary.choice_value_ratio(&("order", "BEFORE".to_string())),
+            None
+        );
     }
 }
@@ -0,0 +1,371 @@
+//! Minimal bindings to cuDNN, used to evaluate reference implementations of convolutions.
+#![allow(non_camel_case_types, non_snake_case)]
+use cuda_sys::cuda::*;
+use libc::{c_int, c_void, size_t};
+use telamon_kernels::linalg;
+
+pub type cudnnStatus_t = c_int;
+pub type cudnnHandle_t = *mut c_void;
+pub type cudnnTensorDescriptor_t = *mut c_void;
+pub type cudnnFilterDescriptor_t = *mut c_void;
+pub type cudnnConvolutionDescriptor_t = *mut c_void;
+pub type cudnnConvolutionFwdAlgo_t = c_int;
+
+const CUDNN_STATUS_SUCCESS: cudnnStatus_t = 0;
+const CUDNN_DATA_FLOAT: c_int = 0;
+const CUDNN_TENSOR_NCHW: c_int = 0;
+const CUDNN_CROSS_CORRELATION: c_int = 1;
+
+/// Performance of a convolution algorithm, as measured by
+/// `cudnnFindConvolutionForwardAlgorithm`.
+#[repr(C)]
+#[allow(dead_code)]
+struct cudnnConvolutionFwdAlgoPerf_t {
+    algo: cudnnConvolutionFwdAlgo_t,
+    status: cudnnStatus_t,
+    time: f32,
+    memory: size_t,
+    determinism: c_int,
+    mathType: c_int,
+    reserved: [c_int; 3],
+}
+
+#[link(name = "cudnn")]
+extern "C" {
+    fn cudnnCreate(handle: *mut cudnnHandle_t) -> cudnnStatus_t;
+    fn cudnnDestroy(handle: cudnnHandle_t) -> cudnnStatus_t;
+    fn cudnnCreateTensorDescriptor(desc: *mut cudnnTensorDescriptor_t) -> cudnnStatus_t;
+    fn cudnnSetTensor4dDescriptor(
+        desc: cudnnTensorDescriptor_t,
+        format: c_int,
+        data_type: c_int,
+        n: c_int,
+        c: c_int,
+        h: c_int,
+        w: c_int,
+    ) -> cudnnStatus_t;
+    fn cudnnDestroyTensorDescriptor(desc: cudnnTensorDescriptor_t) -> cudnnStatus_t;
+    fn cudnnCreateFilterDescriptor(desc: *mut cudnnFilterDescriptor_t) -> cudnnStatus_t;
+    fn cudnnSetFilter4dDescriptor(
+        desc: cudnnFilterDescriptor_t,
+        data_type: c_int,
+        format: c_int,
+        k: c_int,
+        c: c_int,
+        h: c_int,
+        w: c_int,
+    ) -> cudnnStatus_t;
+    fn cudnnDestroyFilterDescriptor(desc: cudnnFilterDescriptor_t) -> cudnnStatus_t;
+    fn cudnnCreateConvolutionDescriptor(
+        desc: *mut cudnnConvolutionDescriptor_t,
+    ) -> cudnnStatus_t;
+    fn cudnnSetConvolution2dDescriptor(
+        desc: cudnnConvolutionDescriptor_t,
+        pad_h: c_int,
+        pad_w: c_int,
+        u: c_int,
+        v: c_int,
+        dilation_h: c_int,
+        dilation_w: c_int,
+        mode: c_int,
+        compute_type: c_int,
+    ) -> cudnnStatus_t;
+    fn cudnnSetConvolutionGroupCount(
+        desc: cudnnConvolutionDescriptor_t,
+        group_count: c_int,
+    ) -> cudnnStatus_t;
+    fn cudnnDestroyConvolutionDescriptor(
+        desc: cudnnConvolutionDescriptor_t,
+    ) -> cudnnStatus_t;
+    fn cudnnFindConvolutionForwardAlgorithm(
+        handle: cudnnHandle_t,
+        x_desc: cudnnTensorDescriptor_t,
+        w_desc: cudnnFilterDescriptor_t,
+        conv_desc: cudnnConvolutionDescriptor_t,
+        y_desc: cudnnTensorDescriptor_t,
+        requested_algo_count: c_int,
+        returned_algo_count: *mut c_int,
+        perf_results: *mut cudnnConvolutionFwdAlgoPerf_t,
+    ) -> cudnnStatus_t;
+    fn cudnnConvolutionForward(
+        handle: cudnnHandle_t,
+        alpha: *const c_void,
+        x_desc: cudnnTensorDescriptor_t,
+        x: *const c_void,
+        w_desc: cudnnFilterDescriptor_t,
+        w: *const c_void,
+        conv_desc: cudnnConvolutionDescriptor_t,
+        algo: cudnnConvolutionFwdAlgo_t,
+        work_space: *mut c_void,
+        work_space_size: size_t,
+        beta: *const c_void,
+        y_desc: cudnnTensorDescriptor_t,
+        y: *mut c_void,
+    ) -> cudnnStatus_t;
+}
+
+/// Checks the cudnn status and panics if an error occured.
+fn check_cudnn(status: cudnnStatus_t) {
+    if status != CUDNN_STATUS_SUCCESS {
+        panic!("error in cudnn: {}", status);
+    }
+}
+
+/// Checks a cuda status and panics if an error occured.
+fn check_cuda(status: CUresult) {
+    if status != cudaError_t::CUDA_SUCCESS {
+        panic!("error in cuda: {:?}", status)
+    }
+}
+
+pub struct CudnnHandle(cudnnHandle_t);
+
+#[allow(clippy::new_without_default)]
+impl CudnnHandle {
+    /// Initialize a new handle.
+    pub fn new() -> Self {
+        unsafe {
+            let mut handle = std::ptr::null_mut();
+            check_cudnn(cudnnCreate(&mut handle));
+            CudnnHandle(handle)
+        }
+    }
+}
+
+impl Drop for CudnnHandle {
+    fn drop(&mut self) {
+        unsafe {
+            check_cudnn(cudnnDestroy(self.0));
+        }
+    }
+}
+
+/// The shape of a single precision convolution on tensors in NCHW layout.
+pub struct Convolution {
+    pub batch: i32,
+    pub in_channels: i32,
+    pub out_channels: i32,
+    pub height: i32,
+    pub width: i32,
+    pub filter_height: i32,
+    pub filter_width: i32,
+    pub stride: i32,
+    pub padding: i32,
+    /// Number of groups the channels are split into. Each group of output channels only
+    /// reads the corresponding group of input channels.
+    pub groups: i32,
+}
+
+impl Convolution {
+    /// Returns the grouped convolution, with one group per channel, that computes the
+    /// same output as `DepthwiseConv`. The input array is already padded.
+    pub fn depthwise(params: &linalg::DepthwiseConvP) -> Self {
+        Convolution {
+            batch: params.batch,
+            in_channels: params.channels,
+            out_channels: params.channels,
+            height: params.padded_height(),
+            width: params.padded_width(),
+            filter_height: params.filter_height,
+            filter_width: params.filter_width,
+            stride: params.stride,
+            padding: 0,
+            groups: params.channels,
+        }
+    }
+
+    /// Returns the convolution with a 1x1 filter that computes the same output as
+    /// `PointwiseConv`.
+    pub fn pointwise(params: &linalg::PointwiseConvP) -> Self {
+        Convolution {
+            batch: params.batch,
+            in_channels: params.in_channels,
+            out_channels: params.out_channels,
+            height: params.height,
+            width: params.width,
+            filter_height: 1,
+            filter_width: 1,
+            stride: 1,
+            padding: 0,
+            groups: 1,
+        }
+    }
+
+    fn out_size(&self, size: i32, filter: i32) -> i32 {
+        (size + 2 * self.padding - filter) / self.stride + 1
+    }
+
+    /// Returns the shape of the output tensor, in NCHW layout.
+    pub fn out_shape(&self) -> [i32; 4] {
+        let out_height = self.out_size(self.height, self.filter_height);
+        let out_width = self.out_size(self.width, self.filter_width);
+        [self.batch, self.out_channels, out_height, out_width]
+    }
+
+    /// Picks the fastest algorithm for the convolution of the `input` and `filter` device
+    /// arrays into `output`, and allocates its workspace.
+    pub unsafe fn prepare<'a>(
+        &self,
+        handle: &'a CudnnHandle,
+        input: *const f32,
+        filter: *const f32,
+        output: *mut f32,
+    ) -> PreparedConvolution<'a> {
+        let [_, _, out_height, out_width] = self.out_shape();
+        let mut input_desc = std::ptr::null_mut();
+        check_cudnn(cudnnCreateTensorDescriptor(&mut input_desc));
+        check_cudnn(cudnnSetTensor4dDescriptor(
+            input_desc,
+            CUDNN_TENSOR_NCHW,
+            CUDNN_DATA_FLOAT,
+            self.batch,
+            self.in_channels,
+            self.height,
+            self.width,
+        ));
+        let mut output_desc = std::ptr::null_mut();
+        check_cudnn(cudnnCreateTensorDescriptor(&mut output_desc));
+        check_cudnn(cudnnSetTensor4dDescriptor(
+            output_desc,
+            CUDNN_TENSOR_NCHW,
+            CUDNN_DATA_FLOAT,
+            self.batch,
+            self.out_channels,
+            out_height,
+            out_width,
+        ));
+        let mut filter_desc = std::ptr::null_mut();
+        check_cudnn(cudnnCreateFilterDescriptor(&mut filter_desc));
+        check_cudnn(cudnnSetFilter4dDescriptor(
+            filter_desc,
+            CUDNN_DATA_FLOAT,
+            CUDNN_TENSOR_NCHW,
+            self.out_channels,
+            self.in_channels / self.groups,
+            self.filter_height,
+            self.filter_width,
+        ));
+        let mut conv_desc = std::ptr::null_mut();
+        check_cudnn(cudnnCreateConvolutionDescriptor(&mut conv_desc));
+        check_cudnn(cudnnSetConvolution2dDescriptor(
+            conv_desc,
+            self.padding,
+            self.padding,
+            self.stride,
+            self.stride,
+            1,
+            1,
+            CUDNN_CROSS_CORRELATION,
+            CUDNN_DATA_FLOAT,
+        ));
+        check_cudnn(cudnnSetConvolutionGroupCount(conv_desc, self.groups));
+        let mut perf = std::mem::zeroed::<cudnnConvolutionFwdAlgoPerf_t>();
+        let mut num_algos = 0;
+        check_cudnn(cudnnFindConvolutionForwardAlgorithm(
+            handle.0,
+            input_desc,
+            filter_desc,
+            conv_desc,
+            output_desc,
+            1,
+            &mut num_algos,
+            &mut perf,
+        ));
+        assert_eq!(num_algos, 1);
+        check_cudnn(perf.status);
+        let mut workspace = 0;
+        if perf.memory > 0 {
+            check_cuda(cuMemAlloc_v2(&mut workspace, perf.memory));
+        }
+        PreparedConvolution {
+            handle,
+            input_desc,
+            output_desc,
+            filter_desc,
+            conv_desc,
+            algo: perf.algo,
+            workspace,
+            workspace_size: perf.memory,
+            input,
+            filter,
+            output,
+        }
+    }
+}
+
+/// A convolution ready to run on the device.
+pub struct PreparedConvolution<'a> {
+    handle: &'a CudnnHandle,
+    input_desc: cudnnTensorDescriptor_t,
+    output_desc: cudnnTensorDescriptor_t,
+    filter_desc: cudnnFilterDescriptor_t,
+    conv_desc: cudnnConvolutionDescriptor_t,
+    algo: cudnnConvolutionFwdAlgo_t,
+    workspace: CUdeviceptr,
+    workspace_size: usize,
+    input: *const f32,
+    filter: *const f32,
+    output: *mut f32,
+}
+
+impl<'a> PreparedConvolution<'a> {
+    /// Runs the convolution.
+    pub unsafe fn run(&self) {
+        check_cudnn(cudnnConvolutionForward(
+            self.handle.0,
+            &1f32 as *const f32 as *const c_void,
+            self.input_desc,
+            self.input as *const c_void,
+            self.filter_desc,
+            self.filter as *const c_void,
+            self.conv_desc,
+            self.algo,
+            self.workspace as *mut c_void,
+            self.workspace_size,
+            &0f32 as *const f32 as *const c_void,
+            self.output_desc,
+            self.output as *mut c_void,
+        ));
+    }
+}
+
+impl<'a> Drop for PreparedConvolution<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            check_cudnn(cudnnDestroyTensorDescriptor(self.input_desc));
+            check_cudnn(cudnnDestroyTensorDescriptor(self.output_desc));
+            check_cudnn(cudnnDestroyFilterDescriptor(self.filter_desc));
+            check_cudnn(cudnnDestroyConvolutionDescriptor(self.conv_desc));
+            if self.workspace_size > 0 {
+                check_cuda(cuMemFree_v2(self.workspace));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures the reference of a depthwise convolution has the shape of the kernel.
+    #[test]
+    fn depthwise_shape() {
+        for &(stride, padding) in &[(1, 0), (1, 1), (2, 0), (2, 1)] {
+            let params = linalg::DepthwiseConvP::new(2, 3, 9, 7)
+                .filter(3, 2)
+                .stride(stride)
+                .padding(padding);
+            let conv = Convolution::depthwise(&params);
+            let (out_height, out_width) = (params.out_height(), params.out_width());
+            assert_eq!(conv.out_shape(), [2, 3, out_height, out_width]);
+            assert_eq!(conv.in_channels / conv.groups, 1);
+        }
+    }
+
+    /// Ensures the reference of a pointwise convolution has the shape of the kernel.
+    #[test]
+    fn pointwise_shape() {
+        let params = linalg::PointwiseConvP::new(2, 3, 5, 9, 7);
+        assert_eq!(Convolution::pointwise(&params).out_shape(), [2, 5, 9, 7]);
+    }
+}
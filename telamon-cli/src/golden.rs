@@ -0,0 +1,76 @@
+//! Known-optimal implementations of small kernels, used to detect search-quality regressions.
+//!
+//! A golden file records the best implementation of a kernel on a device, as found by an
+//! exhaustive search, along with its execution time.  Golden files are stored in a directory
+//! with one sub-directory per device.  The directory also lists the kernels to record in
+//! a `kernels.txt` file, with one kernel per line.
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use telamon::explorer::choice::ActionEx as Action;
+
+use crate::KernelParam;
+
+/// The best known implementation of a kernel on a device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Golden {
+    /// The kernel specification, as passed to `--kernel`.
+    pub kernel: String,
+    /// The name of the device the implementation was found on.
+    pub device: String,
+    /// The execution time of the implementation, in nanoseconds.
+    pub runtime: f64,
+    /// The actions that build the implementation from the kernel, in application order.
+    pub actions: Vec<Action>,
+}
+
+impl Golden {
+    /// Returns the path of the golden file of `kernel` on `device`, in `dir`.
+    pub fn path(dir: &Path, kernel: &str, device: &str) -> PathBuf {
+        let device = device
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        dir.join(device).join(format!("{}.json", kernel))
+    }
+
+    /// Returns the kernels listed in the `kernels.txt` file of `dir`.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn kernels(dir: &Path) -> io::Result<Vec<KernelParam>> {
+        let file = io::BufReader::new(fs::File::open(dir.join("kernels.txt"))?);
+        let mut kernels = Vec::new();
+        for line in file.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let kernel = line.parse::<KernelParam>().map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid kernel `{}`: {}", line, err),
+                )
+            })?;
+            kernels.push(kernel);
+        }
+        Ok(kernels)
+    }
+
+    /// Loads a golden file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_reader(fs::File::open(path)?)?)
+    }
+
+    /// Writes the golden file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer_pretty(fs::File::create(path)?, self)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,17 @@
 #![deny(bare_trait_objects, unused_lifetimes)]
 #![allow(clippy::many_single_char_names)]
 
+#[cfg(feature = "cuda")]
+mod cudnn;
+pub mod golden;
+pub mod onnx;
+pub mod plugin;
+pub mod soak;
+pub mod watch;
+
 use std::error::Error;
 use std::ffi::OsStr;
+use std::marker::PhantomData;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::{fmt, fs, io};
@@ -11,7 +20,9 @@ use structopt::StructOpt;
 
 use telamon::device::{ArgMap, Context};
 use telamon::explorer::{choice::ActionEx as Action, config::Config, Candidate};
-use telamon_kernels::{linalg, Kernel, KernelBuilder};
+use telamon_kernels::{linalg, Kernel, KernelBuilder, ParamsError, Scalar, F16};
+
+use crate::plugin::{BackendPlugin, PluginContext, PluginReference};
 
 #[derive(StructOpt)]
 pub struct CommonOpt {
@@ -26,6 +37,53 @@ pub struct CommonOpt {
     /// If provided, overrides the timeout from the configuration file.
     #[structopt(long = "timeout")]
     timeout: Option<u64>,
+
+    /// Maximum number of implementations to evaluate
+    ///
+    /// If provided, overrides the limit from the configuration file.  Candidates that are
+    /// cut before their evaluation do not count towards the limit.
+    #[structopt(long = "max-evaluations")]
+    max_evaluations: Option<usize>,
+
+    /// Only count and classify candidates, without compiling nor evaluating them
+    ///
+    /// The bound of each implementation is used as its execution time.
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Compare the counters of the search space with the generated code
+    ///
+    /// Mismatches are logged as errors for each implementation.  This is a debugging aid
+    /// for the search space description.
+    #[structopt(long = "check-counters")]
+    check_counters: bool,
+
+    /// Record the launch configuration and the source code of each candidate
+    ///
+    /// They are stored in the event log, which must be enabled in the configuration.
+    /// This generates the code of each candidate a second time.
+    #[structopt(long = "log-code")]
+    log_code: bool,
+
+    /// Remove the dimension sizes that are invalid on the device before the search
+    ///
+    /// A size is removed if the dimension cannot have it with any of its possible kinds.
+    #[structopt(long = "restrict-sizes")]
+    restrict_sizes: bool,
+
+    /// Address on which to wait for workers
+    ///
+    /// If provided, candidates are evaluated by the workers started with `tlcli worker`
+    /// that connect to this address instead of locally.
+    #[structopt(long = "listen")]
+    listen: Option<String>,
+
+    /// Resume the search from a checkpoint
+    ///
+    /// Checkpoints are saved in the `checkpoint_file` of the configuration.  The search must
+    /// use the same kernel and configuration as the one that saved the checkpoint.
+    #[structopt(long = "resume")]
+    resume: Option<String>,
 }
 
 impl CommonOpt {
@@ -38,6 +96,13 @@ impl CommonOpt {
         }?;
 
         config.timeout = config.timeout.or(self.timeout);
+        config.max_evaluations = self.max_evaluations.or(config.max_evaluations);
+        config.dry_run |= self.dry_run;
+        config.check_counters |= self.check_counters;
+        config.log_code |= self.log_code;
+        config.restrict_sizes |= self.restrict_sizes;
+        config.coordinator = self.listen.clone().or(config.coordinator);
+        config.resume = self.resume.clone().or(config.resume);
         Ok(config)
     }
 }
@@ -51,6 +116,68 @@ where
     fn eval_reference(&self, params: &K::Parameters, context: &Self::Context) -> f64;
 }
 
+/// Reference implementations of all the kernels of `KernelParam` and of the layers
+/// imported from ONNX models, with elements of type `S`.
+pub trait KernelReferences<'a, S, C>:
+    Reference<'a, linalg::Axpy<'a, S>, Context = C>
+    + Reference<'a, linalg::MatVec<'a, S>, Context = C>
+    + Reference<'a, linalg::FusedMM<'a, S>, Context = C>
+    + Reference<'a, linalg::BatchMM<'a, S>, Context = C>
+    + Reference<'a, linalg::Gesummv<'a, S>, Context = C>
+    + Reference<'a, linalg::Fused2MM<'a, S>, Context = C>
+    + Reference<'a, linalg::DepthwiseConv<'a, S>, Context = C>
+    + Reference<'a, linalg::PointwiseConv<'a, S>, Context = C>
+    + Reference<'a, linalg::LayerNorm<'a, S>, Context = C>
+    + Reference<'a, linalg::Conv2d<'a, S>, Context = C>
+where
+    S: Scalar,
+    C: Context + ArgMap<'a>,
+{
+}
+
+impl<'a, S, C, R> KernelReferences<'a, S, C> for R
+where
+    S: Scalar,
+    C: Context + ArgMap<'a>,
+    R: Reference<'a, linalg::Axpy<'a, S>, Context = C>
+        + Reference<'a, linalg::MatVec<'a, S>, Context = C>
+        + Reference<'a, linalg::FusedMM<'a, S>, Context = C>
+        + Reference<'a, linalg::BatchMM<'a, S>, Context = C>
+        + Reference<'a, linalg::Gesummv<'a, S>, Context = C>
+        + Reference<'a, linalg::Fused2MM<'a, S>, Context = C>
+        + Reference<'a, linalg::DepthwiseConv<'a, S>, Context = C>
+        + Reference<'a, linalg::PointwiseConv<'a, S>, Context = C>
+        + Reference<'a, linalg::LayerNorm<'a, S>, Context = C>
+        + Reference<'a, linalg::Conv2d<'a, S>, Context = C>,
+{
+}
+
+/// Reference for contexts that cannot run code, such as the fake contexts used to inspect
+/// the generated code.  The reference always takes 1ns, as the fake evaluations do.
+pub struct NoReference<C> {
+    _marker: PhantomData<fn() -> C>,
+}
+
+impl<C> Default for NoReference<C> {
+    fn default() -> Self {
+        NoReference {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, C> Reference<'a, K> for NoReference<C>
+where
+    K: Kernel<'a>,
+    C: Context + 'a,
+{
+    type Context = C;
+
+    fn eval_reference(&self, _params: &K::Parameters, _context: &C) -> f64 {
+        1.
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Bench {
     warmup: usize,
@@ -93,10 +220,12 @@ impl Bench {
 mod cuda_reference {
     use cuda_sys::cublas::*;
     use cuda_sys::cuda::*;
+    use log::warn;
     use telamon_cuda as cuda;
-    use telamon_kernels::linalg;
+    use telamon_kernels::{linalg, Kernel, F16};
 
     use super::Reference;
+    use crate::cudnn::{Convolution, CudnnHandle};
 
     /// Checks the cublas status and panics if an error occured.
     fn check_cublas(status: cublasStatus_t) {
@@ -112,7 +241,9 @@ mod cuda_reference {
         }
     }
 
-    pub struct CublasHandle(cublasHandle_t);
+    /// Handles to the cuBLAS and cuDNN libraries, used to evaluate reference
+    /// implementations.
+    pub struct CublasHandle(cublasHandle_t, CudnnHandle);
 
     #[allow(clippy::new_without_default)]
     impl CublasHandle {
@@ -121,7 +252,7 @@ mod cuda_reference {
             unsafe {
                 let mut handle = std::mem::uninitialized();
                 check_cublas(cublasCreate_v2(&mut handle));
-                CublasHandle(handle)
+                CublasHandle(handle, CudnnHandle::new())
             }
         }
     }
@@ -165,44 +296,209 @@ mod cuda_reference {
     const CUBLAS_N: cublasOperation_t = cublasOperation_t_CUBLAS_OP_N;
     const CUBLAS_T: cublasOperation_t = cublasOperation_t_CUBLAS_OP_T;
 
+    /// Element types for which cuBLAS provides the routines used by the references. The
+    /// methods take the same arguments as the cuBLAS routines, without the increments of
+    /// vectors which are always 1.
+    #[allow(clippy::too_many_arguments)]
+    trait CublasScalar: Copy {
+        /// Converts a constant of the references, or a parameter of the kernels.
+        fn from_f32(value: f32) -> Self;
+
+        unsafe fn axpy(
+            handle: cublasHandle_t,
+            n: libc::c_int,
+            alpha: *const Self,
+            x: *const Self,
+            y: *mut Self,
+        ) -> cublasStatus_t;
+
+        unsafe fn gemv(
+            handle: cublasHandle_t,
+            op: cublasOperation_t,
+            m: libc::c_int,
+            n: libc::c_int,
+            alpha: &Self,
+            a: *const Self,
+            lda: libc::c_int,
+            x: *const Self,
+            beta: &Self,
+            y: *mut Self,
+        ) -> cublasStatus_t;
+
+        unsafe fn gemm(
+            handle: cublasHandle_t,
+            op_a: cublasOperation_t,
+            op_b: cublasOperation_t,
+            m: libc::c_int,
+            n: libc::c_int,
+            k: libc::c_int,
+            alpha: &Self,
+            a: *const Self,
+            lda: libc::c_int,
+            b: *const Self,
+            ldb: libc::c_int,
+            beta: &Self,
+            c: *mut Self,
+            ldc: libc::c_int,
+        ) -> cublasStatus_t;
+
+        unsafe fn gemm_strided_batched(
+            handle: cublasHandle_t,
+            op_a: cublasOperation_t,
+            op_b: cublasOperation_t,
+            m: libc::c_int,
+            n: libc::c_int,
+            k: libc::c_int,
+            alpha: &Self,
+            a: *const Self,
+            lda: libc::c_int,
+            stride_a: libc::c_long,
+            b: *const Self,
+            ldb: libc::c_int,
+            stride_b: libc::c_long,
+            beta: &Self,
+            c: *mut Self,
+            ldc: libc::c_int,
+            stride_c: libc::c_long,
+            batch: libc::c_int,
+        ) -> cublasStatus_t;
+    }
+
+    macro_rules! impl_cublas_scalar {
+        ($t:ident, $axpy:ident, $gemv:ident, $gemm:ident, $gemm_batched:ident) => {
+            #[allow(clippy::too_many_arguments)]
+            impl CublasScalar for $t {
+                fn from_f32(value: f32) -> Self {
+                    value.into()
+                }
+
+                unsafe fn axpy(
+                    handle: cublasHandle_t,
+                    n: libc::c_int,
+                    alpha: *const Self,
+                    x: *const Self,
+                    y: *mut Self,
+                ) -> cublasStatus_t {
+                    $axpy(handle, n, alpha, x, 1, y, 1)
+                }
+
+                unsafe fn gemv(
+                    handle: cublasHandle_t,
+                    op: cublasOperation_t,
+                    m: libc::c_int,
+                    n: libc::c_int,
+                    alpha: &Self,
+                    a: *const Self,
+                    lda: libc::c_int,
+                    x: *const Self,
+                    beta: &Self,
+                    y: *mut Self,
+                ) -> cublasStatus_t {
+                    $gemv(handle, op, m, n, alpha, a, lda, x, 1, beta, y, 1)
+                }
+
+                unsafe fn gemm(
+                    handle: cublasHandle_t,
+                    op_a: cublasOperation_t,
+                    op_b: cublasOperation_t,
+                    m: libc::c_int,
+                    n: libc::c_int,
+                    k: libc::c_int,
+                    alpha: &Self,
+                    a: *const Self,
+                    lda: libc::c_int,
+                    b: *const Self,
+                    ldb: libc::c_int,
+                    beta: &Self,
+                    c: *mut Self,
+                    ldc: libc::c_int,
+                ) -> cublasStatus_t {
+                    $gemm(
+                        handle, op_a, op_b, m, n, k, alpha, a, lda, b, ldb, beta, c, ldc,
+                    )
+                }
+
+                unsafe fn gemm_strided_batched(
+                    handle: cublasHandle_t,
+                    op_a: cublasOperation_t,
+                    op_b: cublasOperation_t,
+                    m: libc::c_int,
+                    n: libc::c_int,
+                    k: libc::c_int,
+                    alpha: &Self,
+                    a: *const Self,
+                    lda: libc::c_int,
+                    stride_a: libc::c_long,
+                    b: *const Self,
+                    ldb: libc::c_int,
+                    stride_b: libc::c_long,
+                    beta: &Self,
+                    c: *mut Self,
+                    ldc: libc::c_int,
+                    stride_c: libc::c_long,
+                    batch: libc::c_int,
+                ) -> cublasStatus_t {
+                    $gemm_batched(
+                        handle, op_a, op_b, m, n, k, alpha, a, lda, stride_a, b, ldb,
+                        stride_b, beta, c, ldc, stride_c, batch,
+                    )
+                }
+            }
+        };
+    }
+
+    impl_cublas_scalar!(
+        f32,
+        cublasSaxpy_v2,
+        cublasSgemv_v2,
+        cublasSgemm_v2,
+        cublasSgemmStridedBatched
+    );
+    impl_cublas_scalar!(
+        f64,
+        cublasDaxpy_v2,
+        cublasDgemv_v2,
+        cublasDgemm_v2,
+        cublasDgemmStridedBatched
+    );
+
     /// Reference implementation for the `Axpy` kernel.
-    fn saxpy_reference(
+    fn axpy_reference<S: CublasScalar>(
         handle: &CublasHandle,
         (n, _): (i32, bool),
         context: &cuda::Context,
     ) -> f64 {
         let n = n as libc::c_int;
-        let alpha = context.get_param("alpha").raw_ptr() as *const f32;
+        let alpha = context.get_param("alpha").raw_ptr() as *const S;
         unsafe {
             let x = get_array("x", context);
             let y = get_array("y", context);
-            time_cuda(|| check_cublas(cublasSaxpy_v2(handle.0, n, alpha, x, 1, y, 1)))
+            time_cuda(|| check_cublas(S::axpy(handle.0, n, alpha, x, y)))
         }
     }
 
     /// Reference implementation for the matrix-vector multiplication.
-    fn matvec_reference(
+    fn matvec_reference<S: CublasScalar>(
         handle: &CublasHandle,
         &(m, n, _): &(i32, i32, bool),
         context: &cuda::Context,
     ) -> f64 {
         let m = m as libc::c_int;
         let n = n as libc::c_int;
+        let (alpha, beta) = (S::from_f32(2.), S::from_f32(3.));
         unsafe {
             let x = get_array("x", context);
             let a = get_array("a", context);
             let y = get_array("y", context);
             time_cuda(|| {
                 let op = cublasOperation_t_CUBLAS_OP_T;
-                check_cublas(cublasSgemv_v2(
-                    handle.0, op, n, m, &2., a, n, x, 1, &3., y, 1,
-                ))
+                check_cublas(S::gemv(handle.0, op, n, m, &alpha, a, n, x, &beta, y))
             })
         }
     }
 
     /// Reference implementation for the matrix-matrix multiplication.
-    fn matmul_reference(
+    fn matmul_reference<S: CublasScalar>(
         handle: &CublasHandle,
         params: &linalg::FusedMMP,
         context: &cuda::Context,
@@ -210,7 +506,12 @@ mod cuda_reference {
         let m = params.m as libc::c_int;
         let n = params.n as libc::c_int;
         let k = params.k as libc::c_int;
-        assert!(params.a_stride == 1);
+        let (one, zero) = (S::from_f32(1.), S::from_f32(0.));
+        if params.a_stride != 1 {
+            // cuBLAS expects the rows of `A` to be contiguous.
+            warn!("cuda reference is not implemented for strided matrices");
+            return 1.;
+        }
         unsafe {
             let a = get_array("a", context);
             let b = get_array("b", context);
@@ -226,15 +527,15 @@ mod cuda_reference {
                 (CUBLAS_N, n)
             };
             time_cuda(|| {
-                check_cublas(cublasSgemm_v2(
-                    handle.0, op_b, op_a, n, m, k, &1., b, ldb, a, lda, &0., c, n,
+                check_cublas(S::gemm(
+                    handle.0, op_b, op_a, n, m, k, &one, b, ldb, a, lda, &zero, c, n,
                 ));
             })
         }
     }
 
     /// Reference implementation for the matrix-matrix multiplication.
-    fn batchmm_reference(
+    fn batchmm_reference<S: CublasScalar>(
         handle: &CublasHandle,
         params: &linalg::BatchMMP,
         context: &cuda::Context,
@@ -243,6 +544,7 @@ mod cuda_reference {
         let n = params.n as libc::c_int;
         let k = params.k as libc::c_int;
         let batch = params.batch as libc::c_int;
+        let (one, zero) = (S::from_f32(1.), S::from_f32(0.));
         unsafe {
             let a = get_array("a", context);
             let b = get_array("b", context);
@@ -261,22 +563,24 @@ mod cuda_reference {
             let stride_b = libc::c_long::from(if params.batch_b { n * k } else { 0 });
             let stride_c = libc::c_long::from(m * n);
             time_cuda(|| {
-                check_cublas(cublasSgemmStridedBatched(
-                    handle.0, op_b, op_a, n, m, k, &1., b, ldb, stride_b, a, lda,
-                    stride_a, &0., c, n, stride_c, batch,
+                check_cublas(S::gemm_strided_batched(
+                    handle.0, op_b, op_a, n, m, k, &one, b, ldb, stride_b, a, lda,
+                    stride_a, &zero, c, n, stride_c, batch,
                 ));
             })
         }
     }
 
     /// Reference implementation for `Gesummv`.
-    fn gesummv_reference(
+    fn gesummv_reference<S: CublasScalar>(
         handle: &CublasHandle,
         &(m, n, _): &(i32, i32, bool),
         context: &cuda::Context,
     ) -> f64 {
         let m = m as libc::c_int;
         let n = n as libc::c_int;
+        let (alpha, beta) = (S::from_f32(3.1), S::from_f32(4.1));
+        let (one, zero) = (S::from_f32(1.), S::from_f32(0.));
         unsafe {
             let a = get_array("a", context);
             let b = get_array("b", context);
@@ -284,71 +588,255 @@ mod cuda_reference {
             let y = get_array("y", context);
             time_cuda(|| {
                 let op = cublasOperation_t_CUBLAS_OP_T;
-                check_cublas(cublasSgemv_v2(
-                    handle.0, op, n, m, &3.1, a, n, x, 1, &0., y, 1,
-                ));
-                check_cublas(cublasSgemv_v2(
-                    handle.0, op, n, m, &4.1, b, n, x, 1, &1., y, 1,
-                ));
+                check_cublas(S::gemv(handle.0, op, n, m, &alpha, a, n, x, &zero, y));
+                check_cublas(S::gemv(handle.0, op, n, m, &beta, b, n, x, &one, y));
             })
         }
     }
 
-    impl<'a> Reference<'a, linalg::Axpy<'a, f32>> for CublasHandle {
-        type Context = cuda::Context<'a>;
-
-        fn eval_reference(&self, params: &(i32, bool), context: &Self::Context) -> f64 {
-            saxpy_reference(self, *params, context)
+    /// Reference implementation for `Fused2MM`, with two matrix-matrix multiplications.
+    fn fused2mm_reference<S: CublasScalar>(
+        handle: &CublasHandle,
+        params: &linalg::Fused2MMP,
+        context: &cuda::Context,
+    ) -> f64 {
+        assert!(params.activation_fun.is_none());
+        let m = params.m as libc::c_int;
+        let n = params.n as libc::c_int;
+        let k = params.k as libc::c_int;
+        let p = params.p as libc::c_int;
+        let op = |transpose| if transpose { CUBLAS_T } else { CUBLAS_N };
+        let ld = |transpose, rows, cols| if transpose { rows } else { cols };
+        let (op_a, lda) = (op(params.transpose_a), ld(params.transpose_a, m, k));
+        let (op_b, ldb) = (op(params.transpose_b), ld(params.transpose_b, k, n));
+        let (op_c, ldc) = (op(params.transpose_c), ld(params.transpose_c, n, p));
+        let (alpha, beta) = (S::from_f32(params.alpha), S::from_f32(params.beta));
+        let (one, zero) = (S::from_f32(1.), S::from_f32(0.));
+        assert!(!params.transpose_d);
+        unsafe {
+            let a = get_array::<S>("a", context);
+            let b = get_array::<S>("b", context);
+            let c = get_array::<S>("c", context);
+            let d = get_array::<S>("d", context);
+            let e = get_array::<S>("e", context);
+            let mut ab = 0;
+            let ab_size = (m * n) as usize * std::mem::size_of::<S>();
+            check_cuda(cuMemAlloc_v2(&mut ab, ab_size));
+            let e_size = (m * p) as usize * std::mem::size_of::<S>();
+            let time = time_cuda(|| {
+                check_cublas(S::gemm(
+                    handle.0,
+                    op_b,
+                    op_a,
+                    n,
+                    m,
+                    k,
+                    &alpha,
+                    b,
+                    ldb,
+                    a,
+                    lda,
+                    &zero,
+                    ab as *mut S,
+                    n,
+                ));
+                check_cuda(cuMemcpyDtoD_v2(e as CUdeviceptr, d as CUdeviceptr, e_size));
+                check_cublas(S::gemm(
+                    handle.0,
+                    op_c,
+                    CUBLAS_N,
+                    p,
+                    m,
+                    n,
+                    &one,
+                    c,
+                    ldc,
+                    ab as *mut S,
+                    n,
+                    &beta,
+                    e,
+                    p,
+                ));
+            });
+            check_cuda(cuMemFree_v2(ab));
+            time
         }
     }
 
-    impl<'a> Reference<'a, linalg::MatVec<'a, f32>> for CublasHandle {
-        type Context = cuda::Context<'a>;
+    /// Reference implementation for `DepthwiseConv`, as a grouped convolution with one
+    /// group per channel. The input array is already padded.
+    fn depthwise_conv_reference(
+        handle: &CublasHandle,
+        params: &linalg::DepthwiseConvP,
+        context: &cuda::Context,
+    ) -> f64 {
+        let conv = Convolution::depthwise(params);
+        unsafe {
+            let input = get_array("input", context);
+            let filter = get_array("filter", context);
+            let output = get_array("output", context);
+            let conv = conv.prepare(&handle.1, input, filter, output);
+            time_cuda(|| conv.run())
+        }
+    }
 
-        fn eval_reference(
-            &self,
-            params: &(i32, i32, bool),
-            context: &Self::Context,
-        ) -> f64 {
-            matvec_reference(self, params, context)
+    /// Reference implementation for `PointwiseConv`, as a convolution with a 1x1 filter.
+    fn pointwise_conv_reference(
+        handle: &CublasHandle,
+        params: &linalg::PointwiseConvP,
+        context: &cuda::Context,
+    ) -> f64 {
+        let conv = Convolution::pointwise(params);
+        unsafe {
+            let input = get_array("input", context);
+            let filter = get_array("filter", context);
+            let output = get_array("output", context);
+            let conv = conv.prepare(&handle.1, input, filter, output);
+            time_cuda(|| conv.run())
         }
     }
 
-    impl<'a> Reference<'a, linalg::FusedMM<'a, f32>> for CublasHandle {
-        type Context = cuda::Context<'a>;
+    /// Implements `Reference` for the kernels with elements of type `$t` that have a
+    /// cuBLAS reference.
+    macro_rules! cublas_reference {
+        ($($t:ident),*) => {
+            $(impl<'a> Reference<'a, linalg::Axpy<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    params: &(i32, bool),
+                    context: &Self::Context,
+                ) -> f64 {
+                    axpy_reference::<$t>(self, *params, context)
+                }
+            }
 
-        fn eval_reference(
-            &self,
-            params: &linalg::FusedMMP,
-            context: &Self::Context,
-        ) -> f64 {
-            matmul_reference(self, params, context)
-        }
+            impl<'a> Reference<'a, linalg::MatVec<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    params: &(i32, i32, bool),
+                    context: &Self::Context,
+                ) -> f64 {
+                    matvec_reference::<$t>(self, params, context)
+                }
+            }
+
+            impl<'a> Reference<'a, linalg::FusedMM<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    params: &linalg::FusedMMP,
+                    context: &Self::Context,
+                ) -> f64 {
+                    matmul_reference::<$t>(self, params, context)
+                }
+            }
+
+            impl<'a> Reference<'a, linalg::BatchMM<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    params: &linalg::BatchMMP,
+                    context: &Self::Context,
+                ) -> f64 {
+                    batchmm_reference::<$t>(self, params, context)
+                }
+            }
+
+            impl<'a> Reference<'a, linalg::Gesummv<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    params: &(i32, i32, bool),
+                    context: &Self::Context,
+                ) -> f64 {
+                    gesummv_reference::<$t>(self, params, context)
+                }
+            }
+
+            impl<'a> Reference<'a, linalg::Fused2MM<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    params: &linalg::Fused2MMP,
+                    context: &Self::Context,
+                ) -> f64 {
+                    fused2mm_reference::<$t>(self, params, context)
+                }
+            })*
+        };
     }
 
-    impl<'a> Reference<'a, linalg::BatchMM<'a, f32>> for CublasHandle {
+    cublas_reference!(f32, f64);
+
+    impl<'a> Reference<'a, linalg::DepthwiseConv<'a, f32>> for CublasHandle {
         type Context = cuda::Context<'a>;
 
         fn eval_reference(
             &self,
-            params: &linalg::BatchMMP,
+            params: &linalg::DepthwiseConvP,
             context: &Self::Context,
         ) -> f64 {
-            batchmm_reference(self, params, context)
+            depthwise_conv_reference(self, params, context)
         }
     }
 
-    impl<'a> Reference<'a, linalg::Gesummv<'a, f32>> for CublasHandle {
+    impl<'a> Reference<'a, linalg::PointwiseConv<'a, f32>> for CublasHandle {
         type Context = cuda::Context<'a>;
 
         fn eval_reference(
             &self,
-            params: &(i32, i32, bool),
+            params: &linalg::PointwiseConvP,
             context: &Self::Context,
         ) -> f64 {
-            gesummv_reference(self, params, context)
+            pointwise_conv_reference(self, params, context)
         }
     }
+
+    /// Implements `Reference` for kernels with elements of type `$t` whose cuBLAS or
+    /// cuDNN reference is not implemented.
+    macro_rules! no_reference {
+        ($t:ident: $($kernel:ident),*) => {
+            $(impl<'a> Reference<'a, linalg::$kernel<'a, $t>> for CublasHandle {
+                type Context = cuda::Context<'a>;
+
+                fn eval_reference(
+                    &self,
+                    _params: &<linalg::$kernel<'a, $t> as Kernel<'a>>::Parameters,
+                    _context: &Self::Context,
+                ) -> f64 {
+                    warn!(
+                        "cuda reference is not implemented for {} with {}",
+                        linalg::$kernel::<'a, $t>::name(),
+                        stringify!($t)
+                    );
+                    1.
+                }
+            })*
+        };
+    }
+
+    no_reference!(f32: LayerNorm, Conv2d);
+    no_reference!(
+        F16: Axpy,
+        MatVec,
+        FusedMM,
+        BatchMM,
+        Gesummv,
+        Fused2MM,
+        DepthwiseConv,
+        PointwiseConv,
+        LayerNorm,
+        Conv2d
+    );
+    no_reference!(f64: DepthwiseConv, PointwiseConv, LayerNorm, Conv2d);
 }
 
 #[cfg(feature = "cuda")]
@@ -357,7 +845,7 @@ pub use cuda_reference::CublasHandle;
 #[cfg(feature = "x86")]
 mod x86_reference {
     use log::warn;
-    use telamon_kernels::linalg;
+    use telamon_kernels::Kernel;
 
     use super::Reference;
 
@@ -366,70 +854,89 @@ mod x86_reference {
         _priv: (),
     }
 
-    impl<'a> Reference<'a, linalg::Axpy<'a, f32>> for X86Reference {
-        type Context = telamon_x86::Context;
-
-        fn eval_reference(&self, _params: &(i32, bool), _context: &Self::Context) -> f64 {
-            warn!("x86 reference is not implemented");
-            1.
-        }
-    }
-
-    impl<'a> Reference<'a, linalg::MatVec<'a, f32>> for X86Reference {
+    impl<'a, K> Reference<'a, K> for X86Reference
+    where
+        K: Kernel<'a>,
+    {
         type Context = telamon_x86::Context;
 
         fn eval_reference(
             &self,
-            _params: &(i32, i32, bool),
+            _params: &K::Parameters,
             _context: &Self::Context,
         ) -> f64 {
             warn!("x86 reference is not implemented");
             1.
         }
     }
+}
 
-    impl<'a> Reference<'a, linalg::Gesummv<'a, f32>> for X86Reference {
-        type Context = telamon_x86::Context;
+#[cfg(feature = "x86")]
+pub use x86_reference::X86Reference;
 
-        fn eval_reference(
-            &self,
-            _params: &(i32, i32, bool),
-            _context: &Self::Context,
-        ) -> f64 {
-            warn!("x86 reference is not implemented");
-            1.
-        }
+#[cfg(feature = "opencl")]
+mod opencl_reference {
+    use log::warn;
+    use telamon_kernels::Kernel;
+
+    use super::Reference;
+
+    #[derive(Default)]
+    pub struct OpenClReference {
+        _priv: (),
     }
 
-    impl<'a> Reference<'a, linalg::FusedMM<'a, f32>> for X86Reference {
-        type Context = telamon_x86::Context;
+    impl<'a, K> Reference<'a, K> for OpenClReference
+    where
+        K: Kernel<'a>,
+    {
+        type Context = telamon_opencl::Context;
 
         fn eval_reference(
             &self,
-            _params: &linalg::FusedMMP,
+            _params: &K::Parameters,
             _context: &Self::Context,
         ) -> f64 {
-            warn!("x86 reference is not implemented");
+            warn!("OpenCL reference is not implemented");
             1.
         }
     }
+}
 
-    impl<'a> Reference<'a, linalg::BatchMM<'a, f32>> for X86Reference {
-        type Context = telamon_x86::Context;
+#[cfg(feature = "opencl")]
+pub use opencl_reference::OpenClReference;
+
+#[cfg(feature = "vulkan")]
+mod vulkan_reference {
+    use log::warn;
+    use telamon_kernels::Kernel;
+
+    use super::Reference;
+
+    #[derive(Default)]
+    pub struct VulkanReference {
+        _priv: (),
+    }
+
+    impl<'a, K> Reference<'a, K> for VulkanReference
+    where
+        K: Kernel<'a>,
+    {
+        type Context = telamon_vulkan::Context;
 
         fn eval_reference(
             &self,
-            _params: &linalg::BatchMMP,
+            _params: &K::Parameters,
             _context: &Self::Context,
         ) -> f64 {
-            warn!("x86 reference is not implemented");
+            warn!("Vulkan reference is not implemented");
             1.
         }
     }
 }
 
-#[cfg(feature = "x86")]
-pub use x86_reference::X86Reference;
+#[cfg(feature = "vulkan")]
+pub use vulkan_reference::VulkanReference;
 
 /// A wrapper type containing a (list of) candidates; a checking function to ensure that an
 /// implementation's output is valid, and a reference function to compare to.
@@ -439,6 +946,46 @@ pub struct KernelBundle<'a> {
     pub reference_fn: Box<dyn Fn() -> f64 + 'a>,
 }
 
+/// Type of the elements the kernels operate on.
+///
+/// Only floating-point types are available: the kernels compute their expected output with
+/// `telamon_kernels::Scalar`, which requires floating-point operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DType {
+    F16,
+    F32,
+    F64,
+}
+
+impl Default for DType {
+    fn default() -> Self {
+        DType::F32
+    }
+}
+
+impl fmt::Display for DType {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match self {
+            DType::F16 => "f16",
+            DType::F32 => "f32",
+            DType::F64 => "f64",
+        })
+    }
+}
+
+impl std::str::FromStr for DType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f16" => Ok(DType::F16),
+            "f32" => Ok(DType::F32),
+            "f64" => Ok(DType::F64),
+            _ => Err(format!("invalid element type: {}", s)),
+        }
+    }
+}
+
 /// Helper enum to create the supported kernel parameters.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KernelParam {
@@ -465,73 +1012,150 @@ pub enum KernelParam {
         m: i32,
         n: i32,
         k: i32,
+        grid_stride: Option<u32>,
+    },
+    Fused2MM {
+        m: i32,
+        n: i32,
+        k: i32,
+        p: i32,
+    },
+    DepthwiseConv {
+        b: i32,
+        c: i32,
+        h: i32,
+        w: i32,
+    },
+    PointwiseConv {
+        b: i32,
+        c: i32,
+        k: i32,
+        h: i32,
+        w: i32,
+    },
+    LayerNorm {
+        m: i32,
+        n: i32,
     },
 }
 
 impl KernelParam {
-    /// Build the kernel in a given context, and returns a list of candidates along with a
-    /// correction checking function and a reference function.
-    pub fn to_bundle<'a, 'b, C, R>(
+    /// Checks that the parameters describe a valid kernel, so that errors are reported before
+    /// a search starts rather than when building the search space.
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        match *self {
+            KernelParam::Axpy { n } => linalg::Axpy::<f32>::validate_params(&(n, true)),
+            KernelParam::MatVec { m, n } => {
+                linalg::MatVec::<f32>::validate_params(&(m, n, true))
+            }
+            KernelParam::Gesummv { m, n } => {
+                linalg::Gesummv::<f32>::validate_params(&(m, n, true))
+            }
+            KernelParam::Gemm { m, n, k, .. } => {
+                linalg::FusedMMP::new(m, n, k).validate()
+            }
+            KernelParam::BatchMM {
+                b,
+                m,
+                n,
+                k,
+                grid_stride,
+            } => batchmm_params(b, m, n, k, grid_stride).validate(),
+            KernelParam::Fused2MM { m, n, k, p } => {
+                fused2mm_params(m, n, k, p).validate()
+            }
+            KernelParam::DepthwiseConv { b, c, h, w } => {
+                depthwise_conv_params(b, c, h, w).validate()
+            }
+            KernelParam::PointwiseConv { b, c, k, h, w } => {
+                linalg::PointwiseConvP::new(b, c, k, h, w).validate()
+            }
+            KernelParam::LayerNorm { m, n } => linalg::LayerNormP::new(m, n).validate(),
+        }
+    }
+}
+
+/// Kernel descriptions that can be built into a `KernelBundle`.
+pub trait ToBundle {
+    /// Build the kernel in a given context, with elements of type `dtype`, and returns a list
+    /// of candidates along with a correction checking function and a reference function.
+    /// The arguments of the kernel are filled with random data generated from `seed`, and
+    /// the tolerances of the correction checks are multiplied by `accuracy_budget`.
+    fn to_bundle<'a, 'b, C, R>(
         &self,
+        dtype: DType,
+        seed: u64,
+        accuracy_budget: f64,
         context: &'b mut C,
         reference: R,
     ) -> (KernelBundle<'b>, &'b C)
     where
         C: Context + ArgMap<'a>,
-        R: Reference<'a, linalg::Axpy<'a, f32>, Context = C>
-            + Reference<'a, linalg::MatVec<'a, f32>, Context = C>
-            + Reference<'a, linalg::FusedMM<'a, f32>, Context = C>
-            + Reference<'a, linalg::BatchMM<'a, f32>, Context = C>
-            + Reference<'a, linalg::Gesummv<'a, f32>, Context = C>
+        R: KernelReferences<'a, F16, C>
+            + KernelReferences<'a, f32, C>
+            + KernelReferences<'a, f64, C>
             + 'b,
-        'a: 'b,
-    {
-        struct Builder<'b, C, R> {
-            context: &'b mut C,
-            reference: R,
-        }
-
-        impl<'b, C, R> Builder<'b, C, R> where {
-            fn build<'a, K>(self, params: K::Parameters) -> (KernelBundle<'b>, &'b C)
-            where
-                K: Kernel<'a> + 'b,
-                K::Parameters: 'b,
-                C: Context + ArgMap<'a>,
-                R: Reference<'a, K, Context = C> + 'b,
-            {
-                let (signature, kernel, context) =
-                    KernelBuilder::default().build::<K, C>(params.clone(), self.context);
-                let signature = Arc::new(signature);
-                let expected = kernel.get_expected_output(context);
-                let candidates = kernel.build_body(signature, context);
-                let check_fn =
-                    move |context: &dyn Context| kernel.check_result(&expected, context);
-                let reference = self.reference;
-                let reference_fn = move || {
-                    Reference::<'_, K>::eval_reference(&reference, &params, context)
-                };
+        'a: 'b;
+}
 
-                (
-                    KernelBundle {
-                        candidates,
-                        check_fn: Box::new(check_fn),
-                        reference_fn: Box::new(reference_fn),
-                    },
-                    context,
-                )
-            }
-        }
+/// Builds kernels in a context, with arguments filled from `seed`.
+pub(crate) struct BundleBuilder<'b, C, R> {
+    pub context: &'b mut C,
+    pub reference: R,
+    pub seed: u64,
+    pub accuracy_budget: f64,
+}
 
-        let builder = Builder { context, reference };
-        match *self {
-            KernelParam::Axpy { n } => {
-                builder.build::<'_, linalg::Axpy<'_, f32>>((n, true))
-            }
+impl<'b, C, R> BundleBuilder<'b, C, R> {
+    /// Builds the kernel `K` with the parameters `params`.
+    pub fn build<'a, K>(self, params: K::Parameters) -> (KernelBundle<'b>, &'b C)
+    where
+        K: Kernel<'a> + 'b,
+        K::Parameters: 'b,
+        C: Context + ArgMap<'a>,
+        R: Reference<'a, K, Context = C> + 'b,
+    {
+        let (signature, kernel, context) = KernelBuilder::default()
+            .seed(self.seed)
+            .build::<K, C>(params.clone(), self.context);
+        let signature = Arc::new(signature);
+        let expected = kernel.get_expected_output(context);
+        let candidates = kernel.build_body(signature, context);
+        let (seed, accuracy_budget) = (self.seed, self.accuracy_budget);
+        let check_fn = move |context: &dyn Context| {
+            kernel
+                .check_result_within(&expected, context, accuracy_budget)
+                .map_err(|err| format!("{} (data seed: {})", err, seed))
+        };
+        let reference = self.reference;
+        let reference_fn =
+            move || Reference::<'_, K>::eval_reference(&reference, &params, context);
+
+        (
+            KernelBundle {
+                candidates,
+                check_fn: Box::new(check_fn),
+                reference_fn: Box::new(reference_fn),
+            },
+            context,
+        )
+    }
+
+    /// Builds the kernel described by `param`, with elements of type `S`.
+    fn build_param<'a, S>(self, param: &KernelParam) -> (KernelBundle<'b>, &'b C)
+    where
+        S: Scalar,
+        C: Context + ArgMap<'a>,
+        R: KernelReferences<'a, S, C> + 'b,
+        'a: 'b,
+    {
+        match *param {
+            KernelParam::Axpy { n } => self.build::<'a, linalg::Axpy<'a, S>>((n, true)),
             KernelParam::MatVec { m, n } => {
-                builder.build::<'_, linalg::MatVec<'_, f32>>((m, n, true))
+                self.build::<'a, linalg::MatVec<'a, S>>((m, n, true))
             }
             KernelParam::Gesummv { m, n } => {
-                builder.build::<'_, linalg::Gesummv<'_, f32>>((m, n, true))
+                self.build::<'a, linalg::Gesummv<'a, S>>((m, n, true))
             }
             KernelParam::Gemm { m, n, k, ta, tb } => {
                 let mut params = linalg::FusedMMP::new(m, n, k);
@@ -541,10 +1165,66 @@ impl KernelParam {
                 if tb {
                     params = params.transpose_b();
                 }
-                builder.build::<'_, linalg::FusedMM<'_, f32>>(params)
+                self.build::<'a, linalg::FusedMM<'a, S>>(params)
+            }
+            KernelParam::BatchMM {
+                b,
+                m,
+                n,
+                k,
+                grid_stride,
+            } => self.build::<'a, linalg::BatchMM<'a, S>>(batchmm_params(
+                b,
+                m,
+                n,
+                k,
+                grid_stride,
+            )),
+            KernelParam::Fused2MM { m, n, k, p } => {
+                self.build::<'a, linalg::Fused2MM<'a, S>>(fused2mm_params(m, n, k, p))
+            }
+            KernelParam::DepthwiseConv { b, c, h, w } => self
+                .build::<'a, linalg::DepthwiseConv<'a, S>>(depthwise_conv_params(
+                    b, c, h, w,
+                )),
+            KernelParam::PointwiseConv { b, c, k, h, w } => {
+                let params = linalg::PointwiseConvP::new(b, c, k, h, w);
+                self.build::<'a, linalg::PointwiseConv<'a, S>>(params)
             }
-            KernelParam::BatchMM { b, m, n, k } => builder
-                .build::<'_, linalg::BatchMM<'_, f32>>(linalg::BatchMMP::new(b, m, n, k)),
+            KernelParam::LayerNorm { m, n } => {
+                self.build::<'a, linalg::LayerNorm<'a, S>>(linalg::LayerNormP::new(m, n))
+            }
+        }
+    }
+}
+
+impl ToBundle for KernelParam {
+    fn to_bundle<'a, 'b, C, R>(
+        &self,
+        dtype: DType,
+        seed: u64,
+        accuracy_budget: f64,
+        context: &'b mut C,
+        reference: R,
+    ) -> (KernelBundle<'b>, &'b C)
+    where
+        C: Context + ArgMap<'a>,
+        R: KernelReferences<'a, F16, C>
+            + KernelReferences<'a, f32, C>
+            + KernelReferences<'a, f64, C>
+            + 'b,
+        'a: 'b,
+    {
+        let builder = BundleBuilder {
+            context,
+            reference,
+            seed,
+            accuracy_budget,
+        };
+        match dtype {
+            DType::F16 => builder.build_param::<F16>(self),
+            DType::F32 => builder.build_param::<f32>(self),
+            DType::F64 => builder.build_param::<f64>(self),
         }
     }
 }
@@ -564,13 +1244,61 @@ impl fmt::Display for KernelParam {
                 if ta { "AT" } else { "A" },
                 if tb { "BT" } else { "B" }
             ),
-            KernelParam::BatchMM { b, m, n, k } => {
-                write!(fmt, "batchmm_{}_{}_{}_{}", b, m, n, k)
+            KernelParam::BatchMM {
+                b,
+                m,
+                n,
+                k,
+                grid_stride,
+            } => {
+                write!(fmt, "batchmm_{}_{}_{}_{}", b, m, n, k)?;
+                if let Some(tiles) = grid_stride {
+                    write!(fmt, "_gs{}", tiles)?;
+                }
+                Ok(())
+            }
+            KernelParam::Fused2MM { m, n, k, p } => {
+                write!(fmt, "fused2mm_{}_{}_{}_{}", m, n, k, p)
             }
+            KernelParam::DepthwiseConv { b, c, h, w } => {
+                write!(fmt, "depthwiseconv_{}_{}_{}_{}", b, c, h, w)
+            }
+            KernelParam::PointwiseConv { b, c, k, h, w } => {
+                write!(fmt, "pointwiseconv_{}_{}_{}_{}_{}", b, c, k, h, w)
+            }
+            KernelParam::LayerNorm { m, n } => write!(fmt, "layernorm_{}_{}", m, n),
         }
     }
 }
 
+/// Parameters of the `BatchMM` kernels built from the command line. If `grid_stride` is
+/// set, the batch dimension is split into that many tiles, each iterating over its share
+/// of the batch.
+fn batchmm_params(
+    b: i32,
+    m: i32,
+    n: i32,
+    k: i32,
+    grid_stride: Option<u32>,
+) -> linalg::BatchMMP {
+    let params = linalg::BatchMMP::new(b, m, n, k);
+    match grid_stride {
+        Some(tiles) => params.grid_stride(tiles),
+        None => params,
+    }
+}
+
+/// Parameters of the `Fused2MM` kernels built from the command line.
+fn fused2mm_params(m: i32, n: i32, k: i32, p: i32) -> linalg::Fused2MMP {
+    linalg::Fused2MMP::new(m, n, k, p, 3.1, 4.1)
+}
+
+/// Parameters of the `DepthwiseConv` kernels built from the command line: 3x3 filters
+/// with a stride of 1, padded so that the output has the size of the input.
+fn depthwise_conv_params(b: i32, c: i32, h: i32, w: i32) -> linalg::DepthwiseConvP {
+    linalg::DepthwiseConvP::new(b, c, h, w).padding(1)
+}
+
 /// An error which can be returned when parsing a kernel.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseKernelError {
@@ -595,6 +1323,9 @@ pub enum KernelErrorKind {
 
     /// A non-integer value was found where an integer value was expected.
     IntError(std::num::ParseIntError),
+
+    /// The parameters were parsed but do not describe a valid kernel.
+    InvalidParameters(ParamsError),
 }
 
 impl ParseKernelError {
@@ -618,6 +1349,9 @@ impl fmt::Display for ParseKernelError {
                 fmt.write_str("extraneous unexpected kernel parameter")
             }
             KernelErrorKind::IntError(error) => fmt::Display::fmt(error, fmt),
+            KernelErrorKind::InvalidParameters(error) => {
+                write!(fmt, "invalid kernel parameters: {}", error)
+            }
         }
     }
 }
@@ -626,6 +1360,7 @@ impl Error for ParseKernelError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match &self.kind {
             KernelErrorKind::IntError(error) => Some(error),
+            KernelErrorKind::InvalidParameters(error) => Some(error),
             _ => None,
         }
     }
@@ -705,7 +1440,50 @@ impl std::str::FromStr for KernelParam {
                 let m = parse_i32(next_part(&mut parts)?)?;
                 let n = parse_i32(next_part(&mut parts)?)?;
                 let k = parse_i32(next_part(&mut parts)?)?;
-                BatchMM { b, m, n, k }
+                // An optional `gs<tiles>` suffix enables grid-stride tiling of the batch.
+                let grid_stride = match parts.next() {
+                    None => None,
+                    Some(part) if part.starts_with("gs") => Some(part[2..].parse()?),
+                    Some(_) => {
+                        return Err(ParseKernelError {
+                            kind: KernelErrorKind::UnexpectedParameter,
+                        })
+                    }
+                };
+                BatchMM {
+                    b,
+                    m,
+                    n,
+                    k,
+                    grid_stride,
+                }
+            }
+            "fused2mm" => {
+                let m = parse_i32(next_part(&mut parts)?)?;
+                let n = parse_i32(next_part(&mut parts)?)?;
+                let k = parse_i32(next_part(&mut parts)?)?;
+                let p = parse_i32(next_part(&mut parts)?)?;
+                Fused2MM { m, n, k, p }
+            }
+            "depthwiseconv" => {
+                let b = parse_i32(next_part(&mut parts)?)?;
+                let c = parse_i32(next_part(&mut parts)?)?;
+                let h = parse_i32(next_part(&mut parts)?)?;
+                let w = parse_i32(next_part(&mut parts)?)?;
+                DepthwiseConv { b, c, h, w }
+            }
+            "pointwiseconv" => {
+                let b = parse_i32(next_part(&mut parts)?)?;
+                let c = parse_i32(next_part(&mut parts)?)?;
+                let k = parse_i32(next_part(&mut parts)?)?;
+                let h = parse_i32(next_part(&mut parts)?)?;
+                let w = parse_i32(next_part(&mut parts)?)?;
+                PointwiseConv { b, c, k, h, w }
+            }
+            "layernorm" => {
+                let m = parse_i32(next_part(&mut parts)?)?;
+                let n = parse_i32(next_part(&mut parts)?)?;
+                LayerNorm { m, n }
             }
             _ => {
                 return Err(ParseKernelError {
@@ -715,12 +1493,15 @@ impl std::str::FromStr for KernelParam {
         };
 
         if parts.next().is_some() {
-            Err(ParseKernelError {
+            return Err(ParseKernelError {
                 kind: KernelErrorKind::UnexpectedParameter,
-            })
-        } else {
-            Ok(result)
+            });
         }
+
+        result.validate().map_err(|error| ParseKernelError {
+            kind: KernelErrorKind::InvalidParameters(error),
+        })?;
+        Ok(result)
     }
 }
 
@@ -729,6 +1510,8 @@ impl std::str::FromStr for KernelParam {
 pub enum Platform {
     X86,
     Cuda,
+    OpenCl,
+    Vulkan,
     __Unsupported,
 }
 
@@ -739,6 +1522,8 @@ impl std::str::FromStr for Platform {
         Ok(match s {
             "x86" => Platform::X86,
             "cuda" => Platform::Cuda,
+            "opencl" => Platform::OpenCl,
+            "vulkan" => Platform::Vulkan,
             _ => return Err(format!("invalid platform: {}", s)),
         })
     }
@@ -756,6 +1541,10 @@ impl Platform {
             Platform::Cuda => {
                 PlatformContextBuilder::Cuda(telamon_cuda::Executor::init())
             }
+            #[cfg(feature = "opencl")]
+            Platform::OpenCl => PlatformContextBuilder::OpenCl,
+            #[cfg(feature = "vulkan")]
+            Platform::Vulkan => PlatformContextBuilder::Vulkan,
             _ => panic!("platform is not supported"),
         }
     }
@@ -766,52 +1555,165 @@ pub enum PlatformContextBuilder {
     X86,
     #[cfg(feature = "cuda")]
     Cuda(telamon_cuda::Executor),
+    #[cfg(feature = "opencl")]
+    OpenCl,
+    #[cfg(feature = "vulkan")]
+    Vulkan,
+    /// Builds code for a stored GPU description, without accessing the GPU.
+    #[cfg(feature = "cuda_codegen")]
+    GpuDesc(telamon_cuda::Gpu),
+    Plugin(BackendPlugin),
 }
 
 impl PlatformContextBuilder {
-    /// Create a new context for this platform.
+    /// Loads a GPU description, as stored by the characterization of the CUDA backend.
+    ///
+    /// The resulting contexts generate code and compute bounds for the described GPU, but
+    /// do not run anything: evaluations always return 1ns.  This allows inspecting the
+    /// code on machines without a GPU nor the CUDA libraries.
+    #[cfg(feature = "cuda_codegen")]
+    pub fn from_gpu_desc<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+        let gpu = serde_json::from_reader(fs::File::open(path)?)?;
+        Ok(PlatformContextBuilder::GpuDesc(gpu))
+    }
+
+    /// Loads a GPU description.  Always fails since CUDA support is disabled.
+    #[cfg(not(feature = "cuda_codegen"))]
+    pub fn from_gpu_desc<P: AsRef<std::path::Path>>(_path: P) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "GPU descriptions require the `cuda_codegen` feature",
+        ))
+    }
+
+    /// Create a new context for this platform.  Fails if a backend plugin cannot create a
+    /// context.
     ///
     /// There can be multiple concurrent contexts on the same platform.
-    pub fn build_context(&self) -> PlatformContext<'_> {
-        match self {
+    pub fn build_context(&self) -> io::Result<PlatformContext<'_>> {
+        Ok(match self {
             #[cfg(feature = "x86")]
-            PlatformContextBuilder::X86 => PlatformContext::X86(
-                telamon_x86::Context::default(),
-                std::marker::PhantomData,
-            ),
+            PlatformContextBuilder::X86 => {
+                PlatformContext::X86(telamon_x86::Context::default(), PhantomData)
+            }
             #[cfg(feature = "cuda")]
             PlatformContextBuilder::Cuda(executor) => {
                 PlatformContext::Cuda(telamon_cuda::Context::new(executor))
             }
-        }
+            #[cfg(feature = "opencl")]
+            PlatformContextBuilder::OpenCl => {
+                PlatformContext::OpenCl(telamon_opencl::Context::new(), PhantomData)
+            }
+            #[cfg(feature = "vulkan")]
+            PlatformContextBuilder::Vulkan => {
+                PlatformContext::Vulkan(telamon_vulkan::Context::new(), PhantomData)
+            }
+            #[cfg(feature = "cuda_codegen")]
+            PlatformContextBuilder::GpuDesc(gpu) => PlatformContext::GpuDesc(
+                telamon::device::fake::Context::new(gpu.clone()),
+                PhantomData,
+            ),
+            PlatformContextBuilder::Plugin(plugin) => {
+                PlatformContext::Plugin(plugin.new_context()?)
+            }
+        })
     }
 }
 
 /// An abstraction over multiple platform's contexts.
 pub enum PlatformContext<'a> {
     #[cfg(feature = "x86")]
-    X86(telamon_x86::Context, std::marker::PhantomData<&'a ()>),
+    X86(telamon_x86::Context, PhantomData<&'a ()>),
     #[cfg(feature = "cuda")]
     Cuda(telamon_cuda::Context<'a>),
+    #[cfg(feature = "opencl")]
+    OpenCl(telamon_opencl::Context, PhantomData<&'a ()>),
+    #[cfg(feature = "vulkan")]
+    Vulkan(telamon_vulkan::Context, PhantomData<&'a ()>),
+    #[cfg(feature = "cuda_codegen")]
+    GpuDesc(
+        telamon::device::fake::Context<telamon_cuda::Gpu>,
+        PhantomData<&'a ()>,
+    ),
+    Plugin(PluginContext<'a>),
 }
 
 impl<'a> PlatformContext<'a> {
     /// Create a kernel bundle, complete with checking and reference function, for the given kernel
-    /// parameters.  Note that all platforms may not support all kernels.
-    pub fn kernel_bundle(
+    /// parameters and element type.  Note that all platforms may not support all kernels.
+    /// The arguments of the kernel are filled with random data generated from `seed`, and
+    /// the tolerances of the correction checks are multiplied by `accuracy_budget`.
+    pub fn kernel_bundle<K: ToBundle + ?Sized>(
         &mut self,
-        kernel: &KernelParam,
+        kernel: &K,
+        dtype: DType,
+        seed: u64,
+        accuracy_budget: f64,
     ) -> (KernelBundle<'_>, &dyn Context) {
         match self {
             #[cfg(feature = "x86")]
             PlatformContext::X86(context, _) => {
-                let (bundle, context) =
-                    kernel.to_bundle(context, X86Reference::default());
+                let (bundle, context) = kernel.to_bundle(
+                    dtype,
+                    seed,
+                    accuracy_budget,
+                    context,
+                    X86Reference::default(),
+                );
                 (bundle, context as &dyn Context)
             }
             #[cfg(feature = "cuda")]
             PlatformContext::Cuda(context) => {
-                let (bundle, context) = kernel.to_bundle(context, CublasHandle::new());
+                let (bundle, context) = kernel.to_bundle(
+                    dtype,
+                    seed,
+                    accuracy_budget,
+                    context,
+                    CublasHandle::new(),
+                );
+                (bundle, context as &dyn Context)
+            }
+            #[cfg(feature = "opencl")]
+            PlatformContext::OpenCl(context, _) => {
+                let (bundle, context) = kernel.to_bundle(
+                    dtype,
+                    seed,
+                    accuracy_budget,
+                    context,
+                    OpenClReference::default(),
+                );
+                (bundle, context as &dyn Context)
+            }
+            #[cfg(feature = "vulkan")]
+            PlatformContext::Vulkan(context, _) => {
+                let (bundle, context) = kernel.to_bundle(
+                    dtype,
+                    seed,
+                    accuracy_budget,
+                    context,
+                    VulkanReference::default(),
+                );
+                (bundle, context as &dyn Context)
+            }
+            #[cfg(feature = "cuda_codegen")]
+            PlatformContext::GpuDesc(context, _) => {
+                let (bundle, context) = kernel.to_bundle(
+                    dtype,
+                    seed,
+                    accuracy_budget,
+                    context,
+                    NoReference::default(),
+                );
+                (bundle, context as &dyn Context)
+            }
+            PlatformContext::Plugin(context) => {
+                let (bundle, context) = kernel.to_bundle(
+                    dtype,
+                    seed,
+                    accuracy_budget,
+                    context,
+                    PluginReference::default(),
+                );
                 (bundle, context as &dyn Context)
             }
         }
@@ -0,0 +1,66 @@
+//! Builds the kernels computing the layers imported from ONNX models.
+use telamon::device::{ArgMap, Context};
+use telamon_kernels::{linalg, Scalar, F16};
+use telamon_onnx::LayerKernel;
+
+use crate::{BundleBuilder, DType, KernelBundle, KernelReferences, ToBundle};
+
+impl<'b, C, R> BundleBuilder<'b, C, R> {
+    /// Builds the kernel computing `layer`, with elements of type `S`.
+    fn build_layer<'a, S>(self, layer: &LayerKernel) -> (KernelBundle<'b>, &'b C)
+    where
+        S: Scalar,
+        C: Context + ArgMap<'a>,
+        R: KernelReferences<'a, S, C> + 'b,
+        'a: 'b,
+    {
+        match layer {
+            LayerKernel::FusedMM(params) => {
+                self.build::<'a, linalg::FusedMM<'a, S>>(params.clone())
+            }
+            LayerKernel::BatchMM(params) => {
+                self.build::<'a, linalg::BatchMM<'a, S>>(*params)
+            }
+            LayerKernel::Conv2d(params) => {
+                self.build::<'a, linalg::Conv2d<'a, S>>(*params)
+            }
+            LayerKernel::DepthwiseConv(params) => {
+                self.build::<'a, linalg::DepthwiseConv<'a, S>>(*params)
+            }
+            LayerKernel::PointwiseConv(params) => {
+                self.build::<'a, linalg::PointwiseConv<'a, S>>(*params)
+            }
+        }
+    }
+}
+
+impl ToBundle for LayerKernel {
+    fn to_bundle<'a, 'b, C, R>(
+        &self,
+        dtype: DType,
+        seed: u64,
+        accuracy_budget: f64,
+        context: &'b mut C,
+        reference: R,
+    ) -> (KernelBundle<'b>, &'b C)
+    where
+        C: Context + ArgMap<'a>,
+        R: KernelReferences<'a, F16, C>
+            + KernelReferences<'a, f32, C>
+            + KernelReferences<'a, f64, C>
+            + 'b,
+        'a: 'b,
+    {
+        let builder = BundleBuilder {
+            context,
+            reference,
+            seed,
+            accuracy_budget,
+        };
+        match dtype {
+            DType::F16 => builder.build_layer::<F16>(self),
+            DType::F32 => builder.build_layer::<f32>(self),
+            DType::F64 => builder.build_layer::<f64>(self),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Loading of backends distributed as shared libraries.
+//!
+//! See `telamon::device::plugin` for a description of the plugin ABI.
+use std::ffi::CString;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+use libloading::Library;
+use log::info;
+
+use telamon::codegen::{Function, Size};
+use telamon::device::plugin::{self, ContextHandle, PluginVTable};
+use telamon::device::{
+    self, ArgMap, ArrayArgument, AsyncEvaluator, CancellationToken, Context, EvalMode,
+    ScalarArgument,
+};
+use telamon::ir;
+use telamon_kernels::Kernel;
+
+use crate::Reference;
+
+/// A backend plugin loaded from a shared library.
+pub struct BackendPlugin {
+    vtable: *const PluginVTable,
+    // Must be dropped last, since `vtable` points into the library.
+    _library: Library,
+}
+
+// The vtable only contains pointers to static data and functions.
+unsafe impl Send for BackendPlugin {}
+unsafe impl Sync for BackendPlugin {}
+
+impl BackendPlugin {
+    /// Loads a plugin from a shared library, and checks that it is compatible with this version
+    /// of Telamon.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let library = Library::new(path.as_ref())?;
+        let vtable =
+            unsafe { *library.get::<*const PluginVTable>(plugin::ENTRY_SYMBOL)? };
+        let plugin = BackendPlugin {
+            vtable,
+            _library: library,
+        };
+        plugin
+            .vtable()
+            .check_compatibility()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        info!(
+            "loaded backend `{}` from {}",
+            plugin.name(),
+            path.as_ref().display()
+        );
+        Ok(plugin)
+    }
+
+    fn vtable(&self) -> &PluginVTable {
+        unsafe { &*self.vtable }
+    }
+
+    /// Returns the name of the backend.
+    pub fn name(&self) -> &str {
+        self.vtable().name()
+    }
+
+    /// Creates a new evaluation context for the backend.
+    pub fn new_context(&self) -> io::Result<PluginContext<'_>> {
+        let mut error = ptr::null_mut();
+        let handle = unsafe { (self.vtable().new_context)(&mut error) };
+        if handle.is_null() {
+            let error = unsafe { self.vtable().take_error(error) };
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "plugin `{}` failed to create a context: {}",
+                    self.name(),
+                    error
+                ),
+            ))
+        } else {
+            Ok(PluginContext {
+                handle,
+                plugin: self,
+            })
+        }
+    }
+}
+
+/// An evaluation context created by a backend plugin.
+pub struct PluginContext<'a> {
+    handle: *mut ContextHandle,
+    plugin: &'a BackendPlugin,
+}
+
+// The underlying context is required to be `Send` by the plugin ABI, and `Sync` by the
+// `Context` trait.
+unsafe impl<'a> Send for PluginContext<'a> {}
+unsafe impl<'a> Sync for PluginContext<'a> {}
+
+impl<'a> PluginContext<'a> {
+    fn vtable(&self) -> &PluginVTable {
+        self.plugin.vtable()
+    }
+
+    /// Panics if a function of the plugin reports that it panicked.
+    fn check(&self, function: &str, success: bool) {
+        if !success {
+            panic!("plugin `{}` panicked in `{}`", self.plugin.name(), function);
+        }
+    }
+
+    /// Calls a function of the plugin that writes its result in `out`.
+    fn call<T, F>(&self, function: &str, f: F) -> T
+    where
+        F: FnOnce(*mut T) -> bool,
+    {
+        let mut out = MaybeUninit::uninit();
+        self.check(function, f(out.as_mut_ptr()));
+        unsafe { out.assume_init() }
+    }
+
+    /// Evaluates the reference implementation of the kernel named `kernel`, with the
+    /// parameters `params` serialized in JSON.
+    pub fn eval_reference(&self, kernel: &str, params: &str) -> io::Result<f64> {
+        let invalid = |err| io::Error::new(io::ErrorKind::InvalidInput, err);
+        let kernel = CString::new(kernel).map_err(invalid)?;
+        let params = CString::new(params).map_err(invalid)?;
+        let mut time = 0.;
+        let mut error = ptr::null_mut();
+        let success = unsafe {
+            (self.vtable().eval_reference)(
+                self.handle,
+                kernel.as_ptr(),
+                params.as_ptr(),
+                &mut time,
+                &mut error,
+            )
+        };
+        if success {
+            Ok(time)
+        } else {
+            let error = unsafe { self.vtable().take_error(error) };
+            Err(io::Error::new(io::ErrorKind::Other, error))
+        }
+    }
+}
+
+impl<'a> Drop for PluginContext<'a> {
+    fn drop(&mut self) {
+        unsafe { (self.vtable().free_context)(self.handle) }
+    }
+}
+
+impl<'a> Context for PluginContext<'a> {
+    fn device(&self) -> Arc<dyn device::Device> {
+        self.call("device", |out| unsafe {
+            (self.vtable().device)(self.handle, out)
+        })
+    }
+
+    fn evaluate(&self, space: &Function, mode: EvalMode) -> Result<f64, ()> {
+        let eval = self.call("evaluate", |out| unsafe {
+            (self.vtable().evaluate)(self.handle, space, &mode, out)
+        });
+        eval.ok_or(())
+    }
+
+    fn benchmark(&self, space: &Function, num_samples: usize) -> Vec<f64> {
+        self.call("benchmark", |out| unsafe {
+            (self.vtable().benchmark)(self.handle, space, num_samples, out)
+        })
+    }
+
+    fn async_eval<'b>(
+        &self,
+        num_workers: usize,
+        num_compilers: usize,
+        mode: EvalMode,
+        cancel: &CancellationToken,
+        inner: &(dyn Fn(&mut dyn AsyncEvaluator<'b>) + Sync),
+    ) {
+        let success = unsafe {
+            (self.vtable().async_eval)(
+                self.handle,
+                num_workers,
+                num_compilers,
+                &mode,
+                cancel,
+                &inner,
+            )
+        };
+        self.check("async_eval", success)
+    }
+
+    fn param_as_size(&self, name: &str) -> Option<u32> {
+        self.call("param_as_size", |out| unsafe {
+            (self.vtable().param_as_size)(self.handle, name.as_ptr(), name.len(), out)
+        })
+    }
+
+    fn eval_size(&self, size: &Size) -> u32 {
+        self.call("eval_size", |out| unsafe {
+            (self.vtable().eval_size)(self.handle, size, out)
+        })
+    }
+
+    fn stabilizer(&self) -> device::Stabilizer {
+        self.call("stabilizer", |out| unsafe {
+            (self.vtable().stabilizer)(self.handle, out)
+        })
+    }
+
+    fn thermal_state(&self) -> Option<device::ThermalState> {
+        self.call("thermal_state", |out| unsafe {
+            (self.vtable().thermal_state)(self.handle, out)
+        })
+    }
+}
+
+impl<'a> ArgMap<'a> for PluginContext<'a> {
+    fn bind_erased_scalar(
+        &mut self,
+        param: &ir::Parameter,
+        value: Box<dyn ScalarArgument>,
+    ) {
+        // The plugin takes ownership of the value.
+        let mut value = ManuallyDrop::new(value);
+        let success =
+            unsafe { (self.vtable().bind_scalar)(self.handle, param, &mut *value) };
+        self.check("bind_scalar", success)
+    }
+
+    fn bind_erased_array(
+        &mut self,
+        param: &ir::Parameter,
+        t: ir::Type,
+        len: usize,
+    ) -> Arc<dyn ArrayArgument + 'a> {
+        let array: Arc<dyn ArrayArgument> = self.call("bind_array", |out| unsafe {
+            (self.vtable().bind_array)(self.handle, param, &t, len, out)
+        });
+        array
+    }
+}
+
+/// Evaluates the reference implementations provided by plugin backends.
+#[derive(Default)]
+pub struct PluginReference<'b> {
+    _marker: PhantomData<&'b ()>,
+}
+
+impl<'a, 'b, K> Reference<'a, K> for PluginReference<'b>
+where
+    K: Kernel<'a>,
+    'b: 'a,
+{
+    type Context = PluginContext<'b>;
+
+    fn eval_reference(&self, params: &K::Parameters, context: &Self::Context) -> f64 {
+        serde_json::to_string(params)
+            .map_err(io::Error::from)
+            .and_then(|params| context.eval_reference(K::name(), &params))
+            .unwrap_or_else(|err| {
+                panic!("cannot evaluate the reference of {}: {}", K::name(), err)
+            })
+    }
+}
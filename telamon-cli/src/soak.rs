@@ -0,0 +1,98 @@
+//! Runs a kernel continuously to measure its performance under thermal soak.
+//!
+//! The search evaluates each candidate a few times on a device that is mostly idle, so the
+//! runtimes it reports are cold-start numbers.  When a kernel runs for minutes, the device
+//! heats up and may lower its clocks.  `soak` keeps the device busy with a single kernel
+//! and samples its runtime along with the temperature and clocks of the device.
+use std::time::{Duration, Instant};
+
+use log::info;
+use serde::Serialize;
+
+use telamon::codegen::Function;
+use telamon::device::{Context, ThermalState};
+use telamon_kernels::statistics;
+
+/// Fraction of the samples, at the end of the soak, used to compute the steady-state runtime.
+const STEADY_STATE_FRACTION: f64 = 0.25;
+
+/// A measurement taken while soaking a kernel.
+#[derive(Debug, Clone, Serialize)]
+pub struct SoakSample {
+    /// Time since the start of the soak, in seconds.
+    pub elapsed: f64,
+    /// Mean runtime of the kernel executions since the previous sample, in nanoseconds.
+    pub runtime: f64,
+    /// State of the device, if it exposes its sensors.
+    pub thermal_state: Option<ThermalState>,
+}
+
+/// Performance of a kernel under thermal soak, compared to its cold-start performance.
+#[derive(Debug, Clone, Serialize)]
+pub struct SoakReport {
+    /// Runtime measured before the soak, in nanoseconds.
+    pub cold_runtime: f64,
+    /// Mean runtime over the last samples of the soak, in nanoseconds.
+    pub steady_runtime: f64,
+    /// Ratio of the steady-state runtime to the cold-start runtime.
+    pub slowdown: f64,
+    /// Highest temperature of the device during the soak, in degrees Celsius.
+    pub max_temperature: Option<u32>,
+    /// Lowest clock of the multiprocessors during the soak, in MHz.
+    pub min_sm_clock: Option<u32>,
+    pub samples: Vec<SoakSample>,
+}
+
+impl SoakReport {
+    fn new(cold_runtime: f64, samples: Vec<SoakSample>) -> Self {
+        let num_steady = (samples.len() as f64 * STEADY_STATE_FRACTION).ceil() as usize;
+        let steady = samples[samples.len() - num_steady..]
+            .iter()
+            .map(|sample| sample.runtime)
+            .collect::<Vec<_>>();
+        let steady_runtime = statistics::mean(&steady);
+        let thermal_states = samples.iter().filter_map(|sample| sample.thermal_state);
+        SoakReport {
+            cold_runtime,
+            steady_runtime,
+            slowdown: steady_runtime / cold_runtime,
+            max_temperature: thermal_states.clone().map(|state| state.temperature).max(),
+            min_sm_clock: thermal_states.map(|state| state.sm_clock).min(),
+            samples,
+        }
+    }
+}
+
+/// Runs `function` continuously for `duration`, taking a sample every `interval`.  At least
+/// one sample is taken.  `cold_runtime` is the runtime of the function measured on an idle
+/// device, in nanoseconds.  Returns `None` if the function fails to execute.
+pub fn soak(
+    context: &dyn Context,
+    function: &Function,
+    cold_runtime: f64,
+    duration: Duration,
+    interval: Duration,
+) -> Option<SoakReport> {
+    let mut kernel = context.compile_kernel(function);
+    let start = Instant::now();
+    let mut samples = vec![];
+    loop {
+        let sample_start = Instant::now();
+        let (mut total, mut num_runs) = (0., 0u32);
+        while num_runs == 0 || sample_start.elapsed() < interval {
+            total += kernel.evaluate()?;
+            num_runs += 1;
+        }
+        let elapsed = start.elapsed();
+        let sample = SoakSample {
+            elapsed: elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9,
+            runtime: total / f64::from(num_runs),
+            thermal_state: context.thermal_state(),
+        };
+        info!("soak sample: {:?}", sample);
+        samples.push(sample);
+        if elapsed >= duration {
+            return Some(SoakReport::new(cold_runtime, samples));
+        }
+    }
+}
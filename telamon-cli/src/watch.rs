@@ -0,0 +1,336 @@
+//! Monitors a running search from its eventlog.
+//!
+//! The search writes its progress to `watch.log`, which is meant to be read after the fact.
+//! `watch` follows the eventlog as the search writes it and displays a dashboard in the
+//! terminal: the best runtime found so far, the evaluation throughput, the causes of the
+//! dead-ends and the depth at which descents end.  Press `q` to quit.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use tui::backend::{Backend, TermionBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::widgets::{BarChart, Block, Borders, Paragraph, Text, Widget};
+use tui::{Frame, Terminal};
+
+use telamon::explorer::eventlog::EventLog;
+use telamon::explorer::mcts::{CauseOfDeath, Event, Message, NodeId, Timed};
+
+/// Window over which the evaluation throughput is computed.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// The state of a search, as reconstructed from its eventlog.
+#[derive(Default)]
+pub struct SearchState {
+    /// Time of the last logged event, relative to the start of the search.
+    pub duration: Duration,
+    pub num_nodes: usize,
+    pub num_evaluations: usize,
+    /// Best runtime found so far, in nanoseconds, with the time at which it was found.
+    pub best: Option<(f64, Duration)>,
+    /// Number of dead-ends for each cause of death.
+    pub deadends: BTreeMap<&'static str, usize>,
+    /// Number of descents that found an implementation, indexed by depth.
+    pub implementation_depths: Vec<usize>,
+    /// Number of descents that reached a dead-end, indexed by depth.
+    pub deadend_depths: Vec<usize>,
+    /// Times of the evaluations in the last `THROUGHPUT_WINDOW`.
+    recent_evaluations: VecDeque<Duration>,
+    /// Depth of each node in the tree.
+    depths: HashMap<NodeId, usize>,
+}
+
+impl SearchState {
+    /// Updates the state with a message of the eventlog.
+    pub fn update(&mut self, message: &Message) {
+        self.duration = std::cmp::max(self.duration, message.timestamp());
+        match message {
+            Message::Node { id, parent, .. } => {
+                self.num_nodes += 1;
+                let depth = parent.map_or(0, |(parent, _)| self.depths[&parent] + 1);
+                self.depths.insert(*id, depth);
+            }
+            Message::Trace { events, .. } => self.update_trace(events),
+            Message::Evaluation {
+                value, result_time, ..
+            } => {
+                self.num_evaluations += 1;
+                self.recent_evaluations.push_back(*result_time);
+                if let Some(value) = *value {
+                    if self.best.map_or(true, |(best, _)| value < best) {
+                        self.best = Some((value, *result_time));
+                    }
+                }
+            }
+            Message::Screening { .. }
+            | Message::Launch { .. }
+            | Message::Source { .. }
+            | Message::Energy { .. } => {}
+        }
+        while self
+            .recent_evaluations
+            .front()
+            .map_or(false, |&time| time + THROUGHPUT_WINDOW < self.duration)
+        {
+            self.recent_evaluations.pop_front();
+        }
+    }
+
+    /// Updates the dead-ends and the depth histograms with the events of a trace.
+    fn update_trace(&mut self, events: &[Timed<Event>]) {
+        let mut depth = 0;
+        for event in events {
+            match event.value {
+                Event::SelectNode(id) => {
+                    depth = self.depths.get(&id).cloned().unwrap_or(0)
+                }
+                Event::SelectChild(..) => depth += 1,
+                Event::Expand => (),
+                Event::Kill(cause) => self.add_deadend(cause, depth),
+                Event::KillChild(_, cause) => self.add_deadend(cause, depth + 1),
+                Event::Implementation => {
+                    increment(&mut self.implementation_depths, depth)
+                }
+            }
+        }
+    }
+
+    fn add_deadend(&mut self, cause: CauseOfDeath, depth: usize) {
+        let cause = match cause {
+            CauseOfDeath::Constraints => "constraints",
+            CauseOfDeath::PerfModel { .. } => "perf model",
+            CauseOfDeath::Backtrack => "backtrack",
+        };
+        *self.deadends.entry(cause).or_insert(0) += 1;
+        increment(&mut self.deadend_depths, depth);
+    }
+
+    /// Returns the number of evaluations per second over the last seconds of the search.
+    pub fn throughput(&self) -> f64 {
+        let window = std::cmp::min(self.duration, THROUGHPUT_WINDOW);
+        let window = window.as_secs() as f64 + f64::from(window.subsec_nanos()) * 1e-9;
+        if window > 0. {
+            self.recent_evaluations.len() as f64 / window
+        } else {
+            0.
+        }
+    }
+}
+
+/// Increments the value at `index`, extending `histogram` if needed.
+fn increment(histogram: &mut Vec<usize>, index: usize) {
+    if histogram.len() <= index {
+        histogram.resize(index + 1, 0);
+    }
+    histogram[index] += 1;
+}
+
+/// Displays a dashboard of the search writing to the eventlog at `path` until the user
+/// quits.  The dashboard is refreshed every `refresh_interval`.
+pub fn watch(path: &Path, refresh_interval: Duration) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let records = EventLog::follow(path, refresh_interval)?.records();
+    thread::spawn(move || {
+        for record in records {
+            let message = record.and_then(|bytes| {
+                bincode::deserialize::<Message>(&bytes)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            });
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut keys = termion::async_stdin().keys();
+    let stdout = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut terminal = Terminal::new(TermionBackend::new(stdout))?;
+    terminal.hide_cursor()?;
+    let mut state = SearchState::default();
+    loop {
+        for message in receiver.try_iter() {
+            state.update(&message?);
+        }
+        terminal.draw(|mut f| draw(&mut f, &state))?;
+        for key in &mut keys {
+            match key? {
+                Key::Char('q') | Key::Ctrl('c') => return terminal.show_cursor(),
+                _ => (),
+            }
+        }
+        thread::sleep(refresh_interval);
+    }
+}
+
+/// Draws the dashboard.
+fn draw<B: Backend>(f: &mut Frame<B>, state: &SearchState) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(8), Constraint::Min(0)].as_ref())
+        .split(f.size());
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(rows[0]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(rows[1]);
+
+    let best = match state.best {
+        Some((best, time)) => format!("{:.4e}ns (after {}s)", best, time.as_secs()),
+        None => "none".to_string(),
+    };
+    let summary = [
+        Text::raw(format!("Elapsed: {}s\n", state.duration.as_secs())),
+        Text::raw(format!("Best runtime: {}\n", best)),
+        Text::raw(format!("Evaluations: {}\n", state.num_evaluations)),
+        Text::raw(format!("Evaluations/s: {:.2}\n", state.throughput())),
+        Text::raw(format!("Nodes: {}\n", state.num_nodes)),
+    ];
+    Paragraph::new(summary.iter())
+        .block(Block::default().borders(Borders::ALL).title("Search"))
+        .render(f, top[0]);
+
+    let deadends = state
+        .deadends
+        .iter()
+        .map(|(&cause, &count)| (cause, count as u64))
+        .collect::<Vec<_>>();
+    BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Dead-end causes"),
+        )
+        .data(&deadends)
+        .bar_width(12)
+        .render(f, top[1]);
+
+    draw_histogram(
+        f,
+        bottom[0],
+        "Implementation depths",
+        &state.implementation_depths,
+    );
+    draw_histogram(f, bottom[1], "Dead-end depths", &state.deadend_depths);
+}
+
+/// Draws a histogram indexed by depth.
+fn draw_histogram<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    title: &str,
+    histogram: &[usize],
+) {
+    let labels = (0..histogram.len())
+        .map(|depth| depth.to_string())
+        .collect::<Vec<_>>();
+    let data = labels
+        .iter()
+        .zip(histogram)
+        .map(|(label, &count)| (label.as_str(), count as u64))
+        .collect::<Vec<_>>();
+    BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(&data)
+        .bar_width(3)
+        .render(f, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use telamon::explorer::mcts::EdgeIndex;
+
+    fn trace(time: Duration, events: Vec<Event>) -> Message {
+        Message::Trace {
+            thread: "ThreadId(1)".to_string(),
+            events: events
+                .into_iter()
+                .map(|value| Timed {
+                    start_time: time,
+                    end_time: time,
+                    value,
+                })
+                .collect(),
+        }
+    }
+
+    fn evaluation(value: Option<f64>, secs: u64) -> Message {
+        Message::Evaluation {
+            id: NodeId::from(1),
+            value,
+            result_time: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn update_search_state() {
+        let time = Duration::from_millis(1);
+        let messages = vec![
+            Message::Node {
+                id: NodeId::from(0),
+                parent: None,
+                children: vec![],
+                bound: None,
+                discovery_time: time,
+            },
+            Message::Node {
+                id: NodeId::from(1),
+                parent: Some((NodeId::from(0), EdgeIndex::from(0))),
+                children: vec![],
+                bound: None,
+                discovery_time: time,
+            },
+            trace(
+                time,
+                vec![Event::SelectNode(NodeId::from(1)), Event::Implementation],
+            ),
+            trace(
+                time,
+                vec![
+                    Event::SelectNode(NodeId::from(0)),
+                    Event::KillChild(EdgeIndex::from(1), CauseOfDeath::Constraints),
+                    Event::Kill(CauseOfDeath::PerfModel { cut: 1. }),
+                ],
+            ),
+            evaluation(Some(3.), 1),
+            evaluation(Some(2.), 2),
+            evaluation(None, 3),
+            evaluation(Some(5.), 4),
+        ];
+        let mut state = SearchState::default();
+        for message in &messages {
+            state.update(message);
+        }
+
+        assert_eq!(state.num_nodes, 2);
+        assert_eq!(state.num_evaluations, 4);
+        assert_eq!(state.duration, Duration::from_secs(4));
+        assert_eq!(state.best, Some((2., Duration::from_secs(2))));
+        assert_eq!(state.deadends["constraints"], 1);
+        assert_eq!(state.deadends["perf model"], 1);
+        assert_eq!(state.implementation_depths, vec![0, 1]);
+        assert_eq!(state.deadend_depths, vec![1, 1]);
+        assert_eq!(state.throughput(), 1.);
+    }
+
+    /// Ensures the throughput only accounts for the evaluations of the last seconds.
+    #[test]
+    fn throughput_window() {
+        let mut state = SearchState::default();
+        assert_eq!(state.throughput(), 0.);
+        for &secs in &[1, 2, 15, 20] {
+            state.update(&evaluation(Some(1.), secs));
+        }
+        assert_eq!(state.throughput(), 2. / THROUGHPUT_WINDOW.as_secs() as f64);
+    }
+}
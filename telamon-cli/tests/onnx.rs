@@ -0,0 +1,36 @@
+//! Searches the kernels of layers imported from ONNX models.
+use telamon::device::{fake, Context};
+use telamon::explorer;
+use telamon_cli::{DType, NoReference, ToBundle};
+use telamon_kernels::linalg::{self, ActivationFunction};
+use telamon_onnx::LayerKernel;
+
+/// Builds the kernel of `layer` on a fake device and searches an implementation.
+fn search_layer(layer: LayerKernel) {
+    let _ = env_logger::try_init();
+    let mut context = fake::Context::<fake::Device>::default();
+    let (bundle, context) =
+        layer.to_bundle(DType::F32, 0, 1., &mut context, NoReference::default());
+    assert!(!bundle.candidates.is_empty());
+
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    config.max_evaluations = Some(4);
+    let best = explorer::find_best_ex(&config, context, bundle.candidates, None, &[])
+        .expect("no implementation found");
+    context.device().gen_code(&best.space, &mut std::io::sink());
+}
+
+#[test]
+fn fused_mm_layer() {
+    let params = linalg::FusedMMP::new(16, 16, 16)
+        .bias()
+        .activation_fun(ActivationFunction::ReLU);
+    search_layer(LayerKernel::FusedMM(params));
+}
+
+#[test]
+fn conv2d_layer() {
+    let params = linalg::Conv2dP::new(1, 4, 4, 8, 8).padding(1);
+    search_layer(LayerKernel::Conv2d(params));
+}
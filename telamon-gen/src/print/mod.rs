@@ -123,6 +123,7 @@ lazy_static! {
         register_template!(engine, restrict_counter);
         register_template!(engine, rule);
         register_template!(engine, run_filters);
+        register_template!(engine, run_filters_parallel);
         register_template!(engine, set_constraints);
         register_template!(engine, set / from_superset);
         register_template!(engine, set / id_getter);
@@ -129,8 +129,12 @@ pub fn apply_action(action: Action, store: &mut DomainStore, diff: &mut DomainDi
     debug!("applying action {:?}", action);
     match action {
         {{~#each choices}}
-            Action::{{to_type_name name}}({{#each arguments}}{{this.[0]}}, {{/each}}value) =>
-            store.restrict_{{name}}({{#each arguments}}{{this.[0]}}, {{/each}}value, diff),
+            Action::{{to_type_name name}}({{#each arguments}}{{this.[0]}}, {{/each}}value) => {
+                #[cfg(feature="trace_propagation")]
+                crate::search_space::trace::action("{{name}}",
+                    &({{#each arguments}}{{this.[0]}}, {{/each}}), &value);
+                store.restrict_{{name}}({{#each arguments}}{{this.[0]}}, {{/each}}value, diff)
+            }
         {{~/each}}
     }
 }
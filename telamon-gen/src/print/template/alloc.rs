@@ -18,5 +18,4 @@ let ({{>lhs}}, {{>rhs}}) = if {{#if arg_names~}}
     ({{>rhs}}, {{>lhs}})
 };
 {{/if~}}
-Arc::make_mut(&mut self.{{name}}).insert(({{~>choice.arg_ids}}),
-                                          {{~>value_type.full_domain value_type}});
+self.{{name}}.insert(({{~>choice.arg_ids}}), {{~>value_type.full_domain value_type}});
@@ -7,6 +7,8 @@
     let mut values = {{>value_type.full_domain choice_full_type}};
     {{>filter_call filter_call}}
     trace!("call restrict from {}, line {}", file!(), line!());
+    #[cfg(feature="trace_propagation")]
+    crate::search_space::trace::filter("{{choice}}", &({{>choice.arg_ids}}), &values);
     {{choice}}::restrict({{>choice.arg_ids}}ir_instance, store, values, diff)?;
 {{~#if is_symmetric}}
 }
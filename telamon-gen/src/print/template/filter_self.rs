@@ -1,2 +1,4 @@
 let values = self::filter({{>choice.arg_names choice}}ir_instance, store);
+#[cfg(feature="trace_propagation")]
+crate::search_space::trace::filter("{{choice.name}}", &({{>choice.arg_ids choice}}), &values);
 self::restrict({{>choice.arg_ids choice}}ir_instance, store, values, diff)?;
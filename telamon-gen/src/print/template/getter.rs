@@ -51,7 +51,7 @@ pub fn set_{{name}}(&mut self{{>args_decl}}, mut value: {{>value_type.name value
         }
     {{~/if}}
     debug!("set {{name}}{:?} to {:?}", {{>args}}, value);
-    *unwrap!(Arc::make_mut(&mut self.{{name}}).get_mut(&{{>args}})) = value;
+    *unwrap!(self.{{name}}.get_mut(&{{>args}})) = value;
 }
 
 /// Restricts the domain of {name} for the given arguments. Put the old value in `diff`
@@ -65,7 +65,7 @@ pub fn restrict_{{name}}(&mut self{{>args_decl}}, mut value: {{>value_type.name
             {{~#if is_antisymmetric}}value = value.inverse();{{/if}}
         }
     {{~/if}}
-    let mut ptr = unwrap!(Arc::make_mut(&mut self.{{name}}).get_mut(&{{>args}}));
+    let mut ptr = unwrap!(self.{{name}}.get_mut(&{{>args}}));
     let old = *ptr;
     ptr.{{>restrict_op}}(value);
     if old != *ptr {
@@ -85,7 +85,7 @@ fn update_{{name}}(&mut self{{>args_decl}}, old_incr: {{>value_type.name value_t
             std::mem::swap(&mut {{arguments.[0].[0]}}, &mut {{arguments.[1].[0]}});
         }
     {{~/if}}
-    let mut ptr = unwrap!(Arc::make_mut(&mut self.{{name}}).get_mut(&{{>args}}));
+    let mut ptr = unwrap!(self.{{name}}.get_mut(&{{>args}}));
     let old = *ptr;
     {{#*inline "op_name"~}}
         {{#ifeq compute_counter.op "+"}}add{{else}}mul{{/ifeq~}}
@@ -12,6 +12,8 @@ use serde::Deserialize;
 #[allow(unused_imports)]
 use utils::*;
 use fxhash::FxHashMap;
+#[cfg(feature="parallel_propagation")]
+use rayon::prelude::*;
 
 {{>store}}
 
@@ -33,6 +35,9 @@ pub fn propagate_changes(diff: &mut DomainDiff, ir_instance: &mut Arc<ir::Functi
                 diff.pop_{{name}}_diff() {
             debug!("propagating {{name}}{:?} {:?} -> {:?}",
                    ({{>choice.arg_names this}}), old, new);
+            #[cfg(feature="trace_propagation")]
+            let _trace = crate::search_space::trace::on_change("{{name}}",
+                &({{>choice.arg_names this}}), &old, &new);
             {{name}}::on_change(old, new,
                 {{~>choice.arg_names this}}ir_instance, store, diff)?;
         }
@@ -66,18 +71,39 @@ use super::process_lowering;
 
 
 /// Initializes the `DomainStore` with available choices for each decision.
-#[allow(unused_variables, unused_mut)]
+#[allow(dead_code)]
 pub fn init_domain(store: &mut DomainStore,
                    ir_instance: &mut ir::Function) -> Result<Vec<Action>, ()> {
+    init_domain_with(store, ir_instance, cfg!(feature="parallel_propagation"))
+}
+
+/// Initializes the `DomainStore` with available choices for each decision.  If `parallel`
+/// is true and the `parallel_propagation` feature is enabled, the filters are computed in
+/// parallel.  Only the initial filtering is parallel: the propagation of the restrictions
+/// that follows is always sequential.
+#[allow(unused_variables, unused_mut)]
+pub fn init_domain_with(store: &mut DomainStore,
+                        ir_instance: &mut ir::Function,
+                        parallel: bool) -> Result<Vec<Action>, ()> {
     trace!("called init_domain from file {}", file!());
     // Run all the filters once.
     let ref mut diff = DomainDiff::default(); // Pass an empty diff to propagate and triggers.
     let mut unused_diff = DomainDiff::default();
+    if !(parallel && cfg!(feature="parallel_propagation")) {
     {{#each choices~}}
         {{#>loop_nest iteration_space~}}
             {{>run_filters this}}
         {{/loop_nest~}}
     {{/each~}}
+    }
+    #[cfg(feature="parallel_propagation")]
+    {
+    if parallel {
+    {{#each choices~}}
+        {{>run_filters_parallel this}}
+    {{/each~}}
+    }
+    }
     {{store.filter_all}}
     // Propagate the filters where necessary.
     let mut actions: Vec<Action> = Vec::new();
@@ -0,0 +1,24 @@
+{{#if compute_counter~}}
+    {{#>loop_nest iteration_space~}}
+        {{>run_filters this}}
+    {{/loop_nest~}}
+{{else~}}
+{
+    // Filters only read the domain, so they are computed in parallel.  Restrictions are then
+    // applied sequentially, in iteration order, to keep the propagation deterministic.
+    let mut filter_args = Vec::new();
+    {{#>loop_nest iteration_space~}}
+        filter_args.push((({{>choice.arg_ids}}), ({{>choice.arg_names}})));
+    {{/loop_nest~}}
+    let filter_values = {
+        let (ir_instance, store) = (&*ir_instance, &*store);
+        filter_args.into_par_iter().map(|(filter_ids, ({{>choice.arg_names}}))| {
+            (filter_ids, {{name}}::filter({{>choice.arg_names}}ir_instance, store))
+        }).collect::<Vec<_>>()
+    };
+    // The argument names are rebound to the ids of the arguments.
+    for (({{>choice.arg_names}}), values) in filter_values {
+        {{name}}::restrict({{>choice.arg_names}}ir_instance, store, values, &mut unused_diff)?;
+    }
+}
+{{/if~}}
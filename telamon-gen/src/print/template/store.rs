@@ -6,7 +6,7 @@
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct DomainStore {
     {{#each choices}}
-        {{name}}: Arc<FxHashMap<{{>choice_ids this}}, {{>value_type.name value_type}}>>,
+        {{name}}: CowMap<{{>choice_ids this}}, {{>value_type.name value_type}}>,
     {{/each}}
 }
 
@@ -0,0 +1,41 @@
+//! Lists the kernels computing the layers of an ONNX model, as JSON.
+use std::path::PathBuf;
+use std::{fs, io, process};
+
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "onnx_kernels")]
+struct Opt {
+    /// Path to the ONNX model.
+    #[structopt(parse(from_os_str))]
+    model: PathBuf,
+
+    /// Writes the kernels to a file instead of the standard output.
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    let import = telamon_onnx::import_file(&opt.model).unwrap_or_else(|err| {
+        eprintln!("cannot import {}: {}", opt.model.display(), err);
+        process::exit(1);
+    });
+    for skipped in &import.skipped {
+        eprintln!(
+            "skipped node `{}` ({}): {}",
+            skipped.node, skipped.op_type, skipped.reason
+        );
+    }
+    let result = match &opt.output {
+        Some(path) => fs::File::create(path)
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer_pretty(file, &import)),
+        None => serde_json::to_writer_pretty(io::stdout(), &import),
+    };
+    if let Err(err) = result {
+        eprintln!("cannot write the kernels: {}", err);
+        process::exit(1);
+    }
+}
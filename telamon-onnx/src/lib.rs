@@ -0,0 +1,729 @@
+//! Imports the layers of ONNX models as Telamon kernels.
+//!
+//! The importer maps the matrix multiplications and the 2D convolutions of a model to the
+//! kernels of `telamon_kernels`, with the sizes of the layers extracted from the graph.  A
+//! `MatMul` or a `Gemm` node is fused with the `Add` of a bias and with the `Relu` or
+//! `Sigmoid` activation that follow it into a single `FusedMM` kernel.  Nodes that cannot
+//! be mapped to a kernel are reported along with the reason they were skipped.
+//!
+//! Sizes must be known statically: symbolic dimensions, such as a dynamic batch size, must
+//! be fixed when the model is exported.
+pub mod proto;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::{error, fmt, fs, io};
+
+use serde::{Deserialize, Serialize};
+use telamon_kernels::linalg::{self, ActivationFunction};
+
+pub use crate::proto::DecodeError;
+
+/// The parameters of the kernel computing a layer.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum LayerKernel {
+    FusedMM(linalg::FusedMMP),
+    BatchMM(linalg::BatchMMP),
+    Conv2d(linalg::Conv2dP),
+    DepthwiseConv(linalg::DepthwiseConvP),
+    PointwiseConv(linalg::PointwiseConvP),
+}
+
+/// A layer of a model, mapped to a kernel.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Layer {
+    /// Names of the nodes computed by the kernel, in the order of the graph.  Nodes without
+    /// a name are designated by the name of their first output.
+    pub nodes: Vec<String>,
+    pub kernel: LayerKernel,
+}
+
+/// A node that could not be mapped to a kernel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedNode {
+    pub node: String,
+    pub op_type: String,
+    pub reason: String,
+}
+
+/// The layers found in a model.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Import {
+    pub layers: Vec<Layer>,
+    pub skipped: Vec<SkippedNode>,
+}
+
+/// Error raised when a model cannot be read.
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(fmt, "{}", err),
+            ImportError::Decode(err) => write!(fmt, "{}", err),
+        }
+    }
+}
+
+impl error::Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+impl From<DecodeError> for ImportError {
+    fn from(err: DecodeError) -> Self {
+        ImportError::Decode(err)
+    }
+}
+
+/// Imports the layers of the ONNX model stored in a file.
+pub fn import_file<P: AsRef<Path>>(path: P) -> Result<Import, ImportError> {
+    import(&fs::read(path)?)
+}
+
+/// Imports the layers of a serialized ONNX model.
+pub fn import(buf: &[u8]) -> Result<Import, ImportError> {
+    let model = proto::Model::decode(buf)?;
+    Ok(import_graph(&model.graph))
+}
+
+/// Maps the nodes of `graph` to kernels.
+pub fn import_graph(graph: &proto::Graph) -> Import {
+    let importer = Importer::new(graph);
+    let mut import = Import::default();
+    let mut fused = vec![false; graph.nodes.len()];
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        if fused[idx] {
+            continue;
+        }
+        let layer = match node.op_type.as_str() {
+            "MatMul" | "Gemm" => importer.import_matmul(idx, &mut fused),
+            "Conv" => importer.import_conv(node),
+            "Add" | "Relu" | "Sigmoid" => {
+                Err("only supported after a matrix multiplication".to_string())
+            }
+            _ => Err("unsupported operator".to_string()),
+        };
+        match layer {
+            Ok(layer) => import.layers.push(layer),
+            Err(reason) => import.skipped.push(SkippedNode {
+                node: node_name(node).to_string(),
+                op_type: node.op_type.clone(),
+                reason,
+            }),
+        }
+    }
+    import
+}
+
+type Shape = Vec<i64>;
+
+/// Maps nodes to kernels, using the shapes known for the values of the graph.
+struct Importer<'a> {
+    graph: &'a proto::Graph,
+    /// Static shapes of the values, either given by the model or inferred.
+    shapes: HashMap<&'a str, Shape>,
+    /// Indexes of the nodes reading each value.
+    consumers: HashMap<&'a str, Vec<usize>>,
+    /// Values returned by the graph, which cannot be fused away.
+    outputs: HashSet<&'a str>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(graph: &'a proto::Graph) -> Self {
+        let mut shapes = HashMap::new();
+        for tensor in &graph.initializers {
+            shapes.insert(tensor.name.as_str(), tensor.dims.clone());
+        }
+        let infos = graph
+            .inputs
+            .iter()
+            .chain(&graph.value_infos)
+            .chain(&graph.outputs);
+        for info in infos {
+            let shape = info
+                .shape
+                .as_ref()
+                .and_then(|dims| dims.iter().cloned().collect());
+            if let Some(shape) = shape {
+                shapes.entry(info.name.as_str()).or_insert(shape);
+            }
+        }
+        let mut consumers = HashMap::<_, Vec<_>>::new();
+        for (idx, node) in graph.nodes.iter().enumerate() {
+            for input in &node.inputs {
+                consumers.entry(input.as_str()).or_default().push(idx);
+            }
+        }
+        let outputs = graph
+            .outputs
+            .iter()
+            .map(|info| info.name.as_str())
+            .collect();
+        let mut importer = Importer {
+            graph,
+            shapes,
+            consumers,
+            outputs,
+        };
+        for node in &graph.nodes {
+            if let (Some(output), Some(shape)) =
+                (node.outputs.first(), importer.infer_shape(node))
+            {
+                importer.shapes.entry(output.as_str()).or_insert(shape);
+            }
+        }
+        importer
+    }
+
+    /// Returns the shape of the input of `node` at position `pos`.
+    fn input_shape(&self, node: &proto::Node, pos: usize) -> Result<&Shape, String> {
+        let name = node
+            .inputs
+            .get(pos)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| format!("missing input {}", pos))?;
+        self.shapes
+            .get(name.as_str())
+            .ok_or_else(|| format!("the shape of `{}` is unknown", name))
+    }
+
+    /// Infers the shape of the first output of `node`, if the shapes of its inputs are
+    /// known.
+    fn infer_shape(&self, node: &proto::Node) -> Option<Shape> {
+        let input = |pos: usize| {
+            let name = node.inputs.get(pos)?;
+            self.shapes.get(name.as_str())
+        };
+        match node.op_type.as_str() {
+            "MatMul" => {
+                let (a, b) = (input(0)?, input(1)?);
+                if a.len() < 2 || b.len() < 2 {
+                    return None;
+                }
+                let mut shape = broadcast(&a[..a.len() - 2], &b[..b.len() - 2])?;
+                shape.push(a[a.len() - 2]);
+                shape.push(b[b.len() - 1]);
+                Some(shape)
+            }
+            "Gemm" => {
+                let (a, b) = (input(0)?, input(1)?);
+                if a.len() != 2 || b.len() != 2 {
+                    return None;
+                }
+                let m = if int_attr(node, "transA", 0) == 0 {
+                    a[0]
+                } else {
+                    a[1]
+                };
+                let n = if int_attr(node, "transB", 0) == 0 {
+                    b[1]
+                } else {
+                    b[0]
+                };
+                Some(vec![m, n])
+            }
+            "Add" => broadcast(input(0)?, input(1)?),
+            "Relu" | "Sigmoid" => input(0).cloned(),
+            "Conv" => {
+                let (x, w) = (input(0)?, input(1)?);
+                if x.len() != 4 || w.len() != 4 {
+                    return None;
+                }
+                let conv = ConvAttributes::new(node).ok()?;
+                let out_size = |size: i64, filter: i64, stride: i64, pad: i64| {
+                    let filter = (filter - 1) * conv.dilation + 1;
+                    (size + 2 * pad - filter) / stride + 1
+                };
+                Some(vec![
+                    x[0],
+                    w[0],
+                    out_size(x[2], w[2], conv.stride, conv.padding),
+                    out_size(x[3], w[3], conv.stride, conv.padding),
+                ])
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the node reading `value`, if it is the only one to read it and if `value`
+    /// is not an output of the graph.
+    fn single_consumer(&self, value: &str) -> Option<usize> {
+        if self.outputs.contains(value) {
+            return None;
+        }
+        match self.consumers.get(value).map(|nodes| &nodes[..]) {
+            Some(&[idx]) => Some(idx),
+            _ => None,
+        }
+    }
+
+    /// Maps a `MatMul` or a `Gemm` node to a kernel, along with the bias and the
+    /// activation that follow it.  Marks the nodes computed by the kernel as fused.
+    fn import_matmul(&self, idx: usize, fused: &mut [bool]) -> Result<Layer, String> {
+        let node = &self.graph.nodes[idx];
+        let mut kernel = if node.op_type == "Gemm" {
+            LayerKernel::FusedMM(self.gemm_params(node)?)
+        } else {
+            self.matmul_kernel(node)?
+        };
+        let mut nodes = vec![idx];
+        if let LayerKernel::FusedMM(params) = &mut kernel {
+            let mut output = node.outputs[0].as_str();
+            if !params.bias {
+                let add = self
+                    .single_consumer(output)
+                    .map(|idx| (idx, &self.graph.nodes[idx]))
+                    .filter(|(_, add)| add.op_type == "Add");
+                if let Some((add_idx, add)) = add {
+                    let bias = add.inputs.iter().find(|input| *input != output);
+                    let is_bias = bias
+                        .and_then(|bias| self.shapes.get(bias.as_str()))
+                        .map_or(false, |shape| is_row(shape, i64::from(params.n)));
+                    if is_bias && add.inputs.len() == 2 {
+                        params.bias = true;
+                        nodes.push(add_idx);
+                        output = add.outputs[0].as_str();
+                    }
+                }
+            }
+            if let Some(act_idx) = self.single_consumer(output) {
+                let activation_fun = match self.graph.nodes[act_idx].op_type.as_str() {
+                    "Relu" => Some(ActivationFunction::ReLU),
+                    "Sigmoid" => Some(ActivationFunction::Sigmoid),
+                    _ => None,
+                };
+                if activation_fun.is_some() {
+                    params.activation_fun = activation_fun;
+                    nodes.push(act_idx);
+                }
+            }
+            params.validate().map_err(|err| err.to_string())?;
+        }
+        for &idx in &nodes {
+            fused[idx] = true;
+        }
+        Ok(Layer {
+            nodes: nodes
+                .into_iter()
+                .map(|idx| node_name(&self.graph.nodes[idx]).to_string())
+                .collect(),
+            kernel,
+        })
+    }
+
+    /// Maps a `MatMul` node to a kernel.  The leading dimensions of the left operand are
+    /// flattened into the rows if the right operand is a matrix.
+    fn matmul_kernel(&self, node: &proto::Node) -> Result<LayerKernel, String> {
+        let a = self.input_shape(node, 0)?;
+        let b = self.input_shape(node, 1)?;
+        let (a_rank, b_rank) = (a.len(), b.len());
+        if a_rank < 2 || b_rank < 2 || a[a_rank - 1] != b[b_rank - 2] {
+            return Err(format!("unsupported operand shapes {:?} and {:?}", a, b));
+        }
+        let k = to_size(a[a_rank - 1])?;
+        let n = to_size(b[b_rank - 1])?;
+        match (a_rank, b_rank) {
+            (_, 2) => {
+                let m = a[..a_rank - 1]
+                    .iter()
+                    .try_fold(1i64, |acc, &dim| acc.checked_mul(dim))
+                    .ok_or_else(|| "the number of rows is too large".to_string())?;
+                Ok(LayerKernel::FusedMM(linalg::FusedMMP::new(
+                    to_size(m)?,
+                    n,
+                    k,
+                )))
+            }
+            (3, 3) if a[0] == b[0] => {
+                let (batch, m) = (to_size(a[0])?, to_size(a[1])?);
+                let params = linalg::BatchMMP::new(batch, m, n, k);
+                params.validate().map_err(|err| err.to_string())?;
+                Ok(LayerKernel::BatchMM(params))
+            }
+            _ => Err(format!("unsupported operand shapes {:?} and {:?}", a, b)),
+        }
+    }
+
+    /// Returns the parameters of the kernel computing a `Gemm` node.  Only biases that
+    /// are broadcast along the rows of the product are supported.
+    fn gemm_params(&self, node: &proto::Node) -> Result<linalg::FusedMMP, String> {
+        let a = self.input_shape(node, 0)?;
+        let b = self.input_shape(node, 1)?;
+        if a.len() != 2 || b.len() != 2 {
+            return Err(format!("unsupported operand shapes {:?} and {:?}", a, b));
+        }
+        let transpose_a = int_attr(node, "transA", 0) != 0;
+        let transpose_b = int_attr(node, "transB", 0) != 0;
+        let (m, k) = if transpose_a {
+            (a[1], a[0])
+        } else {
+            (a[0], a[1])
+        };
+        let (k_b, n) = if transpose_b {
+            (b[1], b[0])
+        } else {
+            (b[0], b[1])
+        };
+        if k != k_b {
+            return Err(format!("incompatible operand shapes {:?} and {:?}", a, b));
+        }
+        if !is_one(float_attr(node, "alpha", 1.0)) {
+            return Err("only `alpha = 1` is supported".to_string());
+        }
+        let mut params = linalg::FusedMMP::new(to_size(m)?, to_size(n)?, to_size(k)?);
+        if transpose_a {
+            params = params.transpose_a();
+        }
+        if transpose_b {
+            params = params.transpose_b();
+        }
+        if node.inputs.get(2).map_or(false, |c| !c.is_empty()) {
+            let c = self.input_shape(node, 2)?;
+            if !is_one(float_attr(node, "beta", 1.0)) {
+                return Err("only `beta = 1` is supported".to_string());
+            }
+            if !is_row(c, n) {
+                return Err(format!("unsupported bias shape {:?}", c));
+            }
+            params = params.bias();
+        }
+        Ok(params)
+    }
+
+    /// Maps a `Conv` node to a kernel.
+    fn import_conv(&self, node: &proto::Node) -> Result<Layer, String> {
+        if node.inputs.get(2).map_or(false, |bias| !bias.is_empty()) {
+            return Err("biases of convolutions are not supported".to_string());
+        }
+        let x = self.input_shape(node, 0)?;
+        let w = self.input_shape(node, 1)?;
+        if x.len() != 4 || w.len() != 4 {
+            return Err(format!("unsupported operand shapes {:?} and {:?}", x, w));
+        }
+        let conv = ConvAttributes::new(node)?;
+        if conv.dilation != 1 {
+            return Err("dilated convolutions are not supported".to_string());
+        }
+        if conv.group * w[1] != x[1] {
+            return Err(format!("incompatible operand shapes {:?} and {:?}", x, w));
+        }
+        let [batch, channels, height, width] = [
+            to_size(x[0])?,
+            to_size(x[1])?,
+            to_size(x[2])?,
+            to_size(x[3])?,
+        ];
+        let [out_channels, filter_height, filter_width] =
+            [to_size(w[0])?, to_size(w[2])?, to_size(w[3])?];
+        let (stride, padding) = (to_size(conv.stride)?, to_size(conv.padding)?);
+        let kernel = if conv.group == 1 {
+            if filter_height == 1 && filter_width == 1 && stride == 1 && padding == 0 {
+                let params = linalg::PointwiseConvP::new(
+                    batch,
+                    channels,
+                    out_channels,
+                    height,
+                    width,
+                );
+                params
+                    .validate()
+                    .map(|()| LayerKernel::PointwiseConv(params))
+            } else {
+                let params =
+                    linalg::Conv2dP::new(batch, channels, out_channels, height, width)
+                        .filter(filter_height, filter_width)
+                        .stride(stride)
+                        .padding(padding);
+                params.validate().map(|()| LayerKernel::Conv2d(params))
+            }
+        } else if conv.group == x[1] && w[0] == x[1] {
+            let params = linalg::DepthwiseConvP::new(batch, channels, height, width)
+                .filter(filter_height, filter_width)
+                .stride(stride)
+                .padding(padding);
+            params
+                .validate()
+                .map(|()| LayerKernel::DepthwiseConv(params))
+        } else {
+            return Err("grouped convolutions are not supported".to_string());
+        };
+        Ok(Layer {
+            nodes: vec![node_name(node).to_string()],
+            kernel: kernel.map_err(|err| err.to_string())?,
+        })
+    }
+}
+
+/// The attributes of a `Conv` node.  Strides, paddings and dilations must be the same
+/// along both spatial dimensions.
+struct ConvAttributes {
+    stride: i64,
+    padding: i64,
+    dilation: i64,
+    group: i64,
+}
+
+impl ConvAttributes {
+    fn new(node: &proto::Node) -> Result<Self, String> {
+        let auto_pad = node.attribute("auto_pad").and_then(|attr| attr.s.as_ref());
+        let padding = match auto_pad.map(|s| s.as_str()) {
+            None | Some("NOTSET") => uniform(ints_attr(node, "pads"), "pads", 0)?,
+            Some("VALID") => 0,
+            Some(auto_pad) => return Err(format!("unsupported padding `{}`", auto_pad)),
+        };
+        let stride = uniform(ints_attr(node, "strides"), "strides", 1)?;
+        if stride <= 0 {
+            return Err("strides must be positive".to_string());
+        }
+        Ok(ConvAttributes {
+            stride,
+            padding,
+            dilation: uniform(ints_attr(node, "dilations"), "dilations", 1)?,
+            group: int_attr(node, "group", 1),
+        })
+    }
+}
+
+/// Returns the value shared by all the elements of `values`, or `default` if `values` is
+/// empty.
+fn uniform(values: &[i64], name: &str, default: i64) -> Result<i64, String> {
+    match values.split_first() {
+        None => Ok(default),
+        Some((&first, rest)) if rest.iter().all(|&value| value == first) => Ok(first),
+        Some(_) => Err(format!("`{}` must be the same on all sides", name)),
+    }
+}
+
+/// Returns the name of a node, or the name of its first output if it has no name.
+fn node_name(node: &proto::Node) -> &str {
+    if node.name.is_empty() {
+        node.outputs.first().map_or("", |output| output.as_str())
+    } else {
+        &node.name
+    }
+}
+
+fn int_attr(node: &proto::Node, name: &str, default: i64) -> i64 {
+    node.attribute(name)
+        .and_then(|attr| attr.i)
+        .unwrap_or(default)
+}
+
+fn float_attr(node: &proto::Node, name: &str, default: f32) -> f32 {
+    node.attribute(name)
+        .and_then(|attr| attr.f)
+        .unwrap_or(default)
+}
+
+fn ints_attr<'a>(node: &'a proto::Node, name: &str) -> &'a [i64] {
+    node.attribute(name).map_or(&[][..], |attr| &attr.ints[..])
+}
+
+fn is_one(value: f32) -> bool {
+    (value - 1.0).abs() <= std::f32::EPSILON
+}
+
+/// Indicates if `shape` is a vector of size `n` or a matrix with a single row of size `n`.
+fn is_row(shape: &[i64], n: i64) -> bool {
+    match shape {
+        [size] | [1, size] => *size == n,
+        _ => false,
+    }
+}
+
+/// Converts a dimension of the graph to the size of a kernel.
+fn to_size(dim: i64) -> Result<i32, String> {
+    i32::try_from(dim).map_err(|_| format!("size {} is too large", dim))
+}
+
+/// Returns the shape of the result of an operation on operands of shapes `lhs` and `rhs`,
+/// following the broadcasting rules of numpy.
+fn broadcast(lhs: &[i64], rhs: &[i64]) -> Option<Shape> {
+    let rank = std::cmp::max(lhs.len(), rhs.len());
+    let dim = |shape: &[i64], pos: usize| {
+        (pos + shape.len())
+            .checked_sub(rank)
+            .map_or(1, |pos| shape[pos])
+    };
+    (0..rank)
+        .map(|pos| match (dim(lhs, pos), dim(rhs, pos)) {
+            (lhs, rhs) if lhs == rhs || rhs == 1 => Some(lhs),
+            (1, rhs) => Some(rhs),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes the messages of a model in the protobuf wire format.
+    #[derive(Default)]
+    struct Message(Vec<u8>);
+
+    impl Message {
+        fn varint(&mut self, mut value: u64) {
+            while value >= 0x80 {
+                self.0.push(value as u8 | 0x80);
+                value >>= 7;
+            }
+            self.0.push(value as u8);
+        }
+
+        fn int(mut self, number: u64, value: i64) -> Self {
+            self.varint(number << 3);
+            self.varint(value as u64);
+            self
+        }
+
+        fn bytes(mut self, number: u64, bytes: &[u8]) -> Self {
+            self.varint(number << 3 | 2);
+            self.varint(bytes.len() as u64);
+            self.0.extend_from_slice(bytes);
+            self
+        }
+
+        fn message(self, number: u64, message: Message) -> Self {
+            self.bytes(number, &message.0)
+        }
+
+        fn packed(self, number: u64, values: &[i64]) -> Self {
+            let mut packed = Message::default();
+            for &value in values {
+                packed.varint(value as u64);
+            }
+            self.message(number, packed)
+        }
+    }
+
+    fn value_info(name: &str, dims: &[i64]) -> Message {
+        let shape = dims.iter().fold(Message::default(), |shape, &dim| {
+            shape.message(1, Message::default().int(1, dim))
+        });
+        let tensor_type = Message::default().int(1, 1).message(2, shape);
+        let type_proto = Message::default().message(1, tensor_type);
+        Message::default()
+            .bytes(1, name.as_bytes())
+            .message(2, type_proto)
+    }
+
+    fn initializer(name: &str, dims: &[i64]) -> Message {
+        dims.iter()
+            .fold(Message::default(), |tensor, &dim| tensor.int(1, dim))
+            .int(2, 1)
+            .bytes(8, name.as_bytes())
+    }
+
+    fn node(op_type: &str, inputs: &[&str], output: &str) -> Message {
+        inputs
+            .iter()
+            .fold(Message::default(), |node, input| {
+                node.bytes(1, input.as_bytes())
+            })
+            .bytes(2, output.as_bytes())
+            .bytes(4, op_type.as_bytes())
+    }
+
+    fn ints(name: &str, values: &[i64]) -> Message {
+        Message::default()
+            .bytes(1, name.as_bytes())
+            .packed(8, values)
+            .int(20, 7)
+    }
+
+    fn import_model(graph: Message) -> Import {
+        let model = Message::default().int(1, 7).message(7, graph);
+        unwrap_import(import(&model.0))
+    }
+
+    fn unwrap_import(import: Result<Import, ImportError>) -> Import {
+        match import {
+            Ok(import) => import,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Ensures a chain of `MatMul`, `Add` and `Relu` nodes is fused in a single kernel.
+    #[test]
+    fn matmul_bias_relu() {
+        let graph = Message::default()
+            .message(1, node("MatMul", &["x", "w"], "h"))
+            .message(1, node("Add", &["h", "b"], "h_bias"))
+            .message(1, node("Relu", &["h_bias"], "y"))
+            .message(5, initializer("w", &[8, 16]))
+            .message(5, initializer("b", &[16]))
+            .message(11, value_info("x", &[2, 4, 8]))
+            .message(12, value_info("y", &[2, 4, 16]));
+        let import = import_model(graph);
+        assert!(import.skipped.is_empty());
+        assert_eq!(import.layers.len(), 1);
+        assert_eq!(import.layers[0].nodes, ["h", "h_bias", "y"]);
+        match &import.layers[0].kernel {
+            LayerKernel::FusedMM(params) => {
+                assert_eq!((params.m, params.n, params.k), (8, 16, 8));
+                assert!(params.bias);
+                match params.activation_fun {
+                    Some(ActivationFunction::ReLU) => (),
+                    _ => panic!("expected a ReLU activation"),
+                }
+            }
+            _ => panic!("expected a fused matrix multiplication"),
+        }
+    }
+
+    /// Ensures convolutions are mapped to the kernels matching their groups and filters,
+    /// using inferred shapes.
+    #[test]
+    fn convolutions() {
+        let group = Message::default().bytes(1, b"group").int(3, 6).int(20, 2);
+        let depthwise = node("Conv", &["y", "w_dw"], "z").message(5, group);
+        let graph = Message::default()
+            .message(
+                1,
+                node("Conv", &["x", "w"], "y")
+                    .message(5, ints("pads", &[1, 1, 1, 1]))
+                    .message(5, ints("strides", &[2, 2])),
+            )
+            .message(1, depthwise)
+            .message(1, node("Conv", &["z", "w_pw"], "t"))
+            .message(1, node("Softmax", &["t"], "u"))
+            .message(5, initializer("w", &[6, 3, 3, 3]))
+            .message(5, initializer("w_dw", &[6, 1, 3, 3]))
+            .message(5, initializer("w_pw", &[4, 6, 1, 1]))
+            .message(11, value_info("x", &[1, 3, 8, 8]));
+        let import = import_model(graph);
+        assert_eq!(import.layers.len(), 3);
+        match &import.layers[0].kernel {
+            LayerKernel::Conv2d(params) => {
+                assert_eq!((params.in_channels, params.out_channels), (3, 6));
+                assert_eq!((params.stride, params.padding), (2, 1));
+                assert_eq!((params.out_height(), params.out_width()), (4, 4));
+            }
+            _ => panic!("expected a 2D convolution"),
+        }
+        match &import.layers[1].kernel {
+            LayerKernel::DepthwiseConv(params) => {
+                assert_eq!((params.channels, params.height, params.width), (6, 4, 4));
+            }
+            _ => panic!("expected a depthwise convolution"),
+        }
+        match &import.layers[2].kernel {
+            LayerKernel::PointwiseConv(params) => {
+                assert_eq!((params.in_channels, params.out_channels), (6, 4));
+            }
+            _ => panic!("expected a pointwise convolution"),
+        }
+        assert_eq!(import.skipped.len(), 1);
+        assert_eq!(import.skipped[0].op_type, "Softmax");
+    }
+}
@@ -0,0 +1,348 @@
+//! Decoder for the subset of the ONNX protobuf messages needed to import kernels.
+//!
+//! The decoder reads the protobuf wire format directly, so that importing a model does not
+//! require a protobuf compiler.  Only the fields used by the importer are decoded; the
+//! others, including the content of the weights, are skipped.
+use std::{error, fmt, str};
+
+/// Error raised when a buffer is not a valid encoding of the expected message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError(String);
+
+impl DecodeError {
+    fn new<S: Into<String>>(msg: S) -> Self {
+        DecodeError(msg.into())
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid ONNX model: {}", self.0)
+    }
+}
+
+impl error::Error for DecodeError {}
+
+/// The value of a field, as encoded on the wire.
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    Varint(u64),
+    /// A 64-bit value.  None of the decoded fields are 64-bit, so the value is dropped.
+    Fixed64,
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    fn int(self) -> Result<i64, DecodeError> {
+        match self {
+            Value::Varint(value) => Ok(value as i64),
+            _ => Err(DecodeError::new("expected an integer")),
+        }
+    }
+
+    fn float(self) -> Result<f32, DecodeError> {
+        match self {
+            Value::Fixed32(bits) => Ok(f32::from_bits(bits)),
+            _ => Err(DecodeError::new("expected a float")),
+        }
+    }
+
+    fn bytes(self) -> Result<&'a [u8], DecodeError> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(DecodeError::new("expected a length-delimited field")),
+        }
+    }
+
+    fn string(self) -> Result<String, DecodeError> {
+        str::from_utf8(self.bytes()?)
+            .map(|s| s.to_string())
+            .map_err(|_| DecodeError::new("invalid UTF-8 string"))
+    }
+
+    /// Appends the integers of a repeated field to `out`.  Repeated integers are either
+    /// packed in a single length-delimited field or encoded as one field per value.
+    fn ints(self, out: &mut Vec<i64>) -> Result<(), DecodeError> {
+        match self {
+            Value::Bytes(bytes) => {
+                let mut reader = Reader { buf: bytes };
+                while !reader.buf.is_empty() {
+                    out.push(reader.varint()? as i64);
+                }
+                Ok(())
+            }
+            value => {
+                out.push(value.int()?);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads fields from a buffer.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .buf
+                .split_first()
+                .ok_or_else(|| DecodeError::new("truncated varint"))?;
+            self.buf = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::new("varint is too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.buf.len() {
+            return Err(DecodeError::new("truncated field"));
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn field(&mut self) -> Result<(u64, Value<'a>), DecodeError> {
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed64
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(self.take(4)?);
+                Value::Fixed32(u32::from_le_bytes(bytes))
+            }
+            wire_type => {
+                return Err(DecodeError::new(format!(
+                    "unsupported wire type {}",
+                    wire_type
+                )));
+            }
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+/// Calls `f` on each field of the message encoded in `buf`, with the number of the field.
+fn for_each_field<'a, F>(buf: &'a [u8], mut f: F) -> Result<(), DecodeError>
+where
+    F: FnMut(u64, Value<'a>) -> Result<(), DecodeError>,
+{
+    let mut reader = Reader { buf };
+    while !reader.buf.is_empty() {
+        let (number, value) = reader.field()?;
+        f(number, value)?;
+    }
+    Ok(())
+}
+
+/// A `ModelProto`.
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    pub graph: Graph,
+}
+
+impl Model {
+    /// Decodes a serialized ONNX model.
+    pub fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut graph = None;
+        for_each_field(buf, |number, value| {
+            if number == 7 {
+                graph = Some(Graph::decode(value.bytes()?)?);
+            }
+            Ok(())
+        })?;
+        let graph = graph.ok_or_else(|| DecodeError::new("the model has no graph"))?;
+        Ok(Model { graph })
+    }
+}
+
+/// A `GraphProto`.  Nodes are sorted in topological order.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub initializers: Vec<TensorInfo>,
+    pub inputs: Vec<ValueInfo>,
+    pub outputs: Vec<ValueInfo>,
+    /// Types of the intermediate values, when the model provides them.
+    pub value_infos: Vec<ValueInfo>,
+}
+
+impl Graph {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut graph = Graph::default();
+        for_each_field(buf, |number, value| {
+            match number {
+                1 => graph.nodes.push(Node::decode(value.bytes()?)?),
+                5 => graph.initializers.push(TensorInfo::decode(value.bytes()?)?),
+                11 => graph.inputs.push(ValueInfo::decode(value.bytes()?)?),
+                12 => graph.outputs.push(ValueInfo::decode(value.bytes()?)?),
+                13 => graph.value_infos.push(ValueInfo::decode(value.bytes()?)?),
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(graph)
+    }
+}
+
+/// A `NodeProto`: an operator applied to named values.
+#[derive(Debug, Clone, Default)]
+pub struct Node {
+    pub name: String,
+    pub op_type: String,
+    /// Names of the inputs.  Missing optional inputs are empty strings.
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Node {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut node = Node::default();
+        for_each_field(buf, |number, value| {
+            match number {
+                1 => node.inputs.push(value.string()?),
+                2 => node.outputs.push(value.string()?),
+                3 => node.name = value.string()?,
+                4 => node.op_type = value.string()?,
+                5 => node.attributes.push(Attribute::decode(value.bytes()?)?),
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(node)
+    }
+
+    /// Returns the attribute with the given name, if any.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|attr| attr.name == name)
+    }
+}
+
+/// An `AttributeProto`.  Only scalar, string and integer list attributes are decoded.
+#[derive(Debug, Clone, Default)]
+pub struct Attribute {
+    pub name: String,
+    pub f: Option<f32>,
+    pub i: Option<i64>,
+    pub s: Option<String>,
+    pub ints: Vec<i64>,
+}
+
+impl Attribute {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut attr = Attribute::default();
+        for_each_field(buf, |number, value| {
+            match number {
+                1 => attr.name = value.string()?,
+                2 => attr.f = Some(value.float()?),
+                3 => attr.i = Some(value.int()?),
+                4 => attr.s = Some(value.string()?),
+                8 => value.ints(&mut attr.ints)?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(attr)
+    }
+}
+
+/// The name and the shape of a `TensorProto`, without its content.
+#[derive(Debug, Clone, Default)]
+pub struct TensorInfo {
+    pub name: String,
+    pub dims: Vec<i64>,
+}
+
+impl TensorInfo {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut tensor = TensorInfo::default();
+        for_each_field(buf, |number, value| {
+            match number {
+                1 => value.ints(&mut tensor.dims)?,
+                8 => tensor.name = value.string()?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(tensor)
+    }
+}
+
+/// A `ValueInfoProto`: the name and the shape of a value.  `shape` is `None` if the value
+/// is not a tensor or if its rank is unknown.  Dimensions that are not known statically,
+/// such as symbolic batch sizes, are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct ValueInfo {
+    pub name: String,
+    pub shape: Option<Vec<Option<i64>>>,
+}
+
+impl ValueInfo {
+    fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut info = ValueInfo::default();
+        for_each_field(buf, |number, value| {
+            match number {
+                1 => info.name = value.string()?,
+                2 => info.shape = decode_type_shape(value.bytes()?)?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        Ok(info)
+    }
+}
+
+/// Decodes the shape of a `TypeProto`, if it describes a tensor with a known rank.
+fn decode_type_shape(buf: &[u8]) -> Result<Option<Vec<Option<i64>>>, DecodeError> {
+    let mut shape = None;
+    for_each_field(buf, |number, value| {
+        if number == 1 {
+            // `TypeProto.Tensor`
+            for_each_field(value.bytes()?, |number, value| {
+                if number == 2 {
+                    shape = Some(decode_shape(value.bytes()?)?);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    })?;
+    Ok(shape)
+}
+
+/// Decodes a `TensorShapeProto`.
+fn decode_shape(buf: &[u8]) -> Result<Vec<Option<i64>>, DecodeError> {
+    let mut dims = vec![];
+    for_each_field(buf, |number, value| {
+        if number == 1 {
+            let mut dim = None;
+            for_each_field(value.bytes()?, |number, value| {
+                if number == 1 {
+                    dim = Some(value.int()?);
+                }
+                Ok(())
+            })?;
+            dims.push(dim);
+        }
+        Ok(())
+    })?;
+    Ok(dims)
+}
@@ -50,8 +50,49 @@ where
         v
     }
 
+    /// Same as `get`, but `gen` may fail, in which case the error is returned and nothing
+    /// is stored. The lock is not held while `gen` runs, so that several threads can
+    /// generate elements in parallel. A hit marks the element as the most recently used.
+    pub fn try_get<F, E>(&self, key: &K, gen: F) -> Result<Arc<V>, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        if let Some(v) = self.map.write().unwrap().get_refresh(key) {
+            return Ok(v.clone());
+        }
+        let v = Arc::new(gen()?);
+        let mut map = self.map.write().unwrap();
+        // Another thread may have generated the element in the meantime.
+        let v = map.entry(key.clone()).or_insert(v).clone();
+        if map.len() > self.capacity {
+            map.pop_front();
+        }
+        Ok(v)
+    }
+
     /// Removes all elements from the `Cache`.
     pub fn clear(&mut self) {
         self.map.get_mut().unwrap().clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures `try_get` evicts the least recently used element.
+    #[test]
+    fn try_get_lru() {
+        let cache = Cache::new(2);
+        let gen = |v| move || Ok::<_, ()>(v);
+        let fail = || -> Result<u32, ()> { panic!("the element is not cached") };
+        cache.try_get(&0, gen(0)).unwrap();
+        cache.try_get(&1, gen(1)).unwrap();
+        assert_eq!(*cache.try_get(&0, fail).unwrap(), 0);
+        cache.try_get(&2, gen(2)).unwrap();
+        assert_eq!(*cache.try_get(&0, fail).unwrap(), 0);
+        assert_eq!(*cache.try_get(&1, gen(10)).unwrap(), 10);
+        assert_eq!(cache.try_get(&3, || Err(())), Err(()));
+        assert_eq!(*cache.try_get(&3, gen(3)).unwrap(), 3);
+    }
+}
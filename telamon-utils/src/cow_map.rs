@@ -0,0 +1,183 @@
+//! Hash map with cheap clones, that copies its entries lazily.
+use fxhash::{FxHashMap, FxHasher};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::sync::Arc;
+use std::{fmt, ops};
+
+/// Number of shards the entries of a `CowMap` are split into.
+const NUM_SHARDS: usize = 64;
+
+/// A hash map whose clones share their entries until they are modified. Entries are split
+/// into shards that are copied independently: modifying an entry only copies the entries
+/// of the same shard, instead of the whole map.
+pub struct CowMap<K, V> {
+    /// The shards of the map. Empty until the first entry is inserted.
+    shards: Arc<Vec<Arc<FxHashMap<K, V>>>>,
+}
+
+impl<K, V> CowMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        CowMap {
+            shards: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    /// Indicates if the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    /// Returns the value associated to `key`, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.get(shard_index(key))?.get(key)
+    }
+
+    /// Iterates over the entries of the map, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+}
+
+impl<K, V> CowMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Returns a mutable reference to the value associated to `key`, if any. Copies the
+    /// shard holding the entry if it is shared with another map.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.shards.is_empty() {
+            return None;
+        }
+        let shard = &mut Arc::make_mut(&mut self.shards)[shard_index(key)];
+        Arc::make_mut(shard).get_mut(key)
+    }
+
+    /// Associates `value` to `key` and returns the previous value, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let shards = Arc::make_mut(&mut self.shards);
+        if shards.is_empty() {
+            shards.resize_with(NUM_SHARDS, Default::default);
+        }
+        let shard = &mut shards[shard_index(&key)];
+        Arc::make_mut(shard).insert(key, value)
+    }
+}
+
+/// Returns the shard holding `key`. The shard is selected with bits of the hash that the
+/// shards do not use to index their own entries.
+fn shard_index<Q: Hash + ?Sized>(key: &Q) -> usize {
+    let mut hasher = FxHasher::default();
+    key.hash(&mut hasher);
+    (hasher.finish() >> 32) as usize % NUM_SHARDS
+}
+
+impl<K, V> Clone for CowMap<K, V> {
+    fn clone(&self) -> Self {
+        CowMap {
+            shards: Arc::clone(&self.shards),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Default for CowMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, K, Q, V> ops::Index<&'a Q> for CowMap<K, V>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for CowMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = CowMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K, V> fmt::Debug for CowMap<K, V>
+where
+    K: Hash + Eq + fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V> Serialize for CowMap<K, V>
+where
+    K: Hash + Eq + Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for CowMap<K, V>
+where
+    K: Hash + Eq + Clone + Deserialize<'de>,
+    V: Clone + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = FxHashMap::<K, V>::deserialize(deserializer)?;
+        Ok(map.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures modifying a clone does not affect the original map.
+    #[test]
+    fn clone_on_write() {
+        let mut map = (0..1000).map(|i| (i, i)).collect::<CowMap<u32, u32>>();
+        let clone = map.clone();
+        *map.get_mut(&10).unwrap() = 0;
+        map.insert(1000, 1000);
+        assert_eq!(map[&10], 0);
+        assert_eq!(clone[&10], 10);
+        assert_eq!(map.len(), 1001);
+        assert_eq!(clone.len(), 1000);
+        assert_eq!(clone.get(&1000), None);
+    }
+}
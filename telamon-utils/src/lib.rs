@@ -2,6 +2,7 @@
 #![warn(clippy::all)]
 
 mod cache;
+mod cow_map;
 mod dag;
 mod iterator;
 pub mod multimap;
@@ -11,6 +12,7 @@ pub mod unwrap;
 mod vec_set;
 
 pub use self::cache::Cache;
+pub use self::cow_map::CowMap;
 pub use self::dag::Dag;
 pub use self::iterator::*;
 pub use self::ndarray::{NDArray, NDRange};
@@ -1,9 +1,12 @@
 //! Contains integration tests for Telamon.
 
-use telamon::device::{fake, Context};
+use telamon::codegen;
+use telamon::device::{fake, AsyncCallback, CancellationToken, Context, KernelEvaluator};
 use telamon::explorer;
 use telamon::helper;
 use telamon::ir::{self, Size, Type};
+use telamon::model::bound;
+use telamon::offline_analysis::space_stats::SpaceStats;
 use telamon::search_space::*;
 
 /// Find the best candidate for a function and outputs it.
@@ -44,6 +47,222 @@ fn two_add() {
     });
 }
 
+/// Ensures the MCTS search explores several root candidates.
+#[test]
+fn several_roots() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let roots = (0..2)
+        .map(|root| {
+            let signature = ir::Signature::new("several_roots");
+            let mut builder = helper::Builder::new(signature.into(), context.device());
+            builder.mov(&root);
+            builder.get()
+        })
+        .collect();
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    config.algorithm = explorer::SearchAlgorithm::Mcts(Default::default());
+    assert!(explorer::find_best(&config, &context, roots, None).is_some());
+}
+
+/// Ensures the search saves a checkpoint when the checkpoint interval is zero, and only
+/// resumes from valid checkpoints.
+#[test]
+fn checkpoint_and_resume() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("checkpoint");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    builder.mov(&0i32);
+    let space = builder.get();
+    let output_dir =
+        std::env::temp_dir().join(format!("telamon-checkpoint-{}", std::process::id()));
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    config.algorithm = explorer::SearchAlgorithm::Mcts(Default::default());
+    config.output_dir = output_dir.to_str().unwrap().to_string();
+    config.checkpoint_file = Some("checkpoint".to_string());
+    config.checkpoint_interval = 0;
+    assert!(explorer::find_best(&config, &context, vec![space.clone()], None).is_some());
+
+    let checkpoint_path = output_dir.join("checkpoint");
+    let checkpoint = explorer::Checkpoint::load(&checkpoint_path).unwrap();
+    assert!(!checkpoint.evaluations.is_empty());
+    config.checkpoint_file = None;
+    config.resume = Some(checkpoint_path.to_str().unwrap().to_string());
+    assert!(explorer::find_best(&config, &context, vec![space.clone()], None).is_some());
+    config.resume = Some(output_dir.join("missing").to_str().unwrap().to_string());
+    assert!(explorer::find_best(&config, &context, vec![space], None).is_none());
+    std::fs::remove_dir_all(output_dir).unwrap();
+}
+
+/// Ensures the search fails without panicking when the cost model cannot be loaded.
+#[test]
+fn missing_cost_model() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("missing_cost_model");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    builder.mov(&0i32);
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    config.algorithm = explorer::SearchAlgorithm::Mcts(explorer::BanditConfig {
+        cost_model: Some(explorer::config::CostModelConfig {
+            path: "missing_cost_model.json".to_string(),
+            weight: 1.,
+        }),
+        ..Default::default()
+    });
+    assert!(explorer::find_best(&config, &context, vec![builder.get()], None).is_none());
+}
+
+/// Ensures a search stops without evaluating candidates once its token is cancelled.
+#[test]
+fn cancelled_search() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("cancelled_search");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    builder.mov(&0i32);
+    let space = builder.get();
+    let candidate = explorer::Candidate::new(space.clone(), bound(&space, &context));
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let best = explorer::find_best_cancellable(
+        &config,
+        &context,
+        vec![candidate],
+        None,
+        &cancel,
+    );
+    assert!(best.is_none());
+}
+
+/// Ensures two searches with the same seed and a single worker make the same choices.
+#[test]
+fn same_seed() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = std::sync::Arc::new(ir::Signature::new("same_seed"));
+    let mut builder = helper::Builder::new(signature, context.device());
+    let dim0 = builder.open_dim(Size::new_const(64));
+    builder.mov(&0i32);
+    let dim1 = builder.open_dim(Size::new_const(16));
+    builder.mov(&1i32);
+    builder.close_dim(&dim1);
+    builder.close_dim(&dim0);
+    let space = builder.get();
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    config.max_evaluations = Some(4);
+    config.seed = Some(42);
+    config.algorithm = explorer::SearchAlgorithm::Mcts(Default::default());
+    let search = || {
+        let candidate = explorer::Candidate::new(space.clone(), bound(&space, &context));
+        let best = explorer::find_best_ex(&config, &context, vec![candidate], None, &[]);
+        best.unwrap().actions.iter().cloned().collect::<Vec<_>>()
+    };
+    assert_eq!(search(), search());
+}
+
+/// Ensures the first descent of a warm-started search follows the warm start actions.
+#[test]
+fn warm_start() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = std::sync::Arc::new(ir::Signature::new("warm_start"));
+    let mut builder = helper::Builder::new(signature, context.device());
+    let dim0 = builder.open_dim(Size::new_const(64));
+    builder.mov(&0i32);
+    let dim1 = builder.open_dim(Size::new_const(16));
+    builder.mov(&1i32);
+    builder.close_dim(&dim1);
+    builder.close_dim(&dim0);
+    let space = builder.get();
+    let mut config = explorer::Config::from_settings_toml();
+    config.num_workers = 1;
+    config.max_evaluations = Some(4);
+    config.algorithm = explorer::SearchAlgorithm::Mcts(Default::default());
+    let search = |config: &explorer::Config, warm_start: &[_]| {
+        let candidate = explorer::Candidate::new(space.clone(), bound(&space, &context));
+        let best =
+            explorer::find_best_ex(config, &context, vec![candidate], None, warm_start);
+        let mut actions = best.unwrap().actions.iter().cloned().collect::<Vec<_>>();
+        actions.reverse();
+        actions
+    };
+    let previous = search(&config, &[]);
+    // The search only evaluates the candidate reached by the warm start.
+    config.max_evaluations = Some(1);
+    let actions = search(&config, &[previous.clone()]);
+    assert!(previous.iter().all(|action| actions.contains(action)));
+}
+
+/// Ensures the statistics on the search space account for every sampled descent.
+#[test]
+fn space_stats() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = std::sync::Arc::new(ir::Signature::new("space_stats"));
+    let mut builder = helper::Builder::new(signature, context.device());
+    let dim0 = builder.open_dim(Size::new_const(64));
+    builder.mov(&0i32);
+    let dim1 = builder.open_dim(Size::new_const(16));
+    builder.mov(&1i32);
+    builder.close_dim(&dim1);
+    builder.close_dim(&dim0);
+    let space = builder.get();
+    let root = explorer::Candidate::new(space.clone(), bound(&space, &context));
+    let stats = SpaceStats::sample(&root, &context, 10);
+    assert_eq!(stats.descents.len() + stats.num_deadends(), 10);
+    assert_eq!(stats.log_size().is_some(), !stats.descents.is_empty());
+    for descent in &stats.descents {
+        assert!(descent.depth > 0);
+        assert!(descent.log_size >= 0.);
+    }
+    assert!(stats.choices.contains_key("DimKind"));
+    for kind_stats in stats.choices.values() {
+        assert!(kind_stats.num_choices > 0);
+        assert!(kind_stats.num_pruned <= kind_stats.num_alternatives);
+    }
+}
+
+/// Ensures the counters of the search space match the generated code on fully specified
+/// candidates.  Each descent picks a different action among the valid ones, so that the
+/// candidates have different dimension kinds.
+#[test]
+fn counters_match_codegen() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = std::sync::Arc::new(ir::Signature::new("counters"));
+    let mut builder = helper::Builder::new(signature, context.device());
+    let dim0 = builder.open_dim(Size::new_const(64));
+    builder.mov(&0i32);
+    let dim1 = builder.open_dim(Size::new_const(16));
+    builder.mov(&1i32);
+    builder.close_dim(&dim1);
+    builder.close_dim(&dim0);
+    let root = builder.get();
+    for offset in 0..4 {
+        let mut space = root.clone();
+        while let Some(choice) = explorer::choice::default_list(&space).next() {
+            let mut children = choice
+                .into_iter()
+                .filter_map(|action| action.apply_to(space.clone()).ok())
+                .collect::<Vec<_>>();
+            assert!(!children.is_empty(), "dead-end in descent {}", offset);
+            space = children.swap_remove(offset % children.len());
+        }
+        let space = explorer::choice::fix_order(space);
+        let function = codegen::Function::build(&space);
+        let mismatches = function.check_counters();
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+    }
+}
+
 /// Ensures the default order between instructions and dimensions is good.
 #[test]
 fn inst_dim_order() {
@@ -241,6 +460,105 @@ fn max_thread_on_setkind() {
     gen_best(&context, space);
 }
 
+/// Ensures scoped dimensions are closed when their guard is dropped.
+#[test]
+fn scoped_dims() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("empty");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let (d0, inst0) = {
+        let mut scope = builder.scoped_dim(Size::new_const(16), Default::default());
+        let inst0 = scope.mov(&0i32);
+        (scope.dim().clone(), inst0)
+    };
+    builder.name_dim("d0", &d0);
+    let inst1 = builder.mov(&0i32);
+    assert!(builder.named_dim("d1").is_none());
+    let d0 = builder.named_dim("d0").unwrap().single();
+    let space = builder.get();
+    assert_eq!(
+        space.domain().get_order(d0.into(), inst0.into()),
+        Order::OUTER
+    );
+    assert!(!space
+        .domain()
+        .get_order(d0.into(), inst1.into())
+        .intersects(Order::OUTER));
+    gen_best(&context, space);
+}
+
+/// Ensures the typed handles of the dimensions of a tiled logical dimension refer to the
+/// right dimensions.
+#[test]
+fn tiled_dim_handles() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("empty");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let pattern = helper::TilingPattern::new_fixed(&[4]);
+    let dim = builder.open_tiled_dim(Size::new_const(64), pattern);
+    builder.mov(&0i32);
+    builder.close_dim(&dim);
+    let tiled = dim.tiled_dim();
+    let tiles = dim.tile_dims().collect::<Vec<_>>();
+    assert_eq!(tiled.id(), dim[0]);
+    assert_eq!(tiles.len(), 1);
+    assert_eq!(tiles[0].id(), dim[1]);
+    builder.order(&tiled, &tiles[0], Order::OUTER);
+    let space = builder.get();
+    assert_eq!(
+        space.domain().get_order(tiled.into(), tiles[0].into()),
+        Order::OUTER
+    );
+    gen_best(&context, space);
+}
+
+/// Ensures names cannot be registered twice.
+#[test]
+#[should_panic(expected = "dimension name `d0` is already used")]
+fn duplicate_dim_name() {
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("empty");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let d0 = builder.open_dim(Size::new_const(16));
+    let d1 = builder.open_dim(Size::new_const(16));
+    builder.name_dim("d0", &d0);
+    builder.name_dim("d0", &d1);
+}
+
+/// Ensures candidates drained after a cancellation are reported to their callback instead
+/// of being evaluated.
+#[test]
+fn drain_cancelled() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("empty");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    builder.mov(&0i32);
+    let space = builder.get();
+    let candidate = explorer::Candidate::new(space.clone(), bound(&space, &context));
+
+    let cancel = CancellationToken::new();
+    let reported = std::sync::Mutex::new(vec![]);
+    let queue = (0..3).map(|i| {
+        if i == 1 {
+            cancel.cancel();
+        }
+        let reported = &reported;
+        let callback: AsyncCallback = Box::new(
+            move |_: explorer::Candidate, kernel: &mut dyn KernelEvaluator| {
+                reported.lock().unwrap().push((i, kernel.evaluate()))
+            },
+        );
+        (candidate.clone(), i, callback)
+    });
+    let mut evaluated = vec![];
+    cancel.drain(queue, |_, i, _| evaluated.push(i));
+    assert_eq!(evaluated, vec![0]);
+    assert_eq!(*reported.lock().unwrap(), vec![(1, None), (2, None)]);
+}
+
 /// Ensures block dimensions are nested under every other dimension.
 #[test]
 fn block_dims() {
@@ -318,6 +636,30 @@ fn vector_dims() {
     gen_best(&context, space);
 }
 
+/// Ensures dimensions along which addresses decrease are not vectorized.
+#[test]
+fn reversed_dims() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("empty");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let base_addr = builder.cast(&0i64, context.device().pointer_type(MemSpace::GLOBAL));
+    let d0 = builder.open_dim(Size::new_const(4));
+    let increments = vec![(&d0, Size::new_const(1))];
+    let addr = builder.induction_var_ex(&base_addr, increments.clone(), &[&d0]);
+    let pattern = builder.tensor_access_pattern_ex(None, increments, &[&d0]);
+    assert!(pattern.is_reversed(d0[0]));
+    assert!(!pattern.is_consecutive(d0[0], Type::I(8)));
+    builder.ld(Type::I(8), &addr, pattern);
+    builder.close_dim(&d0);
+    let space = builder.get();
+    assert!(!space
+        .domain()
+        .get_dim_kind(d0[0])
+        .intersects(DimKind::VECTOR));
+    gen_best(&context, space);
+}
+
 /// Ensure restrictions are applied to unrolled dimensions.
 #[test]
 fn unroll_dims() {
@@ -579,3 +921,55 @@ fn two_level_vectorization() {
     // Try to generate a fully specified candidate.
     gen_best(&context, space);
 }
+
+/// Ensures the arguments recorded from a context can be bound to a new context.
+#[test]
+fn record_arguments() {
+    use telamon::device::recorder::Recorder;
+
+    let _ = env_logger::try_init();
+    let mut recorder = Recorder::new(fake::Context::<fake::Device>::default());
+    recorder.record_seed(42);
+    {
+        let mut builder = helper::SignatureBuilder::new("record", &mut recorder);
+        builder.scalar("n", 1024i32);
+        builder.scalar("alpha", 0.1f32);
+    }
+    let manifest = recorder.manifest(false);
+    assert_eq!(manifest.seed, Some(42));
+    assert_eq!(manifest.scalars.len(), 2);
+
+    let mut context = fake::Context::<fake::Device>::default();
+    manifest.bind(&mut context).unwrap();
+    assert_eq!(context.param_as_size("n"), Some(1024));
+}
+
+/// Ensures the parallel filtering of the initial domain computes the same domain as the
+/// sequential one.
+#[cfg(feature = "parallel_propagation")]
+#[test]
+fn parallel_filters() {
+    let _ = env_logger::try_init();
+    let context = fake::Context::<fake::Device>::default();
+    let signature = ir::Signature::new("parallel_filters");
+    let mut builder = helper::Builder::new(signature.into(), context.device());
+    let base_addr = builder.cast(&0i64, context.device().pointer_type(MemSpace::GLOBAL));
+    let d0 = builder.open_dim(Size::new_const(16));
+    let d1 = builder.open_dim(Size::new_const(4));
+    let (addr, pattern) =
+        builder.tensor_access(&base_addr, None, Type::F(32), &[&d0, &d1]);
+    let ld = builder.ld(Type::F(32), &addr, pattern);
+    builder.mad(&ld, &ld, &2f32);
+    builder.close_dim(&d1);
+    builder.close_dim(&d0);
+    let d2 = builder.open_dim(Size::new_const(32));
+    builder.mul(&0i32, &0i32);
+    builder.close_dim(&d2);
+
+    let parallel = builder.get_clone();
+    let sequential = builder.get_sequential();
+    assert_eq!(
+        format!("{:?}", parallel.domain()),
+        format!("{:?}", sequential.domain())
+    );
+}